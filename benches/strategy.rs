@@ -0,0 +1,312 @@
+//! CPU cost of a tick, gathering the market data of a mint and running both strategies on it,
+//! and wall time of the strategies' size search as Jupiter gets slower to quote. Every venue is
+//! mocked, so the numbers are the bot's own overhead, to compare before and after concurrency
+//! or caching work:
+//!
+//! ```sh
+//! cargo bench --bench strategy -- --save-baseline before
+//! cargo bench --bench strategy -- --baseline before
+//! ```
+
+use arb::{
+    constants::{STABLEBOND_DECIMALS, USDC_DECIMALS},
+    decision_log::Decision,
+    market_data::{MarketData, MarketDataBuilder},
+    math::TokenAmountExt,
+    mock::{
+        MockBondVenue, MockBundleSubmitter, MockOraclePriceSource, MockSolPriceProvider,
+        MockSwapVenue, QuoteSide, TempDir,
+    },
+    providers::FeedValue,
+    strategy::{
+        BuyOnEtherfuseSellOnJupiter, BuyOnJupiterSellOnEtherfuse, Strategy, StrategyResult,
+    },
+    TradingEngine,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::Result as ClientResult,
+    rpc_request::{RpcError, RpcRequest},
+};
+use solana_rpc_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use stablebond_sdk::{
+    accounts::PaymentFeed,
+    types::{Discriminator, PaymentFeedType},
+};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use tokio::runtime::Runtime;
+
+const NAV: f64 = 1.0;
+const TOKEN_BALANCE: u64 = 10_000_000_000;
+const QUOTE_LATENCIES_MS: [u64; 4] = [0, 1, 5, 20];
+
+/// Jupiter ask rising by 1 bp per USDC bought from 95 cents, and bid falling by 1 bp per token
+/// sold from 1.05, so that both strategies search every size and find a trade.
+fn price_curve(side: QuoteSide, amount: u64) -> f64 {
+    match side {
+        QuoteSide::Buy => 0.95 + 0.0001 * amount.to_ui_amount(USDC_DECIMALS),
+        QuoteSide::Sell => 1.05 - 0.0001 * amount.to_ui_amount(STABLEBOND_DECIMALS),
+    }
+}
+
+/// RPC answering the balance reads of the market data and the strategies from canned
+/// responses, every time unlike `RpcClient::new_mock_with_mocks`.
+struct CannedRpc;
+
+#[async_trait::async_trait]
+impl RpcSender for CannedRpc {
+    async fn send(&self, request: RpcRequest, _params: Value) -> ClientResult<Value> {
+        let token_amount = json!({
+            "amount": TOKEN_BALANCE.to_string(),
+            "decimals": USDC_DECIMALS,
+            "uiAmount": TOKEN_BALANCE.to_ui_amount(USDC_DECIMALS),
+            "uiAmountString": TOKEN_BALANCE.to_ui_amount(USDC_DECIMALS).to_string(),
+        });
+        let value = match request {
+            RpcRequest::GetVersion => return Ok(json!({ "solana-core": "1.18.22" })),
+            RpcRequest::GetTokenAccountBalance => token_amount,
+            RpcRequest::GetAccountInfo => json!({
+                "lamports": 2_039_280,
+                "owner": spl_token::id().to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 165,
+                "data": {
+                    "program": "spl-token",
+                    "space": 165,
+                    "parsed": {
+                        "type": "account",
+                        "info": {
+                            "mint": Pubkey::default().to_string(),
+                            "owner": Pubkey::default().to_string(),
+                            "tokenAmount": token_amount,
+                            "state": "initialized",
+                            "isNative": false,
+                        },
+                    },
+                },
+            }),
+            _ => {
+                return Err(RpcError::ForUser(format!("No canned response to {}", request)).into())
+            }
+        };
+        Ok(json!({ "context": { "slot": 1 }, "value": value }))
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "canned".to_string()
+    }
+}
+
+/// The CLI's `StrategyEnum` over the mocks.
+#[derive(Clone)]
+enum MockStrategy {
+    BuyOnJupiter(BuyOnJupiterSellOnEtherfuse<MockSwapVenue, MockBondVenue>),
+    BuyOnEtherfuse(BuyOnEtherfuseSellOnJupiter<MockSwapVenue, MockBondVenue>),
+}
+
+impl Strategy for MockStrategy {
+    fn name(&self) -> &'static str {
+        match self {
+            MockStrategy::BuyOnJupiter(strategy) => strategy.name(),
+            MockStrategy::BuyOnEtherfuse(strategy) => strategy.name(),
+        }
+    }
+
+    async fn process_market_data(
+        &mut self,
+        md: &MarketData,
+        stablebond_mint: &Pubkey,
+    ) -> anyhow::Result<StrategyResult> {
+        match self {
+            MockStrategy::BuyOnJupiter(strategy) => {
+                strategy.process_market_data(md, stablebond_mint).await
+            }
+            MockStrategy::BuyOnEtherfuse(strategy) => {
+                strategy.process_market_data(md, stablebond_mint).await
+            }
+        }
+    }
+}
+
+struct Fixture {
+    rpc_client: Arc<RpcClient>,
+    wallet: Pubkey,
+    keypair_filepath: String,
+    /// Holds the keypair file for as long as the fixture is in use.
+    _temp_dir: TempDir,
+    stablebond_mint: Pubkey,
+    venue: MockBondVenue,
+    oracle: MockOraclePriceSource,
+    jito: MockBundleSubmitter,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let keypair = Keypair::new();
+        let temp_dir = TempDir::create("bench");
+        let keypair_filepath = temp_dir.keypair_file(&keypair);
+        let base_price_feed = Pubkey::new_unique();
+        let payment_feed = PaymentFeed {
+            discriminator: Discriminator::PaymentFeed,
+            version: 0,
+            payment_mint: Pubkey::new_unique(),
+            payment_decimals: USDC_DECIMALS,
+            base_price_feed,
+            quote_price_feed: Pubkey::default(),
+            payment_feed_type: PaymentFeedType::SwitchboardOnDemandUsdcUsd,
+        };
+        let fresh_feed = FeedValue {
+            price: 1.0,
+            age_slots: 0,
+            max_staleness_slots: 1_000,
+        };
+        Self {
+            rpc_client: Arc::new(RpcClient::new_sender(
+                CannedRpc,
+                RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+            )),
+            wallet: keypair.pubkey(),
+            keypair_filepath,
+            _temp_dir: temp_dir,
+            stablebond_mint: Pubkey::new_unique(),
+            venue: MockBondVenue::new(NAV)
+                .with_sell_liquidity(100_000_000_000)
+                .with_purchase_liquidity(100_000_000_000)
+                .with_payment_feed(1.0, payment_feed),
+            oracle: MockOraclePriceSource::new().with_feed(base_price_feed, fresh_feed),
+            jito: MockBundleSubmitter::new(1_000_000),
+        }
+    }
+
+    fn quotes(&self, latency: Duration) -> MockSwapVenue {
+        MockSwapVenue::new(0.0)
+            .with_price_curve(price_curve)
+            .with_latency(latency)
+    }
+
+    fn buy_on_jupiter(&self, latency: Duration) -> MockStrategy {
+        MockStrategy::BuyOnJupiter(BuyOnJupiterSellOnEtherfuse::new(
+            self.rpc_client.clone(),
+            self.quotes(latency),
+            self.keypair_filepath.clone(),
+            self.venue.clone(),
+        ))
+    }
+
+    fn buy_on_etherfuse(&self, latency: Duration) -> MockStrategy {
+        MockStrategy::BuyOnEtherfuse(BuyOnEtherfuseSellOnJupiter::new(
+            self.rpc_client.clone(),
+            self.quotes(latency),
+            self.keypair_filepath.clone(),
+            self.venue.clone(),
+        ))
+    }
+
+    fn engine(&self) -> TradingEngine<MockStrategy> {
+        let mut engine = TradingEngine::new();
+        engine
+            .add_strategy(self.buy_on_etherfuse(Duration::ZERO))
+            .add_strategy(self.buy_on_jupiter(Duration::ZERO));
+        engine
+    }
+
+    async fn market_data(&self) -> MarketData {
+        let stablebond_mint = &self.stablebond_mint;
+        MarketDataBuilder::new(
+            self.rpc_client.clone(),
+            self.wallet,
+            self.venue.clone(),
+            self.jito.clone(),
+            self.oracle.clone(),
+        )
+        .with_etherfuse_price_per_token(stablebond_mint)
+        .await
+        .with_sell_liquidity_usdc_amount(stablebond_mint)
+        .await
+        .with_purchase_liquidity_stablebond_amount(stablebond_mint)
+        .await
+        .with_stablebond_holdings_token_amount(stablebond_mint)
+        .await
+        .with_usdc_holdings_token_amount()
+        .await
+        .with_update_switchboard_oracle_tx(stablebond_mint, &HashSet::new())
+        .await
+        .with_oracle_price(stablebond_mint)
+        .await
+        .with_sol_price(&MockSolPriceProvider::new(300.0))
+        .await
+        .build()
+    }
+
+    async fn tick(&self, mut engine: TradingEngine<MockStrategy>) -> Vec<StrategyResult> {
+        let market_data = self.market_data().await;
+        let mut decision = Decision::new(0, self.stablebond_mint);
+        let results = engine
+            .run_strategies(&market_data, &self.stablebond_mint, &mut decision)
+            .await;
+        assert_eq!(results.len(), 2, "{:?}", decision.strategies);
+        results
+    }
+}
+
+fn tick(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let fixture = Fixture::new();
+    c.bench_function("tick", |b| {
+        b.to_async(&runtime).iter_batched(
+            || fixture.engine(),
+            |engine| fixture.tick(engine),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn size_search(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let fixture = Fixture::new();
+    let market_data = runtime.block_on(fixture.market_data());
+    let mut group = c.benchmark_group("size_search");
+    group.sample_size(10);
+    for latency_ms in QUOTE_LATENCIES_MS {
+        let latency = Duration::from_millis(latency_ms);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}ms", latency_ms)),
+            &latency,
+            |b, &latency| {
+                b.to_async(&runtime).iter_batched(
+                    || fixture.buy_on_jupiter(latency),
+                    |mut strategy| {
+                        let (market_data, stablebond_mint) =
+                            (&market_data, fixture.stablebond_mint);
+                        async move {
+                            strategy
+                                .process_market_data(market_data, &stablebond_mint)
+                                .await
+                                .unwrap()
+                        }
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tick, size_search);
+criterion_main!(benches);
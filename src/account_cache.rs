@@ -0,0 +1,277 @@
+use solana_client::{
+    client_error::Result as ClientResult, nonblocking::rpc_client::RpcClient, rpc_request::RpcError,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use tokio::sync::Notify;
+use tracing::debug;
+
+/// Latest data of the accounts streamed by the Geyser subscriber, read in place of the RPC while
+/// the stream is live. The RPC is still read for accounts that haven't changed since the stream
+/// started, and its answer cached until the stream sends a newer one.
+#[derive(Clone, Default)]
+pub struct AccountCache {
+    accounts: Arc<RwLock<Accounts>>,
+}
+
+#[derive(Default)]
+struct Accounts {
+    live: bool,
+    watched: HashSet<Pubkey>,
+    /// Stablebond mint of the watched accounts whose changes start a tick of its worker, the
+    /// Etherfuse liquidity.
+    liquidity: HashMap<Pubkey, Pubkey>,
+    liquidity_changed: HashMap<Pubkey, Arc<Notify>>,
+    data: HashMap<Pubkey, CachedAccount>,
+}
+
+struct CachedAccount {
+    slot: u64,
+    account: Account,
+}
+
+impl AccountCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn watch(&self, accounts: &[Pubkey]) {
+        let mut cache = self.accounts.write().unwrap();
+        cache.watched.extend(accounts);
+    }
+
+    /// Watches the liquidity `accounts` of `mint`, and wakes `liquidity_changed(mint)` whenever
+    /// one of them changes.
+    pub fn watch_liquidity(&self, mint: &Pubkey, accounts: &[Pubkey]) {
+        let mut cache = self.accounts.write().unwrap();
+        cache.watched.extend(accounts);
+        cache
+            .liquidity
+            .extend(accounts.iter().map(|account| (*account, *mint)));
+        cache.liquidity_changed.entry(*mint).or_default();
+    }
+
+    pub fn watched(&self) -> Vec<Pubkey> {
+        self.accounts
+            .read()
+            .unwrap()
+            .watched
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// Serves cached accounts while live. Going down drops them, since updates may be missed
+    /// until the stream is back.
+    pub fn set_live(&self, live: bool) {
+        let mut cache = self.accounts.write().unwrap();
+        cache.live = live;
+        if !live {
+            cache.data.clear();
+        }
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.accounts.read().unwrap().live
+    }
+
+    /// Cached account and the slot it was written at.
+    pub fn get(&self, pubkey: &Pubkey) -> Option<(u64, Account)> {
+        let cache = self.accounts.read().unwrap();
+        if !cache.live {
+            return None;
+        }
+        cache
+            .data
+            .get(pubkey)
+            .map(|cached| (cached.slot, cached.account.clone()))
+    }
+
+    /// Caches a watched account as of `slot`, unless a later slot is cached already.
+    pub fn update(&self, pubkey: Pubkey, slot: u64, account: Account) {
+        let mut cache = self.accounts.write().unwrap();
+        if !cache.live || !cache.watched.contains(&pubkey) {
+            return;
+        }
+        let changed = match cache.data.get(&pubkey) {
+            Some(cached) if cached.slot > slot => return,
+            Some(cached) => cached.account.data != account.data,
+            None => false,
+        };
+        if let Some(mint) = cache.liquidity.get(&pubkey).filter(|_| changed) {
+            debug!(account = %pubkey, %mint, slot, "Liquidity changed");
+            cache.liquidity_changed[mint].notify_one();
+        }
+        cache.data.insert(pubkey, CachedAccount { slot, account });
+    }
+
+    /// Returns once a liquidity account of `mint` changed since the last call. Never returns
+    /// for mints whose liquidity isn't watched.
+    pub async fn liquidity_changed(&self, mint: &Pubkey) {
+        let notify = self
+            .accounts
+            .read()
+            .unwrap()
+            .liquidity_changed
+            .get(mint)
+            .cloned();
+        match notify {
+            Some(notify) => notify.notified().await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// `pubkey` and the slot it was read at, from `account_cache` when it has it, else from the
+/// RPC.
+pub async fn get_account(
+    rpc_client: &RpcClient,
+    account_cache: Option<&AccountCache>,
+    pubkey: &Pubkey,
+) -> ClientResult<(u64, Account)> {
+    if let Some(cached) = account_cache.and_then(|account_cache| account_cache.get(pubkey)) {
+        return Ok(cached);
+    }
+    let response = rpc_client
+        .get_account_with_commitment(pubkey, rpc_client.commitment())
+        .await?;
+    let account = response
+        .value
+        .ok_or_else(|| RpcError::ForUser(format!("AccountNotFound: pubkey={}", pubkey)))?;
+    if let Some(account_cache) = account_cache {
+        account_cache.update(*pubkey, response.context.slot, account.clone());
+    }
+    Ok((response.context.slot, account))
+}
+
+/// `pubkeys` and the latest slot they were read at, those missing from `account_cache` read in
+/// one RPC call.
+pub async fn get_multiple_accounts(
+    rpc_client: &RpcClient,
+    account_cache: Option<&AccountCache>,
+    pubkeys: &[Pubkey],
+) -> ClientResult<(u64, Vec<Account>)> {
+    let mut slot = 0;
+    let mut accounts = vec![None; pubkeys.len()];
+    if let Some(account_cache) = account_cache {
+        for (pubkey, account) in pubkeys.iter().zip(accounts.iter_mut()) {
+            if let Some((cached_slot, cached)) = account_cache.get(pubkey) {
+                slot = slot.max(cached_slot);
+                *account = Some(cached);
+            }
+        }
+    }
+    let missing: Vec<Pubkey> = pubkeys
+        .iter()
+        .zip(&accounts)
+        .filter(|(_, account)| account.is_none())
+        .map(|(pubkey, _)| *pubkey)
+        .collect();
+    if !missing.is_empty() {
+        let response = rpc_client
+            .get_multiple_accounts_with_commitment(&missing, rpc_client.commitment())
+            .await?;
+        slot = slot.max(response.context.slot);
+        let mut fetched = missing.iter().zip(response.value);
+        for account in accounts.iter_mut().filter(|account| account.is_none()) {
+            let Some((pubkey, Some(fetched))) = fetched.next() else {
+                continue;
+            };
+            if let Some(account_cache) = account_cache {
+                account_cache.update(*pubkey, response.context.slot, fetched.clone());
+            }
+            *account = Some(fetched);
+        }
+    }
+    let accounts = pubkeys
+        .iter()
+        .zip(accounts)
+        .map(|(pubkey, account)| {
+            account.ok_or_else(|| RpcError::ForUser(format!("AccountNotFound: pubkey={}", pubkey)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((slot, accounts))
+}
+
+/// Data of `pubkey`, from `account_cache` when it has it, else from the RPC.
+pub async fn get_account_data(
+    rpc_client: &RpcClient,
+    account_cache: Option<&AccountCache>,
+    pubkey: &Pubkey,
+) -> ClientResult<Vec<u8>> {
+    if account_cache.is_none() {
+        return rpc_client.get_account_data(pubkey).await;
+    }
+    let (_, account) = get_account(rpc_client, account_cache, pubkey).await?;
+    Ok(account.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn with_data(data: u8) -> Account {
+        Account {
+            data: vec![data],
+            ..Default::default()
+        }
+    }
+
+    fn live_cache(account: Pubkey) -> AccountCache {
+        let cache = AccountCache::new();
+        cache.watch_liquidity(&Pubkey::default(), &[account]);
+        cache.set_live(true);
+        cache
+    }
+
+    #[test]
+    fn keeps_the_latest_slot() {
+        let account = Pubkey::new_unique();
+        let cache = live_cache(account);
+
+        cache.update(account, 10, with_data(1));
+        cache.update(account, 9, with_data(2));
+        assert_eq!(cache.get(&account), Some((10, with_data(1))));
+        cache.update(account, 11, with_data(3));
+        assert_eq!(cache.get(&account), Some((11, with_data(3))));
+
+        cache.update(Pubkey::new_unique(), 12, with_data(4));
+        assert_eq!(cache.watched(), vec![account]);
+    }
+
+    #[test]
+    fn serves_nothing_once_the_stream_is_down() {
+        let account = Pubkey::new_unique();
+        let cache = live_cache(account);
+        cache.update(account, 10, with_data(1));
+
+        cache.set_live(false);
+        assert_eq!(cache.get(&account), None);
+        cache.set_live(true);
+        assert_eq!(cache.get(&account), None);
+    }
+
+    #[tokio::test]
+    async fn wakes_up_on_liquidity_changes_of_the_mint() {
+        let (mint, other_mint) = (Pubkey::default(), Pubkey::new_unique());
+        let account = Pubkey::new_unique();
+        let cache = live_cache(account);
+        cache.watch_liquidity(&other_mint, &[Pubkey::new_unique()]);
+
+        cache.update(account, 10, with_data(1));
+        cache.update(account, 11, with_data(1));
+        let woken = tokio::time::timeout(Duration::from_millis(10), cache.liquidity_changed(&mint));
+        assert!(woken.await.is_err());
+
+        cache.update(account, 12, with_data(2));
+        let woken = tokio::time::timeout(
+            Duration::from_millis(10),
+            cache.liquidity_changed(&other_mint),
+        );
+        assert!(woken.await.is_err());
+        let woken = tokio::time::timeout(Duration::from_millis(10), cache.liquidity_changed(&mint));
+        assert!(woken.await.is_ok());
+    }
+}
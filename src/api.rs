@@ -0,0 +1,279 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use rust_decimal::Decimal;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use serde_json::json;
+use std::net::SocketAddr;
+use subtle::ConstantTimeEq;
+use tracing::{error, info, warn};
+
+use crate::{
+    constants::{DEFAULT_API_BIND_ADDRESS, DEFAULT_API_TRADES_LIMIT, MIN_API_TOKEN_LEN},
+    control::ControlPlane,
+};
+
+/// `[api]` section of the config file. The API is only served when it is present.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfig {
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    /// Bearer token every request must carry in its `Authorization` header.
+    #[serde(deserialize_with = "token")]
+    pub token: String,
+}
+
+fn default_bind_address() -> String {
+    DEFAULT_API_BIND_ADDRESS.to_string()
+}
+
+/// Rejects tokens short enough to guess, and the empty one, which `Bearer ` would match.
+fn token<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let token = String::deserialize(deserializer)?;
+    if token.len() < MIN_API_TOKEN_LEN {
+        return Err(D::Error::custom(format!(
+            "must be at least {} characters",
+            MIN_API_TOKEN_LEN
+        )));
+    }
+    Ok(token)
+}
+
+#[derive(Clone)]
+struct ApiState {
+    control: ControlPlane,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct MintQuery {
+    mint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TradesQuery {
+    limit: Option<usize>,
+    mint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PnlQuery {
+    since: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MinProfitBody {
+    min_profit_usd: Decimal,
+}
+
+#[derive(Deserialize)]
+struct ProfileBody {
+    profile: String,
+}
+
+/// Error returned as `{"error": "..."}`.
+struct ApiError(StatusCode, anyhow::Error);
+
+impl ApiError {
+    fn bad_request(e: anyhow::Error) -> Self {
+        Self(StatusCode::BAD_REQUEST, e)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.0.is_server_error() {
+            error!("API error: {:?}", self.1);
+        }
+        (self.0, Json(json!({ "error": self.1.to_string() }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Serves the status, positions and trades of the bot over HTTP, along with the operations of
+/// the control plane, so dashboards and ops tooling can manage it programmatically. Runs until
+/// the server fails.
+pub async fn serve(config: ApiConfig, control: ControlPlane) -> Result<()> {
+    let address: SocketAddr = config.bind_address.parse()?;
+    let state = ApiState {
+        control,
+        token: config.token,
+    };
+    let app = Router::new()
+        .route("/status", get(status))
+        .route("/positions", get(positions))
+        .route("/balances", get(balances))
+        .route("/trades", get(trades))
+        .route("/pnl", get(pnl))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/min-profit", put(set_min_profit))
+        .route("/profile", put(set_profile))
+        .route("/tick", post(tick))
+        .route("/approvals", get(approvals))
+        .route("/approvals/:trade_id/approve", post(approve))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state);
+    info!("Serving the API on {}", address);
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+async fn authorize<B>(
+    State(state): State<ApiState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let header = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    if !authorized(header, &state.token) {
+        warn!(path = %request.uri().path(), "Rejecting unauthenticated API request");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(request).await
+}
+
+/// Whether an `Authorization` header carries the bearer `token`, compared in constant time so
+/// that response times don't leak how much of it a guess got right.
+fn authorized(header: Option<&str>, token: &str) -> bool {
+    header
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .is_some_and(|bearer| {
+            !token.is_empty() && bool::from(bearer.as_bytes().ct_eq(token.as_bytes()))
+        })
+}
+
+async fn status(State(state): State<ApiState>) -> impl IntoResponse {
+    Json(state.control.status())
+}
+
+async fn positions(State(state): State<ApiState>) -> impl IntoResponse {
+    Json(state.control.positions())
+}
+
+async fn balances(State(state): State<ApiState>) -> ApiResult<impl serde::Serialize> {
+    Ok(Json(state.control.balances().await?))
+}
+
+async fn trades(
+    State(state): State<ApiState>,
+    Query(query): Query<TradesQuery>,
+) -> ApiResult<impl serde::Serialize> {
+    Ok(Json(state.control.recent_trades(
+        query.limit.unwrap_or(DEFAULT_API_TRADES_LIMIT),
+        query.mint.as_deref(),
+    )?))
+}
+
+async fn pnl(
+    State(state): State<ApiState>,
+    Query(query): Query<PnlQuery>,
+) -> ApiResult<impl serde::Serialize> {
+    Ok(Json(state.control.pnl(query.since.as_deref())?))
+}
+
+async fn pause(
+    State(state): State<ApiState>,
+    Query(query): Query<MintQuery>,
+) -> ApiResult<impl serde::Serialize> {
+    state
+        .control
+        .pause(query.mint.as_deref())
+        .map_err(ApiError::bad_request)?;
+    Ok(Json(state.control.status()))
+}
+
+async fn resume(
+    State(state): State<ApiState>,
+    Query(query): Query<MintQuery>,
+) -> ApiResult<impl serde::Serialize> {
+    state.control.resume(query.mint.as_deref())?;
+    Ok(Json(state.control.status()))
+}
+
+async fn set_min_profit(
+    State(state): State<ApiState>,
+    Json(body): Json<MinProfitBody>,
+) -> ApiResult<impl serde::Serialize> {
+    state
+        .control
+        .set_min_profit(body.min_profit_usd)
+        .map_err(ApiError::bad_request)?;
+    Ok(Json(state.control.status()))
+}
+
+async fn set_profile(
+    State(state): State<ApiState>,
+    Json(body): Json<ProfileBody>,
+) -> ApiResult<impl serde::Serialize> {
+    state
+        .control
+        .set_profile(&body.profile)
+        .map_err(ApiError::bad_request)?;
+    Ok(Json(state.control.status()))
+}
+
+async fn tick(State(state): State<ApiState>) -> impl IntoResponse {
+    state.control.trigger_tick();
+    StatusCode::ACCEPTED
+}
+
+async fn approvals(State(state): State<ApiState>) -> ApiResult<impl serde::Serialize> {
+    Ok(Json(state.control.pending_approvals()?))
+}
+
+async fn approve(
+    State(state): State<ApiState>,
+    Path(trade_id): Path<String>,
+) -> ApiResult<impl serde::Serialize> {
+    if !state.control.pending_approvals()?.contains_key(&trade_id) {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            anyhow::anyhow!("No trade {} awaits approval", trade_id),
+        ));
+    }
+    state.control.approve(&trade_id).await?;
+    Ok(Json(state.control.pending_approvals()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn authorizes_only_the_bearer_token() {
+        assert!(authorized(Some(&format!("Bearer {}", TOKEN)), TOKEN));
+        assert!(!authorized(None, TOKEN));
+        assert!(!authorized(Some("Bearer wrong"), TOKEN));
+        assert!(!authorized(Some(TOKEN), TOKEN));
+        assert!(!authorized(Some("Bearer "), TOKEN));
+        assert!(!authorized(Some("Bearer "), ""));
+    }
+
+    #[test]
+    fn rejects_short_tokens() {
+        assert!(toml::from_str::<ApiConfig>("token = \"\"").is_err());
+        assert!(toml::from_str::<ApiConfig>("token = \"secret\"").is_err());
+        assert!(toml::from_str::<ApiConfig>(&format!("token = \"{}\"", TOKEN)).is_ok());
+    }
+}
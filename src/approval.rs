@@ -0,0 +1,393 @@
+use anyhow::{anyhow, Context, Result};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::{
+    constants::{
+        APPROVAL_QUEUE_LOCK_TIMEOUT_MS, APPROVAL_QUEUE_STALE_LOCK_SECS,
+        DEFAULT_APPROVAL_EXPIRY_MINUTES, DEFAULT_APPROVAL_QUEUE_FILE,
+    },
+    decision_log::{skip, SkipReason},
+    notifier::{Notifications, Severity},
+};
+
+/// `[approval]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApprovalConfig {
+    /// Notional in USD above which trades wait for approval. Trades are never held when unset.
+    pub threshold_usd: Option<Decimal>,
+    /// File holding the trades awaiting approval, shared with `arb approve`.
+    pub queue_file: String,
+    /// Minutes after which a pending trade, or an unused approval, is dropped.
+    pub expiry_minutes: i64,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            threshold_usd: None,
+            queue_file: DEFAULT_APPROVAL_QUEUE_FILE.to_string(),
+            expiry_minutes: DEFAULT_APPROVAL_EXPIRY_MINUTES,
+        }
+    }
+}
+
+/// A trade held for approval. Its transactions would be stale by the time anyone approves it,
+/// so approving it lets the next trade of the same strategy on the same mint go through, up to
+/// the approved size.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PendingTrade {
+    pub mint: String,
+    pub strategy: String,
+    pub usdc_amount: Decimal,
+    pub expected_profit_usd: Decimal,
+    pub created_at: String,
+    pub approved_at: Option<String>,
+}
+
+/// Holds trades above a notional threshold in a queue until a human signs off on them with
+/// `arb approve <trade-id>`, for desks that require approval of large trades.
+#[derive(Clone)]
+pub struct ApprovalGate {
+    pub config: ApprovalConfig,
+    pub notifications: Notifications,
+}
+
+impl ApprovalGate {
+    pub fn new(config: ApprovalConfig) -> Self {
+        Self {
+            config,
+            notifications: Notifications::new(),
+        }
+    }
+
+    /// Pushes the trades queued for approval to the configured chats.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Errors with an `AwaitingApproval` skip when a trade of `usdc_amount` needs approval and
+    /// hasn't got one, queueing it if it isn't already. Otherwise returns the id of the approval
+    /// the trade executes under, which stays in the queue until it is passed to `consume`.
+    pub async fn check(
+        &self,
+        mint: &Pubkey,
+        strategy: &str,
+        usdc_amount: Decimal,
+        expected_profit_usd: Decimal,
+    ) -> Result<Option<String>> {
+        let Some(threshold_usd) = self.config.threshold_usd else {
+            return Ok(None);
+        };
+        if usdc_amount <= threshold_usd {
+            return Ok(None);
+        }
+        let mint = mint.to_string();
+        let approval = update_queue(&self.config.queue_file, |queue| {
+            self.drop_expired(queue);
+            let queued = queue
+                .iter()
+                .find(|(_, trade)| trade.mint == mint && trade.strategy == strategy)
+                .map(|(trade_id, trade)| (trade_id.clone(), trade.clone()));
+            Ok(match queued {
+                Some((trade_id, trade))
+                    if trade.approved_at.is_some() && usdc_amount <= trade.usdc_amount =>
+                {
+                    info!(trade_id, "Executing approved trade");
+                    Ok(trade_id)
+                }
+                Some((trade_id, trade)) if trade.approved_at.is_some() => Err(format!(
+                    "Trade of ${:.2} exceeds the ${:.2} approved by {}",
+                    usdc_amount, trade.usdc_amount, trade_id
+                )),
+                Some((trade_id, _)) => Err(format!("Trade {} awaits approval", trade_id)),
+                None => {
+                    let trade_id = new_trade_id();
+                    warn!(
+                        trade_id,
+                        %usdc_amount,
+                        %expected_profit_usd,
+                        "Trade of ${:.2} awaits approval. Run `arb approve {}` to execute it",
+                        usdc_amount,
+                        trade_id
+                    );
+                    self.notifications.notify(
+                        Severity::Warning,
+                        format!(
+                            "Trade {} of ${:.2} on {} awaits approval, expecting ${:.4} profit. \
+                             Run `arb approve {}` to execute it",
+                            trade_id, usdc_amount, mint, expected_profit_usd, trade_id
+                        ),
+                    );
+                    queue.insert(
+                        trade_id.clone(),
+                        PendingTrade {
+                            mint: mint.clone(),
+                            strategy: strategy.to_string(),
+                            usdc_amount,
+                            expected_profit_usd,
+                            created_at: chrono::Utc::now().to_rfc3339(),
+                            approved_at: None,
+                        },
+                    );
+                    Err(format!("Trade {} awaits approval", trade_id))
+                }
+            })
+        })
+        .await
+        // Without the queue there's no telling whether the trade was approved.
+        .map_err(|e| skip(SkipReason::AwaitingApproval, format!("{:#}", e)))?;
+        approval.map(Some).map_err(|message| {
+            skip(
+                SkipReason::AwaitingApproval,
+                format!(
+                    "{}: ${:.2} is above the approval threshold of ${:.2}",
+                    message, usdc_amount, threshold_usd
+                ),
+            )
+        })
+    }
+
+    /// Removes the approval `trade_id` from the queue once a trade went through under it. Until
+    /// then a trade that was skipped or failed after `check` can retry under the same approval.
+    pub async fn consume(&self, trade_id: &str) -> Result<()> {
+        update_queue(&self.config.queue_file, |queue| {
+            queue.remove(trade_id);
+            Ok(())
+        })
+        .await?;
+        info!(trade_id, "Consumed approval");
+        Ok(())
+    }
+
+    /// Drops the trades that waited, or whose approval went unused, for longer than the expiry.
+    fn drop_expired(&self, queue: &mut BTreeMap<String, PendingTrade>) {
+        let cutoff = chrono::Utc::now() - chrono::Duration::minutes(self.config.expiry_minutes);
+        queue.retain(|_, trade| {
+            trade
+                .approved_at
+                .as_deref()
+                .unwrap_or(&trade.created_at)
+                .parse::<chrono::DateTime<chrono::Utc>>()
+                .is_ok_and(|at| at > cutoff)
+        });
+    }
+}
+
+/// Approves the pending trade `trade_id`.
+pub async fn approve(queue_file: &str, trade_id: &str) -> Result<()> {
+    update_queue(queue_file, |queue| {
+        let trade = queue
+            .get_mut(trade_id)
+            .ok_or_else(|| anyhow!("No trade {} awaits approval", trade_id))?;
+        trade.approved_at = Some(chrono::Utc::now().to_rfc3339());
+        Ok(())
+    })
+    .await
+}
+
+/// Trades awaiting approval, or approved but not executed yet, by id.
+pub fn pending_trades(queue_file: &str) -> Result<BTreeMap<String, PendingTrade>> {
+    read_queue(queue_file)
+}
+
+fn new_trade_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
+
+/// Applies `update` to the queue in `queue_file` while holding its lock file, so that the bot,
+/// `arb approve` and the API don't overwrite each other's changes. The queue is replaced
+/// atomically, and only if `update` changed it.
+async fn update_queue<T>(
+    queue_file: &str,
+    update: impl FnOnce(&mut BTreeMap<String, PendingTrade>) -> Result<T>,
+) -> Result<T> {
+    let _lock = QueueLock::acquire(queue_file).await?;
+    let mut queue = read_queue(queue_file)?;
+    let before = queue.clone();
+    let result = update(&mut queue)?;
+    if queue != before {
+        write_queue(queue_file, &queue)?;
+    }
+    Ok(result)
+}
+
+/// A missing queue is an empty one, but an unreadable or corrupt one is an error rather than
+/// something to overwrite with an empty queue.
+fn read_queue(path: &str) -> Result<BTreeMap<String, PendingTrade>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("Error reading approval queue {}", path)),
+    };
+    serde_json::from_str(&data).with_context(|| format!("Approval queue {} is corrupt", path))
+}
+
+fn write_queue(path: &str, queue: &BTreeMap<String, PendingTrade>) -> Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, serde_json::to_string_pretty(queue)?)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// `<queue file>.lock`, held while the queue is read, modified and written. Removed when
+/// dropped. Waiting for it sleeps on the runtime rather than blocking its thread.
+struct QueueLock(PathBuf);
+
+impl QueueLock {
+    async fn acquire(queue_file: &str) -> Result<Self> {
+        let path = PathBuf::from(format!("{}.lock", queue_file));
+        let deadline = Instant::now() + Duration::from_millis(APPROVAL_QUEUE_LOCK_TIMEOUT_MS);
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Self(path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    // Left behind by a process that died holding it.
+                    let stale = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .is_ok_and(|modified| {
+                            modified.elapsed().unwrap_or_default()
+                                > Duration::from_secs(APPROVAL_QUEUE_STALE_LOCK_SECS)
+                        });
+                    if stale {
+                        warn!("Removing stale approval queue lock {}", path.display());
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if Instant::now() >= deadline {
+                        return Err(anyhow!(
+                            "Timed out waiting for approval queue lock {}",
+                            path.display()
+                        ));
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Error locking {}", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for QueueLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::TempDir;
+
+    fn gate(queue_file: &str) -> ApprovalGate {
+        ApprovalGate::new(ApprovalConfig {
+            threshold_usd: Some(Decimal::from(100)),
+            queue_file: queue_file.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn corrupt_queue_is_an_error_and_left_alone() {
+        let temp_dir = TempDir::create("approval-test");
+        let queue_file = temp_dir.file("approval_queue.json");
+        fs::write(&queue_file, "{not json").unwrap();
+
+        assert!(pending_trades(&queue_file).is_err());
+        assert!(approve(&queue_file, "deadbeef").await.is_err());
+        assert!(gate(&queue_file)
+            .check(
+                &Pubkey::new_unique(),
+                "strategy",
+                Decimal::from(200),
+                Decimal::ONE
+            )
+            .await
+            .is_err());
+        assert_eq!(fs::read_to_string(&queue_file).unwrap(), "{not json");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_writers_do_not_lose_approvals() {
+        let temp_dir = TempDir::create("approval-test");
+        let queue_file = temp_dir.file("approval_queue.json");
+        let gate = gate(&queue_file);
+        for _ in 0..8 {
+            let _ = gate
+                .check(
+                    &Pubkey::new_unique(),
+                    "strategy",
+                    Decimal::from(200),
+                    Decimal::ONE,
+                )
+                .await;
+        }
+        let trade_ids: Vec<String> = pending_trades(&queue_file).unwrap().into_keys().collect();
+        assert_eq!(trade_ids.len(), 8);
+
+        let approvers: Vec<_> = trade_ids
+            .into_iter()
+            .map(|trade_id| {
+                let queue_file = queue_file.clone();
+                tokio::spawn(async move { approve(&queue_file, &trade_id).await.unwrap() })
+            })
+            .collect();
+        for approver in approvers {
+            approver.await.unwrap();
+        }
+
+        assert!(pending_trades(&queue_file)
+            .unwrap()
+            .values()
+            .all(|trade| trade.approved_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn approval_is_kept_until_consumed() {
+        let temp_dir = TempDir::create("approval-test");
+        let queue_file = temp_dir.file("approval_queue.json");
+        let gate = gate(&queue_file);
+        let mint = Pubkey::new_unique();
+        assert!(gate
+            .check(&mint, "strategy", Decimal::from(200), Decimal::ONE)
+            .await
+            .is_err());
+        let trade_id = pending_trades(&queue_file)
+            .unwrap()
+            .into_keys()
+            .next()
+            .unwrap();
+        approve(&queue_file, &trade_id).await.unwrap();
+
+        // The first attempt under the approval was skipped before it was submitted.
+        for _ in 0..2 {
+            assert_eq!(
+                gate.check(&mint, "strategy", Decimal::from(150), Decimal::ONE)
+                    .await
+                    .unwrap(),
+                Some(trade_id.clone())
+            );
+        }
+        gate.consume(&trade_id).await.unwrap();
+
+        assert!(gate
+            .check(&mint, "strategy", Decimal::from(150), Decimal::ONE)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Clone, Debug)]
+struct Reservation {
+    token_mint: Pubkey,
+    amount: u64,
+    expires_at: Instant,
+}
+
+/// Token amounts spent by bundles that are still pending. Their inputs are still in the wallet
+/// until the bundle lands, so without deducting them the next tick's balance fetch would size
+/// another trade on the same funds. A reservation is released once its bundle's outcome is
+/// known, or after `ttl` in case the outcome never arrives.
+#[derive(Clone)]
+pub struct BalanceReservations {
+    pub ttl: Duration,
+    reservations: Arc<Mutex<HashMap<String, Vec<Reservation>>>>,
+}
+
+impl BalanceReservations {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            reservations: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Reserves `amount` of `token_mint` for the bundle `bundle_id`.
+    pub fn reserve(&self, bundle_id: &str, token_mint: &Pubkey, amount: u64) {
+        if amount == 0 {
+            return;
+        }
+        info!(bundle_id, token_mint = %token_mint, amount, "Reserving balance");
+        self.reservations
+            .lock()
+            .unwrap()
+            .entry(bundle_id.to_string())
+            .or_default()
+            .push(Reservation {
+                token_mint: *token_mint,
+                amount,
+                expires_at: Instant::now() + self.ttl,
+            });
+    }
+
+    pub fn release(&self, bundle_id: &str) {
+        if self
+            .reservations
+            .lock()
+            .unwrap()
+            .remove(bundle_id)
+            .is_some()
+        {
+            info!(bundle_id, "Released balance reservation");
+        }
+    }
+
+    /// Amount of `token_mint` reserved by pending bundles.
+    pub fn reserved(&self, token_mint: &Pubkey) -> u64 {
+        let mut reservations = self.reservations.lock().unwrap();
+        let now = Instant::now();
+        reservations.retain(|bundle_id, reservations| {
+            let expired = reservations.iter().all(|r| r.expires_at <= now);
+            if expired {
+                warn!(
+                    bundle_id,
+                    "Balance reservation expired before its bundle's outcome"
+                );
+            }
+            !expired
+        });
+        reservations
+            .values()
+            .flatten()
+            .filter(|r| r.token_mint == *token_mint)
+            .map(|r| r.amount)
+            .sum()
+    }
+
+    /// `balance` of `token_mint` less the amounts reserved by pending bundles.
+    pub fn available(&self, token_mint: &Pubkey, balance: u64) -> u64 {
+        balance.saturating_sub(self.reserved(token_mint))
+    }
+}
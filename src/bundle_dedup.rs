@@ -0,0 +1,113 @@
+use crate::strategy::{StrategyResult, TradeDirection};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Suppresses re-submission of an opportunity identical to one submitted within the
+/// window, so two ticks seeing the same market don't double the position. Opportunities are
+/// identical when they trade the same mint in the same direction for about the same amount,
+/// however many slots apart they were quoted.
+pub struct BundleDedup {
+    window: Duration,
+    size_bucket: u64,
+    submitted: HashMap<u64, Instant>,
+}
+
+#[derive(Hash)]
+struct OpportunityKey {
+    stablebond_mint: Pubkey,
+    direction: TradeDirection,
+    size_bucket: u64,
+}
+
+impl BundleDedup {
+    pub fn new(window_secs: u64, size_bucket: u64) -> Self {
+        Self {
+            window: Duration::from_secs(window_secs),
+            size_bucket: size_bucket.max(1),
+            submitted: HashMap::new(),
+        }
+    }
+
+    /// Returns `true` if the opportunity wasn't submitted within the window.
+    pub fn check(&mut self, strategy_result: &StrategyResult) -> bool {
+        let now = Instant::now();
+        self.submitted
+            .retain(|_, submitted_at| now.duration_since(*submitted_at) < self.window);
+        !self.submitted.contains_key(&self.key(strategy_result))
+    }
+
+    /// Records the opportunity as submitted. Called once it was sent, so an attempt aborted
+    /// before then doesn't suppress a retry.
+    pub fn record(&mut self, strategy_result: &StrategyResult) {
+        let key = self.key(strategy_result);
+        self.submitted.insert(key, Instant::now());
+    }
+
+    fn key(&self, strategy_result: &StrategyResult) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        OpportunityKey {
+            stablebond_mint: strategy_result.stablebond_mint,
+            direction: strategy_result.direction,
+            size_bucket: strategy_result.usdc_amount / self.size_bucket,
+        }
+        .hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn opportunity(direction: TradeDirection, usdc_amount: u64) -> StrategyResult {
+        StrategyResult {
+            strategy: "test",
+            profit: Decimal::ONE,
+            txs: Vec::new(),
+            stablebond_mint: Pubkey::new_from_array([1; 32]),
+            direction,
+            usdc_amount,
+            capped: false,
+            quoted_at: Instant::now(),
+            legs: Vec::new(),
+            preflight_skipped: None,
+        }
+    }
+
+    #[test]
+    fn dedups_by_mint_direction_and_size_bucket() {
+        let mut dedup = BundleDedup::new(60, 10_000_000);
+        dedup.record(&opportunity(
+            TradeDirection::BuyOnEtherfuseSellOnJupiter,
+            12_000_000,
+        ));
+
+        // Same bucket on a later tick.
+        assert!(!dedup.check(&opportunity(
+            TradeDirection::BuyOnEtherfuseSellOnJupiter,
+            18_000_000
+        )));
+        assert!(dedup.check(&opportunity(
+            TradeDirection::BuyOnEtherfuseSellOnJupiter,
+            25_000_000
+        )));
+        assert!(dedup.check(&opportunity(
+            TradeDirection::BuyOnJupiterSellOnEtherfuse,
+            12_000_000
+        )));
+    }
+
+    #[test]
+    fn unrecorded_opportunity_is_not_a_duplicate() {
+        let mut dedup = BundleDedup::new(60, 10_000_000);
+        let opportunity = opportunity(TradeDirection::BuyOnEtherfuseSellOnJupiter, 12_000_000);
+
+        assert!(dedup.check(&opportunity));
+        // The first attempt was aborted before it was sent.
+        assert!(dedup.check(&opportunity));
+    }
+}
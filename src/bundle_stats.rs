@@ -0,0 +1,137 @@
+use crate::jito::{BundleStatusEnum, TipPercentile};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::error;
+
+/// Number of most recent bundles the rolling statistics are computed over.
+const STATS_WINDOW: usize = 1000;
+
+/// Rolling bundle land-rate statistics, persisted to disk after every bundle so they can be
+/// inspected with the `stats` subcommand while the bot is running.
+#[derive(Clone)]
+pub struct BundleStats {
+    pub region: String,
+    pub path: String,
+    records: Arc<Mutex<VecDeque<BundleRecord>>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BundleRecord {
+    pub region: String,
+    pub tip_percentile: TipPercentile,
+    pub status: BundleStatusEnum,
+    pub time_to_land_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LandRate {
+    pub submitted: u64,
+    pub landed: u64,
+    pub failed: u64,
+    pub timed_out: u64,
+    pub total_time_to_land_ms: u64,
+}
+
+impl LandRate {
+    pub fn land_rate(&self) -> f64 {
+        if self.submitted == 0 {
+            return 0.0;
+        }
+        self.landed as f64 / self.submitted as f64
+    }
+
+    pub fn average_time_to_land_ms(&self) -> Option<u64> {
+        self.total_time_to_land_ms.checked_div(self.landed)
+    }
+
+    fn add(&mut self, record: &BundleRecord) {
+        self.submitted += 1;
+        match record.status {
+            BundleStatusEnum::Landed => {
+                self.landed += 1;
+                self.total_time_to_land_ms += record.time_to_land_ms.unwrap_or(0);
+            }
+            BundleStatusEnum::Timeout => self.timed_out += 1,
+            _ => self.failed += 1,
+        }
+    }
+}
+
+impl BundleStats {
+    /// Loads previously persisted records from `path` so the statistics survive restarts.
+    pub fn new(path: String, region: String) -> Self {
+        let records = load_records(&path).unwrap_or_default();
+        Self {
+            region,
+            path,
+            records: Arc::new(Mutex::new(records)),
+        }
+    }
+
+    pub fn record(
+        &self,
+        tip_percentile: TipPercentile,
+        status: BundleStatusEnum,
+        time_to_land: Option<Duration>,
+    ) {
+        let mut records = self.records.lock().unwrap();
+        records.push_back(BundleRecord {
+            region: self.region.clone(),
+            tip_percentile,
+            status,
+            time_to_land_ms: time_to_land.map(|t| t.as_millis() as u64),
+        });
+        while records.len() > STATS_WINDOW {
+            records.pop_front();
+        }
+        if let Err(e) = save_records(&self.path, &records) {
+            error!("Error saving bundle stats: {:?}", e);
+        }
+    }
+
+    pub fn by_region(&self) -> BTreeMap<String, LandRate> {
+        aggregate(&self.records.lock().unwrap(), |record| {
+            record.region.clone()
+        })
+    }
+
+    pub fn by_tip_percentile(&self) -> BTreeMap<String, LandRate> {
+        aggregate(&self.records.lock().unwrap(), |record| {
+            format!("{:?}", record.tip_percentile)
+        })
+    }
+}
+
+fn aggregate(
+    records: &VecDeque<BundleRecord>,
+    key: impl Fn(&BundleRecord) -> String,
+) -> BTreeMap<String, LandRate> {
+    let mut land_rates: BTreeMap<String, LandRate> = BTreeMap::new();
+    for record in records {
+        land_rates.entry(key(record)).or_default().add(record);
+    }
+    land_rates
+}
+
+fn load_records(path: &str) -> Result<VecDeque<BundleRecord>> {
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn save_records(path: &str, records: &VecDeque<BundleRecord>) -> Result<()> {
+    fs::write(path, serde_json::to_string(records)?)?;
+    Ok(())
+}
+
+/// Region of a block engine, e.g. `slc` for `https://slc.mainnet.block-engine.jito.wtf`.
+pub fn region_from_url(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .and_then(|host| host.split('.').next().map(|region| region.to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
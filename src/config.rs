@@ -0,0 +1,457 @@
+use serde::de::{DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use solana_program::pubkey::Pubkey;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    marker::PhantomData,
+    str::FromStr,
+};
+use toml::{Table, Value};
+#[cfg(feature = "notifications")]
+use tracing::info;
+
+#[cfg(feature = "api")]
+use crate::api::ApiConfig;
+use crate::{
+    approval::ApprovalConfig,
+    dust_sweep::DustSweepConfig,
+    error::ConfigError,
+    gas_top_up::GasTopUpConfig,
+    http::HttpConfig,
+    leg_failure::LegFailureConfig,
+    pool_state::PoolConfig,
+    priority_fee::PriorityFeeConfig,
+    profile::ProfileConfig,
+    rate_limiter::{Endpoint, RateLimitConfig},
+    risk::RiskConfig,
+    route_check::RouteCheckConfig,
+    spread_history::AdaptiveIntervalConfig,
+    trade_chunking::ChunkingConfig,
+    unwind::UnwindConfig,
+    usdc_peg::UsdcPegConfig,
+};
+#[cfg(feature = "notifications")]
+use crate::{
+    event_sink::WebhookSinkConfig,
+    http::build_http_client,
+    notifier::{DiscordNotifier, Notifications, NotifierEnum, SlackNotifier, WebhookConfig},
+    telegram::{TelegramConfig, TelegramNotifier},
+};
+
+pub const CONFIG_FILE: &str = "tokens.toml";
+
+/// Sections the config file may have. Any other is reported as a likely typo.
+const SECTIONS: &[&str] = &[
+    "tokens",
+    "priority_fee",
+    "rate_limits",
+    "http",
+    "risk",
+    "unwind",
+    "leg_failure",
+    "gas_top_up",
+    "dust_sweep",
+    "approval",
+    "profiles",
+    "pools",
+    "tick_intervals",
+    "adaptive_interval",
+    "chunking",
+    "route_check",
+    "usdc_peg",
+    "webhook",
+    "api",
+    "telegram",
+    "discord",
+    "slack",
+];
+
+type Result<T> = std::result::Result<T, ConfigError>;
+
+/// Text of the config file, once it is known to be valid TOML.
+fn read_config() -> Result<String> {
+    let toml_str = fs::read_to_string(CONFIG_FILE).map_err(|source| ConfigError::Read {
+        path: CONFIG_FILE.to_string(),
+        source,
+    })?;
+    toml_str
+        .parse::<Value>()
+        .map_err(|source| ConfigError::Parse {
+            path: CONFIG_FILE.to_string(),
+            source,
+        })?;
+    Ok(toml_str)
+}
+
+/// The `[section]` of the config file, `None` when it is absent. Deserialized from the text
+/// rather than a parsed `Value`, so that errors point at the line of the offending field.
+fn parse_section<T: DeserializeOwned>(source: &str, section: &str) -> Result<Option<T>> {
+    SectionSeed {
+        section,
+        marker: PhantomData,
+    }
+    .deserialize(toml::Deserializer::new(source))
+    .map_err(|e| ConfigError::InvalidSection {
+        section: section.to_string(),
+        message: with_suggestion(e.to_string()),
+    })
+}
+
+/// Deserializes one section of the config file and skips the others.
+struct SectionSeed<'a, T> {
+    section: &'a str,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: DeserializeOwned> DeserializeSeed<'de> for SectionSeed<'_, T> {
+    type Value = Option<T>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Option<T>, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for SectionSeed<'_, T> {
+    type Value = Option<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML table")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Option<T>, A::Error> {
+        let mut section = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.section {
+                section = Some(map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(section)
+    }
+}
+
+/// `message` followed by the closest valid name, when it is about an unknown field or variant
+/// that is likely a typo of one.
+fn with_suggestion(message: String) -> String {
+    let Some(start) = message
+        .find("unknown field `")
+        .or_else(|| message.find("unknown variant `"))
+    else {
+        return message;
+    };
+    let mut quoted = message[start..].split('`').skip(1).step_by(2);
+    let Some(unknown) = quoted.next() else {
+        return message;
+    };
+    match closest(unknown, quoted) {
+        Some(suggestion) => format!(
+            "{}\nhelp: did you mean `{}`?",
+            message.trim_end(),
+            suggestion
+        ),
+        None => message,
+    }
+}
+
+/// The candidate within a few edits of `name`, if any.
+fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substituted = diagonal + (a != *b) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Line of the first occurrence of `text` in `source`, counted from 1.
+fn line_of(source: &str, text: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| line.contains(text))
+        .map(|index| index + 1)
+}
+
+/// Parses a mint of `section`, pointing at its line when it isn't a valid pubkey.
+fn parse_mint(source: &str, section: &str, mint: &str) -> Result<Pubkey> {
+    Pubkey::from_str(mint).map_err(|e| ConfigError::InvalidSection {
+        section: section.to_string(),
+        message: match line_of(source, mint) {
+            Some(line) => format!("line {}: `{}` is not a valid pubkey: {}", line, mint, e),
+            None => format!("`{}` is not a valid pubkey: {}", mint, e),
+        },
+    })
+}
+
+/// Parses the keys of a `[section]` keyed by stablebond mint.
+fn parse_by_mint<T: DeserializeOwned>(source: &str, section: &str) -> Result<HashMap<Pubkey, T>> {
+    let by_mint: HashMap<String, T> = parse_section(source, section)?.unwrap_or_default();
+    by_mint
+        .into_iter()
+        .map(|(mint, value)| Ok((parse_mint(source, section, &mint)?, value)))
+        .collect()
+}
+
+fn parse_tokens(source: &str) -> Result<Vec<Pubkey>> {
+    let tokens: Vec<String> =
+        parse_section(source, "tokens")?.ok_or_else(|| ConfigError::MissingSection {
+            section: "tokens".to_string(),
+            message: "list the stablebond mints to trade, e.g. tokens = [\"<MINT>\"]".to_string(),
+        })?;
+    tokens
+        .iter()
+        .map(|token| parse_mint(source, "tokens", token))
+        .collect()
+}
+
+/// Sections of `source` that aren't config sections, with the closest one when it looks like
+/// a typo.
+fn unknown_sections(source: &str) -> Vec<ConfigError> {
+    let Ok(table) = source.parse::<Table>() else {
+        return Vec::new();
+    };
+    table
+        .keys()
+        .filter(|section| !SECTIONS.contains(&section.as_str()))
+        .map(|section| {
+            let line = line_of(source, &format!("[{}", section))
+                .or_else(|| line_of(source, section))
+                .map_or_else(String::new, |line| format!("line {}: ", line));
+            ConfigError::UnknownSection {
+                section: section.clone(),
+                message: match closest(section, SECTIONS.iter().copied()) {
+                    Some(suggestion) => format!("{}did you mean [{}]?", line, suggestion),
+                    None => format!("{}expected one of {}", line, SECTIONS.join(", ")),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Every error in the config file: invalid TOML, unknown sections, invalid fields and mints,
+/// instead of stopping at the first one. Empty when the file is valid.
+pub fn check_config() -> Vec<ConfigError> {
+    let source = match read_config() {
+        Ok(source) => source,
+        Err(e) => return vec![e],
+    };
+    let mut errors = unknown_sections(&source);
+    let checks: Vec<Result<()>> = vec![
+        parse_tokens(&source).map(drop),
+        parse_by_mint::<PoolConfig>(&source, "pools").map(drop),
+        parse_by_mint::<u64>(&source, "tick_intervals").map(drop),
+        parse_section::<AdaptiveIntervalConfig>(&source, "adaptive_interval").map(drop),
+        parse_section::<ChunkingConfig>(&source, "chunking").map(drop),
+        parse_section::<RouteCheckConfig>(&source, "route_check").map(drop),
+        parse_section::<UsdcPegConfig>(&source, "usdc_peg").map(drop),
+        parse_section::<PriorityFeeConfig>(&source, "priority_fee").map(drop),
+        parse_section::<HashMap<Endpoint, RateLimitConfig>>(&source, "rate_limits").map(drop),
+        parse_section::<HttpConfig>(&source, "http").map(drop),
+        parse_section::<RiskConfig>(&source, "risk").map(drop),
+        parse_section::<UnwindConfig>(&source, "unwind").map(drop),
+        parse_section::<LegFailureConfig>(&source, "leg_failure").map(drop),
+        parse_section::<GasTopUpConfig>(&source, "gas_top_up").map(drop),
+        parse_section::<DustSweepConfig>(&source, "dust_sweep").map(drop),
+        parse_section::<ApprovalConfig>(&source, "approval").map(drop),
+        parse_section::<BTreeMap<String, ProfileConfig>>(&source, "profiles").map(drop),
+        #[cfg(feature = "notifications")]
+        parse_section::<WebhookSinkConfig>(&source, "webhook").map(drop),
+        #[cfg(feature = "notifications")]
+        parse_section::<TelegramConfig>(&source, "telegram").map(drop),
+        #[cfg(feature = "notifications")]
+        parse_section::<WebhookConfig>(&source, "discord").map(drop),
+        #[cfg(feature = "notifications")]
+        parse_section::<WebhookConfig>(&source, "slack").map(drop),
+        #[cfg(feature = "api")]
+        parse_section::<ApiConfig>(&source, "api").map(drop),
+    ];
+    errors.extend(checks.into_iter().filter_map(|check| check.err()));
+    errors
+}
+
+pub fn parse_toml_config() -> Result<Vec<Pubkey>> {
+    parse_tokens(&read_config()?)
+}
+
+/// Pools of the `[pools]` section of the config file, keyed by stablebond mint.
+pub fn parse_pool_config() -> Result<HashMap<Pubkey, PoolConfig>> {
+    parse_by_mint(&read_config()?, "pools")
+}
+
+/// Seconds between the ticks of each mint in the `[tick_intervals]` section of the config
+/// file, by stablebond mint. Mints left out tick at the `--tick-interval-secs` default.
+pub fn parse_tick_interval_config() -> Result<HashMap<Pubkey, u64>> {
+    parse_by_mint(&read_config()?, "tick_intervals")
+}
+
+pub fn parse_adaptive_interval_config() -> Result<AdaptiveIntervalConfig> {
+    Ok(parse_section(&read_config()?, "adaptive_interval")?.unwrap_or_default())
+}
+
+pub fn parse_chunking_config() -> Result<ChunkingConfig> {
+    Ok(parse_section(&read_config()?, "chunking")?.unwrap_or_default())
+}
+
+pub fn parse_route_check_config() -> Result<RouteCheckConfig> {
+    Ok(parse_section(&read_config()?, "route_check")?.unwrap_or_default())
+}
+
+pub fn parse_usdc_peg_config() -> Result<UsdcPegConfig> {
+    Ok(parse_section(&read_config()?, "usdc_peg")?.unwrap_or_default())
+}
+
+pub fn parse_priority_fee_config() -> Result<PriorityFeeConfig> {
+    Ok(parse_section(&read_config()?, "priority_fee")?.unwrap_or_default())
+}
+
+pub fn parse_rate_limit_config() -> Result<HashMap<Endpoint, RateLimitConfig>> {
+    Ok(parse_section(&read_config()?, "rate_limits")?.unwrap_or_default())
+}
+
+pub fn parse_risk_config() -> Result<RiskConfig> {
+    Ok(parse_section(&read_config()?, "risk")?.unwrap_or_default())
+}
+
+pub fn parse_unwind_config() -> Result<UnwindConfig> {
+    Ok(parse_section(&read_config()?, "unwind")?.unwrap_or_default())
+}
+
+pub fn parse_leg_failure_config() -> Result<LegFailureConfig> {
+    Ok(parse_section(&read_config()?, "leg_failure")?.unwrap_or_default())
+}
+
+pub fn parse_dust_sweep_config() -> Result<DustSweepConfig> {
+    Ok(parse_section(&read_config()?, "dust_sweep")?.unwrap_or_default())
+}
+
+pub fn parse_gas_top_up_config() -> Result<GasTopUpConfig> {
+    Ok(parse_section(&read_config()?, "gas_top_up")?.unwrap_or_default())
+}
+
+pub fn parse_approval_config() -> Result<ApprovalConfig> {
+    Ok(parse_section(&read_config()?, "approval")?.unwrap_or_default())
+}
+
+/// `[profiles]` section of the config file, overrides of the built-in profiles and new ones by
+/// name.
+pub fn parse_profile_config() -> Result<BTreeMap<String, ProfileConfig>> {
+    Ok(parse_section(&read_config()?, "profiles")?.unwrap_or_default())
+}
+
+#[cfg(feature = "notifications")]
+pub fn parse_webhook_config() -> Result<Option<WebhookSinkConfig>> {
+    parse_section(&read_config()?, "webhook")
+}
+
+#[cfg(feature = "api")]
+pub fn parse_api_config() -> Result<Option<ApiConfig>> {
+    parse_section(&read_config()?, "api")
+}
+
+/// Notifiers of the `[telegram]`, `[discord]` and `[slack]` sections of the config file.
+#[cfg(feature = "notifications")]
+pub fn build_notifications(http_config: &HttpConfig) -> Result<Notifications> {
+    let source = read_config()?;
+    let http_client = || {
+        build_http_client(http_config, None).map_err(|e| ConfigError::InvalidSection {
+            section: "http".to_string(),
+            message: e.to_string(),
+        })
+    };
+
+    let mut notifications = Notifications::new();
+    if let Some(config) = parse_section::<TelegramConfig>(&source, "telegram")? {
+        info!("Sending notifications to Telegram chat {}", config.chat_id);
+        notifications.add_notifier(NotifierEnum::Telegram(
+            TelegramNotifier::new(config).with_http_client(http_client()?),
+        ));
+    }
+    if let Some(config) = parse_section::<WebhookConfig>(&source, "discord")? {
+        info!("Sending notifications to Discord");
+        notifications.add_notifier(NotifierEnum::Discord(
+            DiscordNotifier::new(config).with_http_client(http_client()?),
+        ));
+    }
+    if let Some(config) = parse_section::<WebhookConfig>(&source, "slack")? {
+        info!("Sending notifications to Slack");
+        notifications.add_notifier(NotifierEnum::Slack(
+            SlackNotifier::new(config).with_http_client(http_client()?),
+        ));
+    }
+    Ok(notifications)
+}
+
+pub fn parse_http_config() -> Result<HttpConfig> {
+    Ok(parse_section(&read_config()?, "http")?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_at_the_line_of_unknown_fields_with_a_suggestion() {
+        let source = "tokens = []\n\n[gas_top_up]\nenabled = true\nmin_sool = 0.1\n";
+
+        let message = parse_section::<GasTopUpConfig>(source, "gas_top_up")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(message.contains("line 5"), "{}", message);
+        assert!(message.contains("did you mean `min_sol`?"), "{}", message);
+    }
+
+    #[test]
+    fn reports_invalid_mints_and_unknown_sections_by_line() {
+        let source = "tokens = [\n  \"CETES7CKqqKQizuSN6iWQwmTeFRjbJR6Vw2XRKfEDR8f\",\n  \"not-a-mint\",\n]\n\n\
+                      [gas_top_upp]\nenabled = true\n";
+
+        let message = parse_tokens(source).err().unwrap().to_string();
+        assert!(message.contains("line 3: `not-a-mint`"), "{}", message);
+        let errors = unknown_sections(source);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "Unknown [gas_top_upp] section: line 6: did you mean [gas_top_up]?"
+        );
+    }
+
+    #[test]
+    fn rejects_rate_limits_without_requests() {
+        let source = "tokens = []\n\n[rate_limits.jupiter]\nwindow_secs = 60\nmax_requests = 0\n";
+
+        let message = parse_section::<HashMap<Endpoint, RateLimitConfig>>(source, "rate_limits")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(message.contains("must be greater than 0"), "{}", message);
+        assert!(message.contains("line 5"), "{}", message);
+    }
+
+    #[test]
+    fn requires_the_tokens() {
+        assert!(matches!(
+            parse_tokens("[http]\ntimeout_secs = 5\n"),
+            Err(ConfigError::MissingSection { .. })
+        ));
+    }
+}
@@ -1,15 +1,220 @@
+use rust_decimal::Decimal;
+
 pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 pub const USDC_DECIMALS: u8 = 6;
 pub const STABLEBOND_DECIMALS: u8 = 6;
+pub const SOL_DECIMALS: u8 = 9;
 pub const MIN_USDC_AMOUNT: u64 = 1000000;
-pub const MAX_USDC_AMOUNT_PER_TRADE: f64 = 1000.0;
+pub const MAX_USDC_AMOUNT_PER_TRADE: Decimal = Decimal::from_parts(1000, 0, 0, false, 0);
 pub const MAX_STABLEBOND_AMOUNT_PER_TRADE: u64 = 20_000_000_000;
 
 // Strategy constants
-pub const MIN_TRADE_PERCENT: f64 = 0.01;
-pub const MAX_TRADE_PERCENT: f64 = 1.0;
+pub const MIN_TRADE_PERCENT: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+pub const MAX_TRADE_PERCENT: Decimal = Decimal::ONE;
 pub const INITIAL_POINTS: usize = 8;
 pub const MAX_RETRIES: u32 = 3;
 pub const RETRY_DELAY_MS: u64 = 60000;
+pub const DEFAULT_MIN_PROFIT_USD: Decimal = Decimal::ONE;
 
 pub const SLIPPAGE_BIPS: u64 = 20;
+
+// Jito constants
+pub const MAX_BUNDLE_TXS: usize = 5;
+pub const BUNDLE_RETRIES_PER_TIP_PERCENTILE: u32 = 2;
+pub const BUNDLE_VALIDITY_WINDOW_SECS: u64 = 60;
+pub const RECONCILIATION_SIGNATURE_LIMIT: usize = 1_000;
+pub const BALANCE_RESERVATION_TTL_SECS: u64 = 300;
+pub const MAX_TIP_PROFIT_SHARE: f64 = 0.5;
+pub const TIP_FLOOR_TTL_SECS: u64 = 30;
+pub const TIP_FLOOR_RETRY_SECS: u64 = 5;
+/// In blocks, i.e. block heights rather than slots.
+pub const DEFAULT_MAX_BLOCKHASH_AGE: u64 = 60;
+/// Smallest tip the block engine accepts.
+pub const DEFAULT_MIN_TIP_LAMPORTS: u64 = 1_000;
+pub const BUNDLE_RESULTS_RECONNECT_BASE_SECS: u64 = 1;
+pub const BUNDLE_RESULTS_RECONNECT_MAX_SECS: u64 = 60;
+/// Pushed bundle results are kept this long, well past the bundles' validity window.
+pub const BUNDLE_RESULT_TTL_SECS: u64 = 300;
+pub const DEDUP_SIZE_BUCKET_USDC_AMOUNT: u64 = 10_000_000;
+pub const JITO_TIP_FLOOR_URL: &str = "https://bundles.jito.wtf/api/v1/bundles/tip_floor";
+
+// Slot timing constants
+pub const SLOT_DURATION_MS: u64 = 400;
+pub const DEFAULT_MAX_SUBMISSION_DEFERRAL_MS: u64 = 800;
+pub const LEADER_SCHEDULE_FETCH_SLOTS: u64 = 1_000;
+pub const LEADER_MIN_BUNDLES: u64 = 3;
+pub const LEADER_MIN_LAND_RATE: f64 = 0.1;
+pub const LEADER_STATS_WINDOW: u64 = 20;
+pub const SLOT_SUBSCRIPTION_RETRY_SECS: u64 = 5;
+
+// Raydium constants
+pub const RAYDIUM_SWAP_URL: &str = "https://transaction-v1.raydium.io";
+pub const RAYDIUM_CPMM_PROGRAM_ID: &str = "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C";
+pub const RAYDIUM_CLMM_PROGRAM_ID: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+pub const POOL_FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+
+// Geyser constants
+pub const GEYSER_RECONNECT_DELAY_SECS: u64 = 5;
+
+// SOL price constants
+pub const COINGECKO_SOL_PRICE_URL: &str =
+    "https://api.coingecko.com/api/v3/simple/price?ids=solana&vs_currencies=usd";
+pub const JUPITER_SOL_PRICE_URL: &str =
+    "https://api.jup.ag/price/v2?ids=So11111111111111111111111111111111111111112";
+pub const PYTH_SOL_PRICE_URL: &str = "https://hermes.pyth.network/v2/updates/price/latest\
+     ?ids[]=0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
+pub const PYTH_MAX_PRICE_AGE_SECS: u64 = 60;
+pub const SOL_PRICE_TTL_SECS: u64 = 30;
+
+// USDC peg constants
+pub const COINGECKO_USDC_PRICE_URL: &str =
+    "https://api.coingecko.com/api/v3/simple/price?ids=usd-coin&vs_currencies=usd";
+pub const PYTH_USDC_PRICE_URL: &str = "https://hermes.pyth.network/v2/updates/price/latest\
+     ?ids[]=0xeaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
+pub const DEFAULT_MAX_USDC_DEPEG_BPS: f64 = 50.0;
+pub const DEFAULT_USDC_PEG_CHECK_INTERVAL_SECS: u64 = 60;
+
+// HTTP constants
+pub const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_HTTP_CONNECT_TIMEOUT_SECS: u64 = 5;
+pub const DEFAULT_HTTP_MAX_RETRIES: u32 = 3;
+
+// Rate limit constants, in requests per window
+pub const RATE_LIMIT_WINDOW_SECS: u64 = 1;
+pub const RPC_RATE_LIMIT: usize = 10;
+pub const JUPITER_RATE_LIMIT: usize = 1;
+pub const ETHERFUSE_RATE_LIMIT: usize = 5;
+pub const JITO_RATE_LIMIT: usize = 1;
+pub const COINGECKO_RATE_LIMIT: usize = 1;
+pub const PYTH_RATE_LIMIT: usize = 5;
+pub const RAYDIUM_RATE_LIMIT: usize = 5;
+pub const RATE_LIMIT_BACKOFF_FACTOR: f64 = 0.5;
+pub const RATE_LIMIT_RECOVERY_FACTOR: f64 = 1.25;
+pub const RATE_LIMIT_RECOVERY_SECS: u64 = 30;
+pub const MIN_RATE_LIMIT_SHARE: f64 = 0.1;
+
+// Execution constants
+pub const TPU_CONFIRMATION_TIMEOUT_SECS: u64 = 30;
+pub const TPU_RESEND_INTERVAL_MS: u64 = 2_000;
+
+// Oracle constants
+pub const ORACLE_STALENESS_MARGIN_SLOTS: u64 = 10;
+pub const DEFAULT_MAX_ORACLE_DIVERGENCE_BPS: u64 = 100;
+pub const MAX_IMPLIED_PRICE_DEVIATION_BPS: u64 = 500;
+pub const ORACLE_UPDATE_ATTEMPTS: u32 = 2;
+pub const SWITCHBOARD_ON_DEMAND_PROGRAM_ID: &str = "SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv";
+
+// Transaction constants
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 400_000;
+pub const DEFAULT_COMPUTE_UNIT_PRICE: u64 = 100_000;
+pub const TRADE_TRANSACTIONS: u64 = 2;
+pub const COMPUTE_UNIT_LIMIT_MARGIN: f64 = 0.1;
+pub const APPENDED_IX_COMPUTE_UNITS: u32 = 5_000;
+pub const TRADE_MEMO_PREFIX: &str = "etherfuse-arb:";
+pub const TRADE_MEMO_COMPUTE_UNITS: u32 = 10_000;
+pub const DEFAULT_PRIORITY_FEE_PERCENTILE: f64 = 75.0;
+pub const MAX_PRIORITY_FEE_MICRO_LAMPORTS: u64 = 1_000_000;
+pub const MAX_LOOKUP_TABLE_EXTEND_ADDRESSES: usize = 20;
+pub const BLOCKHASH_REFRESH_INTERVAL_MS: u64 = 2_000;
+pub const MAX_CACHED_BLOCKHASH_AGE_SLOTS: u64 = 10;
+pub const CONFIRMATION_POLL_INTERVAL_MS: u64 = 500;
+pub const DEFAULT_LATENCY_BUDGET_MS: u64 = 15_000;
+
+// Risk constants
+pub const DEFAULT_DAILY_LOSS_LIMIT_USD: Decimal = Decimal::from_parts(100, 0, 0, false, 0);
+pub const DEFAULT_MAX_CONSECUTIVE_FAILED_BUNDLES: u32 = 10;
+pub const DEFAULT_KILL_SWITCH_FILE: &str = "KILL_SWITCH";
+pub const DEFAULT_MAX_POSITION_USD: Decimal = Decimal::from_parts(25_000, 0, 0, false, 0);
+pub const DEFAULT_MAX_NOTIONAL_PER_HOUR_USD: Decimal = Decimal::from_parts(20_000, 0, 0, false, 0);
+pub const DEFAULT_MAX_NOTIONAL_PER_DAY_USD: Decimal = Decimal::from_parts(100_000, 0, 0, false, 0);
+pub const DEFAULT_UNWIND_STOP_LOSS_USD: Decimal = Decimal::from_parts(50, 0, 0, false, 0);
+pub const DEFAULT_UNWIND_MAX_HOLDING_HOURS: f64 = 72.0;
+pub const DEFAULT_MAX_HEDGE_LOSS_USD: Decimal = Decimal::from_parts(25, 0, 0, false, 0);
+
+// Gas top-up constants
+pub const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+pub const DEFAULT_GAS_TOP_UP_MIN_SOL: f64 = 0.05;
+pub const DEFAULT_GAS_TOP_UP_USDC: Decimal = Decimal::from_parts(20, 0, 0, false, 0);
+pub const DEFAULT_GAS_TOP_UP_MAX_DAILY_USDC: Decimal = Decimal::from_parts(100, 0, 0, false, 0);
+pub const DEFAULT_GAS_TOP_UP_SLIPPAGE_BPS: u64 = 50;
+
+// Dust sweep constants
+pub const DEFAULT_DUST_MIN_USD: f64 = 1.0;
+pub const DEFAULT_DUST_MAX_USD: f64 = 50.0;
+pub const DEFAULT_DUST_SWEEP_INTERVAL_MINS: u64 = 60;
+pub const DEFAULT_DUST_SWEEP_SLIPPAGE_BPS: u64 = 100;
+
+// Mint worker constants
+pub const DEFAULT_TICK_INTERVAL_SECS: u64 = 300;
+pub const WORKER_RESTART_BASE_SECS: u64 = 1;
+pub const WORKER_RESTART_MAX_SECS: u64 = 300;
+pub const CRANKED_FEED_TTL_SECS: u64 = 60;
+
+// Profile constants
+pub const DEFAULT_PROFILE: &str = "normal";
+pub const DEFAULT_QUOTE_SLIPPAGE_BPS: u64 = 100;
+pub const CONSERVATIVE_MIN_PROFIT_USD: Decimal = Decimal::from_parts(5, 0, 0, false, 0);
+pub const CONSERVATIVE_QUOTE_SLIPPAGE_BPS: u64 = 50;
+pub const CONSERVATIVE_MAX_TIP_PROFIT_SHARE: f64 = 0.25;
+pub const CONSERVATIVE_MAX_CONCURRENT_MINTS: usize = 1;
+pub const AGGRESSIVE_MIN_PROFIT_USD: Decimal = Decimal::from_parts(25, 0, 0, false, 2);
+pub const AGGRESSIVE_QUOTE_SLIPPAGE_BPS: u64 = 200;
+pub const AGGRESSIVE_MAX_TIP_PROFIT_SHARE: f64 = 0.8;
+
+// Approval constants
+pub const DEFAULT_APPROVAL_QUEUE_FILE: &str = "pending_approvals.json";
+pub const DEFAULT_APPROVAL_EXPIRY_MINUTES: i64 = 60;
+pub const APPROVAL_QUEUE_LOCK_TIMEOUT_MS: u64 = 2_000;
+pub const APPROVAL_QUEUE_STALE_LOCK_SECS: u64 = 30;
+
+// Mint blacklist constants
+pub const MINT_FAILURE_THRESHOLD: u32 = 3;
+pub const MINT_BLACKLIST_BASE_SECS: u64 = 600;
+pub const MINT_BLACKLIST_MAX_SECS: u64 = 86_400;
+
+// Notification constants
+pub const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+pub const EXPLORER_TX_URL: &str = "https://solscan.io/tx/";
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Arb-Signature";
+pub const TELEGRAM_POLL_TIMEOUT_SECS: u64 = 5;
+pub const TELEGRAM_POLL_RETRY_SECS: u64 = 10;
+
+// Error aggregation constants
+pub const ERROR_SUMMARY_INTERVAL_SECS: u64 = 300;
+pub const ERROR_ESCALATION_THRESHOLD: u64 = 10;
+pub const ERROR_ESCALATION_FACTOR: u64 = 10;
+/// Error classes tracked at once, beyond which classes idle for a summary interval are evicted.
+pub const MAX_ERROR_CLASSES: usize = 1_000;
+/// Alphanumeric runs at least this long are ids (signatures, pubkeys, bundle ids, hashes).
+pub const ERROR_ID_MIN_LEN: usize = 32;
+
+// Profit verification constants
+pub const PROFIT_SHORTFALL_ALERT_SHARE: Decimal = Decimal::from_parts(5, 0, 0, false, 1);
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+// API constants
+pub const DEFAULT_API_BIND_ADDRESS: &str = "127.0.0.1:8080";
+pub const DEFAULT_API_TRADES_LIMIT: usize = 50;
+/// Shortest bearer token the API accepts, e.g. `openssl rand -hex 16`.
+pub const MIN_API_TOKEN_LEN: usize = 32;
+
+// Quote budget constants
+pub const QUOTE_BUDGET_OPPORTUNITY_DECAY: f64 = 0.8;
+pub const QUOTE_BUDGET_MIN_WEIGHT: f64 = 0.1;
+
+// Spread history constants
+pub const SPREAD_HISTORY_WINDOW_SECS: u64 = 3_600;
+pub const SPREAD_HISTORY_MIN_SAMPLES: usize = 5;
+pub const DEFAULT_ADAPTIVE_MIN_INTERVAL_SECS: u64 = 30;
+pub const DEFAULT_ADAPTIVE_MAX_INTERVAL_SECS: u64 = 1_800;
+pub const DEFAULT_VOLATILE_SPREAD_STDDEV_BPS: f64 = 10.0;
+pub const DEFAULT_QUIET_SPREAD_STDDEV_BPS: f64 = 1.0;
+
+// Trade chunking constants
+pub const DEFAULT_MAX_TRADE_CHUNKS: u32 = 4;
+pub const DEFAULT_CHUNK_MIN_PROFIT_SHARE: f64 = 0.5;
+pub const DEFAULT_CHUNK_LANDING_TIMEOUT_SECS: u64 = 60;
+
+// Route check constants
+pub const DEFAULT_MAX_SPLIT_DEVIATION_BPS: f64 = 50.0;
@@ -0,0 +1,280 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+};
+use spl_token_2022::ID as SPL_TOKEN_2022_PROGRAM_ID;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::info;
+
+use crate::{
+    approval::{self, PendingTrade},
+    constants::{DEFAULT_APPROVAL_QUEUE_FILE, USDC_MINT},
+    position_tracker::{Position, PositionTracker},
+    profile::{ActiveProfile, Profile},
+    risk::{self, GLOBAL_SCOPE},
+    strategy::MinProfit,
+    supervisor::{Supervisor, WorkerPanics},
+    trade_journal::{PnlGrouping, PnlRow, TradeJournal, TradeRecord},
+    wsol::wsol_balance,
+};
+
+/// What the bot is doing, as reported to operators.
+#[derive(Clone, Debug, Serialize)]
+pub struct Status {
+    pub started_at: String,
+    pub mints: Vec<String>,
+    /// Last completed tick of each mint's worker, by mint.
+    pub ticks: BTreeMap<String, u64>,
+    pub last_tick_at: Option<String>,
+    pub trades_since_start: usize,
+    pub min_profit_usd: Decimal,
+    /// Active profile, when profiles are switchable.
+    pub profile: Option<String>,
+    /// Reasons trading is halted or paused, by mint or `global`.
+    pub halts: BTreeMap<String, String>,
+    /// Panics of the supervised tasks, by task.
+    pub panics: BTreeMap<String, WorkerPanics>,
+}
+
+/// Wallet balances in UI amounts.
+#[derive(Clone, Debug, Serialize)]
+pub struct Balances {
+    pub sol: f64,
+    /// Wrapped SOL, which swaps can leave behind.
+    pub wsol: f64,
+    pub usdc: f64,
+    /// By stablebond mint.
+    pub stablebonds: BTreeMap<String, f64>,
+}
+
+/// Operations shared by every remote control interface, Telegram commands and the HTTP API, so
+/// that they all observe and steer the bot the same way. Pausing goes through the kill switch
+/// file the risk engine reads before every trade.
+#[derive(Clone)]
+pub struct ControlPlane {
+    pub rpc_client: Arc<RpcClient>,
+    pub wallet: Pubkey,
+    pub stablebond_mints: Vec<Pubkey>,
+    pub trade_journal: TradeJournal,
+    pub kill_switch_file: String,
+    pub position_tracker: Option<PositionTracker>,
+    pub min_profit: MinProfit,
+    pub approval_queue_file: String,
+    pub supervisor: Option<Supervisor>,
+    pub profile: Option<ActiveProfile>,
+    status: Arc<RwLock<Status>>,
+    /// Bumped by every operator-triggered tick, watched by each mint worker.
+    tick_requested: Arc<watch::Sender<u64>>,
+}
+
+impl ControlPlane {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        wallet: Pubkey,
+        stablebond_mints: Vec<Pubkey>,
+        trade_journal: TradeJournal,
+        kill_switch_file: String,
+    ) -> Self {
+        let status = Status {
+            started_at: chrono::Utc::now().to_rfc3339(),
+            mints: stablebond_mints
+                .iter()
+                .map(|mint| mint.to_string())
+                .collect(),
+            ticks: BTreeMap::new(),
+            last_tick_at: None,
+            trades_since_start: 0,
+            min_profit_usd: Decimal::ZERO,
+            profile: None,
+            halts: BTreeMap::new(),
+            panics: BTreeMap::new(),
+        };
+        Self {
+            rpc_client,
+            wallet,
+            stablebond_mints,
+            trade_journal,
+            kill_switch_file,
+            position_tracker: None,
+            min_profit: MinProfit::default(),
+            approval_queue_file: DEFAULT_APPROVAL_QUEUE_FILE.to_string(),
+            supervisor: None,
+            profile: None,
+            status: Arc::new(RwLock::new(status)),
+            tick_requested: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    pub fn with_position_tracker(mut self, position_tracker: PositionTracker) -> Self {
+        self.position_tracker = Some(position_tracker);
+        self
+    }
+
+    /// The minimum profit the strategies trade on, as adjusted by operators.
+    pub fn with_min_profit(mut self, min_profit: MinProfit) -> Self {
+        self.min_profit = min_profit;
+        self
+    }
+
+    pub fn with_approval_queue_file(mut self, approval_queue_file: String) -> Self {
+        self.approval_queue_file = approval_queue_file;
+        self
+    }
+
+    /// The supervisor whose panics are reported in the status.
+    pub fn with_supervisor(mut self, supervisor: Supervisor) -> Self {
+        self.supervisor = Some(supervisor);
+        self
+    }
+
+    /// The profile the bot trades with, switchable by operators.
+    pub fn with_profile(mut self, profile: ActiveProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Called by the worker of `mint` at the end of each of its ticks.
+    pub fn record_tick(&self, mint: &Pubkey, tick: u64, trades: usize) {
+        let mut status = self.status.write().unwrap();
+        status.ticks.insert(mint.to_string(), tick);
+        status.last_tick_at = Some(chrono::Utc::now().to_rfc3339());
+        status.trades_since_start += trades;
+    }
+
+    pub fn status(&self) -> Status {
+        let mut status = self.status.read().unwrap().clone();
+        status.halts = risk::halts(&self.kill_switch_file);
+        status.min_profit_usd = self.min_profit.get();
+        status.profile = self.profile.as_ref().map(|profile| profile.name());
+        if let Some(supervisor) = &self.supervisor {
+            status.panics = supervisor.panics();
+        }
+        status
+    }
+
+    /// Sets the expected profit below which the strategies don't trade, from the next tick.
+    pub fn set_min_profit(&self, usd: Decimal) -> Result<()> {
+        if usd < Decimal::ZERO {
+            return Err(anyhow!(
+                "Minimum profit must be a non-negative amount, got {}",
+                usd
+            ));
+        }
+        info!("Minimum profit set to ${:.2}", usd);
+        self.min_profit.set(usd);
+        Ok(())
+    }
+
+    /// Switches to the profile `name`, whose minimum profit replaces the current one. Its
+    /// slippage, tip share and concurrency apply from the next quote, tip and tick.
+    pub fn set_profile(&self, name: &str) -> Result<Profile> {
+        let profile = self
+            .profile
+            .as_ref()
+            .ok_or_else(|| anyhow!("Profiles are not switchable"))?
+            .set(name)?;
+        self.min_profit.set(profile.min_profit_usd);
+        Ok(profile)
+    }
+
+    /// Stablebond positions by mint, empty when they aren't tracked.
+    pub fn positions(&self) -> BTreeMap<String, Position> {
+        self.position_tracker
+            .as_ref()
+            .map(|position_tracker| position_tracker.positions())
+            .unwrap_or_default()
+    }
+
+    /// Latest trades first, of `mint` or every mint.
+    pub fn recent_trades(&self, limit: usize, mint: Option<&str>) -> Result<Vec<TradeRecord>> {
+        self.trade_journal.recent_trades(limit, mint)
+    }
+
+    pub fn pending_approvals(&self) -> Result<BTreeMap<String, PendingTrade>> {
+        approval::pending_trades(&self.approval_queue_file)
+    }
+
+    pub async fn approve(&self, trade_id: &str) -> Result<()> {
+        approval::approve(&self.approval_queue_file, trade_id).await
+    }
+
+    /// Cuts every mint worker's sleep short, so their next ticks start right away.
+    pub fn trigger_tick(&self) {
+        self.tick_requested.send_modify(|requests| *requests += 1);
+    }
+
+    /// Receiver for a mint worker to wait for its ticks on.
+    pub fn subscribe_ticks(&self) -> watch::Receiver<u64> {
+        self.tick_requested.subscribe()
+    }
+
+    /// Called by a mint worker between ticks. Returns after `interval`, or earlier when a tick
+    /// is triggered, including while the worker was busy with its last one.
+    pub async fn wait_for_tick(&self, ticks: &mut watch::Receiver<u64>, interval: Duration) {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            Ok(()) = ticks.changed() => info!("Tick triggered by an operator"),
+        }
+    }
+
+    /// Pauses trading `mint`, or every mint when `None`, until it is resumed.
+    pub fn pause(&self, mint: Option<&str>) -> Result<()> {
+        let scope = match mint {
+            Some(mint) => Pubkey::from_str(mint)?.to_string(),
+            None => GLOBAL_SCOPE.to_string(),
+        };
+        risk::pause(&self.kill_switch_file, &scope, "Paused by an operator")
+    }
+
+    /// Resumes trading `mint`, or every mint when `None`, whether paused or halted.
+    pub fn resume(&self, mint: Option<&str>) -> Result<()> {
+        risk::resume(&self.kill_switch_file, mint)
+    }
+
+    pub async fn balances(&self) -> Result<Balances> {
+        let lamports = self.rpc_client.get_balance(&self.wallet).await?;
+        let usdc_account =
+            get_associated_token_address(&self.wallet, &Pubkey::from_str(USDC_MINT)?);
+        let mut stablebonds = BTreeMap::new();
+        for mint in &self.stablebond_mints {
+            let account = get_associated_token_address_with_program_id(
+                &self.wallet,
+                mint,
+                &SPL_TOKEN_2022_PROGRAM_ID,
+            );
+            stablebonds.insert(mint.to_string(), self.token_balance(&account).await?);
+        }
+        let wsol_lamports = wsol_balance(&self.rpc_client, &self.wallet).await?;
+        Ok(Balances {
+            sol: lamports as f64 / LAMPORTS_PER_SOL as f64,
+            wsol: wsol_lamports.unwrap_or(0) as f64 / LAMPORTS_PER_SOL as f64,
+            usdc: self.token_balance(&usdc_account).await?,
+            stablebonds,
+        })
+    }
+
+    /// Profit per mint of the trades made since `since` (a date or RFC 3339 timestamp), or
+    /// since the start of the UTC day when `None`.
+    pub fn pnl(&self, since: Option<&str>) -> Result<Vec<PnlRow>> {
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        self.trade_journal
+            .pnl(PnlGrouping::Mint, Some(since.unwrap_or(&today)))
+    }
+
+    async fn token_balance(&self, account: &Pubkey) -> Result<f64> {
+        Ok(self
+            .rpc_client
+            .get_token_account(account)
+            .await?
+            .and_then(|account| account.token_amount.ui_amount)
+            .unwrap_or(0.0))
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use crate::{
+    constants::{
+        DEFAULT_COMPUTE_UNIT_LIMIT, LAMPORTS_PER_SIGNATURE, SOL_DECIMALS, TRADE_TRANSACTIONS,
+    },
+    math::{self, FeeBreakdown, TokenAmountExt},
+};
+
+/// What landing a trade costs in SOL, the Jito tip plus the base and priority fees of its
+/// transactions, valued in USD and in the bond's payment currency.
+#[derive(Clone, Copy, Debug)]
+pub struct CostModel {
+    /// Price of SOL in USD.
+    pub sol_price: Decimal,
+    pub tip_lamports: u64,
+    /// Base fees of all the trade's transactions, each signed by our wallet only.
+    pub base_fee_lamports: u64,
+    /// Priority fees of all the trade's transactions.
+    pub priority_fee_lamports: u64,
+    /// Units of the bond's payment currency per USD, when known.
+    pub exchange_rate: Option<Decimal>,
+}
+
+impl CostModel {
+    pub fn new(sol_price: f64, tip_lamports: u64) -> Result<Self> {
+        Ok(Self {
+            sol_price: math::to_decimal(sol_price)?,
+            tip_lamports,
+            base_fee_lamports: TRADE_TRANSACTIONS * LAMPORTS_PER_SIGNATURE,
+            priority_fee_lamports: 0,
+            exchange_rate: None,
+        })
+    }
+
+    /// Pays `compute_unit_price` micro-lamports per compute unit on every transaction, which
+    /// use up to `DEFAULT_COMPUTE_UNIT_LIMIT` each.
+    pub fn with_compute_unit_price(mut self, compute_unit_price: u64) -> Self {
+        let micro_lamports = compute_unit_price as u128 * DEFAULT_COMPUTE_UNIT_LIMIT as u128;
+        let lamports = micro_lamports.div_ceil(1_000_000);
+        self.priority_fee_lamports =
+            u64::try_from(lamports * TRADE_TRANSACTIONS as u128).unwrap_or(u64::MAX);
+        self
+    }
+
+    pub fn with_exchange_rate(mut self, exchange_rate: f64) -> Result<Self> {
+        if exchange_rate <= 0.0 {
+            return Err(anyhow!("Invalid exchange rate {}", exchange_rate));
+        }
+        self.exchange_rate = Some(math::to_decimal(exchange_rate)?);
+        Ok(self)
+    }
+
+    /// Value of `lamports` in USD.
+    pub fn to_usd(&self, lamports: u64) -> Decimal {
+        lamports.to_ui_decimal(SOL_DECIMALS) * self.sol_price
+    }
+
+    /// Value of `lamports` in the bond's payment currency, when its exchange rate is known.
+    pub fn to_payment_currency(&self, lamports: u64) -> Option<Decimal> {
+        Some(self.to_usd(lamports) * self.exchange_rate?)
+    }
+
+    pub fn total_lamports(&self) -> u64 {
+        self.tip_lamports
+            .saturating_add(self.base_fee_lamports)
+            .saturating_add(self.priority_fee_lamports)
+    }
+
+    /// Tip and transaction fees in USD, for `math::net_profit`.
+    pub fn fees(&self) -> FeeBreakdown {
+        FeeBreakdown {
+            tip_usd: self.to_usd(self.tip_lamports),
+            gas_usd: self.to_usd(
+                self.base_fee_lamports
+                    .saturating_add(self.priority_fee_lamports),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_tip_and_fees_at_the_sol_price() {
+        let cost_model = CostModel::new(150.0, 1_000_000)
+            .unwrap()
+            .with_compute_unit_price(100_000)
+            .with_exchange_rate(20.0)
+            .unwrap();
+
+        // 40,000 lamports of priority fee and 5,000 of base fee per transaction.
+        assert_eq!(cost_model.priority_fee_lamports, 80_000);
+        assert_eq!(cost_model.total_lamports(), 1_090_000);
+        let fees = cost_model.fees();
+        assert_eq!(fees.tip_usd, Decimal::new(15, 2));
+        assert_eq!(fees.gas_usd, Decimal::new(135, 4));
+        assert_eq!(
+            cost_model.to_payment_currency(cost_model.total_lamports()),
+            Some(Decimal::new(3270, 3))
+        );
+    }
+
+    #[test]
+    fn rounds_priority_fees_up_to_the_lamport() {
+        let cost_model = CostModel::new(150.0, 0).unwrap().with_compute_unit_price(1);
+
+        assert_eq!(cost_model.priority_fee_lamports, 2);
+        assert_eq!(cost_model.to_payment_currency(0), None);
+    }
+}
@@ -0,0 +1,206 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use tracing::{error, field, info};
+
+use crate::{
+    error::{ArbError, RiskError},
+    strategy::{StrategyResult, TradeBoundsError},
+};
+
+/// Why no trade happened for a mint, or why a strategy found nothing to trade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    MarketDataUnavailable,
+    OracleStale,
+    OracleDivergence,
+    NoLiquidity,
+    InsufficientBalance,
+    QuoteFailed,
+    SpreadBelowThreshold,
+    ProfitBelowMinimum,
+    NoOpportunity,
+    Duplicate,
+    /// The mint kept failing and is cooling off.
+    Blacklisted,
+    /// A leg's transaction couldn't be built.
+    BuildFailed,
+    PreflightFailed,
+    /// The simulated redemption makes less than the minimum profit.
+    SimulatedProfitBelowMinimum,
+    /// The trade's amounts or price broke an invariant, see `TradeBoundsError`.
+    SanityCheckFailed,
+    LatencyBudgetExceeded,
+    RiskLimit,
+    /// The next chunk of a chunked trade makes too little of the first chunk's profit.
+    ChunkDegraded,
+    /// The trade is above the approval threshold and waits for a human to approve it.
+    AwaitingApproval,
+    /// The mint's position was unwound instead.
+    Unwound,
+    ExecutionFailed,
+    /// Anything unexpected, see the detail.
+    Error,
+}
+
+impl SkipReason {
+    /// Reason carried by a `Skip` error, a broken trade invariant or a refusal of the risk
+    /// engine, `Error` for any other error.
+    pub fn of(error: &anyhow::Error) -> Self {
+        if error.is::<TradeBoundsError>() {
+            return SkipReason::SanityCheckFailed;
+        }
+        if error.is::<RiskError>() || matches!(error.downcast_ref(), Some(ArbError::Risk(_))) {
+            return SkipReason::RiskLimit;
+        }
+        error
+            .downcast_ref::<Skip>()
+            .map_or(SkipReason::Error, |skip| skip.reason)
+    }
+}
+
+/// Error returned when there is nothing to trade for an expected reason, as opposed to
+/// something going wrong.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct Skip {
+    pub reason: SkipReason,
+    pub message: String,
+}
+
+pub fn skip(reason: SkipReason, message: impl Into<String>) -> anyhow::Error {
+    Skip {
+        reason,
+        message: message.into(),
+    }
+    .into()
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StrategyDecision {
+    pub strategy: &'static str,
+    pub profit_usd: Option<Decimal>,
+    pub reason: Option<SkipReason>,
+    pub detail: Option<String>,
+}
+
+/// What the bot decided for one mint on one tick, and why.
+#[derive(Clone, Debug, Serialize)]
+pub struct Decision {
+    pub timestamp: String,
+    pub tick: u64,
+    pub mint: String,
+    pub traded: bool,
+    /// Journal id of the trade, once the most profitable strategy's trade was journaled.
+    pub journal_id: Option<i64>,
+    pub backend: Option<&'static str>,
+    pub expected_profit_usd: Option<Decimal>,
+    pub reason: Option<SkipReason>,
+    pub detail: Option<String>,
+    pub strategies: Vec<StrategyDecision>,
+}
+
+impl Decision {
+    pub fn new(tick: u64, mint: Pubkey) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            tick,
+            mint: mint.to_string(),
+            traded: false,
+            journal_id: None,
+            backend: None,
+            expected_profit_usd: None,
+            reason: None,
+            detail: None,
+            strategies: Vec::new(),
+        }
+    }
+
+    pub fn add_strategy(
+        &mut self,
+        strategy: &'static str,
+        result: &anyhow::Result<StrategyResult>,
+    ) {
+        self.strategies.push(match result {
+            Ok(result) => StrategyDecision {
+                strategy,
+                profit_usd: Some(result.profit),
+                reason: None,
+                detail: result.preflight_skipped.clone(),
+            },
+            Err(e) => StrategyDecision {
+                strategy,
+                profit_usd: None,
+                reason: Some(SkipReason::of(e)),
+                detail: Some(e.to_string()),
+            },
+        });
+    }
+
+    /// Whether any strategy found a trade worth making, whether or not it was made.
+    pub fn found_opportunity(&self) -> bool {
+        self.strategies
+            .iter()
+            .any(|strategy| strategy.profit_usd.is_some())
+    }
+
+    pub fn skip(&mut self, reason: SkipReason, detail: impl ToString) {
+        self.reason = Some(reason);
+        self.detail = Some(detail.to_string());
+    }
+
+    pub fn trade(&mut self, backend: &'static str, expected_profit_usd: Decimal) {
+        self.traded = true;
+        self.reason = None;
+        self.detail = None;
+        self.backend = Some(backend);
+        self.expected_profit_usd = Some(expected_profit_usd);
+    }
+}
+
+/// Logs every decision, and appends it as a JSON line to the decision log file when one is
+/// configured, to answer "why didn't the bot trade that spread" after the fact.
+pub struct DecisionLog {
+    file: Option<Mutex<File>>,
+}
+
+impl DecisionLog {
+    pub fn new(path: Option<&str>) -> anyhow::Result<Self> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        };
+        Ok(Self { file })
+    }
+
+    pub fn record(&self, decision: &Decision) {
+        if decision.traded {
+            info!(
+                backend = decision.backend,
+                expected_profit_usd = decision.expected_profit_usd.map(field::display),
+                "Traded"
+            );
+        } else {
+            info!(
+                reason = ?decision.reason,
+                detail = decision.detail.as_deref(),
+                "No trade"
+            );
+        }
+        let Some(file) = &self.file else {
+            return;
+        };
+        let result = serde_json::to_string(decision)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(writeln!(file.lock().unwrap(), "{}", line)?));
+        if let Err(e) = result {
+            error!("Error writing decision log: {:?}", e);
+        }
+    }
+}
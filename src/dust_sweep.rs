@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use solana_account_decoder::{parse_token::UiTokenAccount, UiAccountData};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::TokenAccountsFilter};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use spl_token_2022::ID as SPL_TOKEN_2022_PROGRAM_ID;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::{
+    constants::{
+        DEFAULT_DUST_MAX_USD, DEFAULT_DUST_MIN_USD, DEFAULT_DUST_SWEEP_INTERVAL_MINS,
+        DEFAULT_DUST_SWEEP_SLIPPAGE_BPS, SOL_MINT, USDC_DECIMALS, USDC_MINT,
+    },
+    jupiter::{JupiterClient, JupiterQuoteArgs},
+    math::TokenAmountExt,
+    transaction::{send_txs_sequentially, SendConfig},
+};
+
+/// `[dust_sweep]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DustSweepConfig {
+    pub enabled: bool,
+    /// Balances worth less than this are left alone, as selling them costs more in fees.
+    pub min_usd: f64,
+    /// Balances worth more than this are inventory, not dust, and are left to the unwinder.
+    pub max_usd: f64,
+    pub interval_mins: u64,
+    pub slippage_bps: u64,
+}
+
+impl Default for DustSweepConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_usd: DEFAULT_DUST_MIN_USD,
+            max_usd: DEFAULT_DUST_MAX_USD,
+            interval_mins: DEFAULT_DUST_SWEEP_INTERVAL_MINS,
+            slippage_bps: DEFAULT_DUST_SWEEP_SLIPPAGE_BPS,
+        }
+    }
+}
+
+/// A dust balance sold for USDC.
+#[derive(Clone, Debug)]
+pub struct Sweep {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub usdc_amount: f64,
+    pub signatures: Vec<Signature>,
+}
+
+/// Sells the small balances that failed or partial trades leave behind, stablebonds and the
+/// intermediate tokens of multi-hop routes, back to USDC on Jupiter.
+#[derive(Clone)]
+pub struct DustSweeper {
+    pub config: DustSweepConfig,
+    pub rpc_client: Arc<RpcClient>,
+    pub jupiter_client: JupiterClient,
+    pub wallet: Pubkey,
+    pub send_config: SendConfig,
+}
+
+impl DustSweeper {
+    pub fn new(
+        config: DustSweepConfig,
+        rpc_client: Arc<RpcClient>,
+        jupiter_client: JupiterClient,
+        wallet: Pubkey,
+    ) -> Self {
+        Self {
+            config,
+            rpc_client,
+            jupiter_client,
+            wallet,
+            send_config: SendConfig::default(),
+        }
+    }
+
+    pub fn with_send_config(mut self, send_config: SendConfig) -> Self {
+        self.send_config = send_config;
+        self
+    }
+
+    /// Whether a balance that sells for `usdc_amount` is dust worth sweeping.
+    pub fn is_dust(&self, usdc_amount: f64) -> bool {
+        usdc_amount >= self.config.min_usd && usdc_amount <= self.config.max_usd
+    }
+
+    /// Non-zero balances of the wallet's token accounts, by mint, except USDC and wrapped SOL.
+    pub async fn balances(&self) -> Result<Vec<(Pubkey, u64)>> {
+        let usdc_mint = Pubkey::from_str(USDC_MINT)?;
+        let sol_mint = Pubkey::from_str(SOL_MINT)?;
+        let mut balances = Vec::new();
+        for program_id in [spl_token::id(), SPL_TOKEN_2022_PROGRAM_ID] {
+            let accounts = self
+                .rpc_client
+                .get_token_accounts_by_owner(
+                    &self.wallet,
+                    TokenAccountsFilter::ProgramId(program_id),
+                )
+                .await?;
+            for keyed_account in accounts {
+                let (mint, amount) = token_balance(&keyed_account.account.data)?;
+                if amount > 0 && mint != usdc_mint && mint != sol_mint {
+                    balances.push((mint, amount));
+                }
+            }
+        }
+        Ok(balances)
+    }
+
+    /// Sells every dust balance for USDC. Balances without a route are skipped, a failed swap
+    /// aborts the sweep until the next one.
+    pub async fn sweep(&mut self) -> Result<Vec<Sweep>> {
+        let usdc_mint = Pubkey::from_str(USDC_MINT)?;
+        let mut sweeps = Vec::new();
+        for (mint, amount) in self.balances().await? {
+            let quote = match self
+                .jupiter_client
+                .get_jupiter_quote(JupiterQuoteArgs {
+                    input_mint: mint,
+                    output_mint: usdc_mint,
+                    amount,
+                    slippage_bps: Some(self.config.slippage_bps),
+                    only_direct_routes: false,
+                })
+                .await
+            {
+                Ok(quote) => quote,
+                Err(e) => {
+                    debug!(%mint, amount, "No route to sweep: {}", e);
+                    continue;
+                }
+            };
+            let usdc_amount = quote.out_amount.to_ui_amount(USDC_DECIMALS);
+            if !self.is_dust(usdc_amount) {
+                continue;
+            }
+            let tx = self.jupiter_client.jupiter_swap_tx(quote).await?;
+            let signatures = send_txs_sequentially(&self.rpc_client, &[tx], &self.send_config)
+                .await
+                .map_err(|e| anyhow!("Error sweeping {} of {}: {}", amount, mint, e))?;
+            info!(%mint, amount, usdc_amount, "Swept dust");
+            sweeps.push(Sweep {
+                mint,
+                amount,
+                usdc_amount,
+                signatures,
+            });
+        }
+        Ok(sweeps)
+    }
+}
+
+/// Mint and raw amount of a token account fetched as parsed JSON.
+fn token_balance(data: &UiAccountData) -> Result<(Pubkey, u64)> {
+    let UiAccountData::Json(parsed) = data else {
+        return Err(anyhow!("Token account not returned as parsed JSON"));
+    };
+    let account: UiTokenAccount = serde_json::from_value(
+        parsed
+            .parsed
+            .get("info")
+            .cloned()
+            .ok_or_else(|| anyhow!("Parsed token account without info"))?,
+    )?;
+    Ok((
+        Pubkey::from_str(&account.mint)?,
+        account.token_amount.amount.parse()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_account_decoder::parse_account_data::ParsedAccount;
+
+    #[test]
+    fn sweeps_only_balances_between_the_thresholds() {
+        let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+        let jupiter_client = JupiterClient::new(
+            rpc_client.clone(),
+            "http://localhost".to_string(),
+            "keypair.json".to_string(),
+            crate::rate_limiter::RateLimiter::new(1, 1),
+        );
+        let sweeper = DustSweeper::new(
+            DustSweepConfig {
+                enabled: true,
+                min_usd: 1.0,
+                max_usd: 50.0,
+                ..Default::default()
+            },
+            rpc_client,
+            jupiter_client,
+            Pubkey::new_unique(),
+        );
+
+        assert!(!sweeper.is_dust(0.5));
+        assert!(sweeper.is_dust(12.0));
+        assert!(!sweeper.is_dust(500.0));
+    }
+
+    #[test]
+    fn reads_the_mint_and_amount_of_parsed_token_accounts() {
+        let mint = Pubkey::new_unique();
+        let data = UiAccountData::Json(ParsedAccount {
+            program: "spl-token-2022".to_string(),
+            parsed: serde_json::json!({
+                "type": "account",
+                "info": {
+                    "isNative": false,
+                    "mint": mint.to_string(),
+                    "owner": Pubkey::new_unique().to_string(),
+                    "state": "initialized",
+                    "tokenAmount": {
+                        "amount": "1234",
+                        "decimals": 6,
+                        "uiAmount": 0.001234,
+                        "uiAmountString": "0.001234"
+                    }
+                }
+            }),
+            space: 165,
+        });
+
+        assert_eq!(token_balance(&data).unwrap(), (mint, 1234));
+    }
+}
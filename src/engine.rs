@@ -0,0 +1,1255 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{arg, ValueEnum};
+use futures::StreamExt;
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use rust_decimal::Decimal;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_rpc_client::{http_sender::HttpSender, rpc_client::RpcClientConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, signature::read_keypair_file, signer::Signer,
+};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tracing::{error, info};
+
+use crate::{
+    account_cache::AccountCache,
+    api,
+    approval::ApprovalGate,
+    balance_reservations::BalanceReservations,
+    bundle_dedup::BundleDedup,
+    bundle_stats::{region_from_url, BundleStats},
+    config::{
+        build_notifications, check_config, parse_adaptive_interval_config, parse_api_config,
+        parse_approval_config, parse_chunking_config, parse_dust_sweep_config,
+        parse_gas_top_up_config, parse_http_config, parse_leg_failure_config, parse_pool_config,
+        parse_priority_fee_config, parse_profile_config, parse_rate_limit_config,
+        parse_risk_config, parse_route_check_config, parse_tick_interval_config, parse_toml_config,
+        parse_unwind_config, parse_usdc_peg_config, parse_webhook_config, CONFIG_FILE,
+    },
+    constants::{
+        BALANCE_RESERVATION_TTL_SECS, DEDUP_SIZE_BUCKET_USDC_AMOUNT, DEFAULT_LATENCY_BUDGET_MS,
+        DEFAULT_MAX_ORACLE_DIVERGENCE_BPS, DEFAULT_MAX_SUBMISSION_DEFERRAL_MS,
+        DEFAULT_MIN_TIP_LAMPORTS, DEFAULT_PROFILE, DEFAULT_TICK_INTERVAL_SECS, EXPLORER_TX_URL,
+        MINT_BLACKLIST_BASE_SECS, MINT_BLACKLIST_MAX_SECS, MINT_FAILURE_THRESHOLD,
+        RAYDIUM_SWAP_URL, SOL_DECIMALS, USDC_MINT,
+    },
+    control::ControlPlane,
+    decision_log::DecisionLog,
+    dust_sweep::DustSweeper,
+    error_aggregator::report_error,
+    etherfuse::EtherfuseClient,
+    event_sink::{Event, EventSink},
+    execution::{ExecutionBackendEnum, ExecutionPolicy, JitoBackend, RpcBackend, TpuBackend},
+    gas_top_up::GasTopUp,
+    geyser::GeyserSubscriber,
+    http::build_http_client,
+    jito::Tip,
+    jito::{BundleStatusEnum, JitoClient},
+    jito_grpc::{BundleResults, JitoGrpcClient},
+    jupiter::JupiterClient,
+    leader_schedule::LeaderTracker,
+    leg_failure::LegFailureHandler,
+    lookup_table::LookupTableManager,
+    math::UiAmountExt,
+    mint_blacklist::MintBlacklist,
+    notifier::Severity,
+    pool_state::PoolStateReader,
+    position_tracker::PositionTracker,
+    priority_fee::PriorityFeeEstimator,
+    profile::{profiles, ActiveProfile},
+    profit_verification::ProfitVerifier,
+    quote_budget::QuoteBudget,
+    rate_limiter::{Endpoint, RateLimitedSender, RateLimiterRegistry},
+    raydium::RaydiumClient,
+    reconciliation::Reconciler,
+    research_export::ResearchExporter,
+    risk::RiskEngine,
+    slippage_stats::SlippageStats,
+    sol_price::{SolPriceClient, SolPriceOracle, SolPriceSource},
+    spread_history::SpreadHistory,
+    strategy::{BuyOnEtherfuseSellOnJupiter, BuyOnJupiterSellOnEtherfuse, MinProfit},
+    supervisor::Supervisor,
+    swap_router::{SwapRouter, SwapVenueEnum},
+    switchboard::SwitchboardClient,
+    tip_budget::TipBudget,
+    trade_chunking::{Landings, TradeChunker},
+    trade_journal::{PnlGrouping, TradeJournal},
+    transaction::{SendConfig, TransactionCache},
+    unwind::Unwinder,
+    usdc_peg::{UsdcPegMonitor, UsdcPriceClient},
+    worker::{CrankedFeeds, MintWorker},
+    wsol::spawn_wsol_unwrapper,
+};
+
+/// Options of the bot, the `etherfuse-arb` binary's flags. The rest of its configuration is read
+/// from `tokens.toml`.
+#[derive(clap::Args)]
+pub struct EngineArgs {
+    #[arg(
+        long,
+        value_name = "NETWORK_URL",
+        help = "Network address of your RPC provider",
+        default_value = "https://api.mainnet-beta.solana.com",
+        global = true
+    )]
+    pub rpc: Option<String>,
+
+    #[clap(
+        global = true,
+        short = 'C',
+        long = "config",
+        id = "PATH",
+        help = "Filepath to config file."
+    )]
+    pub config_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "KEYPAIR_FILEPATH",
+        help = "Filepath to signer keypair.",
+        global = true
+    )]
+    pub keypair: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "ETHERFUSE_API_URL",
+        help = "URL to the Etherfuse API",
+        default_value = "https://api.etherfuse.com",
+        global = true
+    )]
+    pub etherfuse_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "JUPITER_QUOTE_API_URL",
+        help = "URL to the Jupiter Quote API",
+        default_value = "https://quote-api.jup.ag/v6",
+        global = true
+    )]
+    pub jupiter_quote_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "RAYDIUM_TRADE_API_URL",
+        help = "URL to the Raydium Trade API",
+        default_value = RAYDIUM_SWAP_URL,
+        global = true
+    )]
+    pub raydium_swap_url: String,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "VENUE",
+        value_delimiter = ',',
+        help = "Venues the stablebonds are bought and sold on besides Etherfuse. Every trade is quoted on all of them and routed to the best price",
+        default_value = "jupiter",
+        global = true
+    )]
+    pub swap_venues: Vec<SwapVenueArg>,
+
+    #[arg(
+        long,
+        value_name = "GEYSER_URL",
+        help = "Yellowstone gRPC (Geyser) endpoint to stream the pool, liquidity and oracle accounts from. Liquidity refills then trigger a tick",
+        global = true
+    )]
+    pub geyser_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        help = "x-token of the Geyser endpoint",
+        env = "GEYSER_X_TOKEN",
+        global = true
+    )]
+    pub geyser_x_token: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "JITO_BUNDLES_URL",
+        help = "URL to the Jito Bundles API",
+        default_value = "https://slc.mainnet.block-engine.jito.wtf:443/api/v1/bundles",
+        global = true
+    )]
+    pub jito_bundles_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "JITO_WSS_URL",
+        help = "URL to the Jito WSS API",
+        default_value = "ws://bundles-api-rest.jito.wtf/api/v1/bundles/tip_stream",
+        global = true
+    )]
+    pub jito_wss_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "JITO_GRPC_URL",
+        help = "URL to the Jito block engine gRPC searcher API, which bundles are sent through and their results streamed from. Bundles are sent and polled through JSON-RPC when unset",
+        global = true
+    )]
+    pub jito_grpc_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "JITO_AUTH_KEYPAIR_FILEPATH",
+        help = "Filepath to the keypair used to authenticate with the Jito gRPC searcher API. Defaults to the signer keypair",
+        global = true
+    )]
+    pub jito_auth_keypair: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Maximum SOL to spend on Jito tips per day, including tips of bundles that fail to land",
+        default_value = "0.5",
+        global = true
+    )]
+    pub daily_tip_budget: Decimal,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        help = "Tip paid when no landed tips are known, and the tip escalation starts from at least",
+        default_value_t = DEFAULT_MIN_TIP_LAMPORTS,
+        global = true
+    )]
+    pub min_tip_lamports: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Window during which an identical opportunity is not submitted again",
+        default_value = "60",
+        global = true
+    )]
+    pub dedup_window_secs: u64,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Seconds between the ticks of each mint, unless set for the mint in [tick_intervals]",
+        default_value_t = DEFAULT_TICK_INTERVAL_SECS,
+        global = true
+    )]
+    pub tick_interval_secs: u64,
+
+    #[arg(
+        long,
+        value_name = "PROFILE",
+        help = "Profile to start with: conservative, normal, aggressive or one from [profiles]",
+        default_value = DEFAULT_PROFILE,
+        global = true
+    )]
+    pub profile: String,
+
+    #[arg(
+        long,
+        value_name = "CALLS",
+        help = "Jupiter quotes per tick shared by the mints, by how often each had opportunities",
+        global = true
+    )]
+    pub quote_budget: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "STATS_FILEPATH",
+        help = "Filepath where bundle land-rate statistics are recorded",
+        default_value = "bundle_stats.json",
+        global = true
+    )]
+    pub stats_file: String,
+
+    #[arg(
+        long,
+        value_name = "SLIPPAGE_STATS_FILEPATH",
+        help = "Filepath where the quoted versus realized output of each trade leg is recorded",
+        default_value = "slippage_stats.json",
+        global = true
+    )]
+    pub slippage_stats_file: String,
+
+    #[arg(
+        long,
+        value_name = "POSITIONS_FILEPATH",
+        help = "Filepath where the stablebond position held in each mint is recorded",
+        default_value = "positions.json",
+        global = true
+    )]
+    pub positions_file: String,
+
+    #[arg(
+        long,
+        value_name = "DECISION_LOG_FILEPATH",
+        help = "Append a JSON line per mint and tick recording whether the bot traded and why not",
+        global = true
+    )]
+    pub decision_log: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DIRECTORY",
+        help = "Append every Jupiter quote and per-tick spread to daily CSV files in this directory",
+        global = true
+    )]
+    pub export_dir: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "JOURNAL_FILEPATH",
+        help = "SQLite database where every executed trade and its outcome is recorded",
+        default_value = "trades.db",
+        global = true
+    )]
+    pub journal: String,
+
+    #[arg(
+        long,
+        value_name = "BLOCKS",
+        help = "Re-sign our transactions whose blockhash is older than this many blocks before submitting a bundle. Ages are in block heights, which skipped slots don't advance, and blockhashes expire after 150",
+        default_value = "60",
+        global = true
+    )]
+    pub max_blockhash_age: u64,
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "Skip trades whose winning quote is older than this by the time they are submitted",
+        default_value_t = DEFAULT_LATENCY_BUDGET_MS,
+        global = true
+    )]
+    pub latency_budget_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "BPS",
+        help = "Skip mints whose on-chain oracle price diverges from the Etherfuse exchange rate by more than this",
+        default_value_t = DEFAULT_MAX_ORACLE_DIVERGENCE_BPS,
+        global = true
+    )]
+    pub max_oracle_divergence_bps: u64,
+
+    #[arg(
+        long,
+        value_name = "Z",
+        help = "Only search spreads at least this many standard deviations above their last hour's mean",
+        global = true
+    )]
+    pub entry_z_score: Option<f64>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SOURCE",
+        value_delimiter = ',',
+        help = "APIs the SOL price is the median of. Mints are skipped when none of them answer",
+        default_value = "coingecko,jupiter,pyth",
+        global = true
+    )]
+    pub sol_price_sources: Vec<SolPriceSource>,
+
+    #[arg(
+        long,
+        help = "Send the transactions sequentially through the RPC, paying the estimated priority fee, if the Jito bundle cannot be submitted",
+        global = true
+    )]
+    pub rpc_fallback: bool,
+
+    #[arg(
+        long,
+        value_name = "COMPUTE_UNITS",
+        help = "Compute unit limit of the Switchboard oracle update transaction. Sized by simulation when unset",
+        global = true
+    )]
+    pub oracle_cu_limit: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "MICRO_LAMPORTS",
+        help = "Compute unit price of the Switchboard oracle update transaction. Estimated from recent prioritization fees when unset",
+        global = true
+    )]
+    pub oracle_cu_price: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "GATEWAY_URL",
+        help = "Switchboard gateway to fall back to when none of the queue's gateways respond. Can be repeated",
+        global = true
+    )]
+    pub switchboard_gateway: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Simulate the Switchboard oracle update transaction right after building it",
+        global = true
+    )]
+    pub simulate_oracle_update: bool,
+
+    #[arg(
+        long,
+        value_name = "LOOKUP_TABLE_ADDRESS",
+        help = "Address lookup table owned by the signer used to compile Etherfuse transactions",
+        global = true
+    )]
+    pub lookup_table: Option<String>,
+
+    #[arg(
+        long,
+        help = "Create an address lookup table for Etherfuse transactions if --lookup-table is not set",
+        global = true
+    )]
+    pub create_lookup_table: bool,
+
+    #[arg(
+        long,
+        help = "Simulate trades before submitting them and skip the ones that would fail",
+        global = true
+    )]
+    pub preflight: bool,
+
+    #[arg(
+        long,
+        help = "Price Jupiter buys at the USDC their simulated redemption pays out instead of at NAV",
+        global = true
+    )]
+    pub simulate_redemption: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "BACKEND",
+        help = "How trades are sent. `auto` sends trades as Jito bundles, and single transactions through the cheapest backend with --public-sends",
+        default_value = "auto",
+        global = true
+    )]
+    pub execution_backend: ExecutionBackendArg,
+
+    #[arg(
+        long,
+        help = "Also send single transactions straight to the leader's TPU",
+        global = true
+    )]
+    pub tpu: bool,
+
+    #[arg(
+        long,
+        help = "Let `auto` send single transactions publicly through the RPC or TPU when cheaper than a Jito bundle, exposing them to front-running",
+        global = true
+    )]
+    pub public_sends: bool,
+
+    #[arg(
+        long,
+        value_name = "WEBSOCKET_URL",
+        help = "Websocket URL of the RPC used to track leaders for TPU sends and slot-aware submission. Derived from --rpc when unset",
+        global = true
+    )]
+    pub ws_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Unwrap the wrapped SOL left behind by swaps back to native SOL this often",
+        global = true
+    )]
+    pub unwrap_wsol_interval_secs: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Follow the leader schedule over the websocket and hold bundles back while the current leader historically rejects them",
+        global = true
+    )]
+    pub slot_aware_submission: bool,
+
+    #[arg(
+        long,
+        value_name = "MILLISECONDS",
+        help = "Longest a bundle is held back for a leader that lands bundles",
+        default_value_t = DEFAULT_MAX_SUBMISSION_DEFERRAL_MS,
+        global = true
+    )]
+    pub max_submission_deferral_ms: u64,
+
+    #[arg(
+        long,
+        help = "Skip the RPC's preflight simulation when sending transactions through the RPC",
+        global = true
+    )]
+    pub skip_preflight: bool,
+
+    #[arg(
+        long,
+        value_name = "RETRIES",
+        help = "Times the RPC node rebroadcasts transactions sent through it. Uses the node's default when unset",
+        global = true
+    )]
+    pub send_max_retries: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "COMMITMENT",
+        help = "Commitment each leg sent through the RPC waits for before the next one is sent: processed, confirmed or finalized",
+        default_value = "processed",
+        global = true
+    )]
+    pub send_commitment: String,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum SwapVenueArg {
+    Jupiter,
+    Raydium,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExecutionBackendArg {
+    Auto,
+    Jito,
+    Rpc,
+    Tpu,
+}
+
+/// The bot wired up from its options and `tokens.toml`: a worker for each stablebond mint,
+/// ready to be spawned, and the supervisor already running the background tasks.
+pub struct Engine {
+    pub supervisor: Supervisor,
+    pub workers: Vec<(Pubkey, MintWorker)>,
+}
+
+/// Validates the config, connects the clients and starts the background tasks, reconciling
+/// the trades a previous run left in flight before returning the mint workers.
+pub async fn build(args: EngineArgs) -> Result<Engine> {
+    let config_errors = check_config();
+    if !config_errors.is_empty() {
+        for e in &config_errors {
+            error!("{}", e);
+        }
+        return Err(anyhow!(
+            "Invalid {}, run `arb config check` for details",
+            CONFIG_FILE
+        ));
+    }
+
+    let stablebond_mints = parse_toml_config()?;
+    info!("Stablebond mints: {:?}", stablebond_mints);
+    let priority_fee_config = parse_priority_fee_config()?;
+    info!("Priority fee: {:?}", priority_fee_config);
+    let rate_limit_config = parse_rate_limit_config()?;
+    info!("Rate limits: {:?}", rate_limit_config);
+    let pool_config = parse_pool_config()?;
+    info!("Pools: {:?}", pool_config);
+    let rate_limiters = RateLimiterRegistry::new(&rate_limit_config);
+    let http_config = parse_http_config()?;
+    info!("HTTP: {:?}", http_config);
+    let http_client = |endpoint| {
+        build_http_client(&http_config, Some(rate_limiters.limiter(endpoint)))
+            .context("Error building HTTP client")
+    };
+
+    let cli_config = if let Some(config_file) = &args.config_file {
+        solana_cli_config::Config::load(config_file)
+            .map_err(|_| anyhow!("Could not find config file `{}`", config_file))?
+    } else if let Some(config_file) = &*solana_cli_config::CONFIG_FILE {
+        solana_cli_config::Config::load(config_file).unwrap_or_default()
+    } else {
+        solana_cli_config::Config::default()
+    };
+
+    let keypair_filepath = args.keypair.unwrap_or(cli_config.keypair_path.clone());
+    let wallet_keypair = read_keypair_file(keypair_filepath.clone())
+        .map_err(|e| anyhow!("Error reading keypair file: {}", e))?;
+    let rpc_client = Arc::new(RpcClient::new_sender(
+        RateLimitedSender::new(
+            HttpSender::new(args.rpc.clone().unwrap()),
+            rate_limiters.limiter(Endpoint::Rpc),
+        ),
+        RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+    ));
+
+    // Shared by every transaction builder, so the engine's transactions share a blockhash and
+    // can rebuild each other's.
+    let transaction_cache = TransactionCache::new(rpc_client.clone());
+    transaction_cache.spawn_blockhash_refresher();
+    let account_cache = args.geyser_url.as_ref().map(|_| AccountCache::new());
+    let mut pool_state_reader = PoolStateReader::new(rpc_client.clone(), pool_config);
+    if let Some(account_cache) = &account_cache {
+        pool_state_reader = pool_state_reader.with_account_cache(account_cache.clone());
+    }
+
+    let jito_jsonrpc_client: HttpClient = HttpClientBuilder::default()
+        .build(args.jito_bundles_url.clone().unwrap())
+        .context("Error building the Jito JSON-RPC client")?;
+
+    let jito_tip_ws = Arc::new(RwLock::new(None));
+    let jito_tip_ws_clone = Arc::clone(&jito_tip_ws);
+    let (ws_stream, _) = connect_async(args.jito_wss_url.clone().unwrap())
+        .await
+        .context("Error connecting to the Jito tip stream")?;
+    let (_, mut read) = ws_stream.split();
+
+    tokio::spawn(async move {
+        while let Some(message) = read.next().await {
+            if let Ok(Message::Text(text)) = message {
+                if let Ok(tips) = serde_json::from_str::<Vec<Tip>>(&text) {
+                    for item in tips {
+                        let mut jito_tip_ws = jito_tip_ws_clone.write().unwrap();
+                        *jito_tip_ws = Some(item);
+                    }
+                }
+            }
+        }
+    });
+
+    let jito_url = args
+        .jito_grpc_url
+        .clone()
+        .or(args.jito_bundles_url.clone())
+        .unwrap();
+    let (bundle_outcome_sender, bundle_outcome_receiver) = mpsc::unbounded_channel();
+    let notifications = build_notifications(&http_config)?;
+    let supervisor = Supervisor::new().with_notifications(notifications.clone());
+    let outcome_notifications = notifications.clone();
+    let event_sink = match parse_webhook_config()? {
+        Some(webhook_config) => {
+            info!("Sending events to {}", webhook_config.url);
+            Some(
+                EventSink::new(webhook_config)
+                    .with_http_client(build_http_client(&http_config, None)?),
+            )
+        }
+        None => None,
+    };
+    let outcome_event_sink = event_sink.clone();
+    let position_tracker = PositionTracker::new(args.positions_file.clone());
+    let profit_verifier = ProfitVerifier::new(rpc_client.clone(), wallet_keypair.pubkey())
+        .with_slippage_stats(SlippageStats::new(args.slippage_stats_file.clone()))
+        .with_position_tracker(position_tracker.clone());
+    let outcome_profit_verifier = profit_verifier.clone();
+    let trade_journal = TradeJournal::open(&args.journal).context("Error opening trade journal")?;
+    let outcome_trade_journal = trade_journal.clone();
+    let risk_config = parse_risk_config()?;
+    info!("Risk: {:?}", risk_config);
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let day_ago = (chrono::Utc::now() - chrono::Duration::days(1)).to_rfc3339();
+    let risk_engine = RiskEngine::new(risk_config)
+        .with_position_tracker(position_tracker.clone())
+        .with_notifications(notifications.clone())
+        .with_event_sink(event_sink.clone())
+        .with_daily_totals(
+            &trade_journal
+                .pnl(PnlGrouping::Mint, Some(&today))
+                .context("Error reading today's trades from the trade journal")?,
+        )
+        .with_recent_submissions(
+            &trade_journal
+                .notional_since(&day_ago)
+                .context("Error reading the last day's trades from the trade journal")?,
+        );
+    let outcome_risk_engine = risk_engine.clone();
+    let approval_config = parse_approval_config()?;
+    info!("Approval: {:?}", approval_config);
+    let profile = ActiveProfile::new(profiles(parse_profile_config()?), &args.profile)
+        .context("Invalid profile")?;
+    info!("Profile {}: {:?}", profile.name(), profile.get());
+    let min_profit = MinProfit::new(profile.get().min_profit_usd);
+    let control = ControlPlane::new(
+        rpc_client.clone(),
+        wallet_keypair.pubkey(),
+        stablebond_mints.clone(),
+        trade_journal.clone(),
+        risk_engine.config.kill_switch_file.clone(),
+    )
+    .with_position_tracker(position_tracker.clone())
+    .with_min_profit(min_profit.clone())
+    .with_profile(profile.clone())
+    .with_approval_queue_file(approval_config.queue_file.clone())
+    .with_supervisor(supervisor.clone());
+    if let Some(telegram) = notifications.telegram() {
+        telegram.spawn_command_listener(control.clone());
+    }
+    if let Some(api_config) = parse_api_config()? {
+        let control = control.clone();
+        tokio::spawn(async move {
+            if let Err(e) = api::serve(api_config, control).await {
+                error!("API server stopped: {:?}", e);
+            }
+        });
+    }
+    let approval_gate =
+        ApprovalGate::new(approval_config).with_notifications(notifications.clone());
+    let mint_blacklist = MintBlacklist::new(
+        MINT_FAILURE_THRESHOLD,
+        Duration::from_secs(MINT_BLACKLIST_BASE_SECS),
+        Duration::from_secs(MINT_BLACKLIST_MAX_SECS),
+    );
+    let outcome_mint_blacklist = mint_blacklist.clone();
+    let balance_reservations =
+        BalanceReservations::new(Duration::from_secs(BALANCE_RESERVATION_TTL_SECS));
+    let outcome_balance_reservations = balance_reservations.clone();
+    let landings = Landings::default();
+    let outcome_landings = landings.clone();
+    // Restarted on the same receiver, so outcomes sent while it was down aren't lost.
+    let bundle_outcome_receiver = Arc::new(tokio::sync::Mutex::new(bundle_outcome_receiver));
+    supervisor.spawn("bundle outcomes", move || {
+        let bundle_outcome_receiver = bundle_outcome_receiver.clone();
+        let outcome_trade_journal = outcome_trade_journal.clone();
+        let outcome_balance_reservations = outcome_balance_reservations.clone();
+        let outcome_landings = outcome_landings.clone();
+        let outcome_risk_engine = outcome_risk_engine.clone();
+        let outcome_mint_blacklist = outcome_mint_blacklist.clone();
+        let outcome_profit_verifier = outcome_profit_verifier.clone();
+        let outcome_notifications = outcome_notifications.clone();
+        let outcome_event_sink = outcome_event_sink.clone();
+        async move {
+            let mut bundle_outcome_receiver = bundle_outcome_receiver.lock().await;
+            while let Some(outcome) = bundle_outcome_receiver.recv().await {
+                info!("Bundle outcome: {:?}", outcome);
+                if let Err(e) = outcome_trade_journal.record_outcome(&outcome) {
+                    error!("Error journaling outcome of {}: {:?}", outcome.bundle_id, e);
+                }
+                outcome_balance_reservations.release(&outcome.submitted_bundle_id);
+                outcome_landings.settle(
+                    &outcome.submitted_bundle_id,
+                    outcome.status == BundleStatusEnum::Landed,
+                );
+                if let Some(expected) = &outcome.expected_profit {
+                    let landed = outcome.status == BundleStatusEnum::Landed;
+                    outcome_risk_engine.record_bundle_outcome(&expected.stablebond_mint, landed);
+                    if landed {
+                        outcome_mint_blacklist.record_success(&expected.stablebond_mint);
+                    } else {
+                        outcome_mint_blacklist.record_failure(
+                            &expected.stablebond_mint,
+                            format!("Bundle {} {:?}", outcome.bundle_id, outcome.status),
+                        );
+                    }
+                }
+                match outcome_profit_verifier.verify(&outcome).await {
+                    Ok(Some(realized)) => {
+                        if let Some(expected) = &outcome.expected_profit {
+                            outcome_risk_engine.record_realized_profit(
+                                &expected.stablebond_mint,
+                                realized.profit_usd,
+                            );
+                            outcome_notifications.notify_trade(
+                                expected,
+                                &realized,
+                                &outcome.signatures,
+                            );
+                            if let Some(event_sink) = &outcome_event_sink {
+                                event_sink.emit(Event::trade_landed(
+                                    &outcome.submitted_bundle_id,
+                                    expected.stablebond_mint,
+                                    expected.profit_usd,
+                                    realized.profit_usd,
+                                    &outcome.signatures,
+                                ));
+                            }
+                        }
+                        if let Err(e) = outcome_trade_journal
+                            .record_realized_profit(&outcome.submitted_bundle_id, &realized)
+                        {
+                            error!(
+                                "Error journaling realized profit of {}: {:?}",
+                                outcome.bundle_id, e
+                            );
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!(
+                            "Error verifying realized profit of {}: {:?}",
+                            outcome.bundle_id, e
+                        );
+                    }
+                }
+            }
+        }
+    });
+
+    let latency_budget = Duration::from_millis(args.latency_budget_ms);
+    let mut jito_client = JitoClient::new(
+        rpc_client.clone(),
+        jito_tip_ws,
+        jito_jsonrpc_client,
+        keypair_filepath.clone(),
+        bundle_outcome_sender.clone(),
+        TipBudget::new(
+            args.daily_tip_budget
+                .to_token_amount_floor(SOL_DECIMALS)
+                .context("Invalid --daily-tip-budget")?,
+        ),
+        BundleStats::new(args.stats_file.clone(), region_from_url(&jito_url)),
+    );
+    let priority_fee_estimator = PriorityFeeEstimator::new(rpc_client.clone(), priority_fee_config);
+    jito_client = jito_client
+        .with_max_blockhash_age(args.max_blockhash_age)
+        .with_min_tip_lamports(args.min_tip_lamports)
+        .with_latency_budget(latency_budget)
+        .with_priority_fee_estimator(priority_fee_estimator.clone())
+        .with_rate_limiter(rate_limiters.limiter(Endpoint::Jito))
+        .with_http_client(http_client(Endpoint::Jito)?)
+        .with_trade_journal(trade_journal.clone())
+        .with_transaction_cache(transaction_cache.clone());
+    jito_client.spawn_tip_floor_refresher();
+    let reconciliation_outcome_sender = bundle_outcome_sender;
+    let ws_url = args.ws_url.clone().unwrap_or_else(|| {
+        solana_cli_config::Config::compute_websocket_url(args.rpc.as_ref().unwrap())
+    });
+    if args.slot_aware_submission {
+        let leader_tracker = LeaderTracker::new(rpc_client.clone())
+            .with_max_deferral(Duration::from_millis(args.max_submission_deferral_ms));
+        leader_tracker.spawn(ws_url.clone());
+        jito_client = jito_client.with_leader_tracker(leader_tracker);
+    }
+
+    if let Some(jito_grpc_url) = args.jito_grpc_url.clone() {
+        let auth_keypair_filepath = args
+            .jito_auth_keypair
+            .clone()
+            .unwrap_or(keypair_filepath.clone());
+        let grpc_client = JitoGrpcClient::connect(jito_grpc_url, auth_keypair_filepath)
+            .await
+            .context("Error connecting to the Jito gRPC searcher API")?;
+
+        jito_client = jito_client
+            .with_bundle_results(BundleResults::spawn(grpc_client.clone()))
+            .with_grpc_client(grpc_client);
+    }
+    // Settled before the workers start, so the interrupted trades' outcomes reach the journal
+    // and the positions before any new trade.
+    match Reconciler::new(
+        trade_journal.clone(),
+        rpc_client.clone(),
+        wallet_keypair.pubkey(),
+        reconciliation_outcome_sender,
+    )
+    .reconcile()
+    .await
+    {
+        Ok(reconciliation) => info!("Interrupted trades reconciled: {:?}", reconciliation),
+        Err(e) => report_error("Error reconciling interrupted trades", e),
+    }
+
+    let tpu_backend = if args.tpu || args.execution_backend == ExecutionBackendArg::Tpu {
+        Some(
+            TpuBackend::new(rpc_client.clone(), &ws_url)
+                .await
+                .context("Error connecting to the TPU")?,
+        )
+    } else {
+        None
+    };
+    let send_config = SendConfig {
+        skip_preflight: args.skip_preflight,
+        max_retries: args.send_max_retries,
+        commitment: CommitmentConfig::from_str(&args.send_commitment)
+            .context("Invalid send commitment")?,
+    };
+    let rpc_backend = RpcBackend::new(rpc_client.clone()).with_send_config(send_config);
+    let mut execution_policy = ExecutionPolicy::new();
+    execution_policy.allow_public_sends(args.public_sends);
+    match args.execution_backend {
+        ExecutionBackendArg::Auto => {
+            if let Some(tpu_backend) = tpu_backend {
+                execution_policy.add_backend(ExecutionBackendEnum::Tpu(tpu_backend));
+            }
+            execution_policy
+                .add_backend(ExecutionBackendEnum::Rpc(rpc_backend))
+                .add_backend(ExecutionBackendEnum::Jito(
+                    JitoBackend::new(jito_client.clone()).with_profile(profile.clone()),
+                ));
+        }
+        ExecutionBackendArg::Jito => {
+            execution_policy.add_backend(ExecutionBackendEnum::Jito(
+                JitoBackend::new(jito_client.clone()).with_profile(profile.clone()),
+            ));
+        }
+        ExecutionBackendArg::Rpc => {
+            execution_policy.add_backend(ExecutionBackendEnum::Rpc(rpc_backend));
+        }
+        ExecutionBackendArg::Tpu => {
+            execution_policy.add_backend(ExecutionBackendEnum::Tpu(tpu_backend.unwrap()));
+        }
+    }
+
+    let mut etherfuse_client = EtherfuseClient::new(
+        rpc_client.clone(),
+        keypair_filepath.clone(),
+        args.etherfuse_url.clone().unwrap(),
+    )
+    .with_priority_fee_estimator(priority_fee_estimator.clone())
+    .with_rate_limiter(rate_limiters.limiter(Endpoint::Etherfuse))
+    .with_http_client(http_client(Endpoint::Etherfuse)?)
+    .with_transaction_cache(transaction_cache.clone());
+    if let Some(account_cache) = &account_cache {
+        etherfuse_client = etherfuse_client.with_account_cache(account_cache.clone());
+    }
+    if args.lookup_table.is_some() || args.create_lookup_table {
+        let lookup_table_address = args
+            .lookup_table
+            .as_ref()
+            .map(|address| Pubkey::from_str(address).context("Invalid lookup table address"))
+            .transpose()?;
+        etherfuse_client = etherfuse_client.with_lookup_table_manager(LookupTableManager::new(
+            transaction_cache.clone(),
+            keypair_filepath.clone(),
+            lookup_table_address,
+        ));
+    }
+
+    if !etherfuse_client
+        .has_kyc_account(&wallet_keypair.pubkey())
+        .await
+    {
+        return Err(anyhow!(
+            "Etherfuse does not have a kyc account associated with this wallet"
+        ));
+    }
+
+    let jupiter_client = JupiterClient::new(
+        rpc_client.clone(),
+        args.jupiter_quote_url.clone().unwrap(),
+        keypair_filepath.clone(),
+        rate_limiters.limiter(Endpoint::Jupiter),
+    )
+    .with_priority_fee_estimator(priority_fee_estimator.clone())
+    .with_http_client(http_client(Endpoint::Jupiter)?)
+    .with_transaction_cache(transaction_cache.clone());
+    // Dust sweeps and gas top-ups quote with their own slippage, not the profile's.
+    let route_check = parse_route_check_config()?;
+    info!("Route check: {:?}", route_check);
+    let mut profiled_jupiter_client = jupiter_client
+        .clone()
+        .with_profile(profile.clone())
+        .with_route_check(route_check);
+    let quote_budget = args
+        .quote_budget
+        .map(|calls_per_tick| QuoteBudget::new(calls_per_tick, &stablebond_mints));
+    if let Some(quote_budget) = &quote_budget {
+        info!(
+            "Quote budget: {} Jupiter calls per tick",
+            quote_budget.calls_per_tick
+        );
+        profiled_jupiter_client = profiled_jupiter_client.with_quote_budget(quote_budget.clone());
+    }
+
+    let mut switchboard_client =
+        SwitchboardClient::new(rpc_client.clone(), keypair_filepath.clone())
+            .with_compute_budget(args.oracle_cu_limit, args.oracle_cu_price)
+            .with_fallback_gateways(args.switchboard_gateway.clone())
+            .with_simulation(args.simulate_oracle_update)
+            .with_priority_fee_estimator(priority_fee_estimator.clone())
+            .with_transaction_cache(transaction_cache.clone());
+
+    if let (Some(geyser_url), Some(account_cache)) = (&args.geyser_url, &account_cache) {
+        switchboard_client = switchboard_client.with_account_cache(account_cache.clone());
+        for stablebond_mint in &stablebond_mints {
+            account_cache.watch_liquidity(
+                stablebond_mint,
+                &etherfuse_client
+                    .liquidity_accounts(stablebond_mint)
+                    .await
+                    .context("Error reading the liquidity accounts")?,
+            );
+            account_cache.watch(
+                &etherfuse_client
+                    .market_accounts(stablebond_mint)
+                    .await
+                    .context("Error reading the market accounts")?,
+            );
+            account_cache.watch(
+                &pool_state_reader
+                    .watched_accounts(stablebond_mint)
+                    .await
+                    .context("Error reading the pool accounts")?,
+            );
+        }
+        GeyserSubscriber::new(geyser_url.clone(), account_cache.clone())
+            .with_x_token(args.geyser_x_token.clone())
+            .spawn();
+    }
+
+    let unwind_config = parse_unwind_config()?;
+    info!("Unwind: {:?}", unwind_config);
+    let unwinder = Unwinder::new(
+        unwind_config,
+        rpc_client.clone(),
+        jupiter_client.clone(),
+        etherfuse_client.clone(),
+        position_tracker.clone(),
+    )
+    .with_send_config(send_config);
+    let leg_failure_config = parse_leg_failure_config()?;
+    info!("Leg failure: {:?}", leg_failure_config);
+    let leg_failure_handler = LegFailureHandler::new(
+        leg_failure_config,
+        unwinder.clone(),
+        risk_engine.config.kill_switch_file.clone(),
+    )
+    .with_notifications(notifications.clone());
+    if let Some(unwrap_wsol_interval_secs) = args.unwrap_wsol_interval_secs {
+        spawn_wsol_unwrapper(
+            transaction_cache.clone(),
+            read_keypair_file(&keypair_filepath)
+                .map_err(|e| anyhow!("Error reading keypair file: {}", e))?,
+            Duration::from_secs(unwrap_wsol_interval_secs),
+            send_config,
+        );
+    }
+    let dust_sweep_config = parse_dust_sweep_config()?;
+    info!("Dust sweep: {:?}", dust_sweep_config);
+    if dust_sweep_config.enabled {
+        let interval = Duration::from_secs(60 * dust_sweep_config.interval_mins);
+        let dust_sweeper = DustSweeper::new(
+            dust_sweep_config,
+            rpc_client.clone(),
+            jupiter_client.clone(),
+            wallet_keypair.pubkey(),
+        )
+        .with_send_config(send_config);
+        let sweep_notifications = notifications.clone();
+        supervisor.spawn("dust sweep", move || {
+            let mut dust_sweeper = dust_sweeper.clone();
+            let sweep_notifications = sweep_notifications.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    match dust_sweeper.sweep().await {
+                        Ok(sweeps) => {
+                            for sweep in sweeps {
+                                let mut message = format!(
+                                    "Swept {} of {} for {:.2} USDC",
+                                    sweep.amount, sweep.mint, sweep.usdc_amount
+                                );
+                                for signature in &sweep.signatures {
+                                    message
+                                        .push_str(&format!("\n{}{}", EXPLORER_TX_URL, signature));
+                                }
+                                sweep_notifications.notify(Severity::Info, message);
+                            }
+                        }
+                        Err(e) => report_error("Error sweeping dust", e),
+                    }
+                }
+            }
+        });
+    }
+    let gas_top_up_config = parse_gas_top_up_config()?;
+    info!("Gas top-up: {:?}", gas_top_up_config);
+    let gas_top_up = GasTopUp::new(
+        gas_top_up_config,
+        rpc_client.clone(),
+        jupiter_client.clone(),
+        wallet_keypair.pubkey(),
+    )
+    .with_send_config(send_config);
+    let gas_top_up_notifications = notifications.clone();
+    let gas_top_up_interval = Duration::from_secs(args.tick_interval_secs);
+    supervisor.spawn("gas top-up", move || {
+        let mut gas_top_up = gas_top_up.clone();
+        let gas_top_up_notifications = gas_top_up_notifications.clone();
+        async move {
+            loop {
+                match gas_top_up.top_up_if_low().await {
+                    Ok(Some(top_up)) => {
+                        let mut message = format!(
+                            "SOL balance was {:.4} SOL, swapped {:.2} USDC for {:.4} SOL",
+                            top_up.sol_balance, top_up.usdc_amount, top_up.sol_amount
+                        );
+                        for signature in &top_up.signatures {
+                            message.push_str(&format!("\n{}{}", EXPLORER_TX_URL, signature));
+                        }
+                        gas_top_up_notifications.notify(Severity::Info, message);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        gas_top_up_notifications.notify(
+                            Severity::Warning,
+                            format!("Error swapping USDC for SOL: {}", e),
+                        );
+                        report_error("Error topping up SOL", e);
+                    }
+                }
+                tokio::time::sleep(gas_top_up_interval).await;
+            }
+        }
+    });
+    let usdc_peg_config = parse_usdc_peg_config()?;
+    info!("USDC peg: {:?}", usdc_peg_config);
+    if usdc_peg_config.enabled {
+        let interval = Duration::from_secs(usdc_peg_config.check_interval_secs);
+        let usdc_peg_monitor = UsdcPegMonitor::new(
+            usdc_peg_config.clone(),
+            usdc_peg_config
+                .sources
+                .iter()
+                .map(|&source| {
+                    Ok(UsdcPriceClient::new(source)
+                        .with_rate_limiter(rate_limiters.limiter(source.endpoint()))
+                        .with_http_client(http_client(source.endpoint())?))
+                })
+                .collect::<Result<_>>()?,
+            risk_engine.config.kill_switch_file.clone(),
+        )
+        .with_notifications(notifications.clone());
+        supervisor.spawn("USDC peg", move || {
+            let usdc_peg_monitor = usdc_peg_monitor.clone();
+            async move {
+                loop {
+                    if let Err(e) = usdc_peg_monitor.check().await {
+                        report_error("Error checking the USDC peg", e);
+                    }
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        });
+    }
+    let swap_router = SwapRouter::new(
+        args.swap_venues
+            .iter()
+            .map(|venue| {
+                Ok(match venue {
+                    SwapVenueArg::Jupiter => {
+                        SwapVenueEnum::Jupiter(profiled_jupiter_client.clone())
+                    }
+                    SwapVenueArg::Raydium => SwapVenueEnum::Raydium(
+                        RaydiumClient::new(
+                            rpc_client.clone(),
+                            args.raydium_swap_url.clone(),
+                            keypair_filepath.clone(),
+                        )
+                        .with_rate_limiter(rate_limiters.limiter(Endpoint::Raydium))
+                        .with_priority_fee_estimator(priority_fee_estimator.clone())
+                        .with_profile(profile.clone())
+                        .with_http_client(http_client(Endpoint::Raydium)?),
+                    ),
+                })
+            })
+            .collect::<Result<_>>()?,
+    );
+    let research_exporter = args
+        .export_dir
+        .as_deref()
+        .map(|dir| ResearchExporter::new(dir).context("Error creating research export directory"))
+        .transpose()?;
+    let spread_history = SpreadHistory::default().with_entry_z_score(args.entry_z_score);
+    let buy_on_etherfuse_sell_on_jupiter = BuyOnEtherfuseSellOnJupiter::new(
+        rpc_client.clone(),
+        swap_router.clone(),
+        keypair_filepath.clone(),
+        etherfuse_client.clone(),
+    )
+    .with_preflight(args.preflight)
+    .with_exporter(research_exporter.clone())
+    .with_spread_history(spread_history.clone())
+    .with_min_profit(min_profit.clone())
+    .with_transaction_cache(transaction_cache.clone());
+
+    let buy_on_jupiter_sell_on_etherfuse = BuyOnJupiterSellOnEtherfuse::new(
+        rpc_client.clone(),
+        swap_router,
+        keypair_filepath.clone(),
+        etherfuse_client.clone(),
+    )
+    .with_preflight(args.preflight)
+    .with_redemption_simulation(args.simulate_redemption)
+    .with_exporter(research_exporter.clone())
+    .with_spread_history(spread_history.clone())
+    .with_min_profit(min_profit.clone())
+    .with_transaction_cache(transaction_cache.clone());
+
+    let sol_price_oracle = SolPriceOracle::new(
+        args.sol_price_sources
+            .iter()
+            .map(|&source| {
+                let endpoint = match source {
+                    SolPriceSource::Coingecko => Endpoint::Coingecko,
+                    SolPriceSource::Jupiter => Endpoint::Jupiter,
+                    SolPriceSource::Pyth => Endpoint::Pyth,
+                };
+                Ok(SolPriceClient::new(source)
+                    .with_rate_limiter(rate_limiters.limiter(endpoint))
+                    .with_http_client(http_client(endpoint)?))
+            })
+            .collect::<Result<_>>()?,
+    );
+    let tick_intervals = parse_tick_interval_config()?;
+    info!("Tick intervals: {:?}", tick_intervals);
+    let adaptive_interval = parse_adaptive_interval_config()?;
+    info!("Adaptive interval: {:?}", adaptive_interval);
+    let chunking = parse_chunking_config()?;
+    info!("Trade chunking: {:?}", chunking);
+    let decision_log =
+        DecisionLog::new(args.decision_log.as_deref()).context("Error opening decision log")?;
+    let mint_worker = MintWorker {
+        rpc_client,
+        wallet_keypair: Arc::new(wallet_keypair),
+        etherfuse_client,
+        jito_client,
+        switchboard_client,
+        jupiter_client: profiled_jupiter_client,
+        pool_state_reader,
+        priority_fee_estimator,
+        sol_price_oracle,
+        balance_reservations,
+        cranked_feeds: CrankedFeeds::default(),
+        position_tracker,
+        unwinder,
+        leg_failure_handler,
+        buy_on_etherfuse_sell_on_jupiter,
+        buy_on_jupiter_sell_on_etherfuse,
+        risk_engine,
+        approval_gate,
+        mint_blacklist,
+        bundle_dedup: Arc::new(Mutex::new(BundleDedup::new(
+            args.dedup_window_secs,
+            DEDUP_SIZE_BUCKET_USDC_AMOUNT,
+        ))),
+        execution_policy,
+        trade_journal,
+        profit_verifier,
+        notifications,
+        event_sink,
+        decision_log: Arc::new(decision_log),
+        control,
+        profile,
+        quote_budget,
+        spread_history,
+        adaptive_interval,
+        trade_chunker: TradeChunker::new(chunking, landings),
+        account_cache,
+        transaction_cache,
+        send_config,
+        usdc_mint: Pubkey::from_str(USDC_MINT).unwrap(),
+        interval: Duration::from_secs(args.tick_interval_secs),
+        latency_budget,
+        max_oracle_divergence_bps: args.max_oracle_divergence_bps,
+        preflight: args.preflight,
+        rpc_fallback: args.rpc_fallback,
+    };
+    let workers = stablebond_mints
+        .iter()
+        .map(|&stablebond_mint| {
+            let mut worker = mint_worker.clone();
+            if let Some(&secs) = tick_intervals.get(&stablebond_mint) {
+                worker.interval = Duration::from_secs(secs);
+            }
+            (stablebond_mint, worker)
+        })
+        .collect();
+    Ok(Engine {
+        supervisor,
+        workers,
+    })
+}
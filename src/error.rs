@@ -0,0 +1,243 @@
+use rust_decimal::Decimal;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_program::pubkey::Pubkey;
+
+use crate::jito::JitoError;
+
+/// Error of the library's public APIs, by what failed, so that embedders and the retry logic
+/// can branch on its kind instead of matching messages.
+#[derive(Debug, thiserror::Error)]
+pub enum ArbError {
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Quote(#[from] QuoteError),
+
+    #[error(transparent)]
+    Venue(#[from] VenueError),
+
+    #[error(transparent)]
+    Execution(#[from] ExecutionError),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Risk(#[from] RiskError),
+
+    #[error(transparent)]
+    Price(#[from] PriceError),
+}
+
+pub type ArbResult<T> = std::result::Result<T, ArbError>;
+
+impl ArbError {
+    /// Whether the same call may succeed if retried later, e.g. after a timeout or a rate
+    /// limit, as opposed to a request that will keep failing.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ArbError::Rpc(e) => e.is_transient(),
+            ArbError::Quote(e) => matches!(e, QuoteError::Request(_)),
+            ArbError::Venue(e) => matches!(e, VenueError::Request(_) | VenueError::Oracle(_)),
+            ArbError::Execution(e) => e.is_transient(),
+            ArbError::Config(_) => false,
+            ArbError::Risk(e) => matches!(e, RiskError::Throttled { .. }),
+            ArbError::Price(e) => !matches!(e, PriceError::InvalidResponse { .. }),
+        }
+    }
+}
+
+impl From<ClientError> for ArbError {
+    fn from(e: ClientError) -> Self {
+        ArbError::Rpc(e.into())
+    }
+}
+
+/// Reading the chain through the RPC.
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    #[error("RPC request failed: {0}")]
+    Request(#[from] ClientError),
+
+    #[error("Account {0} not found")]
+    AccountNotFound(Pubkey),
+
+    #[error("Unable to deserialize account {account}: {message}")]
+    InvalidAccount { account: Pubkey, message: String },
+}
+
+impl RpcError {
+    pub fn invalid_account(account: Pubkey, e: impl ToString) -> Self {
+        RpcError::InvalidAccount {
+            account,
+            message: e.to_string(),
+        }
+    }
+
+    fn is_transient(&self) -> bool {
+        match self {
+            RpcError::Request(e) => matches!(
+                e.kind(),
+                ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)
+            ),
+            RpcError::AccountNotFound(_) | RpcError::InvalidAccount { .. } => false,
+        }
+    }
+}
+
+/// Pricing a swap on a secondary market venue, Jupiter or Raydium.
+#[derive(Debug, thiserror::Error)]
+pub enum QuoteError {
+    /// No answer, e.g. a timeout, a rate limit or a server error.
+    #[error("Swap quote request failed: {0}")]
+    Request(String),
+
+    /// The venue refused the request, e.g. when there is no route for the amount.
+    #[error("Swap venue API: {0}")]
+    Api(String),
+
+    #[error("Invalid swap venue response: {0}")]
+    InvalidResponse(String),
+
+    /// The mint's share of the per-tick quote budget is spent, see `QuoteBudget`.
+    #[error("Quote budget of {0} spent for this tick")]
+    BudgetExhausted(Pubkey),
+
+    /// The route fills part of the size materially worse than the rest, see `RouteCheckConfig`.
+    #[error("Fragile route: {0}")]
+    FragileRoute(String),
+}
+
+/// Pricing stablebonds on Etherfuse, or the oracles the bond program prices them with.
+#[derive(Debug, thiserror::Error)]
+pub enum VenueError {
+    #[error("Unsupported stablebond mint {0}")]
+    UnsupportedMint(Pubkey),
+
+    /// No answer from the Etherfuse API, e.g. a timeout, a rate limit or a server error.
+    #[error("Etherfuse request failed: {0}")]
+    Request(String),
+
+    #[error("Invalid Etherfuse response: {0}")]
+    InvalidResponse(String),
+
+    /// Reading or updating the Switchboard feeds.
+    #[error("Switchboard oracle: {0}")]
+    Oracle(String),
+}
+
+/// Building and landing a trade's transactions.
+#[derive(Debug, thiserror::Error)]
+pub enum ExecutionError {
+    #[error("Unable to build transaction: {0}")]
+    Build(String),
+
+    #[error("No execution backend for {0}")]
+    NoBackend(String),
+
+    /// Sending or confirming through the RPC or the TPU.
+    #[error("Sending transactions failed: {0}")]
+    Send(String),
+
+    #[error(transparent)]
+    Jito(#[from] JitoError),
+}
+
+impl ExecutionError {
+    fn is_transient(&self) -> bool {
+        match self {
+            ExecutionError::Build(_) | ExecutionError::NoBackend(_) => false,
+            ExecutionError::Send(_) => true,
+            ExecutionError::Jito(e) => matches!(
+                e,
+                JitoError::RateLimited(_)
+                    | JitoError::Unavailable(_)
+                    | JitoError::StaleBlockhash(_)
+                    | JitoError::LatencyBudgetExceeded(..)
+            ),
+        }
+    }
+}
+
+/// Pricing SOL in USD, which tips and fees are paid in.
+#[derive(Debug, thiserror::Error)]
+pub enum PriceError {
+    /// No answer from a price source, e.g. a timeout, a rate limit or a server error.
+    #[error("{provider} request failed: {message}")]
+    Request {
+        provider: &'static str,
+        message: String,
+    },
+
+    /// An answer without a usable price, e.g. a missing field, a non-positive price or a stale
+    /// one.
+    #[error("Invalid {provider} response: {message}")]
+    InvalidResponse {
+        provider: &'static str,
+        message: String,
+    },
+
+    /// None of the sources had a price.
+    #[error("No SOL price available: {0}")]
+    Unavailable(String),
+}
+
+/// Reading the config file.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Unable to read {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Unable to parse {path}: {source}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid [{section}] section: {message}")]
+    InvalidSection { section: String, message: String },
+
+    #[error("Missing [{section}] section: {message}")]
+    MissingSection { section: String, message: String },
+
+    #[error("Unknown [{section}] section: {message}")]
+    UnknownSection { section: String, message: String },
+}
+
+/// A trade refused by the risk engine.
+#[derive(Debug, thiserror::Error)]
+pub enum RiskError {
+    #[error(
+        "Trading is halted: {reason}. Run `arb resume` or remove {kill_switch_file} to continue"
+    )]
+    Halted {
+        reason: String,
+        kill_switch_file: String,
+    },
+
+    /// Trading resumes once older trades leave the window, or the UTC day ends.
+    #[error(
+        "${traded_usd:.2} traded over the {window} plus ${notional_usd:.2} exceeds the \
+         throttle of ${limit_usd:.2}"
+    )]
+    Throttled {
+        window: &'static str,
+        traded_usd: Decimal,
+        notional_usd: Decimal,
+        limit_usd: Decimal,
+    },
+
+    #[error(
+        "Position of ${position_usd:.2} plus ${notional_usd:.2} could exceed the maximum \
+         position of ${max_position_usd:.2}"
+    )]
+    PositionLimit {
+        position_usd: Decimal,
+        notional_usd: Decimal,
+        max_position_usd: Decimal,
+    },
+}
@@ -0,0 +1,189 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, warn};
+
+use crate::constants::{
+    ERROR_ESCALATION_FACTOR, ERROR_ESCALATION_THRESHOLD, ERROR_ID_MIN_LEN,
+    ERROR_SUMMARY_INTERVAL_SECS, MAX_ERROR_CLASSES,
+};
+
+lazy_static! {
+    /// Shared by every module, so that the same error reported from several mints or
+    /// strategies is counted once.
+    static ref ERROR_AGGREGATOR: ErrorAggregator = ErrorAggregator::new(
+        Duration::from_secs(ERROR_SUMMARY_INTERVAL_SECS),
+        ERROR_ESCALATION_THRESHOLD,
+    );
+}
+
+/// Reports `error` through the shared aggregator.
+pub fn report_error(context: &str, error: impl Debug) {
+    ERROR_AGGREGATOR.report(context, error);
+}
+
+struct ErrorClass {
+    count: u64,
+    /// Occurrences since the class was last logged.
+    suppressed: u64,
+    logged_at: Instant,
+    seen_at: Instant,
+    next_escalation: u64,
+}
+
+/// Counts repeated errors, e.g. "No route found" on every tick, instead of logging each one.
+/// The first error of a class is logged right away, repeats are summed up at most once per
+/// summary interval, and the class is escalated whenever its count crosses the threshold,
+/// which then grows by `ERROR_ESCALATION_FACTOR`. At most `max_classes` classes are tracked:
+/// past that, classes not seen for a summary interval are evicted, and errors of new classes
+/// are logged without being tracked until there is room again.
+pub struct ErrorAggregator {
+    classes: Mutex<HashMap<String, ErrorClass>>,
+    summary_interval: Duration,
+    escalation_threshold: u64,
+    max_classes: usize,
+}
+
+impl ErrorAggregator {
+    pub fn new(summary_interval: Duration, escalation_threshold: u64) -> Self {
+        Self {
+            classes: Mutex::new(HashMap::new()),
+            summary_interval,
+            escalation_threshold,
+            max_classes: MAX_ERROR_CLASSES,
+        }
+    }
+
+    pub fn with_max_classes(mut self, max_classes: usize) -> Self {
+        self.max_classes = max_classes;
+        self
+    }
+
+    pub fn report(&self, context: &str, error: impl Debug) {
+        let message = format!("{:?}", error);
+        let key = format!("{}: {}", context, error_class(&message));
+        let mut classes = self.classes.lock().unwrap();
+        let Some(class) = classes.get_mut(&key) else {
+            error!("{}: {}", context, message);
+            if classes.len() >= self.max_classes {
+                classes.retain(|_, class| class.seen_at.elapsed() < self.summary_interval);
+            }
+            if classes.len() < self.max_classes {
+                classes.insert(
+                    key,
+                    ErrorClass {
+                        count: 1,
+                        suppressed: 0,
+                        logged_at: Instant::now(),
+                        seen_at: Instant::now(),
+                        next_escalation: self.escalation_threshold,
+                    },
+                );
+            }
+            return;
+        };
+
+        class.count += 1;
+        class.seen_at = Instant::now();
+        if class.count >= class.next_escalation {
+            error!(
+                count = class.count,
+                "{}: {} (occurred {} times)", context, message, class.count
+            );
+            class.next_escalation *= ERROR_ESCALATION_FACTOR;
+        } else if class.logged_at.elapsed() >= self.summary_interval {
+            warn!(
+                count = class.count,
+                "{}: {} (repeated {} times in the last {}s)",
+                context,
+                message,
+                class.suppressed + 1,
+                class.logged_at.elapsed().as_secs()
+            );
+        } else {
+            class.suppressed += 1;
+            debug!("{}: {}", context, message);
+            return;
+        }
+        class.suppressed = 0;
+        class.logged_at = Instant::now();
+    }
+}
+
+/// Masks the ids in an error message, such as signatures, pubkeys and bundle ids in base58 or
+/// hex, and the numbers, such as amounts and slots, so that errors differing only by those are
+/// counted together.
+fn error_class(message: &str) -> String {
+    let mut class = String::with_capacity(message.len());
+    let mut word = String::new();
+    for c in message.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if word.len() >= ERROR_ID_MIN_LEN {
+            class.push('*');
+        } else {
+            let mut in_number = false;
+            for c in word.chars() {
+                if c.is_ascii_digit() {
+                    if !in_number {
+                        class.push('#');
+                    }
+                    in_number = true;
+                } else {
+                    class.push(c);
+                    in_number = false;
+                }
+            }
+        }
+        word.clear();
+        class.push(c);
+    }
+    class.pop();
+    class
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_ids_and_numbers() {
+        let signature = "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+        let pubkey = "CETES7CKqqKQizuSN6iWQwmTeFRjbJR6Vw2XRKfEDR8f";
+        let hex_hash = "0x3fa85f6457174562b3fc2c963f66afa63fa85f6457174562b3fc2c963f66afa6";
+
+        assert_eq!(
+            error_class(&format!("Transaction {} failed at slot 283746", signature)),
+            "Transaction * failed at slot #"
+        );
+        assert_eq!(
+            error_class(&format!("Account {} not found ({})", pubkey, hex_hash)),
+            "Account * not found (*)"
+        );
+        assert_eq!(
+            error_class("Jupiter v6 returned 429"),
+            "Jupiter v# returned #"
+        );
+    }
+
+    #[test]
+    fn evicts_idle_classes_past_the_cap() {
+        let aggregator = ErrorAggregator::new(Duration::from_secs(60), 10).with_max_classes(2);
+        aggregator.report("a", "first");
+        aggregator.report("b", "second");
+        aggregator.report("c", "third");
+        assert_eq!(aggregator.classes.lock().unwrap().len(), 2);
+
+        let aggregator = ErrorAggregator::new(Duration::ZERO, 10).with_max_classes(2);
+        aggregator.report("a", "first");
+        aggregator.report("b", "second");
+        aggregator.report("c", "third");
+        let classes = aggregator.classes.lock().unwrap();
+        assert_eq!(classes.len(), 1);
+        assert!(classes.contains_key("c: \"third\""));
+    }
+}
@@ -1,5 +1,5 @@
-use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use solana_client::client_error::Result as ClientResult;
 use solana_program::{program_pack::Pack, system_program};
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
@@ -17,6 +17,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use lazy_static::lazy_static;
+use reqwest_middleware::ClientWithMiddleware;
 use spl_associated_token_account::{
     get_associated_token_address, get_associated_token_address_with_program_id,
 };
@@ -29,27 +30,40 @@ use stablebond_sdk::{
     find_sell_liquidity_pda,
     instructions::{PurchaseBondV2, PurchaseBondV2InstructionArgs},
 };
+use tracing::error;
 
-use crate::{constants::USDC_MINT, field_as_string, transaction::build_and_sign_tx};
+use crate::{
+    account_cache::{get_account_data, AccountCache},
+    constants::USDC_MINT,
+    error::{ArbResult, ExecutionError, RpcError, VenueError},
+    error_aggregator::report_error,
+    field_as_string,
+    http::default_http_client,
+    lookup_table::LookupTableManager,
+    priority_fee::PriorityFeeEstimator,
+    rate_limiter::RateLimiter,
+    transaction::{TransactionBuilder, TransactionCache},
+};
 
 lazy_static! {
+    /// Currency pair of the Etherfuse exchange rate each stablebond is priced with.
     static ref EXCHANGE_RATE_CONFIGS: HashMap<Pubkey, &'static str> = {
         let mut m = HashMap::new();
         m.insert(
             Pubkey::from_str("CETES7CKqqKQizuSN6iWQwmTeFRjbJR6Vw2XRKfEDR8f").unwrap(),
-            "https://api.etherfuse.com/lookup/exchange_rate/usd_to_mxn",
+            "usd_to_mxn",
         );
         m.insert(
             Pubkey::from_str("USTRYnGgcHAhdWsanv8BG6vHGd4p7UGgoB9NRd8ei7j").unwrap(),
-            "https://api.etherfuse.com/lookup/exchange_rate/usd_to_usd",
+            "usd_to_usd",
         );
         m.insert(
             Pubkey::from_str("GiLTSeSFnNse7xQVYeKdMyckGw66AoRmyggGg1NNd4yr").unwrap(),
-            "https://api.etherfuse.com/lookup/exchange_rate/usd_to_gbp",
+            "usd_to_gbp",
         );
         m.insert(
             Pubkey::from_str("EuroszHk1AL7fHBBsxgeGHsamUqwBpb26oEyt9BcfZ6G").unwrap(),
-            "https://api.etherfuse.com/lookup/exchange_rate/usd_to_eur",
+            "usd_to_eur",
         );
         m
     };
@@ -60,6 +74,12 @@ pub struct EtherfuseClient {
     pub rpc_client: Arc<RpcClient>,
     pub keypair_filepath: String,
     pub etherfuse_api_url: String,
+    pub priority_fee_estimator: Option<PriorityFeeEstimator>,
+    pub lookup_table_manager: Option<LookupTableManager>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub http_client: ClientWithMiddleware,
+    pub account_cache: Option<AccountCache>,
+    pub transaction_cache: TransactionCache,
 }
 
 impl EtherfuseClient {
@@ -69,29 +89,100 @@ impl EtherfuseClient {
         etherfuse_api_url: String,
     ) -> Self {
         Self {
+            transaction_cache: TransactionCache::new(rpc_client.clone()),
             rpc_client,
             keypair_filepath,
             etherfuse_api_url,
+            priority_fee_estimator: None,
+            lookup_table_manager: None,
+            rate_limiter: None,
+            http_client: default_http_client(),
+            account_cache: None,
+        }
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// Throttles calls to the Etherfuse API and the exchange rate APIs.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Reads the bonds, their liquidity and payment feeds from the Geyser stream when live.
+    pub fn with_account_cache(mut self, account_cache: AccountCache) -> Self {
+        self.account_cache = Some(account_cache);
+        self
+    }
+
+    /// Builds transactions on the engine's shared blockhash and lookup tables.
+    pub fn with_transaction_cache(mut self, transaction_cache: TransactionCache) -> Self {
+        self.transaction_cache = transaction_cache;
+        self
+    }
+
+    async fn account_data(&self, pubkey: &Pubkey) -> ClientResult<Vec<u8>> {
+        get_account_data(&self.rpc_client, self.account_cache.as_ref(), pubkey).await
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.wait_if_needed().await;
         }
     }
 
+    async fn get_json<T: DeserializeOwned>(&self, url: String) -> ArbResult<T> {
+        self.wait_for_rate_limit().await;
+        let response = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|e| VenueError::Request(e.to_string()))?;
+        Ok(response
+            .json()
+            .await
+            .map_err(|e| VenueError::InvalidResponse(e.to_string()))?)
+    }
+
+    /// Compiles purchase and redemption transactions as v0 messages against our lookup table.
+    pub fn with_lookup_table_manager(mut self, lookup_table_manager: LookupTableManager) -> Self {
+        self.lookup_table_manager = Some(lookup_table_manager);
+        self
+    }
+
+    pub fn with_priority_fee_estimator(
+        mut self,
+        priority_fee_estimator: PriorityFeeEstimator,
+    ) -> Self {
+        self.priority_fee_estimator = Some(priority_fee_estimator);
+        self
+    }
+
     pub fn signer(&self) -> Keypair {
         read_keypair_file(&self.keypair_filepath).expect("Unable to read keypair filepath")
     }
 
-    pub async fn purchase_ix(&self, amount: u64, stablebond_mint: Pubkey) -> Result<Instruction> {
+    pub async fn purchase_ix(
+        &self,
+        amount: u64,
+        stablebond_mint: Pubkey,
+    ) -> ArbResult<Instruction> {
         let ix_args = PurchaseBondV2InstructionArgs { amount };
 
         let bond_account = find_bond_pda(stablebond_mint).0;
-        let data = self.rpc_client.get_account_data(&bond_account).await?;
-        let bond = Bond::from_bytes(&data).unwrap();
+        let data = self.account_data(&bond_account).await?;
+        let bond =
+            Bond::from_bytes(&data).map_err(|e| RpcError::invalid_account(bond_account, e))?;
 
         let payment_feed_account = find_payment_feed_pda(bond.payment_feed_type).0;
-        let data = self
-            .rpc_client
-            .get_account_data(&payment_feed_account)
-            .await?;
-        let payment_feed = PaymentFeed::from_bytes(&data).unwrap();
+        let data = self.account_data(&payment_feed_account).await?;
+        let payment_feed = PaymentFeed::from_bytes(&data)
+            .map_err(|e| RpcError::invalid_account(payment_feed_account, e))?;
 
         let user_wallet = self.signer();
         let issuance_account = find_issuance_pda(bond_account, bond.issuance_number).0;
@@ -140,26 +231,25 @@ impl EtherfuseClient {
         &self,
         amount: u64,
         stablebond_mint: Pubkey,
-    ) -> Result<VersionedTransaction> {
+    ) -> ArbResult<VersionedTransaction> {
         let ix = self.purchase_ix(amount, stablebond_mint).await?;
-        build_and_sign_tx(&self.rpc_client, &self.signer(), &[ix]).await
+        self.build_and_sign_tx(&[ix]).await
     }
 
     pub async fn instant_bond_redemption_ix(
         &self,
         amount: u64,
         stablebond_mint: Pubkey,
-    ) -> Result<Instruction> {
+    ) -> ArbResult<Instruction> {
         let bond_account = find_bond_pda(stablebond_mint).0;
-        let data = self.rpc_client.get_account_data(&bond_account).await?;
-        let bond = Bond::from_bytes(&data).unwrap();
+        let data = self.account_data(&bond_account).await?;
+        let bond =
+            Bond::from_bytes(&data).map_err(|e| RpcError::invalid_account(bond_account, e))?;
 
         let payment_feed_account = find_payment_feed_pda(bond.payment_feed_type).0;
-        let data = self
-            .rpc_client
-            .get_account_data(&payment_feed_account)
-            .await?;
-        let payment_feed = PaymentFeed::from_bytes(&data).unwrap();
+        let data = self.account_data(&payment_feed_account).await?;
+        let payment_feed = PaymentFeed::from_bytes(&data)
+            .map_err(|e| RpcError::invalid_account(payment_feed_account, e))?;
         let user_wallet = self.signer();
         let issuance_account = find_issuance_pda(bond_account, bond.issuance_number).0;
         let payment_mint_account = payment_feed.payment_mint;
@@ -169,11 +259,9 @@ impl EtherfuseClient {
         }
 
         let sell_liquidity_account = find_sell_liquidity_pda(bond_account).0;
-        let sell_liuqidity_data = self
-            .rpc_client
-            .get_account_data(&sell_liquidity_account)
-            .await?;
-        let sell_liquidity = SellLiquidity::from_bytes(&sell_liuqidity_data).unwrap();
+        let sell_liuqidity_data = self.account_data(&sell_liquidity_account).await?;
+        let sell_liquidity = SellLiquidity::from_bytes(&sell_liuqidity_data)
+            .map_err(|e| RpcError::invalid_account(sell_liquidity_account, e))?;
         let sell_liquidity_token_account =
             get_associated_token_address(&sell_liquidity_account, &payment_feed.payment_mint);
         let ix_args = InstantBondRedemptionV2InstructionArgs { amount };
@@ -216,19 +304,43 @@ impl EtherfuseClient {
         &self,
         amount: u64,
         stablebond_mint: Pubkey,
-    ) -> Result<VersionedTransaction> {
+    ) -> ArbResult<VersionedTransaction> {
         let ix = self
             .instant_bond_redemption_ix(amount, stablebond_mint)
             .await?;
-        build_and_sign_tx(&self.rpc_client, &self.signer(), &[ix]).await
+        self.build_and_sign_tx(&[ix]).await
     }
 
-    pub async fn get_etherfuse_price(&self, stablebond_mint: &Pubkey) -> Result<f64> {
+    async fn build_and_sign_tx(&self, ixs: &[Instruction]) -> ArbResult<VersionedTransaction> {
+        let lookup_tables = match &self.lookup_table_manager {
+            Some(lookup_table_manager) => match lookup_table_manager.lookup_table_for(ixs).await {
+                Ok(lookup_table) => vec![lookup_table],
+                Err(e) => {
+                    error!(
+                        "Error preparing lookup table, building legacy transaction: {:?}",
+                        e
+                    );
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+        let signer = self.signer();
+        TransactionBuilder::new(&self.transaction_cache, &signer)
+            .with_ixs(ixs)
+            .with_lookup_tables(&lookup_tables)
+            .with_priority_fee_estimator(self.priority_fee_estimator.as_ref())
+            .build()
+            .await
+            .map_err(|e| ExecutionError::Build(e.to_string()).into())
+    }
+
+    pub async fn get_etherfuse_price(&self, stablebond_mint: &Pubkey) -> ArbResult<f64> {
         let url = format!(
             "{}/lookup/bonds/cost/{:?}",
             self.etherfuse_api_url, stablebond_mint
         );
-        let res: BondCostResponse = reqwest::get(url).await?.json().await?;
+        let res: BondCostResponse = self.get_json(url).await?;
         let token_value = res.bond_cost_in_payment_token;
 
         match self.get_etherfuse_exchange_rate(*stablebond_mint).await {
@@ -237,68 +349,106 @@ impl EtherfuseClient {
                 Ok(price_in_usd)
             }
             Err(e) => {
-                println!("Error getting etherfuse exchange rate: {:?}", e);
+                report_error("Error getting etherfuse exchange rate", &e);
                 Err(e)
             }
         }
     }
 
-    async fn get_etherfuse_exchange_rate(&self, stablebond_mint: Pubkey) -> Result<f64> {
-        let url = EXCHANGE_RATE_CONFIGS
+    pub async fn get_etherfuse_exchange_rate(&self, stablebond_mint: Pubkey) -> ArbResult<f64> {
+        let pair = EXCHANGE_RATE_CONFIGS
             .get(&stablebond_mint)
-            .ok_or_else(|| anyhow::anyhow!("Unsupported stablebond mint"))?;
+            .ok_or(VenueError::UnsupportedMint(stablebond_mint))?;
+        let url = format!("{}/lookup/exchange_rate/{}", self.etherfuse_api_url, pair);
 
-        let res: ExchangeRateResponse = reqwest::get(*url).await?.json().await?;
-        res.get_rate()
-            .ok_or_else(|| anyhow::anyhow!("No valid exchange rate found in response"))
+        let res: ExchangeRateResponse = self.get_json(url).await?;
+        Ok(res.get_rate().ok_or_else(|| {
+            VenueError::InvalidResponse("No valid exchange rate found in response".to_string())
+        })?)
     }
 
-    pub async fn fetch_sell_liquidity_usdc_amount(&self, stablebond_mint: &Pubkey) -> Result<u64> {
+    pub async fn fetch_sell_liquidity_usdc_amount(
+        &self,
+        stablebond_mint: &Pubkey,
+    ) -> ArbResult<u64> {
         let bond = find_bond_pda(*stablebond_mint).0;
         let usdc_token_account = get_associated_token_address(
             &find_sell_liquidity_pda(bond).0,
-            &Pubkey::from_str(&USDC_MINT).unwrap(),
+            &Pubkey::from_str(USDC_MINT).unwrap(),
         );
-        let usdc_token_account_data = self
-            .rpc_client
-            .get_account_data(&usdc_token_account)
-            .await?;
-        let usdc_token_account_info = TokenAccount::unpack(&usdc_token_account_data)?;
+        let usdc_token_account_data = self.account_data(&usdc_token_account).await?;
+        let usdc_token_account_info = TokenAccount::unpack(&usdc_token_account_data)
+            .map_err(|e| RpcError::invalid_account(usdc_token_account, e))?;
         Ok(usdc_token_account_info.amount)
     }
 
-    pub async fn fetch_payment_feed(&self, stablebond_mint: &Pubkey) -> Result<PaymentFeed> {
+    pub async fn fetch_payment_feed(&self, stablebond_mint: &Pubkey) -> ArbResult<PaymentFeed> {
         let bond = find_bond_pda(*stablebond_mint).0;
-        let data = self.rpc_client.get_account_data(&bond).await?;
-        let bond = Bond::from_bytes(&data).unwrap();
+        let data = self.account_data(&bond).await?;
+        let bond = Bond::from_bytes(&data).map_err(|e| RpcError::invalid_account(bond, e))?;
         let payment_feed_account = find_payment_feed_pda(bond.payment_feed_type).0;
-        let data = self
-            .rpc_client
-            .get_account_data(&payment_feed_account)
-            .await?;
-        let payment_feed = PaymentFeed::from_bytes(&data).unwrap();
+        let data = self.account_data(&payment_feed_account).await?;
+        let payment_feed = PaymentFeed::from_bytes(&data)
+            .map_err(|e| RpcError::invalid_account(payment_feed_account, e))?;
         Ok(payment_feed)
     }
 
     pub async fn fetch_purchase_liquidity_stablebond_amount(
         &self,
         stablebond_mint: &Pubkey,
-    ) -> Result<u64> {
+    ) -> ArbResult<u64> {
         let bond = find_bond_pda(*stablebond_mint).0;
-        let bond_account = self.rpc_client.get_account_data(&bond).await?;
-        let data = Bond::from_bytes(&bond_account)?;
+        let bond_account = self.account_data(&bond).await?;
+        let data =
+            Bond::from_bytes(&bond_account).map_err(|e| RpcError::invalid_account(bond, e))?;
         let issuance = find_issuance_pda(bond, data.issuance_number).0;
-        let data = self.rpc_client.get_account_data(&issuance).await?;
-        let issuance = Issuance::from_bytes(&data)?;
+        let data = self.account_data(&issuance).await?;
+        let issuance =
+            Issuance::from_bytes(&data).map_err(|e| RpcError::invalid_account(issuance, e))?;
         Ok(issuance.liquidity)
     }
 
+    /// Accounts holding the liquidity of `stablebond_mint`: the USDC its redemptions are paid
+    /// from and its current issuance, whose purchase liquidity it tracks.
+    pub async fn liquidity_accounts(&self, stablebond_mint: &Pubkey) -> ArbResult<Vec<Pubkey>> {
+        let bond = find_bond_pda(*stablebond_mint).0;
+        let data = self.account_data(&bond).await?;
+        let issuance_number = Bond::from_bytes(&data)
+            .map_err(|e| RpcError::invalid_account(bond, e))?
+            .issuance_number;
+        Ok(vec![
+            get_associated_token_address(
+                &find_sell_liquidity_pda(bond).0,
+                &Pubkey::from_str(USDC_MINT).unwrap(),
+            ),
+            find_issuance_pda(bond, issuance_number).0,
+        ])
+    }
+
+    /// The other accounts the market data of `stablebond_mint` is read from: the bond, its
+    /// payment feed and the oracle feeds that one prices off.
+    pub async fn market_accounts(&self, stablebond_mint: &Pubkey) -> ArbResult<Vec<Pubkey>> {
+        let bond = find_bond_pda(*stablebond_mint).0;
+        let data = self.account_data(&bond).await?;
+        let payment_feed_type = Bond::from_bytes(&data)
+            .map_err(|e| RpcError::invalid_account(bond, e))?
+            .payment_feed_type;
+        let payment_feed_account = find_payment_feed_pda(payment_feed_type).0;
+        let payment_feed = self.fetch_payment_feed(stablebond_mint).await?;
+        Ok([
+            bond,
+            payment_feed_account,
+            payment_feed.base_price_feed,
+            payment_feed.quote_price_feed,
+        ]
+        .into_iter()
+        .filter(|account| *account != Pubkey::default())
+        .collect())
+    }
+
     pub async fn has_kyc_account(&self, user_wallet: &Pubkey) -> bool {
         let kyc_account = find_kyc_pda(*user_wallet).0;
-        match self.rpc_client.get_account_data(&kyc_account).await {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        self.rpc_client.get_account_data(&kyc_account).await.is_ok()
     }
 }
 
@@ -0,0 +1,146 @@
+use hmac::{Hmac, Mac};
+use reqwest_middleware::ClientWithMiddleware;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use solana_sdk::signature::Signature;
+
+use crate::{
+    constants::WEBHOOK_SIGNATURE_HEADER, error_aggregator::report_error, http::default_http_client,
+    trade_journal::NewTrade,
+};
+
+/// `[webhook]` section of the config file. Events are only sent when it is present.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSinkConfig {
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature of each payload.
+    pub secret: String,
+}
+
+/// Structured event, serialized with its name in the `event` field.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    TickCompleted {
+        tick: u64,
+        mint: String,
+        trades: usize,
+    },
+    TradeSubmitted {
+        trade_id: String,
+        mint: String,
+        strategy: &'static str,
+        backend: &'static str,
+        usdc_amount: Decimal,
+        expected_profit_usd: Decimal,
+    },
+    TradeLanded {
+        trade_id: String,
+        mint: String,
+        expected_profit_usd: Decimal,
+        realized_profit_usd: Decimal,
+        signatures: Vec<String>,
+    },
+    RiskLimitTripped {
+        scope: String,
+        reason: String,
+    },
+}
+
+impl Event {
+    pub fn trade_submitted(trade: &NewTrade) -> Self {
+        Event::TradeSubmitted {
+            trade_id: trade.trade_id.clone(),
+            mint: trade.mint.to_string(),
+            strategy: trade.strategy,
+            backend: trade.backend,
+            usdc_amount: trade.usdc_amount,
+            expected_profit_usd: trade.expected_profit_usd,
+        }
+    }
+
+    pub fn trade_landed(
+        trade_id: &str,
+        mint: impl ToString,
+        expected_profit_usd: Decimal,
+        realized_profit_usd: Decimal,
+        signatures: &[Signature],
+    ) -> Self {
+        Event::TradeLanded {
+            trade_id: trade_id.to_string(),
+            mint: mint.to_string(),
+            expected_profit_usd,
+            realized_profit_usd,
+            signatures: signatures.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    timestamp: String,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// POSTs every event as JSON to a webhook, so risk dashboards and accounting can consume the
+/// bot's events without scraping its logs. Each payload is signed with HMAC-SHA256 over the
+/// raw body, hex encoded in the `X-Arb-Signature` header as `sha256=<signature>`.
+#[derive(Clone)]
+pub struct EventSink {
+    pub config: WebhookSinkConfig,
+    pub http_client: ClientWithMiddleware,
+}
+
+impl EventSink {
+    pub fn new(config: WebhookSinkConfig) -> Self {
+        Self {
+            config,
+            http_client: default_http_client(),
+        }
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// Sends `event` in the background.
+    pub fn emit(&self, event: Event) {
+        let body = match serde_json::to_vec(&Payload {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            event: &event,
+        }) {
+            Ok(body) => body,
+            Err(e) => return report_error("Error serializing event", e),
+        };
+        let signature = format!("sha256={}", sign(&self.config.secret, &body));
+        let request = self
+            .http_client
+            .post(&self.config.url)
+            .header("Content-Type", "application/json")
+            .header(WEBHOOK_SIGNATURE_HEADER, signature)
+            .body(body);
+        tokio::spawn(async move {
+            match request
+                .send()
+                .await
+                .map(|response| response.error_for_status())
+            {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => report_error("Error sending event to webhook", e),
+                Err(e) => report_error("Error sending event to webhook", e),
+            }
+        });
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of `body` keyed by `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
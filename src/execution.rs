@@ -0,0 +1,355 @@
+use enum_dispatch::enum_dispatch;
+use solana_client::nonblocking::tpu_client::TpuClient;
+use solana_program::native_token::LAMPORTS_PER_SOL;
+use solana_quic_client::{QuicConfig, QuicConnectionManager, QuicPool};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, signature::Signature, transaction::VersionedTransaction,
+};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::{
+    constants::{MAX_TIP_PROFIT_SHARE, TPU_CONFIRMATION_TIMEOUT_SECS, TPU_RESEND_INTERVAL_MS},
+    error::{ArbResult, ExecutionError},
+    jito::{BundleHandle, TipPercentile},
+    math,
+    profile::ActiveProfile,
+    profit_verification::ExpectedProfit,
+    providers::BundleSubmitter,
+    transaction::{priority_fee_lamports, send_txs_sequentially, SendConfig},
+};
+
+/// How a trade's transactions were handed off.
+#[derive(Debug)]
+pub enum Execution {
+    /// Accepted by the block engine, the outcome is reported through the Jito outcome channel.
+    Submitted(BundleHandle),
+    /// Landed and confirmed, in order.
+    Confirmed(Vec<Signature>),
+}
+
+// The futures are awaited on the mint workers' spawned tasks, which only the concrete
+// backends' need to be `Send` for.
+#[allow(async_fn_in_trait)]
+#[enum_dispatch]
+pub trait ExecutionBackend {
+    fn name(&self) -> &'static str;
+
+    /// Whether either all of the transactions land or none of them do.
+    fn is_atomic(&self) -> bool;
+
+    /// Whether the transactions are visible to anyone before they land, so the trade can be
+    /// front-run or sandwiched.
+    fn is_public(&self) -> bool;
+
+    /// Lamports paid on top of the base transaction fees to land `txs`: their priority fees,
+    /// plus the tip for bundles.
+    fn cost_lamports(&self, txs: &[VersionedTransaction], expected_profit: &ExpectedProfit) -> u64;
+
+    async fn execute(
+        &mut self,
+        txs: &[VersionedTransaction],
+        expected_profit: ExpectedProfit,
+    ) -> ArbResult<Execution>;
+}
+
+/// Sends the transactions one after the other through the RPC, each waiting for the previous
+/// one to confirm.
+#[derive(Clone)]
+pub struct RpcBackend {
+    pub rpc_client: Arc<RpcClient>,
+    pub send_config: SendConfig,
+}
+
+impl RpcBackend {
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self {
+            rpc_client,
+            send_config: SendConfig::default(),
+        }
+    }
+
+    pub fn with_send_config(mut self, send_config: SendConfig) -> Self {
+        self.send_config = send_config;
+        self
+    }
+}
+
+impl ExecutionBackend for RpcBackend {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    fn is_atomic(&self) -> bool {
+        false
+    }
+
+    fn is_public(&self) -> bool {
+        true
+    }
+
+    fn cost_lamports(
+        &self,
+        txs: &[VersionedTransaction],
+        _expected_profit: &ExpectedProfit,
+    ) -> u64 {
+        txs.iter().map(priority_fee_lamports).sum()
+    }
+
+    async fn execute(
+        &mut self,
+        txs: &[VersionedTransaction],
+        _expected_profit: ExpectedProfit,
+    ) -> ArbResult<Execution> {
+        let signatures = send_txs_sequentially(&self.rpc_client, txs, &self.send_config)
+            .await
+            .map_err(|e| ExecutionError::Send(format!("{:?}", e)))?;
+        Ok(Execution::Confirmed(signatures))
+    }
+}
+
+/// Submits the transactions as a Jito bundle, tipping at most a share of the expected profit.
+#[derive(Clone)]
+pub struct JitoBackend<B> {
+    pub jito_client: B,
+    pub profile: Option<ActiveProfile>,
+}
+
+impl<B: BundleSubmitter> JitoBackend<B> {
+    pub fn new(jito_client: B) -> Self {
+        Self {
+            jito_client,
+            profile: None,
+        }
+    }
+
+    /// Caps the tips at the active profile's share of the expected profit.
+    pub fn with_profile(mut self, profile: ActiveProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    fn max_tip_lamports(&self, expected_profit: &ExpectedProfit) -> u64 {
+        let max_tip_profit_share = self
+            .profile
+            .as_ref()
+            .map_or(MAX_TIP_PROFIT_SHARE, |profile| {
+                profile.get().max_tip_profit_share
+            });
+        (math::to_f64(expected_profit.profit_usd) * max_tip_profit_share
+            / expected_profit.sol_price
+            * LAMPORTS_PER_SOL as f64) as u64
+    }
+}
+
+impl<B: BundleSubmitter> ExecutionBackend for JitoBackend<B> {
+    fn name(&self) -> &'static str {
+        "jito"
+    }
+
+    fn is_atomic(&self) -> bool {
+        true
+    }
+
+    fn is_public(&self) -> bool {
+        false
+    }
+
+    fn cost_lamports(&self, txs: &[VersionedTransaction], expected_profit: &ExpectedProfit) -> u64 {
+        let tip_lamports = self
+            .jito_client
+            .tip_lamports(TipPercentile::P50)
+            .min(self.max_tip_lamports(expected_profit));
+        tip_lamports + txs.iter().map(priority_fee_lamports).sum::<u64>()
+    }
+
+    async fn execute(
+        &mut self,
+        txs: &[VersionedTransaction],
+        expected_profit: ExpectedProfit,
+    ) -> ArbResult<Execution> {
+        // Never tip away more than a share of the expected profit when escalating.
+        let max_tip_lamports = self.max_tip_lamports(&expected_profit);
+        let handle = self
+            .jito_client
+            .send_bundle(txs, max_tip_lamports, expected_profit)
+            .await
+            .map_err(ExecutionError::from)?;
+        Ok(Execution::Submitted(handle))
+    }
+}
+
+/// Sends the transactions straight to the TPU of the upcoming leaders, skipping the RPC's
+/// forwarding, and confirms each through the RPC before sending the next.
+#[derive(Clone)]
+pub struct TpuBackend {
+    pub rpc_client: Arc<RpcClient>,
+    pub tpu_client: Arc<TpuClient<QuicPool, QuicConnectionManager, QuicConfig>>,
+}
+
+impl TpuBackend {
+    pub async fn new(rpc_client: Arc<RpcClient>, websocket_url: &str) -> ArbResult<Self> {
+        let tpu_client =
+            TpuClient::new("arb", rpc_client.clone(), websocket_url, Default::default())
+                .await
+                .map_err(|e| {
+                    ExecutionError::Send(format!("Unable to connect to the TPU: {:?}", e))
+                })?;
+        Ok(Self {
+            rpc_client,
+            tpu_client: Arc::new(tpu_client),
+        })
+    }
+
+    /// Resends the transaction until it is confirmed, since TPU sends are fire-and-forget.
+    async fn send_and_confirm(&self, tx: &VersionedTransaction) -> ArbResult<Signature> {
+        let signature = tx.signatures[0];
+        let wire_tx = bincode::serialize(tx).map_err(|e| ExecutionError::Build(e.to_string()))?;
+        let start_time = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(TPU_CONFIRMATION_TIMEOUT_SECS);
+        while start_time.elapsed() < timeout {
+            if !self.tpu_client.send_wire_transaction(wire_tx.clone()).await {
+                warn!("Unable to send {} to the TPU, retrying", signature);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(TPU_RESEND_INTERVAL_MS)).await;
+            let status = self
+                .rpc_client
+                .get_signature_status_with_commitment(&signature, CommitmentConfig::confirmed())
+                .await?;
+            match status {
+                Some(Ok(())) => return Ok(signature),
+                Some(Err(e)) => {
+                    let message = format!("Transaction {} failed: {:?}", signature, e);
+                    return Err(ExecutionError::Send(message).into());
+                }
+                None => {}
+            }
+        }
+        Err(ExecutionError::Send(format!(
+            "Transaction {} not confirmed after {} seconds",
+            signature, TPU_CONFIRMATION_TIMEOUT_SECS
+        ))
+        .into())
+    }
+}
+
+impl ExecutionBackend for TpuBackend {
+    fn name(&self) -> &'static str {
+        "tpu"
+    }
+
+    fn is_atomic(&self) -> bool {
+        false
+    }
+
+    fn is_public(&self) -> bool {
+        true
+    }
+
+    fn cost_lamports(
+        &self,
+        txs: &[VersionedTransaction],
+        _expected_profit: &ExpectedProfit,
+    ) -> u64 {
+        txs.iter().map(priority_fee_lamports).sum()
+    }
+
+    async fn execute(
+        &mut self,
+        txs: &[VersionedTransaction],
+        _expected_profit: ExpectedProfit,
+    ) -> ArbResult<Execution> {
+        let mut signatures = Vec::with_capacity(txs.len());
+        for (i, tx) in txs.iter().enumerate() {
+            // Each leg depends on the previous one landing, so stop at the first failure.
+            let signature = self.send_and_confirm(tx).await.map_err(|e| {
+                ExecutionError::Send(format!(
+                    "Transaction {}/{} failed, aborting remaining transactions: {}",
+                    i + 1,
+                    txs.len(),
+                    e
+                ))
+            })?;
+            info!("Signature ({}/{}): {:?}", i + 1, txs.len(), signature);
+            signatures.push(signature);
+        }
+        Ok(Execution::Confirmed(signatures))
+    }
+}
+
+#[enum_dispatch(ExecutionBackend)]
+#[derive(Clone)]
+pub enum ExecutionBackendEnum {
+    Rpc(RpcBackend),
+    #[cfg(feature = "jito")]
+    Jito(JitoBackend<crate::jito::JitoClient>),
+    Tpu(TpuBackend),
+}
+
+/// Picks the backend for each trade: trades spanning several transactions need an atomic
+/// backend so that one leg can't land without the other, single transactions go through the
+/// cheapest one. Trades are only sent publicly, where they can be front-run, when public sends
+/// are allowed or no private backend was added. Ties go to the backend added first.
+#[derive(Clone, Default)]
+pub struct ExecutionPolicy {
+    backends: Vec<ExecutionBackendEnum>,
+    public_sends: bool,
+}
+
+impl ExecutionPolicy {
+    pub fn new() -> Self {
+        ExecutionPolicy {
+            backends: Vec::new(),
+            public_sends: false,
+        }
+    }
+
+    pub fn add_backend(&mut self, backend: ExecutionBackendEnum) -> &mut Self {
+        self.backends.push(backend);
+        self
+    }
+
+    /// Lets single transactions go through a public backend when it is the cheapest.
+    pub fn allow_public_sends(&mut self, public_sends: bool) -> &mut Self {
+        self.public_sends = public_sends;
+        self
+    }
+
+    pub fn select(
+        &mut self,
+        txs: &[VersionedTransaction],
+        expected_profit: &ExpectedProfit,
+    ) -> Result<&mut ExecutionBackendEnum, ExecutionError> {
+        if txs.len() > 1 {
+            return self
+                .backends
+                .iter_mut()
+                .find(|backend| backend.is_atomic())
+                .ok_or_else(|| {
+                    ExecutionError::NoBackend(format!(
+                        "an atomic trade of {} transactions",
+                        txs.len()
+                    ))
+                });
+        }
+        let public_sends =
+            self.public_sends || self.backends.iter().all(|backend| backend.is_public());
+        let mut cheapest: Option<(u64, &mut ExecutionBackendEnum)> = None;
+        for backend in self.backends.iter_mut() {
+            if backend.is_public() && !public_sends {
+                continue;
+            }
+            let cost = backend.cost_lamports(txs, expected_profit);
+            if cheapest
+                .as_ref()
+                .map_or(true, |(cheapest_cost, _)| cost < *cheapest_cost)
+            {
+                cheapest = Some((cost, backend));
+            }
+        }
+        cheapest
+            .map(|(_, backend)| backend)
+            .ok_or_else(|| ExecutionError::NoBackend("the trade".to_string()))
+    }
+}
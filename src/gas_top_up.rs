@@ -0,0 +1,240 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Signature};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::{
+    constants::{
+        DEFAULT_GAS_TOP_UP_MAX_DAILY_USDC, DEFAULT_GAS_TOP_UP_MIN_SOL,
+        DEFAULT_GAS_TOP_UP_SLIPPAGE_BPS, DEFAULT_GAS_TOP_UP_USDC, SOL_MINT, USDC_DECIMALS,
+        USDC_MINT,
+    },
+    jupiter::{JupiterClient, JupiterQuoteArgs},
+    math::{TokenAmountExt, UiAmountExt},
+    transaction::{send_txs_sequentially, SendConfig},
+    wsol::{unwrap_residual_wsol, wsol_balance},
+};
+
+/// `[gas_top_up]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GasTopUpConfig {
+    pub enabled: bool,
+    /// SOL balance under which USDC is swapped for SOL.
+    pub min_sol: f64,
+    /// USDC swapped per top-up.
+    pub usdc_amount: Decimal,
+    /// USDC swapped for SOL over a day at most, however low the balance gets.
+    pub max_daily_usdc: Decimal,
+    pub slippage_bps: u64,
+}
+
+impl Default for GasTopUpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_sol: DEFAULT_GAS_TOP_UP_MIN_SOL,
+            usdc_amount: DEFAULT_GAS_TOP_UP_USDC,
+            max_daily_usdc: DEFAULT_GAS_TOP_UP_MAX_DAILY_USDC,
+            slippage_bps: DEFAULT_GAS_TOP_UP_SLIPPAGE_BPS,
+        }
+    }
+}
+
+/// A swap of USDC for SOL that landed.
+#[derive(Clone, Debug)]
+pub struct TopUp {
+    pub usdc_amount: Decimal,
+    pub sol_amount: f64,
+    pub sol_balance: f64,
+    pub signatures: Vec<Signature>,
+}
+
+/// Swaps a little USDC for SOL on Jupiter when the wallet runs low on the SOL that pays for
+/// fees and tips, so the bot doesn't stall. The swaps are outside the arbitrage and have their
+/// own daily limit.
+#[derive(Clone)]
+pub struct GasTopUp {
+    pub config: GasTopUpConfig,
+    pub rpc_client: Arc<RpcClient>,
+    pub jupiter_client: JupiterClient,
+    pub wallet: Pubkey,
+    pub send_config: SendConfig,
+    spend: Arc<Mutex<TopUpSpend>>,
+}
+
+#[derive(Clone, Debug)]
+struct TopUpSpend {
+    day: NaiveDate,
+    usdc_amount: Decimal,
+}
+
+impl GasTopUp {
+    pub fn new(
+        config: GasTopUpConfig,
+        rpc_client: Arc<RpcClient>,
+        jupiter_client: JupiterClient,
+        wallet: Pubkey,
+    ) -> Self {
+        Self {
+            config,
+            rpc_client,
+            jupiter_client,
+            wallet,
+            send_config: SendConfig::default(),
+            spend: Arc::new(Mutex::new(TopUpSpend {
+                day: today(),
+                usdc_amount: Decimal::ZERO,
+            })),
+        }
+    }
+
+    pub fn with_send_config(mut self, send_config: SendConfig) -> Self {
+        self.send_config = send_config;
+        self
+    }
+
+    /// USDC to swap for SOL at `sol_balance`, if the balance is low and today's limit allows
+    /// a top-up.
+    pub fn top_up_usdc_amount(&self, sol_balance: f64) -> Option<Decimal> {
+        if !self.config.enabled || sol_balance >= self.config.min_sol {
+            return None;
+        }
+        let spent = self.spent_today();
+        if spent + self.config.usdc_amount > self.config.max_daily_usdc {
+            warn!(
+                "SOL balance of {} is low, but {} of the {} USDC daily top-up limit is spent",
+                sol_balance, spent, self.config.max_daily_usdc
+            );
+            return None;
+        }
+        Some(self.config.usdc_amount)
+    }
+
+    /// Swaps USDC for SOL when the wallet's SOL balance is under the threshold, after unwrapping
+    /// its wrapped SOL.
+    pub async fn top_up_if_low(&mut self) -> Result<Option<TopUp>> {
+        let mut lamports = self.rpc_client.get_balance(&self.wallet).await?;
+        let min_lamports = (self.config.min_sol * LAMPORTS_PER_SOL as f64) as u64;
+        // Wrapped SOL left behind by swaps pays for gas once unwrapped, without a swap.
+        if self.config.enabled
+            && lamports < min_lamports
+            && wsol_balance(&self.rpc_client, &self.wallet)
+                .await?
+                .is_some_and(|wsol_lamports| wsol_lamports > 0)
+        {
+            let signer = self.jupiter_client.signer();
+            unwrap_residual_wsol(
+                &self.jupiter_client.transaction_cache,
+                &signer,
+                &self.send_config,
+            )
+            .await?;
+            lamports = self.rpc_client.get_balance(&self.wallet).await?;
+        }
+        let sol_balance = lamports as f64 / LAMPORTS_PER_SOL as f64;
+        let Some(usdc_amount) = self.top_up_usdc_amount(sol_balance) else {
+            return Ok(None);
+        };
+        info!(
+            sol_balance,
+            %usdc_amount,
+            "SOL balance low, swapping USDC for SOL"
+        );
+
+        let quote = self
+            .jupiter_client
+            .get_jupiter_quote(JupiterQuoteArgs {
+                input_mint: Pubkey::from_str(USDC_MINT)?,
+                output_mint: Pubkey::from_str(SOL_MINT)?,
+                amount: usdc_amount.to_token_amount_floor(USDC_DECIMALS)?,
+                slippage_bps: Some(self.config.slippage_bps),
+                only_direct_routes: false,
+            })
+            .await?;
+        let sol_amount = quote.out_amount as f64 / LAMPORTS_PER_SOL as f64;
+        let tx = self.jupiter_client.jupiter_swap_tx(quote.clone()).await?;
+        // Counted once sent, since it may land even if its confirmation times out.
+        self.record(quote.in_amount.to_ui_decimal(USDC_DECIMALS));
+        let signatures = send_txs_sequentially(&self.rpc_client, &[tx], &self.send_config)
+            .await
+            .map_err(|e| anyhow!("Error swapping USDC for SOL: {}", e))?;
+        Ok(Some(TopUp {
+            usdc_amount: quote.in_amount.to_ui_decimal(USDC_DECIMALS),
+            sol_amount,
+            sol_balance,
+            signatures,
+        }))
+    }
+
+    fn spent_today(&self) -> Decimal {
+        let mut spend = self.spend.lock().unwrap();
+        roll_over(&mut spend);
+        spend.usdc_amount
+    }
+
+    fn record(&self, usdc_amount: Decimal) {
+        let mut spend = self.spend.lock().unwrap();
+        roll_over(&mut spend);
+        spend.usdc_amount += usdc_amount;
+    }
+}
+
+fn roll_over(spend: &mut TopUpSpend) {
+    let today = today();
+    if spend.day != today {
+        *spend = TopUpSpend {
+            day: today,
+            usdc_amount: Decimal::ZERO,
+        };
+    }
+}
+
+fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gas_top_up(config: GasTopUpConfig) -> GasTopUp {
+        let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+        let jupiter_client = JupiterClient::new(
+            rpc_client.clone(),
+            "http://localhost".to_string(),
+            "keypair.json".to_string(),
+            crate::rate_limiter::RateLimiter::new(1, 1),
+        );
+        GasTopUp::new(config, rpc_client, jupiter_client, Pubkey::new_unique())
+    }
+
+    #[test]
+    fn tops_up_under_the_threshold_within_the_daily_limit() {
+        let gas_top_up = gas_top_up(GasTopUpConfig {
+            enabled: true,
+            min_sol: 0.05,
+            usdc_amount: Decimal::from(20),
+            max_daily_usdc: Decimal::from(50),
+            ..Default::default()
+        });
+
+        assert_eq!(gas_top_up.top_up_usdc_amount(0.1), None);
+        assert_eq!(gas_top_up.top_up_usdc_amount(0.01), Some(Decimal::from(20)));
+        gas_top_up.record(Decimal::from(20));
+        gas_top_up.record(Decimal::from(20));
+        assert_eq!(gas_top_up.top_up_usdc_amount(0.01), None);
+    }
+
+    #[test]
+    fn never_tops_up_when_disabled() {
+        let gas_top_up = gas_top_up(GasTopUpConfig::default());
+
+        assert_eq!(gas_top_up.top_up_usdc_amount(0.0), None);
+    }
+}
@@ -0,0 +1,282 @@
+use anyhow::{anyhow, Result};
+use futures::{channel::mpsc, SinkExt, StreamExt};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tonic::{
+    client::Grpc,
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    metadata::MetadataValue,
+    transport::{Channel, ClientTlsConfig},
+    Request,
+};
+use tracing::{info, warn};
+
+use crate::{account_cache::AccountCache, constants::GEYSER_RECONNECT_DELAY_SECS};
+
+/// Streams the watched accounts of an `AccountCache` from a Yellowstone gRPC (Geyser) endpoint,
+/// so the market data sees liquidity refills and oracle updates as they land instead of on the
+/// next poll. Reconnects for as long as it runs, the cache falling back to the RPC meanwhile.
+#[derive(Clone)]
+pub struct GeyserSubscriber {
+    pub url: String,
+    pub x_token: Option<String>,
+    pub account_cache: AccountCache,
+}
+
+impl GeyserSubscriber {
+    pub fn new(url: String, account_cache: AccountCache) -> Self {
+        Self {
+            url,
+            x_token: None,
+            account_cache,
+        }
+    }
+
+    /// Authenticates with the endpoint's `x-token`.
+    pub fn with_x_token(mut self, x_token: Option<String>) -> Self {
+        self.x_token = x_token;
+        self
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.subscribe().await {
+                    warn!("Geyser stream from {} failed: {:?}", self.url, e);
+                }
+                self.account_cache.set_live(false);
+                tokio::time::sleep(Duration::from_secs(GEYSER_RECONNECT_DELAY_SECS)).await;
+            }
+        })
+    }
+
+    /// Subscribes to the watched accounts and caches their updates until the stream ends.
+    async fn subscribe(&self) -> Result<()> {
+        let mut endpoint = Channel::from_shared(self.url.clone())?;
+        if self.url.starts_with("https") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+        }
+        let channel = endpoint.connect().await?;
+
+        let accounts = self.account_cache.watched();
+        let (mut requests, receiver) = mpsc::unbounded();
+        requests.send(subscribe_request(&accounts)).await?;
+        let mut request = Request::new(receiver);
+        if let Some(x_token) = &self.x_token {
+            request
+                .metadata_mut()
+                .insert("x-token", MetadataValue::try_from(x_token.as_str())?);
+        }
+        let mut grpc = Grpc::new(channel);
+        grpc.ready().await?;
+        let mut updates = grpc
+            .streaming(
+                request,
+                PathAndQuery::from_static("/geyser.Geyser/Subscribe"),
+                ProstCodec::<proto::SubscribeRequest, proto::SubscribeUpdate>::default(),
+            )
+            .await?
+            .into_inner();
+        info!(
+            "Streaming {} accounts from Geyser at {}",
+            accounts.len(),
+            self.url
+        );
+        self.account_cache.set_live(true);
+
+        while let Some(update) = updates.next().await {
+            if let Some(ping) = handle_update(&self.account_cache, update?)? {
+                requests.send(ping).await?;
+            }
+        }
+        Err(anyhow!("Stream closed by the server"))
+    }
+}
+
+fn subscribe_request(accounts: &[Pubkey]) -> proto::SubscribeRequest {
+    proto::SubscribeRequest {
+        accounts: HashMap::from([(
+            "arb".to_string(),
+            proto::SubscribeRequestFilterAccounts {
+                account: accounts.iter().map(Pubkey::to_string).collect(),
+                owner: Vec::new(),
+            },
+        )]),
+        commitment: Some(proto::CommitmentLevel::Confirmed as i32),
+        ping: None,
+    }
+}
+
+/// Caches an account update. Returns the request answering the server's pings, which closes
+/// idle streams otherwise.
+fn handle_update(
+    account_cache: &AccountCache,
+    update: proto::SubscribeUpdate,
+) -> Result<Option<proto::SubscribeRequest>> {
+    match update.update_oneof {
+        Some(proto::UpdateOneof::Account(update)) => {
+            let info = update
+                .account
+                .ok_or_else(|| anyhow!("Account update without an account"))?;
+            let pubkey = Pubkey::try_from(info.pubkey.as_slice())
+                .map_err(|_| anyhow!("Invalid pubkey in account update"))?;
+            let owner = Pubkey::try_from(info.owner.as_slice())
+                .map_err(|_| anyhow!("Invalid owner in account update"))?;
+            account_cache.update(
+                pubkey,
+                update.slot,
+                Account {
+                    lamports: info.lamports,
+                    data: info.data,
+                    owner,
+                    executable: info.executable,
+                    rent_epoch: info.rent_epoch,
+                },
+            );
+            Ok(None)
+        }
+        Some(proto::UpdateOneof::Ping(_)) => Ok(Some(proto::SubscribeRequest {
+            ping: Some(proto::SubscribeRequestPing { id: 1 }),
+            ..Default::default()
+        })),
+        _ => Ok(None),
+    }
+}
+
+/// Hand-written messages for the subset of the Yellowstone `geyser` proto that we use.
+pub mod proto {
+    use std::collections::HashMap;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum CommitmentLevel {
+        Processed = 0,
+        Confirmed = 1,
+        Finalized = 2,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequest {
+        #[prost(map = "string, message", tag = "1")]
+        pub accounts: HashMap<String, SubscribeRequestFilterAccounts>,
+        #[prost(enumeration = "CommitmentLevel", optional, tag = "6")]
+        pub commitment: Option<i32>,
+        #[prost(message, optional, tag = "9")]
+        pub ping: Option<SubscribeRequestPing>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequestFilterAccounts {
+        #[prost(string, repeated, tag = "2")]
+        pub account: Vec<String>,
+        #[prost(string, repeated, tag = "3")]
+        pub owner: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequestPing {
+        #[prost(int32, tag = "1")]
+        pub id: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdate {
+        #[prost(string, repeated, tag = "1")]
+        pub filters: Vec<String>,
+        #[prost(oneof = "UpdateOneof", tags = "2, 6, 9")]
+        pub update_oneof: Option<UpdateOneof>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum UpdateOneof {
+        #[prost(message, tag = "2")]
+        Account(SubscribeUpdateAccount),
+        #[prost(message, tag = "6")]
+        Ping(SubscribeUpdatePing),
+        #[prost(message, tag = "9")]
+        Pong(SubscribeUpdatePong),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdateAccount {
+        #[prost(message, optional, tag = "1")]
+        pub account: Option<SubscribeUpdateAccountInfo>,
+        #[prost(uint64, tag = "2")]
+        pub slot: u64,
+        #[prost(bool, tag = "3")]
+        pub is_startup: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdateAccountInfo {
+        #[prost(bytes = "vec", tag = "1")]
+        pub pubkey: Vec<u8>,
+        #[prost(uint64, tag = "2")]
+        pub lamports: u64,
+        #[prost(bytes = "vec", tag = "3")]
+        pub owner: Vec<u8>,
+        #[prost(bool, tag = "4")]
+        pub executable: bool,
+        #[prost(uint64, tag = "5")]
+        pub rent_epoch: u64,
+        #[prost(bytes = "vec", tag = "6")]
+        pub data: Vec<u8>,
+        #[prost(uint64, tag = "7")]
+        pub write_version: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdatePing {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdatePong {
+        #[prost(int32, tag = "1")]
+        pub id: i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn caches_account_updates_and_answers_pings() {
+        let account = Pubkey::new_unique();
+        let account_cache = AccountCache::new();
+        account_cache.watch(&[account]);
+        account_cache.set_live(true);
+        let update = proto::SubscribeUpdate {
+            filters: vec!["arb".to_string()],
+            update_oneof: Some(proto::UpdateOneof::Account(proto::SubscribeUpdateAccount {
+                account: Some(proto::SubscribeUpdateAccountInfo {
+                    pubkey: account.to_bytes().to_vec(),
+                    lamports: 2_039_280,
+                    owner: spl_token::id().to_bytes().to_vec(),
+                    data: vec![1, 2, 3],
+                    ..Default::default()
+                }),
+                slot: 42,
+                is_startup: false,
+            })),
+        };
+        // As received off the wire.
+        let update = proto::SubscribeUpdate::decode(update.encode_to_vec().as_slice()).unwrap();
+
+        assert_eq!(handle_update(&account_cache, update).unwrap(), None);
+        let (slot, cached) = account_cache.get(&account).unwrap();
+        assert_eq!(slot, 42);
+        assert_eq!(cached.data, vec![1, 2, 3]);
+        assert_eq!(cached.owner, spl_token::id());
+
+        let ping = proto::SubscribeUpdate {
+            filters: Vec::new(),
+            update_oneof: Some(proto::UpdateOneof::Ping(proto::SubscribeUpdatePing {})),
+        };
+        let pong = handle_update(&account_cache, ping).unwrap().unwrap();
+        assert!(pong.ping.is_some() && pong.accounts.is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use reqwest::{header::HeaderMap, Method, Request, Response, StatusCode};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
+use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use task_local_extensions::Extensions;
+use tokio::sync::broadcast;
+use tracing::Instrument;
+
+use crate::{
+    constants::{
+        DEFAULT_HTTP_CONNECT_TIMEOUT_SECS, DEFAULT_HTTP_MAX_RETRIES, DEFAULT_HTTP_TIMEOUT_SECS,
+    },
+    rate_limiter::RateLimiter,
+};
+
+lazy_static! {
+    /// Client used by HTTP clients that weren't given one built from the config file.
+    static ref DEFAULT_HTTP_CLIENT: ClientWithMiddleware =
+        build_http_client(&HttpConfig::default(), None).expect("Unable to build HTTP client");
+}
+
+/// `[http]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Timeout of a single attempt, from connecting to reading the whole body.
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// Retries on connection errors, timeouts, 429s and 5xx responses, with exponential
+    /// backoff.
+    pub max_retries: u32,
+    /// HTTP(S) or SOCKS5 proxy URL, e.g. `socks5://127.0.0.1:1080`.
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: DEFAULT_HTTP_TIMEOUT_SECS,
+            connect_timeout_secs: DEFAULT_HTTP_CONNECT_TIMEOUT_SECS,
+            max_retries: DEFAULT_HTTP_MAX_RETRIES,
+            proxy: None,
+        }
+    }
+}
+
+/// HTTP client for the Jupiter, Etherfuse, Jito and Coingecko clients, so they all get the
+/// same timeouts, retries, proxy and tracing. 429 responses tighten `rate_limiter`.
+pub fn build_http_client(
+    config: &HttpConfig,
+    rate_limiter: Option<RateLimiter>,
+) -> Result<ClientWithMiddleware> {
+    let mut client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs));
+    if let Some(proxy) = &config.proxy {
+        client = client.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(config.max_retries);
+    let mut client = ClientBuilder::new(client.build()?)
+        .with(TracingMiddleware)
+        .with(CoalescingMiddleware::default())
+        .with(RetryTransientMiddleware::new_with_policy(retry_policy));
+    if let Some(rate_limiter) = rate_limiter {
+        // Below the retries, so that every rate-limited attempt is fed back.
+        client = client.with(RateLimitFeedbackMiddleware { rate_limiter });
+    }
+    Ok(client.build())
+}
+
+pub fn default_http_client() -> ClientWithMiddleware {
+    DEFAULT_HTTP_CLIENT.clone()
+}
+
+/// Wraps each request, retries included, in a span recording its status and latency.
+struct TracingMiddleware;
+
+#[async_trait]
+impl Middleware for TracingMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let span = tracing::info_span!(
+            "http_request",
+            method = %req.method(),
+            host = req.url().host_str().unwrap_or_default(),
+            path = req.url().path(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        let started_at = Instant::now();
+        let response = next.run(req, extensions).instrument(span.clone()).await;
+        span.record("latency_ms", started_at.elapsed().as_millis() as u64);
+        match &response {
+            Ok(response) => {
+                span.record("status", response.status().as_u16());
+            }
+            Err(e) => {
+                span.in_scope(|| tracing::warn!(error = %e, "HTTP request failed"));
+            }
+        }
+        response
+    }
+}
+
+/// Tightens the endpoint's rate limiter whenever it answers with a 429.
+struct RateLimitFeedbackMiddleware {
+    rate_limiter: RateLimiter,
+}
+
+#[async_trait]
+impl Middleware for RateLimitFeedbackMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let response = next.run(req, extensions).await;
+        if let Ok(response) = &response {
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                self.rate_limiter.record_rate_limited();
+            }
+        }
+        response
+    }
+}
+
+/// Response of a coalesced request, buffered so it can be handed to every caller.
+#[derive(Clone)]
+struct BufferedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl From<BufferedResponse> for Response {
+    fn from(buffered: BufferedResponse) -> Self {
+        let mut response = http::Response::new(buffered.body);
+        *response.status_mut() = buffered.status;
+        *response.headers_mut() = buffered.headers;
+        Response::from(response)
+    }
+}
+
+type InflightRequests = HashMap<String, broadcast::Sender<Result<BufferedResponse, String>>>;
+
+/// Collapses identical GET requests made while one is already in flight, e.g. several mints
+/// asking for the same exchange rate, into that request and shares its response.
+#[derive(Default)]
+struct CoalescingMiddleware {
+    inflight: Mutex<InflightRequests>,
+}
+
+#[async_trait]
+impl Middleware for CoalescingMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if req.method() != Method::GET {
+            return next.run(req, extensions).await;
+        }
+
+        let key = req.url().to_string();
+        let mut receiver = {
+            let mut inflight = self.inflight.lock().unwrap();
+            match inflight.get(&key) {
+                Some(sender) => Some(sender.subscribe()),
+                None => {
+                    inflight.insert(key.clone(), broadcast::channel(1).0);
+                    None
+                }
+            }
+        };
+        if let Some(receiver) = receiver.as_mut() {
+            return match receiver.recv().await {
+                Ok(Ok(buffered)) => Ok(buffered.into()),
+                Ok(Err(e)) => Err(reqwest_middleware::Error::Middleware(anyhow::anyhow!(e))),
+                Err(e) => Err(reqwest_middleware::Error::Middleware(anyhow::anyhow!(
+                    "Coalesced request to {} was dropped: {}",
+                    key,
+                    e
+                ))),
+            };
+        }
+
+        // Stops followers from waiting forever if this request is cancelled.
+        let mut leader = InflightGuard {
+            inflight: &self.inflight,
+            key: &key,
+            removed: false,
+        };
+        let result = match next.run(req, extensions).await {
+            Ok(response) => {
+                let status = response.status();
+                let headers = response.headers().clone();
+                response
+                    .bytes()
+                    .await
+                    .map(|body| BufferedResponse {
+                        status,
+                        headers,
+                        body: body.to_vec(),
+                    })
+                    .map_err(reqwest_middleware::Error::from)
+            }
+            Err(e) => Err(e),
+        };
+        // Removed before sending, so that every caller that subscribed gets the response and
+        // later callers start a new request.
+        if let Some(sender) = leader.remove() {
+            let _ = sender.send(
+                result
+                    .as_ref()
+                    .map(|buffered| buffered.clone())
+                    .map_err(|e| e.to_string()),
+            );
+        }
+        result.map(Response::from)
+    }
+}
+
+struct InflightGuard<'a> {
+    inflight: &'a Mutex<InflightRequests>,
+    key: &'a str,
+    removed: bool,
+}
+
+impl InflightGuard<'_> {
+    fn remove(&mut self) -> Option<broadcast::Sender<Result<BufferedResponse, String>>> {
+        self.removed = true;
+        self.inflight.lock().unwrap().remove(self.key)
+    }
+}
+
+impl Drop for InflightGuard<'_> {
+    fn drop(&mut self) {
+        // A later request for the same URL may have taken the slot since.
+        if !self.removed {
+            self.remove();
+        }
+    }
+}
@@ -1,151 +1,71 @@
-#![allow(dead_code)]
-use crate::transaction::build_and_sign_tx;
+use crate::constants::SOL_DECIMALS;
+use crate::math::UiAmountExt;
+use crate::profit_verification::ExpectedProfit;
+use crate::rate_limiter::RateLimiter;
 use anyhow::Result;
-use base58::ToBase58;
-use jsonrpsee::core::client::ClientT;
-use jsonrpsee::http_client::HttpClient;
-use jsonrpsee::rpc_params;
-use serde::Deserialize;
-use solana_program::native_token::LAMPORTS_PER_SOL;
-use solana_rpc_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::{read_keypair_file, Keypair};
-use solana_sdk::signer::Signer;
-use solana_sdk::system_instruction;
-use solana_sdk::transaction::VersionedTransaction;
-use std::sync::Arc;
-
-#[derive(Clone)]
-pub struct JitoClient {
-    pub rpc_client: Arc<RpcClient>,
-    pub wss_client: Arc<std::sync::RwLock<u64>>,    
-    pub jsonrpc_client: HttpClient,
-    pub keypair_filepath: String,
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Signature;
+
+// The block engine client needs the `jito` feature, the types it shares with the execution
+// backends and the mocks don't.
+#[cfg(feature = "jito")]
+mod client;
+
+#[cfg(feature = "jito")]
+pub use client::{JitoClient, TipFloor};
+
+/// Latest landed tips, from Jito's tip floor API at `url`.
+pub async fn fetch_tip_floor(
+    url: &str,
+    http_client: &ClientWithMiddleware,
+    rate_limiter: Option<&RateLimiter>,
+) -> Result<Tip> {
+    if let Some(rate_limiter) = rate_limiter {
+        rate_limiter.wait_if_needed().await;
+    }
+    let tips: Vec<Tip> = http_client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    tips.into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Failed to get jito tip"))
 }
 
-impl JitoClient {
-    pub fn new(
-        rpc_client: Arc<RpcClient>,
-        wss_client: Arc<std::sync::RwLock<u64>>,
-        jsonrpc_client: HttpClient,
-        keypair_filepath: String,
-    ) -> Self {
-        Self {
-            rpc_client,
-            keypair_filepath,
-            wss_client,
-            jsonrpc_client,
-        }
-    }
+/// Reasons the block engine refused a bundle. A bundle that is accepted but loses the
+/// auction is not an error, it's reported as a `BundleOutcome` that didn't land.
+#[derive(thiserror::Error, Debug)]
+pub enum JitoError {
+    #[error("rate limited by the block engine: {0}")]
+    RateLimited(String),
 
-    pub fn signer(&self) -> Keypair {
-        read_keypair_file(&self.keypair_filepath).expect("Failed to load keypair")
-    }
+    #[error("bundle invalid: {0}")]
+    BundleInvalid(String),
 
-    pub async fn send_bundle(&mut self, txs: &[VersionedTransaction]) -> Result<()> {
-        let jito_tip = self.wss_client.read().unwrap();
-
-        let tippers: Vec<String> = self
-            .jsonrpc_client
-            .request("getTipAccounts", rpc_params![""])
-            .await?;
-
-        let tip_ix = system_instruction::transfer(
-            &self.signer().pubkey(),
-            &Pubkey::try_from(tippers[0].to_string().as_str()).unwrap(),
-            *jito_tip,
-        );
-        // print amount in sol not lamports
-        println!("SOL (Jito) tip: {:?}", (*jito_tip as f64) / (LAMPORTS_PER_SOL as f64));
-        let tip_tx = build_and_sign_tx(&self.rpc_client, &self.signer(), &[tip_ix]).await?;
-
-        let txs: Vec<String> = [txs, &[tip_tx]]
-            .concat()
-            .iter()
-            .map(|tx| bincode::serialize(tx).unwrap().to_base58())
-            .collect::<Vec<String>>();
-
-        let params = rpc_params![txs];
-        let resp: Result<String, _> = self.jsonrpc_client.request("sendBundle", params).await;
-        match resp {
-            Ok(bundle) => {
-                let now = chrono::Local::now();
-                println!(
-                    "[{}] https://explorer.jito.wtf/bundle/{bundle}",
-                    now.format("%Y-%m-%d %H:%M:%S")
-                );
-                match self.check_bundle_status(&bundle).await {
-                    Ok(BundleStatusEnum::Landed) => println!("Bundle landed successfully"),
-                    Ok(BundleStatusEnum::Failed) => println!("Bundle failed to land"),
-                    Ok(BundleStatusEnum::Invalid) => println!("Bundle invalid"),
-                    Ok(BundleStatusEnum::Pending) => println!("Bundle pending"),
-                    Ok(BundleStatusEnum::Unknown) => println!("Bundle unknown"),
-                    Ok(BundleStatusEnum::Timeout) => println!("Bundle timeout"),
-                    Err(e) => eprintln!("Error checking bundle status: {:?}", e),
-                }
-            }
-            Err(err) => {
-                eprintln!("Error: {:?}", err);
-            }
-        }
-        Ok(())
-    }
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
 
-    async fn check_bundle_status(&self, bundle_id: &str) -> Result<BundleStatusEnum> {
-        let start_time = std::time::Instant::now();
-        let timeout = std::time::Duration::from_secs(30);
-
-        while start_time.elapsed() < timeout {
-            let params = rpc_params![[bundle_id]];
-            let response: Option<BundleStatusResponse> = self
-                .jsonrpc_client
-                .request("getInflightBundleStatuses", params)
-                .await?;
-
-            if let Some(resp) = response {
-                if let Some(status) = resp.value.first() {
-                    match status.status.as_str() {
-                        "Landed" => return Ok(BundleStatusEnum::Landed),
-                        "Failed" => return Ok(BundleStatusEnum::Failed),
-                        "Pending" | "Invalid" => {
-                            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                            if start_time.elapsed() >= timeout {
-                                return Ok(BundleStatusEnum::Timeout);
-                            }
-                            continue;
-                        }
-                        _ => {
-                            eprintln!("Unknown status: {}", status.status);
-                            return Ok(BundleStatusEnum::Unknown);
-                        }
-                    }
-                }
-            }
-
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
+    #[error("block engine unavailable: {0}")]
+    Unavailable(String),
 
-        Ok(BundleStatusEnum::Timeout)
-    }
+    #[error("stale blockhash: {0}")]
+    StaleBlockhash(String),
 
-    pub async fn get_jito_tip(&self) -> Result<u64> {
-        let client = reqwest::Client::new();
-        if let Ok(response) = client
-            .get("https://bundles.jito.wtf/api/v1/bundles/tip_floor")
-            .send()
-            .await
-        {
-            if let Ok(tips) = response.json::<Vec<Tip>>().await {
-                for item in tips {
-                    return Ok((item.ema_landed_tips_50th_percentile * (10_f64).powf(9.0)) as u64);
-                }
-            }
-        }
-        Err(anyhow::anyhow!("Failed to get jito tip"))
-    }
+    #[error("daily tip budget of {0} SOL exhausted")]
+    TipBudgetExhausted(f64),
+
+    #[error("quote is {0}ms old, over the latency budget of {1}ms")]
+    LatencyBudgetExceeded(u128, u128),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Tip {
     pub time: String,
     pub landed_tips_25th_percentile: f64,
@@ -156,25 +76,66 @@ pub struct Tip {
     pub ema_landed_tips_50th_percentile: f64,
 }
 
-#[derive(Debug, Deserialize)]
-struct BundleStatus {
-    bundle_id: String,
-    status: String,
-    landed_slot: Option<u64>,
+impl Tip {
+    pub fn lamports(&self, percentile: TipPercentile) -> u64 {
+        let sol = match percentile {
+            TipPercentile::P25 => self.landed_tips_25th_percentile,
+            TipPercentile::P50 => self.landed_tips_50th_percentile,
+            TipPercentile::P75 => self.landed_tips_75th_percentile,
+            TipPercentile::P95 => self.landed_tips_95th_percentile,
+            TipPercentile::P99 => self.landed_tips_99th_percentile,
+        };
+        // Rounded up, as a tip below the floor is less likely to land. The API's tips are
+        // finite and non-negative.
+        sol.to_token_amount_ceil(SOL_DECIMALS).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum TipPercentile {
+    P25,
+    P50,
+    P75,
+    P95,
+    P99,
+}
+
+impl TipPercentile {
+    pub fn next(self) -> Option<Self> {
+        match self {
+            TipPercentile::P25 => Some(TipPercentile::P50),
+            TipPercentile::P50 => Some(TipPercentile::P75),
+            TipPercentile::P75 => Some(TipPercentile::P95),
+            TipPercentile::P95 => Some(TipPercentile::P99),
+            TipPercentile::P99 => None,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct BundleStatusResponse {
-    context: Context,
-    value: Vec<BundleStatus>,
+#[derive(Clone, Debug)]
+pub struct BundleHandle {
+    pub bundle_id: String,
+    pub submitted_at: std::time::Instant,
+    /// Signatures of the bundle's transactions as submitted, tip included.
+    pub signatures: Vec<Signature>,
 }
 
-#[derive(Debug, Deserialize)]
-struct Context {
-    slot: u64,
+#[derive(Clone, Debug)]
+pub struct BundleOutcome {
+    /// Id of the last submission, which differs from `submitted_bundle_id` when the bundle was
+    /// resubmitted with a higher tip.
+    pub bundle_id: String,
+    pub submitted_bundle_id: String,
+    pub status: BundleStatusEnum,
+    pub landed_slot: Option<u64>,
+    pub signatures: Vec<Signature>,
+    pub finalized: bool,
+    pub tip_lamports: u64,
+    pub expected_profit: Option<ExpectedProfit>,
 }
 
-enum BundleStatusEnum {
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum BundleStatusEnum {
     Landed,
     Failed,
     Pending,
@@ -183,3 +144,10 @@ enum BundleStatusEnum {
     Timeout,
 }
 
+impl BundleStatusEnum {
+    /// Whether a higher tip could land a bundle that ended this way, i.e. it was outbid or
+    /// timed out. Invalid bundles are rejected again whatever the tip.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, BundleStatusEnum::Failed | BundleStatusEnum::Timeout)
+    }
+}
@@ -0,0 +1,811 @@
+use super::{
+    fetch_tip_floor, BundleHandle, BundleOutcome, BundleStatusEnum, JitoError, Tip, TipPercentile,
+};
+use crate::bundle_stats::BundleStats;
+use crate::constants::{
+    BUNDLE_RETRIES_PER_TIP_PERCENTILE, BUNDLE_VALIDITY_WINDOW_SECS, DEFAULT_MAX_BLOCKHASH_AGE,
+    DEFAULT_MIN_TIP_LAMPORTS, JITO_TIP_FLOOR_URL, MAX_BUNDLE_TXS, SOL_DECIMALS,
+    TIP_FLOOR_RETRY_SECS, TIP_FLOOR_TTL_SECS,
+};
+use crate::error_aggregator::report_error;
+use crate::http::default_http_client;
+use crate::jito_grpc::{BundleResults, JitoGrpcClient};
+use crate::leader_schedule::LeaderTracker;
+use crate::math::UiAmountExt;
+use crate::priority_fee::PriorityFeeEstimator;
+use crate::profit_verification::ExpectedProfit;
+use crate::rate_limiter::RateLimiter;
+use crate::tip_budget::TipBudget;
+use crate::trade_journal::TradeJournal;
+use crate::transaction::{
+    append_ixs_to_tx, build_and_sign_tx, merge_txs, refresh_stale_txs, validate_tx_size,
+    TransactionCache,
+};
+use anyhow::Result;
+use base58::ToBase58;
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::http_client::HttpClient;
+use jsonrpsee::rpc_params;
+use rand::seq::SliceRandom;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use solana_program::native_token::LAMPORTS_PER_SOL;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature};
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::VersionedTransaction;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{error, info, info_span, warn, Instrument};
+
+#[derive(Clone)]
+pub struct JitoClient {
+    pub rpc_client: Arc<RpcClient>,
+    pub wss_client: Arc<std::sync::RwLock<Option<Tip>>>,
+    pub jsonrpc_client: HttpClient,
+    pub keypair_filepath: String,
+    pub tip_accounts: Vec<Pubkey>,
+    pub outcome_sender: UnboundedSender<BundleOutcome>,
+    pub grpc_client: Option<JitoGrpcClient>,
+    pub bundle_results: Option<BundleResults>,
+    pub tip_budget: TipBudget,
+    pub bundle_stats: BundleStats,
+    pub tip_floor: Arc<std::sync::RwLock<Option<TipFloor>>>,
+    pub max_blockhash_age: u64,
+    pub min_tip_lamports: u64,
+    pub priority_fee_estimator: Option<PriorityFeeEstimator>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub http_client: ClientWithMiddleware,
+    pub tip_floor_url: String,
+    pub latency_budget: Option<Duration>,
+    pub leader_tracker: Option<LeaderTracker>,
+    pub trade_journal: Option<TradeJournal>,
+    pub transaction_cache: TransactionCache,
+}
+
+pub struct TipFloor {
+    pub tip: Tip,
+    pub fetched_at: std::time::Instant,
+}
+
+impl JitoClient {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        wss_client: Arc<std::sync::RwLock<Option<Tip>>>,
+        jsonrpc_client: HttpClient,
+        keypair_filepath: String,
+        outcome_sender: UnboundedSender<BundleOutcome>,
+        tip_budget: TipBudget,
+        bundle_stats: BundleStats,
+    ) -> Self {
+        Self {
+            transaction_cache: TransactionCache::new(rpc_client.clone()),
+            rpc_client,
+            keypair_filepath,
+            wss_client,
+            jsonrpc_client,
+            tip_accounts: Vec::new(),
+            outcome_sender,
+            grpc_client: None,
+            bundle_results: None,
+            tip_budget,
+            bundle_stats,
+            tip_floor: Arc::new(std::sync::RwLock::new(None)),
+            max_blockhash_age: DEFAULT_MAX_BLOCKHASH_AGE,
+            min_tip_lamports: DEFAULT_MIN_TIP_LAMPORTS,
+            priority_fee_estimator: None,
+            rate_limiter: None,
+            http_client: default_http_client(),
+            tip_floor_url: JITO_TIP_FLOOR_URL.to_string(),
+            latency_budget: None,
+            leader_tracker: None,
+            trade_journal: None,
+        }
+    }
+
+    /// Used to fetch the tip floor.
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    pub fn with_tip_floor_url(mut self, tip_floor_url: String) -> Self {
+        self.tip_floor_url = tip_floor_url;
+        self
+    }
+
+    /// Throttles calls to the tip floor API.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn with_priority_fee_estimator(
+        mut self,
+        priority_fee_estimator: PriorityFeeEstimator,
+    ) -> Self {
+        self.priority_fee_estimator = Some(priority_fee_estimator);
+        self
+    }
+
+    /// Transactions we built whose blockhash is older than this many blocks are re-signed
+    /// before submission, other transactions abort the submission.
+    pub fn with_max_blockhash_age(mut self, max_blockhash_age: u64) -> Self {
+        self.max_blockhash_age = max_blockhash_age;
+        self
+    }
+
+    /// Tip paid when the tip stream and floor are unavailable or below it, and the starting
+    /// point of tip escalation.
+    pub fn with_min_tip_lamports(mut self, min_tip_lamports: u64) -> Self {
+        self.min_tip_lamports = min_tip_lamports;
+        self
+    }
+
+    /// Refuses to submit a bundle more than `latency_budget` after its quote was received,
+    /// since the price has probably moved by then.
+    pub fn with_latency_budget(mut self, latency_budget: Duration) -> Self {
+        self.latency_budget = Some(latency_budget);
+        self
+    }
+
+    /// Holds bundles back while the current leader rejects them, for a leader that lands them.
+    pub fn with_leader_tracker(mut self, leader_tracker: LeaderTracker) -> Self {
+        self.leader_tracker = Some(leader_tracker);
+        self
+    }
+
+    /// Journals the signatures of every resubmission of a bundle with a higher tip, for the
+    /// trade to be reconciled on-chain after a restart whichever of them lands.
+    pub fn with_trade_journal(mut self, trade_journal: TradeJournal) -> Self {
+        self.trade_journal = Some(trade_journal);
+        self
+    }
+
+    /// Re-signs stale transactions and builds the tip on the engine's shared blockhash and
+    /// lookup tables.
+    pub fn with_transaction_cache(mut self, transaction_cache: TransactionCache) -> Self {
+        self.transaction_cache = transaction_cache;
+        self
+    }
+
+    /// Submits bundles and fetches tip accounts through the authenticated gRPC searcher
+    /// API instead of the JSON-RPC endpoint.
+    pub fn with_grpc_client(mut self, grpc_client: JitoGrpcClient) -> Self {
+        self.grpc_client = Some(grpc_client);
+        self
+    }
+
+    /// Confirms bundles from the results pushed by the block engine rather than by polling.
+    pub fn with_bundle_results(mut self, bundle_results: BundleResults) -> Self {
+        self.bundle_results = Some(bundle_results);
+        self
+    }
+
+    pub fn signer(&self) -> Keypair {
+        read_keypair_file(&self.keypair_filepath).expect("Failed to load keypair")
+    }
+
+    /// Latest landed tip at `percentile` from the tip stream, in lamports. Falls back to the
+    /// cached tip floor until the stream has delivered a tip, and to the minimum tip without
+    /// either.
+    pub fn tip_lamports(&self, percentile: TipPercentile) -> u64 {
+        let streamed_tip = self.wss_client.read().unwrap().clone();
+        streamed_tip
+            .or_else(|| self.tip_floor())
+            .map_or(0, |tip| tip.lamports(percentile))
+            .max(self.min_tip_lamports)
+    }
+
+    /// Submits the bundle and returns as soon as the block engine accepts it. Landing is
+    /// confirmed in a background task, which escalates the tip percentile whenever the bundle
+    /// fails to land `BUNDLE_RETRIES_PER_TIP_PERCENTILE` times in a row and reports the final
+    /// outcome, tagged with `expected_profit`, through the client's outcome channel.
+    pub async fn send_bundle(
+        &mut self,
+        txs: &[VersionedTransaction],
+        max_tip_lamports: u64,
+        expected_profit: ExpectedProfit,
+    ) -> Result<BundleHandle, JitoError> {
+        let jito_tip = self.tip_lamports(TipPercentile::P50).min(max_tip_lamports);
+        let (bundle_id, signatures) = self
+            .submit_bundle(txs, jito_tip, expected_profit.quoted_at)
+            .await?;
+        let handle = BundleHandle {
+            bundle_id: bundle_id.clone(),
+            submitted_at: std::time::Instant::now(),
+            signatures,
+        };
+
+        let mut client = self.clone();
+        let txs = txs.to_vec();
+        let span = info_span!("bundle", bundle_id = %bundle_id);
+        tokio::spawn(
+            async move {
+                let mut outcome = client
+                    .confirm_with_tip_escalation(
+                        &txs,
+                        max_tip_lamports,
+                        bundle_id.clone(),
+                        jito_tip,
+                        expected_profit.quoted_at,
+                    )
+                    .await;
+                outcome.submitted_bundle_id = bundle_id;
+                outcome.expected_profit = Some(expected_profit);
+                if client.outcome_sender.send(outcome).is_err() {
+                    error!("Bundle outcome receiver dropped");
+                }
+            }
+            .instrument(span),
+        );
+        Ok(handle)
+    }
+
+    /// Waits for the bundle to land, resubmitting with a higher tip percentile while it is
+    /// outbid or times out, until the validity window elapses, the quote received at
+    /// `quoted_at` goes past the latency budget or the next tip would exceed
+    /// `max_tip_lamports`. Bundles that are invalid or whose status is unknown are not
+    /// resubmitted.
+    async fn confirm_with_tip_escalation(
+        &mut self,
+        txs: &[VersionedTransaction],
+        max_tip_lamports: u64,
+        bundle_id: String,
+        jito_tip: u64,
+        quoted_at: Instant,
+    ) -> BundleOutcome {
+        let start_time = std::time::Instant::now();
+        let validity_window = std::time::Duration::from_secs(BUNDLE_VALIDITY_WINDOW_SECS);
+        let mut percentile = TipPercentile::P50;
+        let mut failures = 0;
+        let submitted_bundle_id = bundle_id.clone();
+        let mut outcome = self.wait_for_bundle(bundle_id, jito_tip, percentile).await;
+
+        loop {
+            if !outcome.status.is_retryable() || start_time.elapsed() >= validity_window {
+                return outcome;
+            }
+            if let Some(latency_budget) = self.latency_budget {
+                if quoted_at.elapsed() > latency_budget {
+                    warn!("Quote is past the latency budget, giving up on bundle");
+                    return outcome;
+                }
+            }
+
+            failures += 1;
+            if failures >= BUNDLE_RETRIES_PER_TIP_PERCENTILE {
+                match percentile.next() {
+                    Some(next) if self.tip_lamports(next) <= max_tip_lamports => {
+                        info!(
+                            "Escalating tip from {:?} to {:?} percentile",
+                            percentile, next
+                        );
+                        percentile = next;
+                        failures = 0;
+                    }
+                    _ => {
+                        warn!("Tip cap reached, giving up on bundle");
+                        return outcome;
+                    }
+                }
+            } else {
+                info!("Retrying bundle at the {:?} tip percentile", percentile);
+            }
+
+            let jito_tip = self.tip_lamports(percentile).min(max_tip_lamports);
+            match self.submit_bundle(txs, jito_tip, quoted_at).await {
+                Ok((bundle_id, signatures)) => {
+                    if let Some(trade_journal) = &self.trade_journal {
+                        if let Err(e) = trade_journal.record_submission(
+                            &submitted_bundle_id,
+                            &bundle_id,
+                            &signatures,
+                        ) {
+                            report_error("Error journaling bundle resubmission", e);
+                        }
+                    }
+                    outcome = self.wait_for_bundle(bundle_id, jito_tip, percentile).await
+                }
+                Err(e) => {
+                    error!("Error resubmitting bundle: {:?}", e);
+                    return outcome;
+                }
+            }
+        }
+    }
+
+    /// Tips `jito_tip` lamports and submits the bundle, returning its bundle id and the
+    /// signatures of its transactions as signed. `quoted_at`, when the trade's quote was
+    /// received, is checked against the latency budget once the bundle is signed. The tip is reserved against the tip budget until
+    /// `wait_for_bundle` settles it, and released if the bundle isn't accepted.
+    async fn submit_bundle(
+        &mut self,
+        txs: &[VersionedTransaction],
+        jito_tip: u64,
+        quoted_at: Instant,
+    ) -> Result<(String, Vec<Signature>), JitoError> {
+        if !self.tip_budget.try_reserve(jito_tip) {
+            return Err(JitoError::TipBudgetExhausted(
+                self.tip_budget.daily_budget_lamports as f64 / LAMPORTS_PER_SOL as f64,
+            ));
+        }
+        let result = self.submit_tipped_bundle(txs, jito_tip, quoted_at).await;
+        if result.is_err() {
+            self.tip_budget.release(jito_tip);
+        }
+        result
+    }
+
+    async fn submit_tipped_bundle(
+        &mut self,
+        txs: &[VersionedTransaction],
+        jito_tip: u64,
+        quoted_at: Instant,
+    ) -> Result<(String, Vec<Signature>), JitoError> {
+        let tip_account = self.random_tip_account().await?;
+
+        let tip_ix = system_instruction::transfer(&self.signer().pubkey(), &tip_account, jito_tip);
+        // print amount in sol not lamports
+        info!(
+            "SOL (Jito) tip: {:?}",
+            (jito_tip as f64) / (LAMPORTS_PER_SOL as f64)
+        );
+        let signer = self.signer();
+        let mut txs = refresh_stale_txs(
+            &self.transaction_cache,
+            &signer,
+            txs,
+            self.max_blockhash_age,
+        )
+        .await
+        .map_err(|e| JitoError::StaleBlockhash(e.to_string()))?;
+        // Tip from the last transaction when we built it ourselves to save a bundle slot,
+        // otherwise fall back to a standalone tip transaction.
+        let last_tx_with_tip = match txs.last() {
+            Some(tx) => append_ixs_to_tx(&self.transaction_cache, &signer, tx, &[tip_ix.clone()])?,
+            None => None,
+        };
+        match last_tx_with_tip {
+            Some(tx) => {
+                txs.pop();
+                txs.push(tx);
+            }
+            None => txs.push(
+                build_and_sign_tx(
+                    &self.transaction_cache,
+                    &signer,
+                    &[],
+                    &[tip_ix],
+                    self.priority_fee_estimator.as_ref(),
+                )
+                .await?,
+            ),
+        }
+        let txs = compact_bundle(&self.transaction_cache, &signer, txs)
+            .map_err(|e| JitoError::BundleInvalid(e.to_string()))?;
+        if let Some(leader_tracker) = &self.leader_tracker {
+            let mut delay = leader_tracker.submission_delay();
+            // Never wait the quote past the latency budget.
+            if let Some(latency_budget) = self.latency_budget {
+                delay = delay.min(latency_budget.saturating_sub(quoted_at.elapsed()));
+            }
+            if !delay.is_zero() {
+                info!(
+                    delay_ms = delay.as_millis() as u64,
+                    "Deferring bundle to the next leader that lands bundles"
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+        let latency = quoted_at.elapsed();
+        info!(
+            latency_ms = latency.as_millis() as u64,
+            "Quote to submission latency"
+        );
+        if let Some(latency_budget) = self.latency_budget {
+            if latency > latency_budget {
+                return Err(JitoError::LatencyBudgetExceeded(
+                    latency.as_millis(),
+                    latency_budget.as_millis(),
+                ));
+            }
+        }
+
+        let resp: Result<String, JitoError> = match &self.grpc_client {
+            Some(grpc_client) => grpc_client
+                .send_bundle(&txs)
+                .await
+                .map_err(JitoError::from_grpc),
+            None => {
+                let txs: Vec<String> = txs
+                    .iter()
+                    .map(|tx| bincode::serialize(tx).unwrap().to_base58())
+                    .collect::<Vec<String>>();
+
+                let params = rpc_params![txs];
+                self.jsonrpc_client
+                    .request("sendBundle", params)
+                    .await
+                    .map_err(JitoError::from_jsonrpc)
+            }
+        };
+        if let Ok(bundle) = &resp {
+            info!("https://explorer.jito.wtf/bundle/{bundle}");
+            if let Some(leader_tracker) = &self.leader_tracker {
+                leader_tracker.record_submission(bundle);
+            }
+        }
+        let signatures = txs.iter().map(|tx| tx.signatures[0]).collect();
+        resp.map(|bundle_id| (bundle_id, signatures))
+    }
+
+    async fn wait_for_bundle(
+        &self,
+        bundle_id: String,
+        jito_tip: u64,
+        percentile: TipPercentile,
+    ) -> BundleOutcome {
+        let start_time = std::time::Instant::now();
+        let status = match self.check_bundle_status(&bundle_id).await {
+            Ok(status) => status,
+            Err(e) => {
+                report_error("Error checking bundle status", e);
+                BundleStatusEnum::Unknown
+            }
+        };
+        let time_to_land = start_time.elapsed();
+        let mut outcome = self.confirm_bundle(bundle_id, status).await;
+        outcome.tip_lamports = jito_tip;
+        let landed = outcome.status == BundleStatusEnum::Landed;
+        self.tip_budget.settle(jito_tip, landed);
+        if let Some(leader_tracker) = &self.leader_tracker {
+            leader_tracker.record_outcome(&outcome.bundle_id, landed);
+        }
+        self.bundle_stats
+            .record(percentile, outcome.status, landed.then_some(time_to_land));
+        match outcome.status {
+            BundleStatusEnum::Landed => info!(
+                "Bundle landed successfully in slot {:?} (finalized: {})",
+                outcome.landed_slot, outcome.finalized
+            ),
+            BundleStatusEnum::Failed => warn!("Bundle failed to land"),
+            BundleStatusEnum::Invalid => warn!("Bundle invalid"),
+            BundleStatusEnum::Pending => info!("Bundle pending"),
+            BundleStatusEnum::Unknown => warn!("Bundle unknown"),
+            BundleStatusEnum::Timeout => warn!("Bundle timeout"),
+        }
+        outcome
+    }
+
+    /// Picks a random tip account so bundles don't all contend on the same one. The tip
+    /// account list is fetched once and cached for the lifetime of the client. Failing to
+    /// fetch it is classified like a failed submission, so that an unreachable block engine
+    /// is `Unavailable` whether or not the tip accounts were cached.
+    async fn random_tip_account(&mut self) -> Result<Pubkey, JitoError> {
+        if self.tip_accounts.is_empty() {
+            let tippers: Vec<String> = match &self.grpc_client {
+                Some(grpc_client) => grpc_client
+                    .get_tip_accounts()
+                    .await
+                    .map_err(JitoError::from_grpc)?,
+                None => self
+                    .jsonrpc_client
+                    .request("getTipAccounts", rpc_params![""])
+                    .await
+                    .map_err(JitoError::from_jsonrpc)?,
+            };
+            self.tip_accounts = tippers
+                .iter()
+                .map(|tipper| Pubkey::from_str(tipper))
+                .collect::<Result<Vec<Pubkey>, _>>()
+                .map_err(|e| JitoError::Other(e.into()))?;
+        }
+        self.tip_accounts
+            .choose(&mut rand::thread_rng())
+            .copied()
+            .ok_or_else(|| JitoError::Unavailable("No Jito tip accounts available".to_string()))
+    }
+
+    /// Waits for the bundle's inflight status, from the pushed bundle results while their
+    /// stream is up and by polling `getInflightBundleStatuses` otherwise.
+    async fn check_bundle_status(&self, bundle_id: &str) -> Result<BundleStatusEnum> {
+        let start_time = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(30);
+
+        while start_time.elapsed() < timeout {
+            if let Some(bundle_results) = &self.bundle_results {
+                match bundle_results.status(bundle_id) {
+                    Some(BundleStatusEnum::Pending) | None => {}
+                    Some(status) => return Ok(status),
+                }
+                if bundle_results.is_live() {
+                    bundle_results.wait(Duration::from_secs(1)).await;
+                    continue;
+                }
+            }
+            let params = rpc_params![[bundle_id]];
+            let response: Option<BundleStatusResponse> = self
+                .jsonrpc_client
+                .request("getInflightBundleStatuses", params)
+                .await?;
+
+            if let Some(resp) = response {
+                if let Some(status) = resp.value.first() {
+                    match status.status.as_str() {
+                        "Landed" => return Ok(BundleStatusEnum::Landed),
+                        "Failed" => return Ok(BundleStatusEnum::Failed),
+                        // `Invalid` only means the bundle isn't in the inflight index, which is
+                        // also the case right after it was sent, so keep polling; on timeout
+                        // `getBundleStatuses` still has the final say.
+                        "Pending" | "Invalid" => {
+                            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                            if start_time.elapsed() >= timeout {
+                                return Ok(BundleStatusEnum::Timeout);
+                            }
+                            continue;
+                        }
+                        _ => {
+                            error!("Unknown status: {}", status.status);
+                            return Ok(BundleStatusEnum::Unknown);
+                        }
+                    }
+                }
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        }
+
+        Ok(BundleStatusEnum::Timeout)
+    }
+
+    /// Follows up on the inflight status with `getBundleStatuses`, which covers bundles that
+    /// have left the inflight window, to capture the landed slot and transaction signatures
+    /// and wait for the bundle to be finalized.
+    async fn confirm_bundle(&self, bundle_id: String, status: BundleStatusEnum) -> BundleOutcome {
+        let mut outcome = BundleOutcome {
+            submitted_bundle_id: bundle_id.clone(),
+            bundle_id,
+            status,
+            landed_slot: None,
+            signatures: Vec::new(),
+            finalized: false,
+            tip_lamports: 0,
+            expected_profit: None,
+        };
+        if matches!(status, BundleStatusEnum::Failed | BundleStatusEnum::Invalid) {
+            return outcome;
+        }
+
+        let start_time = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(30);
+        loop {
+            let params = rpc_params![[outcome.bundle_id.as_str()]];
+            let response: Result<LandedBundleStatusResponse, _> = self
+                .jsonrpc_client
+                .request("getBundleStatuses", params)
+                .await;
+            match response.map(|resp| resp.value.into_iter().flatten().next()) {
+                Ok(Some(landed)) => {
+                    outcome.status = BundleStatusEnum::Landed;
+                    outcome.landed_slot = Some(landed.slot);
+                    outcome.signatures = landed
+                        .transactions
+                        .iter()
+                        .filter_map(|signature| Signature::from_str(signature).ok())
+                        .collect();
+                    outcome.finalized = landed.confirmation_status == "finalized";
+                    if outcome.finalized {
+                        break;
+                    }
+                }
+                // Nothing to wait for if the inflight status never saw it land.
+                Ok(None) if status != BundleStatusEnum::Landed => break,
+                Ok(None) => {}
+                Err(e) => {
+                    report_error("Error getting bundle statuses", e);
+                    break;
+                }
+            }
+
+            if start_time.elapsed() >= timeout {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        }
+        outcome
+    }
+
+    pub async fn get_jito_tip(&self) -> Result<u64> {
+        let tip = fetch_tip_floor(
+            &self.tip_floor_url,
+            &self.http_client,
+            self.rate_limiter.as_ref(),
+        )
+        .await?;
+        tip.ema_landed_tips_50th_percentile
+            .to_token_amount_ceil(SOL_DECIMALS)
+    }
+
+    /// Keeps the tip floor cache fresh in the background. Failed refreshes keep the last
+    /// known good tip floor and are retried sooner.
+    pub fn spawn_tip_floor_refresher(&self) {
+        let tip_floor = Arc::clone(&self.tip_floor);
+        let rate_limiter = self.rate_limiter.clone();
+        let http_client = self.http_client.clone();
+        let tip_floor_url = self.tip_floor_url.clone();
+        tokio::spawn(async move {
+            loop {
+                let wait = match fetch_tip_floor(
+                    &tip_floor_url,
+                    &http_client,
+                    rate_limiter.as_ref(),
+                )
+                .await
+                {
+                    Ok(tip) => {
+                        *tip_floor.write().unwrap() = Some(TipFloor {
+                            tip,
+                            fetched_at: std::time::Instant::now(),
+                        });
+                        TIP_FLOOR_TTL_SECS
+                    }
+                    Err(e) => {
+                        report_error("Error refreshing Jito tip floor", e);
+                        TIP_FLOOR_RETRY_SECS
+                    }
+                };
+                tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+            }
+        });
+    }
+
+    /// Last known good tip floor, even if a refresh has since failed.
+    pub fn tip_floor(&self) -> Option<Tip> {
+        let tip_floor = self.tip_floor.read().unwrap();
+        tip_floor.as_ref().map(|tip_floor| {
+            let age = tip_floor.fetched_at.elapsed().as_secs();
+            if age > TIP_FLOOR_TTL_SECS * 2 {
+                warn!("Using stale Jito tip floor from {}s ago", age);
+            }
+            tip_floor.tip.clone()
+        })
+    }
+}
+
+/// Merges adjacent transactions we built ourselves until the bundle fits in
+/// `MAX_BUNDLE_TXS`, then checks every transaction fits in a packet. The bundle is never
+/// split across several bundles since the legs must land atomically.
+fn compact_bundle(
+    transaction_cache: &TransactionCache,
+    signer: &Keypair,
+    mut txs: Vec<VersionedTransaction>,
+) -> Result<Vec<VersionedTransaction>> {
+    while txs.len() > MAX_BUNDLE_TXS {
+        let mut merged = false;
+        for i in 0..txs.len() - 1 {
+            if let Some(tx) = merge_txs(transaction_cache, signer, &txs[i], &txs[i + 1])? {
+                txs.splice(i..i + 2, [tx]);
+                merged = true;
+                break;
+            }
+        }
+        if !merged {
+            return Err(anyhow::anyhow!(
+                "Bundle has {} transactions, above the {} transaction limit, and can't be compacted",
+                txs.len(),
+                MAX_BUNDLE_TXS
+            ));
+        }
+    }
+
+    for (i, tx) in txs.iter().enumerate() {
+        validate_tx_size(tx).map_err(|e| anyhow::anyhow!("Bundle transaction {}: {}", i, e))?;
+    }
+    Ok(txs)
+}
+
+/// Phrases of the block engine's JSON-RPC error messages that blame the bundle itself. Matched
+/// whole, so that transient errors merely mentioning a bundle or a transaction aren't counted
+/// against the mint.
+const BUNDLE_INVALID_MESSAGES: [&str; 7] = [
+    "bundle contains an already processed transaction",
+    "bundles must write lock at least one tip account",
+    "bundle exceeds max",
+    "transaction simulation failed",
+    "bundle simulation failed",
+    "transaction could not be decoded",
+    "signature verification failure",
+];
+
+/// Phrase of the block engine's error messages for a bundle signed with an expired blockhash,
+/// which a fresh blockhash fixes.
+const BLOCKHASH_NOT_FOUND_MESSAGE: &str = "blockhash not found";
+
+/// JSON-RPC error code the block engine rejects malformed bundles with.
+const INVALID_PARAMS_CODE: i32 = -32602;
+
+impl JitoError {
+    fn from_jsonrpc(err: jsonrpsee::core::ClientError) -> Self {
+        match err {
+            jsonrpsee::core::ClientError::Call(err) => {
+                let message = err.message().to_lowercase();
+                if message.contains("rate limit") {
+                    JitoError::RateLimited(err.message().to_string())
+                } else if message.contains("unauthorized") {
+                    JitoError::Unauthorized(err.message().to_string())
+                } else if message.contains(BLOCKHASH_NOT_FOUND_MESSAGE) {
+                    JitoError::StaleBlockhash(err.message().to_string())
+                } else if err.code() == INVALID_PARAMS_CODE
+                    || BUNDLE_INVALID_MESSAGES
+                        .iter()
+                        .any(|invalid| message.contains(invalid))
+                {
+                    JitoError::BundleInvalid(err.message().to_string())
+                } else {
+                    // Not necessarily our bundle's fault, e.g. an internal error or an outage.
+                    JitoError::Unavailable(err.message().to_string())
+                }
+            }
+            jsonrpsee::core::ClientError::Transport(err) => {
+                let message = err.to_string();
+                if message.contains("429") {
+                    JitoError::RateLimited(message)
+                } else if message.contains("401") || message.contains("403") {
+                    JitoError::Unauthorized(message)
+                } else {
+                    JitoError::Unavailable(message)
+                }
+            }
+            jsonrpsee::core::ClientError::RequestTimeout => JitoError::Unavailable(err.to_string()),
+            err => JitoError::Other(err.into()),
+        }
+    }
+
+    fn from_grpc(err: anyhow::Error) -> Self {
+        let status = match err.downcast_ref::<tonic::Status>() {
+            Some(status) => status,
+            None => return JitoError::Unavailable(err.to_string()),
+        };
+        let message = status.message().to_string();
+        if message.to_lowercase().contains(BLOCKHASH_NOT_FOUND_MESSAGE) {
+            return JitoError::StaleBlockhash(message);
+        }
+        match status.code() {
+            tonic::Code::ResourceExhausted => JitoError::RateLimited(message),
+            tonic::Code::InvalidArgument | tonic::Code::FailedPrecondition => {
+                JitoError::BundleInvalid(message)
+            }
+            tonic::Code::Unauthenticated | tonic::Code::PermissionDenied => {
+                JitoError::Unauthorized(message)
+            }
+            _ => JitoError::Unavailable(message),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BundleStatus {
+    status: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BundleStatusResponse {
+    value: Vec<BundleStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LandedBundleStatus {
+    transactions: Vec<String>,
+    slot: u64,
+    confirmation_status: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LandedBundleStatusResponse {
+    value: Vec<Option<LandedBundleStatus>>,
+}
@@ -0,0 +1,594 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::{
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    transaction::VersionedTransaction,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, Notify};
+use tonic::{
+    client::Grpc,
+    codec::{ProstCodec, Streaming},
+    codegen::http::uri::PathAndQuery,
+    metadata::MetadataValue,
+    transport::{Channel, ClientTlsConfig},
+    Request,
+};
+use tracing::{info, warn};
+
+use crate::{
+    constants::{
+        BUNDLE_RESULTS_RECONNECT_BASE_SECS, BUNDLE_RESULTS_RECONNECT_MAX_SECS,
+        BUNDLE_RESULT_TTL_SECS,
+    },
+    jito::BundleStatusEnum,
+};
+
+/// Refresh the access token this long before it expires.
+const ACCESS_TOKEN_REFRESH_MARGIN_SECS: i64 = 60;
+
+/// Searcher client for the Jito block engine gRPC API. Authenticates with the block engine
+/// using a challenge signed by the auth keypair and attaches the access token to every call.
+#[derive(Clone)]
+pub struct JitoGrpcClient {
+    pub channel: Channel,
+    pub auth_keypair_filepath: String,
+    tokens: Arc<Mutex<AuthTokens>>,
+}
+
+struct AuthTokens {
+    access_token: proto::Token,
+    refresh_token: proto::Token,
+}
+
+impl JitoGrpcClient {
+    pub async fn connect(url: String, auth_keypair_filepath: String) -> Result<Self> {
+        let mut endpoint = Channel::from_shared(url.clone())?;
+        if url.starts_with("https") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+        }
+        let channel = endpoint.connect().await?;
+        let auth_keypair = read_keypair_file(&auth_keypair_filepath)
+            .map_err(|e| anyhow!("Unable to read Jito auth keypair: {:?}", e))?;
+        let tokens = authenticate(channel.clone(), &auth_keypair).await?;
+
+        Ok(Self {
+            channel,
+            auth_keypair_filepath,
+            tokens: Arc::new(Mutex::new(tokens)),
+        })
+    }
+
+    /// Submits the transactions as a bundle, returning the bundle id.
+    pub async fn send_bundle(&self, txs: &[VersionedTransaction]) -> Result<String> {
+        let packets = txs
+            .iter()
+            .map(|tx| {
+                let data = bincode::serialize(tx)?;
+                Ok(proto::Packet {
+                    meta: Some(proto::Meta {
+                        size: data.len() as u64,
+                        ..Default::default()
+                    }),
+                    data,
+                })
+            })
+            .collect::<Result<Vec<proto::Packet>>>()?;
+        let request = proto::SendBundleRequest {
+            bundle: Some(proto::Bundle {
+                header: None,
+                packets,
+            }),
+        };
+
+        let response: proto::SendBundleResponse = self
+            .unary("/searcher.SearcherService/SendBundle", request)
+            .await?;
+        Ok(response.uuid)
+    }
+
+    pub async fn get_tip_accounts(&self) -> Result<Vec<String>> {
+        let response: proto::GetTipAccountsResponse = self
+            .unary(
+                "/searcher.SearcherService/GetTipAccounts",
+                proto::GetTipAccountsRequest {},
+            )
+            .await?;
+        Ok(response.accounts)
+    }
+
+    /// Opens a stream of results for every bundle submitted with this auth keypair.
+    pub async fn subscribe_bundle_results(&self) -> Result<Streaming<proto::BundleResult>> {
+        let request = self
+            .authorized_request(proto::SubscribeBundleResultsRequest {})
+            .await?;
+        let mut grpc = Grpc::new(self.channel.clone());
+        grpc.ready().await?;
+        let response = grpc
+            .server_streaming(
+                request,
+                PathAndQuery::from_static("/searcher.SearcherService/SubscribeBundleResults"),
+                ProstCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    async fn unary<Req, Resp>(&self, path: &'static str, message: Req) -> Result<Resp>
+    where
+        Req: prost::Message + Send + Sync + 'static,
+        Resp: prost::Message + Default + Send + Sync + 'static,
+    {
+        let request = self.authorized_request(message).await?;
+        let mut grpc = Grpc::new(self.channel.clone());
+        grpc.ready().await?;
+        let response = grpc
+            .unary(
+                request,
+                PathAndQuery::from_static(path),
+                ProstCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    async fn authorized_request<T>(&self, message: T) -> Result<Request<T>> {
+        let access_token = self.access_token().await?;
+        let mut request = Request::new(message);
+        request.metadata_mut().insert(
+            "authorization",
+            MetadataValue::try_from(format!("Bearer {}", access_token))?,
+        );
+        Ok(request)
+    }
+
+    /// Returns a valid access token, refreshing it (or re-authenticating once the refresh
+    /// token has expired too) when it is about to expire.
+    async fn access_token(&self) -> Result<String> {
+        let mut tokens = self.tokens.lock().await;
+        if !expires_soon(&tokens.access_token) {
+            return Ok(tokens.access_token.value.clone());
+        }
+
+        if expires_soon(&tokens.refresh_token) {
+            let auth_keypair = read_keypair_file(&self.auth_keypair_filepath)
+                .map_err(|e| anyhow!("Unable to read Jito auth keypair: {:?}", e))?;
+            *tokens = authenticate(self.channel.clone(), &auth_keypair).await?;
+        } else {
+            let mut grpc = Grpc::new(self.channel.clone());
+            grpc.ready().await?;
+            let response: proto::RefreshAccessTokenResponse = grpc
+                .unary(
+                    Request::new(proto::RefreshAccessTokenRequest {
+                        refresh_token: tokens.refresh_token.value.clone(),
+                    }),
+                    PathAndQuery::from_static("/auth.AuthService/RefreshAccessToken"),
+                    ProstCodec::default(),
+                )
+                .await?
+                .into_inner();
+            tokens.access_token = response
+                .access_token
+                .ok_or_else(|| anyhow!("Missing access token in refresh response"))?;
+        }
+        Ok(tokens.access_token.value.clone())
+    }
+}
+
+async fn authenticate(channel: Channel, auth_keypair: &Keypair) -> Result<AuthTokens> {
+    let mut grpc = Grpc::new(channel);
+    grpc.ready().await?;
+    let challenge: proto::GenerateAuthChallengeResponse = grpc
+        .unary(
+            Request::new(proto::GenerateAuthChallengeRequest {
+                role: proto::Role::Searcher as i32,
+                pubkey: auth_keypair.pubkey().to_bytes().to_vec(),
+            }),
+            PathAndQuery::from_static("/auth.AuthService/GenerateAuthChallenge"),
+            ProstCodec::default(),
+        )
+        .await?
+        .into_inner();
+
+    let challenge = format!("{}-{}", auth_keypair.pubkey(), challenge.challenge);
+    let signed_challenge = auth_keypair.sign_message(challenge.as_bytes());
+
+    grpc.ready().await?;
+    let tokens: proto::GenerateAuthTokensResponse = grpc
+        .unary(
+            Request::new(proto::GenerateAuthTokensRequest {
+                challenge,
+                client_pubkey: auth_keypair.pubkey().to_bytes().to_vec(),
+                signed_challenge: signed_challenge.as_ref().to_vec(),
+            }),
+            PathAndQuery::from_static("/auth.AuthService/GenerateAuthTokens"),
+            ProstCodec::default(),
+        )
+        .await?
+        .into_inner();
+
+    Ok(AuthTokens {
+        access_token: tokens
+            .access_token
+            .ok_or_else(|| anyhow!("Missing access token in auth response"))?,
+        refresh_token: tokens
+            .refresh_token
+            .ok_or_else(|| anyhow!("Missing refresh token in auth response"))?,
+    })
+}
+
+fn expires_soon(token: &proto::Token) -> bool {
+    match &token.expires_at_utc {
+        Some(expires_at) => {
+            expires_at.seconds - ACCESS_TOKEN_REFRESH_MARGIN_SECS < chrono::Utc::now().timestamp()
+        }
+        None => false,
+    }
+}
+
+/// Results of our bundles pushed by the block engine's `SubscribeBundleResults` stream, by
+/// bundle id, so confirmation learns that a bundle landed or was rejected without polling for
+/// it. The stream is resubscribed with a growing backoff whenever it fails; while it is down,
+/// confirmation polls the JSON-RPC API instead.
+#[derive(Clone, Default)]
+pub struct BundleResults {
+    results: Arc<std::sync::Mutex<HashMap<String, (BundleStatusEnum, Instant)>>>,
+    live: Arc<AtomicBool>,
+    updated: Arc<Notify>,
+}
+
+impl BundleResults {
+    /// Subscribes to the results of the bundles submitted with `grpc_client`'s auth keypair
+    /// for as long as the bot runs.
+    pub fn spawn(grpc_client: JitoGrpcClient) -> Self {
+        let bundle_results = Self::default();
+        let subscriber = bundle_results.clone();
+        tokio::spawn(async move {
+            let mut failures = 0;
+            loop {
+                if let Err(e) = subscriber.subscribe(&grpc_client, &mut failures).await {
+                    warn!("Jito bundle results stream failed: {:?}", e);
+                }
+                subscriber.live.store(false, Ordering::Relaxed);
+                tokio::time::sleep(reconnect_backoff(failures)).await;
+                failures += 1;
+            }
+        });
+        bundle_results
+    }
+
+    /// Records the results of the stream until it ends. `failures` is reset once the stream
+    /// delivers a result.
+    async fn subscribe(&self, grpc_client: &JitoGrpcClient, failures: &mut u32) -> Result<()> {
+        let mut stream = grpc_client.subscribe_bundle_results().await?;
+        info!("Streaming Jito bundle results");
+        self.live.store(true, Ordering::Relaxed);
+        while let Some(result) = stream.message().await? {
+            *failures = 0;
+            self.record(result);
+        }
+        Err(anyhow!("Stream closed by the server"))
+    }
+
+    fn record(&self, result: proto::BundleResult) {
+        let Some(status) = result.result.as_ref().map(bundle_status) else {
+            return;
+        };
+        let mut results = self.results.lock().unwrap();
+        results.retain(|_, (_, received_at)| {
+            received_at.elapsed() < Duration::from_secs(BUNDLE_RESULT_TTL_SECS)
+        });
+        // Results of other auctions don't undo a landing.
+        let landed = matches!(
+            results.get(&result.bundle_id),
+            Some((BundleStatusEnum::Landed, _))
+        );
+        if !landed {
+            results.insert(result.bundle_id, (status, Instant::now()));
+        }
+        drop(results);
+        self.updated.notify_waiters();
+    }
+
+    /// Latest pushed status of the bundle, if any.
+    pub fn status(&self, bundle_id: &str) -> Option<BundleStatusEnum> {
+        self.results
+            .lock()
+            .unwrap()
+            .get(bundle_id)
+            .map(|(status, _)| *status)
+    }
+
+    /// Whether the stream is up, i.e. the results of new bundles will be pushed.
+    pub fn is_live(&self) -> bool {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    /// Waits until a result is pushed or `timeout` elapses.
+    pub async fn wait(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.updated.notified()).await;
+    }
+}
+
+/// Bundle status a pushed result amounts to. Accepted bundles are still pending, bundles
+/// outbid or dropped failed to land and can be retried with a higher tip, while bundles that
+/// failed simulation are invalid.
+fn bundle_status(kind: &proto::BundleResultKind) -> BundleStatusEnum {
+    match kind {
+        proto::BundleResultKind::Accepted(_) => BundleStatusEnum::Pending,
+        proto::BundleResultKind::Processed(_) | proto::BundleResultKind::Finalized(_) => {
+            BundleStatusEnum::Landed
+        }
+        proto::BundleResultKind::Rejected(proto::Rejected {
+            reason: Some(proto::RejectedReason::SimulationFailure(_)),
+        }) => BundleStatusEnum::Invalid,
+        proto::BundleResultKind::Rejected(_) | proto::BundleResultKind::Dropped(_) => {
+            BundleStatusEnum::Failed
+        }
+    }
+}
+
+/// Delay before resubscribing after `failures` failed subscriptions in a row, doubling from
+/// the base up to the maximum.
+fn reconnect_backoff(failures: u32) -> Duration {
+    let secs = BUNDLE_RESULTS_RECONNECT_BASE_SECS.saturating_mul(1 << failures.min(16));
+    Duration::from_secs(secs.min(BUNDLE_RESULTS_RECONNECT_MAX_SECS))
+}
+
+/// Hand-written messages for the subset of the Jito `auth`, `searcher`, `bundle` and `packet`
+/// protos that we use.
+pub mod proto {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Role {
+        Relayer = 0,
+        Searcher = 1,
+        Validator = 2,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GenerateAuthChallengeRequest {
+        #[prost(enumeration = "Role", tag = "1")]
+        pub role: i32,
+        #[prost(bytes = "vec", tag = "2")]
+        pub pubkey: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GenerateAuthChallengeResponse {
+        #[prost(string, tag = "1")]
+        pub challenge: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GenerateAuthTokensRequest {
+        #[prost(string, tag = "1")]
+        pub challenge: String,
+        #[prost(bytes = "vec", tag = "2")]
+        pub client_pubkey: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub signed_challenge: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Token {
+        #[prost(string, tag = "1")]
+        pub value: String,
+        #[prost(message, optional, tag = "2")]
+        pub expires_at_utc: Option<prost_types::Timestamp>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GenerateAuthTokensResponse {
+        #[prost(message, optional, tag = "1")]
+        pub access_token: Option<Token>,
+        #[prost(message, optional, tag = "2")]
+        pub refresh_token: Option<Token>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RefreshAccessTokenRequest {
+        #[prost(string, tag = "1")]
+        pub refresh_token: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RefreshAccessTokenResponse {
+        #[prost(message, optional, tag = "1")]
+        pub access_token: Option<Token>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Meta {
+        #[prost(uint64, tag = "1")]
+        pub size: u64,
+        #[prost(string, tag = "2")]
+        pub addr: String,
+        #[prost(uint32, tag = "3")]
+        pub port: u32,
+        #[prost(uint64, tag = "5")]
+        pub sender_stake: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Packet {
+        #[prost(bytes = "vec", tag = "1")]
+        pub data: Vec<u8>,
+        #[prost(message, optional, tag = "2")]
+        pub meta: Option<Meta>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Header {
+        #[prost(message, optional, tag = "1")]
+        pub ts: Option<prost_types::Timestamp>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Bundle {
+        #[prost(message, optional, tag = "2")]
+        pub header: Option<Header>,
+        #[prost(message, repeated, tag = "3")]
+        pub packets: Vec<Packet>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SendBundleRequest {
+        #[prost(message, optional, tag = "1")]
+        pub bundle: Option<Bundle>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SendBundleResponse {
+        #[prost(string, tag = "1")]
+        pub uuid: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetTipAccountsRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetTipAccountsResponse {
+        #[prost(string, repeated, tag = "1")]
+        pub accounts: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeBundleResultsRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Accepted {
+        #[prost(uint64, tag = "1")]
+        pub slot: u64,
+        #[prost(string, tag = "2")]
+        pub validator_identity: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Rejected {
+        #[prost(oneof = "RejectedReason", tags = "1, 2, 3, 4, 5")]
+        pub reason: Option<RejectedReason>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum RejectedReason {
+        #[prost(message, tag = "1")]
+        StateAuctionBidRejected(BidRejected),
+        #[prost(message, tag = "2")]
+        WinningBatchBidRejected(BidRejected),
+        #[prost(message, tag = "3")]
+        SimulationFailure(SimulationFailure),
+        #[prost(message, tag = "4")]
+        InternalError(RejectionMessage),
+        #[prost(message, tag = "5")]
+        DroppedBundle(RejectionMessage),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BidRejected {
+        #[prost(string, tag = "1")]
+        pub auction_id: String,
+        #[prost(uint64, tag = "2")]
+        pub simulated_bid_lamports: u64,
+        #[prost(string, optional, tag = "3")]
+        pub msg: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SimulationFailure {
+        #[prost(string, tag = "1")]
+        pub tx_signature: String,
+        #[prost(string, optional, tag = "2")]
+        pub msg: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RejectionMessage {
+        #[prost(string, tag = "1")]
+        pub msg: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Finalized {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Processed {
+        #[prost(string, tag = "1")]
+        pub validator_identity: String,
+        #[prost(uint64, tag = "2")]
+        pub slot: u64,
+        #[prost(uint64, tag = "3")]
+        pub bundle_index: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Dropped {
+        #[prost(int32, tag = "1")]
+        pub reason: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BundleResult {
+        #[prost(string, tag = "1")]
+        pub bundle_id: String,
+        #[prost(oneof = "BundleResultKind", tags = "2, 3, 4, 5, 6")]
+        pub result: Option<BundleResultKind>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum BundleResultKind {
+        #[prost(message, tag = "2")]
+        Accepted(Accepted),
+        #[prost(message, tag = "3")]
+        Rejected(Rejected),
+        #[prost(message, tag = "4")]
+        Finalized(Finalized),
+        #[prost(message, tag = "5")]
+        Processed(Processed),
+        #[prost(message, tag = "6")]
+        Dropped(Dropped),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(bundle_id: &str, kind: proto::BundleResultKind) -> proto::BundleResult {
+        proto::BundleResult {
+            bundle_id: bundle_id.to_string(),
+            result: Some(kind),
+        }
+    }
+
+    #[test]
+    fn records_pushed_results_by_bundle_id() {
+        let bundle_results = BundleResults::default();
+        let outbid = proto::BundleResultKind::Rejected(proto::Rejected {
+            reason: Some(proto::RejectedReason::StateAuctionBidRejected(
+                proto::BidRejected::default(),
+            )),
+        });
+        let processed = proto::BundleResultKind::Processed(proto::Processed::default());
+
+        bundle_results.record(result(
+            "a",
+            proto::BundleResultKind::Accepted(Default::default()),
+        ));
+        bundle_results.record(result("b", outbid.clone()));
+        bundle_results.record(result("c", processed));
+        bundle_results.record(result("c", outbid));
+
+        assert_eq!(bundle_results.status("a"), Some(BundleStatusEnum::Pending));
+        assert_eq!(bundle_results.status("b"), Some(BundleStatusEnum::Failed));
+        assert_eq!(bundle_results.status("c"), Some(BundleStatusEnum::Landed));
+        assert_eq!(bundle_results.status("d"), None);
+    }
+}
@@ -1,53 +1,131 @@
-use crate::constants::USDC_MINT;
+use crate::constants::{DEFAULT_QUOTE_SLIPPAGE_BPS, USDC_MINT};
+use crate::error::{ArbResult, ExecutionError, QuoteError};
 use crate::field_as_string;
+use crate::http::default_http_client;
+use crate::lookup_table::fetch_lookup_tables;
+use crate::priority_fee::PriorityFeeEstimator;
+use crate::profile::ActiveProfile;
+use crate::providers::SwapQuote;
+use crate::quote_budget::QuoteBudget;
 use crate::rate_limiter::RateLimiter;
+use crate::route_check::{fragile_split, FragileRoutePolicy, RouteCheckConfig};
+use crate::slippage_stats::Venue;
+use crate::transaction::{compute_unit_limit, TransactionBuilder, TransactionCache};
+use reqwest_middleware::ClientWithMiddleware;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::signature::{read_keypair_file, Keypair};
 use solana_sdk::signer::Signer;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::warn;
 
 use {
     anyhow::Result,
     serde::{Deserialize, Serialize},
-    solana_sdk::{
-        pubkey::{ParsePubkeyError, Pubkey},
-        transaction::VersionedTransaction,
-    },
+    solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction},
     std::collections::HashMap,
 };
 
 #[derive(Clone)]
 pub struct JupiterClient {
+    pub rpc_client: Arc<RpcClient>,
     pub jupiter_quote_url: String,
     pub keypair_filepath: String,
     pub rate_limiter: RateLimiter,
+    pub priority_fee_estimator: Option<PriorityFeeEstimator>,
+    pub profile: Option<ActiveProfile>,
+    pub quote_budget: Option<QuoteBudget>,
+    pub route_check: RouteCheckConfig,
+    pub http_client: ClientWithMiddleware,
+    pub transaction_cache: TransactionCache,
 }
 
 impl JupiterClient {
     pub fn new(
+        rpc_client: Arc<RpcClient>,
         jupiter_quote_url: String,
         keypair_filepath: String,
         rate_limiter: RateLimiter,
     ) -> Self {
         JupiterClient {
+            transaction_cache: TransactionCache::new(rpc_client.clone()),
+            rpc_client,
             jupiter_quote_url,
             keypair_filepath,
             rate_limiter,
+            priority_fee_estimator: None,
+            profile: None,
+            quote_budget: None,
+            route_check: RouteCheckConfig::default(),
+            http_client: default_http_client(),
         }
     }
 
-    pub fn signer(&self) -> Keypair {
-        read_keypair_file(self.keypair_filepath.clone())
-            .expect(format!("No keypair found at {}", self.keypair_filepath).as_str())
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    pub fn with_priority_fee_estimator(
+        mut self,
+        priority_fee_estimator: PriorityFeeEstimator,
+    ) -> Self {
+        self.priority_fee_estimator = Some(priority_fee_estimator);
+        self
+    }
+
+    /// Quotes the stablebonds with the active profile's slippage.
+    pub fn with_profile(mut self, profile: ActiveProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Charges the stablebond quotes to their mint's share of the per-tick quote budget.
+    pub fn with_quote_budget(mut self, quote_budget: QuoteBudget) -> Self {
+        self.quote_budget = Some(quote_budget);
+        self
+    }
+
+    /// Re-quotes or rejects the stablebond quotes whose route fills part of the size much worse
+    /// than the rest.
+    pub fn with_route_check(mut self, route_check: RouteCheckConfig) -> Self {
+        self.route_check = route_check;
+        self
+    }
+
+    /// Builds swap transactions on the engine's shared blockhash and records their lookup
+    /// tables in it.
+    pub fn with_transaction_cache(mut self, transaction_cache: TransactionCache) -> Self {
+        self.transaction_cache = transaction_cache;
+        self
+    }
+
+    fn spend_quote(&self, stablebond_mint: &Pubkey) -> Result<(), QuoteError> {
+        match &self.quote_budget {
+            Some(quote_budget) if !quote_budget.try_spend(stablebond_mint) => {
+                Err(QuoteError::BudgetExhausted(*stablebond_mint))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn slippage_bps(&self) -> u64 {
+        self.profile
+            .as_ref()
+            .map_or(DEFAULT_QUOTE_SLIPPAGE_BPS, |profile| {
+                profile.get().slippage_bps
+            })
     }
 
-    pub fn sign_tx(&self, tx: VersionedTransaction) -> Result<VersionedTransaction> {
-        let signed_tx = VersionedTransaction::try_new(tx.message, &[&self.signer()])
-            .map_err(|e| anyhow::anyhow!("Failed to create transaction: {}", e))?;
-        Ok(signed_tx)
+    pub fn signer(&self) -> Keypair {
+        read_keypair_file(self.keypair_filepath.clone())
+            .unwrap_or_else(|_| panic!("No keypair found at {}", self.keypair_filepath))
     }
 
-    pub async fn get_jupiter_quote(&mut self, args: JupiterQuoteArgs) -> Result<Quote> {
-        let url = format!(
+    pub async fn get_jupiter_quote(&mut self, args: JupiterQuoteArgs) -> Result<Quote, QuoteError> {
+        let mut url = format!(
             "{}/quote?inputMint={}&outputMint={}&amount={}&slippageBps={}",
             self.jupiter_quote_url,
             args.input_mint,
@@ -55,14 +133,24 @@ impl JupiterClient {
             args.amount,
             args.slippage_bps.unwrap_or(300),
         );
+        if args.only_direct_routes {
+            url.push_str("&onlyDirectRoutes=true");
+        }
 
         self.rate_limiter.wait_if_needed().await;
-        let quote = maybe_jupiter_api_error(reqwest::get(url).await?.json().await?)?;
-        Ok(quote)
+        let response = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| QuoteError::Request(e.to_string()))?;
+        maybe_jupiter_api_error(response).await
     }
 
-    pub async fn jupiter_swap_tx(&mut self, quote: Quote) -> Result<VersionedTransaction> {
-        let url = format!("{}/swap", self.jupiter_quote_url);
+    /// Builds the swap transaction ourselves from Jupiter's swap instructions, so it can be
+    /// rebuilt with a fresh blockhash and merged with our other transactions.
+    pub async fn jupiter_swap_tx(&mut self, quote: Quote) -> ArbResult<VersionedTransaction> {
+        let url = format!("{}/swap-instructions", self.jupiter_quote_url);
 
         let request = SwapRequest {
             user_public_key: self.signer().pubkey(),
@@ -76,40 +164,79 @@ impl JupiterClient {
         };
 
         self.rate_limiter.wait_if_needed().await;
-        let response = maybe_jupiter_api_error::<SwapResponse>(
-            reqwest::Client::builder()
-                .build()?
-                .post(url)
-                .json(&request)
-                .send()
-                .await?
-                .error_for_status()?
-                .json()
-                .await?,
-        )?;
-
-        fn decode(base64_transaction: String) -> Result<VersionedTransaction> {
-            bincode::deserialize(&base64::decode(base64_transaction)?).map_err(|err| err.into())
+        let response = self
+            .http_client
+            .post(url)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|e| QuoteError::Request(e.to_string()))?;
+        let response: SwapInstructionsResponse = maybe_jupiter_api_error(response).await?;
+
+        let invalid_response = |e: anyhow::Error| QuoteError::InvalidResponse(e.to_string());
+        let compute_budget_ixs = response
+            .compute_budget_instructions
+            .into_iter()
+            .map(Instruction::try_from)
+            .collect::<Result<Vec<_>>>()
+            .map_err(invalid_response)?;
+        let mut ixs = response
+            .setup_instructions
+            .into_iter()
+            .map(Instruction::try_from)
+            .collect::<Result<Vec<_>>>()
+            .map_err(invalid_response)?;
+        ixs.push(
+            response
+                .swap_instruction
+                .try_into()
+                .map_err(invalid_response)?,
+        );
+        if let Some(cleanup_instruction) = response.cleanup_instruction {
+            ixs.push(cleanup_instruction.try_into().map_err(invalid_response)?);
+        }
+        let lookup_table_addresses = response
+            .address_lookup_table_addresses
+            .iter()
+            .map(|address| Pubkey::from_str(address))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| QuoteError::InvalidResponse(e.to_string()))?;
+        let lookup_tables = fetch_lookup_tables(&self.rpc_client, &lookup_table_addresses).await?;
+        for lookup_table in lookup_tables.iter() {
+            self.transaction_cache
+                .record_lookup_table(lookup_table.clone());
         }
 
-        let swap_transaction: VersionedTransaction = decode(response.swap_transaction)?;
-        self.sign_tx(swap_transaction)
+        // Jupiter sizes the compute unit limit by simulating the swap.
+        let signer = self.signer();
+        TransactionBuilder::new(&self.transaction_cache, &signer)
+            .with_ixs(&ixs)
+            .with_lookup_tables(&lookup_tables)
+            .with_compute_unit_limit(compute_unit_limit(&compute_budget_ixs))
+            .with_priority_fee_estimator(self.priority_fee_estimator.as_ref())
+            .build()
+            .await
+            .map_err(|e| ExecutionError::Build(e.to_string()).into())
     }
 
     pub async fn sell_quote(
         &mut self,
         stablebond_mint: &Pubkey,
         amount: u64,
-    ) -> Result<(f64, Quote)> {
+    ) -> Result<(f64, Quote), QuoteError> {
         let jupiter_quote_args = JupiterQuoteArgs {
-            input_mint: stablebond_mint.clone(),
+            input_mint: *stablebond_mint,
             output_mint: Pubkey::from_str(USDC_MINT).unwrap(),
             amount,
-            slippage_bps: Some(100),
+            slippage_bps: Some(self.slippage_bps()),
+            only_direct_routes: false,
         };
-        let quote = self.get_jupiter_quote(jupiter_quote_args).await?;
+        let quote = self
+            .checked_quote(stablebond_mint, jupiter_quote_args)
+            .await?;
         let jup_price_usd_to_token: f64 = quote.in_amount as f64 / quote.out_amount as f64;
-        let jup_price_token_to_usd: f64 = 1 as f64 / jup_price_usd_to_token;
+        let jup_price_token_to_usd: f64 = 1_f64 / jup_price_usd_to_token;
         Ok((jup_price_token_to_usd, quote))
     }
 
@@ -117,39 +244,59 @@ impl JupiterClient {
         &mut self,
         stablebond_mint: &Pubkey,
         amount: u64,
-    ) -> Result<(f64, Quote)> {
+    ) -> Result<(f64, Quote), QuoteError> {
         let jupiter_quote_args = JupiterQuoteArgs {
             input_mint: Pubkey::from_str(USDC_MINT).unwrap(),
-            output_mint: stablebond_mint.clone(),
+            output_mint: *stablebond_mint,
             amount,
-            slippage_bps: Some(100),
+            slippage_bps: Some(self.slippage_bps()),
+            only_direct_routes: false,
         };
-        let quote = self.get_jupiter_quote(jupiter_quote_args).await?;
+        let quote = self
+            .checked_quote(stablebond_mint, jupiter_quote_args)
+            .await?;
         let jup_price_token_to_usd: f64 = quote.in_amount as f64 / quote.out_amount as f64;
         Ok((jup_price_token_to_usd, quote))
     }
-}
-
-/// The Errors that may occur while using this crate
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("reqwest: {0}")]
-    Reqwest(#[from] reqwest::Error),
-
-    #[error("invalid pubkey in response data: {0}")]
-    ParsePubkey(#[from] ParsePubkeyError),
-
-    #[error("base64: {0}")]
-    Base64Decode(#[from] base64::DecodeError),
 
-    #[error("bincode: {0}")]
-    Bincode(#[from] bincode::Error),
-
-    #[error("Jupiter API: {0}")]
-    JupiterApi(String),
-
-    #[error("serde_json: {0}")]
-    SerdeJson(#[from] serde_json::Error),
+    /// Quotes a stablebond swap, charged to the mint's quote budget, handling a fragile route
+    /// as the route check's policy says when it is enabled.
+    async fn checked_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        args: JupiterQuoteArgs,
+    ) -> Result<Quote, QuoteError> {
+        self.spend_quote(stablebond_mint)?;
+        let quote = self.get_jupiter_quote(args.clone()).await?;
+        let max_deviation_bps = self.route_check.max_split_deviation_bps;
+        let split = match fragile_split(&quote.route_plan, max_deviation_bps) {
+            Some(split) if self.route_check.enabled => split,
+            _ => return Ok(quote),
+        };
+        warn!(
+            amount = args.amount,
+            route = quote.route(),
+            split = split.label,
+            deviation_bps = split.deviation_bps,
+            "Fragile route"
+        );
+        if self.route_check.policy == FragileRoutePolicy::DirectRoutes {
+            self.spend_quote(stablebond_mint)?;
+            let direct = self
+                .get_jupiter_quote(JupiterQuoteArgs {
+                    only_direct_routes: true,
+                    ..args
+                })
+                .await?;
+            if fragile_split(&direct.route_plan, max_deviation_bps).is_none() {
+                return Ok(direct);
+            }
+        }
+        Err(QuoteError::FragileRoute(format!(
+            "{} fills {}% of the route {:.0} bps below its best split",
+            split.label, split.percent, split.deviation_bps
+        )))
+    }
 }
 
 /// Generic response with timing information
@@ -192,6 +339,41 @@ pub struct Quote {
     pub route_plan: Vec<RoutePlan>,
     pub context_slot: u64,
     pub time_taken: f64,
+    /// When the quote was received, to measure how long it takes to act on it.
+    #[serde(skip, default = "Instant::now")]
+    pub fetched_at: Instant,
+}
+
+impl Quote {
+    /// Labels of the AMMs the quote routes through, e.g. `Meteora DLMM+Orca V2`.
+    pub fn route(&self) -> String {
+        self.route_plan
+            .iter()
+            .map(|route_plan| route_plan.swap_info.label.as_str())
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+}
+
+impl TryFrom<Quote> for SwapQuote {
+    type Error = QuoteError;
+
+    fn try_from(quote: Quote) -> Result<Self, QuoteError> {
+        let invalid_response =
+            |e: &dyn std::fmt::Display| QuoteError::InvalidResponse(e.to_string());
+        Ok(SwapQuote {
+            venue: Venue::Jupiter,
+            input_mint: Pubkey::from_str(&quote.input_mint).map_err(|e| invalid_response(&e))?,
+            in_amount: quote.in_amount,
+            output_mint: Pubkey::from_str(&quote.output_mint).map_err(|e| invalid_response(&e))?,
+            out_amount: quote.out_amount,
+            price_impact_pct: quote.price_impact_pct,
+            route: quote.route(),
+            context_slot: quote.context_slot,
+            fetched_at: quote.fetched_at,
+            response: serde_json::to_value(&quote).map_err(|e| invalid_response(&e))?,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -205,17 +387,17 @@ pub struct RoutePlan {
 #[serde(rename_all = "camelCase")]
 pub struct SwapInfo {
     #[serde(with = "field_as_string")]
-    amm_key: String,
-    label: String,
-    input_mint: String,
-    output_mint: String,
+    pub amm_key: String,
+    pub label: String,
+    pub input_mint: String,
+    pub output_mint: String,
     #[serde(with = "field_as_string")]
-    in_amount: u64,
+    pub in_amount: u64,
     #[serde(with = "field_as_string")]
-    out_amount: String,
+    pub out_amount: String,
     #[serde(with = "field_as_string")]
-    fee_amount: u64,
-    fee_mint: String,
+    pub fee_amount: u64,
+    pub fee_mint: String,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -259,15 +441,63 @@ struct SwapRequest {
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct SwapResponse {
-    swap_transaction: String,
+struct SwapInstructionsResponse {
+    #[serde(default)]
+    compute_budget_instructions: Vec<SwapInstruction>,
+    #[serde(default)]
+    setup_instructions: Vec<SwapInstruction>,
+    swap_instruction: SwapInstruction,
+    cleanup_instruction: Option<SwapInstruction>,
+    #[serde(default)]
+    address_lookup_table_addresses: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwapInstruction {
+    #[serde(with = "field_as_string")]
+    program_id: Pubkey,
+    accounts: Vec<SwapAccountMeta>,
+    data: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwapAccountMeta {
+    #[serde(with = "field_as_string")]
+    pubkey: Pubkey,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+impl TryFrom<SwapInstruction> for Instruction {
+    type Error = anyhow::Error;
+
+    fn try_from(ix: SwapInstruction) -> Result<Self> {
+        Ok(Instruction {
+            program_id: ix.program_id,
+            accounts: ix
+                .accounts
+                .into_iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.pubkey,
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: base64::decode(ix.data)?,
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct JupiterQuoteArgs {
     pub input_mint: Pubkey,
     pub output_mint: Pubkey,
     pub amount: u64,
     pub slippage_bps: Option<u64>,
+    /// Route through a single AMM, without splits or intermediate tokens.
+    pub only_direct_routes: bool,
 }
 
 pub struct JupiterSwapArgs {
@@ -284,13 +514,13 @@ impl From<JupiterSwapArgs> for JupiterQuoteArgs {
             output_mint: swap_args.output_mint,
             amount: swap_args.amount,
             slippage_bps: swap_args.slippage_bps,
+            only_direct_routes: false,
         }
     }
 }
 
-pub type JupiterResult<T> = std::result::Result<T, Error>;
-
-fn maybe_jupiter_api_error<T>(value: serde_json::Value) -> JupiterResult<T>
+/// Body of a Jupiter response, or the error Jupiter answered with instead.
+async fn maybe_jupiter_api_error<T>(response: reqwest::Response) -> Result<T, QuoteError>
 where
     T: serde::de::DeserializeOwned,
 {
@@ -298,9 +528,13 @@ where
     struct ErrorResponse {
         error: String,
     }
+    let value: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| QuoteError::InvalidResponse(e.to_string()))?;
     if let Ok(ErrorResponse { error }) = serde_json::from_value::<ErrorResponse>(value.clone()) {
-        Err(Error::JupiterApi(error))
+        Err(QuoteError::Api(error))
     } else {
-        serde_json::from_value(value).map_err(|err| err.into())
+        serde_json::from_value(value).map_err(|e| QuoteError::InvalidResponse(e.to_string()))
     }
 }
@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use solana_client::nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::constants::{
+    DEFAULT_MAX_SUBMISSION_DEFERRAL_MS, LEADER_MIN_BUNDLES, LEADER_MIN_LAND_RATE,
+    LEADER_SCHEDULE_FETCH_SLOTS, LEADER_STATS_WINDOW, SLOT_DURATION_MS,
+    SLOT_SUBSCRIPTION_RETRY_SECS,
+};
+
+/// Follows the current slot over the RPC websocket and the leaders of the upcoming slots, and
+/// how many of the bundles submitted during each leader's slots landed. Leaders that aren't
+/// connected to the block engine never land them, so a bundle due while one of them is
+/// producing blocks is held back until the next leader that does, rather than tipped higher.
+#[derive(Clone)]
+pub struct LeaderTracker {
+    pub rpc_client: Arc<RpcClient>,
+    pub max_deferral: Duration,
+    state: Arc<RwLock<LeaderState>>,
+}
+
+#[derive(Default)]
+struct LeaderState {
+    slot: u64,
+    /// Leaders of the consecutive slots from `first_slot`.
+    first_slot: u64,
+    leaders: Vec<Pubkey>,
+    bundles: HashMap<Pubkey, LeaderBundles>,
+    /// Leader each pending bundle was submitted to, by bundle id.
+    pending: HashMap<String, Pubkey>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct LeaderBundles {
+    submitted: u64,
+    landed: u64,
+}
+
+impl LeaderState {
+    fn leader(&self, slot: u64) -> Option<Pubkey> {
+        let index = slot.checked_sub(self.first_slot)?;
+        self.leaders.get(index as usize).copied()
+    }
+
+    /// Whether enough of the bundles submitted to `leader` were dropped to stop sending it
+    /// more. Leaders we know nothing about are given a chance.
+    fn rejects_bundles(&self, leader: &Pubkey) -> bool {
+        self.bundles.get(leader).map_or(false, |bundles| {
+            bundles.submitted >= LEADER_MIN_BUNDLES
+                && (bundles.landed as f64 / bundles.submitted as f64) < LEADER_MIN_LAND_RATE
+        })
+    }
+}
+
+impl LeaderTracker {
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self {
+            rpc_client,
+            max_deferral: Duration::from_millis(DEFAULT_MAX_SUBMISSION_DEFERRAL_MS),
+            state: Arc::new(RwLock::new(LeaderState::default())),
+        }
+    }
+
+    /// Longest a submission is held back waiting for a better leader.
+    pub fn with_max_deferral(mut self, max_deferral: Duration) -> Self {
+        self.max_deferral = max_deferral;
+        self
+    }
+
+    /// Follows the slots from the websocket at `ws_url`, resubscribing when it drops.
+    pub fn spawn(&self, ws_url: String) -> JoinHandle<()> {
+        let tracker = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = tracker.follow_slots(&ws_url).await {
+                    warn!("Slot subscription to {} failed: {:?}", ws_url, e);
+                }
+                tokio::time::sleep(Duration::from_secs(SLOT_SUBSCRIPTION_RETRY_SECS)).await;
+            }
+        })
+    }
+
+    async fn follow_slots(&self, ws_url: &str) -> Result<()> {
+        let pubsub_client = PubsubClient::new(ws_url).await?;
+        let (mut slots, unsubscribe) = pubsub_client.slot_subscribe().await?;
+        while let Some(slot_info) = slots.next().await {
+            self.state.write().unwrap().slot = slot_info.slot;
+            if self.needs_leaders(slot_info.slot) {
+                if let Err(e) = self.fetch_leaders(slot_info.slot).await {
+                    warn!("Error fetching the leader schedule: {:?}", e);
+                }
+            }
+        }
+        unsubscribe().await;
+        Err(anyhow!("Slot subscription closed"))
+    }
+
+    /// Whether the known leaders end within the longest deferral from `slot`.
+    fn needs_leaders(&self, slot: u64) -> bool {
+        let state = self.state.read().unwrap();
+        state.leader(slot + self.max_deferral_slots()).is_none()
+    }
+
+    async fn fetch_leaders(&self, slot: u64) -> Result<()> {
+        let leaders = self
+            .rpc_client
+            .get_slot_leaders(slot, LEADER_SCHEDULE_FETCH_SLOTS)
+            .await?;
+        let mut state = self.state.write().unwrap();
+        state.first_slot = slot;
+        state.leaders = leaders;
+        Ok(())
+    }
+
+    fn max_deferral_slots(&self) -> u64 {
+        self.max_deferral.as_millis() as u64 / SLOT_DURATION_MS
+    }
+
+    /// How long to hold a bundle back for the first upcoming leader that doesn't reject
+    /// bundles. Nothing when the current leader lands them, when the leaders aren't known yet,
+    /// or when none of the leaders within the longest deferral land them either.
+    pub fn submission_delay(&self) -> Duration {
+        let state = self.state.read().unwrap();
+        if state.slot == 0 {
+            return Duration::ZERO;
+        }
+        for slots in 0..=self.max_deferral_slots() {
+            match state.leader(state.slot + slots) {
+                Some(leader) if state.rejects_bundles(&leader) => continue,
+                _ => return Duration::from_millis(slots * SLOT_DURATION_MS),
+            }
+        }
+        Duration::ZERO
+    }
+
+    /// Remembers the leader `bundle_id` was submitted to, to credit it with its outcome.
+    pub fn record_submission(&self, bundle_id: &str) {
+        let mut state = self.state.write().unwrap();
+        if let Some(leader) = state.leader(state.slot) {
+            state.pending.insert(bundle_id.to_string(), leader);
+        }
+    }
+
+    pub fn record_outcome(&self, bundle_id: &str, landed: bool) {
+        let mut state = self.state.write().unwrap();
+        let Some(leader) = state.pending.remove(bundle_id) else {
+            return;
+        };
+        let bundles = state.bundles.entry(leader).or_default();
+        // Halved once the window is full, so a leader that connects to the block engine
+        // later isn't held back by its past forever.
+        if bundles.submitted >= LEADER_STATS_WINDOW {
+            bundles.submitted /= 2;
+            bundles.landed /= 2;
+        }
+        bundles.submitted += 1;
+        bundles.landed += landed as u64;
+        if !landed && state.rejects_bundles(&leader) {
+            info!(%leader, "Leader rejects bundles, deferring submissions during its slots");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(leaders: &[Pubkey]) -> LeaderTracker {
+        let tracker = LeaderTracker::new(Arc::new(RpcClient::new_mock("succeeds".to_string())));
+        {
+            let mut state = tracker.state.write().unwrap();
+            state.slot = 100;
+            state.first_slot = 100;
+            // Each leader produces four consecutive slots.
+            state.leaders = leaders.iter().flat_map(|leader| [*leader; 4]).collect();
+        }
+        tracker
+    }
+
+    fn reject_bundles(tracker: &LeaderTracker, leader: Pubkey) {
+        for i in 0..LEADER_MIN_BUNDLES {
+            let bundle_id = format!("{}-{}", leader, i);
+            tracker
+                .state
+                .write()
+                .unwrap()
+                .pending
+                .insert(bundle_id.clone(), leader);
+            tracker.record_outcome(&bundle_id, false);
+        }
+    }
+
+    #[test]
+    fn defers_to_the_next_leader_that_lands_bundles() {
+        let (rejecting, landing) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = tracker(&[rejecting, landing]);
+        assert_eq!(tracker.submission_delay(), Duration::ZERO);
+
+        reject_bundles(&tracker, rejecting);
+        tracker.state.write().unwrap().slot = 102;
+        assert_eq!(
+            tracker.submission_delay(),
+            Duration::from_millis(2 * SLOT_DURATION_MS)
+        );
+    }
+
+    #[test]
+    fn submits_right_away_when_no_leader_in_reach_lands_bundles() {
+        let (rejecting, landing) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = tracker(&[rejecting, landing])
+            .with_max_deferral(Duration::from_millis(2 * SLOT_DURATION_MS));
+        reject_bundles(&tracker, rejecting);
+
+        assert_eq!(tracker.submission_delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn credits_the_leader_a_bundle_was_submitted_to() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = tracker(&[first, second]);
+
+        tracker.record_submission("bundle");
+        tracker.state.write().unwrap().slot = 104;
+        tracker.record_outcome("bundle", true);
+
+        let state = tracker.state.read().unwrap();
+        assert_eq!(state.bundles[&first].landed, 1);
+        assert!(!state.bundles.contains_key(&second));
+        assert!(state.pending.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use solana_program::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::{
+    constants::{DEFAULT_MAX_HEDGE_LOSS_USD, STABLEBOND_DECIMALS},
+    math::{self, TokenAmountExt, UiAmountExt},
+    notifier::{Notifications, Severity},
+    risk,
+    slippage_stats::TradeLeg,
+    strategy::TradeDirection,
+    unwind::{Exit, Unwinder},
+};
+
+/// What to do with the stablebonds a trade bought when its selling leg fails, which can only
+/// happen when its legs are sent one after the other instead of atomically.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LegFailurePolicy {
+    /// Sell them right away through the venue the failed leg didn't use.
+    Hedge,
+    /// Keep them as inventory, left to the unwinder.
+    #[default]
+    Hold,
+    /// Keep them and pause trading the mint until an operator resumes it.
+    Pause,
+}
+
+/// `[leg_failure]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LegFailureConfig {
+    pub policy: LegFailurePolicy,
+    /// Loss against the buy price past which a hedge pauses the mint instead.
+    pub max_hedge_loss_usd: Decimal,
+}
+
+impl Default for LegFailureConfig {
+    fn default() -> Self {
+        Self {
+            policy: LegFailurePolicy::default(),
+            max_hedge_loss_usd: DEFAULT_MAX_HEDGE_LOSS_USD,
+        }
+    }
+}
+
+/// Stablebonds bought by a trade whose selling leg failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrandedLeg {
+    pub mint: Pubkey,
+    pub direction: TradeDirection,
+    pub amount: u64,
+    /// USDC paid per stablebond.
+    pub buy_price: Decimal,
+}
+
+impl StrandedLeg {
+    /// The stablebonds a trade priced at `legs` was left with when only the first `landed` of
+    /// its `txs` transactions landed: a trade's last two transactions buy and then sell, after
+    /// any oracle updates.
+    pub fn from_partial_send(
+        mint: &Pubkey,
+        direction: TradeDirection,
+        legs: &[TradeLeg],
+        txs: usize,
+        landed: usize,
+    ) -> Option<Self> {
+        if txs < 2 || landed != txs - 1 {
+            return None;
+        }
+        let [buy, sell] = legs else {
+            return None;
+        };
+        Some(Self {
+            mint: *mint,
+            direction,
+            amount: sell
+                .input_amount
+                .to_token_amount_floor(STABLEBOND_DECIMALS)
+                .ok()?,
+            buy_price: math::to_decimal(buy.input_amount / buy.expected_output_amount).ok()?,
+        })
+    }
+
+    fn ui_amount(&self) -> Decimal {
+        self.amount.to_ui_decimal(STABLEBOND_DECIMALS)
+    }
+}
+
+/// Applies the `LegFailurePolicy` to the stablebonds a failed selling leg left behind, which
+/// are first recorded in the position so that they are tracked whatever the policy.
+#[derive(Clone)]
+pub struct LegFailureHandler {
+    pub config: LegFailureConfig,
+    pub unwinder: Unwinder,
+    pub kill_switch_file: String,
+    pub notifications: Notifications,
+}
+
+impl LegFailureHandler {
+    pub fn new(config: LegFailureConfig, unwinder: Unwinder, kill_switch_file: String) -> Self {
+        Self {
+            config,
+            unwinder,
+            kill_switch_file,
+            notifications: Notifications::new(),
+        }
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Handles `leg`, hedging it against Etherfuse's `nav` and `sell_liquidity_usdc_amount`
+    /// when it was Jupiter's leg that failed.
+    pub async fn handle(
+        &self,
+        leg: &StrandedLeg,
+        nav: Option<f64>,
+        sell_liquidity_usdc_amount: Option<u64>,
+    ) {
+        warn!(
+            mint = %leg.mint,
+            amount = %leg.ui_amount(),
+            policy = ?self.config.policy,
+            "Selling leg failed after the buying leg landed"
+        );
+        self.unwinder
+            .position_tracker
+            .record_fill(&leg.mint, leg.ui_amount(), leg.buy_price);
+        let stranded = format!(
+            "{:.2} stablebonds of {} bought at ${:.4} after the selling leg failed",
+            leg.ui_amount(),
+            leg.mint,
+            leg.buy_price
+        );
+        match self.config.policy {
+            LegFailurePolicy::Hold => {
+                self.notifications
+                    .notify(Severity::Warning, format!("Holding {}", stranded));
+            }
+            LegFailurePolicy::Pause => self.pause(leg, format!("Holding {}", stranded)),
+            LegFailurePolicy::Hedge => {
+                match self.hedge(leg, nav, sell_liquidity_usdc_amount).await {
+                    Ok(venue) => {
+                        info!(mint = %leg.mint, venue, "Hedged the stranded leg");
+                        self.notifications.notify(
+                            Severity::Warning,
+                            format!("Hedged {} through {}", stranded, venue),
+                        );
+                    }
+                    Err(e) => self.pause(leg, format!("Unable to hedge {}: {}", stranded, e)),
+                }
+            }
+        }
+    }
+
+    /// Sells the leg through the venue its failed leg didn't use, returning that venue.
+    async fn hedge(
+        &self,
+        leg: &StrandedLeg,
+        nav: Option<f64>,
+        sell_liquidity_usdc_amount: Option<u64>,
+    ) -> Result<&'static str> {
+        let exit = match leg.direction {
+            // The Etherfuse redemption failed.
+            TradeDirection::BuyOnJupiterSellOnEtherfuse => {
+                let (price, quote) = self
+                    .unwinder
+                    .jupiter_client
+                    .clone()
+                    .sell_quote(&leg.mint, leg.amount)
+                    .await?;
+                Exit::Jupiter { price, quote }
+            }
+            // The Jupiter sell failed.
+            TradeDirection::BuyOnEtherfuseSellOnJupiter => {
+                let nav = nav.ok_or_else(|| anyhow!("The Etherfuse NAV is unknown"))?;
+                self.unwinder
+                    .best_exit(leg.amount, nav, sell_liquidity_usdc_amount, None)
+                    .ok_or_else(|| anyhow!("Etherfuse has no liquidity to redeem them"))?
+            }
+        };
+        let loss_usd = leg.ui_amount() * (leg.buy_price - math::to_decimal(exit.price())?);
+        if loss_usd > self.config.max_hedge_loss_usd {
+            return Err(anyhow!(
+                "Selling through {} would lose ${:.2}, more than ${:.2}",
+                exit.venue(),
+                loss_usd,
+                self.config.max_hedge_loss_usd
+            ));
+        }
+        let venue = exit.venue();
+        self.unwinder
+            .clone()
+            .unwind(&leg.mint, leg.amount, exit)
+            .await?;
+        Ok(venue)
+    }
+
+    fn pause(&self, leg: &StrandedLeg, reason: String) {
+        if let Err(e) = risk::pause(&self.kill_switch_file, &leg.mint.to_string(), &reason) {
+            warn!("Error pausing {}: {:?}", leg.mint, e);
+        }
+        self.notifications.notify(
+            Severity::Critical,
+            format!("Paused {}: {}", leg.mint, reason),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slippage_stats::Venue;
+
+    fn leg(venue: Venue, input_amount: f64, expected_output_amount: f64) -> TradeLeg {
+        TradeLeg {
+            venue,
+            route: String::new(),
+            input_mint: Pubkey::new_unique(),
+            input_amount,
+            output_mint: Pubkey::new_unique(),
+            expected_output_amount,
+            price_impact: 0.0,
+        }
+    }
+
+    #[test]
+    fn strands_the_bought_stablebonds_only_when_the_selling_leg_failed() {
+        let mint = Pubkey::new_unique();
+        let direction = TradeDirection::BuyOnJupiterSellOnEtherfuse;
+        let legs = [
+            leg(Venue::Jupiter, 105.0, 100.0),
+            leg(Venue::Etherfuse, 100.0, 106.0),
+        ];
+
+        // An oracle update and both legs.
+        assert_eq!(
+            StrandedLeg::from_partial_send(&mint, direction, &legs, 3, 2),
+            Some(StrandedLeg {
+                mint,
+                direction,
+                amount: 100_000_000,
+                buy_price: Decimal::new(105, 2),
+            })
+        );
+        assert_eq!(
+            StrandedLeg::from_partial_send(&mint, direction, &legs, 3, 1),
+            None
+        );
+        assert_eq!(
+            StrandedLeg::from_partial_send(&mint, direction, &legs, 1, 0),
+            None
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Arbitrage between Etherfuse stablebonds and Jupiter. The `etherfuse-arb` binary is a thin CLI
+//! over this crate, which other services can embed to run the same clients and strategies.
+//!
+//! The Jito client, the Switchboard client, the notifiers, the HTTP API and the Geyser subscriber
+//! are behind the `jito`, `switchboard`, `notifications`, `api` and `geyser` features, all on by
+//! default. Embedders that only build Etherfuse and Jupiter transactions can turn them off to skip
+//! their dependencies.
+
+pub mod account_cache;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod approval;
+pub mod balance_reservations;
+pub mod bundle_dedup;
+pub mod bundle_stats;
+pub mod config;
+pub mod constants;
+pub mod control;
+pub mod cost_model;
+pub mod decision_log;
+pub mod dust_sweep;
+#[cfg(all(
+    feature = "jito",
+    feature = "switchboard",
+    feature = "notifications",
+    feature = "api",
+    feature = "geyser"
+))]
+pub mod engine;
+pub mod error;
+pub mod error_aggregator;
+pub mod etherfuse;
+#[cfg(feature = "notifications")]
+pub mod event_sink;
+pub mod execution;
+pub mod field_as_string;
+pub mod gas_top_up;
+#[cfg(feature = "geyser")]
+pub mod geyser;
+pub mod http;
+pub mod jito;
+#[cfg(feature = "jito")]
+pub mod jito_grpc;
+pub mod jupiter;
+pub mod leader_schedule;
+pub mod leg_failure;
+pub mod lookup_table;
+pub mod market_data;
+pub mod math;
+pub mod mint_blacklist;
+pub mod mock;
+pub mod notifier;
+pub mod pool_state;
+pub mod position_tracker;
+pub mod priority_fee;
+pub mod profile;
+pub mod profit_verification;
+pub mod providers;
+pub mod quote_budget;
+pub mod rate_limiter;
+pub mod raydium;
+pub mod reconciliation;
+pub mod research_export;
+pub mod risk;
+pub mod route_check;
+pub mod slippage_stats;
+pub mod sol_price;
+pub mod spread_history;
+pub mod strategy;
+pub mod supervisor;
+pub mod swap_router;
+#[cfg(feature = "switchboard")]
+pub mod switchboard;
+#[cfg(feature = "notifications")]
+pub mod telegram;
+pub mod tip_budget;
+pub mod trade_chunking;
+pub mod trade_journal;
+pub mod trading_engine;
+pub mod transaction;
+pub mod unwind;
+pub mod usdc_peg;
+#[cfg(all(feature = "jito", feature = "switchboard", feature = "notifications"))]
+pub mod worker;
+pub mod wsol;
+
+pub use error::{ArbError, ArbResult};
+pub use etherfuse::EtherfuseClient;
+#[cfg(feature = "jito")]
+pub use jito::JitoClient;
+pub use jupiter::JupiterClient;
+pub use market_data::{MarketData, MarketDataBuilder};
+pub use strategy::{
+    BuyOnEtherfuseSellOnJupiter, BuyOnJupiterSellOnEtherfuse, Strategy, StrategyEnum,
+    StrategyResult,
+};
+pub use trading_engine::TradingEngine;
@@ -0,0 +1,303 @@
+use anyhow::Result;
+use solana_program::{
+    address_lookup_table::{
+        instruction::{create_lookup_table, extend_lookup_table},
+        state::AddressLookupTable,
+        AddressLookupTableAccount,
+    },
+    instruction::Instruction,
+    pubkey::Pubkey,
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::{
+    constants::{MAX_LOOKUP_TABLE_EXTEND_ADDRESSES, SLOT_DURATION_MS},
+    error::RpcError,
+    error_aggregator::report_error,
+    transaction::{sign_and_send_ixs, SendConfig, TransactionCache},
+};
+
+/// Maintains an address lookup table owned by our wallet holding the static accounts of our
+/// own transactions (bonds, issuances, feeds, ATAs), so they compile to smaller v0 messages.
+/// The table is extended in the background, and addresses are only compiled against once the
+/// extension is active, from the slot after it.
+#[derive(Clone)]
+pub struct LookupTableManager {
+    pub rpc_client: Arc<RpcClient>,
+    pub transaction_cache: TransactionCache,
+    pub keypair_filepath: String,
+    /// The table with only its active addresses.
+    table: Arc<Mutex<Option<AddressLookupTableAccount>>>,
+    address: Arc<Mutex<Option<Pubkey>>>,
+    extending: Arc<AtomicBool>,
+}
+
+impl LookupTableManager {
+    /// Uses the lookup table at `address`, or creates one on first use when `None`, and
+    /// records it in `transaction_cache` so transactions compiled against it can be rebuilt.
+    pub fn new(
+        transaction_cache: TransactionCache,
+        keypair_filepath: String,
+        address: Option<Pubkey>,
+    ) -> Self {
+        Self {
+            rpc_client: transaction_cache.rpc_client.clone(),
+            transaction_cache,
+            keypair_filepath,
+            table: Arc::new(Mutex::new(None)),
+            address: Arc::new(Mutex::new(address)),
+            extending: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn signer(&self) -> Keypair {
+        read_keypair_file(&self.keypair_filepath).expect("Unable to read keypair filepath")
+    }
+
+    /// Lookup table to compile `ixs` against, extending it in the background with the accounts
+    /// of `ixs` it lacks.
+    pub async fn lookup_table_for(&self, ixs: &[Instruction]) -> Result<AddressLookupTableAccount> {
+        let mut addresses: Vec<Pubkey> = ixs
+            .iter()
+            .flat_map(|ix| ix.accounts.iter())
+            .filter(|account| !account.is_signer)
+            .map(|account| account.pubkey)
+            .collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        self.ensure_addresses(&addresses).await
+    }
+
+    /// Lookup table with its active addresses, without waiting for the missing `addresses`:
+    /// they are added by an extension in the background, and stay static accounts of the
+    /// transactions compiled meanwhile.
+    pub async fn ensure_addresses(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<AddressLookupTableAccount> {
+        let mut table = self.table.lock().await;
+        let lookup_table = match table.as_ref() {
+            Some(lookup_table) => lookup_table.clone(),
+            None => {
+                let lookup_table = self.load_or_create().await?;
+                self.transaction_cache
+                    .record_lookup_table(lookup_table.clone());
+                *table = Some(lookup_table.clone());
+                lookup_table
+            }
+        };
+
+        let missing: Vec<Pubkey> = addresses
+            .iter()
+            .filter(|address| !lookup_table.addresses.contains(address))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            self.spawn_extension(lookup_table.key, missing);
+        }
+        Ok(lookup_table)
+    }
+
+    /// Extends the table with `missing` unless an extension is already running, and swaps in
+    /// the extended table once its new addresses are active.
+    fn spawn_extension(&self, address: Pubkey, missing: Vec<Pubkey>) {
+        if self.extending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            match manager.extend_and_activate(address, missing).await {
+                Ok(lookup_table) => {
+                    manager
+                        .transaction_cache
+                        .record_lookup_table(lookup_table.clone());
+                    *manager.table.lock().await = Some(lookup_table);
+                }
+                Err(e) => report_error("Error extending lookup table", e),
+            }
+            manager.extending.store(false, Ordering::SeqCst);
+        });
+    }
+
+    async fn extend_and_activate(
+        &self,
+        address: Pubkey,
+        missing: Vec<Pubkey>,
+    ) -> Result<AddressLookupTableAccount> {
+        self.extend(address, missing).await?;
+        loop {
+            let (lookup_table, pending) = self.load(address).await?;
+            if !pending {
+                return Ok(lookup_table);
+            }
+            tokio::time::sleep(Duration::from_millis(SLOT_DURATION_MS)).await;
+        }
+    }
+
+    async fn load_or_create(&self) -> Result<AddressLookupTableAccount> {
+        let mut address = self.address.lock().await;
+        if let Some(address) = *address {
+            return Ok(self.load(address).await?.0);
+        }
+
+        let signer = self.signer();
+        let recent_slot = self
+            .rpc_client
+            .get_slot_with_commitment(CommitmentConfig::finalized())
+            .await?;
+        let (create_ix, lookup_table_address) =
+            create_lookup_table(signer.pubkey(), signer.pubkey(), recent_slot);
+        sign_and_send_ixs(
+            &self.transaction_cache,
+            &signer,
+            &[create_ix],
+            &SendConfig::default(),
+        )
+        .await?;
+        info!(
+            "Created lookup table {}, pass --lookup-table {} to reuse it",
+            lookup_table_address, lookup_table_address
+        );
+        *address = Some(lookup_table_address);
+        Ok(AddressLookupTableAccount {
+            key: lookup_table_address,
+            addresses: Vec::new(),
+        })
+    }
+
+    /// Loads the table with its active addresses, and whether the last extension's addresses
+    /// were left out because they aren't active yet.
+    async fn load(&self, address: Pubkey) -> Result<(AddressLookupTableAccount, bool)> {
+        let data = self.rpc_client.get_account_data(&address).await?;
+        let current_slot = self
+            .rpc_client
+            .get_slot_with_commitment(CommitmentConfig::processed())
+            .await?;
+        Ok(active_lookup_table(address, &data, current_slot)?)
+    }
+
+    async fn extend(&self, address: Pubkey, new_addresses: Vec<Pubkey>) -> Result<()> {
+        let signer = self.signer();
+        for chunk in new_addresses.chunks(MAX_LOOKUP_TABLE_EXTEND_ADDRESSES) {
+            let extend_ix = extend_lookup_table(
+                address,
+                signer.pubkey(),
+                Some(signer.pubkey()),
+                chunk.to_vec(),
+            );
+            sign_and_send_ixs(
+                &self.transaction_cache,
+                &signer,
+                &[extend_ix],
+                &SendConfig::default(),
+            )
+            .await?;
+            info!(
+                "Extended lookup table {} with {} addresses",
+                address,
+                chunk.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Fetches the lookup tables at `addresses`, e.g. the ones a Jupiter route relies on.
+pub async fn fetch_lookup_tables(
+    rpc_client: &RpcClient,
+    addresses: &[Pubkey],
+) -> Result<Vec<AddressLookupTableAccount>, RpcError> {
+    let accounts = rpc_client.get_multiple_accounts(addresses).await?;
+    addresses
+        .iter()
+        .zip(accounts)
+        .map(|(address, account)| {
+            let account = account.ok_or(RpcError::AccountNotFound(*address))?;
+            deserialize_lookup_table(*address, &account.data)
+        })
+        .collect()
+}
+
+/// Lookup table with the addresses transactions can use at `current_slot`. Addresses added by
+/// an extension can only be looked up from the slot after it, so those of an extension in
+/// `current_slot` are left out, which the returned flag tells.
+fn active_lookup_table(
+    address: Pubkey,
+    data: &[u8],
+    current_slot: u64,
+) -> Result<(AddressLookupTableAccount, bool), RpcError> {
+    let lookup_table = AddressLookupTable::deserialize(data)
+        .map_err(|e| RpcError::invalid_account(address, format!("{:?}", e)))?;
+    let mut addresses = lookup_table.addresses.to_vec();
+    let pending = current_slot <= lookup_table.meta.last_extended_slot
+        && (lookup_table.meta.last_extended_slot_start_index as usize) < addresses.len();
+    if pending {
+        addresses.truncate(lookup_table.meta.last_extended_slot_start_index as usize);
+    }
+    Ok((
+        AddressLookupTableAccount {
+            key: address,
+            addresses,
+        },
+        pending,
+    ))
+}
+
+fn deserialize_lookup_table(
+    address: Pubkey,
+    data: &[u8],
+) -> Result<AddressLookupTableAccount, RpcError> {
+    let lookup_table = AddressLookupTable::deserialize(data)
+        .map_err(|e| RpcError::invalid_account(address, format!("{:?}", e)))?;
+    Ok(AddressLookupTableAccount {
+        key: address,
+        addresses: lookup_table.addresses.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::address_lookup_table::state::LookupTableMeta;
+    use std::borrow::Cow;
+
+    #[test]
+    fn leaves_out_addresses_extended_in_the_current_slot() {
+        let address = Pubkey::new_unique();
+        let addresses = vec![
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let data = AddressLookupTable {
+            meta: LookupTableMeta {
+                last_extended_slot: 100,
+                last_extended_slot_start_index: 1,
+                ..Default::default()
+            },
+            addresses: Cow::Owned(addresses.clone()),
+        }
+        .serialize_for_tests()
+        .unwrap();
+
+        let (lookup_table, pending) = active_lookup_table(address, &data, 100).unwrap();
+        assert_eq!(lookup_table.addresses, addresses[..1]);
+        assert!(pending);
+
+        let (lookup_table, pending) = active_lookup_table(address, &data, 101).unwrap();
+        assert_eq!(lookup_table.addresses, addresses);
+        assert!(!pending);
+    }
+}
@@ -1,288 +1,401 @@
-mod constants;
-mod etherfuse;
-mod field_as_string;
-mod jito;
-mod jupiter;
-mod market_data;
-mod math;
-mod rate_limiter;
-mod strategy;
-mod switchboard;
-mod trading_engine;
-mod transaction;
-mod coingecko;
-
-use crate::{
-    etherfuse::EtherfuseClient, jito::JitoClient, jupiter::JupiterClient,
-    switchboard::SwitchboardClient, trading_engine::TradingEngine,
-};
 use anyhow::Result;
-use clap::{arg, command, Parser};
-use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
-use market_data::{MarketData, MarketDataBuilder};
-use rate_limiter::RateLimiter;
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_program::pubkey::Pubkey;
-use solana_sdk::{
-    commitment_config::CommitmentConfig, signature::read_keypair_file, signer::Signer,
-};
-use std::str::FromStr;
-use std::{sync::Arc, sync::RwLock};
-use std::{fs, time::Duration};
-
-use strategy::{
-    BuyOnEtherfuseSellOnJupiter, BuyOnJupiterSellOnEtherfuse, StrategyEnum, StrategyResult,
+use arb::{
+    approval::{self, PendingTrade},
+    bundle_stats::{BundleStats, LandRate},
+    config::{check_config, parse_approval_config, parse_risk_config, CONFIG_FILE},
+    engine::{self, EngineArgs},
+    position_tracker::{Position, PositionTracker},
+    risk,
+    slippage_stats::{SlippageStats, SlippageSummary},
+    trade_journal::{PnlGrouping, PnlRow, TradeJournal, TradeRecord},
 };
-use toml::Value;
-
-use futures::StreamExt;
-use tokio_tungstenite::connect_async;
-use tokio_tungstenite::tungstenite::protocol::Message;
-use crate::jito::Tip;
+use clap::{arg, command, Parser, Subcommand, ValueEnum};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
 #[derive(Parser)]
 #[command(about, version)]
 struct Args {
-    #[arg(
-        long,
-        value_name = "NETWORK_URL",
-        help = "Network address of your RPC provider",
-        default_value = "https://api.mainnet-beta.solana.com",
-        global = true
-    )]
-    rpc: Option<String>,
+    #[command(subcommand)]
+    command: Option<Commands>,
 
-    #[clap(
-        global = true,
-        short = 'C',
-        long = "config",
-        id = "PATH",
-        help = "Filepath to config file."
-    )]
-    config_file: Option<String>,
+    #[command(flatten)]
+    engine: EngineArgs,
 
     #[arg(
         long,
-        value_name = "KEYPAIR_FILEPATH",
-        help = "Filepath to signer keypair.",
+        value_name = "FORMAT",
+        help = "Log output format, filtered by RUST_LOG (default: info)",
+        value_enum,
+        default_value = "text",
         global = true
     )]
-    keypair: Option<String>,
+    log_format: LogFormat,
 
     #[arg(
         long,
-        value_name = "ETHERFUSE_API_URL",
-        help = "URL to the Etherfuse API",
-        default_value = "https://api.etherfuse.com",
+        value_name = "DSN",
+        help = "Report panics and errors to this Sentry (or compatible) DSN",
+        env = "SENTRY_DSN",
         global = true
     )]
-    etherfuse_url: Option<String>,
-
-    #[arg(
-        long,
-        value_name = "JUPITER_QUOTE_API_URL",
-        help = "URL to the Jupiter Quote API",
-        default_value = "https://quote-api.jup.ag/v6",
-        global = true
-    )]
-    jupiter_quote_url: Option<String>,
+    sentry_dsn: Option<String>,
+}
 
-    #[arg(
-        long,
-        value_name = "JITO_BUNDLES_URL",
-        help = "URL to the Jito Bundles API",
-        default_value = "https://slc.mainnet.block-engine.jito.wtf:443/api/v1/bundles",
-        global = true
-    )]
-    jito_bundles_url: Option<String>,
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
 
-    #[arg(
-        long,
-        value_name = "JITO_WSS_URL",
-        help = "URL to the Jito WSS API",
-        default_value = "ws://bundles-api-rest.jito.wtf/api/v1/bundles/tip_stream",
-        global = true
-    )]
-    jito_wss_url: Option<String>,
+#[derive(Subcommand)]
+enum Commands {
+    #[command(about = "Print bundle land-rate and slippage statistics recorded by the bot")]
+    Stats,
+    #[command(about = "Print the most recent trades from the trade journal")]
+    History {
+        #[arg(long, default_value_t = 20, help = "Number of trades to print")]
+        limit: usize,
+        #[arg(
+            long,
+            value_name = "MINT",
+            help = "Only print trades of this stablebond"
+        )]
+        mint: Option<String>,
+        #[arg(long, help = "Print as CSV")]
+        csv: bool,
+    },
+    #[command(about = "Print realized and unrealized profit from the trade journal")]
+    Pnl {
+        #[arg(
+            long,
+            value_enum,
+            default_value = "mint",
+            help = "What to sum profit up by"
+        )]
+        by: PnlGrouping,
+        #[arg(
+            long,
+            value_name = "DATE",
+            help = "Only count trades made since this UTC date (YYYY-MM-DD) or RFC 3339 timestamp"
+        )]
+        since: Option<String>,
+        #[arg(long, help = "Print as CSV")]
+        csv: bool,
+    },
+    #[command(about = "Resume trading after the risk engine or the kill switch halted it")]
+    Resume {
+        #[arg(long, value_name = "MINT", help = "Only resume this stablebond")]
+        mint: Option<String>,
+    },
+    #[command(about = "Approve a trade held for approval, letting the next such trade execute")]
+    Approve {
+        #[arg(
+            value_name = "TRADE_ID",
+            help = "Id of the trade, as printed by `arb approvals`"
+        )]
+        trade_id: String,
+    },
+    #[command(about = "Print the trades awaiting approval")]
+    Approvals,
+    #[command(about = "Inspect the tokens.toml config file")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
 }
 
+#[derive(Subcommand)]
+enum ConfigCommand {
+    #[command(about = "Report every error in tokens.toml with its line, without running the bot")]
+    Check,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
-    let stablebond_mints = parse_toml_config().unwrap();
-    println!("Stablebond mints: {:?}", stablebond_mints);
-
-    let cli_config = if let Some(config_file) = &args.config_file {
-        solana_cli_config::Config::load(config_file).unwrap_or_else(|_| {
-            eprintln!("error: Could not find config file `{}`", config_file);
-            std::process::exit(1);
-        })
-    } else if let Some(config_file) = &*solana_cli_config::CONFIG_FILE {
-        solana_cli_config::Config::load(config_file).unwrap_or_default()
-    } else {
-        solana_cli_config::Config::default()
-    };
-
-    let keypair_filepath = args.keypair.unwrap_or(cli_config.keypair_path.clone());
-    let wallet_keypair =
-        read_keypair_file(keypair_filepath.clone()).expect("Error reading keypair file");
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(
-        args.rpc.unwrap(),
-        CommitmentConfig::confirmed(),
-    ));
-
-    let jito_jsonrpc_client: HttpClient = HttpClientBuilder::default()
-    .build(args.jito_bundles_url.clone().unwrap())
-    .expect("Error");
-
-    let jito_tip_ws = Arc::new(RwLock::new(0_u64));
-    let jito_tip_ws_clone = Arc::clone(&jito_tip_ws);    
-    let (ws_stream, _) = connect_async(args.jito_wss_url.clone().unwrap()).await.unwrap();
-    let (_, mut read) = ws_stream.split();
-
-    tokio::spawn(async move {
-        while let Some(message) = read.next().await {
-            if let Ok(Message::Text(text)) = message {
-                if let Ok(tips) = serde_json::from_str::<Vec<Tip>>(&text) {
-                    for item in tips {
-                        let mut jito_tip_ws = jito_tip_ws_clone.write().unwrap();
-                        *jito_tip_ws = (item.landed_tips_50th_percentile * (10_f64).powf(9.0)) as u64;
-                    }
-                }
+    match &args.command {
+        Some(Commands::Stats) => {
+            let bundle_stats = BundleStats::new(args.engine.stats_file, String::new());
+            println!("Bundle land rate by region:");
+            print_land_rates(&bundle_stats.by_region());
+            println!("Bundle land rate by tip percentile:");
+            print_land_rates(&bundle_stats.by_tip_percentile());
+            print_slippage(&SlippageStats::new(args.engine.slippage_stats_file).by_route());
+            print_positions(&PositionTracker::new(args.engine.positions_file).positions());
+            return Ok(());
+        }
+        Some(Commands::History { limit, mint, csv }) => {
+            let trades =
+                TradeJournal::open(&args.engine.journal)?.recent_trades(*limit, mint.as_deref())?;
+            print_history(trades, *csv);
+            return Ok(());
+        }
+        Some(Commands::Pnl { by, since, csv }) => {
+            let rows = TradeJournal::open(&args.engine.journal)?.pnl(*by, since.as_deref())?;
+            print_pnl(*by, rows, *csv);
+            return Ok(());
+        }
+        Some(Commands::Resume { mint }) => {
+            let risk_config = parse_risk_config()?;
+            risk::resume(&risk_config.kill_switch_file, mint.as_deref())?;
+            println!("Trading resumed");
+            return Ok(());
+        }
+        Some(Commands::Approve { trade_id }) => {
+            let approval_config = parse_approval_config()?;
+            approval::approve(&approval_config.queue_file, trade_id).await?;
+            println!("Trade {} approved", trade_id);
+            return Ok(());
+        }
+        Some(Commands::Approvals) => {
+            print_queue(approval::pending_trades(
+                &parse_approval_config()?.queue_file,
+            )?);
+            return Ok(());
+        }
+        Some(Commands::Config {
+            command: ConfigCommand::Check,
+        }) => {
+            let errors = check_config();
+            if errors.is_empty() {
+                println!("{} is valid", CONFIG_FILE);
+                return Ok(());
+            }
+            for e in &errors {
+                eprintln!("{}\n", e);
             }
+            std::process::exit(1);
         }
+        None => {}
+    }
+    let _sentry_guard = init_logging(args.log_format, args.sentry_dsn.as_deref());
+    let engine = engine::build(args.engine).await?;
+    // Each mint ticks on its own task, so a slow mint doesn't hold back the others.
+    let supervisor = engine.supervisor;
+    let workers = engine.workers.into_iter().map(|(stablebond_mint, worker)| {
+        supervisor.spawn(format!("mint {}", stablebond_mint), move || {
+            worker.clone().run(stablebond_mint)
+        })
     });
+    futures::future::join_all(workers).await;
+    Ok(())
+}
 
-    let mut jito_client = JitoClient::new(
-        rpc_client.clone(),
-        jito_tip_ws,
-        jito_jsonrpc_client,
-        keypair_filepath.clone(),
-    );
-
-    let etherfuse_client = EtherfuseClient::new(
-        rpc_client.clone(),
-        keypair_filepath.clone(),
-        args.etherfuse_url.clone().unwrap(),
-    );
-
-    if !etherfuse_client
-        .has_kyc_account(&wallet_keypair.pubkey())
-        .await
-    {
-        println!("Etherfuse does not have a kyc account associated with this wallet. Exiting...");
-        std::process::exit(1);
+fn print_land_rates(land_rates: &BTreeMap<String, LandRate>) {
+    for (key, land_rate) in land_rates {
+        println!(
+            "  {}: submitted {}, landed {}, failed {}, timed out {}, land rate {:.1}%, avg time to land {}",
+            key,
+            land_rate.submitted,
+            land_rate.landed,
+            land_rate.failed,
+            land_rate.timed_out,
+            land_rate.land_rate() * 100.0,
+            land_rate
+                .average_time_to_land_ms()
+                .map(|ms| format!("{}ms", ms))
+                .unwrap_or_else(|| "-".to_string()),
+        );
     }
+}
 
-    let rate_limiter = RateLimiter::new(1, 1);
-
-    let jupiter_client = JupiterClient::new(
-        args.jupiter_quote_url.clone().unwrap(),
-        keypair_filepath.clone(),
-        rate_limiter.clone(),
-    );
-
-    let switchboard_client = SwitchboardClient::new(rpc_client.clone(), keypair_filepath.clone());
-
-    let buy_on_etherfuse_sell_on_jupiter = BuyOnEtherfuseSellOnJupiter::new(
-        rpc_client.clone(),
-        jupiter_client.clone(),
-        keypair_filepath.clone(),
-        etherfuse_client.clone(),
-    );
-
-    let buy_on_jupiter_sell_on_etherfuse = BuyOnJupiterSellOnEtherfuse::new(
-        rpc_client.clone(),
-        jupiter_client.clone(),
-        keypair_filepath.clone(),
-        etherfuse_client.clone(),
-    );
-
-    loop {        
-        for stablebond_mint in &stablebond_mints {
-            let market_data: MarketData = MarketDataBuilder::new(
-                rpc_client.clone(),
-                wallet_keypair.pubkey(),
-                etherfuse_client.clone(),
-                jito_client.clone(),
-                switchboard_client.clone(),
-            )
-            .with_etherfuse_price_per_token(&stablebond_mint)
-            .await
-            .with_sell_liquidity_usdc_amount(&stablebond_mint)
-            .await
-            .with_purchase_liquidity_stablebond_amount(&stablebond_mint)
-            .await
-            .with_stablebond_holdings_token_amount(&stablebond_mint)
-            .await
-            .with_usdc_holdings_token_amount()
-            .await
-            .with_update_switchboard_oracle_tx(&stablebond_mint)
-            .await
-            .with_sol_price()
-            .await
-            .build();
-        
-            match market_data.sol_price {
-                Some(price) => println!("Current SOL price: ${:.2}", price),
-                None => {
-                    println!("Warning: Unable to get SOL price, skipping this iteration");
-                    continue;
-                }
-            };
-
-            let strategies = TradingEngine::new()
-                .add_strategy(StrategyEnum::BuyOnEtherfuseSellOnJupiter(
-                    buy_on_etherfuse_sell_on_jupiter.clone(),
-                ))
-                .add_strategy(StrategyEnum::BuyOnJupiterSellOnEtherfuse(
-                    buy_on_jupiter_sell_on_etherfuse.clone(),
-                ))
-                .run_strategies(&market_data, &stablebond_mint)
-                .await;
-
-            if strategies.is_empty() {
-                println!("No strategies found for {:?}", stablebond_mint);
-                continue;
-            }
+fn print_slippage(summaries: &BTreeMap<String, SlippageSummary>) {
+    println!("Slippage by venue and route:");
+    for (key, summary) in summaries {
+        println!(
+            "  {}: fills {}, avg slippage {:.1} bps, worst {:.1} bps",
+            key,
+            summary.fills,
+            summary.average_slippage_bps(),
+            summary.worst_slippage_bps,
+        );
+    }
+}
 
-            let mut most_profitable_strategy: StrategyResult = strategies[0].clone();
-            for s in strategies {
-                if s.profit > most_profitable_strategy.profit {
-                    most_profitable_strategy = s.clone();
-                }
-            }
+fn print_positions(positions: &BTreeMap<String, Position>) {
+    println!("Positions:");
+    for (mint, position) in positions {
+        println!(
+            "  {}: size {:.6} @ ${:.6}, exposure ${:.2}, unrealized ${} (NAV) ${} \
+             (Jupiter bid), realized ${:.4}",
+            mint,
+            position.size,
+            position.average_entry_price,
+            position.exposure_usd(),
+            position
+                .unrealized_pnl_nav_usd()
+                .map_or("-".to_string(), |pnl| format!("{:.4}", pnl)),
+            position
+                .unrealized_pnl_bid_usd()
+                .map_or("-".to_string(), |pnl| format!("{:.4}", pnl)),
+            position.realized_pnl_usd,
+        );
+    }
+}
 
+fn print_history(trades: Vec<TradeRecord>, csv: bool) {
+    if csv {
+        println!(
+            "trade_id,trade_uuid,created_at,mint,strategy,backend,status,usdc_amount,\
+             expected_profit_usd,realized_profit_usd,tip_lamports"
+        );
+        for trade in trades {
             println!(
-                "==================================Most profitable strategy: {:?} ==================================",
-                most_profitable_strategy
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                trade.trade_id,
+                trade.trade_uuid.unwrap_or_default(),
+                trade.created_at,
+                trade.mint,
+                trade.strategy,
+                trade.backend,
+                trade.status,
+                trade.usdc_amount,
+                trade.expected_profit_usd,
+                trade
+                    .realized_profit_usd
+                    .map_or(String::new(), |profit| profit.to_string()),
+                trade
+                    .tip_lamports
+                    .map_or(String::new(), |tip| tip.to_string()),
             );
-            let mut txs = most_profitable_strategy.txs;
-            if let Some(update_oracle_tx) = market_data.switchboard_update_tx {
-                txs.insert(0, update_oracle_tx);
-            }
-            if let Err(e) = jito_client.send_bundle(&txs).await {
-                println!("Error sending bundle: {:?}", e)
-            }
         }
-        println!("========== Sleeping for 5 minutes ==========");
-        tokio::time::sleep(Duration::from_secs(60 * 5)).await;
+        return;
+    }
+    println!("Recent trades:");
+    for trade in trades {
+        println!(
+            "  {} {} {} via {}: {}, {:.2} USDC, expected ${:.4}, realized {}",
+            trade.created_at,
+            trade.mint,
+            trade.strategy,
+            trade.backend,
+            trade.status,
+            trade.usdc_amount,
+            trade.expected_profit_usd,
+            trade
+                .realized_profit_usd
+                .map_or("-".to_string(), |profit| format!("${:.4}", profit)),
+        );
     }
 }
 
-fn parse_toml_config() -> Result<Vec<Pubkey>> {
-    let toml_str = fs::read_to_string("tokens.toml")?;
-    let value = toml_str.parse::<Value>()?;
-
-    let mut result: Vec<Pubkey> = Vec::new();
-    if let Some(tokens) = value.get("tokens").and_then(|v| v.as_array()) {
-        for token in tokens {
-            if let Some(s) = token.as_str() {
-                result.push(Pubkey::from_str(s).unwrap());
-            }
+fn print_pnl(grouping: PnlGrouping, rows: Vec<PnlRow>, csv: bool) {
+    if csv {
+        println!(
+            "{},trades,landed,realized_profit_usd,unrealized_profit_usd,tip_lamports,\
+             gross_spread_usd,jupiter_price_impact_usd,jupiter_slippage_usd,\
+             etherfuse_fee_usd,jito_tip_usd,priority_fee_usd,base_fee_usd,\
+             oracle_update_usd,other_cost_usd",
+            format!("{:?}", grouping).to_lowercase()
+        );
+        for row in rows {
+            let attribution = &row.attribution;
+            println!(
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                row.key,
+                row.trades,
+                row.landed,
+                row.realized_profit_usd,
+                row.unrealized_profit_usd,
+                row.tip_lamports,
+                attribution.gross_spread_usd,
+                attribution.jupiter_price_impact_usd,
+                attribution.jupiter_slippage_usd,
+                attribution.etherfuse_fee_usd,
+                attribution.jito_tip_usd,
+                attribution.priority_fee_usd,
+                attribution.base_fee_usd,
+                attribution.oracle_update_usd,
+                attribution.other_usd,
+            );
         }
+        return;
+    }
+    println!("PnL by {}:", format!("{:?}", grouping).to_lowercase());
+    let mut total_realized = Decimal::ZERO;
+    let mut total_unrealized = Decimal::ZERO;
+    for row in rows {
+        println!(
+            "  {}: trades {}, landed {}, realized ${:.4}, unrealized ${:.4}, tips {} lamports",
+            row.key,
+            row.trades,
+            row.landed,
+            row.realized_profit_usd,
+            row.unrealized_profit_usd,
+            row.tip_lamports,
+        );
+        let attribution = &row.attribution;
+        println!(
+            "    gross spread ${:.4} - price impact ${:.4} - slippage ${:.4} \
+             - Etherfuse fee ${:.4} - Jito tip ${:.4} - priority fees ${:.4} \
+             - base fees ${:.4} - oracle updates ${:.4} - other ${:.4}",
+            attribution.gross_spread_usd,
+            attribution.jupiter_price_impact_usd,
+            attribution.jupiter_slippage_usd,
+            attribution.etherfuse_fee_usd,
+            attribution.jito_tip_usd,
+            attribution.priority_fee_usd,
+            attribution.base_fee_usd,
+            attribution.oracle_update_usd,
+            attribution.other_usd,
+        );
+        total_realized += row.realized_profit_usd;
+        total_unrealized += row.unrealized_profit_usd;
+    }
+    println!(
+        "Total: realized ${:.4}, unrealized ${:.4}",
+        total_realized, total_unrealized
+    );
+}
+
+fn print_queue(queue: BTreeMap<String, PendingTrade>) {
+    println!("Trades awaiting approval:");
+    for (trade_id, trade) in queue {
+        println!(
+            "  {}: {} {} ${:.2}, expected profit ${:.4}, queued {}{}",
+            trade_id,
+            trade.mint,
+            trade.strategy,
+            trade.usdc_amount,
+            trade.expected_profit_usd,
+            trade.created_at,
+            trade
+                .approved_at
+                .map_or(String::new(), |at| format!(", approved {}", at)),
+        );
     }
+}
 
-    Ok(result)
+/// Error events, along with the fields of the spans they happened in (mint, strategy, bundle
+/// id), and panics are reported to Sentry when `sentry_dsn` is set. The returned guard flushes
+/// pending reports when dropped.
+fn init_logging(
+    log_format: LogFormat,
+    sentry_dsn: Option<&str>,
+) -> Option<sentry::ClientInitGuard> {
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt_layer = match log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
+    let sentry_guard = sentry_dsn.map(|dsn| {
+        sentry::init((
+            dsn,
+            sentry::ClientOptions {
+                release: sentry::release_name!(),
+                ..Default::default()
+            },
+        ))
+    });
+    let sentry_layer = sentry_guard
+        .is_some()
+        .then(|| sentry_tracing::layer().enable_span_attributes());
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(fmt_layer)
+        .with(sentry_layer)
+        .init();
+    sentry_guard
 }
@@ -1,6 +1,13 @@
-use crate::constants::{MAX_STABLEBOND_AMOUNT_PER_TRADE, USDC_MINT};
-use crate::etherfuse::EtherfuseClient;
-use crate::{jito::JitoClient, math, switchboard::SwitchboardClient};
+use crate::balance_reservations::BalanceReservations;
+use crate::constants::{DEFAULT_COMPUTE_UNIT_PRICE, MAX_STABLEBOND_AMOUNT_PER_TRADE, USDC_MINT};
+use crate::cost_model::CostModel;
+use crate::error_aggregator::report_error;
+use crate::pool_state::{PoolState, PoolStateReader};
+use crate::providers::{BondVenue, OraclePriceSource, SolPriceProvider};
+use crate::{
+    jito::TipPercentile, math::UiAmountExt, priority_fee::PriorityFeeEstimator,
+    providers::BundleSubmitter,
+};
 use anyhow::Result;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
@@ -10,9 +17,9 @@ use spl_associated_token_account::{
 };
 use spl_token_2022::ID as SPL_TOKEN_2022_PROGRAM_ID;
 use std::cmp::min;
+use std::collections::HashSet;
 use std::{str::FromStr, sync::Arc};
-use crate::coingecko::get_sol_price;
-
+use tracing::{info, warn};
 
 pub struct MarketData {
     pub etherfuse_price_per_token: Option<f64>,
@@ -21,17 +28,45 @@ pub struct MarketData {
     pub purchase_liquidity_stablebond_amount: Option<u64>,
     pub usdc_holdings_token_amount: Option<u64>,
     pub jito_tip: Option<u64>,
-    pub jito_tip_usd_price: Option<f64>,
     pub sol_price: Option<f64>,
-    pub switchboard_update_tx: Option<VersionedTransaction>,
+    /// Tip and transaction fees of a trade, when the SOL price is known.
+    pub cost_model: Option<CostModel>,
+    pub switchboard_update_txs: Vec<VersionedTransaction>,
+    pub switchboard_updated_feeds: Vec<Pubkey>,
+    pub switchboard_update_error: Option<String>,
+    pub etherfuse_exchange_rate: Option<f64>,
+    pub oracle_price: Option<f64>,
+    pub oracle_age_slots: Option<u64>,
+    /// State of the mint's primary pool, to price trade sizes locally.
+    pub pool_state: Option<PoolState>,
+}
+
+impl MarketData {
+    /// Divergence of the on-chain oracle price from the Etherfuse exchange rate, in bps, when
+    /// both are known. A wide one means one side is stale and the spread isn't real.
+    pub fn oracle_divergence_bps(&self) -> Option<f64> {
+        let (oracle_price, exchange_rate) = (self.oracle_price?, self.etherfuse_exchange_rate?);
+        Some((oracle_price - exchange_rate).abs() / exchange_rate * 10_000.0)
+    }
+
+    /// Price per token in USD at the on-chain oracle's exchange rate instead of Etherfuse's,
+    /// when both rates are known.
+    pub fn oracle_price_per_token(&self) -> Option<f64> {
+        let (price, exchange_rate, oracle_price) = (
+            self.etherfuse_price_per_token?,
+            self.etherfuse_exchange_rate?,
+            self.oracle_price?,
+        );
+        (oracle_price > 0.0).then(|| price * exchange_rate / oracle_price)
+    }
 }
 
-pub struct MarketDataBuilder {
+pub struct MarketDataBuilder<V, O, J> {
     pub rpc_client: Arc<RpcClient>,
     pub wallet: Pubkey,
-    pub etherfuse_client: EtherfuseClient,
-    pub jito_client: JitoClient,
-    pub switchboard_client: SwitchboardClient,
+    pub etherfuse_client: V,
+    pub jito_client: J,
+    pub switchboard_client: O,
     pub etherfuse_price_per_token: Option<f64>,
     pub sell_liquidity_usdc_amount: Option<u64>,
     pub stablebond_holdings_token_amount: Option<u64>,
@@ -39,17 +74,24 @@ pub struct MarketDataBuilder {
     pub usdc_holdings_token_amount: Option<u64>,
     pub jito_tip: Option<u64>,
     pub sol_price: Option<f64>,
-    pub jito_tip_usd_price: Option<f64>,
-    pub switchboard_update_tx: Option<VersionedTransaction>,
+    pub compute_unit_price: Option<u64>,
+    pub switchboard_update_txs: Vec<VersionedTransaction>,
+    pub switchboard_updated_feeds: Vec<Pubkey>,
+    pub switchboard_update_error: Option<String>,
+    pub etherfuse_exchange_rate: Option<f64>,
+    pub oracle_price: Option<f64>,
+    pub oracle_age_slots: Option<u64>,
+    pub pool_state: Option<PoolState>,
+    pub balance_reservations: Option<BalanceReservations>,
 }
 
-impl MarketDataBuilder {
+impl<V: BondVenue, O: OraclePriceSource, J: BundleSubmitter> MarketDataBuilder<V, O, J> {
     pub fn new(
         rpc_client: Arc<RpcClient>,
         wallet: Pubkey,
-        etherfuse_client: EtherfuseClient,
-        jito_client: JitoClient,
-        switchboard_client: SwitchboardClient,
+        etherfuse_client: V,
+        jito_client: J,
+        switchboard_client: O,
     ) -> Self {
         MarketDataBuilder {
             rpc_client,
@@ -63,13 +105,50 @@ impl MarketDataBuilder {
             purchase_liquidity_stablebond_amount: None,
             usdc_holdings_token_amount: None,
             jito_tip: None,
-            jito_tip_usd_price: None,
-            sol_price: None,            
-            switchboard_update_tx: None,
+            sol_price: None,
+            compute_unit_price: None,
+            switchboard_update_txs: Vec::new(),
+            switchboard_updated_feeds: Vec::new(),
+            switchboard_update_error: None,
+            etherfuse_exchange_rate: None,
+            oracle_price: None,
+            oracle_age_slots: None,
+            pool_state: None,
+            balance_reservations: None,
+        }
+    }
+
+    /// Deducts the amounts reserved by pending bundles from the holdings.
+    pub fn with_balance_reservations(mut self, balance_reservations: BalanceReservations) -> Self {
+        self.balance_reservations = Some(balance_reservations);
+        self
+    }
+
+    /// Tip and transaction fees valued at the SOL price and the exchange rate, paying the
+    /// default compute unit price like the transaction builder does when it isn't estimated.
+    fn cost_model(&self) -> Option<CostModel> {
+        let cost_model = CostModel::new(self.sol_price?, self.jito_tip?)
+            .map(|cost_model| {
+                cost_model.with_compute_unit_price(
+                    self.compute_unit_price
+                        .unwrap_or(DEFAULT_COMPUTE_UNIT_PRICE),
+                )
+            })
+            .and_then(|cost_model| match self.etherfuse_exchange_rate {
+                Some(exchange_rate) => cost_model.with_exchange_rate(exchange_rate),
+                None => Ok(cost_model),
+            });
+        match cost_model {
+            Ok(cost_model) => Some(cost_model),
+            Err(e) => {
+                report_error("Error building cost model", e);
+                None
+            }
         }
     }
 
     pub fn build(self) -> MarketData {
+        let cost_model = self.cost_model();
         MarketData {
             etherfuse_price_per_token: self.etherfuse_price_per_token,
             sell_liquidity_usdc_amount: self.sell_liquidity_usdc_amount,
@@ -77,26 +156,28 @@ impl MarketDataBuilder {
             purchase_liquidity_stablebond_amount: self.purchase_liquidity_stablebond_amount,
             usdc_holdings_token_amount: self.usdc_holdings_token_amount,
             jito_tip: self.jito_tip,
-            jito_tip_usd_price: self.jito_tip_usd_price,
             sol_price: self.sol_price,
-            switchboard_update_tx: self.switchboard_update_tx,
+            cost_model,
+            switchboard_update_txs: self.switchboard_update_txs,
+            switchboard_updated_feeds: self.switchboard_updated_feeds,
+            switchboard_update_error: self.switchboard_update_error,
+            etherfuse_exchange_rate: self.etherfuse_exchange_rate,
+            oracle_price: self.oracle_price,
+            oracle_age_slots: self.oracle_age_slots,
+            pool_state: self.pool_state,
         }
     }
 
     pub async fn with_etherfuse_price_per_token(mut self, stablebond_mint: &Pubkey) -> Self {
-        self.etherfuse_price_per_token = Some(
-            self.etherfuse_client
-                .get_etherfuse_price(stablebond_mint)
-                .await
-                .unwrap(),
-        );
+        self.etherfuse_price_per_token =
+            Some(self.etherfuse_client.nav(stablebond_mint).await.unwrap());
         self
     }
 
     pub async fn with_sell_liquidity_usdc_amount(mut self, stablebond_mint: &Pubkey) -> Self {
         self.sell_liquidity_usdc_amount = Some(
             self.etherfuse_client
-                .fetch_sell_liquidity_usdc_amount(stablebond_mint)
+                .sell_liquidity_usdc_amount(stablebond_mint)
                 .await
                 .unwrap_or(0),
         );
@@ -109,7 +190,7 @@ impl MarketDataBuilder {
     ) -> Self {
         self.purchase_liquidity_stablebond_amount = Some(
             self.etherfuse_client
-                .fetch_purchase_liquidity_stablebond_amount(stablebond_mint)
+                .purchase_liquidity_stablebond_amount(stablebond_mint)
                 .await
                 .unwrap_or(0),
         );
@@ -117,55 +198,193 @@ impl MarketDataBuilder {
     }
 
     pub async fn with_stablebond_holdings_token_amount(mut self, stablebond_mint: &Pubkey) -> Self {
+        let balance = self
+            .get_spl_token_22_balance(stablebond_mint)
+            .await
+            .unwrap_or(0);
         self.stablebond_holdings_token_amount = Some(min(
-            self.get_spl_token_22_balance(stablebond_mint)
-                .await
-                .unwrap_or(0),
+            self.available(stablebond_mint, balance),
             MAX_STABLEBOND_AMOUNT_PER_TRADE,
         ));
         self
     }
 
     pub async fn with_usdc_holdings_token_amount(mut self) -> Self {
-        let usdc_mint = Pubkey::from_str(&USDC_MINT).unwrap();
-        self.usdc_holdings_token_amount =
-            Some(self.get_spl_token_balance(&usdc_mint).await.unwrap_or(0));
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let balance = self.get_spl_token_balance(&usdc_mint).await.unwrap_or(0);
+        self.usdc_holdings_token_amount = Some(self.available(&usdc_mint, balance));
         self
     }
 
-    pub async fn with_sol_price(mut self) -> Self {
-        // Default to $300 if Coingecko is down.
-        self.jito_tip = Some(*self.jito_client.wss_client.read().unwrap());
-        self.sol_price = Some(get_sol_price().await.unwrap_or(300.0));
-        self.jito_tip_usd_price = Some(self.jito_tip.unwrap() as f64 / 1e9 * self.sol_price.unwrap());
+    fn available(&self, token_mint: &Pubkey, balance: u64) -> u64 {
+        self.balance_reservations
+            .as_ref()
+            .map_or(balance, |reservations| {
+                reservations.available(token_mint, balance)
+            })
+    }
+
+    /// Leaves the SOL price, and so the cost model, unset when `sol_price_provider` has none,
+    /// rather than pricing the tip off a guess.
+    pub async fn with_sol_price(mut self, sol_price_provider: &impl SolPriceProvider) -> Self {
+        self.jito_tip = Some(self.jito_client.tip_lamports(TipPercentile::P50));
+        match sol_price_provider.sol_price().await {
+            Ok(sol_price) => self.sol_price = Some(sol_price),
+            Err(e) => report_error("Error fetching SOL price", e),
+        }
         self
     }
 
-    pub async fn with_update_switchboard_oracle_tx(mut self, stablebond_mint: &Pubkey) -> Self {
-        let payment_feed = match self
-            .etherfuse_client
-            .fetch_payment_feed(stablebond_mint)
-            .await
-        {
+    /// Compute unit price of the trade's transactions, estimated from the fees recently paid to
+    /// write-lock our USDC and stablebond token accounts, which every leg writes to.
+    pub async fn with_compute_unit_price(
+        mut self,
+        priority_fee_estimator: &PriorityFeeEstimator,
+        stablebond_mint: &Pubkey,
+    ) -> Self {
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let writable_accounts = [
+            get_associated_token_address(&self.wallet, &usdc_mint),
+            get_associated_token_address_with_program_id(
+                &self.wallet,
+                stablebond_mint,
+                &SPL_TOKEN_2022_PROGRAM_ID,
+            ),
+        ];
+        match priority_fee_estimator.estimate(&writable_accounts).await {
+            Ok(compute_unit_price) => self.compute_unit_price = Some(compute_unit_price),
+            Err(e) => warn!(
+                "Error estimating priority fee ({:?}), using default compute unit price",
+                e
+            ),
+        }
+        self
+    }
+
+    /// Leaves the pool state unset when the mint has no pool configured or it can't be read,
+    /// and the strategies quote every size on the swap venues instead.
+    pub async fn with_pool_state(
+        mut self,
+        pool_state_reader: &PoolStateReader,
+        stablebond_mint: &Pubkey,
+    ) -> Self {
+        match pool_state_reader.pool_state(stablebond_mint).await {
+            Ok(pool_state) => self.pool_state = pool_state,
+            Err(e) => report_error("Error reading pool state", e),
+        }
+        self
+    }
+
+    /// Feeds in `cranked_feeds` were already updated by a bundle submitted earlier in this tick,
+    /// e.g. for another bond sharing the same FX feed, and are not updated again.
+    pub async fn with_update_switchboard_oracle_tx(
+        mut self,
+        stablebond_mint: &Pubkey,
+        cranked_feeds: &HashSet<Pubkey>,
+    ) -> Self {
+        let payment_feed = match self.etherfuse_client.payment_feed(stablebond_mint).await {
             Ok(payment_feed) => payment_feed,
             Err(e) => {
-                println!("Error fetching payment feed: {:?}", e);
+                report_error("Error fetching payment feed", e);
                 return self;
             }
         };
 
-        let switchboard_public_feed = if payment_feed.quote_price_feed == Pubkey::default() {
-            payment_feed.base_price_feed
-        } else {
-            payment_feed.quote_price_feed
+        let mut stale_feeds = Vec::new();
+        for public_feed in [payment_feed.base_price_feed, payment_feed.quote_price_feed] {
+            if public_feed == Pubkey::default() {
+                continue;
+            }
+            if cranked_feeds.contains(&public_feed) {
+                info!(
+                    "Switchboard feed {} already updated this tick, skipping",
+                    public_feed
+                );
+                continue;
+            }
+            match self.switchboard_client.is_feed_stale(public_feed).await {
+                Ok(false) => info!("Switchboard feed {} is fresh, skipping", public_feed),
+                Ok(true) => stale_feeds.push(public_feed),
+                Err(e) => {
+                    report_error("Error checking switchboard feed staleness", e);
+                    stale_feeds.push(public_feed);
+                }
+            }
+        }
+        if stale_feeds.is_empty() {
+            return self;
+        }
+
+        match self.switchboard_client.update_txs(&stale_feeds).await {
+            Ok(txs) => {
+                self.switchboard_update_txs = txs;
+                self.switchboard_updated_feeds = stale_feeds;
+            }
+            Err(e) => {
+                warn!(
+                    "Unable to update stale switchboard feeds {:?}: {:?}",
+                    stale_feeds, e
+                );
+                self.switchboard_update_error = Some(e.to_string());
+            }
+        }
+        self
+    }
+
+    /// Price of the bond's payment feed according to the on-chain oracle, in the same units as the
+    /// Etherfuse exchange rate (base feed over quote feed), so the two can be cross-checked.
+    pub async fn with_oracle_price(mut self, stablebond_mint: &Pubkey) -> Self {
+        match self.etherfuse_client.exchange_rate(stablebond_mint).await {
+            Ok(exchange_rate) => self.etherfuse_exchange_rate = Some(exchange_rate),
+            Err(e) => report_error("Error fetching etherfuse exchange rate", e),
+        }
+
+        let payment_feed = match self.etherfuse_client.payment_feed(stablebond_mint).await {
+            Ok(payment_feed) => payment_feed,
+            Err(e) => {
+                report_error("Error fetching payment feed", e);
+                return self;
+            }
         };
+        if payment_feed.base_price_feed == Pubkey::default() {
+            return self;
+        }
 
-        self.switchboard_update_tx = Some(
-            self.switchboard_client
-                .get_update_switchboard_oracle_tx(switchboard_public_feed)
+        let base = match self
+            .switchboard_client
+            .feed_value(payment_feed.base_price_feed)
+            .await
+        {
+            Ok(base) => base,
+            Err(e) => {
+                report_error("Error fetching base price feed", e);
+                return self;
+            }
+        };
+        let (price, age_slots) = if payment_feed.quote_price_feed == Pubkey::default() {
+            (base.price, base.age_slots)
+        } else {
+            match self
+                .switchboard_client
+                .feed_value(payment_feed.quote_price_feed)
                 .await
-                .unwrap(),
-        );
+            {
+                Ok(quote) if quote.price > 0.0 => (
+                    base.price / quote.price,
+                    base.age_slots.max(quote.age_slots),
+                ),
+                Ok(_) => {
+                    warn!("Quote price feed has no value");
+                    return self;
+                }
+                Err(e) => {
+                    report_error("Error fetching quote price feed", e);
+                    return self;
+                }
+            }
+        };
+        self.oracle_price = Some(price);
+        self.oracle_age_slots = Some(age_slots);
         self
     }
 
@@ -177,19 +396,19 @@ impl MarketDataBuilder {
             .await?;
 
         if let Some(token_account) = token_account {
-            return Ok(math::to_token_amount(
-                token_account.token_amount.ui_amount.unwrap_or(0.0),
-                token_account.token_amount.decimals,
-            )
-            .unwrap());
+            return token_account
+                .token_amount
+                .ui_amount
+                .unwrap_or(0.0)
+                .to_token_amount_round(token_account.token_amount.decimals);
         }
-        return Ok(0);
+        Ok(0)
     }
 
     async fn get_spl_token_22_balance(&self, mint: &Pubkey) -> Result<u64> {
         let user_token_account = get_associated_token_address_with_program_id(
             &self.wallet,
-            &mint,
+            mint,
             &SPL_TOKEN_2022_PROGRAM_ID,
         );
         let token_account = self
@@ -198,12 +417,12 @@ impl MarketDataBuilder {
             .await?;
 
         if let Some(token_account) = token_account {
-            return Ok(math::to_token_amount(
-                token_account.token_amount.ui_amount.unwrap_or(0.0),
-                token_account.token_amount.decimals,
-            )
-            .unwrap());
+            return token_account
+                .token_amount
+                .ui_amount
+                .unwrap_or(0.0)
+                .to_token_amount_round(token_account.token_amount.decimals);
         }
-        return Ok(0);
+        Ok(0)
     }
 }
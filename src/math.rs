@@ -1,31 +1,120 @@
 #![allow(dead_code)]
 
 use anyhow::{anyhow, Result};
-use solana_account_decoder::parse_token::token_amount_to_ui_amount;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
 use std::fmt::Display;
 
+/// Conversion of raw token amounts to UI amounts.
 pub trait TokenAmountExt {
+    /// `self / 10^decimals`, finite for any `u64` and `u8` decimals.
     fn to_ui_amount(&self, decimals: u8) -> f64;
+
+    /// As `to_ui_amount`, in the `Result` of the other checked helpers.
+    fn checked_to_ui_amount(&self, decimals: u8) -> Result<f64>;
+
+    /// `self / 10^decimals` as an exact decimal, for money math.
+    fn to_ui_decimal(&self, decimals: u8) -> Decimal;
 }
 
 impl TokenAmountExt for u64 {
     fn to_ui_amount(&self, decimals: u8) -> f64 {
-        token_amount_to_ui_amount(*self, decimals)
-            .ui_amount
-            .unwrap()
+        *self as f64 / 10f64.powi(decimals as i32)
+    }
+
+    fn checked_to_ui_amount(&self, decimals: u8) -> Result<f64> {
+        checked_float_div(checked_as_f64(*self)?, checked_powi(10.0, decimals as i32)?)
+    }
+
+    fn to_ui_decimal(&self, decimals: u8) -> Decimal {
+        Decimal::try_from_i128_with_scale(*self as i128, decimals as u32).unwrap_or_else(|_| {
+            // Past the 28 decimals a `Decimal` holds, which no mint has.
+            Decimal::from_f64(self.to_ui_amount(decimals)).unwrap_or_default()
+        })
     }
 }
 
+/// Conversion of UI amounts to raw token amounts.
 pub trait UiAmountExt {
+    /// `self * 10^decimals`, saturating at `0` and `u64::MAX`, with NaN as `0`. Use the
+    /// variants below where the amount isn't a known-good constant.
     fn to_token_amount(&self, decimals: u8) -> u64;
+
+    /// `self * 10^decimals` rounded down, for amounts we spend, which mustn't exceed what
+    /// they were computed from. Errors on negative, NaN or out of range amounts.
+    fn to_token_amount_floor(&self, decimals: u8) -> Result<u64>;
+
+    /// `self * 10^decimals` rounded up, for amounts that must be met, e.g. a tip floor.
+    fn to_token_amount_ceil(&self, decimals: u8) -> Result<u64>;
+
+    /// `self * 10^decimals` rounded half away from zero, for amounts read back as UI amounts.
+    fn to_token_amount_round(&self, decimals: u8) -> Result<u64>;
 }
 
 impl UiAmountExt for f64 {
     fn to_token_amount(&self, decimals: u8) -> u64 {
-        to_token_amount(*self, decimals).unwrap()
+        (self * 10f64.powi(decimals as i32)) as u64
+    }
+
+    fn to_token_amount_floor(&self, decimals: u8) -> Result<u64> {
+        to_token_amount_with_strategy(*self, decimals, RoundingStrategy::ToNegativeInfinity)
+    }
+
+    fn to_token_amount_ceil(&self, decimals: u8) -> Result<u64> {
+        to_token_amount_with_strategy(*self, decimals, RoundingStrategy::ToPositiveInfinity)
+    }
+
+    fn to_token_amount_round(&self, decimals: u8) -> Result<u64> {
+        to_token_amount_with_strategy(*self, decimals, RoundingStrategy::MidpointAwayFromZero)
     }
 }
 
+impl UiAmountExt for Decimal {
+    fn to_token_amount(&self, decimals: u8) -> u64 {
+        self.to_token_amount_floor(decimals)
+            .unwrap_or(if self.is_sign_negative() { 0 } else { u64::MAX })
+    }
+
+    fn to_token_amount_floor(&self, decimals: u8) -> Result<u64> {
+        decimal_to_token_amount(*self, decimals, RoundingStrategy::ToNegativeInfinity)
+    }
+
+    fn to_token_amount_ceil(&self, decimals: u8) -> Result<u64> {
+        decimal_to_token_amount(*self, decimals, RoundingStrategy::ToPositiveInfinity)
+    }
+
+    fn to_token_amount_round(&self, decimals: u8) -> Result<u64> {
+        decimal_to_token_amount(*self, decimals, RoundingStrategy::MidpointAwayFromZero)
+    }
+}
+
+/// Scales in decimals, so that e.g. 0.07 rounded up to 2 decimals is 7, where the `f64` product
+/// is 7.000000000000001.
+fn to_token_amount_with_strategy(
+    ui_amount: f64,
+    decimals: u8,
+    strategy: RoundingStrategy,
+) -> Result<u64> {
+    decimal_to_token_amount(to_decimal(ui_amount)?, decimals, strategy)
+}
+
+fn decimal_to_token_amount(
+    ui_amount: Decimal,
+    decimals: u8,
+    strategy: RoundingStrategy,
+) -> Result<u64> {
+    let scale = 10i128
+        .checked_pow(decimals as u32)
+        .and_then(|scale| Decimal::try_from_i128_with_scale(scale, 0).ok())
+        .ok_or_else(|| anyhow!("Math overflow"))?;
+    ui_amount
+        .checked_mul(scale)
+        .ok_or_else(|| anyhow!("Math overflow"))?
+        .round_dp_with_strategy(0, strategy)
+        .to_u64()
+        .ok_or_else(|| anyhow!("{} isn't a token amount", ui_amount))
+}
+
 pub fn checked_as_f64<T>(arg: T) -> Result<f64>
 where
     T: Display + num_traits::ToPrimitive + Clone,
@@ -46,7 +135,7 @@ where
     if let Some(res) = option {
         Ok(res)
     } else {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     }
 }
 
@@ -57,7 +146,7 @@ where
     if let Some(res) = arg1.checked_div(&arg2) {
         Ok(res)
     } else {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     }
 }
 
@@ -70,7 +159,7 @@ where
     }
     let res = arg1 / arg2;
     if !res.is_finite() {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     } else {
         Ok(res)
     }
@@ -83,7 +172,7 @@ where
     if let Some(res) = arg1.checked_mul(&arg2) {
         Ok(res)
     } else {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     }
 }
 
@@ -93,7 +182,7 @@ where
 {
     let res = arg1 * arg2;
     if !res.is_finite() {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     } else {
         Ok(res)
     }
@@ -110,7 +199,7 @@ pub fn checked_powi(arg: f64, exp: i32) -> Result<f64> {
     if res.is_finite() {
         Ok(res)
     } else {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     }
 }
 
@@ -132,7 +221,7 @@ where
     if let Some(res) = arg1.checked_add(&arg2) {
         Ok(res)
     } else {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     }
 }
 
@@ -143,33 +232,161 @@ where
     if let Some(res) = arg1.checked_sub(&arg2) {
         Ok(res)
     } else {
-        return Err(anyhow!("Math overflow"));
+        Err(anyhow!("Math overflow"))
     }
 }
 
-pub fn to_ui_amount(amount: u64, decimals: u8) -> Result<f64> {
-    checked_float_div(
-        checked_as_f64(amount)?,
-        checked_powi(10.0, decimals as i32)?,
-    )
+/// Decimal of a price or amount parsed from an API as `f64`.
+pub fn to_decimal(value: f64) -> Result<Decimal> {
+    Decimal::from_f64(value).ok_or_else(|| anyhow!("{} isn't a representable amount", value))
 }
 
-pub fn to_token_amount(ui_amount: f64, decimals: u8) -> Result<u64> {
-    checked_as_u64(checked_float_mul(
-        ui_amount,
-        checked_powi(10.0, decimals as i32)?,
-    )?)
+/// `f64` of a decimal, for the limits, metrics and storage that take floats.
+pub fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
 }
 
-pub fn profit_from_arb(sell_price: f64, buy_price: f64, token_amount: f64) -> Result<f64> {
-    // Calculate total received from sell
-    let sell_proceeds = checked_float_mul(token_amount, sell_price)?;
+/// One side of an arbitrage: `token_amount` of the stablebond bought or sold at `price`, in USD
+/// per token.
+#[derive(Clone, Copy, Debug)]
+pub struct Leg {
+    pub price: Decimal,
+    pub token_amount: Decimal,
+}
 
-    // Calculate total spent on buy
-    let buy_cost = checked_float_mul(token_amount, buy_price)?;
+impl Leg {
+    pub fn new(price: Decimal, token_amount: Decimal) -> Self {
+        Self {
+            price,
+            token_amount,
+        }
+    }
 
-    // Calculate net profit
-    let profit = checked_float_sub(sell_proceeds, buy_cost)?;
+    /// USD value of the leg.
+    pub fn notional(&self) -> Result<Decimal> {
+        self.price
+            .checked_mul(self.token_amount)
+            .ok_or_else(|| anyhow!("Math overflow"))
+    }
+}
 
-    Ok(profit)
+/// What a trade costs on top of the prices of its legs, in USD unless noted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FeeBreakdown {
+    /// Fees the venues charge besides their prices.
+    pub venue_fee_usd: Decimal,
+    /// Worst-case slippage of the sell leg below its price, in bps. Zero sells at the price.
+    pub slippage_bps: u64,
+    pub tip_usd: Decimal,
+    /// Transaction fees.
+    pub gas_usd: Decimal,
+}
+
+/// Profit of buying `buy` and selling `sell`, net of `fees`.
+pub fn net_profit(buy: Leg, sell: Leg, fees: FeeBreakdown) -> Result<Decimal> {
+    let slippage = Decimal::from(fees.slippage_bps) / Decimal::from(10_000);
+    let proceeds = sell
+        .notional()?
+        .checked_mul(Decimal::ONE - slippage)
+        .ok_or_else(|| anyhow!("Math overflow"))?;
+    [
+        buy.notional()?,
+        fees.venue_fee_usd,
+        fees.tip_usd,
+        fees.gas_usd,
+    ]
+    .into_iter()
+    .try_fold(proceeds, |profit, cost| profit.checked_sub(cost))
+    .ok_or_else(|| anyhow!("Math overflow"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_token_and_ui_amounts() {
+        assert_eq!(1_500_000u64.to_ui_amount(6), 1.5);
+        assert_eq!(1_500_000u64.checked_to_ui_amount(6).unwrap(), 1.5);
+        assert_eq!(1.5.to_token_amount(6), 1_500_000);
+        assert_eq!(1.5.to_token_amount_floor(6).unwrap(), 1_500_000);
+        assert_eq!(
+            Decimal::new(15, 1).to_token_amount_floor(6).unwrap(),
+            1_500_000
+        );
+        assert_eq!(Decimal::new(-15, 1).to_token_amount(6), 0);
+    }
+
+    #[test]
+    fn extreme_amounts_saturate_or_error() {
+        assert_eq!(u64::MAX.to_ui_amount(20), u64::MAX as f64 / 1e20);
+        assert!(u64::MAX.checked_to_ui_amount(u8::MAX).unwrap().is_finite());
+
+        assert_eq!((-1.0).to_token_amount(6), 0);
+        assert_eq!(f64::NAN.to_token_amount(6), 0);
+        assert_eq!(f64::INFINITY.to_token_amount(6), u64::MAX);
+        assert_eq!(1e20.to_token_amount(6), u64::MAX);
+        for ui_amount in [-1.0, f64::NAN, f64::INFINITY, 1e20] {
+            assert!(ui_amount.to_token_amount_floor(6).is_err(), "{}", ui_amount);
+            assert!(ui_amount.to_token_amount_ceil(6).is_err(), "{}", ui_amount);
+            assert!(ui_amount.to_token_amount_round(6).is_err(), "{}", ui_amount);
+        }
+    }
+
+    #[test]
+    fn rounds_token_amounts_as_asked() {
+        assert_eq!(1.0000005.to_token_amount_floor(6).unwrap(), 1_000_000);
+        assert_eq!(1.0000005.to_token_amount_ceil(6).unwrap(), 1_000_001);
+        assert_eq!(1.0000005.to_token_amount_round(6).unwrap(), 1_000_001);
+        assert_eq!(1.0000004.to_token_amount_round(6).unwrap(), 1_000_000);
+        // The f64 product is 7.000000000000001, which would round up to 8.
+        assert_eq!(0.07.to_token_amount_ceil(2).unwrap(), 7);
+        // And 0.29 * 100 is 28.999999999999996, which would truncate to 28.
+        assert_eq!(0.29.to_token_amount_floor(2).unwrap(), 29);
+        assert!(1.0.to_token_amount_floor(u8::MAX).is_err());
+    }
+
+    fn usd(value: f64) -> Decimal {
+        to_decimal(value).unwrap()
+    }
+
+    #[test]
+    fn net_profit_takes_every_fee() {
+        let three = 3_000_000u64.to_ui_decimal(6);
+        assert_eq!(three, Decimal::new(3, 0));
+        let fees = |venue_fee_usd, slippage_bps, tip_usd, gas_usd| FeeBreakdown {
+            venue_fee_usd: usd(venue_fee_usd),
+            slippage_bps,
+            tip_usd: usd(tip_usd),
+            gas_usd: usd(gas_usd),
+        };
+        let cases = [
+            // 3 * 1.0 - 3 * 0.9 is 0.2999999999999998 in f64.
+            ("spread only", 0.9, 1.0, fees(0.0, 0, 0.0, 0.0), 0.3),
+            ("losing spread", 1.0, 0.9, fees(0.0, 0, 0.0, 0.0), -0.3),
+            ("venue fee", 0.9, 1.0, fees(0.05, 0, 0.0, 0.0), 0.25),
+            ("slippage bound", 0.9, 1.0, fees(0.0, 100, 0.0, 0.0), 0.27),
+            ("tip", 0.9, 1.0, fees(0.0, 0, 0.1, 0.0), 0.2),
+            ("gas", 0.9, 1.0, fees(0.0, 0, 0.0, 0.001), 0.299),
+            ("every fee", 0.9, 1.0, fees(0.05, 100, 0.1, 0.001), 0.119),
+        ];
+        for (name, buy_price, sell_price, fees, profit) in cases {
+            let buy = Leg::new(usd(buy_price), three);
+            let sell = Leg::new(usd(sell_price), three);
+            assert_eq!(
+                net_profit(buy, sell, fees).unwrap(),
+                usd(profit),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn net_profit_errors_on_overflow() {
+        let buy = Leg::new(Decimal::ONE, Decimal::ONE);
+        let sell = Leg::new(Decimal::MAX, Decimal::TWO);
+        assert!(net_profit(buy, sell, FeeBreakdown::default()).is_err());
+        assert!(to_decimal(f64::NAN).is_err());
+    }
 }
@@ -0,0 +1,88 @@
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Default)]
+struct MintHealth {
+    consecutive_failures: u32,
+    /// Times the mint was blacklisted since it last traded successfully.
+    strikes: u32,
+    blacklisted_until: Option<Instant>,
+    reason: String,
+}
+
+/// Stops trading mints that keep failing, e.g. because the bond is paused or its feed is
+/// broken, instead of spending every tick's quotes and tips on them. A mint is blacklisted
+/// after `failure_threshold` failures in a row, for a cool-off that doubles with every strike
+/// up to `max_cool_off`. Once the cool-off ends the mint is on probation: one more failure
+/// blacklists it again, while a success clears its record.
+#[derive(Clone)]
+pub struct MintBlacklist {
+    pub failure_threshold: u32,
+    pub base_cool_off: Duration,
+    pub max_cool_off: Duration,
+    mints: Arc<Mutex<HashMap<Pubkey, MintHealth>>>,
+}
+
+impl MintBlacklist {
+    pub fn new(failure_threshold: u32, base_cool_off: Duration, max_cool_off: Duration) -> Self {
+        Self {
+            failure_threshold,
+            base_cool_off,
+            max_cool_off,
+            mints: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Remaining cool-off and the failure that caused it, when `mint` is blacklisted.
+    pub fn blacklisted(&self, mint: &Pubkey) -> Option<(Duration, String)> {
+        let mints = self.mints.lock().unwrap();
+        let health = mints.get(mint)?;
+        let remaining = health
+            .blacklisted_until?
+            .checked_duration_since(Instant::now())?;
+        Some((remaining, health.reason.clone()))
+    }
+
+    pub fn record_failure(&self, mint: &Pubkey, reason: impl ToString) {
+        let mut mints = self.mints.lock().unwrap();
+        let health = mints.entry(*mint).or_default();
+        // Outcomes of bundles submitted before the mint was blacklisted.
+        if health
+            .blacklisted_until
+            .is_some_and(|until| until > Instant::now())
+        {
+            return;
+        }
+        health.consecutive_failures += 1;
+        let on_probation = health.strikes > 0;
+        if health.consecutive_failures < self.failure_threshold && !on_probation {
+            return;
+        }
+        let cool_off = self
+            .base_cool_off
+            .saturating_mul(2u32.saturating_pow(health.strikes))
+            .min(self.max_cool_off);
+        health.strikes += 1;
+        health.consecutive_failures = 0;
+        health.blacklisted_until = Some(Instant::now() + cool_off);
+        health.reason = reason.to_string();
+        warn!(
+            mint = %mint,
+            strikes = health.strikes,
+            "Blacklisting mint for {}s: {}",
+            cool_off.as_secs(),
+            health.reason
+        );
+    }
+
+    pub fn record_success(&self, mint: &Pubkey) {
+        if let Some(health) = self.mints.lock().unwrap().remove(mint) {
+            if health.strikes > 0 {
+                info!(mint = %mint, "Mint recovered from the blacklist");
+            }
+        }
+    }
+}
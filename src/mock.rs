@@ -0,0 +1,472 @@
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair},
+    system_program,
+    transaction::{Transaction, VersionedTransaction},
+};
+use stablebond_sdk::accounts::PaymentFeed;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{
+    constants::USDC_MINT,
+    error::{ArbResult, ExecutionError, PriceError, QuoteError, RpcError},
+    jito::{BundleHandle, JitoError, TipPercentile},
+    profit_verification::ExpectedProfit,
+    providers::{
+        BondVenue, BundleSubmitter, FeedValue, OraclePriceSource, SolPriceProvider, SwapQuote,
+        SwapVenue,
+    },
+    slippage_stats::Venue,
+};
+
+/// Unsigned transaction whose only instruction carries `label` as its data, so tests can tell
+/// which mock built it.
+pub fn mock_tx(label: &str) -> VersionedTransaction {
+    let ix = Instruction::new_with_bytes(system_program::id(), label.as_bytes(), Vec::new());
+    Transaction::new_with_payer(&[ix], None).into()
+}
+
+/// Label of a transaction built by `mock_tx`.
+pub fn tx_label(tx: &VersionedTransaction) -> Option<String> {
+    let ix = tx.message.instructions().first()?;
+    String::from_utf8(ix.data.clone()).ok()
+}
+
+/// Directory in the system temp dir for a test's files, removed along with them when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates `arb-<prefix>-<random>` in the temp dir.
+    pub fn create(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("arb-{}-{}", prefix, rand::random::<u64>()));
+        fs::create_dir_all(&path).expect("Unable to create temp dir");
+        Self(path)
+    }
+
+    /// Path of the file `name` in the directory.
+    pub fn file(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().to_string()
+    }
+
+    /// Writes `keypair` to the directory, for clients that read their signer from a file.
+    pub fn keypair_file(&self, keypair: &Keypair) -> String {
+        let path = self.file("keypair.json");
+        write_keypair_file(keypair, &path).expect("Unable to write keypair file");
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteSide {
+    /// USDC for the stablebond.
+    Buy,
+    /// The stablebond for USDC.
+    Sell,
+}
+
+/// Scripted answer to a quote request.
+#[derive(Clone, Debug)]
+pub enum MockQuote {
+    /// Price in USD per token.
+    Price(f64),
+    /// No answer, as `QuoteError::Request`, e.g. a timeout.
+    Error(String),
+    /// Refused, as `QuoteError::Api`, e.g. no route for the amount.
+    Rejected(String),
+}
+
+type PriceCurve = Arc<dyn Fn(QuoteSide, u64) -> f64 + Send + Sync>;
+
+/// Quotes from a script, then from a price curve, instead of Jupiter or another venue.
+#[derive(Clone)]
+pub struct MockSwapVenue {
+    venue: Venue,
+    script: Arc<Mutex<VecDeque<MockQuote>>>,
+    curve: PriceCurve,
+    latency: Duration,
+    requests: Arc<Mutex<Vec<(QuoteSide, u64)>>>,
+    swaps: Arc<Mutex<Vec<SwapQuote>>>,
+    swap_failure: Option<String>,
+}
+
+impl MockSwapVenue {
+    /// Quotes every size at `price`.
+    pub fn new(price: f64) -> Self {
+        Self {
+            venue: Venue::Jupiter,
+            script: Arc::default(),
+            curve: Arc::new(move |_, _| price),
+            latency: Duration::ZERO,
+            requests: Arc::default(),
+            swaps: Arc::default(),
+            swap_failure: None,
+        }
+    }
+
+    /// Prices each quote by its side and the amount it trades, USDC for buys and the
+    /// stablebond for sells, e.g. to model price impact.
+    pub fn with_price_curve(
+        mut self,
+        curve: impl Fn(QuoteSide, u64) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        self.curve = Arc::new(curve);
+        self
+    }
+
+    /// Quotes as `venue` instead of Jupiter.
+    pub fn with_venue(mut self, venue: Venue) -> Self {
+        self.venue = venue;
+        self
+    }
+
+    /// Answers the next requests with `script`, in order, before falling back to the curve.
+    pub fn with_script(self, script: impl IntoIterator<Item = MockQuote>) -> Self {
+        self.script.lock().unwrap().extend(script);
+        self
+    }
+
+    /// Answers each quote after `latency`, like a round trip to Jupiter.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Fails to build every swap transaction with `message`.
+    pub fn with_swap_failure(mut self, message: &str) -> Self {
+        self.swap_failure = Some(message.to_string());
+        self
+    }
+
+    /// Every quote requested, in order.
+    pub fn requests(&self) -> Vec<(QuoteSide, u64)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Every quote a swap transaction was built for, in order.
+    pub fn swaps(&self) -> Vec<SwapQuote> {
+        self.swaps.lock().unwrap().clone()
+    }
+
+    async fn quote(
+        &self,
+        side: QuoteSide,
+        stablebond_mint: &Pubkey,
+        amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        self.requests.lock().unwrap().push((side, amount));
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        let price = match self.script.lock().unwrap().pop_front() {
+            Some(MockQuote::Price(price)) => price,
+            Some(MockQuote::Error(message)) => return Err(QuoteError::Request(message).into()),
+            Some(MockQuote::Rejected(message)) => return Err(QuoteError::Api(message).into()),
+            None => (self.curve)(side, amount),
+        };
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let (input_mint, output_mint, out_amount) = match side {
+            QuoteSide::Buy => (usdc_mint, *stablebond_mint, (amount as f64 / price) as u64),
+            QuoteSide::Sell => (*stablebond_mint, usdc_mint, (amount as f64 * price) as u64),
+        };
+        let quote = SwapQuote {
+            venue: self.venue,
+            input_mint,
+            in_amount: amount,
+            output_mint,
+            out_amount,
+            price_impact_pct: 0.0,
+            route: String::new(),
+            context_slot: 0,
+            fetched_at: std::time::Instant::now(),
+            response: serde_json::Value::Null,
+        };
+        Ok((price, quote))
+    }
+}
+
+impl SwapVenue for MockSwapVenue {
+    fn venue(&self) -> Venue {
+        self.venue
+    }
+
+    async fn buy_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        usdc_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        self.quote(QuoteSide::Buy, stablebond_mint, usdc_amount)
+            .await
+    }
+
+    async fn sell_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        stablebond_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        self.quote(QuoteSide::Sell, stablebond_mint, stablebond_amount)
+            .await
+    }
+
+    async fn swap_tx(&mut self, quote: SwapQuote) -> ArbResult<VersionedTransaction> {
+        self.swaps.lock().unwrap().push(quote);
+        if let Some(message) = &self.swap_failure {
+            return Err(ExecutionError::Build(message.clone()).into());
+        }
+        Ok(mock_tx(&format!("{:?}_swap", self.venue).to_lowercase()))
+    }
+}
+
+/// Etherfuse stand-in with a fixed NAV and liquidity.
+#[derive(Clone)]
+pub struct MockBondVenue {
+    pub nav: f64,
+    pub exchange_rate: f64,
+    pub payment_feed: Option<PaymentFeed>,
+    pub sell_liquidity_usdc_amount: u64,
+    pub purchase_liquidity_stablebond_amount: u64,
+    purchases: Arc<Mutex<Vec<u64>>>,
+    redemptions: Arc<Mutex<Vec<u64>>>,
+}
+
+impl MockBondVenue {
+    /// Venue without liquidity or payment feed.
+    pub fn new(nav: f64) -> Self {
+        Self {
+            nav,
+            exchange_rate: 1.0,
+            payment_feed: None,
+            sell_liquidity_usdc_amount: 0,
+            purchase_liquidity_stablebond_amount: 0,
+            purchases: Arc::default(),
+            redemptions: Arc::default(),
+        }
+    }
+
+    pub fn with_sell_liquidity(mut self, usdc_amount: u64) -> Self {
+        self.sell_liquidity_usdc_amount = usdc_amount;
+        self
+    }
+
+    pub fn with_purchase_liquidity(mut self, stablebond_amount: u64) -> Self {
+        self.purchase_liquidity_stablebond_amount = stablebond_amount;
+        self
+    }
+
+    pub fn with_payment_feed(mut self, exchange_rate: f64, payment_feed: PaymentFeed) -> Self {
+        self.exchange_rate = exchange_rate;
+        self.payment_feed = Some(payment_feed);
+        self
+    }
+
+    /// USDC amounts of the purchase transactions built, in order.
+    pub fn purchases(&self) -> Vec<u64> {
+        self.purchases.lock().unwrap().clone()
+    }
+
+    /// Stablebond amounts of the redemption transactions built, in order.
+    pub fn redemptions(&self) -> Vec<u64> {
+        self.redemptions.lock().unwrap().clone()
+    }
+}
+
+impl BondVenue for MockBondVenue {
+    async fn nav(&self, _stablebond_mint: &Pubkey) -> ArbResult<f64> {
+        Ok(self.nav)
+    }
+
+    async fn exchange_rate(&self, _stablebond_mint: &Pubkey) -> ArbResult<f64> {
+        Ok(self.exchange_rate)
+    }
+
+    async fn payment_feed(&self, stablebond_mint: &Pubkey) -> ArbResult<PaymentFeed> {
+        self.payment_feed
+            .clone()
+            .ok_or_else(|| RpcError::AccountNotFound(*stablebond_mint).into())
+    }
+
+    async fn sell_liquidity_usdc_amount(&self, _stablebond_mint: &Pubkey) -> ArbResult<u64> {
+        Ok(self.sell_liquidity_usdc_amount)
+    }
+
+    async fn purchase_liquidity_stablebond_amount(
+        &self,
+        _stablebond_mint: &Pubkey,
+    ) -> ArbResult<u64> {
+        Ok(self.purchase_liquidity_stablebond_amount)
+    }
+
+    async fn purchase_tx(
+        &self,
+        usdc_amount: u64,
+        _stablebond_mint: Pubkey,
+    ) -> ArbResult<VersionedTransaction> {
+        self.purchases.lock().unwrap().push(usdc_amount);
+        Ok(mock_tx("etherfuse_purchase"))
+    }
+
+    async fn redemption_tx(
+        &self,
+        stablebond_amount: u64,
+        _stablebond_mint: Pubkey,
+    ) -> ArbResult<VersionedTransaction> {
+        self.redemptions.lock().unwrap().push(stablebond_amount);
+        Ok(mock_tx("etherfuse_redemption"))
+    }
+}
+
+/// A bundle handed to `MockBundleSubmitter`.
+#[derive(Clone, Debug)]
+pub struct SubmittedBundle {
+    pub bundle_id: String,
+    pub txs: Vec<VersionedTransaction>,
+    pub max_tip_lamports: u64,
+}
+
+/// Block engine stand-in that accepts every bundle, or rejects every one as unavailable.
+#[derive(Clone, Default)]
+pub struct MockBundleSubmitter {
+    pub tip_lamports: u64,
+    pub failure: Option<String>,
+    bundles: Arc<Mutex<Vec<SubmittedBundle>>>,
+}
+
+impl MockBundleSubmitter {
+    pub fn new(tip_lamports: u64) -> Self {
+        Self {
+            tip_lamports,
+            ..Default::default()
+        }
+    }
+
+    /// Rejects every bundle with `JitoError::Unavailable(message)`.
+    pub fn with_failure(mut self, message: &str) -> Self {
+        self.failure = Some(message.to_string());
+        self
+    }
+
+    /// Every bundle accepted, in order.
+    pub fn bundles(&self) -> Vec<SubmittedBundle> {
+        self.bundles.lock().unwrap().clone()
+    }
+}
+
+impl BundleSubmitter for MockBundleSubmitter {
+    fn tip_lamports(&self, _percentile: TipPercentile) -> u64 {
+        self.tip_lamports
+    }
+
+    async fn send_bundle(
+        &mut self,
+        txs: &[VersionedTransaction],
+        max_tip_lamports: u64,
+        _expected_profit: ExpectedProfit,
+    ) -> Result<BundleHandle, JitoError> {
+        if let Some(message) = &self.failure {
+            return Err(JitoError::Unavailable(message.clone()));
+        }
+        let mut bundles = self.bundles.lock().unwrap();
+        let bundle_id = format!("mock-bundle-{}", bundles.len());
+        let signatures = txs.iter().map(|tx| tx.signatures[0]).collect();
+        bundles.push(SubmittedBundle {
+            bundle_id: bundle_id.clone(),
+            txs: txs.to_vec(),
+            max_tip_lamports,
+        });
+        Ok(BundleHandle {
+            bundle_id,
+            submitted_at: std::time::Instant::now(),
+            signatures,
+        })
+    }
+}
+
+/// Switchboard stand-in serving fixed feed values.
+#[derive(Clone, Default)]
+pub struct MockOraclePriceSource {
+    pub feeds: HashMap<Pubkey, FeedValue>,
+    updates: Arc<Mutex<Vec<Vec<Pubkey>>>>,
+}
+
+impl MockOraclePriceSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_feed(mut self, public_feed: Pubkey, feed_value: FeedValue) -> Self {
+        self.feeds.insert(public_feed, feed_value);
+        self
+    }
+
+    /// Feeds of every update requested, in order.
+    pub fn updates(&self) -> Vec<Vec<Pubkey>> {
+        self.updates.lock().unwrap().clone()
+    }
+}
+
+impl OraclePriceSource for MockOraclePriceSource {
+    async fn feed_value(&self, public_feed: Pubkey) -> ArbResult<FeedValue> {
+        self.feeds
+            .get(&public_feed)
+            .cloned()
+            .ok_or_else(|| RpcError::AccountNotFound(public_feed).into())
+    }
+
+    async fn update_txs(&self, public_feeds: &[Pubkey]) -> ArbResult<Vec<VersionedTransaction>> {
+        self.updates.lock().unwrap().push(public_feeds.to_vec());
+        Ok(vec![mock_tx("switchboard_update")])
+    }
+}
+
+/// SOL price source quoting a fixed price, or failing like a source that's down.
+#[derive(Clone, Default)]
+pub struct MockSolPriceProvider {
+    pub price: Option<f64>,
+    calls: Arc<Mutex<usize>>,
+}
+
+impl MockSolPriceProvider {
+    pub fn new(price: f64) -> Self {
+        Self {
+            price: Some(price),
+            ..Self::default()
+        }
+    }
+
+    pub fn failing() -> Self {
+        Self::default()
+    }
+
+    /// Number of prices asked for.
+    pub fn calls(&self) -> usize {
+        *self.calls.lock().unwrap()
+    }
+}
+
+impl SolPriceProvider for MockSolPriceProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn sol_price(&self) -> ArbResult<f64> {
+        *self.calls.lock().unwrap() += 1;
+        self.price.ok_or_else(|| {
+            PriceError::Request {
+                provider: self.name(),
+                message: "Service unavailable".to_string(),
+            }
+            .into()
+        })
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Deserialize;
+use solana_sdk::signature::Signature;
+
+use crate::{
+    constants::EXPLORER_TX_URL,
+    profit_verification::{ExpectedProfit, RealizedProfit},
+};
+#[cfg(feature = "notifications")]
+use crate::{
+    error_aggregator::report_error, http::default_http_client, telegram::TelegramNotifier,
+};
+#[cfg(feature = "notifications")]
+use enum_dispatch::enum_dispatch;
+#[cfg(feature = "notifications")]
+use reqwest_middleware::ClientWithMiddleware;
+#[cfg(feature = "notifications")]
+use serde_json::{json, Value};
+
+/// How urgently an event needs a human, lowest first.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Landed trades.
+    #[default]
+    Info,
+    /// Events that may need a look, such as unwinds and trades awaiting approval.
+    Warning,
+    /// Trading stopped or a position is stuck, such as risk halts and failed unwinds.
+    Critical,
+}
+
+#[cfg(feature = "notifications")]
+#[enum_dispatch]
+pub trait Notifier {
+    fn name(&self) -> &'static str;
+
+    /// Events below this severity aren't sent.
+    fn min_severity(&self) -> Severity;
+
+    /// Sends `text` in the background, so a slow or unreachable chat never holds up a trade.
+    fn send(&self, severity: Severity, text: &str);
+}
+
+#[cfg(feature = "notifications")]
+#[enum_dispatch(Notifier)]
+#[derive(Clone)]
+pub enum NotifierEnum {
+    Telegram(TelegramNotifier),
+    Discord(DiscordNotifier),
+    Slack(SlackNotifier),
+}
+
+/// `[discord]` and `[slack]` sections of the config file.
+#[cfg(feature = "notifications")]
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub webhook_url: String,
+    #[serde(default)]
+    pub min_severity: Severity,
+}
+
+/// Posts to a Discord channel through a webhook.
+#[cfg(feature = "notifications")]
+#[derive(Clone)]
+pub struct DiscordNotifier {
+    pub config: WebhookConfig,
+    pub http_client: ClientWithMiddleware,
+}
+
+#[cfg(feature = "notifications")]
+impl DiscordNotifier {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self {
+            config,
+            http_client: default_http_client(),
+        }
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
+    fn send(&self, severity: Severity, text: &str) {
+        post_json(
+            self.name(),
+            &self.http_client,
+            self.config.webhook_url.clone(),
+            json!({ "content": format!("{} {}", severity_prefix(severity), text) }),
+        );
+    }
+}
+
+/// Posts to a Slack channel through an incoming webhook.
+#[cfg(feature = "notifications")]
+#[derive(Clone)]
+pub struct SlackNotifier {
+    pub config: WebhookConfig,
+    pub http_client: ClientWithMiddleware,
+}
+
+#[cfg(feature = "notifications")]
+impl SlackNotifier {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self {
+            config,
+            http_client: default_http_client(),
+        }
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
+    fn send(&self, severity: Severity, text: &str) {
+        post_json(
+            self.name(),
+            &self.http_client,
+            self.config.webhook_url.clone(),
+            json!({ "text": format!("{} {}", severity_prefix(severity), text) }),
+        );
+    }
+}
+
+/// Pushes trade events and failures to every configured chat whose minimum severity they
+/// reach, for operators running the bot unattended. Does nothing when none is configured, or
+/// without the `notifications` feature.
+#[derive(Clone, Default)]
+pub struct Notifications {
+    #[cfg(feature = "notifications")]
+    notifiers: Vec<NotifierEnum>,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(feature = "notifications")]
+    pub fn add_notifier(&mut self, notifier: NotifierEnum) -> &mut Self {
+        self.notifiers.push(notifier);
+        self
+    }
+
+    /// The Telegram bot, if one is configured.
+    #[cfg(feature = "notifications")]
+    pub fn telegram(&self) -> Option<&TelegramNotifier> {
+        self.notifiers.iter().find_map(|notifier| match notifier {
+            NotifierEnum::Telegram(telegram) => Some(telegram),
+            _ => None,
+        })
+    }
+
+    #[cfg_attr(not(feature = "notifications"), allow(unused_variables))]
+    pub fn notify(&self, severity: Severity, text: impl AsRef<str>) {
+        #[cfg(feature = "notifications")]
+        for notifier in &self.notifiers {
+            if severity >= notifier.min_severity() {
+                notifier.send(severity, text.as_ref());
+            }
+        }
+    }
+
+    /// Mint, direction, size and realized profit of a landed trade, with links to its
+    /// transactions.
+    pub fn notify_trade(
+        &self,
+        expected: &ExpectedProfit,
+        realized: &RealizedProfit,
+        signatures: &[Signature],
+    ) {
+        let direction = match expected.legs.as_slice() {
+            [buy, .., sell] => format!("bought on {:?}, sold on {:?}", buy.venue, sell.venue),
+            _ => "traded".to_string(),
+        };
+        let size = expected.legs.first().map_or(0.0, |leg| leg.input_amount);
+        let mut message = format!(
+            "Trade landed on {}: {} with {:.2} USDC. Realized ${:.4}, expected ${:.4}",
+            expected.stablebond_mint, direction, size, realized.profit_usd, expected.profit_usd
+        );
+        for signature in signatures {
+            message.push_str(&format!("\n{}{}", EXPLORER_TX_URL, signature));
+        }
+        self.notify(Severity::Info, message);
+    }
+}
+
+/// Tag put in front of every message.
+#[cfg(feature = "notifications")]
+pub fn severity_prefix(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "[INFO]",
+        Severity::Warning => "[WARNING]",
+        Severity::Critical => "[CRITICAL]",
+    }
+}
+
+/// POSTs `body` to `url` in the background.
+#[cfg(feature = "notifications")]
+pub fn post_json(name: &'static str, http_client: &ClientWithMiddleware, url: String, body: Value) {
+    let http_client = http_client.clone();
+    tokio::spawn(async move {
+        let response = http_client.post(url).json(&body).send().await;
+        let context = format!("Error sending {} notification", name);
+        match response.map(|response| response.error_for_status()) {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => report_error(&context, e),
+            Err(e) => report_error(&context, e),
+        }
+    });
+}
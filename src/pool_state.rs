@@ -0,0 +1,390 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use solana_client::{client_error::Result as ClientResult, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{
+    account_cache::{get_account, get_multiple_accounts, AccountCache},
+    constants::{
+        POOL_FEE_RATE_DENOMINATOR, RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID, USDC_MINT,
+    },
+    field_as_string,
+};
+
+// Byte offsets of the fields read from Raydium's accounts, after their 8 byte discriminator.
+const CPMM_AMM_CONFIG: usize = 8;
+const CPMM_TOKEN_0_VAULT: usize = 72;
+const CPMM_TOKEN_1_VAULT: usize = 104;
+const CPMM_TOKEN_0_MINT: usize = 168;
+const CPMM_TOKEN_1_MINT: usize = 200;
+const CPMM_PROTOCOL_FEES_TOKEN_0: usize = 341;
+const CPMM_PROTOCOL_FEES_TOKEN_1: usize = 349;
+const CPMM_FUND_FEES_TOKEN_0: usize = 357;
+const CPMM_FUND_FEES_TOKEN_1: usize = 365;
+const CPMM_CONFIG_TRADE_FEE_RATE: usize = 12;
+const CLMM_AMM_CONFIG: usize = 9;
+const CLMM_TOKEN_MINT_0: usize = 73;
+const CLMM_TOKEN_MINT_1: usize = 105;
+const CLMM_LIQUIDITY: usize = 237;
+const CLMM_SQRT_PRICE_X64: usize = 253;
+const CLMM_CONFIG_TRADE_FEE_RATE: usize = 47;
+const TOKEN_ACCOUNT_AMOUNT: usize = 64;
+
+/// Raydium pool programs whose state can be read and priced locally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolKind {
+    /// Constant product pools.
+    Cpmm,
+    /// Concentrated liquidity pools.
+    Clmm,
+}
+
+/// Entry of the `[pools]` section of the config file: the primary stablebond/USDC pool of a
+/// mint.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    pub kind: PoolKind,
+    #[serde(with = "field_as_string")]
+    pub address: Pubkey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoolCurve {
+    /// x * y = k over the vaults' balances, net of the fees the pool owes its owners.
+    ConstantProduct {
+        usdc_reserve: u64,
+        stablebond_reserve: u64,
+    },
+    /// Liquidity active at the current price, the square root of token 1 per token 0 in Q64.64.
+    /// Swaps are priced as if they stay within the current tick range.
+    Concentrated {
+        liquidity: u128,
+        sqrt_price_x64: u128,
+        usdc_is_token_0: bool,
+    },
+}
+
+/// Snapshot of a stablebond/USDC pool, pricing swaps without asking an aggregator. Only an
+/// estimate: it ignores the rest of the market, and tick crossings on concentrated liquidity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoolState {
+    pub curve: PoolCurve,
+    /// Trade fee taken from the input, in millionths.
+    pub fee_rate: u64,
+    /// Slot the state was read at.
+    pub slot: u64,
+}
+
+impl PoolState {
+    /// Stablebonds bought with `usdc_amount`.
+    pub fn buy_output(&self, usdc_amount: u64) -> Option<u64> {
+        self.swap_output(usdc_amount, true)
+    }
+
+    /// USDC received for `stablebond_amount`.
+    pub fn sell_output(&self, stablebond_amount: u64) -> Option<u64> {
+        self.swap_output(stablebond_amount, false)
+    }
+
+    /// USDC paid per stablebond when buying with `usdc_amount`, like `SwapVenue::buy_quote`.
+    pub fn buy_price(&self, usdc_amount: u64) -> Option<f64> {
+        let stablebond_amount = self.buy_output(usdc_amount).filter(|amount| *amount > 0)?;
+        Some(usdc_amount as f64 / stablebond_amount as f64)
+    }
+
+    /// USDC received per stablebond when selling `stablebond_amount`, like
+    /// `SwapVenue::sell_quote`.
+    pub fn sell_price(&self, stablebond_amount: u64) -> Option<f64> {
+        if stablebond_amount == 0 {
+            return None;
+        }
+        let usdc_amount = self.sell_output(stablebond_amount)?;
+        Some(usdc_amount as f64 / stablebond_amount as f64)
+    }
+
+    fn swap_output(&self, amount_in: u64, usdc_in: bool) -> Option<u64> {
+        let fee_rate = self.fee_rate.min(POOL_FEE_RATE_DENOMINATOR);
+        let amount_in = (amount_in as u128 * (POOL_FEE_RATE_DENOMINATOR - fee_rate) as u128
+            / POOL_FEE_RATE_DENOMINATOR as u128) as u64;
+        match self.curve {
+            PoolCurve::ConstantProduct {
+                usdc_reserve,
+                stablebond_reserve,
+            } => {
+                let (reserve_in, reserve_out) = if usdc_in {
+                    (usdc_reserve, stablebond_reserve)
+                } else {
+                    (stablebond_reserve, usdc_reserve)
+                };
+                let denominator = reserve_in as u128 + amount_in as u128;
+                if denominator == 0 {
+                    return None;
+                }
+                u64::try_from(reserve_out as u128 * amount_in as u128 / denominator).ok()
+            }
+            PoolCurve::Concentrated {
+                liquidity,
+                sqrt_price_x64,
+                usdc_is_token_0,
+            } => {
+                if liquidity == 0 || sqrt_price_x64 == 0 {
+                    return None;
+                }
+                let liquidity = liquidity as f64;
+                let sqrt_price = sqrt_price_x64 as f64 / 2f64.powi(64);
+                let amount_in = amount_in as f64;
+                let amount_out = if usdc_in == usdc_is_token_0 {
+                    // Token 0 in pushes the price of token 0 down.
+                    let next_sqrt_price =
+                        liquidity * sqrt_price / (liquidity + amount_in * sqrt_price);
+                    liquidity * (sqrt_price - next_sqrt_price)
+                } else {
+                    let next_sqrt_price = sqrt_price + amount_in / liquidity;
+                    liquidity * (next_sqrt_price - sqrt_price) / (sqrt_price * next_sqrt_price)
+                };
+                amount_out.is_finite().then_some(amount_out.floor() as u64)
+            }
+        }
+    }
+}
+
+/// Reads the state of the pools in the `[pools]` section of the config file, so the strategies
+/// can price their sizes locally and only ask the swap venues to quote the best one.
+#[derive(Clone)]
+pub struct PoolStateReader {
+    pub rpc_client: Arc<RpcClient>,
+    pub pools: HashMap<Pubkey, PoolConfig>,
+    pub account_cache: Option<AccountCache>,
+}
+
+impl PoolStateReader {
+    pub fn new(rpc_client: Arc<RpcClient>, pools: HashMap<Pubkey, PoolConfig>) -> Self {
+        Self {
+            rpc_client,
+            pools,
+            account_cache: None,
+        }
+    }
+
+    /// Reads the pools from the Geyser stream when live.
+    pub fn with_account_cache(mut self, account_cache: AccountCache) -> Self {
+        self.account_cache = Some(account_cache);
+        self
+    }
+
+    /// Accounts the state of `stablebond_mint`'s pool is read from, none when it has no pool.
+    pub async fn watched_accounts(&self, stablebond_mint: &Pubkey) -> Result<Vec<Pubkey>> {
+        let Some(pool) = self.pools.get(stablebond_mint) else {
+            return Ok(Vec::new());
+        };
+        let (_, pool_account) = self.get_account(&pool.address).await?;
+        let data = &pool_account.data;
+        Ok(match pool.kind {
+            PoolKind::Cpmm => vec![
+                pool.address,
+                read_pubkey(data, CPMM_AMM_CONFIG)?,
+                read_pubkey(data, CPMM_TOKEN_0_VAULT)?,
+                read_pubkey(data, CPMM_TOKEN_1_VAULT)?,
+            ],
+            PoolKind::Clmm => vec![pool.address, read_pubkey(data, CLMM_AMM_CONFIG)?],
+        })
+    }
+
+    async fn get_account(&self, pubkey: &Pubkey) -> ClientResult<(u64, Account)> {
+        get_account(&self.rpc_client, self.account_cache.as_ref(), pubkey).await
+    }
+
+    /// State of `stablebond_mint`'s pool, `None` when it has none configured.
+    pub async fn pool_state(&self, stablebond_mint: &Pubkey) -> Result<Option<PoolState>> {
+        let Some(pool) = self.pools.get(stablebond_mint) else {
+            return Ok(None);
+        };
+        let (_, pool_account) = self.get_account(&pool.address).await?;
+        let state = match pool.kind {
+            PoolKind::Cpmm => self.cpmm_state(&pool_account, stablebond_mint).await?,
+            PoolKind::Clmm => self.clmm_state(&pool_account, stablebond_mint).await?,
+        };
+        Ok(Some(state))
+    }
+
+    async fn cpmm_state(&self, pool: &Account, stablebond_mint: &Pubkey) -> Result<PoolState> {
+        check_owner(pool, RAYDIUM_CPMM_PROGRAM_ID)?;
+        let data = &pool.data;
+        let usdc_is_token_0 = usdc_is_token_0(
+            read_pubkey(data, CPMM_TOKEN_0_MINT)?,
+            read_pubkey(data, CPMM_TOKEN_1_MINT)?,
+            stablebond_mint,
+        )?;
+        let accounts = [
+            read_pubkey(data, CPMM_AMM_CONFIG)?,
+            read_pubkey(data, CPMM_TOKEN_0_VAULT)?,
+            read_pubkey(data, CPMM_TOKEN_1_VAULT)?,
+        ];
+        let (slot, fetched) =
+            get_multiple_accounts(&self.rpc_client, self.account_cache.as_ref(), &accounts).await?;
+        let [amm_config, vault_0, vault_1] = fetched.as_slice() else {
+            return Err(anyhow!("Missing accounts of pool {:?}", accounts));
+        };
+        // The vaults also hold the fees owed to the protocol and the fund until collected.
+        let owed_0 = read_u64(data, CPMM_PROTOCOL_FEES_TOKEN_0)?
+            .saturating_add(read_u64(data, CPMM_FUND_FEES_TOKEN_0)?);
+        let owed_1 = read_u64(data, CPMM_PROTOCOL_FEES_TOKEN_1)?
+            .saturating_add(read_u64(data, CPMM_FUND_FEES_TOKEN_1)?);
+        let reserve_0 = read_u64(&vault_0.data, TOKEN_ACCOUNT_AMOUNT)?.saturating_sub(owed_0);
+        let reserve_1 = read_u64(&vault_1.data, TOKEN_ACCOUNT_AMOUNT)?.saturating_sub(owed_1);
+        let (usdc_reserve, stablebond_reserve) = if usdc_is_token_0 {
+            (reserve_0, reserve_1)
+        } else {
+            (reserve_1, reserve_0)
+        };
+        Ok(PoolState {
+            curve: PoolCurve::ConstantProduct {
+                usdc_reserve,
+                stablebond_reserve,
+            },
+            fee_rate: read_u64(&amm_config.data, CPMM_CONFIG_TRADE_FEE_RATE)?,
+            slot,
+        })
+    }
+
+    async fn clmm_state(&self, pool: &Account, stablebond_mint: &Pubkey) -> Result<PoolState> {
+        check_owner(pool, RAYDIUM_CLMM_PROGRAM_ID)?;
+        let data = &pool.data;
+        let usdc_is_token_0 = usdc_is_token_0(
+            read_pubkey(data, CLMM_TOKEN_MINT_0)?,
+            read_pubkey(data, CLMM_TOKEN_MINT_1)?,
+            stablebond_mint,
+        )?;
+        let (slot, config) = self
+            .get_account(&read_pubkey(data, CLMM_AMM_CONFIG)?)
+            .await?;
+        Ok(PoolState {
+            curve: PoolCurve::Concentrated {
+                liquidity: read_u128(data, CLMM_LIQUIDITY)?,
+                sqrt_price_x64: read_u128(data, CLMM_SQRT_PRICE_X64)?,
+                usdc_is_token_0,
+            },
+            fee_rate: read_u32(&config.data, CLMM_CONFIG_TRADE_FEE_RATE)? as u64,
+            slot,
+        })
+    }
+}
+
+fn check_owner(account: &Account, program_id: &str) -> Result<()> {
+    if account.owner != Pubkey::from_str(program_id)? {
+        return Err(anyhow!(
+            "Pool is owned by {}, not {}",
+            account.owner,
+            program_id
+        ));
+    }
+    Ok(())
+}
+
+/// Whether USDC is the pool's token 0, after checking it trades `stablebond_mint` for USDC.
+fn usdc_is_token_0(mint_0: Pubkey, mint_1: Pubkey, stablebond_mint: &Pubkey) -> Result<bool> {
+    let usdc_mint = Pubkey::from_str(USDC_MINT)?;
+    match (mint_0, mint_1) {
+        (mint_0, mint_1) if mint_0 == usdc_mint && mint_1 == *stablebond_mint => Ok(true),
+        (mint_0, mint_1) if mint_0 == *stablebond_mint && mint_1 == usdc_mint => Ok(false),
+        _ => Err(anyhow!(
+            "Pool trades {} for {}, not {} for USDC",
+            mint_0,
+            mint_1,
+            stablebond_mint
+        )),
+    }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("Account data too short, {} bytes", data.len()))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    read_bytes::<32>(data, offset).map(Pubkey::new_from_array)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    read_bytes(data, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    read_bytes(data, offset).map(u64::from_le_bytes)
+}
+
+fn read_u128(data: &[u8], offset: usize) -> Result<u128> {
+    read_bytes(data, offset).map(u128::from_le_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant_product(usdc_reserve: u64, stablebond_reserve: u64) -> PoolState {
+        PoolState {
+            curve: PoolCurve::ConstantProduct {
+                usdc_reserve,
+                stablebond_reserve,
+            },
+            fee_rate: 2_500,
+            slot: 1,
+        }
+    }
+
+    #[test]
+    fn constant_product_takes_the_fee_then_moves_along_the_curve() {
+        let pool = constant_product(1_000_000_000_000, 2_000_000_000_000);
+
+        // 997.5 USDC after the 0.25% fee, out of a million.
+        assert_eq!(pool.buy_output(1_000_000_000), Some(1_993_011_970));
+        assert_eq!(pool.sell_output(2_000_000_000), Some(996_505_985));
+        let small = pool.buy_price(1_000_000).unwrap();
+        let large = pool.buy_price(100_000_000_000).unwrap();
+        assert!(small < large, "{} {}", small, large);
+    }
+
+    #[test]
+    fn concentrated_liquidity_prices_both_directions_at_the_current_price() {
+        // 4 units of token 1 per token 0, USDC being token 1.
+        let pool = PoolState {
+            curve: PoolCurve::Concentrated {
+                liquidity: 1_000_000_000_000_000,
+                sqrt_price_x64: 2 << 64,
+                usdc_is_token_0: false,
+            },
+            fee_rate: 0,
+            slot: 1,
+        };
+
+        let buy_price = pool.buy_price(1_000_000).unwrap();
+        let sell_price = pool.sell_price(1_000_000).unwrap();
+
+        assert!((buy_price - 4.0).abs() < 1e-3, "{}", buy_price);
+        assert!((sell_price - 4.0).abs() < 1e-3, "{}", sell_price);
+        assert!(sell_price <= 4.0 && buy_price >= 4.0);
+    }
+
+    #[test]
+    fn empty_pools_have_no_price() {
+        assert_eq!(constant_product(0, 0).buy_price(1_000_000), None);
+        assert_eq!(constant_product(1_000_000, 1_000_000).sell_price(0), None);
+    }
+
+    #[test]
+    fn rejects_pools_of_other_mints() {
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let stablebond_mint = Pubkey::new_unique();
+
+        assert!(usdc_is_token_0(usdc_mint, stablebond_mint, &stablebond_mint).unwrap());
+        assert!(!usdc_is_token_0(stablebond_mint, usdc_mint, &stablebond_mint).unwrap());
+        assert!(usdc_is_token_0(usdc_mint, Pubkey::new_unique(), &stablebond_mint).is_err());
+        assert!(read_u64(&[0; 8], 4).is_err());
+    }
+}
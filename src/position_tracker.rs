@@ -0,0 +1,161 @@
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// Stablebond amounts below this are rounding dust rather than inventory.
+const DUST_AMOUNT: Decimal = Decimal::from_parts(1, 0, 0, false, 6);
+
+/// Stablebond inventory held in one mint. Amounts are UI amounts and prices are USD per token.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Position {
+    pub size: Decimal,
+    pub average_entry_price: Decimal,
+    /// Realized by reducing the position, against the average entry price.
+    pub realized_pnl_usd: Decimal,
+    /// Latest Etherfuse NAV.
+    pub nav: Option<Decimal>,
+    /// Latest price Jupiter would pay for the whole position.
+    pub jupiter_bid: Option<Decimal>,
+    /// When the position was last opened from flat.
+    pub opened_at: Option<String>,
+    pub updated_at: String,
+}
+
+impl Position {
+    pub fn exposure_usd(&self) -> Decimal {
+        self.size * self.nav.unwrap_or(self.average_entry_price)
+    }
+
+    pub fn unrealized_pnl_nav_usd(&self) -> Option<Decimal> {
+        self.nav
+            .map(|nav| self.size * (nav - self.average_entry_price))
+    }
+
+    pub fn unrealized_pnl_bid_usd(&self) -> Option<Decimal> {
+        self.jupiter_bid
+            .map(|bid| self.size * (bid - self.average_entry_price))
+    }
+
+    /// Adds `amount`, or removes it when negative, at `price`.
+    fn fill(&mut self, amount: Decimal, price: Decimal) {
+        if amount > Decimal::ZERO {
+            if self.size < DUST_AMOUNT {
+                self.opened_at = Some(chrono::Utc::now().to_rfc3339());
+            }
+            self.average_entry_price =
+                (self.size * self.average_entry_price + amount * price) / (self.size + amount);
+            self.size += amount;
+        } else {
+            let reduced = (-amount).min(self.size);
+            self.realized_pnl_usd += reduced * (price - self.average_entry_price);
+            self.size -= reduced;
+            if self.size < DUST_AMOUNT {
+                self.size = Decimal::ZERO;
+                self.opened_at = None;
+            }
+        }
+        self.updated_at = chrono::Utc::now().to_rfc3339();
+    }
+}
+
+/// Stablebond inventory per mint, updated after each landed trade and reconciled against the
+/// wallet's holdings every tick, and marked against both the Etherfuse NAV and the Jupiter bid.
+/// Persisted to disk so entry prices survive restarts.
+#[derive(Clone)]
+pub struct PositionTracker {
+    pub path: String,
+    positions: Arc<Mutex<BTreeMap<String, Position>>>,
+}
+
+impl PositionTracker {
+    /// Loads previously persisted positions from `path`.
+    pub fn new(path: String) -> Self {
+        let positions = load_positions(&path).unwrap_or_default();
+        Self {
+            path,
+            positions: Arc::new(Mutex::new(positions)),
+        }
+    }
+
+    pub fn position(&self, mint: &Pubkey) -> Option<Position> {
+        self.positions
+            .lock()
+            .unwrap()
+            .get(&mint.to_string())
+            .cloned()
+    }
+
+    pub fn positions(&self) -> BTreeMap<String, Position> {
+        self.positions.lock().unwrap().clone()
+    }
+
+    /// Records the stablebonds a landed trade left in, or took out of, the wallet at `price`.
+    pub fn record_fill(&self, mint: &Pubkey, amount: Decimal, price: Decimal) {
+        if amount.abs() < DUST_AMOUNT {
+            return;
+        }
+        self.update(mint, |position| position.fill(amount, price));
+    }
+
+    /// Adopts the wallet's on-chain `holdings`, valuing any difference with the tracked size at
+    /// `price`, so that deposits, withdrawals and missed fills don't skew the position.
+    pub fn reconcile(&self, mint: &Pubkey, holdings: Decimal, price: Decimal) {
+        let size = self
+            .position(mint)
+            .map_or(Decimal::ZERO, |position| position.size);
+        let difference = holdings - size;
+        if difference.abs() < DUST_AMOUNT {
+            return;
+        }
+        info!(
+            tracked = %size,
+            %holdings,
+            "Reconciling position with wallet holdings"
+        );
+        self.update(mint, |position| position.fill(difference, price));
+    }
+
+    /// Marks the position against the latest NAV and Jupiter bid.
+    pub fn mark(&self, mint: &Pubkey, nav: Decimal, jupiter_bid: Option<Decimal>) {
+        let mut positions = self.positions.lock().unwrap();
+        let Some(position) = positions.get_mut(&mint.to_string()) else {
+            return;
+        };
+        position.nav = Some(nav);
+        position.jupiter_bid = jupiter_bid;
+        info!(
+            size = %position.size,
+            average_entry_price = %position.average_entry_price,
+            exposure_usd = %position.exposure_usd(),
+            unrealized_pnl_nav_usd = ?position.unrealized_pnl_nav_usd(),
+            unrealized_pnl_bid_usd = ?position.unrealized_pnl_bid_usd(),
+            "Position"
+        );
+        if let Err(e) = save_positions(&self.path, &positions) {
+            error!("Error saving positions: {:?}", e);
+        }
+    }
+
+    fn update(&self, mint: &Pubkey, update: impl FnOnce(&mut Position)) {
+        let mut positions = self.positions.lock().unwrap();
+        update(positions.entry(mint.to_string()).or_default());
+        if let Err(e) = save_positions(&self.path, &positions) {
+            error!("Error saving positions: {:?}", e);
+        }
+    }
+}
+
+fn load_positions(path: &str) -> Result<BTreeMap<String, Position>> {
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn save_positions(path: &str, positions: &BTreeMap<String, Position>) -> Result<()> {
+    fs::write(path, serde_json::to_string(positions)?)?;
+    Ok(())
+}
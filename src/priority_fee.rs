@@ -0,0 +1,58 @@
+use anyhow::Result;
+use serde::Deserialize;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+use crate::constants::{DEFAULT_PRIORITY_FEE_PERCENTILE, MAX_PRIORITY_FEE_MICRO_LAMPORTS};
+
+/// `[priority_fee]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriorityFeeConfig {
+    /// Percentile of the recent prioritization fees to pay, between 0 and 100.
+    pub percentile: f64,
+    /// Upper bound on the compute unit price in micro-lamports.
+    pub max_micro_lamports: u64,
+}
+
+impl Default for PriorityFeeConfig {
+    fn default() -> Self {
+        Self {
+            percentile: DEFAULT_PRIORITY_FEE_PERCENTILE,
+            max_micro_lamports: MAX_PRIORITY_FEE_MICRO_LAMPORTS,
+        }
+    }
+}
+
+/// Estimates the compute unit price needed to land a transaction from the fees recently paid
+/// to write-lock the same accounts.
+#[derive(Clone)]
+pub struct PriorityFeeEstimator {
+    pub rpc_client: Arc<RpcClient>,
+    pub config: PriorityFeeConfig,
+}
+
+impl PriorityFeeEstimator {
+    pub fn new(rpc_client: Arc<RpcClient>, config: PriorityFeeConfig) -> Self {
+        Self { rpc_client, config }
+    }
+
+    /// Compute unit price in micro-lamports for a transaction write-locking `writable_accounts`.
+    pub async fn estimate(&self, writable_accounts: &[Pubkey]) -> Result<u64> {
+        let mut fees: Vec<u64> = self
+            .rpc_client
+            .get_recent_prioritization_fees(writable_accounts)
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect();
+        if fees.is_empty() {
+            return Err(anyhow::anyhow!("No recent prioritization fees"));
+        }
+        fees.sort_unstable();
+        let percentile = self.config.percentile.clamp(0.0, 100.0) / 100.0;
+        let index = ((fees.len() - 1) as f64 * percentile).round() as usize;
+        Ok(fees[index].min(self.config.max_micro_lamports))
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::Notify;
+use tracing::info;
+
+use crate::constants::{
+    AGGRESSIVE_MAX_TIP_PROFIT_SHARE, AGGRESSIVE_MIN_PROFIT_USD, AGGRESSIVE_QUOTE_SLIPPAGE_BPS,
+    CONSERVATIVE_MAX_CONCURRENT_MINTS, CONSERVATIVE_MAX_TIP_PROFIT_SHARE,
+    CONSERVATIVE_MIN_PROFIT_USD, CONSERVATIVE_QUOTE_SLIPPAGE_BPS, DEFAULT_MIN_PROFIT_USD,
+    DEFAULT_PROFILE, DEFAULT_QUOTE_SLIPPAGE_BPS, MAX_TIP_PROFIT_SHARE,
+};
+
+/// Settings that together set how much risk the bot takes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Profile {
+    /// Expected profit below which no trade is made.
+    pub min_profit_usd: Decimal,
+    /// Slippage tolerated by the swap venues' quotes.
+    pub slippage_bps: u64,
+    /// Share of the expected profit a Jito tip may reach when escalating.
+    pub max_tip_profit_share: f64,
+    /// Mints ticking at once at most, every mint when `None`.
+    pub max_concurrent_mints: Option<usize>,
+}
+
+impl Profile {
+    pub fn conservative() -> Self {
+        Self {
+            min_profit_usd: CONSERVATIVE_MIN_PROFIT_USD,
+            slippage_bps: CONSERVATIVE_QUOTE_SLIPPAGE_BPS,
+            max_tip_profit_share: CONSERVATIVE_MAX_TIP_PROFIT_SHARE,
+            max_concurrent_mints: Some(CONSERVATIVE_MAX_CONCURRENT_MINTS),
+        }
+    }
+
+    pub fn normal() -> Self {
+        Self {
+            min_profit_usd: DEFAULT_MIN_PROFIT_USD,
+            slippage_bps: DEFAULT_QUOTE_SLIPPAGE_BPS,
+            max_tip_profit_share: MAX_TIP_PROFIT_SHARE,
+            max_concurrent_mints: None,
+        }
+    }
+
+    pub fn aggressive() -> Self {
+        Self {
+            min_profit_usd: AGGRESSIVE_MIN_PROFIT_USD,
+            slippage_bps: AGGRESSIVE_QUOTE_SLIPPAGE_BPS,
+            max_tip_profit_share: AGGRESSIVE_MAX_TIP_PROFIT_SHARE,
+            max_concurrent_mints: None,
+        }
+    }
+}
+
+/// Entry of the `[profiles]` section of the config file. Fields left out keep the value of the
+/// built-in profile of the same name, or of `normal` for new profiles.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub min_profit_usd: Option<Decimal>,
+    pub slippage_bps: Option<u64>,
+    pub max_tip_profit_share: Option<f64>,
+    pub max_concurrent_mints: Option<usize>,
+}
+
+impl ProfileConfig {
+    fn apply(self, profile: Profile) -> Profile {
+        Profile {
+            min_profit_usd: self.min_profit_usd.unwrap_or(profile.min_profit_usd),
+            slippage_bps: self.slippage_bps.unwrap_or(profile.slippage_bps),
+            max_tip_profit_share: self
+                .max_tip_profit_share
+                .unwrap_or(profile.max_tip_profit_share),
+            max_concurrent_mints: self.max_concurrent_mints.or(profile.max_concurrent_mints),
+        }
+    }
+}
+
+/// The built-in profiles with the overrides of the config file applied, and the config file's
+/// own profiles.
+pub fn profiles(configs: BTreeMap<String, ProfileConfig>) -> BTreeMap<String, Profile> {
+    let mut profiles = BTreeMap::from([
+        ("conservative".to_string(), Profile::conservative()),
+        ("normal".to_string(), Profile::normal()),
+        ("aggressive".to_string(), Profile::aggressive()),
+    ]);
+    for (name, config) in configs {
+        let base = profiles.get(&name).cloned().unwrap_or_else(Profile::normal);
+        profiles.insert(name, config.apply(base));
+    }
+    profiles
+}
+
+/// The profile the bot trades with, shared by every clone so that switching it at runtime
+/// takes effect on the next quote, tip and tick. Also limits how many mints tick at once.
+#[derive(Clone)]
+pub struct ActiveProfile {
+    profiles: Arc<BTreeMap<String, Profile>>,
+    active: Arc<RwLock<(String, Profile)>>,
+    ticking: Arc<Mutex<usize>>,
+    tick_done: Arc<Notify>,
+}
+
+/// A mint's turn to tick, given back when dropped.
+pub struct TickPermit {
+    ticking: Arc<Mutex<usize>>,
+    tick_done: Arc<Notify>,
+}
+
+impl Drop for TickPermit {
+    fn drop(&mut self) {
+        *self.ticking.lock().unwrap() -= 1;
+        self.tick_done.notify_waiters();
+    }
+}
+
+impl ActiveProfile {
+    pub fn new(profiles: BTreeMap<String, Profile>, name: &str) -> Result<Self> {
+        let profile = lookup(&profiles, name)?;
+        Ok(Self {
+            active: Arc::new(RwLock::new((name.to_string(), profile))),
+            profiles: Arc::new(profiles),
+            ticking: Arc::new(Mutex::new(0)),
+            tick_done: Arc::new(Notify::new()),
+        })
+    }
+
+    pub fn name(&self) -> String {
+        self.active.read().unwrap().0.clone()
+    }
+
+    pub fn get(&self) -> Profile {
+        self.active.read().unwrap().1.clone()
+    }
+
+    pub fn profiles(&self) -> &BTreeMap<String, Profile> {
+        &self.profiles
+    }
+
+    /// Switches to the profile `name`, returning it.
+    pub fn set(&self, name: &str) -> Result<Profile> {
+        let profile = lookup(&self.profiles, name)?;
+        info!(profile = name, "Switched profile: {:?}", profile);
+        *self.active.write().unwrap() = (name.to_string(), profile.clone());
+        // A higher limit lets waiting mints tick right away.
+        self.tick_done.notify_waiters();
+        Ok(profile)
+    }
+
+    /// Waits until fewer mints than the profile allows are ticking.
+    pub async fn acquire_tick(&self) -> TickPermit {
+        loop {
+            let tick_done = self.tick_done.notified();
+            tokio::pin!(tick_done);
+            // Registered before checking, so a permit given back meanwhile isn't missed.
+            tick_done.as_mut().enable();
+            {
+                let mut ticking = self.ticking.lock().unwrap();
+                if self
+                    .get()
+                    .max_concurrent_mints
+                    .map_or(true, |max| *ticking < max.max(1))
+                {
+                    *ticking += 1;
+                    return TickPermit {
+                        ticking: self.ticking.clone(),
+                        tick_done: self.tick_done.clone(),
+                    };
+                }
+            }
+            tick_done.await;
+        }
+    }
+}
+
+impl Default for ActiveProfile {
+    fn default() -> Self {
+        Self::new(profiles(BTreeMap::new()), DEFAULT_PROFILE).unwrap()
+    }
+}
+
+fn lookup(profiles: &BTreeMap<String, Profile>, name: &str) -> Result<Profile> {
+    profiles.get(name).cloned().ok_or_else(|| {
+        anyhow!(
+            "Unknown profile `{}`, expected one of {}",
+            name,
+            profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn config_overrides_the_built_in_profiles() {
+        let profiles = profiles(BTreeMap::from([
+            (
+                "conservative".to_string(),
+                ProfileConfig {
+                    slippage_bps: Some(30),
+                    ..Default::default()
+                },
+            ),
+            (
+                "night".to_string(),
+                ProfileConfig {
+                    max_concurrent_mints: Some(2),
+                    ..Default::default()
+                },
+            ),
+        ]));
+
+        assert_eq!(profiles["conservative"].slippage_bps, 30);
+        assert_eq!(
+            profiles["conservative"].min_profit_usd,
+            CONSERVATIVE_MIN_PROFIT_USD
+        );
+        assert_eq!(profiles["night"].max_concurrent_mints, Some(2));
+        assert_eq!(profiles["night"].slippage_bps, DEFAULT_QUOTE_SLIPPAGE_BPS);
+        assert!(ActiveProfile::new(profiles, "reckless").is_err());
+    }
+
+    #[tokio::test]
+    async fn limits_the_mints_ticking_at_once() {
+        let profile = ActiveProfile::default();
+        profile.set("conservative").unwrap();
+
+        let permit = profile.acquire_tick().await;
+        let second = tokio::time::timeout(Duration::from_millis(10), profile.acquire_tick());
+        assert!(second.await.is_err());
+
+        drop(permit);
+        let second = tokio::time::timeout(Duration::from_millis(10), profile.acquire_tick());
+        assert!(second.await.is_ok());
+    }
+}
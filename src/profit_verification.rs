@@ -0,0 +1,379 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_program::{
+    message::VersionedMessage, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    system_instruction::SystemInstruction, system_program,
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, UiTransactionEncoding, UiTransactionTokenBalance,
+};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Instant};
+use tracing::{info, warn};
+
+use crate::{
+    constants::{
+        LAMPORTS_PER_SIGNATURE, PROFIT_SHORTFALL_ALERT_SHARE, SWITCHBOARD_ON_DEMAND_PROGRAM_ID,
+        USDC_MINT,
+    },
+    jito::{BundleOutcome, BundleStatusEnum},
+    math,
+    position_tracker::PositionTracker,
+    slippage_stats::{realized_outputs, SlippageStats, TradeLeg, Venue},
+};
+
+/// What the strategy expected a bundle to earn, along with the prices needed to value the
+/// balance changes once it lands.
+#[derive(Clone, Debug)]
+pub struct ExpectedProfit {
+    pub profit_usd: Decimal,
+    pub stablebond_mint: Pubkey,
+    pub stablebond_price: f64,
+    pub sol_price: f64,
+    /// When the quote the trade was priced from was received.
+    pub quoted_at: Instant,
+    pub legs: Vec<TradeLeg>,
+}
+
+/// Balance changes of our wallet across the transactions of a landed bundle.
+#[derive(Clone, Debug, Default)]
+pub struct RealizedProfit {
+    pub usdc_delta: f64,
+    pub stablebond_delta: f64,
+    /// Includes transaction fees and the Jito tip.
+    pub lamports_delta: i64,
+    pub profit_usd: Decimal,
+    /// Token balance changes of our wallet per mint, in UI amounts.
+    pub token_deltas: HashMap<Pubkey, f64>,
+    pub attribution: PnlAttribution,
+}
+
+/// Realized profit of a trade broken down into the spread it captured and what each cost line
+/// took from it, in USD. `gross_spread_usd` minus every other field is the realized profit.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PnlAttribution {
+    /// Profit at the prices quoted before price impact, slippage and the Etherfuse fee.
+    pub gross_spread_usd: Decimal,
+    /// Price impact Jupiter quoted for the size traded.
+    pub jupiter_price_impact_usd: Decimal,
+    /// Shortfall of the Jupiter fills against their quotes.
+    pub jupiter_slippage_usd: Decimal,
+    /// Shortfall of the Etherfuse fills against the NAV they were priced at.
+    pub etherfuse_fee_usd: Decimal,
+    pub jito_tip_usd: Decimal,
+    pub priority_fee_usd: Decimal,
+    pub base_fee_usd: Decimal,
+    /// Fees of the transactions that only updated the Switchboard oracle.
+    pub oracle_update_usd: Decimal,
+    /// SOL spent on anything else, such as rent for new token accounts.
+    pub other_usd: Decimal,
+}
+
+/// SOL our wallet spent across a trade's transactions, per cost line.
+#[derive(Clone, Debug, Default)]
+struct LamportCosts {
+    jito_tip: u64,
+    priority_fee: u64,
+    base_fee: u64,
+    oracle_update: i64,
+}
+
+/// Compares what landed bundles actually earned against what the strategy expected, to catch
+/// slippage, fee or tip estimates drifting away from reality.
+#[derive(Clone)]
+pub struct ProfitVerifier {
+    pub rpc_client: Arc<RpcClient>,
+    pub wallet: Pubkey,
+    pub slippage_stats: Option<SlippageStats>,
+    pub position_tracker: Option<PositionTracker>,
+}
+
+impl ProfitVerifier {
+    pub fn new(rpc_client: Arc<RpcClient>, wallet: Pubkey) -> Self {
+        Self {
+            rpc_client,
+            wallet,
+            slippage_stats: None,
+            position_tracker: None,
+        }
+    }
+
+    /// Records the realized slippage of each leg of the verified trades.
+    pub fn with_slippage_stats(mut self, slippage_stats: SlippageStats) -> Self {
+        self.slippage_stats = Some(slippage_stats);
+        self
+    }
+
+    /// Updates the stablebond position with what each verified trade left in the wallet.
+    pub fn with_position_tracker(mut self, position_tracker: PositionTracker) -> Self {
+        self.position_tracker = Some(position_tracker);
+        self
+    }
+
+    /// Fetches the transactions of a landed bundle and logs the realized profit. Bundles that
+    /// didn't land have nothing to verify.
+    pub async fn verify(&self, outcome: &BundleOutcome) -> Result<Option<RealizedProfit>> {
+        if outcome.status != BundleStatusEnum::Landed || outcome.signatures.is_empty() {
+            return Ok(None);
+        }
+        let Some(expected) = &outcome.expected_profit else {
+            return Ok(None);
+        };
+
+        self.verify_signatures(&outcome.bundle_id, &outcome.signatures, expected)
+            .await
+            .map(Some)
+    }
+
+    /// Logs the realized profit of the landed transactions `signatures` of the trade `label`,
+    /// warning when it falls short of `PROFIT_SHORTFALL_ALERT_SHARE` of the expected profit.
+    pub async fn verify_signatures(
+        &self,
+        label: &str,
+        signatures: &[Signature],
+        expected: &ExpectedProfit,
+    ) -> Result<RealizedProfit> {
+        let realized = self.realized_profit(signatures, expected).await?;
+        info!(
+            trade = label,
+            realized_profit = %realized.profit_usd,
+            expected_profit = %expected.profit_usd,
+            usdc_delta = realized.usdc_delta,
+            stablebond_delta = realized.stablebond_delta,
+            lamports_delta = realized.lamports_delta,
+            gross_spread_usd = %realized.attribution.gross_spread_usd,
+            jupiter_price_impact_usd = %realized.attribution.jupiter_price_impact_usd,
+            jupiter_slippage_usd = %realized.attribution.jupiter_slippage_usd,
+            etherfuse_fee_usd = %realized.attribution.etherfuse_fee_usd,
+            jito_tip_usd = %realized.attribution.jito_tip_usd,
+            priority_fee_usd = %realized.attribution.priority_fee_usd,
+            base_fee_usd = %realized.attribution.base_fee_usd,
+            oracle_update_usd = %realized.attribution.oracle_update_usd,
+            other_usd = %realized.attribution.other_usd,
+            "Trade profit realized"
+        );
+        if let Some(slippage_stats) = &self.slippage_stats {
+            slippage_stats.record(&expected.legs, &realized.token_deltas);
+        }
+        if let Some(position_tracker) = &self.position_tracker {
+            position_tracker.record_fill(
+                &expected.stablebond_mint,
+                math::to_decimal(realized.stablebond_delta)?,
+                math::to_decimal(expected.stablebond_price)?,
+            );
+        }
+        if realized.profit_usd < expected.profit_usd * PROFIT_SHORTFALL_ALERT_SHARE {
+            warn!(
+                "Trade {} realized ${:.2}, short of the expected ${:.2} by ${:.2}",
+                label,
+                realized.profit_usd,
+                expected.profit_usd,
+                expected.profit_usd - realized.profit_usd
+            );
+        }
+        Ok(realized)
+    }
+
+    async fn realized_profit(
+        &self,
+        signatures: &[Signature],
+        expected: &ExpectedProfit,
+    ) -> Result<RealizedProfit> {
+        let usdc_mint = Pubkey::from_str(USDC_MINT)?;
+        let mut token_deltas: HashMap<Pubkey, f64> = HashMap::new();
+        let mut lamports_delta: i64 = 0;
+        let mut costs = LamportCosts::default();
+        for signature in signatures {
+            let tx = self
+                .rpc_client
+                .get_transaction_with_config(
+                    signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::Base64),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+                .await?;
+            let meta = tx
+                .transaction
+                .meta
+                .ok_or_else(|| anyhow!("Transaction {} has no status meta", signature))?;
+            if let Some(err) = meta.err {
+                return Err(anyhow!("Transaction {} failed: {:?}", signature, err));
+            }
+
+            let mut tx_token_deltas: HashMap<Pubkey, f64> = HashMap::new();
+            for (mint, delta) in self.token_deltas(&meta.pre_token_balances, false) {
+                *tx_token_deltas.entry(mint).or_default() += delta;
+            }
+            for (mint, delta) in self.token_deltas(&meta.post_token_balances, true) {
+                *tx_token_deltas.entry(mint).or_default() += delta;
+            }
+            for (mint, delta) in &tx_token_deltas {
+                *token_deltas.entry(*mint).or_default() += delta;
+            }
+            // Our wallet pays the fees of every transaction we sign, so it is always the first
+            // account.
+            let Some(message) = tx.transaction.transaction.decode().map(|tx| tx.message) else {
+                continue;
+            };
+            if message.static_account_keys()[0] != self.wallet {
+                continue;
+            }
+            let tx_lamports_delta = meta.post_balances[0] as i64 - meta.pre_balances[0] as i64;
+            lamports_delta += tx_lamports_delta;
+            let tip = tips(&message);
+            costs.jito_tip += tip;
+            let updates_oracle_only = message
+                .static_account_keys()
+                .contains(&Pubkey::from_str(SWITCHBOARD_ON_DEMAND_PROGRAM_ID).unwrap())
+                && tx_token_deltas
+                    .values()
+                    .all(|delta| delta.abs() < f64::EPSILON);
+            if updates_oracle_only {
+                costs.oracle_update += -tx_lamports_delta - tip as i64;
+            } else {
+                let base_fee =
+                    message.header().num_required_signatures as u64 * LAMPORTS_PER_SIGNATURE;
+                costs.base_fee += base_fee;
+                costs.priority_fee += meta.fee.saturating_sub(base_fee);
+            }
+        }
+
+        let usdc_delta = token_deltas.get(&usdc_mint).copied().unwrap_or(0.0);
+        let stablebond_delta = token_deltas
+            .get(&expected.stablebond_mint)
+            .copied()
+            .unwrap_or(0.0);
+        // The balances and prices are floats as the RPC and the price APIs return them, the
+        // profit is summed in decimals like the strategies' expected profit.
+        let profit_usd = math::to_decimal(usdc_delta)?
+            + math::to_decimal(stablebond_delta)? * math::to_decimal(expected.stablebond_price)?
+            + Decimal::from(lamports_delta) / Decimal::from(LAMPORTS_PER_SOL)
+                * math::to_decimal(expected.sol_price)?;
+        let attribution = attribute(
+            expected,
+            &token_deltas,
+            profit_usd,
+            lamports_delta,
+            &costs,
+            usdc_mint,
+        )?;
+        Ok(RealizedProfit {
+            usdc_delta,
+            stablebond_delta,
+            lamports_delta,
+            profit_usd,
+            token_deltas,
+            attribution,
+        })
+    }
+
+    /// Token amounts held by our wallet per mint, negated for the balances before the
+    /// transaction so that summing both sides gives the delta.
+    fn token_deltas(
+        &self,
+        balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+        post: bool,
+    ) -> Vec<(Pubkey, f64)> {
+        let OptionSerializer::Some(balances) = balances else {
+            return Vec::new();
+        };
+        let wallet = self.wallet.to_string();
+        balances
+            .iter()
+            .filter(|balance| balance.owner.as_ref() == OptionSerializer::Some(&wallet))
+            .filter_map(|balance| {
+                let mint = Pubkey::from_str(&balance.mint).ok()?;
+                let amount = balance.ui_token_amount.ui_amount.unwrap_or(0.0);
+                Some((mint, if post { amount } else { -amount }))
+            })
+            .collect()
+    }
+}
+
+/// SOL transferred out of our wallet, which only the Jito tip does.
+fn tips(message: &VersionedMessage) -> u64 {
+    let account_keys = message.static_account_keys();
+    message
+        .instructions()
+        .iter()
+        .filter(|ix| account_keys.get(ix.program_id_index as usize) == Some(&system_program::id()))
+        .filter(|ix| ix.accounts.first() == Some(&0) && ix.accounts.get(1) != Some(&0))
+        .filter_map(|ix| match bincode::deserialize(&ix.data) {
+            Ok(SystemInstruction::Transfer { lamports }) => Some(lamports),
+            _ => None,
+        })
+        .sum()
+}
+
+/// Values each leg's shortfall against its quote with the prices the trade was priced at,
+/// and the SOL spent with the SOL price. Whatever SOL isn't a tip or fee is `other_usd`.
+fn attribute(
+    expected: &ExpectedProfit,
+    token_deltas: &HashMap<Pubkey, f64>,
+    profit_usd: Decimal,
+    lamports_delta: i64,
+    costs: &LamportCosts,
+    usdc_mint: Pubkey,
+) -> Result<PnlAttribution> {
+    let sol_price = math::to_decimal(expected.sol_price)?;
+    let stablebond_price = math::to_decimal(expected.stablebond_price)?;
+    let sol_usd = |lamports: Decimal| lamports / Decimal::from(LAMPORTS_PER_SOL) * sol_price;
+    let price_usd = |mint: &Pubkey| {
+        if *mint == usdc_mint {
+            Decimal::ONE
+        } else if *mint == expected.stablebond_mint {
+            stablebond_price
+        } else {
+            Decimal::ZERO
+        }
+    };
+    let mut attribution = PnlAttribution {
+        jito_tip_usd: sol_usd(Decimal::from(costs.jito_tip)),
+        priority_fee_usd: sol_usd(Decimal::from(costs.priority_fee)),
+        base_fee_usd: sol_usd(Decimal::from(costs.base_fee)),
+        oracle_update_usd: sol_usd(Decimal::from(costs.oracle_update)),
+        ..Default::default()
+    };
+    attribution.other_usd = sol_usd(-Decimal::from(lamports_delta))
+        - attribution.jito_tip_usd
+        - attribution.priority_fee_usd
+        - attribution.base_fee_usd
+        - attribution.oracle_update_usd;
+
+    let realized_outputs = realized_outputs(&expected.legs, token_deltas);
+    for (leg, realized_output_amount) in expected.legs.iter().zip(realized_outputs) {
+        let price = price_usd(&leg.output_mint);
+        let expected_output_amount = math::to_decimal(leg.expected_output_amount)?;
+        let shortfall_usd =
+            (expected_output_amount - math::to_decimal(realized_output_amount)?) * price;
+        match leg.venue {
+            // Swaps on the secondary market, whichever venue routed them.
+            Venue::Jupiter | Venue::Raydium => {
+                let price_impact = math::to_decimal(leg.price_impact)?;
+                attribution.jupiter_price_impact_usd += (expected_output_amount
+                    * price
+                    * price_impact)
+                    .checked_div(Decimal::ONE - price_impact)
+                    .ok_or_else(|| anyhow!("Price impact of {} is not below 1", price_impact))?;
+                attribution.jupiter_slippage_usd += shortfall_usd;
+            }
+            Venue::Etherfuse => attribution.etherfuse_fee_usd += shortfall_usd,
+        }
+    }
+    attribution.gross_spread_usd = profit_usd
+        + attribution.jupiter_price_impact_usd
+        + attribution.jupiter_slippage_usd
+        + attribution.etherfuse_fee_usd
+        + attribution.jito_tip_usd
+        + attribution.priority_fee_usd
+        + attribution.base_fee_usd
+        + attribution.oracle_update_usd
+        + attribution.other_usd;
+    Ok(attribution)
+}
@@ -0,0 +1,295 @@
+use enum_dispatch::enum_dispatch;
+use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
+use stablebond_sdk::accounts::PaymentFeed;
+use std::time::Instant;
+use tracing::info;
+
+#[cfg(feature = "jito")]
+use crate::jito::JitoClient;
+use crate::{
+    constants::ORACLE_STALENESS_MARGIN_SLOTS,
+    error::{ArbResult, QuoteError},
+    etherfuse::EtherfuseClient,
+    jito::{BundleHandle, JitoError, TipPercentile},
+    jupiter::{self, JupiterClient},
+    profit_verification::ExpectedProfit,
+    raydium::RaydiumClient,
+    slippage_stats::Venue,
+};
+#[cfg(feature = "switchboard")]
+use crate::{error::VenueError, switchboard::SwitchboardClient};
+
+// The traits below are what the strategies, the execution backends and the market data
+// pipeline need from the outside world. The concrete clients implement them against mainnet,
+// and `crate::mock` implements them from canned data for tests.
+//
+// Their futures are awaited on the mint workers' spawned tasks. The traits don't require them
+// to be `Send`; the compiler checks that the concrete clients' are where the workers spawn.
+
+/// Quote of a swap on a `SwapVenue`.
+#[derive(Clone, Debug)]
+pub struct SwapQuote {
+    pub venue: Venue,
+    pub input_mint: Pubkey,
+    pub in_amount: u64,
+    pub output_mint: Pubkey,
+    pub out_amount: u64,
+    pub price_impact_pct: f64,
+    /// AMMs the swap routes through, e.g. `Meteora DLMM+Orca V2`.
+    pub route: String,
+    /// Slot the quote was computed at, 0 when the venue doesn't say.
+    pub context_slot: u64,
+    /// When the quote was received, to measure how long it takes to act on it.
+    pub fetched_at: Instant,
+    /// The quote as the venue answered it, which its swap API takes back to build the swap.
+    pub response: serde_json::Value,
+}
+
+/// Prices and routes swaps between USDC and a stablebond on the secondary market.
+#[allow(async_fn_in_trait)]
+#[enum_dispatch]
+pub trait SwapVenue {
+    fn venue(&self) -> Venue;
+
+    /// Price in USD per token of buying the stablebond with `usdc_amount`, and the quote.
+    async fn buy_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        usdc_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)>;
+
+    /// Price in USD per token of selling `stablebond_amount`, and the quote.
+    async fn sell_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        stablebond_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)>;
+
+    /// Signed transaction executing `quote`.
+    async fn swap_tx(&mut self, quote: SwapQuote) -> ArbResult<VersionedTransaction>;
+}
+
+/// Issues and redeems stablebonds at NAV.
+#[allow(async_fn_in_trait)]
+pub trait BondVenue {
+    /// Price in USD per token the venue buys and sells the stablebond at.
+    async fn nav(&self, stablebond_mint: &Pubkey) -> ArbResult<f64>;
+
+    /// Price of the bond's payment currency, in the units of its payment feed.
+    async fn exchange_rate(&self, stablebond_mint: &Pubkey) -> ArbResult<f64>;
+
+    async fn payment_feed(&self, stablebond_mint: &Pubkey) -> ArbResult<PaymentFeed>;
+
+    /// USDC available to redeem the stablebond against.
+    async fn sell_liquidity_usdc_amount(&self, stablebond_mint: &Pubkey) -> ArbResult<u64>;
+
+    /// Stablebond available to purchase.
+    async fn purchase_liquidity_stablebond_amount(
+        &self,
+        stablebond_mint: &Pubkey,
+    ) -> ArbResult<u64>;
+
+    /// Signed transaction buying the stablebond with `usdc_amount`.
+    async fn purchase_tx(
+        &self,
+        usdc_amount: u64,
+        stablebond_mint: Pubkey,
+    ) -> ArbResult<VersionedTransaction>;
+
+    /// Signed transaction redeeming `stablebond_amount` for USDC right away.
+    async fn redemption_tx(
+        &self,
+        stablebond_amount: u64,
+        stablebond_mint: Pubkey,
+    ) -> ArbResult<VersionedTransaction>;
+}
+
+/// Lands transactions atomically as a tipped bundle.
+#[allow(async_fn_in_trait)]
+pub trait BundleSubmitter {
+    /// Tip the bundles are sent with at `percentile` of the recently landed tips.
+    fn tip_lamports(&self, percentile: TipPercentile) -> u64;
+
+    /// Submits the bundle, tipping no more than `max_tip_lamports`, and returns once it is
+    /// accepted.
+    async fn send_bundle(
+        &mut self,
+        txs: &[VersionedTransaction],
+        max_tip_lamports: u64,
+        expected_profit: ExpectedProfit,
+    ) -> Result<BundleHandle, JitoError>;
+}
+
+/// Price of SOL in USD, which tips and fees are paid in.
+#[allow(async_fn_in_trait)]
+pub trait SolPriceProvider {
+    /// Name of the source, for logs.
+    fn name(&self) -> &'static str;
+
+    /// Current price of SOL in USD. Errors rather than guessing when the source has none.
+    async fn sol_price(&self) -> ArbResult<f64>;
+}
+
+/// Value of a price feed and how old it is.
+#[derive(Clone, Debug)]
+pub struct FeedValue {
+    pub price: f64,
+    pub age_slots: u64,
+    pub max_staleness_slots: u64,
+}
+
+/// On-chain price feeds the bond program prices purchases and redemptions with.
+#[allow(async_fn_in_trait)]
+pub trait OraclePriceSource {
+    /// Current value of the feed and the number of slots since it was last updated.
+    async fn feed_value(&self, public_feed: Pubkey) -> ArbResult<FeedValue>;
+
+    /// Transactions updating all of `public_feeds`.
+    async fn update_txs(&self, public_feeds: &[Pubkey]) -> ArbResult<Vec<VersionedTransaction>>;
+
+    /// Whether the feed is old enough that the bond program could reject it as stale by the
+    /// time the bundle lands. Uses the feed's own `max_staleness` minus a safety margin as
+    /// tolerance.
+    async fn is_feed_stale(&self, public_feed: Pubkey) -> ArbResult<bool> {
+        let feed_value = self.feed_value(public_feed).await?;
+        let tolerance_slots = feed_value
+            .max_staleness_slots
+            .saturating_sub(ORACLE_STALENESS_MARGIN_SLOTS);
+        info!(
+            "Switchboard feed {} last updated {} slots ago (tolerance {} slots)",
+            public_feed, feed_value.age_slots, tolerance_slots
+        );
+        Ok(feed_value.age_slots >= tolerance_slots)
+    }
+}
+
+impl SwapVenue for JupiterClient {
+    fn venue(&self) -> Venue {
+        Venue::Jupiter
+    }
+
+    async fn buy_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        usdc_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        let (price, quote) = JupiterClient::buy_quote(self, stablebond_mint, usdc_amount).await?;
+        Ok((price, quote.try_into()?))
+    }
+
+    async fn sell_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        stablebond_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        let (price, quote) =
+            JupiterClient::sell_quote(self, stablebond_mint, stablebond_amount).await?;
+        Ok((price, quote.try_into()?))
+    }
+
+    async fn swap_tx(&mut self, quote: SwapQuote) -> ArbResult<VersionedTransaction> {
+        let quote: jupiter::Quote = serde_json::from_value(quote.response)
+            .map_err(|e| QuoteError::InvalidResponse(e.to_string()))?;
+        self.jupiter_swap_tx(quote).await
+    }
+}
+
+impl SwapVenue for RaydiumClient {
+    fn venue(&self) -> Venue {
+        Venue::Raydium
+    }
+
+    async fn buy_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        usdc_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        Ok(RaydiumClient::buy_quote(self, stablebond_mint, usdc_amount).await?)
+    }
+
+    async fn sell_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        stablebond_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        Ok(RaydiumClient::sell_quote(self, stablebond_mint, stablebond_amount).await?)
+    }
+
+    async fn swap_tx(&mut self, quote: SwapQuote) -> ArbResult<VersionedTransaction> {
+        self.raydium_swap_tx(quote).await
+    }
+}
+
+impl BondVenue for EtherfuseClient {
+    async fn nav(&self, stablebond_mint: &Pubkey) -> ArbResult<f64> {
+        self.get_etherfuse_price(stablebond_mint).await
+    }
+
+    async fn exchange_rate(&self, stablebond_mint: &Pubkey) -> ArbResult<f64> {
+        self.get_etherfuse_exchange_rate(*stablebond_mint).await
+    }
+
+    async fn payment_feed(&self, stablebond_mint: &Pubkey) -> ArbResult<PaymentFeed> {
+        self.fetch_payment_feed(stablebond_mint).await
+    }
+
+    async fn sell_liquidity_usdc_amount(&self, stablebond_mint: &Pubkey) -> ArbResult<u64> {
+        self.fetch_sell_liquidity_usdc_amount(stablebond_mint).await
+    }
+
+    async fn purchase_liquidity_stablebond_amount(
+        &self,
+        stablebond_mint: &Pubkey,
+    ) -> ArbResult<u64> {
+        self.fetch_purchase_liquidity_stablebond_amount(stablebond_mint)
+            .await
+    }
+
+    async fn purchase_tx(
+        &self,
+        usdc_amount: u64,
+        stablebond_mint: Pubkey,
+    ) -> ArbResult<VersionedTransaction> {
+        EtherfuseClient::purchase_tx(self, usdc_amount, stablebond_mint).await
+    }
+
+    async fn redemption_tx(
+        &self,
+        stablebond_amount: u64,
+        stablebond_mint: Pubkey,
+    ) -> ArbResult<VersionedTransaction> {
+        self.instant_bond_redemption_tx(stablebond_amount, stablebond_mint)
+            .await
+    }
+}
+
+#[cfg(feature = "jito")]
+impl BundleSubmitter for JitoClient {
+    fn tip_lamports(&self, percentile: TipPercentile) -> u64 {
+        JitoClient::tip_lamports(self, percentile)
+    }
+
+    async fn send_bundle(
+        &mut self,
+        txs: &[VersionedTransaction],
+        max_tip_lamports: u64,
+        expected_profit: ExpectedProfit,
+    ) -> Result<BundleHandle, JitoError> {
+        JitoClient::send_bundle(self, txs, max_tip_lamports, expected_profit).await
+    }
+}
+
+#[cfg(feature = "switchboard")]
+impl OraclePriceSource for SwitchboardClient {
+    async fn feed_value(&self, public_feed: Pubkey) -> ArbResult<FeedValue> {
+        self.fetch_feed_value(public_feed)
+            .await
+            .map_err(|e| VenueError::Oracle(e.to_string()).into())
+    }
+
+    async fn update_txs(&self, public_feeds: &[Pubkey]) -> ArbResult<Vec<VersionedTransaction>> {
+        self.get_update_switchboard_oracle_txs(public_feeds)
+            .await
+            .map_err(|e| VenueError::Oracle(e.to_string()).into())
+    }
+}
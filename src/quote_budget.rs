@@ -0,0 +1,123 @@
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use crate::constants::{QUOTE_BUDGET_MIN_WEIGHT, QUOTE_BUDGET_OPPORTUNITY_DECAY};
+
+/// Jupiter quotes each mint may request per tick, out of a budget shared by every mint and
+/// split by how often each mint had an opportunity on its recent ticks. A mint whose quotes keep
+/// failing runs out of its share instead of the others', and every mint keeps a small share so
+/// that a quiet one notices when its spread opens.
+#[derive(Clone)]
+pub struct QuoteBudget {
+    pub calls_per_tick: u32,
+    mints: Arc<Mutex<HashMap<Pubkey, MintQuotes>>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct MintQuotes {
+    /// Decaying average of the ticks with an opportunity, from 0 to 1.
+    opportunity_rate: f64,
+    spent: u32,
+    refused: bool,
+}
+
+impl Default for MintQuotes {
+    fn default() -> Self {
+        // New mints start with a full share, until their ticks show otherwise.
+        Self {
+            opportunity_rate: 1.0,
+            spent: 0,
+            refused: false,
+        }
+    }
+}
+
+impl QuoteBudget {
+    pub fn new(calls_per_tick: u32, mints: &[Pubkey]) -> Self {
+        Self {
+            calls_per_tick,
+            mints: Arc::new(Mutex::new(
+                mints
+                    .iter()
+                    .map(|mint| (*mint, MintQuotes::default()))
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Quotes `mint` may request per tick, at least one.
+    pub fn allocation(&self, mint: &Pubkey) -> u32 {
+        allocation(self.calls_per_tick, &self.mints.lock().unwrap(), mint)
+    }
+
+    /// Counts a quote of `mint`, unless its share of this tick is spent.
+    pub fn try_spend(&self, mint: &Pubkey) -> bool {
+        let mut mints = self.mints.lock().unwrap();
+        let allocation = allocation(self.calls_per_tick, &mints, mint);
+        let quotes = mints.entry(*mint).or_default();
+        if quotes.spent < allocation {
+            quotes.spent += 1;
+            return true;
+        }
+        if !quotes.refused {
+            quotes.refused = true;
+            info!(%mint, allocation, "Quote budget of this tick spent");
+        }
+        false
+    }
+
+    /// Called at the end of each of `mint`'s ticks, to start its next with a fresh share.
+    pub fn record_tick(&self, mint: &Pubkey, found_opportunity: bool) {
+        let mut mints = self.mints.lock().unwrap();
+        let quotes = mints.entry(*mint).or_default();
+        quotes.opportunity_rate = quotes.opportunity_rate * QUOTE_BUDGET_OPPORTUNITY_DECAY
+            + (1.0 - QUOTE_BUDGET_OPPORTUNITY_DECAY) * found_opportunity as u8 as f64;
+        quotes.spent = 0;
+        quotes.refused = false;
+    }
+}
+
+fn allocation(calls_per_tick: u32, mints: &HashMap<Pubkey, MintQuotes>, mint: &Pubkey) -> u32 {
+    let weight = |quotes: &MintQuotes| QUOTE_BUDGET_MIN_WEIGHT + quotes.opportunity_rate;
+    let total_weight: f64 = mints.values().map(weight).sum();
+    let mint_weight = weight(&mints.get(mint).copied().unwrap_or_default());
+    if total_weight == 0.0 {
+        return calls_per_tick.max(1);
+    }
+    ((calls_per_tick as f64 * mint_weight / total_weight) as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_the_budget_to_the_mints_with_opportunities() {
+        let (busy, quiet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let budget = QuoteBudget::new(40, &[busy, quiet]);
+        assert_eq!(budget.allocation(&busy), 20);
+
+        for _ in 0..10 {
+            budget.record_tick(&busy, true);
+            budget.record_tick(&quiet, false);
+        }
+        assert!(budget.allocation(&busy) > 30);
+        assert!(budget.allocation(&quiet) >= 1);
+    }
+
+    #[test]
+    fn refuses_quotes_past_the_share_until_the_next_tick() {
+        let (mint, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let budget = QuoteBudget::new(4, &[mint, other]);
+
+        assert!(budget.try_spend(&mint));
+        assert!(budget.try_spend(&mint));
+        assert!(!budget.try_spend(&mint));
+        assert!(budget.try_spend(&other));
+
+        budget.record_tick(&mint, false);
+        assert!(budget.try_spend(&mint));
+    }
+}
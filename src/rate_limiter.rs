@@ -1,42 +1,395 @@
-use std::collections::VecDeque;
-use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+};
+use solana_rpc_client::rpc_sender::{RpcSender, RpcTransportStats};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use tracing::warn;
 
+use crate::constants::{
+    COINGECKO_RATE_LIMIT, ETHERFUSE_RATE_LIMIT, JITO_RATE_LIMIT, JUPITER_RATE_LIMIT,
+    MIN_RATE_LIMIT_SHARE, PYTH_RATE_LIMIT, RATE_LIMIT_BACKOFF_FACTOR, RATE_LIMIT_RECOVERY_FACTOR,
+    RATE_LIMIT_RECOVERY_SECS, RATE_LIMIT_WINDOW_SECS, RAYDIUM_RATE_LIMIT, RPC_RATE_LIMIT,
+};
+
+/// Classes of endpoints we call, each with its own request budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endpoint {
+    Rpc,
+    Jupiter,
+    Etherfuse,
+    Jito,
+    Coingecko,
+    Pyth,
+    Raydium,
+}
+
+impl Endpoint {
+    const ALL: [Endpoint; 7] = [
+        Endpoint::Rpc,
+        Endpoint::Jupiter,
+        Endpoint::Etherfuse,
+        Endpoint::Jito,
+        Endpoint::Coingecko,
+        Endpoint::Pyth,
+        Endpoint::Raydium,
+    ];
+
+    fn default_rate_limit(&self) -> RateLimitConfig {
+        let max_requests = match self {
+            Endpoint::Rpc => RPC_RATE_LIMIT,
+            Endpoint::Jupiter => JUPITER_RATE_LIMIT,
+            Endpoint::Etherfuse => ETHERFUSE_RATE_LIMIT,
+            Endpoint::Jito => JITO_RATE_LIMIT,
+            Endpoint::Coingecko => COINGECKO_RATE_LIMIT,
+            Endpoint::Pyth => PYTH_RATE_LIMIT,
+            Endpoint::Raydium => RAYDIUM_RATE_LIMIT,
+        };
+        RateLimitConfig {
+            window_secs: RATE_LIMIT_WINDOW_SECS,
+            max_requests,
+            burst: None,
+        }
+    }
+}
+
+/// Entry of the `[rate_limits]` section of the config file.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    #[serde(deserialize_with = "non_zero")]
+    pub window_secs: u64,
+    #[serde(deserialize_with = "non_zero")]
+    pub max_requests: usize,
+    /// Requests allowed back to back after a quiet period, `max_requests` when unset.
+    #[serde(default, deserialize_with = "non_zero_burst")]
+    pub burst: Option<usize>,
+}
+
+/// Rejects a zero limit, which would leave the limiter no requests to hand out and its
+/// waiters sleeping forever.
+fn non_zero<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default + PartialEq,
+{
+    let value = T::deserialize(deserializer)?;
+    if value == T::default() {
+        return Err(D::Error::custom("must be greater than 0"));
+    }
+    Ok(value)
+}
+
+fn non_zero_burst<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    non_zero(deserializer).map(Some)
+}
+
+/// One rate limiter per endpoint class, so that a busy endpoint doesn't eat into the budget of
+/// the others. Endpoints missing from the config keep their default limit.
+#[derive(Clone)]
+pub struct RateLimiterRegistry {
+    limiters: HashMap<Endpoint, RateLimiter>,
+}
+
+impl RateLimiterRegistry {
+    pub fn new(configs: &HashMap<Endpoint, RateLimitConfig>) -> Self {
+        let limiters = Endpoint::ALL
+            .iter()
+            .map(|endpoint| {
+                let config = configs
+                    .get(endpoint)
+                    .copied()
+                    .unwrap_or_else(|| endpoint.default_rate_limit());
+                let rate_limiter = RateLimiter::new(config.window_secs, config.max_requests);
+                let rate_limiter = match config.burst {
+                    Some(burst) => rate_limiter.with_burst(burst),
+                    None => rate_limiter,
+                };
+                (*endpoint, rate_limiter)
+            })
+            .collect();
+        Self { limiters }
+    }
+
+    /// Limiter sharing the budget of every other holder of the same endpoint's limiter.
+    pub fn limiter(&self, endpoint: Endpoint) -> RateLimiter {
+        self.limiters[&endpoint].clone()
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+struct AdaptiveRate {
+    tokens_per_sec: f64,
+    configured_tokens_per_sec: f64,
+    adjusted_at: Instant,
+}
+
+/// Token-bucket rate limiter refilling `max_requests` tokens per window, holding up to `burst`
+/// tokens. Waiters are served in FIFO order, each one sleeping only for its own token, so they
+/// don't all wake up at once when the bucket refills. Clones share the same bucket, so a
+/// limiter cloned into several clients still enforces a single budget across all of them.
+///
+/// The refill rate adapts to the endpoint's actual limit: it's cut whenever the endpoint
+/// answers with a rate-limit error and recovers towards the configured rate after every
+/// `RATE_LIMIT_RECOVERY_SECS` without one.
 #[derive(Clone)]
 pub struct RateLimiter {
-    requests: VecDeque<Instant>,
-    window: Duration,
-    max_requests: usize,
+    bucket: Arc<Mutex<TokenBucket>>,
+    rate: Arc<std::sync::Mutex<AdaptiveRate>>,
+    burst: f64,
 }
 
 impl RateLimiter {
     pub fn new(window_secs: u64, max_requests: usize) -> Self {
+        let tokens_per_sec = max_requests as f64 / window_secs.max(1) as f64;
+        Self::with_bucket(tokens_per_sec, max_requests.max(1) as f64)
+    }
+
+    /// Allows up to `burst` requests back to back after a quiet period. Defaults to
+    /// `max_requests`.
+    pub fn with_burst(self, burst: usize) -> Self {
+        let tokens_per_sec = self.rate.lock().unwrap().configured_tokens_per_sec;
+        Self::with_bucket(tokens_per_sec, burst.max(1) as f64)
+    }
+
+    fn with_bucket(tokens_per_sec: f64, burst: f64) -> Self {
         Self {
-            requests: VecDeque::new(),
-            window: Duration::from_secs(window_secs),
-            max_requests,
+            bucket: Arc::new(Mutex::new(TokenBucket {
+                tokens: burst,
+                refilled_at: Instant::now(),
+            })),
+            rate: Arc::new(std::sync::Mutex::new(AdaptiveRate {
+                tokens_per_sec,
+                configured_tokens_per_sec: tokens_per_sec,
+                adjusted_at: Instant::now(),
+            })),
+            burst,
         }
     }
 
-    pub async fn wait_if_needed(&mut self) {
+    pub async fn wait_if_needed(&self) {
+        // The lock queues waiters in FIFO order, and holding it while sleeping keeps the
+        // next waiter from computing its wait before our token is taken.
+        let mut bucket = self.bucket.lock().await;
+        let tokens_per_sec = self.tokens_per_sec();
+        self.refill(&mut bucket, tokens_per_sec);
+        if bucket.tokens < 1.0 {
+            let wait_secs = (1.0 - bucket.tokens) / tokens_per_sec;
+            tokio::time::sleep(Duration::from_secs_f64(wait_secs)).await;
+            self.refill(&mut bucket, tokens_per_sec);
+        }
+        bucket.tokens = (bucket.tokens - 1.0).max(0.0);
+    }
+
+    /// Tightens the budget after the endpoint answered with a rate-limit error.
+    pub fn record_rate_limited(&self) {
+        let mut rate = self.rate.lock().unwrap();
+        rate.tokens_per_sec = (rate.tokens_per_sec * RATE_LIMIT_BACKOFF_FACTOR)
+            .max(rate.configured_tokens_per_sec * MIN_RATE_LIMIT_SHARE);
+        rate.adjusted_at = Instant::now();
+        warn!(
+            "Rate limited, lowering the request rate to {:.2}/s",
+            rate.tokens_per_sec
+        );
+    }
+
+    /// Current refill rate, relaxed towards the configured rate after a clean period.
+    pub fn tokens_per_sec(&self) -> f64 {
+        let mut rate = self.rate.lock().unwrap();
+        let recovery_period = Duration::from_secs(RATE_LIMIT_RECOVERY_SECS);
+        if rate.tokens_per_sec < rate.configured_tokens_per_sec
+            && rate.adjusted_at.elapsed() >= recovery_period
+        {
+            rate.tokens_per_sec = (rate.tokens_per_sec * RATE_LIMIT_RECOVERY_FACTOR)
+                .min(rate.configured_tokens_per_sec);
+            rate.adjusted_at = Instant::now();
+        }
+        rate.tokens_per_sec
+    }
+
+    fn refill(&self, bucket: &mut TokenBucket, tokens_per_sec: f64) {
         let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * tokens_per_sec).min(self.burst);
+        bucket.refilled_at = now;
+    }
+}
 
-        // Remove old requests outside the window
-        while let Some(request_time) = self.requests.front() {
-            if now.duration_since(*request_time) > self.window {
-                self.requests.pop_front();
-            } else {
-                break;
-            }
+/// RPC transport waiting on a rate limiter before every request, and tightening it when the
+/// node answers with a 429.
+pub struct RateLimitedSender<S> {
+    sender: S,
+    rate_limiter: RateLimiter,
+}
+
+impl<S> RateLimitedSender<S> {
+    pub fn new(sender: S, rate_limiter: RateLimiter) -> Self {
+        Self {
+            sender,
+            rate_limiter,
         }
+    }
+}
 
-        // If at capacity, wait until we can make another request
-        if self.requests.len() >= self.max_requests {
-            if let Some(oldest) = self.requests.front() {
-                let wait_time = self.window - now.duration_since(*oldest);
-                tokio::time::sleep(wait_time).await;
+#[async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for RateLimitedSender<S> {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        self.rate_limiter.wait_if_needed().await;
+        let response = self.sender.send(request, params).await;
+        if let Err(e) = &response {
+            if let ClientErrorKind::Reqwest(e) = e.kind() {
+                if e.status() == Some(StatusCode::TOO_MANY_REQUESTS) {
+                    self.rate_limiter.record_rate_limited();
+                }
             }
         }
+        response
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.sender.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.sender.url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn clones_share_one_budget() {
+        let limiter = RateLimiter::new(1, 2);
+        let clone = limiter.clone();
+        let start = Instant::now();
+
+        limiter.wait_if_needed().await;
+        clone.wait_if_needed().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        clone.wait_if_needed().await;
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_clones_respect_the_rate() {
+        let limiter = RateLimiter::new(1, 2);
+        let start = Instant::now();
+
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    limiter.wait_if_needed().await;
+                    start.elapsed()
+                })
+            })
+            .collect();
+        let mut elapsed = Vec::new();
+        for handle in handles {
+            elapsed.push(handle.await.unwrap());
+        }
+        elapsed.sort();
+
+        // A burst of two, then one request every half second: 0s, 0s, 0.5s, 1s, 1.5s, 2s.
+        for (i, elapsed) in elapsed.iter().enumerate().skip(2) {
+            assert!(*elapsed >= Duration::from_millis(500 * (i as u64 - 1)));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_is_spent_before_waiting() {
+        let limiter = RateLimiter::new(1, 1).with_burst(3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.wait_if_needed().await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        limiter.wait_if_needed().await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiters_are_served_in_order() {
+        let limiter = RateLimiter::new(1, 1);
+        limiter.wait_if_needed().await;
+        let served = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let mut handles = Vec::new();
+        for i in 0..4 {
+            let limiter = limiter.clone();
+            let served = served.clone();
+            handles.push(tokio::spawn(async move {
+                limiter.wait_if_needed().await;
+                served.lock().unwrap().push(i);
+            }));
+            // Let the task queue up on the limiter before spawning the next one.
+            tokio::task::yield_now().await;
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
 
-        self.requests.push_back(now);
+        assert_eq!(*served.lock().unwrap(), vec![0, 1, 2, 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn endpoints_have_independent_budgets() {
+        let configs: HashMap<Endpoint, RateLimitConfig> = toml::from_str(
+            r#"
+            [jupiter]
+            window_secs = 1
+            max_requests = 1
+            "#,
+        )
+        .unwrap();
+        let registry = RateLimiterRegistry::new(&configs);
+        let start = Instant::now();
+
+        registry.limiter(Endpoint::Jupiter).wait_if_needed().await;
+        registry.limiter(Endpoint::Etherfuse).wait_if_needed().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        registry.limiter(Endpoint::Jupiter).wait_if_needed().await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_errors_tighten_then_relax_the_budget() {
+        let limiter = RateLimiter::new(1, 10);
+        assert_eq!(limiter.tokens_per_sec(), 10.0);
+
+        limiter.record_rate_limited();
+        assert_eq!(limiter.tokens_per_sec(), 5.0);
+        limiter.record_rate_limited();
+        assert_eq!(limiter.tokens_per_sec(), 2.5);
+
+        tokio::time::advance(Duration::from_secs(RATE_LIMIT_RECOVERY_SECS)).await;
+        assert!(limiter.tokens_per_sec() > 2.5);
+
+        for _ in 0..10 {
+            tokio::time::advance(Duration::from_secs(RATE_LIMIT_RECOVERY_SECS)).await;
+            limiter.tokens_per_sec();
+        }
+        assert_eq!(limiter.tokens_per_sec(), 10.0);
     }
 }
@@ -0,0 +1,297 @@
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    transaction::VersionedTransaction,
+};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::warn;
+
+use crate::{
+    constants::{DEFAULT_COMPUTE_UNIT_PRICE, DEFAULT_QUOTE_SLIPPAGE_BPS, USDC_MINT},
+    error::{ArbResult, ExecutionError, QuoteError},
+    field_as_string,
+    http::default_http_client,
+    priority_fee::PriorityFeeEstimator,
+    profile::ActiveProfile,
+    providers::SwapQuote,
+    rate_limiter::RateLimiter,
+    slippage_stats::Venue,
+};
+
+/// Quotes and swaps on Raydium's pools directly through its trade API, as an alternative to
+/// Jupiter's routing for the stablebonds with a Raydium pool.
+#[derive(Clone)]
+pub struct RaydiumClient {
+    pub rpc_client: Arc<RpcClient>,
+    pub raydium_swap_url: String,
+    pub keypair_filepath: String,
+    pub priority_fee_estimator: Option<PriorityFeeEstimator>,
+    pub rate_limiter: Option<RateLimiter>,
+    pub profile: Option<ActiveProfile>,
+    pub http_client: ClientWithMiddleware,
+}
+
+impl RaydiumClient {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        raydium_swap_url: String,
+        keypair_filepath: String,
+    ) -> Self {
+        Self {
+            rpc_client,
+            raydium_swap_url,
+            keypair_filepath,
+            priority_fee_estimator: None,
+            rate_limiter: None,
+            profile: None,
+            http_client: default_http_client(),
+        }
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Prices the swap transactions from the fees recently paid to write-lock their pools.
+    pub fn with_priority_fee_estimator(
+        mut self,
+        priority_fee_estimator: PriorityFeeEstimator,
+    ) -> Self {
+        self.priority_fee_estimator = Some(priority_fee_estimator);
+        self
+    }
+
+    /// Quotes with the active profile's slippage.
+    pub fn with_profile(mut self, profile: ActiveProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    fn slippage_bps(&self) -> u64 {
+        self.profile
+            .as_ref()
+            .map_or(DEFAULT_QUOTE_SLIPPAGE_BPS, |profile| {
+                profile.get().slippage_bps
+            })
+    }
+
+    pub fn signer(&self) -> Keypair {
+        read_keypair_file(self.keypair_filepath.clone())
+            .unwrap_or_else(|_| panic!("No keypair found at {}", self.keypair_filepath))
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.wait_if_needed().await;
+        }
+    }
+
+    /// Quote of swapping `amount` of `input_mint` for `output_mint`.
+    pub async fn get_raydium_quote(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        amount: u64,
+        slippage_bps: u64,
+    ) -> Result<SwapQuote, QuoteError> {
+        let url = format!(
+            "{}/compute/swap-base-in?inputMint={}&outputMint={}&amount={}&slippageBps={}&txVersion=V0",
+            self.raydium_swap_url, input_mint, output_mint, amount, slippage_bps,
+        );
+        self.wait_for_rate_limit().await;
+        let response = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|e| QuoteError::Request(e.to_string()))?;
+        let response: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| QuoteError::InvalidResponse(e.to_string()))?;
+        let quote: Quote = raydium_api_data(response.clone())?;
+        Ok(SwapQuote {
+            venue: Venue::Raydium,
+            input_mint: quote.input_mint,
+            in_amount: quote.input_amount,
+            output_mint: quote.output_mint,
+            out_amount: quote.output_amount,
+            price_impact_pct: quote.price_impact_pct / 100.0,
+            route: quote
+                .route_plan
+                .iter()
+                .map(|route_plan| format!("Raydium {}", route_plan.pool_id))
+                .collect::<Vec<_>>()
+                .join("+"),
+            context_slot: 0,
+            fetched_at: Instant::now(),
+            response,
+        })
+    }
+
+    /// Signed transaction executing `quote`, built by Raydium against its pools' current
+    /// accounts.
+    pub async fn raydium_swap_tx(&self, quote: SwapQuote) -> ArbResult<VersionedTransaction> {
+        let pools = raydium_api_data::<Quote>(quote.response.clone())?
+            .route_plan
+            .into_iter()
+            .map(|route_plan| route_plan.pool_id)
+            .collect::<Vec<_>>();
+        let compute_unit_price = self.estimate_compute_unit_price(&pools).await;
+        let signer = self.signer();
+        let request = SwapRequest {
+            compute_unit_price_micro_lamports: compute_unit_price,
+            swap_response: quote.response,
+            tx_version: "V0",
+            wallet: signer.pubkey(),
+            wrap_sol: false,
+            unwrap_sol: false,
+        };
+
+        self.wait_for_rate_limit().await;
+        let response = self
+            .http_client
+            .post(format!(
+                "{}/transaction/swap-base-in",
+                self.raydium_swap_url
+            ))
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|e| QuoteError::Request(e.to_string()))?;
+        let response: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| QuoteError::InvalidResponse(e.to_string()))?;
+        let txs: Vec<SwapTransaction> = raydium_api_data(response)?;
+        // The strategies land the swap as one transaction of their bundle.
+        let [tx] = txs.as_slice() else {
+            return Err(QuoteError::InvalidResponse(format!(
+                "Expected one swap transaction, got {}",
+                txs.len()
+            ))
+            .into());
+        };
+        let tx: VersionedTransaction = base64::decode(&tx.transaction)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| bincode::deserialize(&bytes).map_err(|e| e.to_string()))
+            .map_err(QuoteError::InvalidResponse)?;
+        VersionedTransaction::try_new(tx.message, &[&signer])
+            .map_err(|e| ExecutionError::Build(e.to_string()).into())
+    }
+
+    async fn estimate_compute_unit_price(&self, pools: &[Pubkey]) -> u64 {
+        let Some(priority_fee_estimator) = &self.priority_fee_estimator else {
+            return DEFAULT_COMPUTE_UNIT_PRICE;
+        };
+        match priority_fee_estimator.estimate(pools).await {
+            Ok(compute_unit_price) => compute_unit_price,
+            Err(e) => {
+                warn!(
+                    "Error estimating priority fee ({:?}), using default compute unit price",
+                    e
+                );
+                DEFAULT_COMPUTE_UNIT_PRICE
+            }
+        }
+    }
+
+    pub async fn sell_quote(
+        &self,
+        stablebond_mint: &Pubkey,
+        amount: u64,
+    ) -> Result<(f64, SwapQuote), QuoteError> {
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let quote = self
+            .get_raydium_quote(stablebond_mint, &usdc_mint, amount, self.slippage_bps())
+            .await?;
+        Ok((quote.out_amount as f64 / quote.in_amount as f64, quote))
+    }
+
+    pub async fn buy_quote(
+        &self,
+        stablebond_mint: &Pubkey,
+        amount: u64,
+    ) -> Result<(f64, SwapQuote), QuoteError> {
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let quote = self
+            .get_raydium_quote(&usdc_mint, stablebond_mint, amount, self.slippage_bps())
+            .await?;
+        Ok((quote.in_amount as f64 / quote.out_amount as f64, quote))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Quote {
+    #[serde(with = "field_as_string")]
+    input_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    input_amount: u64,
+    #[serde(with = "field_as_string")]
+    output_mint: Pubkey,
+    #[serde(with = "field_as_string")]
+    output_amount: u64,
+    /// In percent.
+    price_impact_pct: f64,
+    route_plan: Vec<RoutePlan>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoutePlan {
+    #[serde(with = "field_as_string")]
+    pool_id: Pubkey,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SwapRequest {
+    #[serde(with = "field_as_string")]
+    compute_unit_price_micro_lamports: u64,
+    swap_response: serde_json::Value,
+    tx_version: &'static str,
+    #[serde(with = "field_as_string")]
+    wallet: Pubkey,
+    wrap_sol: bool,
+    unwrap_sol: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SwapTransaction {
+    transaction: String,
+}
+
+/// `data` of a Raydium response, or the message Raydium answered with instead.
+fn raydium_api_data<T: DeserializeOwned>(response: serde_json::Value) -> Result<T, QuoteError> {
+    #[derive(Deserialize)]
+    struct Response {
+        success: bool,
+        msg: Option<String>,
+        data: Option<serde_json::Value>,
+    }
+    let response: Response =
+        serde_json::from_value(response).map_err(|e| QuoteError::InvalidResponse(e.to_string()))?;
+    match (response.success, response.data) {
+        (true, Some(data)) => {
+            serde_json::from_value(data).map_err(|e| QuoteError::InvalidResponse(e.to_string()))
+        }
+        (_, _) => Err(QuoteError::Api(
+            response.msg.unwrap_or_else(|| "No data".to_string()),
+        )),
+    }
+}
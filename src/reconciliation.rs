@@ -0,0 +1,297 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::TransactionStatus;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    constants::{BUNDLE_VALIDITY_WINDOW_SECS, RECONCILIATION_SIGNATURE_LIMIT},
+    jito::BundleOutcome,
+    trade_journal::{TradeJournal, TradeState, UnsettledTrade},
+    transaction::parse_trade_memo,
+};
+
+/// How the trades a restart interrupted were settled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub landed: usize,
+    pub failed: usize,
+}
+
+/// Settles the trades a crash or restart left in flight, before the workers trade again, so
+/// that they neither go missing from the PnL nor leave the positions off by their fills.
+///
+/// Submitted bundles are looked up on-chain by the signatures of each of their submissions,
+/// once they are past the validity window, and by the trade id the memo of the wallet's
+/// recent transactions carries, which also finds the trades a restart interrupted before
+/// their submission was journaled. The others failed. Landed trades are reported through the
+/// bundle outcome channel, which verifies their profit, records their fills in the positions
+/// and reconciles them in the journal.
+#[derive(Clone)]
+pub struct Reconciler {
+    pub trade_journal: TradeJournal,
+    pub rpc_client: Arc<RpcClient>,
+    pub wallet: Pubkey,
+    pub outcome_sender: UnboundedSender<BundleOutcome>,
+    pub validity_window: Duration,
+}
+
+impl Reconciler {
+    pub fn new(
+        trade_journal: TradeJournal,
+        rpc_client: Arc<RpcClient>,
+        wallet: Pubkey,
+        outcome_sender: UnboundedSender<BundleOutcome>,
+    ) -> Self {
+        Self {
+            trade_journal,
+            rpc_client,
+            wallet,
+            outcome_sender,
+            validity_window: Duration::from_secs(BUNDLE_VALIDITY_WINDOW_SECS),
+        }
+    }
+
+    pub async fn reconcile(&self) -> Result<Reconciliation> {
+        let trades = self.trade_journal.unsettled_trades()?;
+        let mut reconciliation = Reconciliation::default();
+        // A bundle submitted right before the restart may still land.
+        if let Some(wait) = trades
+            .iter()
+            .filter(|trade| trade.state == TradeState::Submitted)
+            .filter_map(|trade| self.remaining_validity(trade))
+            .max()
+        {
+            info!(
+                "Waiting {}s for the bundles submitted before the restart to land or expire",
+                wait.as_secs()
+            );
+            tokio::time::sleep(wait).await;
+        }
+        let tagged = if trades.iter().any(|trade| {
+            matches!(trade.state, TradeState::Built | TradeState::Submitted)
+                && trade.trade_uuid.is_some()
+        }) {
+            self.tagged_submissions().await?
+        } else {
+            HashMap::new()
+        };
+        for trade in trades {
+            info!(
+                trade_id = trade.trade_id,
+                state = %trade.state,
+                "Reconciling trade interrupted by a restart"
+            );
+            let tagged_submission = || {
+                trade
+                    .trade_uuid
+                    .and_then(|trade_uuid| tagged.get(&trade_uuid).cloned())
+            };
+            let outcome = match trade.state {
+                TradeState::Submitted => self
+                    .landed_submission(&trade)
+                    .await?
+                    .or_else(tagged_submission)
+                    .map(|(signatures, slot)| trade.landed_outcome(signatures, Some(slot))),
+                TradeState::Built => match tagged_submission() {
+                    Some((signatures, slot)) => {
+                        self.trade_journal
+                            .record_tagged_submission(trade.id, &signatures)?;
+                        Some(trade.landed_outcome(signatures, Some(slot)))
+                    }
+                    None => None,
+                },
+                TradeState::Landed => {
+                    Some(trade.landed_outcome(trade.signatures.clone(), trade.landed_slot))
+                }
+                _ => None,
+            };
+            match outcome {
+                Some(outcome) => {
+                    reconciliation.landed += 1;
+                    if self.outcome_sender.send(outcome).is_err() {
+                        error!("Bundle outcome receiver dropped");
+                    }
+                }
+                None => {
+                    reconciliation.failed += 1;
+                    let detail = if trade.state == TradeState::Submitted {
+                        "Not landed before a restart"
+                    } else {
+                        "Not submitted before a restart"
+                    };
+                    self.trade_journal
+                        .transition(trade.id, TradeState::Failed, Some(detail))?;
+                }
+            }
+        }
+        Ok(reconciliation)
+    }
+
+    /// How much longer the trade's bundle may land.
+    fn remaining_validity(&self, trade: &UnsettledTrade) -> Option<Duration> {
+        let submitted_at: DateTime<Utc> = trade.submitted_at.as_deref()?.parse().ok()?;
+        let elapsed = (Utc::now() - submitted_at).to_std().unwrap_or_default();
+        self.validity_window
+            .checked_sub(elapsed)
+            .filter(|wait| !wait.is_zero())
+    }
+
+    /// The signatures and slot of the trade's submission that landed, if any did.
+    async fn landed_submission(
+        &self,
+        trade: &UnsettledTrade,
+    ) -> Result<Option<(Vec<Signature>, u64)>> {
+        let signatures: Vec<Signature> = trade.submissions.iter().flatten().copied().collect();
+        if signatures.is_empty() {
+            return Ok(None);
+        }
+        let statuses = self
+            .rpc_client
+            .get_signature_statuses_with_history(&signatures)
+            .await?
+            .value;
+        let statuses = signatures
+            .into_iter()
+            .zip(statuses)
+            .filter_map(|(signature, status)| Some((signature, status?)))
+            .collect();
+        Ok(landed_submission(&trade.submissions, &statuses))
+    }
+
+    /// The wallet's recent transactions that landed, by the trade id of their memo.
+    async fn tagged_submissions(&self) -> Result<HashMap<Uuid, (Vec<Signature>, u64)>> {
+        let statuses = self
+            .rpc_client
+            .get_signatures_for_address_with_config(
+                &self.wallet,
+                GetConfirmedSignaturesForAddress2Config {
+                    limit: Some(RECONCILIATION_SIGNATURE_LIMIT),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        Ok(tagged_submissions(&statuses))
+    }
+}
+
+/// The signatures, oldest first, and the last slot of the transactions that landed without an
+/// error, by the trade id of their memo.
+fn tagged_submissions(
+    statuses: &[RpcConfirmedTransactionStatusWithSignature],
+) -> HashMap<Uuid, (Vec<Signature>, u64)> {
+    let mut tagged: HashMap<Uuid, (Vec<Signature>, u64)> = HashMap::new();
+    // The RPC lists the newest transactions first.
+    for status in statuses.iter().rev().filter(|status| status.err.is_none()) {
+        let Some(trade_uuid) = status.memo.as_deref().and_then(parse_trade_memo) else {
+            continue;
+        };
+        let Ok(signature) = status.signature.parse() else {
+            continue;
+        };
+        let (signatures, slot) = tagged.entry(trade_uuid).or_default();
+        signatures.push(signature);
+        *slot = (*slot).max(status.slot);
+    }
+    tagged
+}
+
+/// The submission whose transactions all landed without an error, and the slot it landed in.
+/// A bundle lands whole or not at all, so at most one submission did.
+fn landed_submission(
+    submissions: &[Vec<Signature>],
+    statuses: &HashMap<Signature, TransactionStatus>,
+) -> Option<(Vec<Signature>, u64)> {
+    submissions.iter().find_map(|signatures| {
+        let slot = signatures
+            .iter()
+            .map(|signature| {
+                statuses
+                    .get(signature)
+                    .filter(|status| status.err.is_none())
+                    .map(|status| status.slot)
+            })
+            .collect::<Option<Vec<u64>>>()?
+            .into_iter()
+            .max()?;
+        Some((signatures.clone(), slot))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::transaction::TransactionError;
+
+    fn status(slot: u64, err: Option<TransactionError>) -> TransactionStatus {
+        TransactionStatus {
+            slot,
+            confirmations: None,
+            status: err.clone().map_or(Ok(()), Err),
+            err,
+            confirmation_status: None,
+        }
+    }
+
+    #[test]
+    fn finds_the_submission_that_landed_whole() {
+        let first = vec![Signature::new_unique(), Signature::new_unique()];
+        let resubmission = vec![Signature::new_unique(), Signature::new_unique()];
+        let submissions = vec![first.clone(), resubmission.clone()];
+        let mut statuses = HashMap::from([
+            (first[0], status(10, None)),
+            (first[1], status(10, Some(TransactionError::AccountInUse))),
+        ]);
+        assert_eq!(landed_submission(&submissions, &statuses), None);
+
+        statuses.insert(resubmission[0], status(12, None));
+        assert_eq!(landed_submission(&submissions, &statuses), None);
+        statuses.insert(resubmission[1], status(12, None));
+        assert_eq!(
+            landed_submission(&submissions, &statuses),
+            Some((resubmission, 12))
+        );
+    }
+
+    #[test]
+    fn matches_landed_transactions_by_their_trade_memo() {
+        let trade_uuid = Uuid::new_v4();
+        let memo = format!("[50] etherfuse-arb:{}", trade_uuid);
+        let status = |signature: Signature, slot, memo: Option<&str>, err| {
+            RpcConfirmedTransactionStatusWithSignature {
+                signature: signature.to_string(),
+                slot,
+                err,
+                memo: memo.map(str::to_string),
+                block_time: None,
+                confirmation_status: None,
+            }
+        };
+        let (first, second) = (Signature::new_unique(), Signature::new_unique());
+        let statuses = [
+            status(second, 12, Some(&memo), None),
+            status(Signature::new_unique(), 11, Some("[5] hello"), None),
+            status(
+                Signature::new_unique(),
+                11,
+                Some(&memo),
+                Some(TransactionError::AccountInUse),
+            ),
+            status(Signature::new_unique(), 10, None, None),
+            status(first, 10, Some(&format!("[3] gm; {}", &memo)), None),
+        ];
+
+        let tagged = tagged_submissions(&statuses);
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[&trade_uuid], (vec![first, second], 12));
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use rust_decimal::Decimal;
+use solana_program::pubkey::Pubkey;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+const SPREADS_HEADER: &str = "timestamp,mint,strategy,etherfuse_price,oracle_price,\
+    liquidity_usdc_amount,top_of_book_price,spread_bps,quotes_received,best_usdc_amount,\
+    best_profit_usd";
+const QUOTES_HEADER: &str = "timestamp,mint,strategy,trade_percent,usdc_amount,\
+    stablebond_amount,jupiter_price,etherfuse_price,price_impact,potential_profit_usd,route,\
+    context_slot";
+
+/// Best price each strategy found for a mint on one tick. Amounts are UI amounts.
+#[derive(Clone, Debug)]
+pub struct SpreadObservation {
+    pub mint: Pubkey,
+    pub strategy: &'static str,
+    pub etherfuse_price: f64,
+    pub oracle_price: Option<f64>,
+    /// Etherfuse liquidity the strategy could trade against.
+    pub liquidity_usdc_amount: Decimal,
+    /// Jupiter price of the smallest size quoted.
+    pub top_of_book_price: Option<f64>,
+    /// Edge of the Jupiter price over the Etherfuse price in the strategy's direction.
+    pub spread_bps: Option<f64>,
+    pub quotes_received: u32,
+    pub best_usdc_amount: Decimal,
+    pub best_profit_usd: Decimal,
+}
+
+/// One Jupiter quote of the size search. Amounts are UI amounts.
+#[derive(Clone, Debug)]
+pub struct QuoteObservation {
+    pub mint: Pubkey,
+    pub strategy: &'static str,
+    pub trade_percent: Decimal,
+    pub usdc_amount: Decimal,
+    pub stablebond_amount: Decimal,
+    pub jupiter_price: f64,
+    pub etherfuse_price: f64,
+    pub price_impact: f64,
+    pub potential_profit_usd: Decimal,
+    pub route: String,
+    pub context_slot: u64,
+}
+
+/// Appends every spread observation and quote to daily CSV files in a directory, for
+/// analyzing spread dynamics and tuning the size search offline.
+#[derive(Clone)]
+pub struct ResearchExporter {
+    spreads: Arc<Mutex<CsvFile>>,
+    quotes: Arc<Mutex<CsvFile>>,
+}
+
+impl ResearchExporter {
+    pub fn new(dir: &str) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            spreads: Arc::new(Mutex::new(CsvFile::new(dir, "spreads", SPREADS_HEADER))),
+            quotes: Arc::new(Mutex::new(CsvFile::new(dir, "quotes", QUOTES_HEADER))),
+        })
+    }
+
+    pub fn record_spread(&self, spread: &SpreadObservation) {
+        let line = format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            chrono::Utc::now().to_rfc3339(),
+            spread.mint,
+            spread.strategy,
+            spread.etherfuse_price,
+            optional(spread.oracle_price),
+            spread.liquidity_usdc_amount,
+            optional(spread.top_of_book_price),
+            optional(spread.spread_bps),
+            spread.quotes_received,
+            spread.best_usdc_amount,
+            spread.best_profit_usd,
+        );
+        if let Err(e) = self.spreads.lock().unwrap().append(&line) {
+            error!("Error exporting spread: {:?}", e);
+        }
+    }
+
+    pub fn record_quote(&self, quote: &QuoteObservation) {
+        let line = format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            chrono::Utc::now().to_rfc3339(),
+            quote.mint,
+            quote.strategy,
+            quote.trade_percent,
+            quote.usdc_amount,
+            quote.stablebond_amount,
+            quote.jupiter_price,
+            quote.etherfuse_price,
+            quote.price_impact,
+            quote.potential_profit_usd,
+            quote.route,
+            quote.context_slot,
+        );
+        if let Err(e) = self.quotes.lock().unwrap().append(&line) {
+            error!("Error exporting quote: {:?}", e);
+        }
+    }
+}
+
+/// CSV file rotated at UTC midnight, e.g. `quotes-2024-06-01.csv`.
+struct CsvFile {
+    dir: PathBuf,
+    prefix: &'static str,
+    header: &'static str,
+    day: String,
+    file: Option<File>,
+}
+
+impl CsvFile {
+    fn new(dir: &str, prefix: &'static str, header: &'static str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            prefix,
+            header,
+            day: String::new(),
+            file: None,
+        }
+    }
+
+    fn append(&mut self, line: &str) -> Result<()> {
+        let day = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        if self.file.is_none() || day != self.day {
+            let path = self.dir.join(format!("{}-{}.csv", self.prefix, day));
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            if file.metadata()?.len() == 0 {
+                writeln!(file, "{}", self.header)?;
+            }
+            self.file = Some(file);
+            self.day = day;
+        }
+        writeln!(self.file.as_mut().unwrap(), "{}", line)?;
+        Ok(())
+    }
+}
+
+fn optional(value: Option<f64>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
@@ -0,0 +1,460 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use solana_program::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tracing::{error, warn};
+
+#[cfg(feature = "notifications")]
+use crate::event_sink::{Event, EventSink};
+use crate::{
+    constants::{
+        DEFAULT_DAILY_LOSS_LIMIT_USD, DEFAULT_KILL_SWITCH_FILE,
+        DEFAULT_MAX_CONSECUTIVE_FAILED_BUNDLES, DEFAULT_MAX_NOTIONAL_PER_DAY_USD,
+        DEFAULT_MAX_NOTIONAL_PER_HOUR_USD, DEFAULT_MAX_POSITION_USD,
+    },
+    error::RiskError,
+    notifier::{Notifications, Severity},
+    position_tracker::PositionTracker,
+    trade_journal::PnlRow,
+};
+
+/// Scope of a halt that stops every mint.
+pub const GLOBAL_SCOPE: &str = "global";
+
+/// `[risk]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    /// Realized loss over the current UTC day that halts trading.
+    pub daily_loss_limit_usd: Decimal,
+    /// Bundles in a row that failed to land before trading is halted.
+    pub max_consecutive_failed_bundles: u32,
+    /// Apply the limits to each mint separately and only halt the mint that breached them.
+    pub per_mint: bool,
+    /// File recording the halts. Removing it, or running `arb resume`, resumes trading, and
+    /// creating it, even empty, halts every mint.
+    pub kill_switch_file: String,
+    /// Stablebond inventory, valued at NAV, past which no trade may add to a mint's position.
+    pub max_position_usd: Decimal,
+    /// Overrides of `max_position_usd` by mint.
+    pub max_position_usd_per_mint: HashMap<String, Decimal>,
+    /// USDC traded across all mints over the last hour past which trades are throttled.
+    pub max_notional_per_hour_usd: Decimal,
+    /// USDC traded across all mints over the last 24 hours past which trades are throttled.
+    /// The only daily notional limit, `daily_notional_limit_usd` is read as an alias of it.
+    #[serde(alias = "daily_notional_limit_usd")]
+    pub max_notional_per_day_usd: Decimal,
+}
+
+impl RiskConfig {
+    pub fn max_position_usd(&self, mint: &Pubkey) -> Decimal {
+        self.max_position_usd_per_mint
+            .get(&mint.to_string())
+            .copied()
+            .unwrap_or(self.max_position_usd)
+    }
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            daily_loss_limit_usd: DEFAULT_DAILY_LOSS_LIMIT_USD,
+            max_consecutive_failed_bundles: DEFAULT_MAX_CONSECUTIVE_FAILED_BUNDLES,
+            per_mint: false,
+            kill_switch_file: DEFAULT_KILL_SWITCH_FILE.to_string(),
+            max_position_usd: DEFAULT_MAX_POSITION_USD,
+            max_position_usd_per_mint: HashMap::new(),
+            max_notional_per_hour_usd: DEFAULT_MAX_NOTIONAL_PER_HOUR_USD,
+            max_notional_per_day_usd: DEFAULT_MAX_NOTIONAL_PER_DAY_USD,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct DailyRisk {
+    realized_pnl_usd: Decimal,
+    consecutive_failed_bundles: u32,
+}
+
+#[derive(Debug, Default)]
+struct RiskState {
+    day: NaiveDate,
+    scopes: HashMap<String, DailyRisk>,
+    /// Scopes we halted, to reset their limits once an operator lifts the halt.
+    halted: HashSet<String>,
+    /// Time and notional of the submissions of the last 24 hours across all mints, oldest first.
+    submissions: VecDeque<(DateTime<Utc>, Decimal)>,
+}
+
+/// Consulted before every submission. Halts trading, globally or for one mint, when the day's
+/// realized loss or the bundles failing in a row breach their limits. Halts are written to the
+/// kill switch file so they survive restarts until an operator resumes trading.
+#[derive(Clone)]
+pub struct RiskEngine {
+    pub config: RiskConfig,
+    pub position_tracker: Option<PositionTracker>,
+    pub notifications: Notifications,
+    #[cfg(feature = "notifications")]
+    pub event_sink: Option<EventSink>,
+    state: Arc<Mutex<RiskState>>,
+}
+
+impl RiskEngine {
+    pub fn new(config: RiskConfig) -> Self {
+        Self {
+            config,
+            position_tracker: None,
+            notifications: Notifications::new(),
+            #[cfg(feature = "notifications")]
+            event_sink: None,
+            state: Arc::new(Mutex::new(RiskState {
+                day: today(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Enforces the maximum position size against the tracked positions.
+    pub fn with_position_tracker(mut self, position_tracker: PositionTracker) -> Self {
+        self.position_tracker = Some(position_tracker);
+        self
+    }
+
+    /// Pushes halts to the configured chats.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Sends halts to the webhook.
+    #[cfg(feature = "notifications")]
+    pub fn with_event_sink(mut self, event_sink: Option<EventSink>) -> Self {
+        self.event_sink = event_sink;
+        self
+    }
+
+    /// Seeds the day's realized profit from the trade journal's totals per mint since the start
+    /// of the day, so that restarting doesn't reset the loss limit.
+    pub fn with_daily_totals(self, totals: &[PnlRow]) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            for row in totals {
+                let daily = state.scopes.entry(self.scope(&row.key)).or_default();
+                daily.realized_pnl_usd += row.realized_profit_usd;
+            }
+        }
+        self
+    }
+
+    /// Seeds the rolling notional throttle with the time and notional of the trades of the last
+    /// 24 hours, oldest first.
+    pub fn with_recent_submissions(self, submissions: &[(String, Decimal)]) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            for (created_at, notional_usd) in submissions {
+                if let Ok(at) = created_at.parse::<DateTime<Utc>>() {
+                    state.submissions.push_back((at, *notional_usd));
+                }
+            }
+        }
+        self
+    }
+
+    /// Errors when trading `mint` is halted, when a trade of `notional_usd` is throttled, or when
+    /// it could leave the mint's position above its maximum size, in case a leg selling the
+    /// stablebonds it bought fails.
+    pub fn check(&self, mint: &Pubkey, notional_usd: Decimal) -> Result<(), RiskError> {
+        let halts = read_halts(&self.config.kill_switch_file);
+        self.reset_resumed(&halts);
+        if let Some(reason) = halts
+            .get(GLOBAL_SCOPE)
+            .or_else(|| halts.get(&mint.to_string()))
+        {
+            return Err(RiskError::Halted {
+                reason: reason.clone(),
+                kill_switch_file: self.config.kill_switch_file.clone(),
+            });
+        }
+        self.check_throttle(notional_usd)?;
+        if let Some(position_tracker) = &self.position_tracker {
+            let position_usd = position_tracker
+                .position(mint)
+                .map_or(Decimal::ZERO, |position| position.exposure_usd());
+            let max_position_usd = self.config.max_position_usd(mint);
+            if position_usd + notional_usd > max_position_usd {
+                return Err(RiskError::PositionLimit {
+                    position_usd,
+                    notional_usd,
+                    max_position_usd,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record_submission(&self, notional_usd: Decimal) {
+        self.state
+            .lock()
+            .unwrap()
+            .submissions
+            .push_back((Utc::now(), notional_usd));
+    }
+
+    pub fn record_bundle_outcome(&self, mint: &Pubkey, landed: bool) {
+        let scope = self.scope(&mint.to_string());
+        let daily = self.update(&scope, |daily| {
+            if landed {
+                daily.consecutive_failed_bundles = 0;
+            } else {
+                daily.consecutive_failed_bundles += 1;
+            }
+        });
+        if daily.consecutive_failed_bundles >= self.config.max_consecutive_failed_bundles {
+            self.halt(
+                &scope,
+                &format!(
+                    "{} bundles in a row failed to land",
+                    daily.consecutive_failed_bundles
+                ),
+            );
+        }
+    }
+
+    pub fn record_realized_profit(&self, mint: &Pubkey, profit_usd: Decimal) {
+        let scope = self.scope(&mint.to_string());
+        let daily = self.update(&scope, |daily| daily.realized_pnl_usd += profit_usd);
+        if -daily.realized_pnl_usd >= self.config.daily_loss_limit_usd {
+            self.halt(
+                &scope,
+                &format!(
+                    "Realized ${:.2} today, breaching the daily loss limit of ${:.2}",
+                    daily.realized_pnl_usd, self.config.daily_loss_limit_usd
+                ),
+            );
+        }
+    }
+
+    /// Throttles, without halting, trades that would take the notional traded across all mints
+    /// over the last hour or 24 hours past its cap, so that a runaway loop, e.g. on a mispriced
+    /// feed, can't churn through the treasury before anyone notices. Smaller trades may still
+    /// fit the window.
+    fn check_throttle(&self, notional_usd: Decimal) -> Result<(), RiskError> {
+        let now = Utc::now();
+        let mut state = self.state.lock().unwrap();
+        while state
+            .submissions
+            .front()
+            .is_some_and(|(at, _)| now - *at > chrono::Duration::days(1))
+        {
+            state.submissions.pop_front();
+        }
+        let hour_ago = now - chrono::Duration::hours(1);
+        let traded_hour_usd: Decimal = state
+            .submissions
+            .iter()
+            .filter(|(at, _)| *at > hour_ago)
+            .map(|(_, notional)| notional)
+            .sum();
+        let traded_day_usd: Decimal = state.submissions.iter().map(|(_, notional)| notional).sum();
+        for (window, traded_usd, limit_usd) in [
+            (
+                "last hour",
+                traded_hour_usd,
+                self.config.max_notional_per_hour_usd,
+            ),
+            (
+                "last 24 hours",
+                traded_day_usd,
+                self.config.max_notional_per_day_usd,
+            ),
+        ] {
+            if traded_usd + notional_usd > limit_usd {
+                let e = RiskError::Throttled {
+                    window,
+                    traded_usd,
+                    notional_usd,
+                    limit_usd,
+                };
+                warn!("Throttling trade: {}", e);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn halt(&self, scope: &str, reason: &str) {
+        error!(scope, "Halting trading: {}", reason);
+        self.notifications.notify(
+            Severity::Critical,
+            format!("Halting trading ({}): {}", scope, reason),
+        );
+        #[cfg(feature = "notifications")]
+        if let Some(event_sink) = &self.event_sink {
+            event_sink.emit(Event::RiskLimitTripped {
+                scope: scope.to_string(),
+                reason: reason.to_string(),
+            });
+        }
+        self.state.lock().unwrap().halted.insert(scope.to_string());
+        let mut halts = read_halts(&self.config.kill_switch_file);
+        halts.insert(scope.to_string(), reason.to_string());
+        if let Err(e) = write_halts(&self.config.kill_switch_file, &halts) {
+            // Halts are read back from the file, so without it trading goes on.
+            error!("Error writing kill switch file: {:?}", e);
+            self.notifications.notify(
+                Severity::Critical,
+                format!(
+                    "Unable to halt trading ({}), error writing {}: {}",
+                    scope, self.config.kill_switch_file, e
+                ),
+            );
+        }
+    }
+
+    /// Starts the limits of the scopes an operator resumed over, so they aren't breached again
+    /// by the very next trade.
+    fn reset_resumed(&self, halts: &BTreeMap<String, String>) {
+        let mut state = self.state.lock().unwrap();
+        let resumed: Vec<String> = state
+            .halted
+            .iter()
+            .filter(|scope| !halts.contains_key(*scope))
+            .cloned()
+            .collect();
+        for scope in resumed {
+            state.halted.remove(&scope);
+            state.scopes.remove(&scope);
+        }
+    }
+
+    fn scope(&self, mint: &str) -> String {
+        if self.config.per_mint {
+            mint.to_string()
+        } else {
+            GLOBAL_SCOPE.to_string()
+        }
+    }
+
+    fn update(&self, scope: &str, update: impl FnOnce(&mut DailyRisk)) -> DailyRisk {
+        let mut state = self.state.lock().unwrap();
+        let today = today();
+        if state.day != today {
+            // Failures in a row carry over midnight, the daily totals don't.
+            for daily in state.scopes.values_mut() {
+                daily.realized_pnl_usd = Decimal::ZERO;
+            }
+            state.day = today;
+        }
+        let daily = state.scopes.entry(scope.to_string()).or_default();
+        update(daily);
+        daily.clone()
+    }
+}
+
+/// Halts trading `scope`, a mint or `GLOBAL_SCOPE`, e.g. to pause it from a remote control.
+pub fn pause(kill_switch_file: &str, scope: &str, reason: &str) -> Result<()> {
+    let mut halts = read_halts(kill_switch_file);
+    halts.insert(scope.to_string(), reason.to_string());
+    write_halts(kill_switch_file, &halts)
+}
+
+/// Reasons trading is halted, by mint or `GLOBAL_SCOPE`.
+pub fn halts(kill_switch_file: &str) -> BTreeMap<String, String> {
+    read_halts(kill_switch_file)
+}
+
+/// Lifts the halt of `mint`, or every halt when `None`.
+pub fn resume(kill_switch_file: &str, mint: Option<&str>) -> Result<()> {
+    let mut halts = read_halts(kill_switch_file);
+    match mint {
+        Some(mint) => {
+            halts.remove(mint);
+        }
+        None => halts.clear(),
+    }
+    if !halts.is_empty() {
+        return write_halts(kill_switch_file, &halts);
+    }
+    if fs::metadata(kill_switch_file).is_ok() {
+        fs::remove_file(kill_switch_file)?;
+    }
+    Ok(())
+}
+
+/// Reasons trading is halted, by scope. A kill switch file that isn't a JSON object, such as
+/// one created by hand, halts every mint.
+fn read_halts(path: &str) -> BTreeMap<String, String> {
+    let Ok(data) = fs::read_to_string(path) else {
+        return BTreeMap::new();
+    };
+    serde_json::from_str(&data).unwrap_or_else(|_| {
+        BTreeMap::from([(
+            GLOBAL_SCOPE.to_string(),
+            format!("Kill switch file {} is present", path),
+        )])
+    })
+}
+
+fn write_halts(path: &str, halts: &BTreeMap<String, String>) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(halts)?)?;
+    Ok(())
+}
+
+fn today() -> NaiveDate {
+    chrono::Utc::now().date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::TempDir;
+
+    #[test]
+    fn skips_trades_past_the_daily_notional_limit_without_halting() {
+        let temp_dir = TempDir::create("risk-test");
+        let kill_switch_file = temp_dir.file("kill_switch");
+        let risk_engine = RiskEngine::new(RiskConfig {
+            max_notional_per_day_usd: Decimal::from(100),
+            kill_switch_file: kill_switch_file.clone(),
+            ..Default::default()
+        });
+        let mint = Pubkey::new_unique();
+        risk_engine.record_submission(Decimal::from(80));
+
+        assert!(matches!(
+            risk_engine.check(&mint, Decimal::from(30)),
+            Err(RiskError::Throttled { .. })
+        ));
+        assert!(read_halts(&kill_switch_file).is_empty());
+        assert!(risk_engine.check(&mint, Decimal::from(10)).is_ok());
+    }
+
+    #[test]
+    fn sums_notional_exactly() {
+        let temp_dir = TempDir::create("risk-test");
+        let risk_engine = RiskEngine::new(RiskConfig {
+            max_notional_per_hour_usd: Decimal::new(3, 1),
+            kill_switch_file: temp_dir.file("kill_switch"),
+            ..Default::default()
+        });
+        for _ in 0..3 {
+            risk_engine.record_submission(Decimal::new(1, 1));
+        }
+
+        // As f64, 0.1 + 0.1 + 0.1 exceeds 0.3.
+        assert!(risk_engine
+            .check(&Pubkey::new_unique(), Decimal::ZERO)
+            .is_ok());
+    }
+
+    #[test]
+    fn reads_the_old_daily_notional_limit_as_the_rolling_one() {
+        let config: RiskConfig = toml::from_str("daily_notional_limit_usd = 500.0").unwrap();
+
+        assert_eq!(config.max_notional_per_day_usd, Decimal::from(500));
+    }
+}
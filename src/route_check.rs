@@ -0,0 +1,123 @@
+use serde::Deserialize;
+
+use crate::{
+    constants::DEFAULT_MAX_SPLIT_DEVIATION_BPS,
+    jupiter::{RoutePlan, SwapInfo},
+};
+
+/// What to do with a Jupiter quote whose route fills part of the size materially worse than
+/// the rest.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FragileRoutePolicy {
+    /// Re-quote the size through direct routes only, and reject it if those are fragile too.
+    #[default]
+    DirectRoutes,
+    /// Reject the size, so the strategy's search settles on a smaller one.
+    SmallerSize,
+}
+
+/// `[route_check]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteCheckConfig {
+    pub enabled: bool,
+    /// How much worse than the best split of the same hop a split may fill, in basis points.
+    pub max_split_deviation_bps: f64,
+    pub policy: FragileRoutePolicy,
+}
+
+impl Default for RouteCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_split_deviation_bps: DEFAULT_MAX_SPLIT_DEVIATION_BPS,
+            policy: FragileRoutePolicy::default(),
+        }
+    }
+}
+
+/// The split of a route that fills its share of a hop the furthest below the hop's best split.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FragileSplit {
+    /// Label of the AMM the split goes through.
+    pub label: String,
+    /// Share of the hop's input routed through the split.
+    pub percent: u32,
+    pub deviation_bps: f64,
+}
+
+/// The worst split of `route_plan` filling more than `max_deviation_bps` below the best split
+/// between the same mints, if any. The quote's `other_amount_threshold` only bounds the fill
+/// of the whole route, so a split this far off can make part of the size fill much worse than
+/// the quoted average.
+pub fn fragile_split(route_plan: &[RoutePlan], max_deviation_bps: f64) -> Option<FragileSplit> {
+    route_plan
+        .iter()
+        .filter_map(|split| {
+            let best = route_plan
+                .iter()
+                .filter(|other| {
+                    other.swap_info.input_mint == split.swap_info.input_mint
+                        && other.swap_info.output_mint == split.swap_info.output_mint
+                })
+                .filter_map(|other| rate(&other.swap_info))
+                .fold(0.0, f64::max);
+            let deviation_bps = (best - rate(&split.swap_info)?) / best * 10_000.0;
+            (deviation_bps > max_deviation_bps).then(|| FragileSplit {
+                label: split.swap_info.label.clone(),
+                percent: split.percent,
+                deviation_bps,
+            })
+        })
+        .max_by(|a, b| a.deviation_bps.total_cmp(&b.deviation_bps))
+}
+
+/// Output per unit of input of a swap.
+fn rate(swap_info: &SwapInfo) -> Option<f64> {
+    let out_amount: u64 = swap_info.out_amount.parse().ok()?;
+    (swap_info.in_amount > 0).then(|| out_amount as f64 / swap_info.in_amount as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(label: &str, output_mint: &str, percent: u32, in_amount: u64, out: u64) -> RoutePlan {
+        RoutePlan {
+            percent,
+            swap_info: SwapInfo {
+                amm_key: String::new(),
+                label: label.to_string(),
+                input_mint: "USDC".to_string(),
+                output_mint: output_mint.to_string(),
+                in_amount,
+                out_amount: out.to_string(),
+                fee_amount: 0,
+                fee_mint: String::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn flags_the_split_filling_furthest_below_the_best_of_its_hop() {
+        let route_plan = [
+            split("Orca", "CETES", 50, 500, 500),
+            split("Raydium", "CETES", 40, 400, 399),
+            split("Meteora", "CETES", 10, 100, 75),
+            // Another hop, priced at its own rate.
+            split("Phoenix", "SOL", 100, 1_000, 5),
+        ];
+
+        assert_eq!(
+            fragile_split(&route_plan, 50.0),
+            Some(FragileSplit {
+                label: "Meteora".to_string(),
+                percent: 10,
+                deviation_bps: 2_500.0,
+            })
+        );
+        assert_eq!(fragile_split(&route_plan, 3_000.0), None);
+        assert_eq!(fragile_split(&route_plan[..2], 50.0), None);
+    }
+}
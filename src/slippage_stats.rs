@@ -0,0 +1,161 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// Number of most recent legs the rolling statistics are computed over.
+const STATS_WINDOW: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Venue {
+    Jupiter,
+    Raydium,
+    Etherfuse,
+}
+
+/// One swap of a trade, with the amounts the strategy priced it at. Amounts are UI amounts.
+#[derive(Clone, Debug)]
+pub struct TradeLeg {
+    pub venue: Venue,
+    /// AMMs the swap routed through, or the Etherfuse instruction.
+    pub route: String,
+    pub input_mint: Pubkey,
+    pub input_amount: f64,
+    pub output_mint: Pubkey,
+    pub expected_output_amount: f64,
+    /// Price impact the venue quoted, as a fraction, already reflected in the expected output.
+    pub price_impact: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SlippageRecord {
+    pub venue: Venue,
+    pub route: String,
+    pub expected_output_amount: f64,
+    pub realized_output_amount: f64,
+}
+
+impl SlippageRecord {
+    /// Shortfall of the realized output against the quoted output, negative when the fill was
+    /// better than quoted.
+    pub fn slippage_bps(&self) -> f64 {
+        if self.expected_output_amount == 0.0 {
+            return 0.0;
+        }
+        (self.expected_output_amount - self.realized_output_amount) / self.expected_output_amount
+            * 10_000.0
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SlippageSummary {
+    pub fills: u64,
+    pub total_slippage_bps: f64,
+    pub worst_slippage_bps: f64,
+}
+
+impl SlippageSummary {
+    pub fn average_slippage_bps(&self) -> f64 {
+        if self.fills == 0 {
+            return 0.0;
+        }
+        self.total_slippage_bps / self.fills as f64
+    }
+
+    fn add(&mut self, record: &SlippageRecord) {
+        let slippage_bps = record.slippage_bps();
+        if self.fills == 0 || slippage_bps > self.worst_slippage_bps {
+            self.worst_slippage_bps = slippage_bps;
+        }
+        self.fills += 1;
+        self.total_slippage_bps += slippage_bps;
+    }
+}
+
+/// Rolling quoted-versus-realized output of every leg of the trades that landed, per venue
+/// and route, persisted to disk so the `stats` subcommand can show them and the slippage
+/// assumptions of the profit model can be calibrated from real fills.
+#[derive(Clone)]
+pub struct SlippageStats {
+    pub path: String,
+    records: Arc<Mutex<VecDeque<SlippageRecord>>>,
+}
+
+impl SlippageStats {
+    /// Loads previously persisted records from `path` so the statistics survive restarts.
+    pub fn new(path: String) -> Self {
+        let records = load_records(&path).unwrap_or_default();
+        Self {
+            path,
+            records: Arc::new(Mutex::new(records)),
+        }
+    }
+
+    /// Records the realized output of each leg of a landed trade, given our wallet's balance
+    /// changes per mint across the trade.
+    pub fn record(&self, legs: &[TradeLeg], token_deltas: &HashMap<Pubkey, f64>) {
+        let mut records = self.records.lock().unwrap();
+        for (leg, realized_output_amount) in legs.iter().zip(realized_outputs(legs, token_deltas)) {
+            let record = SlippageRecord {
+                venue: leg.venue,
+                route: leg.route.clone(),
+                expected_output_amount: leg.expected_output_amount,
+                realized_output_amount,
+            };
+            info!(
+                venue = ?record.venue,
+                route = %record.route,
+                expected_output = record.expected_output_amount,
+                realized_output = record.realized_output_amount,
+                slippage_bps = record.slippage_bps(),
+                "Leg slippage"
+            );
+            records.push_back(record);
+        }
+        while records.len() > STATS_WINDOW {
+            records.pop_front();
+        }
+        if let Err(e) = save_records(&self.path, &records) {
+            error!("Error saving slippage stats: {:?}", e);
+        }
+    }
+
+    pub fn by_route(&self) -> BTreeMap<String, SlippageSummary> {
+        let mut summaries: BTreeMap<String, SlippageSummary> = BTreeMap::new();
+        for record in self.records.lock().unwrap().iter() {
+            summaries
+                .entry(format!("{:?} {}", record.venue, record.route))
+                .or_default()
+                .add(record);
+        }
+        summaries
+    }
+}
+
+/// Output of each leg implied by the net balance changes. Every leg spends exactly its input
+/// amount, so whatever another leg spent of a leg's output mint was received by that leg.
+pub fn realized_outputs(legs: &[TradeLeg], token_deltas: &HashMap<Pubkey, f64>) -> Vec<f64> {
+    legs.iter()
+        .map(|leg| {
+            let spent_by_other_legs: f64 = legs
+                .iter()
+                .filter(|other| other.input_mint == leg.output_mint)
+                .map(|other| other.input_amount)
+                .sum();
+            token_deltas.get(&leg.output_mint).copied().unwrap_or(0.0) + spent_by_other_legs
+        })
+        .collect()
+}
+
+fn load_records(path: &str) -> Result<VecDeque<SlippageRecord>> {
+    let data = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn save_records(path: &str, records: &VecDeque<SlippageRecord>) -> Result<()> {
+    fs::write(path, serde_json::to_string(records)?)?;
+    Ok(())
+}
@@ -0,0 +1,295 @@
+use clap::ValueEnum;
+use futures::future::join_all;
+use reqwest_middleware::ClientWithMiddleware;
+use serde_json::Value as JsonValue;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::{
+    constants::{
+        COINGECKO_SOL_PRICE_URL, JUPITER_SOL_PRICE_URL, PYTH_MAX_PRICE_AGE_SECS,
+        PYTH_SOL_PRICE_URL, SOL_PRICE_TTL_SECS,
+    },
+    error::{ArbResult, PriceError},
+    http::default_http_client,
+    providers::SolPriceProvider,
+    rate_limiter::RateLimiter,
+};
+
+/// APIs quoting SOL in USD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SolPriceSource {
+    /// Coingecko's simple price API.
+    Coingecko,
+    /// Jupiter's price API, priced off its swap routes.
+    Jupiter,
+    /// Pyth's SOL/USD feed, through the Hermes API.
+    Pyth,
+}
+
+impl SolPriceSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolPriceSource::Coingecko => "Coingecko",
+            SolPriceSource::Jupiter => "Jupiter",
+            SolPriceSource::Pyth => "Pyth",
+        }
+    }
+
+    fn default_url(&self) -> &'static str {
+        match self {
+            SolPriceSource::Coingecko => COINGECKO_SOL_PRICE_URL,
+            SolPriceSource::Jupiter => JUPITER_SOL_PRICE_URL,
+            SolPriceSource::Pyth => PYTH_SOL_PRICE_URL,
+        }
+    }
+
+    /// Price in the source's response, or why there is none.
+    fn parse(&self, v: &JsonValue) -> Result<f64, String> {
+        match self {
+            SolPriceSource::Coingecko => v["solana"]["usd"].as_f64(),
+            // Jupiter quotes prices as strings.
+            SolPriceSource::Jupiter => v["data"]
+                .as_object()
+                .and_then(|data| data.values().next())
+                .and_then(|price| price["price"].as_str())
+                .and_then(|price| price.parse().ok()),
+            SolPriceSource::Pyth => return parse_pyth_price(v, "SOL"),
+        }
+        .ok_or_else(|| format!("No SOL price in {} response: {}", self.name(), v))
+    }
+}
+
+/// Pyth prices are an integer mantissa and a base 10 exponent, published at a unix time. Prices
+/// older than `PYTH_MAX_PRICE_AGE_SECS` are rejected: Hermes keeps serving the last one when
+/// publishers stop.
+pub(crate) fn parse_pyth_price(v: &JsonValue, asset: &str) -> Result<f64, String> {
+    let price = &v["parsed"][0]["price"];
+    let (Some(mantissa), Some(expo), Some(publish_time)) = (
+        price["price"].as_str().and_then(|p| p.parse::<i64>().ok()),
+        price["expo"].as_i64(),
+        price["publish_time"].as_u64(),
+    ) else {
+        return Err(format!("No {} price in Pyth response: {}", asset, v));
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let age = now.saturating_sub(publish_time);
+    if age > PYTH_MAX_PRICE_AGE_SECS {
+        return Err(format!("Pyth {} price is {}s old", asset, age));
+    }
+    Ok(mantissa as f64 * 10f64.powi(expo as i32))
+}
+
+/// Reads the SOL price from one API.
+#[derive(Clone)]
+pub struct SolPriceClient {
+    pub source: SolPriceSource,
+    pub url: String,
+    pub rate_limiter: Option<RateLimiter>,
+    pub http_client: ClientWithMiddleware,
+}
+
+impl SolPriceClient {
+    pub fn new(source: SolPriceSource) -> Self {
+        Self {
+            source,
+            url: source.default_url().to_string(),
+            rate_limiter: None,
+            http_client: default_http_client(),
+        }
+    }
+
+    pub fn with_url(mut self, url: String) -> Self {
+        self.url = url;
+        self
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+}
+
+impl SolPriceProvider for SolPriceClient {
+    fn name(&self) -> &'static str {
+        self.source.name()
+    }
+
+    async fn sol_price(&self) -> ArbResult<f64> {
+        let provider = self.name();
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.wait_if_needed().await;
+        }
+        let response = self
+            .http_client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|e| PriceError::Request {
+                provider,
+                message: e.to_string(),
+            })?;
+        let invalid_response = |message| PriceError::InvalidResponse { provider, message };
+        let v: JsonValue = response
+            .json()
+            .await
+            .map_err(|e| invalid_response(e.to_string()))?;
+        let price = self.source.parse(&v).map_err(invalid_response)?;
+        if !price.is_finite() || price <= 0.0 {
+            return Err(invalid_response(format!("SOL price of {}", price)).into());
+        }
+        Ok(price)
+    }
+}
+
+struct CachedPrice {
+    price: f64,
+    fetched_at: Instant,
+}
+
+/// Median of the SOL price across several sources, so that one source down or off doesn't
+/// misprice the tips. Cached for `SOL_PRICE_TTL_SECS` since every mint of every tick asks.
+/// Errors when no source answers rather than falling back to a stale or made up price.
+#[derive(Clone)]
+pub struct SolPriceOracle<P = SolPriceClient> {
+    pub sources: Vec<P>,
+    pub ttl: Duration,
+    cached: Arc<Mutex<Option<CachedPrice>>>,
+}
+
+impl<P: SolPriceProvider> SolPriceOracle<P> {
+    pub fn new(sources: Vec<P>) -> Self {
+        Self {
+            sources,
+            ttl: Duration::from_secs(SOL_PRICE_TTL_SECS),
+            cached: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn cached_price(&self) -> Option<f64> {
+        let cached = self.cached.lock().unwrap();
+        cached
+            .as_ref()
+            .filter(|cached| cached.fetched_at.elapsed() < self.ttl)
+            .map(|cached| cached.price)
+    }
+}
+
+impl<P: SolPriceProvider> SolPriceProvider for SolPriceOracle<P> {
+    fn name(&self) -> &'static str {
+        "median"
+    }
+
+    async fn sol_price(&self) -> ArbResult<f64> {
+        if let Some(price) = self.cached_price() {
+            return Ok(price);
+        }
+        let results = join_all(self.sources.iter().map(|source| source.sol_price())).await;
+        let mut prices = Vec::new();
+        for (source, result) in self.sources.iter().zip(results) {
+            match result {
+                Ok(price) => prices.push(price),
+                Err(e) => warn!("Error fetching SOL price from {}: {}", source.name(), e),
+            }
+        }
+        let price = median(&mut prices).ok_or_else(|| {
+            PriceError::Unavailable(format!("none of {} sources answered", self.sources.len()))
+        })?;
+        info!(
+            "SOL price of ${:.2} from {} of {} sources",
+            price,
+            prices.len(),
+            self.sources.len()
+        );
+        *self.cached.lock().unwrap() = Some(CachedPrice {
+            price,
+            fetched_at: Instant::now(),
+        });
+        Ok(price)
+    }
+}
+
+/// Middle price, or the mean of the middle two of an even number of them.
+pub(crate) fn median(prices: &mut [f64]) -> Option<f64> {
+    if prices.is_empty() {
+        return None;
+    }
+    prices.sort_by(f64::total_cmp);
+    let mid = prices.len() / 2;
+    if prices.len() % 2 == 0 {
+        Some((prices[mid - 1] + prices[mid]) / 2.0)
+    } else {
+        Some(prices[mid])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::ArbError, mock::MockSolPriceProvider};
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [150.0]), Some(150.0));
+        assert_eq!(median(&mut [152.0, 10.0, 150.0]), Some(150.0));
+        assert_eq!(median(&mut [152.0, 150.0]), Some(151.0));
+    }
+
+    #[tokio::test]
+    async fn oracle_takes_the_median_of_the_sources_that_answer() {
+        let oracle = SolPriceOracle::new(vec![
+            MockSolPriceProvider::new(150.0),
+            MockSolPriceProvider::failing(),
+            MockSolPriceProvider::new(1_000.0),
+            MockSolPriceProvider::new(151.0),
+        ]);
+
+        assert_eq!(oracle.sol_price().await.unwrap(), 151.0);
+    }
+
+    #[tokio::test]
+    async fn oracle_fails_when_no_source_answers() {
+        let oracle = SolPriceOracle::new(vec![
+            MockSolPriceProvider::failing(),
+            MockSolPriceProvider::failing(),
+        ]);
+
+        let err = oracle.sol_price().await.unwrap_err();
+
+        assert!(
+            matches!(err, ArbError::Price(PriceError::Unavailable(_))),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn oracle_caches_the_price_until_the_ttl() {
+        let source = MockSolPriceProvider::new(150.0);
+        let oracle = SolPriceOracle::new(vec![source.clone()]);
+
+        oracle.sol_price().await.unwrap();
+        oracle.sol_price().await.unwrap();
+        assert_eq!(source.calls(), 1);
+
+        let oracle = oracle.with_ttl(Duration::ZERO);
+        oracle.sol_price().await.unwrap();
+        assert_eq!(source.calls(), 2);
+    }
+}
@@ -0,0 +1,225 @@
+use serde::Deserialize;
+use solana_program::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::constants::{
+    DEFAULT_ADAPTIVE_MAX_INTERVAL_SECS, DEFAULT_ADAPTIVE_MIN_INTERVAL_SECS,
+    DEFAULT_QUIET_SPREAD_STDDEV_BPS, DEFAULT_VOLATILE_SPREAD_STDDEV_BPS,
+    SPREAD_HISTORY_MIN_SAMPLES, SPREAD_HISTORY_WINDOW_SECS,
+};
+
+/// Mean and standard deviation of the spreads of a mint and strategy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpreadStats {
+    pub samples: usize,
+    pub mean_bps: f64,
+    pub stddev_bps: f64,
+}
+
+/// Spreads in bps and when they were seen, by mint and strategy.
+type Spreads = HashMap<(Pubkey, &'static str), VecDeque<(Instant, f64)>>;
+
+/// Top-of-book spreads the strategies saw between Etherfuse and the swap venues over the last
+/// hour, by mint and strategy, since the two directions' spreads move in opposite ways.
+#[derive(Clone)]
+pub struct SpreadHistory {
+    pub window: Duration,
+    /// Standard deviations above its mean a spread must be for the strategies to search it.
+    pub entry_z_score: Option<f64>,
+    spreads: Arc<Mutex<Spreads>>,
+}
+
+impl Default for SpreadHistory {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(SPREAD_HISTORY_WINDOW_SECS),
+            entry_z_score: None,
+            spreads: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl SpreadHistory {
+    /// Has the strategies skip the spreads that aren't `entry_z_score` standard deviations
+    /// above their mean, usual spreads which at best cover the fees.
+    pub fn with_entry_z_score(mut self, entry_z_score: Option<f64>) -> Self {
+        self.entry_z_score = entry_z_score;
+        self
+    }
+
+    pub fn record(&self, mint: &Pubkey, strategy: &'static str, spread_bps: f64) {
+        self.record_at(mint, strategy, spread_bps, Instant::now());
+    }
+
+    fn record_at(&self, mint: &Pubkey, strategy: &'static str, spread_bps: f64, at: Instant) {
+        let mut spreads = self.spreads.lock().unwrap();
+        let samples = spreads.entry((*mint, strategy)).or_default();
+        samples.push_back((at, spread_bps));
+        while samples
+            .front()
+            .is_some_and(|(sampled_at, _)| at.duration_since(*sampled_at) > self.window)
+        {
+            samples.pop_front();
+        }
+    }
+
+    /// Statistics of the spreads within the window, once there are enough of them.
+    pub fn stats(&self, mint: &Pubkey, strategy: &'static str) -> Option<SpreadStats> {
+        let spreads = self.spreads.lock().unwrap();
+        let samples: Vec<f64> = spreads
+            .get(&(*mint, strategy))?
+            .iter()
+            .filter(|(sampled_at, _)| sampled_at.elapsed() <= self.window)
+            .map(|(_, spread_bps)| *spread_bps)
+            .collect();
+        if samples.len() < SPREAD_HISTORY_MIN_SAMPLES {
+            return None;
+        }
+        let mean_bps = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance = samples
+            .iter()
+            .map(|spread_bps| (spread_bps - mean_bps).powi(2))
+            .sum::<f64>()
+            / (samples.len() - 1) as f64;
+        Some(SpreadStats {
+            samples: samples.len(),
+            mean_bps,
+            stddev_bps: variance.sqrt(),
+        })
+    }
+
+    /// Standard deviations `spread_bps` is above the mean of the mint and strategy's spreads,
+    /// once there are enough of them.
+    pub fn z_score(&self, mint: &Pubkey, strategy: &'static str, spread_bps: f64) -> Option<f64> {
+        let stats = self.stats(mint, strategy)?;
+        let deviation = spread_bps - stats.mean_bps;
+        if stats.stddev_bps == 0.0 {
+            // Any move of a spread that never moved is unusual.
+            return Some(if deviation > 0.0 { f64::INFINITY } else { 0.0 });
+        }
+        Some(deviation / stats.stddev_bps)
+    }
+
+    /// Standard deviation of the mint's spreads, of its most volatile strategy.
+    pub fn volatility_bps(&self, mint: &Pubkey) -> Option<f64> {
+        let strategies: Vec<&'static str> = self
+            .spreads
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|(spread_mint, _)| spread_mint == mint)
+            .map(|(_, strategy)| *strategy)
+            .collect();
+        strategies
+            .into_iter()
+            .filter_map(|strategy| self.stats(mint, strategy))
+            .map(|stats| stats.stddev_bps)
+            .reduce(f64::max)
+    }
+}
+
+/// `[adaptive_interval]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveIntervalConfig {
+    pub enabled: bool,
+    pub min_secs: u64,
+    pub max_secs: u64,
+    /// Spread standard deviation above which a mint ticks more often.
+    pub volatile_bps: f64,
+    /// Spread standard deviation below which a mint ticks less often.
+    pub quiet_bps: f64,
+}
+
+impl Default for AdaptiveIntervalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_secs: DEFAULT_ADAPTIVE_MIN_INTERVAL_SECS,
+            max_secs: DEFAULT_ADAPTIVE_MAX_INTERVAL_SECS,
+            volatile_bps: DEFAULT_VOLATILE_SPREAD_STDDEV_BPS,
+            quiet_bps: DEFAULT_QUIET_SPREAD_STDDEV_BPS,
+        }
+    }
+}
+
+impl AdaptiveIntervalConfig {
+    /// The mint's `interval`, shortened in proportion to how far its spread's volatility is
+    /// above `volatile_bps` or lengthened to how far it is below `quiet_bps`, within the bounds.
+    /// Unchanged in between, and until the spread has enough history.
+    pub fn interval(&self, interval: Duration, volatility_bps: Option<f64>) -> Duration {
+        let Some(volatility_bps) = volatility_bps.filter(|_| self.enabled) else {
+            return interval;
+        };
+        let min = Duration::from_secs(self.min_secs).min(interval);
+        let max = Duration::from_secs(self.max_secs).max(interval);
+        if volatility_bps > self.volatile_bps {
+            interval
+                .mul_f64(self.volatile_bps / volatility_bps)
+                .max(min)
+        } else if volatility_bps < self.quiet_bps {
+            if volatility_bps <= 0.0 {
+                return max;
+            }
+            interval.mul_f64(self.quiet_bps / volatility_bps).min(max)
+        } else {
+            interval
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_hour_of_spreads() {
+        let history = SpreadHistory::default();
+        let mint = Pubkey::new_unique();
+        let start = Instant::now();
+        history.record_at(&mint, "strategy", 100.0, start);
+        for (i, spread_bps) in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].iter().enumerate() {
+            let at = start + Duration::from_secs(SPREAD_HISTORY_WINDOW_SECS + i as u64 + 1);
+            history.record_at(&mint, "strategy", *spread_bps, at);
+        }
+
+        let stats = history.stats(&mint, "strategy").unwrap();
+        assert_eq!(stats.samples, 8);
+        assert_eq!(stats.mean_bps, 5.0);
+        assert!((stats.stddev_bps - 2.138).abs() < 0.001);
+        assert_eq!(history.stats(&Pubkey::new_unique(), "strategy"), None);
+    }
+
+    #[test]
+    fn adapts_the_interval_to_the_volatility_within_bounds() {
+        let config = AdaptiveIntervalConfig {
+            enabled: true,
+            min_secs: 60,
+            max_secs: 900,
+            volatile_bps: 10.0,
+            quiet_bps: 1.0,
+        };
+        let interval = Duration::from_secs(300);
+
+        assert_eq!(config.interval(interval, None), interval);
+        assert_eq!(config.interval(interval, Some(5.0)), interval);
+        assert_eq!(
+            config.interval(interval, Some(20.0)),
+            Duration::from_secs(150)
+        );
+        assert_eq!(
+            config.interval(interval, Some(100.0)),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            config.interval(interval, Some(0.5)),
+            Duration::from_secs(600)
+        );
+        assert_eq!(
+            config.interval(interval, Some(0.0)),
+            Duration::from_secs(900)
+        );
+    }
+}
@@ -1,23 +1,68 @@
+use crate::cost_model::CostModel;
 use crate::market_data::MarketData;
 use crate::math;
-use crate::math::{TokenAmountExt, UiAmountExt};
+use crate::math::{FeeBreakdown, Leg, TokenAmountExt, UiAmountExt};
 use crate::{
     constants::{
-        INITIAL_POINTS, MAX_RETRIES, MAX_TRADE_PERCENT, MAX_USDC_AMOUNT_PER_TRADE,
-        MIN_TRADE_PERCENT, MIN_USDC_AMOUNT, RETRY_DELAY_MS, SLIPPAGE_BIPS, STABLEBOND_DECIMALS,
-        USDC_DECIMALS,
+        DEFAULT_MIN_PROFIT_USD, INITIAL_POINTS, MAX_IMPLIED_PRICE_DEVIATION_BPS, MAX_RETRIES,
+        MAX_TRADE_PERCENT, MAX_USDC_AMOUNT_PER_TRADE, MIN_TRADE_PERCENT, MIN_USDC_AMOUNT,
+        RETRY_DELAY_MS, SLIPPAGE_BIPS, STABLEBOND_DECIMALS, USDC_DECIMALS, USDC_MINT,
     },
-    jupiter::JupiterClient,
+    decision_log::{skip, SkipReason},
+    error::{ArbError, QuoteError},
+    providers::{BondVenue, SwapQuote, SwapVenue},
+    research_export::{QuoteObservation, ResearchExporter, SpreadObservation},
+    slippage_stats::{TradeLeg, Venue},
+    spread_history::SpreadHistory,
+    transaction::{compose_atomic_tx, simulate_token_balance, simulate_tx, TransactionCache},
 };
-use crate::{etherfuse::EtherfuseClient, jupiter::Quote};
-use anyhow::Result;
+use crate::{etherfuse::EtherfuseClient, swap_router::SwapRouter};
+use anyhow::{anyhow, Result};
 use enum_dispatch::enum_dispatch;
+use rust_decimal::Decimal;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
-use std::sync::Arc;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Signer},
+    transaction::VersionedTransaction,
+};
+use spl_associated_token_account::get_associated_token_address;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+/// Expected profit in USD below which no trade is made, shared by every clone of the strategies.
+#[derive(Clone, Debug)]
+pub struct MinProfit(Arc<RwLock<Decimal>>);
+
+impl MinProfit {
+    pub fn new(usd: Decimal) -> Self {
+        Self(Arc::new(RwLock::new(usd)))
+    }
 
+    pub fn get(&self) -> Decimal {
+        *self.0.read().unwrap()
+    }
+
+    pub fn set(&self, usd: Decimal) {
+        *self.0.write().unwrap() = usd;
+    }
+}
+
+impl Default for MinProfit {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_PROFIT_USD)
+    }
+}
+
+// The futures are awaited on the mint workers' spawned tasks, which only the concrete
+// strategies' need to be `Send` for.
+#[allow(async_fn_in_trait)]
 #[enum_dispatch]
 pub trait Strategy {
+    fn name(&self) -> &'static str;
+
     async fn process_market_data(
         &mut self,
         md: &MarketData,
@@ -26,59 +71,139 @@ pub trait Strategy {
 }
 
 #[derive(Clone)]
-pub struct BuyOnEtherfuseSellOnJupiter {
+pub struct BuyOnEtherfuseSellOnJupiter<Q = SwapRouter, V = EtherfuseClient> {
     pub rpc_client: Arc<RpcClient>,
     pub keypair_filepath: String,
-    pub jupiter_client: JupiterClient,
-    pub etherfuse_client: EtherfuseClient,
+    pub swap_venue: Q,
+    pub etherfuse_client: V,
+    pub preflight: bool,
+    pub exporter: Option<ResearchExporter>,
+    pub spread_history: Option<SpreadHistory>,
+    pub min_profit: MinProfit,
+    pub transaction_cache: TransactionCache,
 }
 
-impl BuyOnEtherfuseSellOnJupiter {
+impl<Q: SwapVenue, V: BondVenue> BuyOnEtherfuseSellOnJupiter<Q, V> {
     pub fn new(
         rpc_client: Arc<RpcClient>,
-        jupiter_client: JupiterClient,
+        swap_venue: Q,
         keypair_filepath: String,
-        etherfuse_client: EtherfuseClient,
+        etherfuse_client: V,
     ) -> Self {
         BuyOnEtherfuseSellOnJupiter {
+            transaction_cache: TransactionCache::new(rpc_client.clone()),
             rpc_client,
             keypair_filepath,
-            jupiter_client,
+            swap_venue,
             etherfuse_client,
+            preflight: false,
+            exporter: None,
+            spread_history: None,
+            min_profit: MinProfit::default(),
         }
     }
+
+    /// Simulates the trade before handing it to the Jito client.
+    pub fn with_preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    /// Exports every quote and the spread found on each tick for research.
+    pub fn with_exporter(mut self, exporter: Option<ResearchExporter>) -> Self {
+        self.exporter = exporter;
+        self
+    }
+
+    /// Shares the minimum profit with the control plane, so operators can adjust it at runtime.
+    pub fn with_min_profit(mut self, min_profit: MinProfit) -> Self {
+        self.min_profit = min_profit;
+        self
+    }
+
+    /// Records the spread found on each tick.
+    pub fn with_spread_history(mut self, spread_history: SpreadHistory) -> Self {
+        self.spread_history = Some(spread_history);
+        self
+    }
+
+    /// Lookup tables the oracle updates and legs are rebuilt with to simulate them as one.
+    pub fn with_transaction_cache(mut self, transaction_cache: TransactionCache) -> Self {
+        self.transaction_cache = transaction_cache;
+        self
+    }
 }
 
 #[derive(Clone)]
-pub struct BuyOnJupiterSellOnEtherfuse {
+pub struct BuyOnJupiterSellOnEtherfuse<Q = SwapRouter, V = EtherfuseClient> {
     pub rpc_client: Arc<RpcClient>,
-    pub jupiter_client: JupiterClient,
+    pub swap_venue: Q,
     pub keypair_filepath: String,
-    pub etherfuse_client: EtherfuseClient,
+    pub etherfuse_client: V,
+    pub preflight: bool,
+    pub simulate_redemption: bool,
+    pub exporter: Option<ResearchExporter>,
+    pub spread_history: Option<SpreadHistory>,
+    pub min_profit: MinProfit,
+    pub transaction_cache: TransactionCache,
 }
 
-impl BuyOnJupiterSellOnEtherfuse {
+impl<Q: SwapVenue, V: BondVenue> BuyOnJupiterSellOnEtherfuse<Q, V> {
     pub fn new(
         rpc_client: Arc<RpcClient>,
-        jupiter_client: JupiterClient,
+        swap_venue: Q,
         keypair_filepath: String,
-        etherfuse_client: EtherfuseClient,
+        etherfuse_client: V,
     ) -> Self {
         BuyOnJupiterSellOnEtherfuse {
+            transaction_cache: TransactionCache::new(rpc_client.clone()),
             rpc_client,
-            jupiter_client,
+            swap_venue,
             keypair_filepath,
             etherfuse_client,
+            preflight: false,
+            simulate_redemption: false,
+            exporter: None,
+            spread_history: None,
+            min_profit: MinProfit::default(),
         }
     }
-}
 
-#[derive(Clone)]
-pub struct SellOnJupiterBuyOnEtherfuse {
-    pub rpc_client: Arc<RpcClient>,
-    pub jupiter_client: JupiterClient,
-    pub keypair_filepath: String,
-    pub etherfuse_client: EtherfuseClient,
+    /// Simulates the trade before handing it to the Jito client.
+    pub fn with_preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    /// Prices the trade at the USDC its simulated redemption pays out, instead of at NAV.
+    pub fn with_redemption_simulation(mut self, simulate_redemption: bool) -> Self {
+        self.simulate_redemption = simulate_redemption;
+        self
+    }
+
+    /// Exports every quote and the spread found on each tick for research.
+    pub fn with_exporter(mut self, exporter: Option<ResearchExporter>) -> Self {
+        self.exporter = exporter;
+        self
+    }
+
+    /// Shares the minimum profit with the control plane, so operators can adjust it at runtime.
+    pub fn with_min_profit(mut self, min_profit: MinProfit) -> Self {
+        self.min_profit = min_profit;
+        self
+    }
+
+    /// Records the spread found on each tick.
+    pub fn with_spread_history(mut self, spread_history: SpreadHistory) -> Self {
+        self.spread_history = Some(spread_history);
+        self
+    }
+
+    /// Lookup tables the oracle updates and legs are rebuilt with to simulate them as one.
+    pub fn with_transaction_cache(mut self, transaction_cache: TransactionCache) -> Self {
+        self.transaction_cache = transaction_cache;
+        self
+    }
 }
 
 #[enum_dispatch(Strategy)]
@@ -87,30 +212,45 @@ pub enum StrategyEnum {
     BuyOnEtherfuseSellOnJupiter,
 }
 
-impl Strategy for BuyOnJupiterSellOnEtherfuse {
+impl<Q: SwapVenue, V: BondVenue> Strategy for BuyOnJupiterSellOnEtherfuse<Q, V> {
+    fn name(&self) -> &'static str {
+        "BuyOnJupiterSellOnEtherfuse"
+    }
+
     async fn process_market_data(
         &mut self,
         md: &MarketData,
         stablebond_mint: &Pubkey,
     ) -> Result<StrategyResult> {
-        let mut sell_liquidity_usdc_amount = md
-            .sell_liquidity_usdc_amount
-            .ok_or_else(|| anyhow::anyhow!("Missing sell_liquidity_usdc_amount"))?;
-        let usdc_holdings_token_amount = md
-            .usdc_holdings_token_amount
-            .ok_or_else(|| anyhow::anyhow!("Missing usdc_holdings_token_amount"))?;
-        let etherfuse_price_per_token = md
-            .etherfuse_price_per_token
-            .ok_or_else(|| anyhow::anyhow!("Missing etherfuse_price_per_token"))?;
+        let mut sell_liquidity_usdc_amount = md.sell_liquidity_usdc_amount.ok_or_else(|| {
+            skip(
+                SkipReason::MarketDataUnavailable,
+                "Missing sell_liquidity_usdc_amount",
+            )
+        })?;
+        let usdc_holdings_token_amount = md.usdc_holdings_token_amount.ok_or_else(|| {
+            skip(
+                SkipReason::MarketDataUnavailable,
+                "Missing usdc_holdings_token_amount",
+            )
+        })?;
+        let etherfuse_price_per_token = md.etherfuse_price_per_token.ok_or_else(|| {
+            skip(
+                SkipReason::MarketDataUnavailable,
+                "Missing etherfuse_price_per_token",
+            )
+        })?;
 
         if usdc_holdings_token_amount == 0 {
-            return Err(anyhow::anyhow!(
-                "USDC holdings are required for this strategy"
+            return Err(skip(
+                SkipReason::InsufficientBalance,
+                "USDC holdings are required for this strategy",
             ));
         }
         if sell_liquidity_usdc_amount == 0 {
-            return Err(anyhow::anyhow!(
-                "Sell liquidity in USDC is required for this strategy"
+            return Err(skip(
+                SkipReason::NoLiquidity,
+                "Sell liquidity in USDC is required for this strategy",
             ));
         }
 
@@ -125,326 +265,1368 @@ impl Strategy for BuyOnJupiterSellOnEtherfuse {
                 }
             };
 
-        let max_usdc_token_amount_to_redeem = (sell_liquidity_usdc_amount
-            .min(usdc_holdings_token_amount))
-        .min(MAX_USDC_AMOUNT_PER_TRADE.to_token_amount(USDC_DECIMALS));
+        let max_usdc_token_amount_per_trade =
+            MAX_USDC_AMOUNT_PER_TRADE.to_token_amount_floor(USDC_DECIMALS)?;
+        let uncapped_usdc_token_amount = sell_liquidity_usdc_amount.min(usdc_holdings_token_amount);
+        let max_usdc_token_amount_to_redeem =
+            uncapped_usdc_token_amount.min(max_usdc_token_amount_per_trade);
 
-        let mut best_profit: f64 = 0.0;
+        let etherfuse_price = math::to_decimal(etherfuse_price_per_token)?;
+        let fees = bundle_fees(md)?;
+        let mut best_profit = Decimal::ZERO;
         let mut best_usdc_amount = 0;
         let mut best_stablebond_amount = 0;
-        let mut best_quote: Option<Quote> = None;
+        let mut best_quote: Option<SwapQuote> = None;
+        let mut best_price = 0.0;
+        let mut best_trade_percent = Decimal::ZERO;
+        let mut quotes_received = 0;
+        let mut top_of_book_price: Option<f64> = None;
+        let mut ordinary = None;
+        let spread_bps_at =
+            |price: f64| (etherfuse_price_per_token - price) / etherfuse_price_per_token * 10_000.0;
 
         let max_amount = max_usdc_token_amount_to_redeem;
 
-        // Generate initial test points with exponential distribution
-        let points: Vec<f64> = (0..INITIAL_POINTS)
-            .map(|i| {
-                let t = i as f64 / (INITIAL_POINTS - 1) as f64;
-                let exp_t = t.powf(1.5); // Exponential distribution
-                MIN_TRADE_PERCENT + (MAX_TRADE_PERCENT - MIN_TRADE_PERCENT) * exp_t
-            })
-            .collect();
         // Test each trade size
-        for trade_percent in points {
-            let usdc_amount = (max_amount as f64 * trade_percent) as u64;
-            let stablebond_amount = (usdc_amount as f64 / etherfuse_price_per_token) as u64;
+        for trade_percent in candidate_trade_sizes(
+            md,
+            TradeDirection::BuyOnJupiterSellOnEtherfuse,
+            max_amount,
+            etherfuse_price,
+        )? {
+            let usdc_amount = usdc_amount_at_size(max_amount, trade_percent)?;
+            let stablebond_amount = stablebond_amount_at(usdc_amount, etherfuse_price)?;
 
             // Skip tiny amounts
             if usdc_amount < MIN_USDC_AMOUNT {
                 continue;
             }
 
-            // Get quote with retries
-            let mut retries = 0;
-            let quote_result = loop {
-                match self
-                    .jupiter_client
-                    .buy_quote(stablebond_mint, usdc_amount)
-                    .await
-                {
-                    Ok(quote) => break Some(quote),
-                    Err(e) => {
-                        retries += 1;
-                        if retries >= MAX_RETRIES {
-                            println!("Failed to get quote after {} retries: {}", MAX_RETRIES, e);
-                            break None;
-                        }
-                        println!("Retry {}/{}: {}", retries, MAX_RETRIES, e);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_DELAY_MS))
-                            .await;
-                    }
-                }
-            };
+            let quote_result = quote_with_retries(
+                &mut self.swap_venue,
+                TradeDirection::BuyOnJupiterSellOnEtherfuse,
+                stablebond_mint,
+                usdc_amount,
+            )
+            .await;
 
             let (price_when_buying, buy_quote) = match quote_result {
                 Some(quote) => quote,
                 None => continue,
             };
+            quotes_received += 1;
+            top_of_book_price.get_or_insert(price_when_buying);
+            if quotes_received == 1 {
+                ordinary = ordinary_spread(
+                    self.spread_history.as_ref(),
+                    stablebond_mint,
+                    self.name(),
+                    spread_bps_at(price_when_buying),
+                );
+                if ordinary.is_some() {
+                    break;
+                }
+            }
 
             // Calculate price impact
             let price_impact =
                 (price_when_buying - etherfuse_price_per_token) / etherfuse_price_per_token;
 
-            let potential_profit = match math::profit_from_arb(
-                etherfuse_price_per_token,
-                price_when_buying,
-                stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS),
-            ) {
-                Ok(profit) => profit - md.jito_tip_usd_price.unwrap_or(0.10),
+            let token_amount = stablebond_amount.to_ui_decimal(STABLEBOND_DECIMALS);
+            let profit = math::to_decimal(price_when_buying).and_then(|price| {
+                math::net_profit(
+                    Leg::new(price, token_amount),
+                    Leg::new(etherfuse_price, token_amount),
+                    fees,
+                )
+            });
+            let potential_profit = match profit {
+                Ok(profit) => profit,
                 Err(e) => {
-                    println!("Error calculating profit: {}. Skipping.", e);
+                    warn!("Error calculating profit: {}. Skipping.", e);
                     continue;
                 }
             };
 
-            println!("\nTrade Analysis for BuyOnJupiterSellOnEtherfuse:");
-            println!("Trade Size: {}% of max", trade_percent * 100.0);
-            println!("USDC Amount: {}", usdc_amount.to_ui_amount(USDC_DECIMALS));
-            println!("Price Impact: {:.2}%", price_impact * 100.0);
-            println!(
-                "Jito tip usd price: {}",
-                md.jito_tip_usd_price.unwrap_or(0.10)
+            debug!(
+                trade_percent = %(trade_percent * Decimal::ONE_HUNDRED),
+                usdc_amount = usdc_amount.to_ui_amount(USDC_DECIMALS),
+                price_impact = price_impact * 100.0,
+                jito_tip_usd = %fees.tip_usd,
+                potential_profit = %potential_profit,
+                jupiter_buy_price = price_when_buying,
+                etherfuse_sell_price = etherfuse_price_per_token,
+                "Trade analysis"
             );
-            println!("Potential Profit: {}", potential_profit);
-            println!("Buy price on jupiter: {}", price_when_buying);
-            println!("Sell price on etherfuse: {}", etherfuse_price_per_token);
-            println!("Stablebond: {:?}", stablebond_mint);
+            if let Some(exporter) = &self.exporter {
+                exporter.record_quote(&QuoteObservation {
+                    mint: *stablebond_mint,
+                    strategy: self.name(),
+                    trade_percent,
+                    usdc_amount: usdc_amount.to_ui_decimal(USDC_DECIMALS),
+                    stablebond_amount: stablebond_amount.to_ui_decimal(STABLEBOND_DECIMALS),
+                    jupiter_price: price_when_buying,
+                    etherfuse_price: etherfuse_price_per_token,
+                    price_impact,
+                    potential_profit_usd: potential_profit,
+                    route: buy_quote.route.clone(),
+                    context_slot: buy_quote.context_slot,
+                });
+            }
 
             if potential_profit > best_profit {
-                println!("\n🎯 New best trade found!");
-                println!("Previous best profit: {}", best_profit);
-                println!("New best profit: {}", potential_profit);
+                debug!(
+                    previous_best_profit = %best_profit,
+                    best_profit = %potential_profit,
+                    "New best trade found"
+                );
 
                 best_profit = potential_profit;
                 best_usdc_amount = usdc_amount;
                 best_stablebond_amount = stablebond_amount;
+                best_price = price_when_buying;
+                best_trade_percent = trade_percent;
                 best_quote = Some(buy_quote);
             }
         }
 
-        println!("\n🏁 Search Complete");
-        println!("Final best profit: {}", best_profit);
-        println!(
-            "Final USDC amount: {}",
-            best_usdc_amount.to_ui_amount(USDC_DECIMALS)
-        );
-        println!(
-            "Final Stablebond amount: {}",
-            best_stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS)
+        info!(
+            best_profit = %best_profit,
+            usdc_amount = best_usdc_amount.to_ui_amount(USDC_DECIMALS),
+            stablebond_amount = best_stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS),
+            "Search complete"
         );
-
-        if best_quote.is_none() {
-            return Err(anyhow::anyhow!("No profitable trades found"));
+        let spread_bps = top_of_book_price.map(spread_bps_at);
+        if let (Some(spread_history), Some(spread_bps)) = (&self.spread_history, spread_bps) {
+            spread_history.record(stablebond_mint, self.name(), spread_bps);
         }
-        if best_profit < 1.0 {
-            return Err(anyhow::anyhow!(
-                "All trades were less than $1.00 USD profit"
-            ));
+        if let Some(exporter) = &self.exporter {
+            exporter.record_spread(&SpreadObservation {
+                mint: *stablebond_mint,
+                strategy: self.name(),
+                etherfuse_price: etherfuse_price_per_token,
+                oracle_price: md.oracle_price,
+                liquidity_usdc_amount: sell_liquidity_usdc_amount.to_ui_decimal(USDC_DECIMALS),
+                top_of_book_price,
+                spread_bps,
+                quotes_received,
+                best_usdc_amount: best_usdc_amount.to_ui_decimal(USDC_DECIMALS),
+                best_profit_usd: best_profit,
+            });
         }
-        let mut txs: Vec<VersionedTransaction> = Vec::new();
-        if let Ok(buy_on_jupiter_tx) = self
-            .jupiter_client
-            .jupiter_swap_tx(best_quote.unwrap())
+        if let Some(message) = ordinary {
+            return Err(skip(SkipReason::SpreadBelowThreshold, message));
+        }
+
+        check_search_result(
+            quotes_received,
+            best_quote.is_some(),
+            best_profit,
+            self.min_profit.get(),
+        )?;
+        let usdc_balance = check_trade_bounds(
+            &self.rpc_client,
+            &self.keypair_filepath,
+            best_usdc_amount,
+            best_stablebond_amount,
+            best_price,
+            etherfuse_price_per_token,
+            md.oracle_price_per_token(),
+        )
+        .await?;
+        let best_quote = best_quote.unwrap();
+        let quoted_at = best_quote.fetched_at;
+        let best_stablebond_ui_amount = best_stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS);
+        let mut legs = vec![
+            swap_leg(&best_quote, USDC_DECIMALS, STABLEBOND_DECIMALS),
+            TradeLeg {
+                venue: Venue::Etherfuse,
+                route: "instant_bond_redemption".to_string(),
+                input_mint: *stablebond_mint,
+                input_amount: best_stablebond_ui_amount,
+                output_mint: Pubkey::from_str(USDC_MINT)?,
+                expected_output_amount: best_stablebond_ui_amount * etherfuse_price_per_token,
+                price_impact: 0.0,
+            },
+        ];
+        let buy_on_jupiter_tx = self.swap_venue.swap_tx(best_quote).await.map_err(|e| {
+            skip(
+                SkipReason::BuildFailed,
+                format!("Error building swap transaction: {}", e),
+            )
+        })?;
+        let redeem_on_etherfuse_tx = self
+            .etherfuse_client
+            .redemption_tx(best_stablebond_amount, *stablebond_mint)
             .await
-        {
-            if let Ok(redeem_on_etherfuse_tx) = self
-                .etherfuse_client
-                .instant_bond_redemption_tx(best_stablebond_amount, stablebond_mint.clone())
-                .await
+            .map_err(|e| {
+                skip(
+                    SkipReason::BuildFailed,
+                    format!("Error building redemption transaction: {}", e),
+                )
+            })?;
+        let txs = vec![buy_on_jupiter_tx, redeem_on_etherfuse_tx];
+        // The Jupiter buy doesn't depend on the oracle update preceding it in the bundle.
+        if self.preflight {
+            preflight_first_leg(&self.rpc_client, &txs).await?;
+        }
+        if self.simulate_redemption {
+            if let Some(usdc_balance_after) =
+                simulate_usdc_balance(&self.transaction_cache, &self.keypair_filepath, md, &txs)
+                    .await?
             {
-                txs.push(buy_on_jupiter_tx);
-                txs.push(redeem_on_etherfuse_tx);
+                let (redeemed_usdc_amount, simulated_profit) =
+                    simulated_profit(usdc_balance, usdc_balance_after, best_usdc_amount, fees)?;
+                info!(
+                    nav_profit = %best_profit,
+                    simulated_profit = %simulated_profit,
+                    redeemed_usdc_amount = redeemed_usdc_amount.to_ui_amount(USDC_DECIMALS),
+                    "Simulated the redemption"
+                );
+                if simulated_profit < self.min_profit.get() {
+                    return Err(skip(
+                        SkipReason::SimulatedProfitBelowMinimum,
+                        format!(
+                            "Simulated profit of ${:.4} is less than ${:.2}, NAV estimate ${:.4}",
+                            simulated_profit,
+                            self.min_profit.get(),
+                            best_profit
+                        ),
+                    ));
+                }
+                best_profit = simulated_profit;
+                legs[1].expected_output_amount = redeemed_usdc_amount.to_ui_amount(USDC_DECIMALS);
             }
         }
-        return Ok(StrategyResult {
+        Ok(StrategyResult {
+            strategy: self.name(),
             profit: best_profit,
             txs,
-        });
+            stablebond_mint: *stablebond_mint,
+            direction: TradeDirection::BuyOnJupiterSellOnEtherfuse,
+            usdc_amount: best_usdc_amount,
+            capped: best_trade_percent >= MAX_TRADE_PERCENT
+                && uncapped_usdc_token_amount > max_usdc_token_amount_per_trade,
+            quoted_at,
+            legs,
+            preflight_skipped: None,
+        })
     }
 }
 
-impl Strategy for BuyOnEtherfuseSellOnJupiter {
+impl<Q: SwapVenue, V: BondVenue> Strategy for BuyOnEtherfuseSellOnJupiter<Q, V> {
+    fn name(&self) -> &'static str {
+        "BuyOnEtherfuseSellOnJupiter"
+    }
+
     async fn process_market_data(
         &mut self,
         md: &MarketData,
         stablebond_mint: &Pubkey,
     ) -> Result<StrategyResult> {
-        let usdc_holdings_token_amount = md
-            .usdc_holdings_token_amount
-            .ok_or_else(|| anyhow::anyhow!("Missing usdc_holdings_token_amount"))?;
-        let purchase_liquidity_stablebond_amount = md
-            .purchase_liquidity_stablebond_amount
-            .ok_or_else(|| anyhow::anyhow!("Missing purchase_liquidity_stablebond_amount"))?;
-        let etherfuse_price_per_token = md
-            .etherfuse_price_per_token
-            .ok_or_else(|| anyhow::anyhow!("Missing etherfuse_price_per_token"))?;
+        let usdc_holdings_token_amount = md.usdc_holdings_token_amount.ok_or_else(|| {
+            skip(
+                SkipReason::MarketDataUnavailable,
+                "Missing usdc_holdings_token_amount",
+            )
+        })?;
+        let purchase_liquidity_stablebond_amount =
+            md.purchase_liquidity_stablebond_amount.ok_or_else(|| {
+                skip(
+                    SkipReason::MarketDataUnavailable,
+                    "Missing purchase_liquidity_stablebond_amount",
+                )
+            })?;
+        let etherfuse_price_per_token = md.etherfuse_price_per_token.ok_or_else(|| {
+            skip(
+                SkipReason::MarketDataUnavailable,
+                "Missing etherfuse_price_per_token",
+            )
+        })?;
 
         if usdc_holdings_token_amount == 0 {
-            return Err(anyhow::anyhow!(
-                "USDC holdings are required for this strategy"
+            return Err(skip(
+                SkipReason::InsufficientBalance,
+                "USDC holdings are required for this strategy",
             ));
         }
         if purchase_liquidity_stablebond_amount == 0 {
-            return Err(anyhow::anyhow!(
-                "Stablebond purchase liquidity is required for this strategy"
+            return Err(skip(
+                SkipReason::NoLiquidity,
+                "Stablebond purchase liquidity is required for this strategy",
             ));
         }
 
-        let purchase_liquidity_ui_amount_ =
-            purchase_liquidity_stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS);
-        let max_usdc_to_purchase_ui_amount =
-            math::checked_float_mul(purchase_liquidity_ui_amount_, etherfuse_price_per_token)?.min(
-                usdc_holdings_token_amount
-                    .to_ui_amount(USDC_DECIMALS)
-                    .min(MAX_USDC_AMOUNT_PER_TRADE),
-            );
+        let etherfuse_price = math::to_decimal(etherfuse_price_per_token)?;
+        let purchase_liquidity_usdc_ui_amount = purchase_liquidity_stablebond_amount
+            .to_ui_decimal(STABLEBOND_DECIMALS)
+            .checked_mul(etherfuse_price)
+            .ok_or_else(|| anyhow!("Math overflow"))?;
+        let uncapped_usdc_ui_amount = purchase_liquidity_usdc_ui_amount
+            .min(usdc_holdings_token_amount.to_ui_decimal(USDC_DECIMALS));
+        let max_usdc_to_purchase_ui_amount = uncapped_usdc_ui_amount.min(MAX_USDC_AMOUNT_PER_TRADE);
         let max_usdc_to_purchase_token_amount =
-            max_usdc_to_purchase_ui_amount.to_token_amount(STABLEBOND_DECIMALS);
+            max_usdc_to_purchase_ui_amount.to_token_amount_floor(USDC_DECIMALS)?;
 
-        let mut best_profit: f64 = 0.0;
+        let fees = bundle_fees(md)?;
+        let mut best_profit = Decimal::ZERO;
         let mut best_usdc_amount = 0;
         let mut best_stablebond_amount = 0;
-        let mut best_quote: Option<Quote> = None;
+        let mut best_quote: Option<SwapQuote> = None;
+        let mut best_price = 0.0;
+        let mut best_trade_percent = Decimal::ZERO;
+        let mut quotes_received = 0;
+        let mut top_of_book_price: Option<f64> = None;
+        let mut ordinary = None;
+        let spread_bps_at =
+            |price: f64| (price - etherfuse_price_per_token) / etherfuse_price_per_token * 10_000.0;
 
         let max_amount = max_usdc_to_purchase_token_amount;
 
-        // Generate initial test points with exponential distribution
-        let points: Vec<f64> = (0..INITIAL_POINTS)
-            .map(|i| {
-                let t = i as f64 / (INITIAL_POINTS - 1) as f64;
-                let exp_t = t.powf(1.5); // Exponential distribution
-                MIN_TRADE_PERCENT + (MAX_TRADE_PERCENT - MIN_TRADE_PERCENT) * exp_t
-            })
-            .collect();
-
         // Test each trade size
-        for trade_percent in points {
-            let usdc_amount = (max_amount as f64 * trade_percent) as u64;
-            let stablebond_amount = (usdc_amount as f64 / etherfuse_price_per_token) as u64;
+        for trade_percent in candidate_trade_sizes(
+            md,
+            TradeDirection::BuyOnEtherfuseSellOnJupiter,
+            max_amount,
+            etherfuse_price,
+        )? {
+            let usdc_amount = usdc_amount_at_size(max_amount, trade_percent)?;
+            let stablebond_amount = stablebond_amount_at(usdc_amount, etherfuse_price)?;
 
             // Skip tiny amounts
             if usdc_amount < MIN_USDC_AMOUNT {
                 continue;
             }
 
-            // Get quote with retries
-            let mut retries = 0;
-            let quote_result = loop {
-                match self
-                    .jupiter_client
-                    .sell_quote(stablebond_mint, stablebond_amount)
-                    .await
-                {
-                    Ok(quote) => break Some(quote),
-                    Err(e) => {
-                        retries += 1;
-                        if retries >= MAX_RETRIES {
-                            println!("Failed to get quote after {} retries: {}", MAX_RETRIES, e);
-                            break None;
-                        }
-                        println!("Retry {}/{}: {}", retries, MAX_RETRIES, e);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_DELAY_MS))
-                            .await;
-                    }
-                }
-            };
+            let quote_result = quote_with_retries(
+                &mut self.swap_venue,
+                TradeDirection::BuyOnEtherfuseSellOnJupiter,
+                stablebond_mint,
+                stablebond_amount,
+            )
+            .await;
 
             let (price_per_token_when_selling, sell_quote) = match quote_result {
                 Some(quote) => quote,
                 None => continue,
             };
+            quotes_received += 1;
+            top_of_book_price.get_or_insert(price_per_token_when_selling);
+            if quotes_received == 1 {
+                ordinary = ordinary_spread(
+                    self.spread_history.as_ref(),
+                    stablebond_mint,
+                    self.name(),
+                    spread_bps_at(price_per_token_when_selling),
+                );
+                if ordinary.is_some() {
+                    break;
+                }
+            }
 
             // Calculate price impact (note the reversed order for selling)
             let price_impact = (etherfuse_price_per_token - price_per_token_when_selling)
                 / etherfuse_price_per_token;
 
-            let potential_profit = match math::profit_from_arb(
-                price_per_token_when_selling,
-                etherfuse_price_per_token,
-                stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS),
-            ) {
-                Ok(profit) => profit - md.jito_tip_usd_price.unwrap_or(0.10),
+            let token_amount = stablebond_amount.to_ui_decimal(STABLEBOND_DECIMALS);
+            let profit = math::to_decimal(price_per_token_when_selling).and_then(|price| {
+                math::net_profit(
+                    Leg::new(etherfuse_price, token_amount),
+                    Leg::new(price, token_amount),
+                    fees,
+                )
+            });
+            let potential_profit = match profit {
+                Ok(profit) => profit,
                 Err(e) => {
-                    println!("Error calculating profit: {}. Skipping.", e);
+                    warn!("Error calculating profit: {}. Skipping.", e);
                     continue;
                 }
             };
 
-            println!("\nTrade Analysis for BuyOnEtherfuseSellOnJupiter:");
-            println!("Trade Size: {}% of max", trade_percent * 100.0);
-            println!("USDC Amount: {}", usdc_amount.to_ui_amount(USDC_DECIMALS));
-            println!("Price Impact: {:.2}%", price_impact * 100.0);
-            println!(
-                "Jito tip usd price: {}",
-                md.jito_tip_usd_price.unwrap_or(0.10)
+            debug!(
+                trade_percent = %(trade_percent * Decimal::ONE_HUNDRED),
+                usdc_amount = usdc_amount.to_ui_amount(USDC_DECIMALS),
+                price_impact = price_impact * 100.0,
+                jito_tip_usd = %fees.tip_usd,
+                potential_profit = %potential_profit,
+                etherfuse_buy_price = etherfuse_price_per_token,
+                jupiter_sell_price = price_per_token_when_selling,
+                "Trade analysis"
             );
-            println!("Potential Profit: {}", potential_profit);
-            println!("Buy price on etherfuse: {}", etherfuse_price_per_token);
-            println!("Sell price on jupiter: {}", price_per_token_when_selling);
-            println!("Stablebond: {:?}", stablebond_mint);
+            if let Some(exporter) = &self.exporter {
+                exporter.record_quote(&QuoteObservation {
+                    mint: *stablebond_mint,
+                    strategy: self.name(),
+                    trade_percent,
+                    usdc_amount: usdc_amount.to_ui_decimal(USDC_DECIMALS),
+                    stablebond_amount: stablebond_amount.to_ui_decimal(STABLEBOND_DECIMALS),
+                    jupiter_price: price_per_token_when_selling,
+                    etherfuse_price: etherfuse_price_per_token,
+                    price_impact,
+                    potential_profit_usd: potential_profit,
+                    route: sell_quote.route.clone(),
+                    context_slot: sell_quote.context_slot,
+                });
+            }
 
             if potential_profit > best_profit {
-                println!("\n🎯 New best trade found!");
-                println!("Previous best profit: {}", best_profit);
-                println!("New best profit: {}", potential_profit);
+                debug!(
+                    previous_best_profit = %best_profit,
+                    best_profit = %potential_profit,
+                    "New best trade found"
+                );
 
                 best_profit = potential_profit;
                 best_usdc_amount = usdc_amount;
                 best_stablebond_amount = stablebond_amount;
+                best_price = price_per_token_when_selling;
+                best_trade_percent = trade_percent;
                 best_quote = Some(sell_quote);
             }
         }
 
-        println!("\n🏁 Search Complete");
-        println!("Final best profit: {}", best_profit);
-        println!("Final USDC amount: {}", best_usdc_amount);
-        println!("Final Stablebond amount: {}", best_stablebond_amount);
-
-        if best_quote.is_none() {
-            return Err(anyhow::anyhow!("No profitable trades found"));
+        info!(
+            best_profit = %best_profit,
+            usdc_amount = best_usdc_amount.to_ui_amount(USDC_DECIMALS),
+            stablebond_amount = best_stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS),
+            "Search complete"
+        );
+        let spread_bps = top_of_book_price.map(spread_bps_at);
+        if let (Some(spread_history), Some(spread_bps)) = (&self.spread_history, spread_bps) {
+            spread_history.record(stablebond_mint, self.name(), spread_bps);
         }
-
-        if best_profit < 1.0 {
-            return Err(anyhow::anyhow!(
-                "All trades were less than $1.00 USD profit"
-            ));
+        if let Some(exporter) = &self.exporter {
+            exporter.record_spread(&SpreadObservation {
+                mint: *stablebond_mint,
+                strategy: self.name(),
+                etherfuse_price: etherfuse_price_per_token,
+                oracle_price: md.oracle_price,
+                liquidity_usdc_amount: purchase_liquidity_usdc_ui_amount,
+                top_of_book_price,
+                spread_bps,
+                quotes_received,
+                best_usdc_amount: best_usdc_amount.to_ui_decimal(USDC_DECIMALS),
+                best_profit_usd: best_profit,
+            });
+        }
+        if let Some(message) = ordinary {
+            return Err(skip(SkipReason::SpreadBelowThreshold, message));
         }
-        let mut txs: Vec<VersionedTransaction> = Vec::new();
-        if let Ok(buy_on_etherfuse_tx) = self
+
+        check_search_result(
+            quotes_received,
+            best_quote.is_some(),
+            best_profit,
+            self.min_profit.get(),
+        )?;
+        check_trade_bounds(
+            &self.rpc_client,
+            &self.keypair_filepath,
+            best_usdc_amount,
+            best_stablebond_amount,
+            best_price,
+            etherfuse_price_per_token,
+            md.oracle_price_per_token(),
+        )
+        .await?;
+        let best_quote = best_quote.unwrap();
+        let quoted_at = best_quote.fetched_at;
+        let best_usdc_ui_amount = best_usdc_amount.to_ui_amount(USDC_DECIMALS);
+        let legs = vec![
+            TradeLeg {
+                venue: Venue::Etherfuse,
+                route: "purchase_bond".to_string(),
+                input_mint: Pubkey::from_str(USDC_MINT)?,
+                input_amount: best_usdc_ui_amount,
+                output_mint: *stablebond_mint,
+                expected_output_amount: best_usdc_ui_amount / etherfuse_price_per_token,
+                price_impact: 0.0,
+            },
+            swap_leg(&best_quote, STABLEBOND_DECIMALS, USDC_DECIMALS),
+        ];
+        let buy_on_etherfuse_tx = self
             .etherfuse_client
-            .purchase_tx(best_usdc_amount, stablebond_mint.clone())
+            .purchase_tx(best_usdc_amount, *stablebond_mint)
             .await
-        {
-            if let Ok(sell_on_jupiter_tx) = self
-                .jupiter_client
-                .jupiter_swap_tx(best_quote.unwrap())
-                .await
-            {
-                txs.push(buy_on_etherfuse_tx);
-                txs.push(sell_on_jupiter_tx);
+            .map_err(|e| {
+                skip(
+                    SkipReason::BuildFailed,
+                    format!("Error building purchase transaction: {}", e),
+                )
+            })?;
+        let sell_on_jupiter_tx = self.swap_venue.swap_tx(best_quote).await.map_err(|e| {
+            skip(
+                SkipReason::BuildFailed,
+                format!("Error building swap transaction: {}", e),
+            )
+        })?;
+        let txs = vec![buy_on_etherfuse_tx, sell_on_jupiter_tx];
+        let mut preflight_skipped = None;
+        if self.preflight {
+            if md.switchboard_update_txs.is_empty() {
+                preflight_first_leg(&self.rpc_client, &txs).await?;
+            } else {
+                // The Etherfuse purchase fails in isolation while it waits on the oracle update.
+                preflight_skipped = preflight_after_oracle_update(
+                    &self.transaction_cache,
+                    &self.keypair_filepath,
+                    md,
+                    &txs,
+                )
+                .await?;
             }
         }
-        return Ok(StrategyResult {
+        Ok(StrategyResult {
+            strategy: self.name(),
             profit: best_profit,
             txs,
-        });
+            stablebond_mint: *stablebond_mint,
+            direction: TradeDirection::BuyOnEtherfuseSellOnJupiter,
+            usdc_amount: best_usdc_amount,
+            capped: best_trade_percent >= MAX_TRADE_PERCENT
+                && uncapped_usdc_ui_amount > MAX_USDC_AMOUNT_PER_TRADE,
+            quoted_at,
+            legs,
+            preflight_skipped,
+        })
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TradeDirection {
+    BuyOnEtherfuseSellOnJupiter,
+    BuyOnJupiterSellOnEtherfuse,
+}
+
 #[derive(Clone)]
 pub struct StrategyResult {
-    pub profit: f64,
+    pub strategy: &'static str,
+    pub profit: Decimal,
     pub txs: Vec<VersionedTransaction>,
+    pub stablebond_mint: Pubkey,
+    pub direction: TradeDirection,
+    pub usdc_amount: u64,
+    /// Whether the trade is as large as `MAX_USDC_AMOUNT_PER_TRADE` allows while the liquidity
+    /// and balances would take more, so a larger trade is worth splitting into chunks.
+    pub capped: bool,
+    /// When the winning Jupiter quote was received.
+    pub quoted_at: Instant,
+    pub legs: Vec<TradeLeg>,
+    /// Why the trade went unsimulated although preflight is on.
+    pub preflight_skipped: Option<String>,
 }
 
 impl std::fmt::Debug for StrategyResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Profit: {}, Tx Count: {}", self.profit, self.txs.len())
+        write!(
+            f,
+            "Profit: {}, Tx Count: {}, Direction: {:?}",
+            self.profit,
+            self.txs.len(),
+            self.direction
+        )
     }
 }
 
+/// Trade sizes to quote, as fractions of the most the trade can take, denser towards the small
+/// end where the price impact is lowest.
+fn trade_size_points() -> Vec<Decimal> {
+    (0..INITIAL_POINTS)
+        .map(|i| {
+            let t = i as f64 / (INITIAL_POINTS - 1) as f64;
+            // Exponential distribution, in whole bps of the range.
+            let exp_t = math::to_decimal(t.powf(1.5))
+                .unwrap_or_default()
+                .round_dp(4);
+            MIN_TRADE_PERCENT + (MAX_TRADE_PERCENT - MIN_TRADE_PERCENT) * exp_t
+        })
+        .collect()
+}
+
+/// USDC amount of a trade of `trade_percent` of `max_usdc_amount`, rounded down.
+fn usdc_amount_at_size(max_usdc_amount: u64, trade_percent: Decimal) -> Result<u64> {
+    max_usdc_amount
+        .to_ui_decimal(USDC_DECIMALS)
+        .checked_mul(trade_percent)
+        .ok_or_else(|| anyhow!("Math overflow"))?
+        .to_token_amount_floor(USDC_DECIMALS)
+}
+
+/// Stablebonds `usdc_amount` buys at `price`, rounded down.
+fn stablebond_amount_at(usdc_amount: u64, price: Decimal) -> Result<u64> {
+    usdc_amount
+        .to_ui_decimal(USDC_DECIMALS)
+        .checked_div(price)
+        .ok_or_else(|| anyhow!("Math overflow"))?
+        .to_token_amount_floor(STABLEBOND_DECIMALS)
+}
+
+/// Trade sizes to quote on the swap venues: all of `trade_size_points`, or when the mint's pool
+/// state is known, only the one it prices best, saving `INITIAL_POINTS - 1` quotes per search.
+/// The fees are the same at every size, so the best size is the one with the widest spread.
+fn candidate_trade_sizes(
+    md: &MarketData,
+    direction: TradeDirection,
+    max_usdc_amount: u64,
+    etherfuse_price: Decimal,
+) -> Result<Vec<Decimal>> {
+    let Some(pool_state) = &md.pool_state else {
+        return Ok(trade_size_points());
+    };
+    let mut best: Option<(Decimal, Decimal)> = None;
+    for trade_percent in trade_size_points() {
+        let usdc_amount = usdc_amount_at_size(max_usdc_amount, trade_percent)?;
+        if usdc_amount < MIN_USDC_AMOUNT {
+            continue;
+        }
+        let stablebond_amount = stablebond_amount_at(usdc_amount, etherfuse_price)?;
+        let pool_price = match direction {
+            TradeDirection::BuyOnJupiterSellOnEtherfuse => pool_state.buy_price(usdc_amount),
+            TradeDirection::BuyOnEtherfuseSellOnJupiter => pool_state.sell_price(stablebond_amount),
+        };
+        let Some(Ok(pool_price)) = pool_price.map(math::to_decimal) else {
+            continue;
+        };
+        let spread = match direction {
+            TradeDirection::BuyOnJupiterSellOnEtherfuse => etherfuse_price - pool_price,
+            TradeDirection::BuyOnEtherfuseSellOnJupiter => pool_price - etherfuse_price,
+        };
+        let gross_profit = spread * stablebond_amount.to_ui_decimal(STABLEBOND_DECIMALS);
+        if best.map_or(true, |(_, best_profit)| gross_profit > best_profit) {
+            best = Some((trade_percent, gross_profit));
+        }
+    }
+    match best {
+        Some((trade_percent, gross_profit)) => {
+            debug!(
+                trade_percent = %(trade_percent * Decimal::ONE_HUNDRED),
+                %gross_profit,
+                pool_slot = pool_state.slot,
+                "Pre-quoted best size on the pool"
+            );
+            Ok(vec![trade_percent])
+        }
+        None => Ok(trade_size_points()),
+    }
+}
+
+/// Costs of a trade's bundle besides the prices of its legs: the Jito tip and the fees of its
+/// transactions. Trades aren't priced without them.
+fn bundle_fees(md: &MarketData) -> Result<FeeBreakdown> {
+    md.cost_model
+        .as_ref()
+        .map(CostModel::fees)
+        .ok_or_else(|| anyhow!("No cost model, the SOL price is unknown"))
+}
+
+/// Jupiter quote for the leg of `direction` that trades on Jupiter: buying the stablebond with
+/// `amount` of USDC, or selling `amount` of it. Retries failed requests, and gives up with
+/// `None` after `MAX_RETRIES`.
+async fn quote_with_retries(
+    swap_venue: &mut impl SwapVenue,
+    direction: TradeDirection,
+    stablebond_mint: &Pubkey,
+    amount: u64,
+) -> Option<(f64, SwapQuote)> {
+    let mut retries = 0;
+    loop {
+        let result = match direction {
+            TradeDirection::BuyOnJupiterSellOnEtherfuse => {
+                swap_venue.buy_quote(stablebond_mint, amount).await
+            }
+            TradeDirection::BuyOnEtherfuseSellOnJupiter => {
+                swap_venue.sell_quote(stablebond_mint, amount).await
+            }
+        };
+        match result {
+            Ok(quote) => return Some(quote),
+            // Logged once per tick by the budget.
+            Err(ArbError::Quote(QuoteError::BudgetExhausted(_))) => return None,
+            Err(e) if !e.is_transient() => {
+                // e.g. no route for the amount, which retrying won't change.
+                warn!("Quote rejected: {}", e);
+                return None;
+            }
+            Err(e) => {
+                retries += 1;
+                if retries >= MAX_RETRIES {
+                    warn!("Failed to get quote after {} retries: {}", MAX_RETRIES, e);
+                    return None;
+                }
+                warn!("Retry {}/{}: {}", retries, MAX_RETRIES, e);
+                tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_DELAY_MS)).await;
+            }
+        }
+    }
+}
+
+/// Skip message when the top-of-book spread is too ordinary to search the trade sizes for: fewer
+/// than the entry z-score's standard deviations above the mint's usual spread in this direction.
+/// Nothing without an entry z-score, or until the spread has enough history.
+fn ordinary_spread(
+    spread_history: Option<&SpreadHistory>,
+    stablebond_mint: &Pubkey,
+    strategy: &'static str,
+    spread_bps: f64,
+) -> Option<String> {
+    let spread_history = spread_history?;
+    let entry_z_score = spread_history.entry_z_score?;
+    let z_score = spread_history.z_score(stablebond_mint, strategy, spread_bps)?;
+    (z_score < entry_z_score).then(|| {
+        format!(
+            "Spread of {:.1} bps is {:.2} standard deviations from its mean, under {}",
+            spread_bps, z_score, entry_z_score
+        )
+    })
+}
+
+/// Skips the trade when the search over trade sizes got no quote, found no profitable size or
+/// found none above the minimum profit.
+fn check_search_result(
+    quotes_received: u32,
+    found: bool,
+    best_profit: Decimal,
+    min_profit: Decimal,
+) -> Result<()> {
+    if quotes_received == 0 {
+        return Err(skip(SkipReason::QuoteFailed, "No Jupiter quotes received"));
+    }
+    if !found {
+        return Err(skip(
+            SkipReason::SpreadBelowThreshold,
+            "No profitable trades found",
+        ));
+    }
+    if best_profit < min_profit {
+        return Err(skip(
+            SkipReason::ProfitBelowMinimum,
+            format!("All trades were less than ${:.2} USD profit", min_profit),
+        ));
+    }
+    Ok(())
+}
+
+/// Simulates the first leg of the trade. Later legs spend what the first leg buys, so they
+/// can't be simulated on their own.
+async fn preflight_first_leg(rpc_client: &RpcClient, txs: &[VersionedTransaction]) -> Result<()> {
+    if let Some(tx) = txs.first() {
+        simulate_tx(rpc_client, tx).await.map_err(|e| {
+            skip(
+                SkipReason::PreflightFailed,
+                format!("Preflight failed: {}", e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Simulates the oracle updates and the legs of the trade composed into one transaction, since
+/// the Etherfuse purchase waits on the oracle update and the later legs spend what it buys.
+/// Falls back to the first leg alone when the whole trade doesn't fit in one transaction, and
+/// returns why nothing was simulated when even that doesn't fit.
+async fn preflight_after_oracle_update(
+    transaction_cache: &TransactionCache,
+    keypair_filepath: &str,
+    md: &MarketData,
+    txs: &[VersionedTransaction],
+) -> Result<Option<String>> {
+    if txs.is_empty() {
+        return Ok(None);
+    }
+    let keypair = read_keypair_file(keypair_filepath)
+        .map_err(|e| anyhow!("No keypair found at {}: {}", keypair_filepath, e))?;
+    for legs in [txs, &txs[..1]] {
+        let composed: Vec<VersionedTransaction> = md
+            .switchboard_update_txs
+            .iter()
+            .chain(legs)
+            .cloned()
+            .collect();
+        if let Some(tx) = compose_atomic_tx(transaction_cache, &keypair, &composed)? {
+            simulate_tx(&transaction_cache.rpc_client, &tx)
+                .await
+                .map_err(|e| {
+                    skip(
+                        SkipReason::PreflightFailed,
+                        format!("Preflight failed: {}", e),
+                    )
+                })?;
+            return Ok(None);
+        }
+    }
+    let reason = "Preflight skipped: the oracle update and the first leg don't fit in one \
+        transaction";
+    warn!("{}", reason);
+    Ok(Some(reason.to_string()))
+}
+
+/// USDC balance the wallet is left with once the oracle updates and `txs` run, simulated as
+/// one transaction since the redemption spends what the swap buys. `None` when they don't fit in
+/// one.
+async fn simulate_usdc_balance(
+    transaction_cache: &TransactionCache,
+    keypair_filepath: &str,
+    md: &MarketData,
+    txs: &[VersionedTransaction],
+) -> Result<Option<u64>> {
+    let keypair = read_keypair_file(keypair_filepath)
+        .map_err(|e| anyhow!("No keypair found at {}: {}", keypair_filepath, e))?;
+    let txs: Vec<VersionedTransaction> = md
+        .switchboard_update_txs
+        .iter()
+        .chain(txs)
+        .cloned()
+        .collect();
+    let Some(tx) = compose_atomic_tx(transaction_cache, &keypair, &txs)? else {
+        debug!("Trade doesn't fit in one transaction, pricing it at NAV");
+        return Ok(None);
+    };
+    let usdc_account =
+        get_associated_token_address(&keypair.pubkey(), &Pubkey::from_str(USDC_MINT)?);
+    simulate_token_balance(&transaction_cache.rpc_client, &tx, &usdc_account)
+        .await
+        .map(Some)
+        .map_err(|e| {
+            skip(
+                SkipReason::PreflightFailed,
+                format!("Redemption simulation failed: {}", e),
+            )
+        })
+}
+
+/// USDC the redemption paid out, and the profit net of the tip and gas, from the USDC balance
+/// before and after the simulated trade that spent `usdc_amount` on the swap. Venue fees are in
+/// the balance already.
+fn simulated_profit(
+    balance_before: u64,
+    balance_after: u64,
+    usdc_amount: u64,
+    fees: FeeBreakdown,
+) -> Result<(u64, Decimal)> {
+    let redeemed_usdc_amount = (balance_after + usdc_amount).saturating_sub(balance_before);
+    let gross_profit = Decimal::new(
+        balance_after as i64 - balance_before as i64,
+        USDC_DECIMALS as u32,
+    );
+    let profit = gross_profit
+        .checked_sub(fees.tip_usd)
+        .and_then(|profit| profit.checked_sub(fees.gas_usd))
+        .ok_or_else(|| anyhow!("Math overflow"))?;
+    Ok((redeemed_usdc_amount, profit))
+}
+
+/// Invariant a trade broke, found right before its transactions are built.
+#[derive(Debug, thiserror::Error)]
+pub enum TradeBoundsError {
+    #[error("Trade amount is zero")]
+    ZeroAmount,
+    #[error("Trade of {amount} USDC exceeds the per-trade cap of {cap} USDC")]
+    AboveCap { amount: Decimal, cap: Decimal },
+    #[error("Trade of {amount} USDC exceeds the USDC balance of {balance}")]
+    AboveBalance { amount: Decimal, balance: Decimal },
+    #[error(
+        "Implied price {price} is more than {max_deviation_bps} bps away from the {reference} \
+        price {reference_price}"
+    )]
+    PriceOutOfBounds {
+        price: f64,
+        reference: &'static str,
+        reference_price: f64,
+        max_deviation_bps: u64,
+    },
+}
+
+/// Checks the trade's amounts against the per-trade cap and a fresh read of our USDC balance,
+/// and its Jupiter price against the price at the oracle's exchange rate, or NAV without one,
+/// so that a bug or bad market data aborts the trade instead of sending a doomed or dangerous
+/// bundle. Returns the balance read.
+async fn check_trade_bounds(
+    rpc_client: &RpcClient,
+    keypair_filepath: &str,
+    usdc_amount: u64,
+    stablebond_amount: u64,
+    implied_price: f64,
+    nav: f64,
+    oracle_price: Option<f64>,
+) -> Result<u64> {
+    if usdc_amount == 0 || stablebond_amount == 0 {
+        return Err(TradeBoundsError::ZeroAmount.into());
+    }
+    let usdc_ui_amount = usdc_amount.to_ui_decimal(USDC_DECIMALS);
+    if usdc_ui_amount > MAX_USDC_AMOUNT_PER_TRADE {
+        return Err(TradeBoundsError::AboveCap {
+            amount: usdc_ui_amount,
+            cap: MAX_USDC_AMOUNT_PER_TRADE,
+        }
+        .into());
+    }
+    let (reference, reference_price) = match oracle_price {
+        Some(oracle_price) => ("oracle", oracle_price),
+        None => ("NAV", nav),
+    };
+    let deviation_bps = (implied_price - reference_price).abs() / reference_price * 10_000.0;
+    if !deviation_bps.is_finite() || deviation_bps > MAX_IMPLIED_PRICE_DEVIATION_BPS as f64 {
+        return Err(TradeBoundsError::PriceOutOfBounds {
+            price: implied_price,
+            reference,
+            reference_price,
+            max_deviation_bps: MAX_IMPLIED_PRICE_DEVIATION_BPS,
+        }
+        .into());
+    }
+    let wallet = read_keypair_file(keypair_filepath)
+        .map_err(|e| anyhow!("No keypair found at {}: {}", keypair_filepath, e))?
+        .pubkey();
+    let usdc_account = get_associated_token_address(&wallet, &Pubkey::from_str(USDC_MINT)?);
+    let balance: u64 = rpc_client
+        .get_token_account_balance(&usdc_account)
+        .await?
+        .amount
+        .parse()?;
+    if usdc_amount > balance {
+        return Err(TradeBoundsError::AboveBalance {
+            amount: usdc_ui_amount,
+            balance: balance.to_ui_decimal(USDC_DECIMALS),
+        }
+        .into());
+    }
+    Ok(balance)
+}
+
 fn adjust_amount_for_slippage(amount: u64, bips: u64) -> Result<u64> {
     let subtraction =
         math::checked_mul(amount, bips).and_then(|product| math::checked_div(product, 10000))?;
     math::checked_sub(amount, subtraction)
 }
+
+/// The secondary market swap of a trade, priced at the quote's output amount.
+fn swap_leg(quote: &SwapQuote, input_decimals: u8, output_decimals: u8) -> TradeLeg {
+    TradeLeg {
+        venue: quote.venue,
+        route: quote.route.clone(),
+        input_mint: quote.input_mint,
+        input_amount: quote.in_amount.to_ui_amount(input_decimals),
+        output_mint: quote.output_mint,
+        expected_output_amount: quote.out_amount.to_ui_amount(output_decimals),
+        price_impact: quote.price_impact_pct,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{tx_label, MockBondVenue, MockQuote, MockSwapVenue, QuoteSide, TempDir};
+    use crate::pool_state::{PoolCurve, PoolState};
+    use serde_json::json;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::signature::Keypair;
+    use std::collections::HashMap;
+
+    const NAV: f64 = 1.0;
+    const USDC_BALANCE: u64 = 10_000_000_000;
+
+    fn stablebond_mint() -> Pubkey {
+        Pubkey::new_from_array([7; 32])
+    }
+
+    fn market_data() -> MarketData {
+        MarketData {
+            etherfuse_price_per_token: Some(NAV),
+            sell_liquidity_usdc_amount: Some(100_000_000_000),
+            stablebond_holdings_token_amount: Some(0),
+            purchase_liquidity_stablebond_amount: Some(100_000_000_000),
+            usdc_holdings_token_amount: Some(USDC_BALANCE),
+            jito_tip: Some(1_000_000),
+            sol_price: Some(100.0),
+            cost_model: Some(CostModel::new(100.0, 1_000_000).unwrap()),
+            switchboard_update_txs: Vec::new(),
+            switchboard_updated_feeds: Vec::new(),
+            switchboard_update_error: None,
+            etherfuse_exchange_rate: None,
+            oracle_price: None,
+            oracle_age_slots: None,
+            pool_state: None,
+        }
+    }
+
+    /// RPC answering the fresh USDC balance read of `check_trade_bounds`.
+    fn rpc_client() -> Arc<RpcClient> {
+        let mut mocks = HashMap::new();
+        mocks.insert(
+            RpcRequest::GetTokenAccountBalance,
+            json!({
+                "context": { "slot": 1 },
+                "value": {
+                    "amount": USDC_BALANCE.to_string(),
+                    "decimals": USDC_DECIMALS,
+                    "uiAmount": USDC_BALANCE.to_ui_amount(USDC_DECIMALS),
+                    "uiAmountString": USDC_BALANCE.to_ui_amount(USDC_DECIMALS).to_string(),
+                },
+            }),
+        );
+        Arc::new(RpcClient::new_mock_with_mocks(
+            "succeeds".to_string(),
+            mocks,
+        ))
+    }
+
+    /// The strategy, along with the directory of its keypair file to keep until it's done.
+    fn buy_on_jupiter(
+        quotes: MockSwapVenue,
+        venue: MockBondVenue,
+    ) -> (
+        BuyOnJupiterSellOnEtherfuse<MockSwapVenue, MockBondVenue>,
+        TempDir,
+    ) {
+        let temp_dir = TempDir::create("strategy-test");
+        let strategy = BuyOnJupiterSellOnEtherfuse::new(
+            rpc_client(),
+            quotes,
+            temp_dir.keypair_file(&Keypair::new()),
+            venue,
+        );
+        (strategy, temp_dir)
+    }
+
+    fn buy_on_etherfuse(
+        quotes: MockSwapVenue,
+        venue: MockBondVenue,
+    ) -> (
+        BuyOnEtherfuseSellOnJupiter<MockSwapVenue, MockBondVenue>,
+        TempDir,
+    ) {
+        let temp_dir = TempDir::create("strategy-test");
+        let strategy = BuyOnEtherfuseSellOnJupiter::new(
+            rpc_client(),
+            quotes,
+            temp_dir.keypair_file(&Keypair::new()),
+            venue,
+        );
+        (strategy, temp_dir)
+    }
+
+    /// Most USDC a trade takes, given the per-trade cap binds.
+    fn max_usdc_amount() -> u64 {
+        MAX_USDC_AMOUNT_PER_TRADE.to_token_amount(USDC_DECIMALS)
+    }
+
+    /// USDC amount the size search quotes at `point` of `max_usdc_amount`.
+    fn usdc_amount_at(point: Decimal) -> u64 {
+        usdc_amount_at_size(max_usdc_amount(), point).unwrap()
+    }
+
+    /// Jupiter ask rising by 1 bp per USDC bought from 95 cents, so the profit peaks at 250 USDC.
+    fn rising_ask(_: QuoteSide, usdc_amount: u64) -> f64 {
+        0.95 + 0.0001 * usdc_amount.to_ui_amount(USDC_DECIMALS)
+    }
+
+    /// Jupiter bid falling by 1 bp per token sold from 1.05, so the profit peaks at 250 tokens.
+    fn falling_bid(_: QuoteSide, stablebond_amount: u64) -> f64 {
+        1.05 - 0.0001 * stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS)
+    }
+
+    fn skip_reason(result: Result<StrategyResult>) -> SkipReason {
+        SkipReason::of(&result.unwrap_err())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn buy_on_jupiter_picks_the_most_profitable_size() {
+        let quotes = MockSwapVenue::new(0.0).with_price_curve(rising_ask);
+        let venue = MockBondVenue::new(NAV);
+        let (mut strategy, _temp_dir) = buy_on_jupiter(quotes.clone(), venue.clone());
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await
+            .unwrap();
+
+        // Of the sizes tried, 28.8% of 1000 USDC is the closest to the 250 USDC peak.
+        let usdc_amount = usdc_amount_at(trade_size_points()[3]);
+        let stablebond_amount = (usdc_amount.to_ui_amount(USDC_DECIMALS) / NAV)
+            .to_token_amount_floor(STABLEBOND_DECIMALS)
+            .unwrap();
+        let price = rising_ask(QuoteSide::Buy, usdc_amount);
+        // Less the Jito tip and the base fee of two transactions at 100 USD per SOL.
+        let profit =
+            (NAV - price) * stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS) - 0.10 - 0.001;
+        assert_eq!(result.usdc_amount, usdc_amount);
+        assert!((math::to_f64(result.profit) - profit).abs() < 1e-9);
+        assert_eq!(
+            result.direction,
+            TradeDirection::BuyOnJupiterSellOnEtherfuse
+        );
+        assert_eq!(quotes.requests().len(), INITIAL_POINTS);
+        assert_eq!(quotes.swaps()[0].in_amount, usdc_amount);
+        assert_eq!(venue.redemptions(), vec![stablebond_amount]);
+        let labels: Vec<_> = result.txs.iter().filter_map(tx_label).collect();
+        assert_eq!(labels, ["jupiter_swap", "etherfuse_redemption"]);
+        assert_eq!(result.legs[0].venue, Venue::Jupiter);
+        assert_eq!(result.legs[1].venue, Venue::Etherfuse);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn buy_on_etherfuse_picks_the_most_profitable_size() {
+        let quotes = MockSwapVenue::new(0.0).with_price_curve(falling_bid);
+        let venue = MockBondVenue::new(NAV);
+        let (mut strategy, _temp_dir) = buy_on_etherfuse(quotes.clone(), venue.clone());
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await
+            .unwrap();
+
+        let usdc_amount = usdc_amount_at(trade_size_points()[3]);
+        let stablebond_amount = (usdc_amount.to_ui_amount(USDC_DECIMALS) / NAV)
+            .to_token_amount_floor(STABLEBOND_DECIMALS)
+            .unwrap();
+        let price = falling_bid(QuoteSide::Sell, stablebond_amount);
+        let profit =
+            (price - NAV) * stablebond_amount.to_ui_amount(STABLEBOND_DECIMALS) - 0.10 - 0.001;
+        assert_eq!(result.usdc_amount, usdc_amount);
+        assert!((math::to_f64(result.profit) - profit).abs() < 1e-9);
+        assert_eq!(
+            result.direction,
+            TradeDirection::BuyOnEtherfuseSellOnJupiter
+        );
+        assert!(quotes
+            .requests()
+            .iter()
+            .all(|(side, _)| *side == QuoteSide::Sell));
+        assert_eq!(quotes.swaps()[0].in_amount, stablebond_amount);
+        assert_eq!(venue.purchases(), vec![usdc_amount]);
+        let labels: Vec<_> = result.txs.iter().filter_map(tx_label).collect();
+        assert_eq!(labels, ["etherfuse_purchase", "jupiter_swap"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_quotes_are_retried_then_the_size_is_skipped() {
+        let error = || MockQuote::Error("429 Too Many Requests".to_string());
+        // The smallest size fails once then succeeds, the next fails every retry.
+        let quotes = MockSwapVenue::new(0.0)
+            .with_price_curve(rising_ask)
+            .with_script([error(), MockQuote::Price(0.95), error(), error(), error()]);
+        let (mut strategy, _temp_dir) = buy_on_jupiter(quotes.clone(), MockBondVenue::new(NAV));
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await
+            .unwrap();
+
+        let requests = quotes.requests();
+        let amounts: Vec<u64> = trade_size_points()
+            .iter()
+            .map(|point| usdc_amount_at(*point))
+            .collect();
+        assert_eq!(
+            requests.len(),
+            2 + MAX_RETRIES as usize + INITIAL_POINTS - 2
+        );
+        assert_eq!(requests[0].1, amounts[0]);
+        assert_eq!(requests[1].1, amounts[0]);
+        assert!(requests[2..5]
+            .iter()
+            .all(|(_, amount)| *amount == amounts[1]));
+        assert_eq!(requests[5].1, amounts[2]);
+        assert_eq!(result.usdc_amount, amounts[3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejected_quotes_are_not_retried() {
+        let quotes = MockSwapVenue::new(0.0)
+            .with_price_curve(rising_ask)
+            .with_script([MockQuote::Rejected("No routes found".to_string())]);
+        let (mut strategy, _temp_dir) = buy_on_jupiter(quotes.clone(), MockBondVenue::new(NAV));
+
+        strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await
+            .unwrap();
+
+        let requests = quotes.requests();
+        assert_eq!(requests.len(), INITIAL_POINTS);
+        assert_ne!(requests[0].1, requests[1].1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_when_every_quote_fails() {
+        let quotes = MockSwapVenue::new(0.0).with_script(
+            (0..INITIAL_POINTS * MAX_RETRIES as usize)
+                .map(|_| MockQuote::Error("timeout".to_string())),
+        );
+        let (mut strategy, _temp_dir) = buy_on_jupiter(quotes, MockBondVenue::new(NAV));
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await;
+
+        assert_eq!(skip_reason(result), SkipReason::QuoteFailed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn searches_only_unusual_spreads() {
+        let spread_history = SpreadHistory::default().with_entry_z_score(Some(2.0));
+        for spread_bps in [8.0, 10.0, 12.0, 10.0, 10.0] {
+            spread_history.record(
+                &stablebond_mint(),
+                "BuyOnJupiterSellOnEtherfuse",
+                spread_bps,
+            );
+        }
+
+        // 10 bps below NAV, the usual spread.
+        let quotes = MockSwapVenue::new(0.999);
+        let (strategy, _temp_dir) = buy_on_jupiter(quotes.clone(), MockBondVenue::new(NAV));
+        let mut strategy = strategy.with_spread_history(spread_history.clone());
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await;
+        assert_eq!(skip_reason(result), SkipReason::SpreadBelowThreshold);
+        assert_eq!(quotes.requests().len(), 1);
+
+        let quotes = MockSwapVenue::new(0.99);
+        let (strategy, _temp_dir) = buy_on_jupiter(quotes.clone(), MockBondVenue::new(NAV));
+        let mut strategy = strategy.with_spread_history(spread_history);
+        let _ = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await;
+        assert_eq!(quotes.requests().len(), INITIAL_POINTS);
+    }
+
+    #[test]
+    fn prices_the_trade_at_the_simulated_redemption() {
+        let fees = FeeBreakdown {
+            venue_fee_usd: Decimal::ONE,
+            slippage_bps: 50,
+            tip_usd: Decimal::new(25, 2),
+            gas_usd: Decimal::new(1, 2),
+        };
+
+        // 1,000 USDC swapped for stablebonds that redeem for 1,003 USDC.
+        let (redeemed, profit) =
+            simulated_profit(5_000_000_000, 5_003_000_000, 1_000_000_000, fees).unwrap();
+        assert_eq!(redeemed, 1_003_000_000);
+        assert_eq!(profit, Decimal::new(274, 2));
+
+        let (redeemed, profit) =
+            simulated_profit(5_000_000_000, 4_999_000_000, 1_000_000_000, fees).unwrap();
+        assert_eq!(redeemed, 999_000_000);
+        assert_eq!(profit, Decimal::new(-126, 2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_when_jupiter_is_above_nav() {
+        let (mut strategy, _temp_dir) =
+            buy_on_jupiter(MockSwapVenue::new(1.01), MockBondVenue::new(NAV));
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await;
+
+        assert_eq!(skip_reason(result), SkipReason::SpreadBelowThreshold);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_below_the_minimum_profit_until_it_is_lowered() {
+        // 10 bps below NAV makes at most $0.90 after the tip.
+        let quotes = MockSwapVenue::new(0.999);
+        let min_profit = MinProfit::default();
+        let (strategy, _temp_dir) = buy_on_jupiter(quotes, MockBondVenue::new(NAV));
+        let mut strategy = strategy.with_min_profit(min_profit.clone());
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await;
+        assert_eq!(skip_reason(result), SkipReason::ProfitBelowMinimum);
+
+        min_profit.set(Decimal::new(5, 1));
+        strategy.rpc_client = rpc_client();
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await
+            .unwrap();
+        assert_eq!(result.usdc_amount, max_usdc_amount());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn quotes_only_the_size_the_pool_prices_best() {
+        let quotes = MockSwapVenue::new(0.0).with_price_curve(rising_ask);
+        let (mut strategy, _temp_dir) = buy_on_jupiter(quotes.clone(), MockBondVenue::new(NAV));
+        // Priced at 95 cents plus 1 bp per USDC bought, like `rising_ask`.
+        let md = MarketData {
+            pool_state: Some(PoolState {
+                curve: PoolCurve::ConstantProduct {
+                    usdc_reserve: 9_500_000_000,
+                    stablebond_reserve: 10_000_000_000,
+                },
+                fee_rate: 0,
+                slot: 1,
+            }),
+            ..market_data()
+        };
+
+        let result = strategy
+            .process_market_data(&md, &stablebond_mint())
+            .await
+            .unwrap();
+
+        let usdc_amount = usdc_amount_at(trade_size_points()[3]);
+        assert_eq!(quotes.requests(), vec![(QuoteSide::Buy, usdc_amount)]);
+        assert_eq!(result.usdc_amount, usdc_amount);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejects_an_implied_price_far_from_nav() {
+        // A quote 10% below NAV is more likely bad data than a real spread.
+        let (mut strategy, _temp_dir) =
+            buy_on_jupiter(MockSwapVenue::new(0.9), MockBondVenue::new(NAV));
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await;
+
+        assert_eq!(skip_reason(result), SkipReason::SanityCheckFailed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn aborts_when_a_leg_fails_to_build() {
+        let quotes = MockSwapVenue::new(0.99).with_swap_failure("no route");
+        let (mut strategy, _temp_dir) = buy_on_jupiter(quotes, MockBondVenue::new(NAV));
+
+        let result = strategy
+            .process_market_data(&market_data(), &stablebond_mint())
+            .await;
+
+        assert_eq!(skip_reason(result), SkipReason::BuildFailed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejects_an_implied_price_far_from_the_oracle() {
+        // 1% below NAV, but NAV at the oracle's exchange rate is 10% higher.
+        let (mut strategy, _temp_dir) =
+            buy_on_jupiter(MockSwapVenue::new(0.99), MockBondVenue::new(NAV));
+        let md = MarketData {
+            etherfuse_exchange_rate: Some(1.0),
+            oracle_price: Some(0.9),
+            ..market_data()
+        };
+
+        let result = strategy.process_market_data(&md, &stablebond_mint()).await;
+
+        assert_eq!(skip_reason(result), SkipReason::SanityCheckFailed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_without_usdc() {
+        let (mut strategy, _temp_dir) =
+            buy_on_jupiter(MockSwapVenue::new(0.95), MockBondVenue::new(NAV));
+        let md = MarketData {
+            usdc_holdings_token_amount: Some(0),
+            ..market_data()
+        };
+
+        let result = strategy.process_market_data(&md, &stablebond_mint()).await;
+
+        assert_eq!(skip_reason(result), SkipReason::InsufficientBalance);
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::{
+    constants::{WORKER_RESTART_BASE_SECS, WORKER_RESTART_MAX_SECS},
+    notifier::{Notifications, Severity},
+};
+
+/// Panics of one supervised task since the bot started.
+#[derive(Clone, Debug, Serialize)]
+pub struct WorkerPanics {
+    pub count: u64,
+    pub last_message: String,
+    pub last_at: String,
+}
+
+/// Keeps the bot's long-running tasks, the mint workers and the background loops, running
+/// through panics: a task that panics is restarted after a backoff, while the others carry on.
+/// Panics are notified and counted by task, for the status reported to operators.
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    pub notifications: Notifications,
+    panics: Arc<RwLock<BTreeMap<String, WorkerPanics>>>,
+}
+
+/// Delay before restarting a worker that panicked `restarts` times in a row, doubling from the
+/// base up to the maximum.
+pub fn restart_backoff(restarts: u32) -> Duration {
+    let secs = WORKER_RESTART_BASE_SECS.saturating_mul(1 << restarts.min(16));
+    Duration::from_secs(secs.min(WORKER_RESTART_MAX_SECS))
+}
+
+/// Message a task panicked with, when it panicked with a string.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Panics of every task that panicked, by task name.
+    pub fn panics(&self) -> BTreeMap<String, WorkerPanics> {
+        self.panics.read().unwrap().clone()
+    }
+
+    /// Supervises the worker built by `worker` on a task of its own.
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, worker: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        let name = name.into();
+        tokio::spawn(async move { supervisor.supervise(name, worker).await })
+    }
+
+    /// Runs the worker built by `worker` on its own task, and starts a new one whenever it
+    /// panics, after a backoff that grows with each panic in a row. A worker that ran for
+    /// longer than the longest backoff before panicking starts over from the base delay.
+    /// Returns once a worker finishes or is cancelled.
+    pub async fn supervise<F, Fut>(&self, name: String, mut worker: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut restarts = 0;
+        loop {
+            let started_at = Instant::now();
+            match tokio::spawn(worker()).await {
+                Ok(()) => return,
+                Err(e) if e.is_panic() => {
+                    if started_at.elapsed() > Duration::from_secs(WORKER_RESTART_MAX_SECS) {
+                        restarts = 0;
+                    }
+                    let message = panic_message(e.into_panic().as_ref());
+                    let backoff = restart_backoff(restarts);
+                    self.record_panic(&name, &message, backoff);
+                    tokio::time::sleep(backoff).await;
+                    restarts += 1;
+                    info!(worker = %name, restarts, "Restarting worker");
+                }
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn record_panic(&self, name: &str, message: &str, backoff: Duration) {
+        error!(
+            worker = %name,
+            "Worker panicked, restarting in {}s: {}",
+            backoff.as_secs(),
+            message
+        );
+        self.notifications.notify(
+            Severity::Critical,
+            format!(
+                "{} panicked, restarting in {}s: {}",
+                name,
+                backoff.as_secs(),
+                message
+            ),
+        );
+        let mut panics = self.panics.write().unwrap();
+        let panics = panics.entry(name.to_string()).or_insert(WorkerPanics {
+            count: 0,
+            last_message: String::new(),
+            last_at: String::new(),
+        });
+        panics.count += 1;
+        panics.last_message = message.to_string();
+        panics.last_at = chrono::Utc::now().to_rfc3339();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_maximum() {
+        assert_eq!(restart_backoff(0), Duration::from_secs(1));
+        assert_eq!(restart_backoff(3), Duration::from_secs(8));
+        assert_eq!(
+            restart_backoff(20),
+            Duration::from_secs(WORKER_RESTART_MAX_SECS)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_a_worker_until_it_stops_panicking() {
+        let supervisor = Supervisor::new();
+        let runs = Arc::new(AtomicU32::new(0));
+        let worker_runs = runs.clone();
+        supervisor
+            .supervise("test".to_string(), move || {
+                let runs = worker_runs.clone();
+                async move {
+                    let run = runs.fetch_add(1, Ordering::SeqCst);
+                    if run < 2 {
+                        panic!("run {} failed", run);
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let panics = &supervisor.panics()["test"];
+        assert_eq!(panics.count, 2);
+        assert_eq!(panics.last_message, "run 1 failed");
+    }
+}
@@ -0,0 +1,196 @@
+use enum_dispatch::enum_dispatch;
+use futures::future::join_all;
+use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
+use tracing::debug;
+
+use crate::{
+    error::{ArbError, ArbResult, ExecutionError},
+    jupiter::JupiterClient,
+    providers::{SwapQuote, SwapVenue},
+    raydium::RaydiumClient,
+    slippage_stats::Venue,
+};
+
+#[enum_dispatch(SwapVenue)]
+#[derive(Clone)]
+pub enum SwapVenueEnum {
+    Jupiter(JupiterClient),
+    Raydium(RaydiumClient),
+}
+
+/// Quotes every swap on all its venues and keeps the best price, then builds the swap on the
+/// venue that quoted it. With a single venue it is that venue.
+#[derive(Clone)]
+pub struct SwapRouter<S = SwapVenueEnum> {
+    pub venues: Vec<S>,
+}
+
+impl<S: SwapVenue> SwapRouter<S> {
+    pub fn new(venues: Vec<S>) -> Self {
+        Self { venues }
+    }
+
+    /// Best of the venues' quotes, the lowest price when buying and the highest when selling.
+    /// When none of them quotes, fails with a transient error if any, so the caller retries.
+    fn best_quote(
+        &self,
+        results: Vec<ArbResult<(f64, SwapQuote)>>,
+        buying: bool,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        let mut best: Option<(f64, SwapQuote)> = None;
+        let mut error: Option<ArbError> = None;
+        for (venue, result) in self.venues.iter().zip(results) {
+            match result {
+                Ok((price, quote)) => {
+                    debug!(venue = ?venue.venue(), price, "Swap quote");
+                    let better = best.as_ref().map_or(true, |(best_price, _)| {
+                        if buying {
+                            price < *best_price
+                        } else {
+                            price > *best_price
+                        }
+                    });
+                    if better {
+                        best = Some((price, quote));
+                    }
+                }
+                Err(e) => {
+                    debug!(venue = ?venue.venue(), "No swap quote: {}", e);
+                    if error.as_ref().map_or(true, |error| !error.is_transient()) {
+                        error = Some(e);
+                    }
+                }
+            }
+        }
+        best.ok_or_else(|| {
+            error.unwrap_or_else(|| ExecutionError::NoBackend("swaps".to_string()).into())
+        })
+    }
+}
+
+impl<S: SwapVenue> SwapVenue for SwapRouter<S> {
+    /// Venue of the first venue, the one quoted first.
+    fn venue(&self) -> Venue {
+        self.venues.first().map_or(Venue::Jupiter, SwapVenue::venue)
+    }
+
+    async fn buy_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        usdc_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        let results = join_all(
+            self.venues
+                .iter_mut()
+                .map(|venue| venue.buy_quote(stablebond_mint, usdc_amount)),
+        )
+        .await;
+        self.best_quote(results, true)
+    }
+
+    async fn sell_quote(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        stablebond_amount: u64,
+    ) -> ArbResult<(f64, SwapQuote)> {
+        let results = join_all(
+            self.venues
+                .iter_mut()
+                .map(|venue| venue.sell_quote(stablebond_mint, stablebond_amount)),
+        )
+        .await;
+        self.best_quote(results, false)
+    }
+
+    async fn swap_tx(&mut self, quote: SwapQuote) -> ArbResult<VersionedTransaction> {
+        let Some(venue) = self
+            .venues
+            .iter_mut()
+            .find(|venue| venue.venue() == quote.venue)
+        else {
+            return Err(ExecutionError::NoBackend(format!("{:?} swaps", quote.venue)).into());
+        };
+        venue.swap_tx(quote).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{tx_label, MockQuote, MockSwapVenue};
+
+    fn stablebond_mint() -> Pubkey {
+        Pubkey::new_from_array([7; 32])
+    }
+
+    fn router() -> (MockSwapVenue, MockSwapVenue, SwapRouter<MockSwapVenue>) {
+        let jupiter = MockSwapVenue::new(1.01);
+        let raydium = MockSwapVenue::new(1.00).with_venue(Venue::Raydium);
+        let router = SwapRouter::new(vec![jupiter.clone(), raydium.clone()]);
+        (jupiter, raydium, router)
+    }
+
+    #[tokio::test]
+    async fn buys_on_the_cheapest_venue() {
+        let (jupiter, raydium, mut router) = router();
+
+        let (price, quote) = router
+            .buy_quote(&stablebond_mint(), 1_000_000)
+            .await
+            .unwrap();
+        let tx = router.swap_tx(quote).await.unwrap();
+
+        assert_eq!(price, 1.00);
+        assert_eq!(tx_label(&tx).unwrap(), "raydium_swap");
+        assert_eq!(raydium.swaps().len(), 1);
+        assert!(jupiter.swaps().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sells_on_the_best_bid() {
+        let (jupiter, raydium, mut router) = router();
+
+        let (price, quote) = router
+            .sell_quote(&stablebond_mint(), 1_000_000)
+            .await
+            .unwrap();
+        router.swap_tx(quote).await.unwrap();
+
+        assert_eq!(price, 1.01);
+        assert_eq!(jupiter.swaps().len(), 1);
+        assert!(raydium.swaps().is_empty());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_venues_that_quote() {
+        let jupiter = MockSwapVenue::new(1.01)
+            .with_script([MockQuote::Rejected("No routes found".to_string())]);
+        let raydium = MockSwapVenue::new(1.02).with_venue(Venue::Raydium);
+        let mut router = SwapRouter::new(vec![jupiter, raydium]);
+
+        let (price, quote) = router
+            .buy_quote(&stablebond_mint(), 1_000_000)
+            .await
+            .unwrap();
+
+        assert_eq!(price, 1.02);
+        assert_eq!(quote.venue, Venue::Raydium);
+    }
+
+    #[tokio::test]
+    async fn prefers_a_transient_error_when_no_venue_quotes() {
+        let jupiter =
+            MockSwapVenue::new(1.01).with_script([MockQuote::Rejected("No routes".to_string())]);
+        let raydium = MockSwapVenue::new(1.02)
+            .with_venue(Venue::Raydium)
+            .with_script([MockQuote::Error("429 Too Many Requests".to_string())]);
+        let mut router = SwapRouter::new(vec![jupiter, raydium]);
+
+        let err = router
+            .buy_quote(&stablebond_mint(), 1_000_000)
+            .await
+            .unwrap_err();
+
+        assert!(err.is_transient(), "{}", err);
+    }
+}
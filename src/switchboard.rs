@@ -4,61 +4,216 @@ use solana_program::{
 };
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    compute_budget::ComputeBudgetInstruction,
-    message::{v0::Message, VersionedMessage::V0},
     signature::{read_keypair_file, Keypair},
     signer::Signer,
     transaction::VersionedTransaction,
 };
 use std::sync::Arc;
-use switchboard_on_demand_client;
+use switchboard_on_demand_client::PullFeedAccountData;
+use tracing::warn;
+
+use crate::{
+    account_cache::{get_account_data, AccountCache},
+    constants::ORACLE_UPDATE_ATTEMPTS,
+    priority_fee::PriorityFeeEstimator,
+    providers::FeedValue,
+    transaction::{simulate_tx, validate_tx_size, TransactionBuilder, TransactionCache},
+};
 
 #[derive(Clone)]
 pub struct SwitchboardClient {
     pub rpc_client: Arc<RpcClient>,
     pub keypair_filepath: String,
+    pub compute_unit_limit: Option<u32>,
+    pub compute_unit_price: Option<u64>,
+    pub fallback_gateway_urls: Vec<String>,
+    pub simulate: bool,
+    pub priority_fee_estimator: Option<PriorityFeeEstimator>,
+    pub account_cache: Option<AccountCache>,
+    pub transaction_cache: TransactionCache,
 }
 
 impl SwitchboardClient {
     pub fn new(rpc_client: Arc<RpcClient>, keypair_filepath: String) -> Self {
         Self {
+            transaction_cache: TransactionCache::new(rpc_client.clone()),
             rpc_client,
             keypair_filepath,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            fallback_gateway_urls: Vec::new(),
+            simulate: false,
+            priority_fee_estimator: None,
+            account_cache: None,
         }
     }
 
+    pub fn with_priority_fee_estimator(
+        mut self,
+        priority_fee_estimator: PriorityFeeEstimator,
+    ) -> Self {
+        self.priority_fee_estimator = Some(priority_fee_estimator);
+        self
+    }
+
+    /// Reads the feeds from the Geyser stream when live.
+    pub fn with_account_cache(mut self, account_cache: AccountCache) -> Self {
+        self.account_cache = Some(account_cache);
+        self
+    }
+
+    /// Builds the update transactions on the engine's shared blockhash and records the feeds'
+    /// lookup tables in it.
+    pub fn with_transaction_cache(mut self, transaction_cache: TransactionCache) -> Self {
+        self.transaction_cache = transaction_cache;
+        self
+    }
+
+    pub fn with_simulation(mut self, simulate: bool) -> Self {
+        self.simulate = simulate;
+        self
+    }
+
+    /// Gateways tried, in order, when none of the gateways of the feed's queue pass the test.
+    pub fn with_fallback_gateways(mut self, fallback_gateway_urls: Vec<String>) -> Self {
+        self.fallback_gateway_urls = fallback_gateway_urls;
+        self
+    }
+
+    /// Overrides the compute budget of the oracle update transaction, which is otherwise set by
+    /// the transaction builder. The price needs to keep up with congestion, otherwise the update
+    /// gets out-bid and sinks the whole bundle.
+    pub fn with_compute_budget(
+        mut self,
+        compute_unit_limit: Option<u32>,
+        compute_unit_price: Option<u64>,
+    ) -> Self {
+        self.compute_unit_limit = compute_unit_limit;
+        self.compute_unit_price = compute_unit_price;
+        self
+    }
+
     fn signer(&self) -> Keypair {
         read_keypair_file(self.keypair_filepath.clone())
-            .expect(format!("No keypair found at {}", self.keypair_filepath).as_str())
+            .unwrap_or_else(|_| panic!("No keypair found at {}", self.keypair_filepath))
+    }
+
+    /// Current value of the feed and the number of slots since it was last updated.
+    pub async fn fetch_feed_value(&self, public_feed: Pubkey) -> Result<FeedValue> {
+        let data =
+            get_account_data(&self.rpc_client, self.account_cache.as_ref(), &public_feed).await?;
+        let feed_data = data
+            .get(8..8 + std::mem::size_of::<PullFeedAccountData>())
+            .map(bytemuck::pod_read_unaligned::<PullFeedAccountData>)
+            .ok_or_else(|| anyhow!("Invalid pull feed account {}", public_feed))?;
+        let current_slot = self.rpc_client.get_slot().await?;
+        Ok(FeedValue {
+            price: feed_data.result.value as f64
+                / 10f64.powi(switchboard_on_demand_client::PRECISION as i32),
+            age_slots: current_slot.saturating_sub(feed_data.result.slot),
+            max_staleness_slots: feed_data.max_staleness as u64,
+        })
     }
 
-    pub async fn get_update_switchboard_oracle_tx(
+    /// Builds transactions updating all of `public_feeds`. When simulation is enabled, the
+    /// transactions are simulated right away and rebuilt once if the simulation fails, since an
+    /// oracle update failing on-chain fails the whole bundle.
+    pub async fn get_update_switchboard_oracle_txs(
         &self,
-        public_feed: Pubkey,
-    ) -> Result<VersionedTransaction> {
-        let (update_oracle_ix, lookup_tables) = self
-            .fetch_oracle_feed(public_feed, self.signer().pubkey())
-            .await?;
+        public_feeds: &[Pubkey],
+    ) -> Result<Vec<VersionedTransaction>> {
+        let mut attempt = 1;
+        loop {
+            let txs = self.build_update_txs(public_feeds).await?;
+            if !self.simulate {
+                return Ok(txs);
+            }
+            let mut simulation_error = None;
+            for tx in txs.iter() {
+                if let Err(e) = simulate_tx(&self.rpc_client, tx).await {
+                    simulation_error = Some(e);
+                    break;
+                }
+            }
+            match simulation_error {
+                None => return Ok(txs),
+                Some(e) if attempt >= ORACLE_UPDATE_ATTEMPTS => return Err(e),
+                Some(e) => warn!("Oracle update simulation failed, rebuilding: {:?}", e),
+            }
+            attempt += 1;
+        }
+    }
 
+    /// Packs as many update instructions into each transaction as fit within the packet size.
+    async fn build_update_txs(&self, public_feeds: &[Pubkey]) -> Result<Vec<VersionedTransaction>> {
+        let signer = self.signer();
         let blockhash = self
-            .rpc_client
-            .get_latest_blockhash()
+            .transaction_cache
+            .latest_blockhash()
             .await
             .map_err(|e| anyhow!("Unable to get latest blockhash: {:?}", e))?;
-        let msg = Message::try_compile(
-            &self.signer().pubkey(),
-            &[
-                ComputeBudgetInstruction::set_compute_unit_limit(250_000),
-                ComputeBudgetInstruction::set_compute_unit_price(100000),
-                update_oracle_ix.clone(),
-            ],
-            &lookup_tables,
-            blockhash,
-        )
-        .map_err(|e| anyhow!("Unable to compile transaction message: {:?}", e))?;
-        let tx = VersionedTransaction::try_new(V0(msg), &[&self.signer()])
-            .map_err(|e| anyhow!("Unable to create versioned transaction: {:?}", e))?;
-        Ok(tx)
+        let mut txs = Vec::new();
+        let mut ixs: Vec<Instruction> = Vec::new();
+        let mut lookup_tables: Vec<AddressLookupTableAccount> = Vec::new();
+        for public_feed in public_feeds {
+            let (update_oracle_ix, feed_lookup_tables) = self
+                .fetch_oracle_feed(*public_feed, signer.pubkey())
+                .await?;
+            for lookup_table in feed_lookup_tables.iter() {
+                self.transaction_cache
+                    .record_lookup_table(lookup_table.clone());
+            }
+
+            let mut candidate_ixs = ixs.clone();
+            candidate_ixs.push(update_oracle_ix.clone());
+            let mut candidate_lookup_tables = lookup_tables.clone();
+            for lookup_table in feed_lookup_tables.iter() {
+                if !candidate_lookup_tables
+                    .iter()
+                    .any(|table| table.key == lookup_table.key)
+                {
+                    candidate_lookup_tables.push(lookup_table.clone());
+                }
+            }
+
+            let candidate_tx = self
+                .update_tx_builder(&signer, &candidate_ixs, &candidate_lookup_tables)
+                .build_unsimulated(blockhash)?;
+            if ixs.is_empty() || validate_tx_size(&candidate_tx).is_ok() {
+                ixs = candidate_ixs;
+                lookup_tables = candidate_lookup_tables;
+            } else {
+                txs.push(
+                    self.update_tx_builder(&signer, &ixs, &lookup_tables)
+                        .build()
+                        .await?,
+                );
+                ixs = vec![update_oracle_ix];
+                lookup_tables = feed_lookup_tables;
+            }
+        }
+        if !ixs.is_empty() {
+            txs.push(
+                self.update_tx_builder(&signer, &ixs, &lookup_tables)
+                    .build()
+                    .await?,
+            );
+        }
+        Ok(txs)
+    }
+
+    fn update_tx_builder<'a>(
+        &'a self,
+        signer: &'a Keypair,
+        update_oracle_ixs: &[Instruction],
+        lookup_tables: &[AddressLookupTableAccount],
+    ) -> TransactionBuilder<'a> {
+        TransactionBuilder::new(&self.transaction_cache, signer)
+            .with_ixs(update_oracle_ixs)
+            .with_lookup_tables(lookup_tables)
+            .with_compute_unit_limit(self.compute_unit_limit)
+            .with_compute_unit_price(self.compute_unit_price)
+            .with_priority_fee_estimator(self.priority_fee_estimator.as_ref())
     }
 
     async fn fetch_oracle_feed(
@@ -79,8 +234,12 @@ impl SwitchboardClient {
         .await
         .map_err(|e| anyhow!("Unable to fetch gateways: {:?}", e))?;
         // test gateways and return first working one
+        let fallback_gws = self
+            .fallback_gateway_urls
+            .iter()
+            .map(|url| switchboard_on_demand_client::Gateway::new(url.clone()));
         let mut gw = None;
-        for trial_gw in gws {
+        for trial_gw in gws.into_iter().chain(fallback_gws) {
             if trial_gw.test_gateway().await {
                 gw = Some(trial_gw);
                 break;
@@ -0,0 +1,278 @@
+use anyhow::{anyhow, Result};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{
+    constants::{TELEGRAM_API_URL, TELEGRAM_POLL_RETRY_SECS, TELEGRAM_POLL_TIMEOUT_SECS},
+    control::ControlPlane,
+    error_aggregator::report_error,
+    http::default_http_client,
+    notifier::{post_json, severity_prefix, Notifier, Severity},
+};
+
+const HELP: &str = "Commands:
+/status - what the bot is doing and why it is halted
+/pause [MINT] - pause trading, every mint or one
+/resume [MINT] - resume trading, every mint or one
+/balances - wallet balances
+/profile [NAME] - show the active profile, or switch to another
+/pnl [today|YYYY-MM-DD] - profit per mint since the start of the day or a date";
+
+/// `[telegram]` section of the config file. Notifications are only sent when it is present.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// Token of the bot, as given by @BotFather.
+    pub bot_token: String,
+    /// Chat the bot posts to.
+    pub chat_id: String,
+    #[serde(default)]
+    pub min_severity: Severity,
+    /// Chats whose commands the bot obeys. Commands are ignored when empty.
+    #[serde(default)]
+    pub allowed_chat_ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct UpdatesResponse {
+    ok: bool,
+    #[serde(default)]
+    result: Vec<Update>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Posts to a Telegram chat through a bot, and takes commands from allow-listed chats.
+#[derive(Clone)]
+pub struct TelegramNotifier {
+    pub config: TelegramConfig,
+    pub api_url: String,
+    pub http_client: ClientWithMiddleware,
+}
+
+impl TelegramNotifier {
+    pub fn new(config: TelegramConfig) -> Self {
+        Self {
+            config,
+            api_url: TELEGRAM_API_URL.to_string(),
+            http_client: default_http_client(),
+        }
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// Polls the bot's messages in the background and answers the commands of allow-listed
+    /// chats through `control`. Does nothing when no chat is allowed.
+    pub fn spawn_command_listener(&self, control: ControlPlane) {
+        if self.config.allowed_chat_ids.is_empty() {
+            return;
+        }
+        info!(
+            "Taking Telegram commands from chats {:?}",
+            self.config.allowed_chat_ids
+        );
+        let bot = self.clone();
+        tokio::spawn(async move {
+            let mut offset = 0;
+            loop {
+                let updates = match bot.get_updates(offset).await {
+                    Ok(updates) => updates,
+                    Err(e) => {
+                        report_error("Error getting Telegram updates", e);
+                        tokio::time::sleep(Duration::from_secs(TELEGRAM_POLL_RETRY_SECS)).await;
+                        continue;
+                    }
+                };
+                for update in updates {
+                    offset = update.update_id + 1;
+                    let Some(Message {
+                        chat,
+                        text: Some(text),
+                    }) = update.message
+                    else {
+                        continue;
+                    };
+                    if !bot.config.allowed_chat_ids.contains(&chat.id.to_string()) {
+                        warn!(
+                            chat = chat.id,
+                            "Ignoring Telegram command from a chat not allowed"
+                        );
+                        continue;
+                    }
+                    info!(chat = chat.id, "Telegram command: {}", text);
+                    let reply = handle_command(&control, &text)
+                        .await
+                        .unwrap_or_else(|e| format!("Error: {}", e));
+                    bot.reply(chat.id, reply);
+                }
+            }
+        });
+    }
+
+    async fn get_updates(&self, offset: i64) -> Result<Vec<Update>> {
+        let url = format!(
+            "{}/bot{}/getUpdates?timeout={}&offset={}",
+            self.api_url, self.config.bot_token, TELEGRAM_POLL_TIMEOUT_SECS, offset
+        );
+        let response: UpdatesResponse = self.http_client.get(url).send().await?.json().await?;
+        if !response.ok {
+            return Err(anyhow!(
+                "Telegram error: {}",
+                response.description.unwrap_or_default()
+            ));
+        }
+        Ok(response.result)
+    }
+
+    fn reply(&self, chat_id: i64, text: String) {
+        post_json(
+            self.name(),
+            &self.http_client,
+            format!("{}/bot{}/sendMessage", self.api_url, self.config.bot_token),
+            json!({ "chat_id": chat_id, "text": text }),
+        );
+    }
+}
+
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
+    fn send(&self, severity: Severity, text: &str) {
+        post_json(
+            self.name(),
+            &self.http_client,
+            format!("{}/bot{}/sendMessage", self.api_url, self.config.bot_token),
+            json!({
+                "chat_id": self.config.chat_id,
+                "text": format!("{} {}", severity_prefix(severity), text),
+                "disable_web_page_preview": true,
+            }),
+        );
+    }
+}
+
+async fn handle_command(control: &ControlPlane, text: &str) -> Result<String> {
+    let mut words = text.split_whitespace();
+    // Commands sent in groups are suffixed with the bot's name, e.g. `/status@arb_bot`.
+    let command = words
+        .next()
+        .unwrap_or_default()
+        .split('@')
+        .next()
+        .unwrap_or_default();
+    let argument = words.next();
+    match command {
+        "/status" => {
+            let status = control.status();
+            let mut reply = format!(
+                "Running since {}, last tick at {}, {} trades since start, trading {} mints",
+                status.started_at,
+                status.last_tick_at.as_deref().unwrap_or("-"),
+                status.trades_since_start,
+                status.mints.len()
+            );
+            if let Some(profile) = &status.profile {
+                reply.push_str(&format!(
+                    "\nProfile {}, minimum profit ${:.2}",
+                    profile, status.min_profit_usd
+                ));
+            }
+            for (mint, tick) in status.ticks {
+                reply.push_str(&format!("\n{}: tick {}", mint, tick));
+            }
+            if status.halts.is_empty() {
+                reply.push_str("\nNot halted");
+            }
+            for (scope, reason) in status.halts {
+                reply.push_str(&format!("\nHalted {}: {}", scope, reason));
+            }
+            for (worker, panics) in status.panics {
+                reply.push_str(&format!(
+                    "\n{} panicked {} times, last at {}: {}",
+                    worker, panics.count, panics.last_at, panics.last_message
+                ));
+            }
+            Ok(reply)
+        }
+        "/pause" => {
+            control.pause(argument)?;
+            Ok(format!("Paused {}", argument.unwrap_or("every mint")))
+        }
+        "/resume" => {
+            control.resume(argument)?;
+            Ok(format!("Resumed {}", argument.unwrap_or("every mint")))
+        }
+        "/balances" => {
+            let balances = control.balances().await?;
+            let mut reply = format!(
+                "SOL: {:.4}\nWrapped SOL: {:.4}\nUSDC: {:.2}",
+                balances.sol, balances.wsol, balances.usdc
+            );
+            for (mint, amount) in balances.stablebonds {
+                reply.push_str(&format!("\n{}: {:.6}", mint, amount));
+            }
+            Ok(reply)
+        }
+        "/profile" => {
+            if let Some(name) = argument {
+                let profile = control.set_profile(name)?;
+                return Ok(format!("Switched to profile {}: {:?}", name, profile));
+            }
+            let Some(profile) = &control.profile else {
+                return Ok("Profiles are not switchable".to_string());
+            };
+            let mut reply = format!("Profile: {}", profile.name());
+            for (name, settings) in profile.profiles() {
+                reply.push_str(&format!("\n{}: {:?}", name, settings));
+            }
+            Ok(reply)
+        }
+        "/pnl" => {
+            let rows = control.pnl(argument.filter(|argument| *argument != "today"))?;
+            if rows.is_empty() {
+                return Ok("No trades".to_string());
+            }
+            let mut reply = String::new();
+            for row in rows {
+                reply.push_str(&format!(
+                    "{}: {} trades, {} landed, realized ${:.4}, unrealized ${:.4}\n",
+                    row.key,
+                    row.trades,
+                    row.landed,
+                    row.realized_profit_usd,
+                    row.unrealized_profit_usd
+                ));
+            }
+            Ok(reply)
+        }
+        _ => Ok(HELP.to_string()),
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::NaiveDate;
+use solana_program::native_token::LAMPORTS_PER_SOL;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// Tracks the SOL spent on Jito tips over the current day and enforces a daily budget.
+/// A tip is reserved against the budget when its bundle is submitted, so that bundles in
+/// flight at once can't overshoot it, and settled once we know whether the bundle landed.
+/// Tips of bundles that failed to land are counted too.
+#[derive(Clone)]
+pub struct TipBudget {
+    pub daily_budget_lamports: u64,
+    spend: Arc<Mutex<TipSpend>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TipSpend {
+    pub day: NaiveDate,
+    pub landed_lamports: u64,
+    pub failed_lamports: u64,
+    /// Tips of bundles submitted but not landed or failed yet.
+    pub reserved_lamports: u64,
+}
+
+impl TipSpend {
+    pub fn total_lamports(&self) -> u64 {
+        self.landed_lamports
+            .saturating_add(self.failed_lamports)
+            .saturating_add(self.reserved_lamports)
+    }
+}
+
+impl TipBudget {
+    pub fn new(daily_budget_lamports: u64) -> Self {
+        Self {
+            daily_budget_lamports,
+            spend: Arc::new(Mutex::new(TipSpend {
+                day: today(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Reserves `tip_lamports` if tipping it more today stays within the budget. A reserved
+    /// tip must be settled, or released if its bundle was never submitted.
+    pub fn try_reserve(&self, tip_lamports: u64) -> bool {
+        let mut spend = self.spend.lock().unwrap();
+        roll_over(&mut spend);
+        if spend.total_lamports().saturating_add(tip_lamports) > self.daily_budget_lamports {
+            return false;
+        }
+        spend.reserved_lamports = spend.reserved_lamports.saturating_add(tip_lamports);
+        true
+    }
+
+    /// Returns a reserved tip to the budget, for a bundle the block engine didn't accept.
+    pub fn release(&self, tip_lamports: u64) {
+        let mut spend = self.spend.lock().unwrap();
+        roll_over(&mut spend);
+        spend.reserved_lamports = spend.reserved_lamports.saturating_sub(tip_lamports);
+    }
+
+    /// Counts a reserved tip as spent once its bundle landed or failed.
+    pub fn settle(&self, tip_lamports: u64, landed: bool) {
+        let mut spend = self.spend.lock().unwrap();
+        roll_over(&mut spend);
+        spend.reserved_lamports = spend.reserved_lamports.saturating_sub(tip_lamports);
+        if landed {
+            spend.landed_lamports = spend.landed_lamports.saturating_add(tip_lamports);
+        } else {
+            spend.failed_lamports = spend.failed_lamports.saturating_add(tip_lamports);
+        }
+        info!(
+            "Tips spent today: {} SOL landed, {} SOL failed, {} SOL in flight, {} SOL budget",
+            spend.landed_lamports as f64 / LAMPORTS_PER_SOL as f64,
+            spend.failed_lamports as f64 / LAMPORTS_PER_SOL as f64,
+            spend.reserved_lamports as f64 / LAMPORTS_PER_SOL as f64,
+            self.daily_budget_lamports as f64 / LAMPORTS_PER_SOL as f64,
+        );
+        if spend.total_lamports() >= self.daily_budget_lamports {
+            warn!(
+                "Daily tip budget of {} SOL exhausted, no more bundles until tomorrow",
+                self.daily_budget_lamports as f64 / LAMPORTS_PER_SOL as f64
+            );
+        }
+    }
+
+    pub fn spend(&self) -> TipSpend {
+        let mut spend = self.spend.lock().unwrap();
+        roll_over(&mut spend);
+        spend.clone()
+    }
+}
+
+fn roll_over(spend: &mut TipSpend) {
+    let today = today();
+    if spend.day != today {
+        // Bundles still in flight settle against the new day.
+        *spend = TipSpend {
+            day: today,
+            reserved_lamports: spend.reserved_lamports,
+            ..Default::default()
+        };
+    }
+}
+
+fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tips_in_flight_count_against_the_budget() {
+        let tip_budget = TipBudget::new(1_000);
+        assert!(tip_budget.try_reserve(600));
+        assert!(!tip_budget.clone().try_reserve(600));
+
+        tip_budget.release(600);
+        assert!(tip_budget.try_reserve(600));
+
+        tip_budget.settle(600, false);
+        let spend = tip_budget.spend();
+        assert_eq!(spend.failed_lamports, 600);
+        assert_eq!(spend.reserved_lamports, 0);
+        assert!(tip_budget.try_reserve(400));
+        assert!(!tip_budget.try_reserve(1));
+    }
+}
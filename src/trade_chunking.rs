@@ -0,0 +1,218 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use crate::constants::{
+    DEFAULT_CHUNK_LANDING_TIMEOUT_SECS, DEFAULT_CHUNK_MIN_PROFIT_SHARE, DEFAULT_MAX_TRADE_CHUNKS,
+};
+
+/// `[chunking]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkingConfig {
+    pub enabled: bool,
+    /// Bundles a trade is split into at most, counting the first.
+    pub max_chunks: u32,
+    /// Share of the first chunk's profit each next chunk must make, or the trade stops there.
+    pub min_profit_share: f64,
+    /// How long to wait for a chunk's bundle to land before giving up on the next.
+    pub landing_timeout_secs: u64,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_chunks: DEFAULT_MAX_TRADE_CHUNKS,
+            min_profit_share: DEFAULT_CHUNK_MIN_PROFIT_SHARE,
+            landing_timeout_secs: DEFAULT_CHUNK_LANDING_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// A trade larger than one bundle may carry, traded one bundle at a time, each re-quoted once
+/// the previous one landed.
+#[derive(Debug)]
+pub struct ChunkedTrade {
+    /// Chunks traded so far.
+    pub chunks: u32,
+    pub first_profit: Decimal,
+    /// Resolves once the last chunk's bundle lands or fails, `None` once it is known to have
+    /// landed.
+    pub landing: Option<oneshot::Receiver<bool>>,
+}
+
+impl ChunkedTrade {
+    pub fn new(first_profit: Decimal) -> Self {
+        Self {
+            chunks: 0,
+            first_profit,
+            landing: None,
+        }
+    }
+}
+
+/// Splits the trades capped at `MAX_USDC_AMOUNT_PER_TRADE` into sequential bundles: once a
+/// capped trade's bundle lands, the mint ticks again right away and trades the next chunk at a
+/// fresh quote, until `max_chunks`, the trade is no longer capped, or a chunk's profit degrades.
+#[derive(Clone)]
+pub struct TradeChunker {
+    pub config: ChunkingConfig,
+    pub landings: Landings,
+}
+
+impl TradeChunker {
+    pub fn new(config: ChunkingConfig, landings: Landings) -> Self {
+        Self { config, landings }
+    }
+
+    /// Why the next chunk of `trade`, quoted at `profit`, shouldn't be traded, if it shouldn't.
+    pub fn degraded(&self, trade: &ChunkedTrade, profit: Decimal) -> Option<String> {
+        let share = Decimal::try_from(self.config.min_profit_share).unwrap_or(Decimal::ONE);
+        let min_profit = trade.first_profit * share;
+        (profit < min_profit).then(|| {
+            format!(
+                "Chunk {} makes ${:.4}, less than {:.0}% of the first chunk's ${:.4}",
+                trade.chunks + 1,
+                profit,
+                self.config.min_profit_share * 100.0,
+                trade.first_profit
+            )
+        })
+    }
+
+    /// Records a traded chunk, returning the trade to continue on the next tick, if any. The
+    /// chunk's landing is watched when it was submitted as the bundle `bundle_id`, and known to
+    /// have landed otherwise.
+    pub fn record_chunk(
+        &self,
+        trade: Option<ChunkedTrade>,
+        capped: bool,
+        profit: Decimal,
+        bundle_id: Option<&str>,
+    ) -> Option<ChunkedTrade> {
+        if !self.config.enabled || !capped {
+            return None;
+        }
+        let mut trade = trade.unwrap_or_else(|| ChunkedTrade::new(profit));
+        trade.chunks += 1;
+        if trade.chunks >= self.config.max_chunks {
+            info!("Traded the last of {} chunks", trade.chunks);
+            return None;
+        }
+        info!(
+            "Traded chunk {} of up to {}, trading the next once it lands",
+            trade.chunks, self.config.max_chunks
+        );
+        trade.landing = bundle_id.map(|bundle_id| self.landings.watch(bundle_id));
+        Some(trade)
+    }
+
+    /// Waits for the last chunk of `trade` to land, returning whether the next one should be
+    /// traded.
+    pub async fn wait_for_landing(&self, trade: &mut ChunkedTrade) -> bool {
+        let Some(landing) = trade.landing.take() else {
+            return true;
+        };
+        let timeout = Duration::from_secs(self.config.landing_timeout_secs);
+        match tokio::time::timeout(timeout, landing).await {
+            Ok(Ok(true)) => true,
+            Ok(_) => {
+                warn!("Chunk {} didn't land, stopping the trade", trade.chunks);
+                false
+            }
+            Err(_) => {
+                warn!(
+                    "Chunk {} didn't land within {}s, stopping the trade",
+                    trade.chunks,
+                    timeout.as_secs()
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Outcomes of the bundles a chunked trade waits on, by submitted bundle id.
+#[derive(Clone, Default)]
+pub struct Landings {
+    waiters: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>,
+}
+
+impl Landings {
+    /// Resolves with whether `bundle_id` landed, once its outcome is settled.
+    pub fn watch(&self, bundle_id: &str) -> oneshot::Receiver<bool> {
+        let (sender, receiver) = oneshot::channel();
+        self.waiters
+            .lock()
+            .unwrap()
+            .insert(bundle_id.to_string(), sender);
+        receiver
+    }
+
+    /// Called with the outcome of every bundle, the ones nobody watches are ignored.
+    pub fn settle(&self, bundle_id: &str, landed: bool) {
+        if let Some(sender) = self.waiters.lock().unwrap().remove(bundle_id) {
+            let _ = sender.send(landed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunker() -> TradeChunker {
+        TradeChunker::new(
+            ChunkingConfig {
+                enabled: true,
+                max_chunks: 3,
+                min_profit_share: 0.5,
+                ..Default::default()
+            },
+            Landings::default(),
+        )
+    }
+
+    #[test]
+    fn stops_at_a_chunk_worth_less_than_the_share_of_the_first() {
+        let chunker = chunker();
+        let trade = ChunkedTrade::new(Decimal::from(10));
+
+        assert_eq!(chunker.degraded(&trade, Decimal::from(6)), None);
+        assert_eq!(chunker.degraded(&trade, Decimal::from(5)), None);
+        assert!(chunker.degraded(&trade, Decimal::new(499, 2)).is_some());
+    }
+
+    #[test]
+    fn continues_capped_trades_up_to_the_most_chunks() {
+        let chunker = chunker();
+        let profit = Decimal::from(10);
+
+        assert!(chunker.record_chunk(None, false, profit, None).is_none());
+        let trade = chunker.record_chunk(None, true, profit, None).unwrap();
+        let trade = chunker
+            .record_chunk(Some(trade), true, Decimal::from(8), None)
+            .unwrap();
+        assert_eq!((trade.chunks, trade.first_profit), (2, profit));
+        assert!(chunker
+            .record_chunk(Some(trade), true, profit, None)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_landing_of_the_chunk_bundle() {
+        let chunker = chunker();
+        let mut trade = chunker
+            .record_chunk(None, true, Decimal::from(10), Some("bundle"))
+            .unwrap();
+
+        chunker.landings.settle("other", false);
+        chunker.landings.settle("bundle", true);
+        assert!(chunker.wait_for_landing(&mut trade).await);
+    }
+}
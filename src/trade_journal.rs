@@ -0,0 +1,1032 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use rusqlite::{params, types::Type, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::{
+    jito::{BundleOutcome, BundleStatusEnum},
+    market_data::MarketData,
+    math,
+    profit_verification::{ExpectedProfit, PnlAttribution, RealizedProfit},
+    slippage_stats::{TradeLeg, Venue},
+    strategy::TradeDirection,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS trades (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    trade_id TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    mint TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    strategy TEXT NOT NULL,
+    backend TEXT NOT NULL,
+    usdc_amount REAL NOT NULL,
+    expected_profit_usd REAL NOT NULL,
+    status TEXT NOT NULL,
+    bundle_id TEXT,
+    signatures TEXT,
+    landed_slot INTEGER,
+    tip_lamports INTEGER,
+    realized_profit_usd REAL,
+    usdc_delta REAL,
+    stablebond_delta REAL,
+    lamports_delta INTEGER,
+    gross_spread_usd REAL,
+    jupiter_price_impact_usd REAL,
+    jupiter_slippage_usd REAL,
+    etherfuse_fee_usd REAL,
+    jito_tip_usd REAL,
+    priority_fee_usd REAL,
+    base_fee_usd REAL,
+    oracle_update_usd REAL,
+    other_cost_usd REAL,
+    stablebond_price REAL,
+    sol_price REAL,
+    submitted_at TEXT,
+    submitted_signatures TEXT,
+    trade_uuid TEXT,
+    quotes TEXT
+);
+CREATE INDEX IF NOT EXISTS trades_created_at ON trades (created_at);
+CREATE TABLE IF NOT EXISTS trade_transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    trade INTEGER NOT NULL REFERENCES trades (id),
+    state TEXT NOT NULL,
+    at TEXT NOT NULL,
+    detail TEXT
+);
+CREATE INDEX IF NOT EXISTS trade_transitions_trade ON trade_transitions (trade);
+";
+
+/// Columns added to `trades` since its first version, added to older journals when opened.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("stablebond_price", "REAL"),
+    ("sol_price", "REAL"),
+    ("submitted_at", "TEXT"),
+    ("submitted_signatures", "TEXT"),
+    ("trade_uuid", "TEXT"),
+    ("quotes", "TEXT"),
+];
+
+/// Where a trade is in its lifecycle, stored as its `status` in the journal. Each transition
+/// is also appended to `trade_transitions`, so a trade's history can be replayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeState {
+    /// A strategy found it profitable.
+    Discovered,
+    /// Its size passed the risk limits, the approval gate and the duplicate check.
+    Sized,
+    /// Its transactions are signed and ready to be handed to an execution backend.
+    Built,
+    /// Handed to an execution backend, which hasn't reported it landed yet.
+    Submitted,
+    Landed,
+    /// Abandoned before it was submitted, or didn't land.
+    Failed,
+    /// Landed and its realized profit verified.
+    Reconciled,
+}
+
+impl TradeState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TradeState::Discovered => "discovered",
+            TradeState::Sized => "sized",
+            TradeState::Built => "built",
+            TradeState::Submitted => "submitted",
+            TradeState::Landed => "landed",
+            TradeState::Failed => "failed",
+            TradeState::Reconciled => "reconciled",
+        }
+    }
+
+    /// Whether a trade in this state may move to `next`: one step forward, or to `Failed` at
+    /// any point before it lands.
+    pub fn can_transition_to(self, next: TradeState) -> bool {
+        use TradeState::*;
+        matches!(
+            (self, next),
+            (Discovered, Sized)
+                | (Sized, Built)
+                | (Built, Submitted)
+                | (Submitted, Landed)
+                | (Landed, Reconciled)
+                | (Discovered | Sized | Built | Submitted, Failed)
+        )
+    }
+
+    pub fn is_final(self) -> bool {
+        matches!(self, TradeState::Failed | TradeState::Reconciled)
+    }
+}
+
+impl fmt::Display for TradeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TradeState {
+    type Err = anyhow::Error;
+
+    fn from_str(state: &str) -> Result<Self> {
+        Ok(match state {
+            "discovered" => TradeState::Discovered,
+            "sized" => TradeState::Sized,
+            "built" => TradeState::Built,
+            "submitted" => TradeState::Submitted,
+            "landed" => TradeState::Landed,
+            // Bundles that timed out were journaled as such before the lifecycle.
+            "failed" | "timed_out" => TradeState::Failed,
+            "reconciled" => TradeState::Reconciled,
+            _ => return Err(anyhow!("Unknown trade state {}", state)),
+        })
+    }
+}
+
+/// A trade as read back from the journal.
+#[derive(Clone, Debug, Serialize)]
+pub struct TradeRecord {
+    pub trade_id: String,
+    /// Carried by the memo of the trade's transactions, absent for trades journaled before.
+    pub trade_uuid: Option<String>,
+    pub created_at: String,
+    pub mint: String,
+    pub strategy: String,
+    pub backend: String,
+    pub status: String,
+    pub usdc_amount: Decimal,
+    pub expected_profit_usd: Decimal,
+    pub realized_profit_usd: Option<Decimal>,
+    pub tip_lamports: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PnlGrouping {
+    Mint,
+    /// UTC day the trade was made.
+    Day,
+    Strategy,
+}
+
+impl PnlGrouping {
+    fn column(&self) -> &'static str {
+        match self {
+            PnlGrouping::Mint => "mint",
+            PnlGrouping::Day => "substr(created_at, 1, 10)",
+            PnlGrouping::Strategy => "strategy",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PnlRow {
+    pub key: String,
+    pub trades: u64,
+    pub landed: u64,
+    pub realized_profit_usd: Decimal,
+    /// Expected profit of the trades that are still pending or haven't been verified yet.
+    pub unrealized_profit_usd: Decimal,
+    pub tip_lamports: u64,
+    /// USDC traded, in UI amount.
+    pub notional_usd: Decimal,
+    /// Summed over the verified trades.
+    pub attribution: PnlAttribution,
+}
+
+/// A trade as a strategy found it, before it is sized, built and executed.
+#[derive(Clone, Debug)]
+pub struct DiscoveredTrade {
+    /// Stays the trade's id for good, unlike `trade_id`, and tags its transactions.
+    pub trade_uuid: Uuid,
+    pub mint: Pubkey,
+    pub direction: TradeDirection,
+    pub strategy: &'static str,
+    /// UI amount.
+    pub usdc_amount: Decimal,
+    pub expected_profit_usd: Decimal,
+    pub stablebond_price: f64,
+    pub sol_price: f64,
+    pub quotes: QuoteSnapshot,
+}
+
+/// The quotes a trade was priced at when it was found, journaled as JSON with its discovery so
+/// that expected and realized profits can be traced back to the prices behind them.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct QuoteSnapshot {
+    pub etherfuse_price_per_token: Option<f64>,
+    pub etherfuse_exchange_rate: Option<f64>,
+    pub oracle_price: Option<f64>,
+    pub legs: Vec<QuotedLeg>,
+}
+
+impl QuoteSnapshot {
+    pub fn new(market_data: &MarketData, legs: &[TradeLeg]) -> Self {
+        Self {
+            etherfuse_price_per_token: market_data.etherfuse_price_per_token,
+            etherfuse_exchange_rate: market_data.etherfuse_exchange_rate,
+            oracle_price: market_data.oracle_price,
+            legs: legs.iter().map(QuotedLeg::from).collect(),
+        }
+    }
+}
+
+/// A leg of a trade as its venue quoted it. Amounts are UI amounts.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct QuotedLeg {
+    pub venue: Venue,
+    pub route: String,
+    pub input_mint: String,
+    pub input_amount: f64,
+    pub output_mint: String,
+    pub expected_output_amount: f64,
+    pub price_impact: f64,
+}
+
+impl From<&TradeLeg> for QuotedLeg {
+    fn from(leg: &TradeLeg) -> Self {
+        Self {
+            venue: leg.venue,
+            route: leg.route.clone(),
+            input_mint: leg.input_mint.to_string(),
+            input_amount: leg.input_amount,
+            output_mint: leg.output_mint.to_string(),
+            expected_output_amount: leg.expected_output_amount,
+            price_impact: leg.price_impact,
+        }
+    }
+}
+
+/// A trade a restart left without a final state.
+#[derive(Clone, Debug)]
+pub struct UnsettledTrade {
+    pub id: i64,
+    pub trade_id: String,
+    pub trade_uuid: Option<Uuid>,
+    pub state: TradeState,
+    pub mint: Pubkey,
+    pub backend: String,
+    pub expected_profit_usd: Decimal,
+    pub stablebond_price: f64,
+    pub sol_price: f64,
+    pub submitted_at: Option<String>,
+    pub bundle_id: Option<String>,
+    /// Signatures of each submission of the trade's bundle, the first and its resubmissions.
+    pub submissions: Vec<Vec<Signature>>,
+    pub signatures: Vec<Signature>,
+    pub landed_slot: Option<u64>,
+    pub tip_lamports: Option<u64>,
+}
+
+impl UnsettledTrade {
+    /// The trade's expected profit as journaled, without the legs, which aren't.
+    pub fn expected_profit(&self) -> ExpectedProfit {
+        ExpectedProfit {
+            profit_usd: self.expected_profit_usd,
+            stablebond_mint: self.mint,
+            stablebond_price: self.stablebond_price,
+            sol_price: self.sol_price,
+            quoted_at: Instant::now(),
+            legs: Vec::new(),
+        }
+    }
+
+    /// The outcome of the trade once its transactions `signatures` landed in `landed_slot`, to
+    /// reconcile it like any other landed trade.
+    pub fn landed_outcome(
+        &self,
+        signatures: Vec<Signature>,
+        landed_slot: Option<u64>,
+    ) -> BundleOutcome {
+        BundleOutcome {
+            bundle_id: self
+                .bundle_id
+                .clone()
+                .unwrap_or_else(|| self.trade_id.clone()),
+            submitted_bundle_id: self.trade_id.clone(),
+            status: BundleStatusEnum::Landed,
+            landed_slot,
+            signatures,
+            finalized: false,
+            tip_lamports: self.tip_lamports.unwrap_or_default(),
+            expected_profit: Some(self.expected_profit()),
+        }
+    }
+}
+
+/// A trade as it is handed off for execution.
+#[derive(Clone, Debug)]
+pub struct NewTrade {
+    /// Id of the bundle it was first submitted as, or its first signature when sent through
+    /// the RPC or TPU.
+    pub trade_id: String,
+    pub mint: Pubkey,
+    pub direction: TradeDirection,
+    pub strategy: &'static str,
+    pub backend: &'static str,
+    /// UI amount.
+    pub usdc_amount: Decimal,
+    pub expected_profit_usd: Decimal,
+}
+
+/// Every trade the bot finds and what came of it, persisted to SQLite so the history survives
+/// restarts and can be queried offline. Each trade moves through the states of `TradeState`,
+/// which lets the trades a restart interrupted be recovered.
+#[derive(Clone)]
+pub struct TradeJournal {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl TradeJournal {
+    pub fn open(path: &str) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        migrate(&connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Journals a trade a strategy found, returning its journal id. It goes by a provisional
+    /// trade id until it is submitted. Journaling the same `trade_uuid` again returns the
+    /// trade already journaled.
+    pub fn record_discovery(&self, trade: &DiscoveredTrade) -> Result<i64> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let connection = self.connection.lock().unwrap();
+        let inserted = connection.execute(
+            "INSERT INTO trades (trade_id, created_at, updated_at, mint, direction, strategy,
+                backend, usdc_amount, expected_profit_usd, status, stablebond_price, sol_price,
+                trade_uuid, quotes)
+             VALUES (?1, ?2, ?2, ?3, ?4, ?5, '', ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+             ON CONFLICT (trade_uuid) DO NOTHING",
+            params![
+                format!("{:016x}", rand::random::<u64>()),
+                now,
+                trade.mint.to_string(),
+                format!("{:?}", trade.direction),
+                trade.strategy,
+                math::to_f64(trade.usdc_amount),
+                math::to_f64(trade.expected_profit_usd),
+                TradeState::Discovered.as_str(),
+                trade.stablebond_price,
+                trade.sol_price,
+                trade.trade_uuid.to_string(),
+                serde_json::to_string(&trade.quotes)?,
+            ],
+        )?;
+        let id = connection.query_row(
+            "SELECT id FROM trades WHERE trade_uuid = ?1",
+            params![trade.trade_uuid.to_string()],
+            |row| row.get(0),
+        )?;
+        if inserted > 0 {
+            record_transition(&connection, id, TradeState::Discovered, None, &now)?;
+        }
+        Ok(id)
+    }
+
+    /// The quotes the trade `id` was priced at when it was found, absent for trades journaled
+    /// before they were.
+    pub fn quotes(&self, id: i64) -> Result<Option<QuoteSnapshot>> {
+        let quotes: Option<String> = self.connection.lock().unwrap().query_row(
+            "SELECT quotes FROM trades WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(quotes
+            .map(|quotes| serde_json::from_str(&quotes))
+            .transpose()?)
+    }
+
+    /// Moves the trade `id` to `state`. Moving a trade to the state it is in does nothing, so
+    /// that outcomes reported twice are harmless.
+    pub fn transition(&self, id: i64, state: TradeState, detail: Option<&str>) -> Result<()> {
+        transition(&self.connection.lock().unwrap(), id, state, detail)
+    }
+
+    /// Records the hand-off of the trade `id` for execution, under the id of the bundle it
+    /// was submitted as or its first signature. Trades confirmed on submission, with their
+    /// `signatures`, have landed.
+    pub fn record_trade(&self, id: i64, trade: &NewTrade, signatures: &[Signature]) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        submit_trade(&connection, id, trade, signatures)?;
+        if !signatures.is_empty() {
+            transition(&connection, id, TradeState::Landed, None)?;
+        }
+        Ok(())
+    }
+
+    /// Records the trade `id` as failed after only its first transactions, `signatures`,
+    /// landed, so that a restart doesn't find them on-chain and take the trade for landed.
+    pub fn record_partial_trade(
+        &self,
+        id: i64,
+        trade: &NewTrade,
+        signatures: &[Signature],
+        detail: &str,
+    ) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        submit_trade(&connection, id, trade, signatures)?;
+        transition(&connection, id, TradeState::Failed, Some(detail))
+    }
+
+    /// Records a submission of the bundle of the trade `trade_id` as `bundle_id`, the first or
+    /// a resubmission with a higher tip, with the signatures of its transactions.
+    pub fn record_submission(
+        &self,
+        trade_id: &str,
+        bundle_id: &str,
+        signatures: &[Signature],
+    ) -> Result<()> {
+        self.connection.lock().unwrap().execute(
+            "UPDATE trades SET updated_at = ?2, bundle_id = ?3,
+                submitted_signatures = COALESCE(submitted_signatures || ';', '') || ?4
+             WHERE trade_id = ?1",
+            params![
+                trade_id,
+                chrono::Utc::now().to_rfc3339(),
+                bundle_id,
+                join_signatures(signatures),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Records that the trade `id`, which a restart interrupted before its submission was
+    /// journaled, was found on-chain by the memo of its transactions `signatures`.
+    pub fn record_tagged_submission(&self, id: i64, signatures: &[Signature]) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "UPDATE trades SET updated_at = ?2, submitted_at = ?2, submitted_signatures = ?3
+             WHERE id = ?1",
+            params![id, now, join_signatures(signatures)],
+        )?;
+        transition(
+            &connection,
+            id,
+            TradeState::Submitted,
+            Some("Found on-chain by its memo"),
+        )
+    }
+
+    /// Records the final outcome of a bundle, which may have been resubmitted under another
+    /// bundle id with a higher tip.
+    pub fn record_outcome(&self, outcome: &BundleOutcome) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        let Some(id) = trade_journal_id(&connection, &outcome.submitted_bundle_id)? else {
+            return Ok(());
+        };
+        connection.execute(
+            "UPDATE trades SET updated_at = ?2, bundle_id = ?3, signatures = ?4, landed_slot = ?5,
+                tip_lamports = ?6
+             WHERE id = ?1",
+            params![
+                id,
+                chrono::Utc::now().to_rfc3339(),
+                outcome.bundle_id,
+                join_signatures(&outcome.signatures),
+                outcome.landed_slot,
+                outcome.tip_lamports,
+            ],
+        )?;
+        match outcome.status {
+            BundleStatusEnum::Landed => transition(&connection, id, TradeState::Landed, None),
+            status => transition(
+                &connection,
+                id,
+                TradeState::Failed,
+                Some(&format!("Bundle {:?}", status)),
+            ),
+        }
+    }
+
+    /// Records the realized profit of a landed trade, which reconciles it.
+    pub fn record_realized_profit(&self, trade_id: &str, realized: &RealizedProfit) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "UPDATE trades SET updated_at = ?2, realized_profit_usd = ?3, usdc_delta = ?4,
+                stablebond_delta = ?5, lamports_delta = ?6, gross_spread_usd = ?7,
+                jupiter_price_impact_usd = ?8, jupiter_slippage_usd = ?9, etherfuse_fee_usd = ?10,
+                jito_tip_usd = ?11, priority_fee_usd = ?12, base_fee_usd = ?13,
+                oracle_update_usd = ?14, other_cost_usd = ?15
+             WHERE trade_id = ?1",
+            params![
+                trade_id,
+                chrono::Utc::now().to_rfc3339(),
+                math::to_f64(realized.profit_usd),
+                realized.usdc_delta,
+                realized.stablebond_delta,
+                realized.lamports_delta,
+                math::to_f64(realized.attribution.gross_spread_usd),
+                math::to_f64(realized.attribution.jupiter_price_impact_usd),
+                math::to_f64(realized.attribution.jupiter_slippage_usd),
+                math::to_f64(realized.attribution.etherfuse_fee_usd),
+                math::to_f64(realized.attribution.jito_tip_usd),
+                math::to_f64(realized.attribution.priority_fee_usd),
+                math::to_f64(realized.attribution.base_fee_usd),
+                math::to_f64(realized.attribution.oracle_update_usd),
+                math::to_f64(realized.attribution.other_usd),
+            ],
+        )?;
+        if let Some(id) = trade_journal_id(&connection, trade_id)? {
+            transition(&connection, id, TradeState::Reconciled, None)?;
+        }
+        Ok(())
+    }
+
+    /// Trades without a final state, which a restart interrupted.
+    pub fn unsettled_trades(&self) -> Result<Vec<UnsettledTrade>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, trade_id, status, mint, backend, expected_profit_usd, stablebond_price,
+                sol_price, bundle_id, signatures, landed_slot, tip_lamports, submitted_at,
+                submitted_signatures, trade_uuid
+             FROM trades
+             WHERE status NOT IN ('failed', 'timed_out', 'reconciled')
+             ORDER BY id",
+        )?;
+        let trades = statement
+            .query_map([], |row| {
+                Ok(UnsettledTrade {
+                    id: row.get(0)?,
+                    trade_id: row.get(1)?,
+                    trade_uuid: row
+                        .get::<_, Option<String>>(14)?
+                        .map(|trade_uuid| parse_text(14, &trade_uuid))
+                        .transpose()?,
+                    state: parse_text(2, &row.get::<_, String>(2)?)?,
+                    mint: parse_text(3, &row.get::<_, String>(3)?)?,
+                    backend: row.get(4)?,
+                    expected_profit_usd: to_usd(5, row.get(5)?)?,
+                    stablebond_price: row.get::<_, Option<f64>>(6)?.unwrap_or_default(),
+                    sol_price: row.get::<_, Option<f64>>(7)?.unwrap_or_default(),
+                    bundle_id: row.get(8)?,
+                    signatures: parse_signatures(9, row.get::<_, Option<String>>(9)?.as_deref())?,
+                    landed_slot: row.get(10)?,
+                    tip_lamports: row.get(11)?,
+                    submitted_at: row.get(12)?,
+                    submissions: row.get::<_, Option<String>>(13)?.map_or(
+                        Ok(Vec::new()),
+                        |submissions| {
+                            submissions
+                                .split(';')
+                                .map(|signatures| parse_signatures(13, Some(signatures)))
+                                .collect()
+                        },
+                    )?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(trades)
+    }
+
+    /// States the trade `id` went through, oldest first, with when and why.
+    pub fn transitions(&self, id: i64) -> Result<Vec<(TradeState, String, Option<String>)>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT state, at, detail FROM trade_transitions WHERE trade = ?1 ORDER BY id",
+        )?;
+        let transitions = statement
+            .query_map(params![id], |row| {
+                Ok((
+                    parse_text(0, &row.get::<_, String>(0)?)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(transitions)
+    }
+
+    /// Most recent trades first, optionally of a single mint.
+    pub fn recent_trades(&self, limit: usize, mint: Option<&str>) -> Result<Vec<TradeRecord>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT trade_id, created_at, mint, strategy, backend, status, usdc_amount,
+                expected_profit_usd, realized_profit_usd, tip_lamports, trade_uuid
+             FROM trades
+             WHERE submitted_at IS NOT NULL AND (?1 IS NULL OR mint = ?1)
+             ORDER BY created_at DESC
+             LIMIT ?2",
+        )?;
+        let trades = statement
+            .query_map(params![mint, limit as i64], |row| {
+                Ok(TradeRecord {
+                    trade_id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    mint: row.get(2)?,
+                    strategy: row.get(3)?,
+                    backend: row.get(4)?,
+                    status: row.get(5)?,
+                    usdc_amount: to_usd(6, row.get(6)?)?,
+                    expected_profit_usd: to_usd(7, row.get(7)?)?,
+                    realized_profit_usd: row
+                        .get::<_, Option<f64>>(8)?
+                        .map(|usd| to_usd(8, usd))
+                        .transpose()?,
+                    tip_lamports: row.get(9)?,
+                    trade_uuid: row.get(10)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(trades)
+    }
+
+    /// Time and USDC notional of each trade made since `since`, oldest first.
+    pub fn notional_since(&self, since: &str) -> Result<Vec<(String, Decimal)>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT created_at, usdc_amount FROM trades
+             WHERE submitted_at IS NOT NULL AND created_at >= ?1
+             ORDER BY created_at",
+        )?;
+        let notional = statement
+            .query_map(params![since], |row| {
+                Ok((row.get(0)?, to_usd(1, row.get(1)?)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(notional)
+    }
+
+    /// Profit of the trades made since `since` (an RFC 3339 timestamp or date), summed up per
+    /// mint, day or strategy.
+    pub fn pnl(&self, grouping: PnlGrouping, since: Option<&str>) -> Result<Vec<PnlRow>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {} AS key, COUNT(*), SUM(status IN ('landed', 'reconciled')),
+                COALESCE(SUM(realized_profit_usd), 0.0),
+                COALESCE(SUM(CASE WHEN realized_profit_usd IS NULL
+                    AND status IN ('submitted', 'landed') THEN expected_profit_usd END), 0.0),
+                COALESCE(SUM(tip_lamports), 0),
+                COALESCE(SUM(gross_spread_usd), 0.0), COALESCE(SUM(jupiter_price_impact_usd), 0.0),
+                COALESCE(SUM(jupiter_slippage_usd), 0.0), COALESCE(SUM(etherfuse_fee_usd), 0.0),
+                COALESCE(SUM(jito_tip_usd), 0.0), COALESCE(SUM(priority_fee_usd), 0.0),
+                COALESCE(SUM(base_fee_usd), 0.0), COALESCE(SUM(oracle_update_usd), 0.0),
+                COALESCE(SUM(other_cost_usd), 0.0), SUM(usdc_amount)
+             FROM trades
+             WHERE submitted_at IS NOT NULL AND (?1 IS NULL OR created_at >= ?1)
+             GROUP BY key
+             ORDER BY key",
+            grouping.column()
+        ))?;
+        let rows = statement
+            .query_map(params![since], |row| {
+                Ok(PnlRow {
+                    key: row.get(0)?,
+                    trades: row.get(1)?,
+                    landed: row.get(2)?,
+                    realized_profit_usd: to_usd(3, row.get(3)?)?,
+                    unrealized_profit_usd: to_usd(4, row.get(4)?)?,
+                    tip_lamports: row.get(5)?,
+                    notional_usd: to_usd(15, row.get(15)?)?,
+                    attribution: PnlAttribution {
+                        gross_spread_usd: to_usd(6, row.get(6)?)?,
+                        jupiter_price_impact_usd: to_usd(7, row.get(7)?)?,
+                        jupiter_slippage_usd: to_usd(8, row.get(8)?)?,
+                        etherfuse_fee_usd: to_usd(9, row.get(9)?)?,
+                        jito_tip_usd: to_usd(10, row.get(10)?)?,
+                        priority_fee_usd: to_usd(11, row.get(11)?)?,
+                        base_fee_usd: to_usd(12, row.get(12)?)?,
+                        oracle_update_usd: to_usd(13, row.get(13)?)?,
+                        other_usd: to_usd(14, row.get(14)?)?,
+                    },
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+}
+
+/// Amount in USD read from the REAL column `index`. Profits and notional amounts are `Decimal`s
+/// in the journal's API, and stored as REAL for SQLite to sum them.
+fn to_usd(index: usize, usd: f64) -> rusqlite::Result<Decimal> {
+    math::to_decimal(usd)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Real, e.into()))
+}
+
+/// Adds the columns older journals lack. Their trades were journaled once submitted, and the
+/// landed ones whose profit was verified are reconciled.
+fn migrate(connection: &Connection) -> Result<()> {
+    let columns = connection
+        .prepare("SELECT name FROM pragma_table_info('trades')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (column, column_type) in ADDED_COLUMNS {
+        if columns.iter().any(|existing| existing == column) {
+            continue;
+        }
+        connection.execute_batch(&format!(
+            "ALTER TABLE trades ADD COLUMN {} {}",
+            column, column_type
+        ))?;
+        if *column == "submitted_at" {
+            connection.execute_batch(
+                "UPDATE trades SET submitted_at = created_at;
+                 UPDATE trades SET status = 'reconciled'
+                 WHERE status = 'landed' AND realized_profit_usd IS NOT NULL;",
+            )?;
+        }
+    }
+    // Created once the column exists, older trades have none.
+    connection.execute_batch(
+        "CREATE UNIQUE INDEX IF NOT EXISTS trades_trade_uuid ON trades (trade_uuid)",
+    )?;
+    Ok(())
+}
+
+/// Moves the trade `id` to `Submitted` as `trade`, with the `signatures` that landed.
+fn submit_trade(
+    connection: &Connection,
+    id: i64,
+    trade: &NewTrade,
+    signatures: &[Signature],
+) -> Result<()> {
+    connection.execute(
+        "UPDATE trades SET trade_id = ?2, updated_at = ?3, submitted_at = ?3, backend = ?4,
+            usdc_amount = ?5, expected_profit_usd = ?6, signatures = ?7
+         WHERE id = ?1",
+        params![
+            id,
+            trade.trade_id,
+            chrono::Utc::now().to_rfc3339(),
+            trade.backend,
+            math::to_f64(trade.usdc_amount),
+            math::to_f64(trade.expected_profit_usd),
+            join_signatures(signatures),
+        ],
+    )?;
+    transition(connection, id, TradeState::Submitted, Some(trade.backend))
+}
+
+fn transition(
+    connection: &Connection,
+    id: i64,
+    state: TradeState,
+    detail: Option<&str>,
+) -> Result<()> {
+    let current: TradeState = connection
+        .query_row(
+            "SELECT status FROM trades WHERE id = ?1",
+            params![id],
+            |row| row.get::<_, String>(0),
+        )?
+        .parse()?;
+    if current == state {
+        return Ok(());
+    }
+    if !current.can_transition_to(state) {
+        return Err(anyhow!(
+            "Trade {} can't go from {} to {}",
+            id,
+            current,
+            state
+        ));
+    }
+    let now = chrono::Utc::now().to_rfc3339();
+    connection.execute(
+        "UPDATE trades SET status = ?2, updated_at = ?3 WHERE id = ?1",
+        params![id, state.as_str(), now],
+    )?;
+    record_transition(connection, id, state, detail, &now)
+}
+
+fn record_transition(
+    connection: &Connection,
+    id: i64,
+    state: TradeState,
+    detail: Option<&str>,
+    at: &str,
+) -> Result<()> {
+    connection.execute(
+        "INSERT INTO trade_transitions (trade, state, at, detail) VALUES (?1, ?2, ?3, ?4)",
+        params![id, state.as_str(), at, detail],
+    )?;
+    Ok(())
+}
+
+/// Journal id of the trade submitted as `trade_id`.
+fn trade_journal_id(connection: &Connection, trade_id: &str) -> Result<Option<i64>> {
+    Ok(connection
+        .query_row(
+            "SELECT id FROM trades WHERE trade_id = ?1",
+            params![trade_id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Comma separated signatures read from the TEXT column `index`.
+fn parse_signatures(index: usize, signatures: Option<&str>) -> rusqlite::Result<Vec<Signature>> {
+    signatures
+        .filter(|signatures| !signatures.is_empty())
+        .map_or(Ok(Vec::new()), |signatures| {
+            signatures
+                .split(',')
+                .map(|signature| parse_text(index, signature))
+                .collect()
+        })
+}
+
+/// Value parsed from the TEXT column `index`.
+fn parse_text<T>(index: usize, text: &str) -> rusqlite::Result<T>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    text.parse()
+        .map_err(|e: T::Err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, e.into()))
+}
+
+fn join_signatures(signatures: &[Signature]) -> Option<String> {
+    if signatures.is_empty() {
+        return None;
+    }
+    Some(
+        signatures
+            .iter()
+            .map(|signature| signature.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discover(journal: &TradeJournal) -> i64 {
+        discover_uuid(journal, Uuid::new_v4())
+    }
+
+    fn discover_uuid(journal: &TradeJournal, trade_uuid: Uuid) -> i64 {
+        journal
+            .record_discovery(&DiscoveredTrade {
+                trade_uuid,
+                mint: Pubkey::new_unique(),
+                direction: TradeDirection::BuyOnJupiterSellOnEtherfuse,
+                strategy: "BuyOnJupiterSellOnEtherfuse",
+                usdc_amount: Decimal::from(100),
+                expected_profit_usd: Decimal::ONE,
+                stablebond_price: 1.05,
+                sol_price: 150.0,
+                quotes: QuoteSnapshot {
+                    etherfuse_price_per_token: Some(1.05),
+                    ..Default::default()
+                },
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn moves_trades_through_their_lifecycle() {
+        let journal = TradeJournal::open(":memory:").unwrap();
+        let id = discover(&journal);
+        assert_eq!(
+            journal
+                .quotes(id)
+                .unwrap()
+                .unwrap()
+                .etherfuse_price_per_token,
+            Some(1.05)
+        );
+        journal.transition(id, TradeState::Sized, None).unwrap();
+        journal.transition(id, TradeState::Built, None).unwrap();
+        let trade = NewTrade {
+            trade_id: "bundle".to_string(),
+            mint: Pubkey::new_unique(),
+            direction: TradeDirection::BuyOnJupiterSellOnEtherfuse,
+            strategy: "BuyOnJupiterSellOnEtherfuse",
+            backend: "jito",
+            usdc_amount: Decimal::from(100),
+            expected_profit_usd: Decimal::ONE,
+        };
+        journal.record_trade(id, &trade, &[]).unwrap();
+        let resubmission = [Signature::new_unique(), Signature::new_unique()];
+        journal
+            .record_submission("bundle", "bundle", &[Signature::new_unique()])
+            .unwrap();
+        journal
+            .record_submission("bundle", "resubmitted", &resubmission)
+            .unwrap();
+
+        let unsettled = journal.unsettled_trades().unwrap();
+        assert_eq!(unsettled.len(), 1);
+        assert_eq!(unsettled[0].state, TradeState::Submitted);
+        assert_eq!(unsettled[0].trade_id, "bundle");
+        assert_eq!(unsettled[0].bundle_id.as_deref(), Some("resubmitted"));
+        assert_eq!(unsettled[0].submissions.len(), 2);
+        assert_eq!(unsettled[0].submissions[1], resubmission);
+        let outcome = unsettled[0].landed_outcome(Vec::new(), Some(1));
+        journal.record_outcome(&outcome).unwrap();
+        journal.record_outcome(&outcome).unwrap();
+        assert_eq!(
+            journal.unsettled_trades().unwrap()[0].state,
+            TradeState::Landed
+        );
+        journal
+            .record_realized_profit("bundle", &RealizedProfit::default())
+            .unwrap();
+
+        assert!(journal.unsettled_trades().unwrap().is_empty());
+        let states: Vec<TradeState> = journal
+            .transitions(id)
+            .unwrap()
+            .into_iter()
+            .map(|(state, _, _)| state)
+            .collect();
+        assert_eq!(
+            states,
+            [
+                TradeState::Discovered,
+                TradeState::Sized,
+                TradeState::Built,
+                TradeState::Submitted,
+                TradeState::Landed,
+                TradeState::Reconciled
+            ]
+        );
+        assert_eq!(
+            journal.recent_trades(10, None).unwrap()[0].status,
+            "reconciled"
+        );
+    }
+
+    #[test]
+    fn partially_landed_trades_fail_with_their_signatures() {
+        let journal = TradeJournal::open(":memory:").unwrap();
+        let id = discover(&journal);
+        journal.transition(id, TradeState::Sized, None).unwrap();
+        journal.transition(id, TradeState::Built, None).unwrap();
+        let landed = [Signature::new_unique()];
+        let trade = NewTrade {
+            trade_id: landed[0].to_string(),
+            mint: Pubkey::new_unique(),
+            direction: TradeDirection::BuyOnJupiterSellOnEtherfuse,
+            strategy: "BuyOnJupiterSellOnEtherfuse",
+            backend: "rpc",
+            usdc_amount: Decimal::from(100),
+            expected_profit_usd: Decimal::ONE,
+        };
+
+        journal
+            .record_partial_trade(id, &trade, &landed, "Only 1 of 2 transactions landed")
+            .unwrap();
+
+        assert!(journal.unsettled_trades().unwrap().is_empty());
+        let transitions = journal.transitions(id).unwrap();
+        let (state, _, detail) = transitions.last().unwrap();
+        assert_eq!(*state, TradeState::Failed);
+        assert_eq!(detail.as_deref(), Some("Only 1 of 2 transactions landed"));
+        let record = &journal.recent_trades(10, None).unwrap()[0];
+        assert_eq!(record.trade_id, landed[0].to_string());
+        assert_eq!(record.status, "failed");
+    }
+
+    #[test]
+    fn refuses_transitions_out_of_order() {
+        let journal = TradeJournal::open(":memory:").unwrap();
+        let id = discover(&journal);
+
+        assert!(journal.transition(id, TradeState::Submitted, None).is_err());
+        journal
+            .transition(id, TradeState::Failed, Some("Risk limit"))
+            .unwrap();
+        assert!(journal.transition(id, TradeState::Sized, None).is_err());
+        assert!(journal.unsettled_trades().unwrap().is_empty());
+        assert!(journal.recent_trades(10, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn journals_each_trade_uuid_once() {
+        let journal = TradeJournal::open(":memory:").unwrap();
+        let trade_uuid = Uuid::new_v4();
+        let id = discover_uuid(&journal, trade_uuid);
+        assert_eq!(discover_uuid(&journal, trade_uuid), id);
+        assert_eq!(journal.transitions(id).unwrap().len(), 1);
+
+        journal.transition(id, TradeState::Sized, None).unwrap();
+        journal.transition(id, TradeState::Built, None).unwrap();
+        journal
+            .record_tagged_submission(id, &[Signature::new_unique()])
+            .unwrap();
+        let unsettled = journal.unsettled_trades().unwrap();
+        assert_eq!(unsettled[0].trade_uuid, Some(trade_uuid));
+        assert_eq!(unsettled[0].state, TradeState::Submitted);
+        assert_eq!(
+            journal.recent_trades(10, None).unwrap()[0].trade_uuid,
+            Some(trade_uuid.to_string())
+        );
+    }
+}
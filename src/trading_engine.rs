@@ -1,32 +1,56 @@
+use crate::decision_log::{Decision, Skip};
+use crate::error_aggregator::report_error;
 use crate::market_data::MarketData;
 use crate::strategy::{Strategy, StrategyEnum, StrategyResult};
 use solana_sdk::pubkey::Pubkey;
-pub struct TradingEngine {
-    strategies: Vec<StrategyEnum>,
+use tracing::{debug, info_span, Instrument};
+
+/// Runs the strategies of a tick, `StrategyEnum` for the CLI's, any `Strategy` for embedders
+/// and benchmarks running their own.
+pub struct TradingEngine<S = StrategyEnum> {
+    strategies: Vec<S>,
+}
+
+impl<S> Default for TradingEngine<S> {
+    fn default() -> Self {
+        TradingEngine {
+            strategies: Vec::new(),
+        }
+    }
 }
 
-impl TradingEngine {
+impl<S: Strategy> TradingEngine<S> {
     pub fn new() -> Self {
         TradingEngine {
             strategies: Vec::new(),
         }
     }
 
-    pub fn add_strategy(&mut self, strategy: StrategyEnum) -> &mut Self {
+    pub fn add_strategy(&mut self, strategy: S) -> &mut Self {
         self.strategies.push(strategy);
         self
     }
 
+    /// Runs every strategy, recording why the ones that found nothing to trade skipped in
+    /// `decision`.
     pub async fn run_strategies(
         &mut self,
         md: &MarketData,
         stablebond_mint: &Pubkey,
+        decision: &mut Decision,
     ) -> Vec<StrategyResult> {
         let mut results: Vec<crate::strategy::StrategyResult> = Vec::new();
         for strategy in &mut self.strategies {
-            match strategy.process_market_data(md, stablebond_mint).await {
+            let span = info_span!("strategy", name = strategy.name());
+            let result = strategy
+                .process_market_data(md, stablebond_mint)
+                .instrument(span)
+                .await;
+            decision.add_strategy(strategy.name(), &result);
+            match result {
                 Ok(result) => results.push(result),
-                Err(e) => println!("Error processing market data: {:?}", e),
+                Err(e) if e.is::<Skip>() => debug!("{} skipped: {}", strategy.name(), e),
+                Err(e) => report_error("Error processing market data", e),
             }
         }
         results
@@ -1,13 +1,76 @@
 #![allow(dead_code)]
 use anyhow::Result;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{
+    RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    compute_budget::ComputeBudgetInstruction,
-    instruction::Instruction,
+    address_lookup_table::AddressLookupTableAccount,
+    clock::{DEFAULT_MS_PER_SLOT, MAX_PROCESSING_AGE},
+    commitment_config::CommitmentConfig,
+    compute_budget::{self, ComputeBudgetInstruction},
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::{v0, Message, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
     signature::{Keypair, Signature, Signer},
-    transaction::{Transaction, VersionedTransaction},
+    transaction::{VersionedTransaction, MAX_TX_ACCOUNT_LOCKS},
+};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    constants::{
+        APPENDED_IX_COMPUTE_UNITS, BLOCKHASH_REFRESH_INTERVAL_MS, COMPUTE_UNIT_LIMIT_MARGIN,
+        CONFIRMATION_POLL_INTERVAL_MS, DEFAULT_COMPUTE_UNIT_LIMIT, DEFAULT_COMPUTE_UNIT_PRICE,
+        MAX_CACHED_BLOCKHASH_AGE_SLOTS, MAX_COMPUTE_UNIT_LIMIT, TRADE_MEMO_COMPUTE_UNITS,
+        TRADE_MEMO_PREFIX,
+    },
+    error_aggregator::report_error,
+    priority_fee::PriorityFeeEstimator,
 };
 
+/// First byte of a `ComputeBudgetInstruction::SetComputeUnitLimit` instruction.
+const SET_COMPUTE_UNIT_LIMIT_DISCRIMINATOR: u8 = 2;
+/// First byte of a `ComputeBudgetInstruction::SetComputeUnitPrice` instruction.
+const SET_COMPUTE_UNIT_PRICE_DISCRIMINATOR: u8 = 3;
+
+/// Blockhashes and lookup tables of the cluster `rpc_client` points at, shared by the
+/// transaction builders of an engine. Cheap to clone, clones share the cache.
+#[derive(Clone)]
+pub struct TransactionCache {
+    pub rpc_client: Arc<RpcClient>,
+    /// Latest blockhash shared by all transaction builders, so the transactions of a bundle
+    /// share a blockhash and don't each cost an RPC round trip.
+    blockhash: Arc<RwLock<Option<CachedBlockhash>>>,
+    /// Last valid block height of every blockhash we've seen, used to tell how old a
+    /// transaction's blockhash is.
+    last_valid_block_heights: Arc<Mutex<HashMap<Hash, u64>>>,
+    /// Lookup tables our own versioned transactions are compiled against.
+    lookup_tables: Arc<Mutex<HashMap<Pubkey, AddressLookupTableAccount>>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CachedBlockhash {
+    blockhash: Hash,
+    fetched_at: Instant,
+}
+
+impl CachedBlockhash {
+    /// Slots elapsed since the blockhash was fetched, estimated from the slot duration.
+    fn age_slots(&self) -> u64 {
+        self.fetched_at.elapsed().as_millis() as u64 / DEFAULT_MS_PER_SLOT
+    }
+}
+
 pub fn sign_tx(keypair: &Keypair, tx: VersionedTransaction) -> Result<VersionedTransaction> {
     let signed_tx = VersionedTransaction::try_new(tx.message, &[keypair])
         .map_err(|e| anyhow::anyhow!("Failed to create transaction: {}", e))?;
@@ -15,57 +78,1103 @@ pub fn sign_tx(keypair: &Keypair, tx: VersionedTransaction) -> Result<VersionedT
 }
 
 pub async fn build_and_sign_tx(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    additional_signers: &[&Keypair],
+    ixs: &[Instruction],
+    priority_fee_estimator: Option<&PriorityFeeEstimator>,
+) -> Result<VersionedTransaction> {
+    TransactionBuilder::new(cache, keypair)
+        .with_signers(additional_signers)
+        .with_ixs(ixs)
+        .with_priority_fee_estimator(priority_fee_estimator)
+        .build()
+        .await
+}
+
+/// Builds and signs our own transactions. Unless set explicitly, the compute unit limit is
+/// sized by simulating the instructions and the compute unit price comes from the priority-fee
+/// estimator, falling back to `DEFAULT_COMPUTE_UNIT_PRICE`. Transactions without lookup tables are built as legacy
+/// transactions so they can be rebuilt and merged later on.
+pub struct TransactionBuilder<'a> {
+    cache: &'a TransactionCache,
+    rpc_client: &'a RpcClient,
+    payer: &'a Keypair,
+    ixs: Vec<Instruction>,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    compute_unit_limit: Option<u32>,
+    compute_unit_price: Option<u64>,
+    priority_fee_estimator: Option<&'a PriorityFeeEstimator>,
+    simulate: bool,
+    signers: Vec<&'a Keypair>,
+}
+
+impl<'a> TransactionBuilder<'a> {
+    pub fn new(cache: &'a TransactionCache, payer: &'a Keypair) -> Self {
+        Self {
+            cache,
+            rpc_client: &cache.rpc_client,
+            payer,
+            ixs: Vec::new(),
+            lookup_tables: Vec::new(),
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            priority_fee_estimator: None,
+            simulate: false,
+            signers: Vec::new(),
+        }
+    }
+
+    /// Signers required by the instructions besides the payer.
+    pub fn with_signers(mut self, signers: &[&'a Keypair]) -> Self {
+        self.signers.extend_from_slice(signers);
+        self
+    }
+
+    pub fn with_ixs(mut self, ixs: &[Instruction]) -> Self {
+        self.ixs.extend_from_slice(ixs);
+        self
+    }
+
+    pub fn with_lookup_tables(mut self, lookup_tables: &[AddressLookupTableAccount]) -> Self {
+        self.lookup_tables.extend_from_slice(lookup_tables);
+        self
+    }
+
+    pub fn with_compute_unit_limit(mut self, compute_unit_limit: Option<u32>) -> Self {
+        self.compute_unit_limit = compute_unit_limit;
+        self
+    }
+
+    pub fn with_compute_unit_price(mut self, compute_unit_price: Option<u64>) -> Self {
+        self.compute_unit_price = compute_unit_price;
+        self
+    }
+
+    pub fn with_priority_fee_estimator(
+        mut self,
+        priority_fee_estimator: Option<&'a PriorityFeeEstimator>,
+    ) -> Self {
+        self.priority_fee_estimator = priority_fee_estimator;
+        self
+    }
+
+    /// Simulates the signed transaction and fails with the program logs if it would fail.
+    pub fn with_simulation(mut self, simulate: bool) -> Self {
+        self.simulate = simulate;
+        self
+    }
+
+    pub async fn build(&self) -> Result<VersionedTransaction> {
+        let blockhash = self.cache.latest_blockhash().await?;
+        let compute_unit_price = self.estimate_compute_unit_price().await;
+        self.validate(compute_unit_price, blockhash)?;
+        let compute_unit_limit = match self.compute_unit_limit {
+            Some(compute_unit_limit) => compute_unit_limit,
+            None => {
+                self.simulate_compute_unit_limit(compute_unit_price, blockhash)
+                    .await
+            }
+        };
+        let tx = self.sign(compute_unit_limit, compute_unit_price, blockhash)?;
+        if self.simulate {
+            simulate_tx(self.rpc_client, &tx).await?;
+        }
+        Ok(tx)
+    }
+
+    async fn estimate_compute_unit_price(&self) -> u64 {
+        if let Some(compute_unit_price) = self.compute_unit_price {
+            return compute_unit_price;
+        }
+        let priority_fee_estimator = match self.priority_fee_estimator {
+            Some(priority_fee_estimator) => priority_fee_estimator,
+            None => return DEFAULT_COMPUTE_UNIT_PRICE,
+        };
+        let mut writable_accounts: Vec<Pubkey> = self
+            .ixs
+            .iter()
+            .flat_map(|ix| ix.accounts.iter())
+            .filter(|account| account.is_writable)
+            .map(|account| account.pubkey)
+            .collect();
+        writable_accounts.sort_unstable();
+        writable_accounts.dedup();
+        match priority_fee_estimator.estimate(&writable_accounts).await {
+            Ok(compute_unit_price) => compute_unit_price,
+            Err(e) => {
+                warn!(
+                    "Error estimating priority fee ({:?}), using default compute unit price",
+                    e
+                );
+                DEFAULT_COMPUTE_UNIT_PRICE
+            }
+        }
+    }
+
+    /// Signs the transaction without simulating it, e.g. to check its size.
+    pub fn build_unsimulated(&self, blockhash: Hash) -> Result<VersionedTransaction> {
+        self.sign(
+            self.compute_unit_limit.unwrap_or(MAX_COMPUTE_UNIT_LIMIT),
+            self.compute_unit_price
+                .unwrap_or(DEFAULT_COMPUTE_UNIT_PRICE),
+            blockhash,
+        )
+    }
+
+    /// Compute units used in simulation plus a margin. Instructions that depend on earlier
+    /// transactions of the bundle, e.g. redeeming bonds bought in the same bundle, fail in
+    /// isolation, in which case `DEFAULT_COMPUTE_UNIT_LIMIT` is used.
+    async fn simulate_compute_unit_limit(&self, compute_unit_price: u64, blockhash: Hash) -> u32 {
+        let tx = match self.sign(MAX_COMPUTE_UNIT_LIMIT, compute_unit_price, blockhash) {
+            Ok(tx) => tx,
+            Err(_) => return DEFAULT_COMPUTE_UNIT_LIMIT,
+        };
+        let result = self
+            .rpc_client
+            .simulate_transaction_with_config(
+                &tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.rpc_client.commitment()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        match result {
+            Ok(response) => match (response.value.err, response.value.units_consumed) {
+                (None, Some(units_consumed)) => {
+                    let compute_unit_limit =
+                        units_consumed as f64 * (1.0 + COMPUTE_UNIT_LIMIT_MARGIN);
+                    (compute_unit_limit as u32).min(MAX_COMPUTE_UNIT_LIMIT)
+                }
+                (err, _) => {
+                    warn!(
+                        "Simulation failed ({:?}), using default compute unit limit",
+                        err
+                    );
+                    DEFAULT_COMPUTE_UNIT_LIMIT
+                }
+            },
+            Err(e) => {
+                warn!(
+                    "Error simulating transaction ({:?}), using default compute unit limit",
+                    e
+                );
+                DEFAULT_COMPUTE_UNIT_LIMIT
+            }
+        }
+    }
+
+    /// Checks every account the instructions need a signature from has a signer, and that the
+    /// transaction fits in a packet and references no more accounts than a transaction can
+    /// lock, naming the instruction that breaks the limit instead of letting the RPC or block
+    /// engine reject the bundle opaquely.
+    fn validate(&self, compute_unit_price: u64, blockhash: Hash) -> Result<()> {
+        let signers: Vec<Pubkey> = std::iter::once(self.payer)
+            .chain(self.signers.iter().copied())
+            .map(Signer::pubkey)
+            .collect();
+        for (ix_index, ix) in self.ixs.iter().enumerate() {
+            if let Some(account) = ix
+                .accounts
+                .iter()
+                .find(|account| account.is_signer && !signers.contains(&account.pubkey))
+            {
+                return Err(anyhow::anyhow!(
+                    "Instruction {} requires a signature from {}, which isn't a signer",
+                    ix_index,
+                    account.pubkey
+                ));
+            }
+        }
+
+        let tx = self.sign(MAX_COMPUTE_UNIT_LIMIT, compute_unit_price, blockhash)?;
+
+        let size = bincode::serialized_size(&tx)?;
+        if size > PACKET_DATA_SIZE as u64 {
+            let ix_index = self.first_ix_exceeding(compute_unit_price, blockhash, |tx| {
+                bincode::serialized_size(tx).map_or(true, |size| size > PACKET_DATA_SIZE as u64)
+            });
+            return Err(anyhow::anyhow!(
+                "Transaction is {} bytes, above the {} byte limit, from instruction {}",
+                size,
+                PACKET_DATA_SIZE,
+                ix_index
+            ));
+        }
+
+        let num_accounts = num_account_keys(&tx);
+        if num_accounts > MAX_TX_ACCOUNT_LOCKS {
+            let ix_index = self.first_ix_exceeding(compute_unit_price, blockhash, |tx| {
+                num_account_keys(tx) > MAX_TX_ACCOUNT_LOCKS
+            });
+            return Err(anyhow::anyhow!(
+                "Transaction references {} accounts, above the {} account limit, from instruction {}",
+                num_accounts,
+                MAX_TX_ACCOUNT_LOCKS,
+                ix_index
+            ));
+        }
+        Ok(())
+    }
+
+    /// Index of the first instruction that makes the transaction exceed a limit.
+    fn first_ix_exceeding(
+        &self,
+        compute_unit_price: u64,
+        blockhash: Hash,
+        exceeds: impl Fn(&VersionedTransaction) -> bool,
+    ) -> usize {
+        (0..self.ixs.len())
+            .find(|&i| {
+                self.sign_ixs(
+                    &self.ixs[..=i],
+                    MAX_COMPUTE_UNIT_LIMIT,
+                    compute_unit_price,
+                    blockhash,
+                )
+                .map_or(true, |tx| exceeds(&tx))
+            })
+            .unwrap_or(self.ixs.len())
+    }
+
+    fn sign(
+        &self,
+        compute_unit_limit: u32,
+        compute_unit_price: u64,
+        blockhash: Hash,
+    ) -> Result<VersionedTransaction> {
+        self.sign_ixs(&self.ixs, compute_unit_limit, compute_unit_price, blockhash)
+    }
+
+    fn sign_ixs(
+        &self,
+        ixs: &[Instruction],
+        compute_unit_limit: u32,
+        compute_unit_price: u64,
+        blockhash: Hash,
+    ) -> Result<VersionedTransaction> {
+        let mut all_ixs = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit),
+            ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price),
+        ];
+        all_ixs.extend_from_slice(ixs);
+        sign_tx_with_signers(
+            self.payer,
+            &self.signers,
+            &all_ixs,
+            &self.lookup_tables,
+            blockhash,
+        )
+    }
+}
+
+/// Simulates the transaction, failing with the program logs if it would fail on-chain.
+pub async fn simulate_tx(rpc_client: &RpcClient, tx: &VersionedTransaction) -> Result<()> {
+    let result = rpc_client
+        .simulate_transaction_with_config(
+            tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(rpc_client.commitment()),
+                ..Default::default()
+            },
+        )
+        .await?
+        .value;
+    if let Some(err) = result.err {
+        return Err(anyhow::anyhow!(
+            "Simulation error {:?}, logs: {:?}",
+            err,
+            result.logs.unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+/// Simulates the transaction and returns the balance `token_account` is left with.
+pub async fn simulate_token_balance(
     rpc_client: &RpcClient,
+    tx: &VersionedTransaction,
+    token_account: &Pubkey,
+) -> Result<u64> {
+    let result = rpc_client
+        .simulate_transaction_with_config(
+            tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(rpc_client.commitment()),
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: vec![token_account.to_string()],
+                }),
+                ..Default::default()
+            },
+        )
+        .await?
+        .value;
+    if let Some(err) = result.err {
+        return Err(anyhow::anyhow!(
+            "Simulation error {:?}, logs: {:?}",
+            err,
+            result.logs.unwrap_or_default()
+        ));
+    }
+    let data = result
+        .accounts
+        .and_then(|accounts| accounts.into_iter().next().flatten())
+        .and_then(|account| account.data.decode())
+        .ok_or_else(|| {
+            anyhow::anyhow!("Simulation didn't return token account {}", token_account)
+        })?;
+    Ok(StateWithExtensions::<TokenAccount>::unpack(&data)?
+        .base
+        .amount)
+}
+
+/// Number of accounts the transaction references, including the ones loaded from lookup tables.
+fn num_account_keys(tx: &VersionedTransaction) -> usize {
+    let num_loaded: usize = tx
+        .message
+        .address_table_lookups()
+        .unwrap_or_default()
+        .iter()
+        .map(|lookup| lookup.writable_indexes.len() + lookup.readonly_indexes.len())
+        .sum();
+    tx.message.static_account_keys().len() + num_loaded
+}
+
+impl TransactionCache {
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self {
+            rpc_client,
+            blockhash: Arc::default(),
+            last_valid_block_heights: Arc::default(),
+            lookup_tables: Arc::default(),
+        }
+    }
+
+    /// Latest blockhash from the cache, fetching it when the cache is empty or older than
+    /// `MAX_CACHED_BLOCKHASH_AGE_SLOTS`, e.g. when the background refresher is not running.
+    pub async fn latest_blockhash(&self) -> Result<Hash> {
+        let cached = *self.blockhash.read().unwrap();
+        match cached {
+            Some(cached) if cached.age_slots() <= MAX_CACHED_BLOCKHASH_AGE_SLOTS => {
+                Ok(cached.blockhash)
+            }
+            _ => self.refresh_blockhash().await,
+        }
+    }
+
+    /// Fetches the latest blockhash, records its last valid block height and caches it, e.g.
+    /// after the block engine rejected a bundle for an expired blockhash.
+    pub async fn refresh_blockhash(&self) -> Result<Hash> {
+        let (blockhash, last_valid_block_height) = self
+            .rpc_client
+            .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+            .await?;
+        self.record_blockhash(blockhash, last_valid_block_height);
+        *self.blockhash.write().unwrap() = Some(CachedBlockhash {
+            blockhash,
+            fetched_at: Instant::now(),
+        });
+        Ok(blockhash)
+    }
+
+    /// Keeps the blockhash cache fresh in the background.
+    pub fn spawn_blockhash_refresher(&self) {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = cache.refresh_blockhash().await {
+                    report_error("Error refreshing blockhash", e);
+                }
+                tokio::time::sleep(Duration::from_millis(BLOCKHASH_REFRESH_INTERVAL_MS)).await;
+            }
+        });
+    }
+
+    pub fn record_blockhash(&self, blockhash: Hash, last_valid_block_height: u64) {
+        let mut heights = self.last_valid_block_heights.lock().unwrap();
+        // Forget blockhashes that expired long ago.
+        heights.retain(|_, height| *height + (MAX_PROCESSING_AGE as u64) > last_valid_block_height);
+        heights.insert(blockhash, last_valid_block_height);
+    }
+
+    /// Age of the blockhash in blocks, `None` if we never saw it and it's still valid.
+    async fn blockhash_age(&self, blockhash: &Hash, block_height: u64) -> Result<Option<u64>> {
+        let last_valid_block_height = self
+            .last_valid_block_heights
+            .lock()
+            .unwrap()
+            .get(blockhash)
+            .copied();
+        match last_valid_block_height {
+            Some(last_valid_block_height) => Ok(Some(
+                (block_height + MAX_PROCESSING_AGE as u64).saturating_sub(last_valid_block_height),
+            )),
+            None => {
+                let valid = self
+                    .rpc_client
+                    .is_blockhash_valid(blockhash, CommitmentConfig::processed())
+                    .await?;
+                Ok((!valid).then_some(u64::MAX))
+            }
+        }
+    }
+
+    /// Remembers a lookup table so our versioned transactions compiled against it can be
+    /// rebuilt.
+    pub fn record_lookup_table(&self, lookup_table: AddressLookupTableAccount) {
+        self.lookup_tables
+            .lock()
+            .unwrap()
+            .insert(lookup_table.key, lookup_table);
+    }
+
+    /// Instructions and lookup tables of a transaction whose only signer is `keypair`, i.e. one
+    /// we built ourselves and can rebuild. Versioned transactions are only rebuildable when all
+    /// their lookup tables were recorded with `record_lookup_table`.
+    fn own_ixs(
+        &self,
+        keypair: &Keypair,
+        tx: &VersionedTransaction,
+    ) -> Option<(Vec<Instruction>, Vec<AddressLookupTableAccount>)> {
+        own_ixs(&self.lookup_tables.lock().unwrap(), keypair, tx)
+    }
+}
+
+/// Rebuilds the transactions we built ourselves whose blockhash is more than `max_age`
+/// blocks old with a fresh blockhash. Fails if any other transaction, such as a Jupiter
+/// swap, is too old since we can't rebuild it.
+pub async fn refresh_stale_txs(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    txs: &[VersionedTransaction],
+    max_age: u64,
+) -> Result<Vec<VersionedTransaction>> {
+    let block_height = cache.rpc_client.get_block_height().await?;
+    let mut fresh_blockhash = None;
+    let mut refreshed = Vec::with_capacity(txs.len());
+
+    for (i, tx) in txs.iter().enumerate() {
+        let blockhash = *tx.message.recent_blockhash();
+        let age = match cache.blockhash_age(&blockhash, block_height).await? {
+            Some(age) if age > max_age => age,
+            _ => {
+                refreshed.push(tx.clone());
+                continue;
+            }
+        };
+        let (ixs, lookup_tables) = cache.own_ixs(keypair, tx).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Transaction {} blockhash is {} blocks old and the transaction can't be rebuilt",
+                i,
+                age
+            )
+        })?;
+        let blockhash = match fresh_blockhash {
+            Some(blockhash) => blockhash,
+            None => {
+                let blockhash = cache.latest_blockhash().await?;
+                fresh_blockhash = Some(blockhash);
+                blockhash
+            }
+        };
+        info!(
+            "Re-signing transaction {} with blockhash {} blocks old",
+            i, age
+        );
+        refreshed.push(sign_own_tx(keypair, &ixs, &lookup_tables, blockhash)?);
+    }
+    Ok(refreshed)
+}
+
+/// Rebuilds a transaction signed only by `keypair` with `ixs` appended, keeping its blockhash.
+/// Returns `None` when the transaction can't be extended, e.g. versioned transactions built by
+/// Jupiter that rely on lookup tables we don't know, or if the result is too large.
+pub fn append_ixs_to_tx(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    tx: &VersionedTransaction,
+    ixs: &[Instruction],
+) -> Result<Option<VersionedTransaction>> {
+    extend_tx(
+        cache,
+        keypair,
+        tx,
+        ixs,
+        &[],
+        APPENDED_IX_COMPUTE_UNITS.saturating_mul(ixs.len() as u32),
+    )
+}
+
+/// Memo instruction carrying the id of the trade a transaction belongs to, so the trade's
+/// on-chain activity can be matched back to its journal entry.
+pub fn trade_memo_ix(trade_uuid: &Uuid) -> Instruction {
+    spl_memo::build_memo(
+        format!("{}{}", TRADE_MEMO_PREFIX, trade_uuid).as_bytes(),
+        &[],
+    )
+}
+
+/// Tags the transactions signed only by `keypair` with the memo of the trade `trade_uuid`,
+/// leaving the ones that can't be rebuilt or have no room for it as they are. Returns how many
+/// were tagged.
+pub fn tag_txs(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    txs: &mut [VersionedTransaction],
+    trade_uuid: &Uuid,
+) -> Result<usize> {
+    let memo_ix = trade_memo_ix(trade_uuid);
+    let mut tagged = 0;
+    for tx in txs.iter_mut() {
+        if let Some(tagged_tx) = extend_tx(
+            cache,
+            keypair,
+            tx,
+            std::slice::from_ref(&memo_ix),
+            &[],
+            TRADE_MEMO_COMPUTE_UNITS,
+        )? {
+            *tx = tagged_tx;
+            tagged += 1;
+        }
+    }
+    Ok(tagged)
+}
+
+/// Trade id of a transaction's memos as the RPC reports them, e.g. in
+/// `getSignaturesForAddress`: each prefixed with its length and joined by `; `.
+pub fn parse_trade_memo(memos: &str) -> Option<Uuid> {
+    memos.split("; ").find_map(|memo| {
+        let memo = memo.split_once("] ").map_or(memo, |(_, memo)| memo);
+        memo.strip_prefix(TRADE_MEMO_PREFIX)?.parse().ok()
+    })
+}
+
+/// Merges two transactions signed only by `keypair` into one, keeping the compute budget
+/// instructions of the first with the compute unit limits of both added up. Returns `None`
+/// when either transaction can't be rebuilt or the merged transaction is too large.
+pub fn merge_txs(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    first: &VersionedTransaction,
+    second: &VersionedTransaction,
+) -> Result<Option<VersionedTransaction>> {
+    let (second_ixs, second_lookup_tables) = match cache.own_ixs(keypair, second) {
+        Some(own) => own,
+        None => return Ok(None),
+    };
+    let second_compute_unit_limit =
+        compute_unit_limit(&second_ixs).unwrap_or(DEFAULT_COMPUTE_UNIT_LIMIT);
+    // Duplicate compute budget instructions make the whole transaction fail.
+    let second_ixs: Vec<Instruction> = second_ixs
+        .into_iter()
+        .filter(|ix| ix.program_id != compute_budget::id())
+        .collect();
+    extend_tx(
+        cache,
+        keypair,
+        first,
+        &second_ixs,
+        &second_lookup_tables,
+        second_compute_unit_limit,
+    )
+}
+
+fn extend_tx(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    tx: &VersionedTransaction,
+    ixs: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    extra_compute_units: u32,
+) -> Result<Option<VersionedTransaction>> {
+    let (mut all_ixs, mut all_lookup_tables) = match cache.own_ixs(keypair, tx) {
+        Some(own) => own,
+        None => return Ok(None),
+    };
+    for lookup_table in lookup_tables {
+        if !all_lookup_tables
+            .iter()
+            .any(|table| table.key == lookup_table.key)
+        {
+            all_lookup_tables.push(lookup_table.clone());
+        }
+    }
+    // The limit of a simulated transaction leaves no room for more instructions.
+    for ix in all_ixs.iter_mut() {
+        if let Some(limit) = compute_unit_limit(std::slice::from_ref(ix)) {
+            *ix = ComputeBudgetInstruction::set_compute_unit_limit(
+                limit
+                    .saturating_add(extra_compute_units)
+                    .min(MAX_COMPUTE_UNIT_LIMIT),
+            );
+        }
+    }
+    all_ixs.extend_from_slice(ixs);
+    let tx = sign_own_tx(
+        keypair,
+        &all_ixs,
+        &all_lookup_tables,
+        *tx.message.recent_blockhash(),
+    )?;
+
+    if bincode::serialized_size(&tx)? > PACKET_DATA_SIZE as u64 {
+        return Ok(None);
+    }
+    Ok(Some(tx))
+}
+
+/// Combines transactions signed only by `keypair`, e.g. the oracle update and both arb legs,
+/// into a single transaction so the legs can't land separately. Compute unit limits are added
+/// up and the highest compute unit price is kept. Returns `None` when a transaction can't be
+/// rebuilt or the combined transaction exceeds the size or compute limits, in which case the
+/// transactions should be sent as a bundle.
+pub fn compose_atomic_tx(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    txs: &[VersionedTransaction],
+) -> Result<Option<VersionedTransaction>> {
+    let blockhash = match txs.first() {
+        Some(tx) => *tx.message.recent_blockhash(),
+        None => return Ok(None),
+    };
+    let mut total_compute_unit_limit: u64 = 0;
+    let mut max_compute_unit_price: u64 = 0;
+    let mut all_ixs = Vec::new();
+    let mut all_lookup_tables: Vec<AddressLookupTableAccount> = Vec::new();
+    for tx in txs {
+        let (ixs, lookup_tables) = match cache.own_ixs(keypair, tx) {
+            Some(own) => own,
+            None => return Ok(None),
+        };
+        total_compute_unit_limit +=
+            compute_unit_limit(&ixs).unwrap_or(DEFAULT_COMPUTE_UNIT_LIMIT) as u64;
+        max_compute_unit_price = max_compute_unit_price.max(compute_unit_price(&ixs).unwrap_or(0));
+        all_ixs.extend(
+            ixs.into_iter()
+                .filter(|ix| ix.program_id != compute_budget::id()),
+        );
+        for lookup_table in lookup_tables {
+            if !all_lookup_tables
+                .iter()
+                .any(|table| table.key == lookup_table.key)
+            {
+                all_lookup_tables.push(lookup_table);
+            }
+        }
+    }
+    if total_compute_unit_limit > MAX_COMPUTE_UNIT_LIMIT as u64 {
+        return Ok(None);
+    }
+
+    let mut ixs = vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(total_compute_unit_limit as u32),
+        ComputeBudgetInstruction::set_compute_unit_price(max_compute_unit_price),
+    ];
+    ixs.extend(all_ixs);
+    // Compiling fails when there are more accounts than a message can reference.
+    let tx = match sign_own_tx(keypair, &ixs, &all_lookup_tables, blockhash) {
+        Ok(tx) => tx,
+        Err(_) => return Ok(None),
+    };
+    if validate_tx_size(&tx).is_err() {
+        return Ok(None);
+    }
+    Ok(Some(tx))
+}
+
+/// Raises the compute unit price of the transactions signed only by `keypair` to the estimate
+/// for the accounts they write, keeping their blockhash. Bundle legs may pay validators
+/// through the Jito tip alone, so sent through the RPC instead they need a priority fee to
+/// land. Transactions that can't be rebuilt, or already pay at least the estimate, are left as
+/// they are.
+pub async fn apply_priority_fee(
+    cache: &TransactionCache,
+    keypair: &Keypair,
+    txs: &[VersionedTransaction],
+    priority_fee_estimator: &PriorityFeeEstimator,
+) -> Result<Vec<VersionedTransaction>> {
+    let mut priced = Vec::with_capacity(txs.len());
+    for tx in txs {
+        let (ixs, lookup_tables) = match cache.own_ixs(keypair, tx) {
+            Some(own) => own,
+            None => {
+                priced.push(tx.clone());
+                continue;
+            }
+        };
+        let mut writable_accounts: Vec<Pubkey> = ixs
+            .iter()
+            .flat_map(|ix| ix.accounts.iter())
+            .filter(|account| account.is_writable)
+            .map(|account| account.pubkey)
+            .collect();
+        writable_accounts.sort_unstable();
+        writable_accounts.dedup();
+        let estimate = match priority_fee_estimator.estimate(&writable_accounts).await {
+            Ok(compute_unit_price) => compute_unit_price,
+            Err(e) => {
+                warn!(
+                    "Error estimating priority fee ({:?}), using default compute unit price",
+                    e
+                );
+                DEFAULT_COMPUTE_UNIT_PRICE
+            }
+        };
+        if compute_unit_price(&ixs).unwrap_or(0) >= estimate {
+            priced.push(tx.clone());
+            continue;
+        }
+        let mut all_ixs = vec![ComputeBudgetInstruction::set_compute_unit_price(estimate)];
+        all_ixs.extend(ixs.into_iter().filter(|ix| {
+            ix.program_id != compute_budget::id()
+                || compute_unit_price(std::slice::from_ref(ix)).is_none()
+        }));
+        let priced_tx = sign_own_tx(
+            keypair,
+            &all_ixs,
+            &lookup_tables,
+            *tx.message.recent_blockhash(),
+        )?;
+        if validate_tx_size(&priced_tx).is_ok() {
+            priced.push(priced_tx);
+        } else {
+            priced.push(tx.clone());
+        }
+    }
+    Ok(priced)
+}
+
+/// Lamports the transaction pays in priority fees, its compute unit price times its compute
+/// unit limit.
+pub fn priority_fee_lamports(tx: &VersionedTransaction) -> u64 {
+    let keys = tx.message.static_account_keys();
+    let compute_budget_ixs: Vec<Instruction> = tx
+        .message
+        .instructions()
+        .iter()
+        .filter(|ix| keys.get(ix.program_id_index as usize) == Some(&compute_budget::id()))
+        .map(|ix| Instruction::new_with_bytes(compute_budget::id(), &ix.data, Vec::new()))
+        .collect();
+    let compute_unit_price = compute_unit_price(&compute_budget_ixs).unwrap_or(0) as u128;
+    let compute_unit_limit =
+        compute_unit_limit(&compute_budget_ixs).unwrap_or(DEFAULT_COMPUTE_UNIT_LIMIT) as u128;
+    (compute_unit_price * compute_unit_limit / 1_000_000) as u64
+}
+
+/// Compute unit price set by a `SetComputeUnitPrice` instruction among `ixs`, if any.
+fn compute_unit_price(ixs: &[Instruction]) -> Option<u64> {
+    ixs.iter()
+        .filter(|ix| ix.program_id == compute_budget::id())
+        .find_map(|ix| match ix.data.as_slice() {
+            [SET_COMPUTE_UNIT_PRICE_DISCRIMINATOR, price @ ..] => {
+                Some(u64::from_le_bytes(price.try_into().ok()?))
+            }
+            _ => None,
+        })
+}
+
+/// Compute unit limit set by a `SetComputeUnitLimit` instruction among `ixs`, if any.
+pub fn compute_unit_limit(ixs: &[Instruction]) -> Option<u32> {
+    ixs.iter()
+        .filter(|ix| ix.program_id == compute_budget::id())
+        .find_map(|ix| match ix.data.as_slice() {
+            [SET_COMPUTE_UNIT_LIMIT_DISCRIMINATOR, a, b, c, d] => {
+                Some(u32::from_le_bytes([*a, *b, *c, *d]))
+            }
+            _ => None,
+        })
+}
+
+pub fn validate_tx_size(tx: &VersionedTransaction) -> Result<()> {
+    let size = bincode::serialized_size(tx)?;
+    if size > PACKET_DATA_SIZE as u64 {
+        return Err(anyhow::anyhow!(
+            "Transaction is {} bytes, above the {} byte limit",
+            size,
+            PACKET_DATA_SIZE
+        ));
+    }
+    Ok(())
+}
+
+/// Signs a transaction paid by `keypair`, as a legacy transaction when there are no lookup
+/// tables so it stays rebuildable without them.
+fn sign_own_tx(
     keypair: &Keypair,
     ixs: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    sign_tx_with_signers(keypair, &[], ixs, lookup_tables, blockhash)
+}
+
+/// Signs a transaction paid by `payer` that also requires `additional_signers`, e.g. an
+/// ephemeral keypair or a delegate. Signatures are ordered by the signer positions in the
+/// compiled message, whatever the order of `additional_signers`.
+pub fn sign_tx_with_signers(
+    payer: &Keypair,
+    additional_signers: &[&Keypair],
+    ixs: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
 ) -> Result<VersionedTransaction> {
-    let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(100000);
-    let mut ixs_with_priority = vec![priority_fee_ix];
-    ixs_with_priority.extend_from_slice(ixs);
-    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
-    let signing_keypair = keypair;
-    let tx: Transaction = Transaction::new_signed_with_payer(
-        &ixs_with_priority,
-        Some(&keypair.pubkey()),
-        &[&signing_keypair],
-        recent_blockhash,
-    );
-    Ok(tx.into())
+    let mut signers: Vec<&Keypair> = vec![payer];
+    for signer in additional_signers {
+        if !signers.iter().any(|s| s.pubkey() == signer.pubkey()) {
+            signers.push(signer);
+        }
+    }
+    let message = if lookup_tables.is_empty() {
+        VersionedMessage::Legacy(Message::new_with_blockhash(
+            ixs,
+            Some(&payer.pubkey()),
+            &blockhash,
+        ))
+    } else {
+        VersionedMessage::V0(
+            v0::Message::try_compile(&payer.pubkey(), ixs, lookup_tables, blockhash)
+                .map_err(|e| anyhow::anyhow!("Unable to compile transaction message: {:?}", e))?,
+        )
+    };
+    VersionedTransaction::try_new(message, &signers)
+        .map_err(|e| anyhow::anyhow!("Unable to sign transaction: {:?}", e))
+}
+
+/// `TransactionCache::own_ixs` against the lookup tables we know.
+fn own_ixs(
+    known_lookup_tables: &HashMap<Pubkey, AddressLookupTableAccount>,
+    keypair: &Keypair,
+    tx: &VersionedTransaction,
+) -> Option<(Vec<Instruction>, Vec<AddressLookupTableAccount>)> {
+    let header = tx.message.header();
+    let static_keys = tx.message.static_account_keys();
+    if header.num_required_signatures != 1 || static_keys.first() != Some(&keypair.pubkey()) {
+        return None;
+    }
+    let message = match &tx.message {
+        VersionedMessage::Legacy(message) => return Some((decompile_ixs(message), Vec::new())),
+        VersionedMessage::V0(message) => message,
+    };
+
+    let mut lookup_tables = Vec::with_capacity(message.address_table_lookups.len());
+    let mut writable_keys = Vec::new();
+    let mut readonly_keys = Vec::new();
+    for lookup in message.address_table_lookups.iter() {
+        let lookup_table = known_lookup_tables.get(&lookup.account_key)?;
+        for &i in lookup.writable_indexes.iter() {
+            writable_keys.push(*lookup_table.addresses.get(i as usize)?);
+        }
+        for &i in lookup.readonly_indexes.iter() {
+            readonly_keys.push(*lookup_table.addresses.get(i as usize)?);
+        }
+        lookup_tables.push(lookup_table.clone());
+    }
+
+    let num_signed = header.num_required_signatures as usize;
+    let num_writable_signed = num_signed - header.num_readonly_signed_accounts as usize;
+    let num_writable_unsigned = static_keys.len() - header.num_readonly_unsigned_accounts as usize;
+    let num_writable = static_keys.len() + writable_keys.len();
+    let account_keys: Vec<Pubkey> = static_keys
+        .iter()
+        .chain(writable_keys.iter())
+        .chain(readonly_keys.iter())
+        .copied()
+        .collect();
+    let ixs = message
+        .instructions
+        .iter()
+        .map(|ix| Instruction {
+            program_id: account_keys[ix.program_id_index as usize],
+            accounts: ix
+                .accounts
+                .iter()
+                .map(|&i| {
+                    let i = i as usize;
+                    AccountMeta {
+                        pubkey: account_keys[i],
+                        is_signer: i < num_signed,
+                        is_writable: i < num_writable_signed
+                            || (num_signed..num_writable_unsigned).contains(&i)
+                            || (static_keys.len()..num_writable).contains(&i),
+                    }
+                })
+                .collect(),
+            data: ix.data.clone(),
+        })
+        .collect();
+    Some((ixs, lookup_tables))
+}
+
+fn decompile_ixs(message: &Message) -> Vec<Instruction> {
+    message
+        .instructions
+        .iter()
+        .map(|ix| Instruction {
+            program_id: message.account_keys[ix.program_id_index as usize],
+            accounts: ix
+                .accounts
+                .iter()
+                .map(|&i| {
+                    let i = i as usize;
+                    AccountMeta {
+                        pubkey: message.account_keys[i],
+                        is_signer: message.is_signer(i),
+                        is_writable: message.is_writable(i),
+                    }
+                })
+                .collect(),
+            data: ix.data.clone(),
+        })
+        .collect()
+}
+
+/// How a transaction is sent through the RPC and how long to block on its confirmation.
+/// Chained legs only need each leg processed before sending the next, while one-off sends can
+/// afford to wait for a stronger commitment.
+#[derive(Clone, Copy, Debug)]
+pub struct SendConfig {
+    pub skip_preflight: bool,
+    /// Times the RPC node rebroadcasts the transaction, its own default when `None`.
+    pub max_retries: Option<usize>,
+    pub commitment: CommitmentConfig,
+}
+
+impl Default for SendConfig {
+    fn default() -> Self {
+        Self {
+            skip_preflight: false,
+            max_retries: None,
+            commitment: CommitmentConfig::confirmed(),
+        }
+    }
+}
+
+/// Sends the transaction and waits until it reaches the commitment of `config`, or its
+/// blockhash expires.
+pub async fn send_and_confirm_tx(
+    rpc_client: &RpcClient,
+    tx: &VersionedTransaction,
+    config: &SendConfig,
+) -> Result<Signature> {
+    let signature = rpc_client
+        .send_transaction_with_config(
+            tx,
+            RpcSendTransactionConfig {
+                skip_preflight: config.skip_preflight,
+                preflight_commitment: Some(config.commitment.commitment),
+                max_retries: config.max_retries,
+                ..RpcSendTransactionConfig::default()
+            },
+        )
+        .await?;
+    let blockhash = *tx.message.recent_blockhash();
+    loop {
+        match rpc_client
+            .get_signature_status_with_commitment(&signature, config.commitment)
+            .await?
+        {
+            Some(Ok(())) => return Ok(signature),
+            Some(Err(e)) => {
+                return Err(anyhow::anyhow!("Transaction {} failed: {:?}", signature, e))
+            }
+            None => {}
+        }
+        if !rpc_client
+            .is_blockhash_valid(&blockhash, CommitmentConfig::processed())
+            .await?
+        {
+            return Err(anyhow::anyhow!(
+                "Transaction {} expired before reaching {:?} commitment",
+                signature,
+                config.commitment.commitment
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(CONFIRMATION_POLL_INTERVAL_MS)).await;
+    }
 }
 
 pub async fn sign_and_send_tx(
     rpc_client: &RpcClient,
     keypair: &Keypair,
     tx: VersionedTransaction,
+    config: &SendConfig,
 ) -> Result<Signature> {
     let signed_tx = sign_tx(keypair, tx)?;
 
-    match rpc_client.send_and_confirm_transaction(&signed_tx).await {
+    match send_and_confirm_tx(rpc_client, &signed_tx, config).await {
         Ok(signature) => {
-            println!("Signature: {:?}", signature);
+            info!("Signature: {:?}", signature);
             Ok(signature)
         }
         Err(err) => {
-            eprintln!("Error: {:?}", err);
-            Err(err.into())
+            error!("Error: {:?}", err);
+            Err(err)
         }
     }
 }
 
 pub async fn sign_and_send_ixs(
-    rpc_client: &RpcClient,
+    cache: &TransactionCache,
     keypair: &Keypair,
     ixs: &[Instruction],
+    config: &SendConfig,
 ) -> Result<Signature> {
-    let tx = build_and_sign_tx(rpc_client, keypair, ixs).await?;
-    match rpc_client.send_and_confirm_transaction(&tx).await {
+    let tx = build_and_sign_tx(cache, keypair, &[], ixs, None).await?;
+    match send_and_confirm_tx(&cache.rpc_client, &tx, config).await {
         Ok(signature) => {
-            println!("Signature: {:?}", signature);
+            info!("Signature: {:?}", signature);
             Ok(signature)
         }
         Err(err) => {
-            eprintln!("Error: {:?}", err);
-            Err(err.into())
+            error!("Error: {:?}", err);
+            Err(err)
+        }
+    }
+}
+
+pub async fn send_txs_sequentially(
+    rpc_client: &RpcClient,
+    txs: &[VersionedTransaction],
+    config: &SendConfig,
+) -> Result<Vec<Signature>> {
+    let (signatures, result) = send_txs_until_failure(rpc_client, txs, config).await;
+    result.map(|_| signatures)
+}
+
+/// Sends the transactions one after the other until one fails, returning the signatures of
+/// the ones that landed before it, e.g. to handle a trade left with only its first legs.
+pub async fn send_txs_until_failure(
+    rpc_client: &RpcClient,
+    txs: &[VersionedTransaction],
+    config: &SendConfig,
+) -> (Vec<Signature>, Result<()>) {
+    let mut signatures = Vec::with_capacity(txs.len());
+    for (i, tx) in txs.iter().enumerate() {
+        // Each leg depends on the previous one landing, so stop at the first failure.
+        match send_and_confirm_tx(rpc_client, tx, config).await {
+            Ok(signature) => {
+                info!("Signature ({}/{}): {:?}", i + 1, txs.len(), signature);
+                signatures.push(signature);
+            }
+            Err(err) => {
+                let err = anyhow::anyhow!(
+                    "Transaction {}/{} failed, aborting remaining transactions: {:?}",
+                    i + 1,
+                    txs.len(),
+                    err
+                );
+                return (signatures, Err(err));
+            }
         }
     }
+    (signatures, Ok(()))
 }
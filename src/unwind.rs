@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::{
+    constants::{
+        DEFAULT_UNWIND_MAX_HOLDING_HOURS, DEFAULT_UNWIND_STOP_LOSS_USD, STABLEBOND_DECIMALS,
+        USDC_DECIMALS,
+    },
+    etherfuse::EtherfuseClient,
+    jupiter::{JupiterClient, Quote},
+    math::{self, TokenAmountExt, UiAmountExt},
+    position_tracker::{Position, PositionTracker},
+    transaction::{send_txs_sequentially, SendConfig},
+};
+
+/// `[unwind]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnwindConfig {
+    pub enabled: bool,
+    /// Unrealized loss, marked against the best exit, that triggers an unwind.
+    pub stop_loss_usd: Decimal,
+    /// Age of a position that triggers an unwind, whatever its PnL.
+    pub max_holding_hours: f64,
+}
+
+impl Default for UnwindConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stop_loss_usd: DEFAULT_UNWIND_STOP_LOSS_USD,
+            max_holding_hours: DEFAULT_UNWIND_MAX_HOLDING_HOURS,
+        }
+    }
+}
+
+/// Where a position can be sold, and at what price per token.
+#[derive(Clone, Debug)]
+pub enum Exit {
+    Jupiter {
+        price: f64,
+        quote: Quote,
+    },
+    /// Etherfuse's instant redemption at NAV, limited by its USDC liquidity.
+    Etherfuse {
+        price: f64,
+        amount: u64,
+    },
+}
+
+impl Exit {
+    pub fn price(&self) -> f64 {
+        match self {
+            Exit::Jupiter { price, .. } | Exit::Etherfuse { price, .. } => *price,
+        }
+    }
+
+    pub fn venue(&self) -> &'static str {
+        match self {
+            Exit::Jupiter { .. } => "jupiter",
+            Exit::Etherfuse { .. } => "etherfuse",
+        }
+    }
+}
+
+/// Sells stablebond positions that breached the stop loss or were held too long through
+/// whichever venue pays the most, accepting a loss rather than holding depreciating inventory.
+#[derive(Clone)]
+pub struct Unwinder {
+    pub config: UnwindConfig,
+    pub rpc_client: Arc<RpcClient>,
+    pub jupiter_client: JupiterClient,
+    pub etherfuse_client: EtherfuseClient,
+    pub position_tracker: PositionTracker,
+    pub send_config: SendConfig,
+}
+
+impl Unwinder {
+    pub fn new(
+        config: UnwindConfig,
+        rpc_client: Arc<RpcClient>,
+        jupiter_client: JupiterClient,
+        etherfuse_client: EtherfuseClient,
+        position_tracker: PositionTracker,
+    ) -> Self {
+        Self {
+            config,
+            rpc_client,
+            jupiter_client,
+            etherfuse_client,
+            position_tracker,
+            send_config: SendConfig::default(),
+        }
+    }
+
+    pub fn with_send_config(mut self, send_config: SendConfig) -> Self {
+        self.send_config = send_config;
+        self
+    }
+
+    /// Why `position` should be unwound when sold at `exit_price`, if it should.
+    pub fn unwind_reason(&self, position: &Position, exit_price: f64) -> Option<String> {
+        if !self.config.enabled || position.size <= Decimal::ZERO {
+            return None;
+        }
+        let exit_price = math::to_decimal(exit_price).ok()?;
+        let pnl_usd = position.size * (exit_price - position.average_entry_price);
+        if -pnl_usd >= self.config.stop_loss_usd {
+            return Some(format!(
+                "Unrealized loss of ${:.2} breached the stop loss of ${:.2}",
+                -pnl_usd, self.config.stop_loss_usd
+            ));
+        }
+        let held_hours = position
+            .opened_at
+            .as_deref()
+            .and_then(|opened_at| chrono::DateTime::parse_from_rfc3339(opened_at).ok())
+            .map(|opened_at| {
+                (chrono::Utc::now() - opened_at.with_timezone(&chrono::Utc)).num_minutes() as f64
+                    / 60.0
+            })?;
+        if held_hours >= self.config.max_holding_hours {
+            return Some(format!(
+                "Position held for {:.1} hours, longer than {:.1} hours",
+                held_hours, self.config.max_holding_hours
+            ));
+        }
+        None
+    }
+
+    /// Best exit for `holdings` of `mint`, comparing the Jupiter quote for the whole position
+    /// with redeeming what Etherfuse's liquidity allows at `nav`.
+    pub fn best_exit(
+        &self,
+        holdings: u64,
+        nav: f64,
+        sell_liquidity_usdc_amount: Option<u64>,
+        jupiter_quote: Option<(f64, Quote)>,
+    ) -> Option<Exit> {
+        let redeemable = sell_liquidity_usdc_amount
+            .and_then(|liquidity| {
+                (liquidity.to_ui_amount(USDC_DECIMALS) / nav)
+                    .to_token_amount_floor(STABLEBOND_DECIMALS)
+                    .ok()
+            })
+            .unwrap_or(0)
+            .min(holdings);
+        let etherfuse = (redeemable > 0).then_some(Exit::Etherfuse {
+            price: nav,
+            amount: redeemable,
+        });
+        let jupiter = jupiter_quote.map(|(price, quote)| Exit::Jupiter { price, quote });
+        // Only a full redemption beats selling the whole position on Jupiter at a better price.
+        match (jupiter, etherfuse) {
+            (Some(jupiter), Some(Exit::Etherfuse { price, amount }))
+                if amount < holdings || jupiter.price() >= price =>
+            {
+                Some(jupiter)
+            }
+            (jupiter, etherfuse) => etherfuse.or(jupiter),
+        }
+    }
+
+    /// Sells the position through `exit` and records the fill.
+    pub async fn unwind(
+        &mut self,
+        mint: &Pubkey,
+        holdings: u64,
+        exit: Exit,
+    ) -> Result<Vec<Signature>> {
+        let price = math::to_decimal(exit.price())?;
+        let venue = exit.venue();
+        let (tx, amount) = match exit {
+            Exit::Jupiter { quote, .. } => {
+                (self.jupiter_client.jupiter_swap_tx(quote).await?, holdings)
+            }
+            Exit::Etherfuse { amount, .. } => (
+                self.etherfuse_client
+                    .instant_bond_redemption_tx(amount, *mint)
+                    .await?,
+                amount,
+            ),
+        };
+        let signatures = send_txs_sequentially(&self.rpc_client, &[tx], &self.send_config)
+            .await
+            .map_err(|e| anyhow!("Error unwinding through {}: {}", venue, e))?;
+        if amount < holdings {
+            warn!("Only part of the position could be unwound");
+        }
+        let amount = amount.to_ui_decimal(STABLEBOND_DECIMALS);
+        self.position_tracker.record_fill(mint, -amount, price);
+        info!(venue, %amount, %price, "Unwound position");
+        Ok(signatures)
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tracing::{info, warn};
+
+use crate::{
+    constants::{
+        COINGECKO_USDC_PRICE_URL, DEFAULT_MAX_USDC_DEPEG_BPS, DEFAULT_USDC_PEG_CHECK_INTERVAL_SECS,
+        PYTH_USDC_PRICE_URL,
+    },
+    error::{ArbResult, PriceError},
+    http::default_http_client,
+    notifier::{Notifications, Severity},
+    rate_limiter::{Endpoint, RateLimiter},
+    risk::{self, GLOBAL_SCOPE},
+    sol_price::{median, parse_pyth_price},
+};
+
+/// APIs quoting USDC in USD.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsdcPriceSource {
+    /// Coingecko's simple price API, aggregated across exchanges.
+    Coingecko,
+    /// Pyth's USDC/USD feed, through the Hermes API.
+    Pyth,
+}
+
+impl UsdcPriceSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UsdcPriceSource::Coingecko => "Coingecko",
+            UsdcPriceSource::Pyth => "Pyth",
+        }
+    }
+
+    /// The rate limited endpoint the source is called through.
+    pub fn endpoint(&self) -> Endpoint {
+        match self {
+            UsdcPriceSource::Coingecko => Endpoint::Coingecko,
+            UsdcPriceSource::Pyth => Endpoint::Pyth,
+        }
+    }
+
+    fn url(&self) -> &'static str {
+        match self {
+            UsdcPriceSource::Coingecko => COINGECKO_USDC_PRICE_URL,
+            UsdcPriceSource::Pyth => PYTH_USDC_PRICE_URL,
+        }
+    }
+
+    /// Price in the source's response, or why there is none.
+    fn parse(&self, v: &JsonValue) -> Result<f64, String> {
+        match self {
+            UsdcPriceSource::Coingecko => v["usd-coin"]["usd"]
+                .as_f64()
+                .ok_or_else(|| format!("No USDC price in Coingecko response: {}", v)),
+            UsdcPriceSource::Pyth => parse_pyth_price(v, "USDC"),
+        }
+    }
+}
+
+/// `[usdc_peg]` section of the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsdcPegConfig {
+    pub enabled: bool,
+    /// How far off $1 USDC may trade, in basis points, before trading is halted.
+    pub max_deviation_bps: f64,
+    pub check_interval_secs: u64,
+    pub sources: Vec<UsdcPriceSource>,
+}
+
+impl Default for UsdcPegConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_deviation_bps: DEFAULT_MAX_USDC_DEPEG_BPS,
+            check_interval_secs: DEFAULT_USDC_PEG_CHECK_INTERVAL_SECS,
+            sources: vec![UsdcPriceSource::Coingecko, UsdcPriceSource::Pyth],
+        }
+    }
+}
+
+impl UsdcPegConfig {
+    /// Why trading should halt at a USDC `price`, if it should.
+    pub fn depeg(&self, price: f64) -> Option<String> {
+        let deviation_bps = (price - 1.0).abs() * 10_000.0;
+        (deviation_bps > self.max_deviation_bps).then(|| {
+            format!(
+                "USDC trades at ${:.4}, {:.0} bps off its peg, more than {:.0} bps",
+                price, deviation_bps, self.max_deviation_bps
+            )
+        })
+    }
+}
+
+/// Reads the USDC price from one API.
+#[derive(Clone)]
+pub struct UsdcPriceClient {
+    pub source: UsdcPriceSource,
+    pub rate_limiter: Option<RateLimiter>,
+    pub http_client: ClientWithMiddleware,
+}
+
+impl UsdcPriceClient {
+    pub fn new(source: UsdcPriceSource) -> Self {
+        Self {
+            source,
+            rate_limiter: None,
+            http_client: default_http_client(),
+        }
+    }
+
+    pub fn with_http_client(mut self, http_client: ClientWithMiddleware) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub async fn usdc_price(&self) -> ArbResult<f64> {
+        let provider = self.source.name();
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.wait_if_needed().await;
+        }
+        let response = self
+            .http_client
+            .get(self.source.url())
+            .send()
+            .await
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|e| PriceError::Request {
+                provider,
+                message: e.to_string(),
+            })?;
+        let invalid_response = |message| PriceError::InvalidResponse { provider, message };
+        let v: JsonValue = response
+            .json()
+            .await
+            .map_err(|e| invalid_response(e.to_string()))?;
+        let price = self.source.parse(&v).map_err(invalid_response)?;
+        if !price.is_finite() || price <= 0.0 {
+            return Err(invalid_response(format!("USDC price of {}", price)).into());
+        }
+        Ok(price)
+    }
+}
+
+/// Every price and profit the bot computes is in USDC taken at $1, so a USDC de-peg would
+/// misprice every trade alike. Cross-checks the USDC price across several sources, and halts
+/// every mint through the kill switch when their median is off the peg by more than
+/// `max_deviation_bps`, until an operator resumes trading.
+#[derive(Clone)]
+pub struct UsdcPegMonitor {
+    pub config: UsdcPegConfig,
+    pub sources: Vec<UsdcPriceClient>,
+    pub kill_switch_file: String,
+    pub notifications: Notifications,
+}
+
+impl UsdcPegMonitor {
+    pub fn new(
+        config: UsdcPegConfig,
+        sources: Vec<UsdcPriceClient>,
+        kill_switch_file: String,
+    ) -> Self {
+        Self {
+            config,
+            sources,
+            kill_switch_file,
+            notifications: Notifications::new(),
+        }
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Median USDC price of the sources that answer.
+    pub async fn usdc_price(&self) -> Result<f64> {
+        let results = join_all(self.sources.iter().map(|source| source.usdc_price())).await;
+        let mut prices = Vec::new();
+        for (source, result) in self.sources.iter().zip(results) {
+            match result {
+                Ok(price) => prices.push(price),
+                Err(e) => warn!(
+                    "Error fetching USDC price from {}: {}",
+                    source.source.name(),
+                    e
+                ),
+            }
+        }
+        median(&mut prices).ok_or_else(|| {
+            anyhow!(
+                "No USDC price available: none of {} sources answered",
+                self.sources.len()
+            )
+        })
+    }
+
+    /// Checks the USDC price, halting every mint when it is off its peg, and returns it. A halt
+    /// for another reason is left as it is.
+    pub async fn check(&self) -> Result<f64> {
+        let price = self.usdc_price().await?;
+        info!("USDC price of ${:.4}", price);
+        if let Some(reason) = self.config.depeg(price) {
+            if !risk::halts(&self.kill_switch_file).contains_key(GLOBAL_SCOPE) {
+                risk::pause(&self.kill_switch_file, GLOBAL_SCOPE, &reason)?;
+                warn!("Halted trading: {}", reason);
+                self.notifications
+                    .notify(Severity::Critical, format!("Halted trading: {}", reason));
+            }
+        }
+        Ok(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn halts_past_the_deviation_either_side_of_the_peg() {
+        let config = UsdcPegConfig {
+            max_deviation_bps: 50.0,
+            ..Default::default()
+        };
+
+        assert_eq!(config.depeg(1.0), None);
+        assert_eq!(config.depeg(1.004), None);
+        assert_eq!(config.depeg(0.996), None);
+        assert!(config.depeg(1.006).is_some());
+        assert!(config.depeg(0.97).is_some());
+    }
+
+    #[test]
+    fn parses_the_coingecko_usdc_price() {
+        let source = UsdcPriceSource::Coingecko;
+
+        assert_eq!(
+            source.parse(&json!({"usd-coin": {"usd": 0.9998}})),
+            Ok(0.9998)
+        );
+        assert!(source.parse(&json!({"solana": {"usd": 150.0}})).is_err());
+    }
+}
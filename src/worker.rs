@@ -0,0 +1,812 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use crate::{
+    account_cache::AccountCache,
+    approval::ApprovalGate,
+    balance_reservations::BalanceReservations,
+    bundle_dedup::BundleDedup,
+    constants::{CRANKED_FEED_TTL_SECS, STABLEBOND_DECIMALS, USDC_DECIMALS},
+    control::ControlPlane,
+    decision_log::{Decision, DecisionLog, SkipReason},
+    error::{ArbError, ExecutionError},
+    error_aggregator::report_error,
+    etherfuse::EtherfuseClient,
+    event_sink::{Event, EventSink},
+    execution::{Execution, ExecutionBackend, ExecutionPolicy},
+    jito::{JitoClient, JitoError},
+    jupiter::JupiterClient,
+    leg_failure::{LegFailureHandler, StrandedLeg},
+    market_data::{MarketData, MarketDataBuilder},
+    math::{self, TokenAmountExt},
+    mint_blacklist::MintBlacklist,
+    notifier::{Notifications, Severity},
+    pool_state::PoolStateReader,
+    position_tracker::PositionTracker,
+    priority_fee::PriorityFeeEstimator,
+    profile::ActiveProfile,
+    profit_verification::{ExpectedProfit, ProfitVerifier},
+    quote_budget::QuoteBudget,
+    risk::RiskEngine,
+    sol_price::SolPriceOracle,
+    spread_history::{AdaptiveIntervalConfig, SpreadHistory},
+    strategy::{
+        BuyOnEtherfuseSellOnJupiter, BuyOnJupiterSellOnEtherfuse, StrategyEnum, StrategyResult,
+    },
+    switchboard::SwitchboardClient,
+    trade_chunking::{ChunkedTrade, TradeChunker},
+    trade_journal::{DiscoveredTrade, NewTrade, QuoteSnapshot, TradeJournal, TradeState},
+    trading_engine::TradingEngine,
+    transaction::{
+        apply_priority_fee, compose_atomic_tx, send_txs_until_failure, simulate_tx, tag_txs,
+        SendConfig, TransactionCache,
+    },
+    unwind::Unwinder,
+};
+
+/// Oracle feeds updated by recently submitted bundles, shared across the mint workers so that a
+/// feed two mints share isn't updated twice in a row.
+#[derive(Clone, Default)]
+pub struct CrankedFeeds(Arc<Mutex<HashMap<Pubkey, Instant>>>);
+
+impl CrankedFeeds {
+    fn recent(&self) -> HashSet<Pubkey> {
+        let mut feeds = self.0.lock().unwrap();
+        feeds.retain(|_, cranked_at| {
+            cranked_at.elapsed() < Duration::from_secs(CRANKED_FEED_TTL_SECS)
+        });
+        feeds.keys().copied().collect()
+    }
+
+    fn extend(&self, feeds: impl IntoIterator<Item = Pubkey>) {
+        let now = Instant::now();
+        self.0
+            .lock()
+            .unwrap()
+            .extend(feeds.into_iter().map(|feed| (feed, now)));
+    }
+}
+
+/// Everything a mint's worker trades with. Cloned for each mint, the clients sharing their rate
+/// limiters, caches and limits across workers.
+#[derive(Clone)]
+pub struct MintWorker {
+    pub rpc_client: Arc<RpcClient>,
+    pub wallet_keypair: Arc<Keypair>,
+    pub etherfuse_client: EtherfuseClient,
+    pub jito_client: JitoClient,
+    pub switchboard_client: SwitchboardClient,
+    pub jupiter_client: JupiterClient,
+    pub pool_state_reader: PoolStateReader,
+    pub priority_fee_estimator: PriorityFeeEstimator,
+    pub sol_price_oracle: SolPriceOracle,
+    pub balance_reservations: BalanceReservations,
+    pub cranked_feeds: CrankedFeeds,
+    pub position_tracker: PositionTracker,
+    pub unwinder: Unwinder,
+    pub leg_failure_handler: LegFailureHandler,
+    pub buy_on_etherfuse_sell_on_jupiter: BuyOnEtherfuseSellOnJupiter,
+    pub buy_on_jupiter_sell_on_etherfuse: BuyOnJupiterSellOnEtherfuse,
+    pub risk_engine: RiskEngine,
+    pub approval_gate: ApprovalGate,
+    pub mint_blacklist: MintBlacklist,
+    pub bundle_dedup: Arc<Mutex<BundleDedup>>,
+    pub execution_policy: ExecutionPolicy,
+    pub trade_journal: TradeJournal,
+    pub profit_verifier: ProfitVerifier,
+    pub notifications: Notifications,
+    pub event_sink: Option<EventSink>,
+    pub decision_log: Arc<DecisionLog>,
+    pub control: ControlPlane,
+    pub profile: ActiveProfile,
+    pub quote_budget: Option<QuoteBudget>,
+    pub spread_history: SpreadHistory,
+    pub adaptive_interval: AdaptiveIntervalConfig,
+    pub trade_chunker: TradeChunker,
+    pub account_cache: Option<AccountCache>,
+    pub transaction_cache: TransactionCache,
+    pub send_config: SendConfig,
+    pub usdc_mint: Pubkey,
+    pub interval: Duration,
+    pub latency_budget: Duration,
+    pub max_oracle_divergence_bps: u64,
+    pub preflight: bool,
+    pub rpc_fallback: bool,
+}
+
+impl MintWorker {
+    /// Ticks `stablebond_mint` every interval, adapted to its spread's volatility, or earlier
+    /// when an operator triggers a tick or its liquidity changes, or right away to trade the
+    /// next chunk of a trade once the last landed.
+    pub async fn run(mut self, stablebond_mint: Pubkey) {
+        let mint_span = info_span!("mint", mint = %stablebond_mint);
+        let mut ticks = self.control.subscribe_ticks();
+        let mut chunked_trade: Option<ChunkedTrade> = None;
+        for tick in 0u64.. {
+            let tick_span = info_span!(parent: &mint_span, "tick", tick);
+            let mut decision = Decision::new(tick, stablebond_mint);
+            // Held for the tick, so no more mints tick at once than the profile allows.
+            let permit = self.profile.acquire_tick().await;
+            self.tick(&stablebond_mint, &mut decision, &mut chunked_trade)
+                .instrument(tick_span.clone())
+                .await;
+            drop(permit);
+            if let (Some(journal_id), false) = (decision.journal_id, decision.traded) {
+                let detail = decision.detail.as_deref();
+                if let Err(e) =
+                    self.trade_journal
+                        .transition(journal_id, TradeState::Failed, detail)
+                {
+                    tick_span.in_scope(|| report_error("Error journaling trade", e));
+                }
+            }
+            tick_span.in_scope(|| self.decision_log.record(&decision));
+            if let Some(quote_budget) = &self.quote_budget {
+                quote_budget.record_tick(&stablebond_mint, decision.found_opportunity());
+            }
+            let trades = decision.traded as usize;
+            self.control.record_tick(&stablebond_mint, tick, trades);
+            if let Some(event_sink) = &self.event_sink {
+                event_sink.emit(Event::TickCompleted {
+                    tick,
+                    mint: stablebond_mint.to_string(),
+                    trades,
+                });
+            }
+            if !decision.traded {
+                chunked_trade = None;
+            }
+            if let Some(trade) = &mut chunked_trade {
+                let landed = self
+                    .trade_chunker
+                    .wait_for_landing(trade)
+                    .instrument(tick_span.clone())
+                    .await;
+                if landed {
+                    continue;
+                }
+                chunked_trade = None;
+            }
+            let volatility_bps = self.spread_history.volatility_bps(&stablebond_mint);
+            let interval = self
+                .adaptive_interval
+                .interval(self.interval, volatility_bps);
+            tick_span.in_scope(|| {
+                info!(
+                    spread_volatility_bps = volatility_bps,
+                    "Sleeping for {}s",
+                    interval.as_secs()
+                )
+            });
+            let wait_for_tick = self.control.wait_for_tick(&mut ticks, interval);
+            match &self.account_cache {
+                Some(account_cache) => tokio::select! {
+                    _ = wait_for_tick => {}
+                    _ = account_cache.liquidity_changed(&stablebond_mint) => {
+                        tick_span.in_scope(|| info!("Tick triggered by a liquidity change"))
+                    }
+                },
+                None => wait_for_tick.await,
+            }
+        }
+    }
+
+    /// Evaluates `stablebond_mint` once and trades its most profitable opportunity, recording
+    /// why it didn't in `decision` otherwise. Continues `chunked_trade` when there is one.
+    async fn tick(
+        &mut self,
+        stablebond_mint: &Pubkey,
+        decision: &mut Decision,
+        chunked_trade: &mut Option<ChunkedTrade>,
+    ) {
+        let Self {
+            ref rpc_client,
+            ref wallet_keypair,
+            ref etherfuse_client,
+            ref jito_client,
+            ref switchboard_client,
+            ref jupiter_client,
+            ref pool_state_reader,
+            ref priority_fee_estimator,
+            ref sol_price_oracle,
+            ref balance_reservations,
+            ref cranked_feeds,
+            ref position_tracker,
+            ref unwinder,
+            ref leg_failure_handler,
+            ref buy_on_etherfuse_sell_on_jupiter,
+            ref buy_on_jupiter_sell_on_etherfuse,
+            ref risk_engine,
+            ref approval_gate,
+            ref mint_blacklist,
+            ref bundle_dedup,
+            ref mut execution_policy,
+            ref trade_journal,
+            ref profit_verifier,
+            ref notifications,
+            ref event_sink,
+            ref trade_chunker,
+            ref transaction_cache,
+            ref send_config,
+            ref usdc_mint,
+            ref latency_budget,
+            ref max_oracle_divergence_bps,
+            ref preflight,
+            ref rpc_fallback,
+            ..
+        } = *self;
+        if let Some((remaining, reason)) = mint_blacklist.blacklisted(stablebond_mint) {
+            decision.skip(
+                SkipReason::Blacklisted,
+                format!(
+                    "Blacklisted for another {}s after: {}",
+                    remaining.as_secs(),
+                    reason
+                ),
+            );
+            return;
+        }
+        let market_data: MarketData = MarketDataBuilder::new(
+            rpc_client.clone(),
+            wallet_keypair.pubkey(),
+            etherfuse_client.clone(),
+            jito_client.clone(),
+            switchboard_client.clone(),
+        )
+        .with_balance_reservations(balance_reservations.clone())
+        .with_etherfuse_price_per_token(stablebond_mint)
+        .await
+        .with_sell_liquidity_usdc_amount(stablebond_mint)
+        .await
+        .with_purchase_liquidity_stablebond_amount(stablebond_mint)
+        .await
+        .with_stablebond_holdings_token_amount(stablebond_mint)
+        .await
+        .with_usdc_holdings_token_amount()
+        .await
+        .with_update_switchboard_oracle_tx(stablebond_mint, &cranked_feeds.recent())
+        .await
+        .with_oracle_price(stablebond_mint)
+        .await
+        .with_compute_unit_price(priority_fee_estimator, stablebond_mint)
+        .await
+        .with_sol_price(sol_price_oracle)
+        .await
+        .with_pool_state(pool_state_reader, stablebond_mint)
+        .await
+        .build();
+
+        if let (Some(holdings), Some(nav), Some(nav_usd)) = (
+            market_data.stablebond_holdings_token_amount,
+            market_data.etherfuse_price_per_token,
+            market_data
+                .etherfuse_price_per_token
+                .and_then(|nav| math::to_decimal(nav).ok()),
+        ) {
+            position_tracker.reconcile(
+                stablebond_mint,
+                holdings.to_ui_decimal(STABLEBOND_DECIMALS),
+                nav_usd,
+            );
+            let jupiter_quote = if holdings > 0 {
+                match jupiter_client
+                    .clone()
+                    .sell_quote(stablebond_mint, holdings)
+                    .await
+                {
+                    Ok(quote) => Some(quote),
+                    Err(e) => {
+                        report_error("Error quoting the position on Jupiter", e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            let jupiter_bid = jupiter_quote
+                .as_ref()
+                .and_then(|(price, _)| math::to_decimal(*price).ok());
+            position_tracker.mark(stablebond_mint, nav_usd, jupiter_bid);
+
+            let exit = unwinder.best_exit(
+                holdings,
+                nav,
+                market_data.sell_liquidity_usdc_amount,
+                jupiter_quote,
+            );
+            if let (Some(position), Some(exit)) = (position_tracker.position(stablebond_mint), exit)
+            {
+                if let Some(reason) = unwinder.unwind_reason(&position, exit.price()) {
+                    warn!("Unwinding position: {}", reason);
+                    match unwinder
+                        .clone()
+                        .unwind(stablebond_mint, holdings, exit)
+                        .await
+                    {
+                        Ok(_) => {
+                            notifications.notify(
+                                Severity::Warning,
+                                format!("Unwound position in {}: {}", stablebond_mint, reason),
+                            );
+                            decision.skip(SkipReason::Unwound, reason);
+                            return;
+                        }
+                        Err(e) => {
+                            notifications.notify(
+                                Severity::Critical,
+                                format!("Error unwinding position in {}: {}", stablebond_mint, e),
+                            );
+                            report_error("Error unwinding position", e);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(e) = &market_data.switchboard_update_error {
+            decision.skip(
+                SkipReason::OracleStale,
+                format!("The oracle feeds are stale and cannot be updated: {}", e),
+            );
+            return;
+        }
+
+        if let Some(divergence_bps) = market_data.oracle_divergence_bps() {
+            if divergence_bps > *max_oracle_divergence_bps as f64 {
+                decision.skip(
+                    SkipReason::OracleDivergence,
+                    format!(
+                        "Oracle price {} diverges {:.0} bps from Etherfuse exchange \
+                         rate {} ({} slots old)",
+                        market_data.oracle_price.unwrap_or_default(),
+                        divergence_bps,
+                        market_data.etherfuse_exchange_rate.unwrap_or_default(),
+                        market_data.oracle_age_slots.unwrap_or_default()
+                    ),
+                );
+                return;
+            }
+        }
+
+        let sol_price = match market_data.sol_price {
+            Some(price) => {
+                info!("Current SOL price: ${:.2}", price);
+                price
+            }
+            None => {
+                decision.skip(SkipReason::MarketDataUnavailable, "Unable to get SOL price");
+                return;
+            }
+        };
+        if let Some(cost_model) = &market_data.cost_model {
+            let lamports = cost_model.total_lamports();
+            let payment_currency = cost_model
+                .to_payment_currency(lamports)
+                .map_or_else(|| "unknown".to_string(), |cost| format!("{:.4}", cost));
+            info!(
+                "Tip and fees of {} lamports: ${:.4}, {} in the bond's payment currency",
+                lamports,
+                cost_model.to_usd(lamports),
+                payment_currency
+            );
+        }
+
+        let strategies = TradingEngine::new()
+            .add_strategy(StrategyEnum::BuyOnEtherfuseSellOnJupiter(
+                buy_on_etherfuse_sell_on_jupiter.clone(),
+            ))
+            .add_strategy(StrategyEnum::BuyOnJupiterSellOnEtherfuse(
+                buy_on_jupiter_sell_on_etherfuse.clone(),
+            ))
+            .run_strategies(&market_data, stablebond_mint, decision)
+            .await;
+        for strategy in &decision.strategies {
+            if strategy.reason == Some(SkipReason::PreflightFailed) {
+                mint_blacklist.record_failure(
+                    stablebond_mint,
+                    strategy.detail.as_deref().unwrap_or_default(),
+                );
+            }
+        }
+
+        if strategies.is_empty() {
+            decision.skip(SkipReason::NoOpportunity, "No strategy found a trade");
+            return;
+        }
+
+        let mut most_profitable_strategy: StrategyResult = strategies[0].clone();
+        for s in strategies {
+            if s.profit > most_profitable_strategy.profit {
+                most_profitable_strategy = s.clone();
+            }
+        }
+
+        info!("Most profitable strategy: {:?}", most_profitable_strategy);
+        // Without its legs the bundle would only pay the tip.
+        if most_profitable_strategy.txs.is_empty() {
+            decision.skip(
+                SkipReason::BuildFailed,
+                "Strategy built no transactions for the trade",
+            );
+            return;
+        }
+        let usdc_amount = most_profitable_strategy
+            .usdc_amount
+            .to_ui_decimal(USDC_DECIMALS);
+        let trade_uuid = Uuid::new_v4();
+        let journal_id = match trade_journal.record_discovery(&DiscoveredTrade {
+            trade_uuid,
+            mint: *stablebond_mint,
+            direction: most_profitable_strategy.direction,
+            strategy: most_profitable_strategy.strategy,
+            usdc_amount,
+            expected_profit_usd: most_profitable_strategy.profit,
+            stablebond_price: market_data.etherfuse_price_per_token.unwrap_or(0.0),
+            sol_price,
+            quotes: QuoteSnapshot::new(&market_data, &most_profitable_strategy.legs),
+        }) {
+            Ok(journal_id) => Some(journal_id),
+            Err(e) => {
+                report_error("Error journaling trade", e);
+                None
+            }
+        };
+        decision.journal_id = journal_id;
+        let transition = |state: TradeState| {
+            if let Some(journal_id) = journal_id {
+                if let Err(e) = trade_journal.transition(journal_id, state, None) {
+                    report_error("Error journaling trade", e);
+                }
+            }
+        };
+        if let Some(trade) = chunked_trade {
+            if let Some(detail) = trade_chunker.degraded(trade, most_profitable_strategy.profit) {
+                decision.skip(SkipReason::ChunkDegraded, detail);
+                return;
+            }
+        }
+        if let Err(e) = risk_engine.check(stablebond_mint, usdc_amount) {
+            decision.skip(SkipReason::RiskLimit, &e);
+            return;
+        }
+        let approval = match approval_gate
+            .check(
+                stablebond_mint,
+                most_profitable_strategy.strategy,
+                usdc_amount,
+                most_profitable_strategy.profit,
+            )
+            .await
+        {
+            Ok(approval) => approval,
+            Err(e) => {
+                decision.skip(SkipReason::AwaitingApproval, &e);
+                return;
+            }
+        };
+        if !bundle_dedup
+            .lock()
+            .unwrap()
+            .check(&most_profitable_strategy)
+        {
+            decision.skip(
+                SkipReason::Duplicate,
+                "Duplicate of a recently submitted opportunity",
+            );
+            return;
+        }
+        transition(TradeState::Sized);
+        let mut txs = std::mem::take(&mut most_profitable_strategy.txs);
+        txs.splice(0..0, market_data.switchboard_update_txs);
+        // A single transaction removes the risk of one leg landing without the other.
+        let composed = match compose_atomic_tx(transaction_cache, wallet_keypair, &txs) {
+            Ok(Some(tx)) => {
+                info!(
+                    "Composed {} transactions into one atomic transaction",
+                    txs.len()
+                );
+                txs = vec![tx];
+                true
+            }
+            Ok(None) => {
+                info!("Transactions don't fit in one, sending them as a bundle");
+                false
+            }
+            Err(e) => {
+                report_error("Error composing atomic transaction", e);
+                false
+            }
+        };
+        // Tagged once composed, so a composed transaction carries a single memo.
+        match tag_txs(transaction_cache, wallet_keypair, &mut txs, &trade_uuid) {
+            Ok(tagged) => info!(
+                "Tagged {} of {} transactions with trade {}",
+                tagged,
+                txs.len(),
+                trade_uuid
+            ),
+            Err(e) => report_error("Error tagging transactions", e),
+        }
+        if composed && *preflight {
+            if let Err(e) = simulate_tx(rpc_client, &txs[0]).await {
+                let detail = format!("Preflight failed: {}", e);
+                mint_blacklist.record_failure(stablebond_mint, &detail);
+                decision.skip(SkipReason::PreflightFailed, detail);
+                return;
+            }
+        }
+        let expected_profit = ExpectedProfit {
+            profit_usd: most_profitable_strategy.profit,
+            stablebond_mint: *stablebond_mint,
+            stablebond_price: market_data.etherfuse_price_per_token.unwrap_or(0.0),
+            sol_price,
+            quoted_at: most_profitable_strategy.quoted_at,
+            legs: most_profitable_strategy.legs.clone(),
+        };
+        // Checked again by the Jito client once the bundle is signed.
+        let latency = expected_profit.quoted_at.elapsed();
+        if latency > *latency_budget {
+            decision.skip(
+                SkipReason::LatencyBudgetExceeded,
+                format!(
+                    "Quote is {}ms old, over the latency budget of {}ms",
+                    latency.as_millis(),
+                    latency_budget.as_millis()
+                ),
+            );
+            return;
+        }
+        transition(TradeState::Built);
+        let backend = match execution_policy.select(&txs, &expected_profit) {
+            Ok(backend) => backend,
+            Err(e) => {
+                decision.skip(SkipReason::ExecutionFailed, &e);
+                report_error("Error selecting execution backend, skipping", e);
+                return;
+            }
+        };
+        info!("Executing through {}", backend.name());
+        let new_trade = |trade_id: String, backend: &'static str| NewTrade {
+            trade_id,
+            mint: *stablebond_mint,
+            direction: most_profitable_strategy.direction,
+            strategy: most_profitable_strategy.strategy,
+            backend,
+            usdc_amount,
+            expected_profit_usd: expected_profit.profit_usd,
+        };
+        let record_trade = |trade: &NewTrade, signatures: &[Signature]| {
+            if let Some(journal_id) = journal_id {
+                if let Err(e) = trade_journal.record_trade(journal_id, trade, signatures) {
+                    report_error("Error journaling trade", e);
+                }
+            }
+        };
+        // Consumed once the trade went through, so a trade that fails keeps its approval.
+        let consume_approval = || async {
+            if let Some(trade_id) = &approval {
+                if let Err(e) = approval_gate.consume(trade_id).await {
+                    report_error("Error consuming approval", e);
+                }
+            }
+        };
+        let mut result = backend
+            .execute(&txs, expected_profit.clone())
+            .await
+            .map(|execution| (backend.name(), execution));
+        if let Err(e) = &result {
+            if *rpc_fallback
+                && matches!(
+                    jito_error(e),
+                    Some(JitoError::Unavailable(_) | JitoError::RateLimited(_))
+                )
+            {
+                report_error("Error sending bundle", e);
+                warn!("Falling back to sending transactions through the RPC");
+                let txs = match apply_priority_fee(
+                    transaction_cache,
+                    wallet_keypair,
+                    &txs,
+                    priority_fee_estimator,
+                )
+                .await
+                {
+                    Ok(priced_txs) => priced_txs,
+                    Err(e) => {
+                        report_error("Error applying priority fee", e);
+                        txs
+                    }
+                };
+                let (signatures, sent) =
+                    send_txs_until_failure(rpc_client, &txs, send_config).await;
+                match sent {
+                    // Landed like a trade confirmed by the backend.
+                    Ok(()) => result = Ok(("rpc", Execution::Confirmed(signatures))),
+                    Err(e) => {
+                        report_error("Error sending transactions through the RPC", e);
+                        // Journaled as failed with what landed, so that a restart doesn't
+                        // find those transactions by their memo and take the trade for landed.
+                        if let (Some(journal_id), Some(signature)) =
+                            (journal_id, signatures.first())
+                        {
+                            let detail = format!(
+                                "Only {} of {} transactions landed",
+                                signatures.len(),
+                                txs.len()
+                            );
+                            if let Err(e) = trade_journal.record_partial_trade(
+                                journal_id,
+                                &new_trade(signature.to_string(), "rpc"),
+                                &signatures,
+                                &detail,
+                            ) {
+                                report_error("Error journaling trade", e);
+                            }
+                        }
+                        // Sent one after the other, the buying leg may have landed.
+                        if let Some(leg) = StrandedLeg::from_partial_send(
+                            stablebond_mint,
+                            most_profitable_strategy.direction,
+                            &most_profitable_strategy.legs,
+                            txs.len(),
+                            signatures.len(),
+                        ) {
+                            // The USDC was spent even though the trade didn't complete.
+                            risk_engine.record_submission(usdc_amount);
+                            leg_failure_handler
+                                .handle(
+                                    &leg,
+                                    market_data.etherfuse_price_per_token,
+                                    market_data.sell_liquidity_usdc_amount,
+                                )
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+        match result {
+            Ok((backend_name, Execution::Submitted(handle))) => {
+                decision.trade(backend_name, expected_profit.profit_usd);
+                risk_engine.record_submission(usdc_amount);
+                bundle_dedup
+                    .lock()
+                    .unwrap()
+                    .record(&most_profitable_strategy);
+                consume_approval().await;
+                info!("Submitted bundle {}", handle.bundle_id);
+                *chunked_trade = trade_chunker.record_chunk(
+                    chunked_trade.take(),
+                    most_profitable_strategy.capped,
+                    expected_profit.profit_usd,
+                    Some(&handle.bundle_id),
+                );
+                balance_reservations.reserve(
+                    &handle.bundle_id,
+                    usdc_mint,
+                    most_profitable_strategy.usdc_amount,
+                );
+                cranked_feeds.extend(market_data.switchboard_updated_feeds);
+                let trade = new_trade(handle.bundle_id, backend_name);
+                if let Some(event_sink) = &event_sink {
+                    event_sink.emit(Event::trade_submitted(&trade));
+                }
+                record_trade(&trade, &[]);
+                if let Err(e) = trade_journal.record_submission(
+                    &trade.trade_id,
+                    &trade.trade_id,
+                    &handle.signatures,
+                ) {
+                    report_error("Error journaling trade", e);
+                }
+            }
+            Ok((backend_name, Execution::Confirmed(signatures))) => {
+                decision.trade(backend_name, expected_profit.profit_usd);
+                risk_engine.record_submission(usdc_amount);
+                bundle_dedup
+                    .lock()
+                    .unwrap()
+                    .record(&most_profitable_strategy);
+                consume_approval().await;
+                mint_blacklist.record_success(stablebond_mint);
+                *chunked_trade = trade_chunker.record_chunk(
+                    chunked_trade.take(),
+                    most_profitable_strategy.capped,
+                    expected_profit.profit_usd,
+                    None,
+                );
+                cranked_feeds.extend(market_data.switchboard_updated_feeds);
+                // Named after its first transaction, or its memo when there were none to send.
+                let trade_id = signatures
+                    .first()
+                    .map_or_else(|| trade_uuid.to_string(), Signature::to_string);
+                let trade = new_trade(trade_id.clone(), backend_name);
+                if let Some(event_sink) = &event_sink {
+                    event_sink.emit(Event::trade_submitted(&trade));
+                }
+                record_trade(&trade, &signatures);
+                match profit_verifier
+                    .verify_signatures(&trade_id, &signatures, &expected_profit)
+                    .await
+                {
+                    Ok(realized) => {
+                        risk_engine.record_realized_profit(stablebond_mint, realized.profit_usd);
+                        notifications.notify_trade(&expected_profit, &realized, &signatures);
+                        if let Some(event_sink) = &event_sink {
+                            event_sink.emit(Event::trade_landed(
+                                &trade_id,
+                                stablebond_mint,
+                                expected_profit.profit_usd,
+                                realized.profit_usd,
+                                &signatures,
+                            ));
+                        }
+                        if let Err(e) = trade_journal.record_realized_profit(&trade_id, &realized) {
+                            report_error("Error journaling realized profit", e);
+                        }
+                    }
+                    Err(e) => report_error("Error verifying realized profit", e),
+                }
+            }
+            Err(e) => match jito_error(&e) {
+                Some(JitoError::TipBudgetExhausted(_)) => {
+                    decision.skip(SkipReason::RiskLimit, &e);
+                }
+                Some(JitoError::LatencyBudgetExceeded(..)) => {
+                    decision.skip(SkipReason::LatencyBudgetExceeded, &e);
+                }
+                // Not the mint's fault, the next trade is built on a fresh blockhash.
+                Some(JitoError::StaleBlockhash(_)) => {
+                    decision.skip(SkipReason::ExecutionFailed, &e);
+                    report_error("Error sending bundle", e);
+                    if let Err(e) = transaction_cache.refresh_blockhash().await {
+                        report_error("Error refreshing blockhash", e);
+                    }
+                }
+                Some(JitoError::Unavailable(_) | JitoError::RateLimited(_)) => {
+                    decision.skip(SkipReason::ExecutionFailed, &e);
+                    // Already reported when falling back to the RPC.
+                    if !*rpc_fallback {
+                        report_error("Error sending bundle", e);
+                    }
+                }
+                _ => {
+                    decision.skip(SkipReason::ExecutionFailed, &e);
+                    mint_blacklist.record_failure(stablebond_mint, &e);
+                    report_error("Error executing trade, skipping", e);
+                }
+            },
+        }
+    }
+}
+
+/// The Jito error a trade's execution failed with, if any.
+fn jito_error(e: &ArbError) -> Option<&JitoError> {
+    match e {
+        ArbError::Execution(ExecutionError::Jito(e)) => Some(e),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cranked_feeds_are_shared_across_clones() {
+        let cranked_feeds = CrankedFeeds::default();
+        let feed = Pubkey::new_unique();
+        cranked_feeds.clone().extend([feed]);
+
+        assert_eq!(cranked_feeds.recent(), HashSet::from([feed]));
+    }
+}
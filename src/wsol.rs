@@ -0,0 +1,129 @@
+use anyhow::Result;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::info;
+
+use crate::{
+    error_aggregator::report_error,
+    transaction::{sign_and_send_ixs, SendConfig, TransactionCache},
+};
+
+/// Wrapped SOL mint.
+pub fn wsol_mint() -> Pubkey {
+    Pubkey::from_str(crate::constants::SOL_MINT).unwrap()
+}
+
+/// `wallet`'s wrapped SOL account, the one Jupiter wraps and unwraps SOL through.
+pub fn wsol_account(wallet: &Pubkey) -> Pubkey {
+    get_associated_token_address(wallet, &wsol_mint())
+}
+
+/// Wraps `lamports` of `wallet`'s SOL, creating its wrapped SOL account if needed.
+pub fn wrap_sol_ixs(wallet: &Pubkey, lamports: u64) -> Result<Vec<Instruction>> {
+    let account = wsol_account(wallet);
+    Ok(vec![
+        create_associated_token_account_idempotent(wallet, wallet, &wsol_mint(), &spl_token::id()),
+        system_instruction::transfer(wallet, &account, lamports),
+        spl_token::instruction::sync_native(&spl_token::id(), &account)?,
+    ])
+}
+
+/// Closes `wallet`'s wrapped SOL account, unwrapping its balance and rent back to native SOL.
+pub fn unwrap_sol_ix(wallet: &Pubkey) -> Result<Instruction> {
+    Ok(spl_token::instruction::close_account(
+        &spl_token::id(),
+        &wsol_account(wallet),
+        wallet,
+        wallet,
+        &[],
+    )?)
+}
+
+/// Wrapped SOL held by `wallet`, in lamports and without the account's rent. `None` when the
+/// wallet has no wrapped SOL account.
+pub async fn wsol_balance(rpc_client: &RpcClient, wallet: &Pubkey) -> Result<Option<u64>> {
+    let account = rpc_client.get_token_account(&wsol_account(wallet)).await?;
+    Ok(account
+        .map(|account| account.token_amount.amount.parse())
+        .transpose()?)
+}
+
+/// Unwraps whatever wrapped SOL a swap left behind, returning the lamports unwrapped and the
+/// signature. Nothing when the wallet has no wrapped SOL account.
+pub async fn unwrap_residual_wsol(
+    transaction_cache: &TransactionCache,
+    keypair: &Keypair,
+    send_config: &SendConfig,
+) -> Result<Option<(u64, Signature)>> {
+    let wallet = keypair.pubkey();
+    let Some(lamports) = wsol_balance(&transaction_cache.rpc_client, &wallet).await? else {
+        return Ok(None);
+    };
+    let signature = sign_and_send_ixs(
+        transaction_cache,
+        keypair,
+        &[unwrap_sol_ix(&wallet)?],
+        send_config,
+    )
+    .await?;
+    info!(lamports, "Unwrapped residual wrapped SOL");
+    Ok(Some((lamports, signature)))
+}
+
+/// Unwraps residual wrapped SOL every `interval`.
+pub fn spawn_wsol_unwrapper(
+    transaction_cache: TransactionCache,
+    keypair: Keypair,
+    interval: Duration,
+    send_config: SendConfig,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = unwrap_residual_wsol(&transaction_cache, &keypair, &send_config).await {
+                report_error("Error unwrapping residual wrapped SOL", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_token::instruction::TokenInstruction;
+
+    #[test]
+    fn wraps_and_unwraps_through_the_associated_account() {
+        let wallet = Pubkey::new_unique();
+        let account = wsol_account(&wallet);
+
+        let wrap = wrap_sol_ixs(&wallet, 1_000).unwrap();
+        assert_eq!(
+            wrap[1],
+            system_instruction::transfer(&wallet, &account, 1_000)
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&wrap[2].data).unwrap(),
+            TokenInstruction::SyncNative
+        );
+
+        let unwrap = unwrap_sol_ix(&wallet).unwrap();
+        assert_eq!(
+            TokenInstruction::unpack(&unwrap.data).unwrap(),
+            TokenInstruction::CloseAccount
+        );
+        assert_eq!(unwrap.accounts[0].pubkey, account);
+        assert_eq!(unwrap.accounts[1].pubkey, wallet);
+    }
+}
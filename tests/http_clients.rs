@@ -0,0 +1,899 @@
+//! The HTTP clients against a local server replaying captured responses of the Jupiter,
+//! Raydium, Etherfuse, Jito, Coingecko and Pyth APIs, along with the error and malformed payloads they
+//! answer with when things go wrong.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arb::{
+    constants::USDC_MINT,
+    error::{ArbError, PriceError, QuoteError},
+    http::{build_http_client, HttpConfig},
+    jito::fetch_tip_floor,
+    jupiter::JupiterClient,
+    mock::TempDir,
+    providers::SolPriceProvider,
+    rate_limiter::RateLimiter,
+    raydium::RaydiumClient,
+    sol_price::{SolPriceClient, SolPriceSource},
+    ArbResult, EtherfuseClient,
+};
+use reqwest_middleware::ClientWithMiddleware;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::Hash,
+    message::{v0, VersionedMessage},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    system_instruction,
+    transaction::VersionedTransaction,
+};
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const CETES_MINT: &str = "CETES7CKqqKQizuSN6iWQwmTeFRjbJR6Vw2XRKfEDR8f";
+
+fn fixture(name: &str) -> serde_json::Value {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn cetes() -> Pubkey {
+    Pubkey::from_str(CETES_MINT).unwrap()
+}
+
+/// Client retrying transient errors `max_retries` times, with the production backoff.
+fn http_client(max_retries: u32) -> ClientWithMiddleware {
+    let config = HttpConfig {
+        max_retries,
+        ..Default::default()
+    };
+    build_http_client(&config, None).unwrap()
+}
+
+fn rpc_client() -> Arc<RpcClient> {
+    Arc::new(RpcClient::new_mock("succeeds".to_string()))
+}
+
+/// Clients read their keypair file from `temp_dir`, which must outlive them.
+fn jupiter_client(server: &MockServer, temp_dir: &TempDir, max_retries: u32) -> JupiterClient {
+    JupiterClient::new(
+        rpc_client(),
+        server.uri(),
+        temp_dir.keypair_file(&Keypair::new()),
+        RateLimiter::new(1, 100),
+    )
+    .with_http_client(http_client(max_retries))
+}
+
+fn raydium_client(server: &MockServer, temp_dir: &TempDir) -> RaydiumClient {
+    RaydiumClient::new(
+        rpc_client(),
+        server.uri(),
+        temp_dir.keypair_file(&Keypair::new()),
+    )
+    .with_http_client(http_client(0))
+}
+
+fn etherfuse_client(server: &MockServer, temp_dir: &TempDir, max_retries: u32) -> EtherfuseClient {
+    EtherfuseClient::new(
+        rpc_client(),
+        temp_dir.keypair_file(&Keypair::new()),
+        server.uri(),
+    )
+    .with_http_client(http_client(max_retries))
+}
+
+async fn received_requests(server: &MockServer) -> usize {
+    server.received_requests().await.unwrap().len()
+}
+
+#[tokio::test]
+async fn jupiter_buy_quote_parses_captured_quote() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/quote"))
+        .and(query_param("inputMint", USDC_MINT))
+        .and(query_param("outputMint", CETES_MINT))
+        .and(query_param("amount", "1000000000"))
+        .and(query_param("slippageBps", "100"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("jupiter_quote.json")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let (price, quote) = jupiter_client(&server, &temp_dir, 0)
+        .buy_quote(&cetes(), 1_000_000_000)
+        .await
+        .unwrap();
+
+    assert_eq!(quote.in_amount, 1_000_000_000);
+    assert_eq!(quote.out_amount, 18_315_018_315);
+    assert_eq!(quote.other_amount_threshold, 18_131_868_131);
+    assert_eq!(quote.context_slot, 287_654_321);
+    assert_eq!(quote.route(), "Meteora DLMM");
+    assert!((price - 0.0546).abs() < 1e-9, "price {}", price);
+}
+
+#[tokio::test]
+async fn jupiter_quote_surfaces_api_error_payload() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/quote"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+            "error": "Could not find any route",
+            "errorCode": "COULD_NOT_FIND_ANY_ROUTE",
+        })))
+        .mount(&server)
+        .await;
+
+    let err = jupiter_client(&server, &temp_dir, 0)
+        .sell_quote(&cetes(), 1_000_000)
+        .await
+        .unwrap_err();
+
+    match err {
+        QuoteError::Api(message) => assert_eq!(message, "Could not find any route"),
+        _ => panic!("Expected a Jupiter API error, got {:?}", err),
+    }
+    // Client errors aren't transient, so they aren't retried.
+    assert_eq!(received_requests(&server).await, 1);
+}
+
+#[tokio::test]
+async fn jupiter_quote_rejects_malformed_payload() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    let mut quote = fixture("jupiter_quote.json");
+    quote["outAmount"] = serde_json::json!("not a number");
+    Mock::given(method("GET"))
+        .and(path("/quote"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(quote))
+        .mount(&server)
+        .await;
+
+    let result = jupiter_client(&server, &temp_dir, 0)
+        .buy_quote(&cetes(), 1_000_000_000)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn jupiter_quote_retries_server_errors() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/quote"))
+        .respond_with(ResponseTemplate::new(502).set_body_string("Bad Gateway"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/quote"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("jupiter_quote.json")))
+        .mount(&server)
+        .await;
+
+    let (_, quote) = jupiter_client(&server, &temp_dir, 1)
+        .buy_quote(&cetes(), 1_000_000_000)
+        .await
+        .unwrap();
+
+    assert_eq!(quote.out_amount, 18_315_018_315);
+    assert_eq!(received_requests(&server).await, 2);
+}
+
+#[tokio::test]
+async fn jupiter_swap_tx_builds_transaction_from_captured_instructions() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/swap-instructions"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(fixture("jupiter_swap_instructions.json")),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    let mut client = jupiter_client(&server, &temp_dir, 0);
+    let quote = serde_json::from_value(fixture("jupiter_quote.json")).unwrap();
+
+    let tx = client.jupiter_swap_tx(quote).await.unwrap();
+
+    let swap_program = Pubkey::from_str("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4").unwrap();
+    let account_keys = tx.message.static_account_keys();
+    assert!(account_keys.contains(&swap_program));
+    let request = &server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["quoteResponse"]["outAmount"], "18315018315");
+    assert_eq!(body["wrapAndUnwrapSOL"], true);
+}
+
+#[tokio::test]
+async fn jupiter_swap_tx_fails_on_server_error() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/swap-instructions"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("Internal Server Error"))
+        .mount(&server)
+        .await;
+    let mut client = jupiter_client(&server, &temp_dir, 0);
+    let quote = serde_json::from_value(fixture("jupiter_quote.json")).unwrap();
+
+    let err = client.jupiter_swap_tx(quote).await.unwrap_err();
+
+    assert!(err.to_string().contains("500"), "{}", err);
+}
+
+#[tokio::test]
+async fn raydium_buy_quote_parses_captured_quote() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/compute/swap-base-in"))
+        .and(query_param("inputMint", USDC_MINT))
+        .and(query_param("outputMint", CETES_MINT))
+        .and(query_param("amount", "1000000000"))
+        .and(query_param("slippageBps", "100"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("raydium_quote.json")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let (price, quote) = raydium_client(&server, &temp_dir)
+        .buy_quote(&cetes(), 1_000_000_000)
+        .await
+        .unwrap();
+
+    assert_eq!(quote.in_amount, 1_000_000_000);
+    assert_eq!(quote.out_amount, 18_298_261_205);
+    assert_eq!(
+        quote.route,
+        "Raydium 7Ht4mUFq1zVRcQbfRuJ3kPbw3xTmmVeBbUAEdd8rqBnp"
+    );
+    // Raydium quotes price impact in percent.
+    assert!((quote.price_impact_pct - 0.0007).abs() < 1e-12);
+    assert!((price - 0.05465).abs() < 1e-5, "price {}", price);
+}
+
+#[tokio::test]
+async fn raydium_quote_surfaces_api_error_payload() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    // Raydium answers 200 with `success: false` when it can't route the swap.
+    Mock::given(method("GET"))
+        .and(path("/compute/swap-base-in"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "3f1c9d2e-5b7a-4c8e-9f0a-1d2b3c4e5f61",
+            "success": false,
+            "version": "V1",
+            "msg": "ROUTE_NOT_FOUND",
+        })))
+        .mount(&server)
+        .await;
+
+    let err = raydium_client(&server, &temp_dir)
+        .sell_quote(&cetes(), 1_000_000)
+        .await
+        .unwrap_err();
+
+    match err {
+        QuoteError::Api(message) => assert_eq!(message, "ROUTE_NOT_FOUND"),
+        _ => panic!("Expected a Raydium API error, got {:?}", err),
+    }
+}
+
+#[tokio::test]
+async fn raydium_swap_tx_signs_the_transaction_it_returns() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/compute/swap-base-in"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("raydium_quote.json")))
+        .mount(&server)
+        .await;
+    let client = raydium_client(&server, &temp_dir);
+    let signer = read_keypair_file(&client.keypair_filepath).unwrap();
+    let instruction = system_instruction::transfer(&signer.pubkey(), &Pubkey::new_unique(), 1);
+    let message =
+        v0::Message::try_compile(&signer.pubkey(), &[instruction], &[], Hash::default()).unwrap();
+    // Raydium returns the swap unsigned, with a default signature in the payer's slot.
+    let unsigned = VersionedTransaction {
+        signatures: vec![Default::default()],
+        message: VersionedMessage::V0(message),
+    };
+    Mock::given(method("POST"))
+        .and(path("/transaction/swap-base-in"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": "3f1c9d2e-5b7a-4c8e-9f0a-1d2b3c4e5f62",
+            "success": true,
+            "version": "V1",
+            "data": [{ "transaction": base64::encode(bincode::serialize(&unsigned).unwrap()) }],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let (_, quote) = client.buy_quote(&cetes(), 1_000_000_000).await.unwrap();
+
+    let tx = client.raydium_swap_tx(quote).await.unwrap();
+
+    assert_eq!(tx.message, unsigned.message);
+    assert!(tx.verify_with_results().iter().all(|verified| *verified));
+    let request = server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|request| request.url.path() == "/transaction/swap-base-in")
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["wallet"], signer.pubkey().to_string());
+    assert_eq!(body["swapResponse"]["data"]["outputAmount"], "18298261205");
+}
+
+#[tokio::test]
+async fn etherfuse_price_converts_bond_cost_with_exchange_rate() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/lookup/bonds/cost/{}", CETES_MINT)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("etherfuse_bond_cost.json")))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/lookup/exchange_rate/usd_to_mxn"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(fixture("etherfuse_usd_to_mxn.json")),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let price = etherfuse_client(&server, &temp_dir, 0)
+        .get_etherfuse_price(&cetes())
+        .await
+        .unwrap();
+
+    assert!((price - 1.0245 / 18.75).abs() < 1e-12, "price {}", price);
+}
+
+#[tokio::test]
+async fn etherfuse_price_rejects_malformed_bond_cost() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/lookup/bonds/cost/{}", CETES_MINT)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "bond_cost_in_payment_token": "n/a",
+        })))
+        .mount(&server)
+        .await;
+
+    let result = etherfuse_client(&server, &temp_dir, 0)
+        .get_etherfuse_price(&cetes())
+        .await;
+
+    assert!(result.is_err());
+    // Never priced, so the exchange rate isn't fetched.
+    assert_eq!(received_requests(&server).await, 1);
+}
+
+#[tokio::test]
+async fn etherfuse_exchange_rate_rejects_payload_without_rate() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/lookup/exchange_rate/usd_to_mxn"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "usd_to_mxn": "0",
+        })))
+        .mount(&server)
+        .await;
+
+    let err = etherfuse_client(&server, &temp_dir, 0)
+        .get_etherfuse_exchange_rate(cetes())
+        .await
+        .unwrap_err();
+
+    assert!(
+        err.to_string().contains("No valid exchange rate"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn etherfuse_exchange_rate_rejects_unsupported_mint() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+
+    let err = etherfuse_client(&server, &temp_dir, 0)
+        .get_etherfuse_exchange_rate(Pubkey::new_unique())
+        .await
+        .unwrap_err();
+
+    assert!(
+        err.to_string().contains("Unsupported stablebond mint"),
+        "{}",
+        err
+    );
+    assert_eq!(received_requests(&server).await, 0);
+}
+
+#[tokio::test]
+async fn etherfuse_exchange_rate_retries_server_errors() {
+    let temp_dir = TempDir::create("http-test");
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/lookup/exchange_rate/usd_to_mxn"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/lookup/exchange_rate/usd_to_mxn"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(fixture("etherfuse_usd_to_mxn.json")),
+        )
+        .mount(&server)
+        .await;
+
+    let exchange_rate = etherfuse_client(&server, &temp_dir, 1)
+        .get_etherfuse_exchange_rate(cetes())
+        .await
+        .unwrap();
+
+    assert_eq!(exchange_rate, 18.75);
+    assert_eq!(received_requests(&server).await, 2);
+}
+
+#[tokio::test]
+async fn jito_tip_floor_parses_captured_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/bundles/tip_floor"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("jito_tip_floor.json")))
+        .mount(&server)
+        .await;
+    let url = format!("{}/api/v1/bundles/tip_floor", server.uri());
+
+    let tip = fetch_tip_floor(&url, &http_client(0), None).await.unwrap();
+
+    assert_eq!(tip.time, "2024-09-18T14:21:36Z");
+    assert_eq!(tip.lamports(arb::jito::TipPercentile::P50), 10_000);
+    assert_eq!(tip.lamports(arb::jito::TipPercentile::P99), 12_900_000);
+}
+
+#[tokio::test]
+async fn jito_tip_floor_rejects_empty_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/bundles/tip_floor"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+        .mount(&server)
+        .await;
+    let url = format!("{}/api/v1/bundles/tip_floor", server.uri());
+
+    let result = fetch_tip_floor(&url, &http_client(0), None).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn jito_tip_floor_retries_rate_limited_requests() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/bundles/tip_floor"))
+        .respond_with(ResponseTemplate::new(429).set_body_string("Too Many Requests"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/bundles/tip_floor"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("jito_tip_floor.json")))
+        .mount(&server)
+        .await;
+    let url = format!("{}/api/v1/bundles/tip_floor", server.uri());
+
+    let tip = fetch_tip_floor(&url, &http_client(1), None).await.unwrap();
+
+    assert_eq!(tip.ema_landed_tips_50th_percentile, 0.0000123);
+    assert_eq!(received_requests(&server).await, 2);
+}
+
+#[tokio::test]
+async fn jito_tip_floor_fails_once_retries_are_exhausted() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v1/bundles/tip_floor"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    let url = format!("{}/api/v1/bundles/tip_floor", server.uri());
+
+    let err = fetch_tip_floor(&url, &http_client(1), None)
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("500"), "{}", err);
+    assert_eq!(received_requests(&server).await, 2);
+}
+
+async fn get_sol_price(source: SolPriceSource, url: String) -> ArbResult<f64> {
+    SolPriceClient::new(source)
+        .with_url(url)
+        .with_http_client(http_client(0))
+        .sol_price()
+        .await
+}
+
+#[tokio::test]
+async fn coingecko_sol_price_parses_captured_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v3/simple/price"))
+        .and(query_param("ids", "solana"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("coingecko_sol_price.json")))
+        .mount(&server)
+        .await;
+    let url = format!(
+        "{}/api/v3/simple/price?ids=solana&vs_currencies=usd",
+        server.uri()
+    );
+
+    let sol_price = get_sol_price(SolPriceSource::Coingecko, url).await.unwrap();
+
+    assert_eq!(sol_price, 134.27);
+}
+
+#[tokio::test]
+async fn coingecko_sol_price_rejects_payload_without_price() {
+    let server = MockServer::start().await;
+    // What Coingecko answers once the free tier's rate limit is hit.
+    Mock::given(method("GET"))
+        .and(path("/api/v3/simple/price"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "status": {
+                "error_code": 429,
+                "error_message": "You've exceeded the Rate Limit.",
+            },
+        })))
+        .mount(&server)
+        .await;
+    let url = format!("{}/api/v3/simple/price", server.uri());
+
+    let err = get_sol_price(SolPriceSource::Coingecko, url)
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("No SOL price"), "{}", err);
+}
+
+#[tokio::test]
+async fn coingecko_sol_price_rejects_malformed_payload() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/v3/simple/price"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>Bad Gateway</html>"))
+        .mount(&server)
+        .await;
+    let url = format!("{}/api/v3/simple/price", server.uri());
+
+    let result = get_sol_price(SolPriceSource::Coingecko, url).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn jupiter_sol_price_parses_captured_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/price/v2"))
+        .and(query_param(
+            "ids",
+            "So11111111111111111111111111111111111111112",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("jupiter_sol_price.json")))
+        .mount(&server)
+        .await;
+    let url = format!(
+        "{}/price/v2?ids=So11111111111111111111111111111111111111112",
+        server.uri()
+    );
+
+    let sol_price = get_sol_price(SolPriceSource::Jupiter, url).await.unwrap();
+
+    assert_eq!(sol_price, 134.3152885);
+}
+
+/// Hermes answer for the SOL/USD feed, published `age_secs` ago.
+fn pyth_sol_price(age_secs: u64) -> serde_json::Value {
+    let mut response = fixture("pyth_sol_price.json");
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    response["parsed"][0]["price"]["publish_time"] = (now - age_secs).into();
+    response
+}
+
+#[tokio::test]
+async fn pyth_sol_price_parses_captured_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v2/updates/price/latest"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(pyth_sol_price(5)))
+        .mount(&server)
+        .await;
+    let url = format!("{}/v2/updates/price/latest", server.uri());
+
+    let sol_price = get_sol_price(SolPriceSource::Pyth, url).await.unwrap();
+
+    assert!((sol_price - 134.3125).abs() < 1e-9, "{}", sol_price);
+}
+
+#[tokio::test]
+async fn pyth_sol_price_rejects_stale_price() {
+    let server = MockServer::start().await;
+    // What Hermes keeps serving once the feed's publishers stop.
+    Mock::given(method("GET"))
+        .and(path("/v2/updates/price/latest"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(pyth_sol_price(3_600)))
+        .mount(&server)
+        .await;
+    let url = format!("{}/v2/updates/price/latest", server.uri());
+
+    let err = get_sol_price(SolPriceSource::Pyth, url).await.unwrap_err();
+
+    assert!(
+        matches!(err, ArbError::Price(PriceError::InvalidResponse { .. })),
+        "{}",
+        err
+    );
+    assert!(!err.is_transient());
+}
+
+/// The block engine client, which needs the `jito` feature.
+#[cfg(feature = "jito")]
+mod jito_client {
+    use super::*;
+    use arb::{
+        bundle_stats::BundleStats,
+        jito::{BundleOutcome, BundleStatusEnum, JitoError},
+        profit_verification::ExpectedProfit,
+        tip_budget::TipBudget,
+        JitoClient,
+    };
+    use tokio::sync::mpsc::UnboundedReceiver;
+    use wiremock::matchers::body_partial_json;
+
+    fn jito_client(server: &MockServer, temp_dir: &TempDir) -> JitoClient {
+        jito_client_with_outcomes(server, temp_dir).0
+    }
+
+    /// Client along with the receiving end of its bundle outcome channel.
+    fn jito_client_with_outcomes(
+        server: &MockServer,
+        temp_dir: &TempDir,
+    ) -> (JitoClient, UnboundedReceiver<BundleOutcome>) {
+        let jsonrpc_client = jsonrpsee::http_client::HttpClientBuilder::default()
+            .build(server.uri())
+            .unwrap();
+        let (outcome_sender, outcome_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let client = JitoClient::new(
+            rpc_client(),
+            Arc::new(std::sync::RwLock::new(None)),
+            jsonrpc_client,
+            temp_dir.keypair_file(&Keypair::new()),
+            outcome_sender,
+            TipBudget::new(u64::MAX),
+            BundleStats::new(temp_dir.file("bundle_stats.json"), "test".to_string()),
+        );
+        (client, outcome_receiver)
+    }
+
+    /// Answers the JSON-RPC `method` with `result`, at most `times` times when given.
+    async fn mock_jsonrpc(
+        server: &MockServer,
+        method_name: &str,
+        result: serde_json::Value,
+        times: Option<u64>,
+    ) {
+        let mock = Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": method_name}),
+            ))
+            .respond_with(move |request: &wiremock::Request| {
+                let id = request.body_json::<serde_json::Value>().unwrap()["id"].clone();
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": result,
+                }))
+            });
+        match times {
+            Some(times) => mock.up_to_n_times(times).mount(server).await,
+            None => mock.mount(server).await,
+        }
+    }
+
+    #[tokio::test]
+    async fn bundle_missing_from_the_inflight_index_is_still_followed_up() {
+        let temp_dir = TempDir::create("http-test");
+        let server = MockServer::start().await;
+        mock_jsonrpc(
+            &server,
+            "getTipAccounts",
+            serde_json::json!([Pubkey::new_unique().to_string()]),
+            None,
+        )
+        .await;
+        mock_jsonrpc(&server, "sendBundle", serde_json::json!("bundle"), None).await;
+        // Not indexed yet right after `sendBundle`.
+        mock_jsonrpc(
+            &server,
+            "getInflightBundleStatuses",
+            serde_json::json!({"value": [{"status": "Invalid"}]}),
+            Some(1),
+        )
+        .await;
+        mock_jsonrpc(
+            &server,
+            "getInflightBundleStatuses",
+            serde_json::json!({"value": [{"status": "Landed"}]}),
+            None,
+        )
+        .await;
+        mock_jsonrpc(
+            &server,
+            "getBundleStatuses",
+            serde_json::json!({"value": [{
+                "transactions": [],
+                "slot": 42,
+                "confirmation_status": "finalized",
+            }]}),
+            None,
+        )
+        .await;
+        let (mut client, mut outcomes) = jito_client_with_outcomes(&server, &temp_dir);
+
+        client
+            .send_bundle(&[], 1_000, expected_profit())
+            .await
+            .unwrap();
+        let outcome = outcomes.recv().await.unwrap();
+
+        assert_eq!(outcome.status, BundleStatusEnum::Landed);
+        assert_eq!(outcome.landed_slot, Some(42));
+    }
+
+    fn expected_profit() -> ExpectedProfit {
+        ExpectedProfit {
+            profit_usd: rust_decimal::Decimal::ONE,
+            stablebond_mint: cetes(),
+            stablebond_price: 1.0,
+            sol_price: 150.0,
+            quoted_at: std::time::Instant::now(),
+            legs: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn unavailable_when_tip_accounts_cannot_be_fetched() {
+        let temp_dir = TempDir::create("http-test");
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "getTipAccounts"}),
+            ))
+            .respond_with(ResponseTemplate::new(503).set_body_string("Service Unavailable"))
+            .mount(&server)
+            .await;
+
+        let err = jito_client(&server, &temp_dir)
+            .send_bundle(&[], 1_000, expected_profit())
+            .await
+            .unwrap_err();
+
+        // Sent through the RPC fallback rather than dropped as an unknown error.
+        assert!(matches!(err, JitoError::Unavailable(_)), "{:?}", err);
+    }
+
+    /// The error `send_bundle` returns when the block engine answers `sendBundle` with a
+    /// JSON-RPC error.
+    async fn send_bundle_error(code: i32, message: &str) -> JitoError {
+        let temp_dir = TempDir::create("http-test");
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "getTipAccounts"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": [Pubkey::new_unique().to_string()],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"method": "sendBundle"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": {"code": code, "message": message},
+            })))
+            .mount(&server)
+            .await;
+
+        let err = jito_client(&server, &temp_dir)
+            .send_bundle(&[], 1_000, expected_profit())
+            .await
+            .unwrap_err();
+        assert_eq!(received_requests(&server).await, 2);
+        err
+    }
+
+    #[tokio::test]
+    async fn unknown_block_engine_error_is_unavailable() {
+        let err = send_bundle_error(-32603, "Internal error").await;
+
+        assert!(matches!(err, JitoError::Unavailable(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn transient_errors_mentioning_the_bundle_are_unavailable() {
+        for message in [
+            "bundle forwarding unavailable",
+            "failed to process transaction",
+            "tip payment program not ready",
+            "error decoding response from the relayer",
+        ] {
+            let err = send_bundle_error(-32603, message).await;
+
+            assert!(
+                matches!(err, JitoError::Unavailable(_)),
+                "{}: {:?}",
+                message,
+                err
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_blockhash_is_stale_rather_than_invalid() {
+        let err = send_bundle_error(-32602, "Blockhash not found").await;
+
+        assert!(matches!(err, JitoError::StaleBlockhash(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn rejected_bundles_are_invalid() {
+        for (code, message) in [
+            (-32603, "Bundle contains an already processed transaction"),
+            (
+                -32603,
+                "bundles must write lock at least one tip account to be eligible",
+            ),
+            (-32602, "invalid params"),
+        ] {
+            let err = send_bundle_error(code, message).await;
+
+            assert!(
+                matches!(err, JitoError::BundleInvalid(_)),
+                "{}: {:?}",
+                message,
+                err
+            );
+        }
+    }
+}
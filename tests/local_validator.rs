@@ -0,0 +1,383 @@
+//! End-to-end test of the Etherfuse purchase and instant redemption transactions against a
+//! `solana-test-validator` with the stablebond program and a bond's accounts loaded from
+//! mainnet. Needs `solana-test-validator` on the `PATH`, or at `SOLANA_TEST_VALIDATOR`, and
+//! the fixtures dumped by `dump_fixtures`:
+//!
+//! ```sh
+//! ARB_DUMP_KYC_WALLET=<any KYC'd wallet> cargo test --features local-validator \
+//!     --test local_validator -- --ignored dump_fixtures
+//! cargo test --features local-validator --test local_validator
+//! ```
+#![cfg(feature = "local-validator")]
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use arb::{constants::USDC_MINT, mock::TempDir, EtherfuseClient};
+use serde_json::json;
+use solana_program::program_pack::Pack;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::Account,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    commitment_config::CommitmentConfig,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_program,
+    transaction::VersionedTransaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+};
+use stablebond_sdk::{
+    accounts::{Bond, PaymentFeed, SellLiquidity},
+    find_bond_pda, find_issuance_pda, find_kyc_pda, find_payment_feed_pda, find_payment_pda,
+    find_sell_liquidity_pda,
+};
+
+const CETES_MINT: &str = "CETES7CKqqKQizuSN6iWQwmTeFRjbJR6Vw2XRKfEDR8f";
+const MAINNET_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+const VALIDATOR_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+/// USDC the test wallet starts with.
+const WALLET_USDC_AMOUNT: u64 = 1_000_000_000;
+const PURCHASE_USDC_AMOUNT: u64 = 100_000_000;
+
+/// Dumped by `dump_fixtures`:
+/// - `stablebond.so`, the stablebond program
+/// - `accounts/`, the bond's accounts, in the format of `solana account --output json`
+/// - `kyc.json`, a KYC account, copied to the test wallet's KYC address
+/// - `mint`, the stablebond mint the accounts are for
+/// - `slot`, the slot they were dumped at, so the price feeds aren't stale
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/validator")
+}
+
+fn write_account_json(path: &Path, pubkey: &Pubkey, account: &Account) -> Result<()> {
+    let json = json!({
+        "pubkey": pubkey.to_string(),
+        "account": {
+            "lamports": account.lamports,
+            "data": [base64::encode(&account.data), "base64"],
+            "owner": account.owner.to_string(),
+            "executable": account.executable,
+            "rentEpoch": 0,
+            "space": account.data.len(),
+        },
+    });
+    std::fs::write(path, serde_json::to_string_pretty(&json)?)?;
+    Ok(())
+}
+
+fn read_fixture(name: &str) -> Result<String> {
+    let path = fixtures_dir().join(name);
+    std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "No fixture at {}, dump the fixtures with the ignored dump_fixtures test",
+            path.display()
+        )
+    })
+}
+
+fn read_account_fixture(name: &str) -> Result<Account> {
+    let json: serde_json::Value = serde_json::from_str(&read_fixture(name)?)?;
+    let account = &json["account"];
+    Ok(Account {
+        lamports: account["lamports"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("No lamports in {}", name))?,
+        data: base64::decode(
+            account["data"][0]
+                .as_str()
+                .ok_or_else(|| anyhow!("No data in {}", name))?,
+        )?,
+        owner: Pubkey::from_str(account["owner"].as_str().unwrap_or_default())?,
+        executable: account["executable"].as_bool().unwrap_or_default(),
+        rent_epoch: 0,
+    })
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// `solana-test-validator` running in a temporary ledger until dropped.
+struct LocalValidator {
+    child: Child,
+    ledger: PathBuf,
+    rpc_client: Arc<RpcClient>,
+}
+
+impl LocalValidator {
+    /// Starts a validator with the fixtures and `accounts` loaded, and waits until it serves
+    /// requests.
+    async fn start(accounts: &[(Pubkey, Account)]) -> Result<Self> {
+        let fixtures = fixtures_dir();
+        let slot = read_fixture("slot")?;
+        let ledger =
+            std::env::temp_dir().join(format!("arb-test-ledger-{}", rand::random::<u64>()));
+        let extra_accounts = ledger.join("extra_accounts");
+        std::fs::create_dir_all(&extra_accounts)?;
+        for (pubkey, account) in accounts {
+            write_account_json(
+                &extra_accounts.join(format!("{}.json", pubkey)),
+                pubkey,
+                account,
+            )?;
+        }
+
+        let rpc_port = free_port();
+        let binary = std::env::var("SOLANA_TEST_VALIDATOR")
+            .unwrap_or_else(|_| "solana-test-validator".to_string());
+        let child = Command::new(binary)
+            .arg("--quiet")
+            .arg("--reset")
+            .arg("--ledger")
+            .arg(ledger.join("ledger"))
+            .args(["--rpc-port", &rpc_port.to_string()])
+            .args(["--faucet-port", &free_port().to_string()])
+            .args(["--warp-slot", slot.trim()])
+            .arg("--bpf-program")
+            .arg(stablebond_sdk::ID.to_string())
+            .arg(fixtures.join("stablebond.so"))
+            .arg("--account-dir")
+            .arg(fixtures.join("accounts"))
+            .arg("--account-dir")
+            .arg(&extra_accounts)
+            .stdout(Stdio::null())
+            .spawn()
+            .context("Unable to start solana-test-validator")?;
+        let validator = Self {
+            child,
+            ledger,
+            rpc_client: Arc::new(RpcClient::new_with_commitment(
+                format!("http://127.0.0.1:{}", rpc_port),
+                CommitmentConfig::confirmed(),
+            )),
+        };
+
+        let started_at = Instant::now();
+        while validator.rpc_client.get_health().await.is_err() {
+            if started_at.elapsed() > VALIDATOR_STARTUP_TIMEOUT {
+                return Err(anyhow!("solana-test-validator didn't start in time"));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        // Transactions are rejected until the first block after the warp.
+        while validator.rpc_client.get_latest_blockhash().await.is_err() {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        Ok(validator)
+    }
+}
+
+impl Drop for LocalValidator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.ledger);
+    }
+}
+
+/// Funded wallet with USDC and the KYC account the bond program requires of buyers.
+fn wallet_accounts(wallet: &Pubkey) -> Result<Vec<(Pubkey, Account)>> {
+    let usdc_mint = Pubkey::from_str(USDC_MINT)?;
+    let mut usdc_account = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint: usdc_mint,
+        owner: *wallet,
+        amount: WALLET_USDC_AMOUNT,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut usdc_account);
+    Ok(vec![
+        (
+            *wallet,
+            Account {
+                lamports: 10 * LAMPORTS_PER_SOL,
+                owner: system_program::id(),
+                ..Default::default()
+            },
+        ),
+        (
+            get_associated_token_address(wallet, &usdc_mint),
+            Account {
+                lamports: LAMPORTS_PER_SOL,
+                data: usdc_account,
+                owner: spl_token::id(),
+                ..Default::default()
+            },
+        ),
+        (find_kyc_pda(*wallet).0, read_account_fixture("kyc.json")?),
+    ])
+}
+
+async fn token_balance(rpc_client: &RpcClient, account: &Pubkey) -> u64 {
+    rpc_client
+        .get_token_account_balance(account)
+        .await
+        .map(|balance| balance.amount.parse().unwrap())
+        .unwrap_or(0)
+}
+
+async fn simulate_and_send(rpc_client: &RpcClient, tx: &VersionedTransaction) {
+    let simulation = rpc_client.simulate_transaction(tx).await.unwrap().value;
+    assert!(
+        simulation.err.is_none(),
+        "Simulation failed: {:?}\n{:#?}",
+        simulation.err,
+        simulation.logs
+    );
+    rpc_client.send_and_confirm_transaction(tx).await.unwrap();
+}
+
+#[tokio::test]
+async fn purchase_and_instant_redemption_move_balances() {
+    let wallet = Keypair::new();
+    let temp_dir = TempDir::create("validator-test");
+    let keypair_filepath = temp_dir.keypair_file(&wallet);
+    let stablebond_mint = Pubkey::from_str(read_fixture("mint").unwrap().trim()).unwrap();
+    let validator = LocalValidator::start(&wallet_accounts(&wallet.pubkey()).unwrap())
+        .await
+        .unwrap();
+    let rpc_client = &validator.rpc_client;
+    let etherfuse_client =
+        EtherfuseClient::new(Arc::clone(rpc_client), keypair_filepath, String::new());
+    let usdc_account =
+        get_associated_token_address(&wallet.pubkey(), &Pubkey::from_str(USDC_MINT).unwrap());
+    let stablebond_account = get_associated_token_address_with_program_id(
+        &wallet.pubkey(),
+        &stablebond_mint,
+        &spl_token_2022::id(),
+    );
+
+    let tx = etherfuse_client
+        .purchase_tx(PURCHASE_USDC_AMOUNT, stablebond_mint)
+        .await
+        .unwrap();
+    simulate_and_send(rpc_client, &tx).await;
+
+    assert_eq!(
+        token_balance(rpc_client, &usdc_account).await,
+        WALLET_USDC_AMOUNT - PURCHASE_USDC_AMOUNT
+    );
+    let stablebond_amount = token_balance(rpc_client, &stablebond_account).await;
+    assert!(stablebond_amount > 0);
+
+    let tx = etherfuse_client
+        .instant_bond_redemption_tx(stablebond_amount, stablebond_mint)
+        .await
+        .unwrap();
+    simulate_and_send(rpc_client, &tx).await;
+
+    assert_eq!(token_balance(rpc_client, &stablebond_account).await, 0);
+    let redeemed_usdc_amount = token_balance(rpc_client, &usdc_account).await
+        - (WALLET_USDC_AMOUNT - PURCHASE_USDC_AMOUNT);
+    assert!(redeemed_usdc_amount > 0);
+    // Bought and sold back at NAV, less the redemption fee.
+    assert!(redeemed_usdc_amount <= PURCHASE_USDC_AMOUNT);
+}
+
+/// Dumps the stablebond program and the accounts of `ARB_DUMP_MINT` (CETES by default) from
+/// `ARB_DUMP_RPC_URL` (mainnet by default) into the fixtures directory. The KYC account of
+/// `ARB_DUMP_KYC_WALLET` serves as a template for the test wallet's.
+#[tokio::test]
+#[ignore = "dumps the fixtures from mainnet"]
+async fn dump_fixtures() {
+    let rpc_url = std::env::var("ARB_DUMP_RPC_URL").unwrap_or(MAINNET_RPC_URL.to_string());
+    let stablebond_mint =
+        Pubkey::from_str(&std::env::var("ARB_DUMP_MINT").unwrap_or(CETES_MINT.to_string()))
+            .unwrap();
+    let kyc_wallet = Pubkey::from_str(
+        &std::env::var("ARB_DUMP_KYC_WALLET").expect("ARB_DUMP_KYC_WALLET is required"),
+    )
+    .unwrap();
+    let rpc_client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+    let fixtures = fixtures_dir();
+    let accounts_dir = fixtures.join("accounts");
+    std::fs::create_dir_all(&accounts_dir).unwrap();
+
+    let slot = rpc_client.get_slot().await.unwrap();
+    let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+    let bond_account = find_bond_pda(stablebond_mint).0;
+    let bond =
+        Bond::from_bytes(&rpc_client.get_account_data(&bond_account).await.unwrap()).unwrap();
+    let payment_feed_account = find_payment_feed_pda(bond.payment_feed_type).0;
+    let payment_feed = PaymentFeed::from_bytes(
+        &rpc_client
+            .get_account_data(&payment_feed_account)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    let issuance_account = find_issuance_pda(bond_account, bond.issuance_number).0;
+    let payment_account = find_payment_pda(issuance_account).0;
+    let sell_liquidity_account = find_sell_liquidity_pda(bond_account).0;
+    let sell_liquidity = SellLiquidity::from_bytes(
+        &rpc_client
+            .get_account_data(&sell_liquidity_account)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    let mut addresses = vec![
+        stablebond_mint,
+        usdc_mint,
+        bond_account,
+        issuance_account,
+        payment_account,
+        get_associated_token_address(&payment_account, &payment_feed.payment_mint),
+        payment_feed_account,
+        payment_feed.base_price_feed,
+        sell_liquidity_account,
+        get_associated_token_address(&sell_liquidity_account, &payment_feed.payment_mint),
+        get_associated_token_address(&sell_liquidity.fee_collector, &payment_feed.payment_mint),
+    ];
+    if payment_feed.quote_price_feed != Pubkey::default() {
+        addresses.push(payment_feed.quote_price_feed);
+    }
+    addresses.sort_unstable();
+    addresses.dedup();
+    for address in addresses {
+        let account = rpc_client.get_account(&address).await.unwrap();
+        write_account_json(
+            &accounts_dir.join(format!("{}.json", address)),
+            &address,
+            &account,
+        )
+        .unwrap();
+    }
+    let kyc_account = rpc_client
+        .get_account(&find_kyc_pda(kyc_wallet).0)
+        .await
+        .unwrap();
+    write_account_json(&fixtures.join("kyc.json"), &kyc_wallet, &kyc_account).unwrap();
+
+    let program_data_account = Pubkey::find_program_address(
+        &[stablebond_sdk::ID.as_ref()],
+        &bpf_loader_upgradeable::id(),
+    )
+    .0;
+    let program_data = rpc_client
+        .get_account_data(&program_data_account)
+        .await
+        .unwrap();
+    std::fs::write(
+        fixtures.join("stablebond.so"),
+        &program_data[UpgradeableLoaderState::size_of_programdata_metadata()..],
+    )
+    .unwrap();
+    std::fs::write(fixtures.join("mint"), stablebond_mint.to_string()).unwrap();
+    std::fs::write(fixtures.join("slot"), slot.to_string()).unwrap();
+}